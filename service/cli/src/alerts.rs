@@ -0,0 +1,79 @@
+use std::process::Command;
+
+use order_book_service_types::{filter::SummaryFilter, proto::Summary};
+
+/// Evaluates rules against each summary, triggering only when a rule starts matching
+/// so that a condition which persists doesn't repeatedly alert.
+pub(crate) struct AlertMonitor {
+    rules: Vec<(SummaryFilter, bool)>,
+    exec: Option<String>,
+}
+
+impl AlertMonitor {
+    /// `exec` is a shell command run on each alert, the rule is passed to it in `ALERT_RULE`.
+    pub(crate) fn new(rules: Vec<SummaryFilter>, exec: Option<String>) -> Self {
+        Self {
+            rules: rules.into_iter().map(|rule| (rule, false)).collect(),
+            exec,
+        }
+    }
+
+    pub(crate) fn check(&mut self, summary: &Summary) {
+        for triggered in self.newly_matched(summary) {
+            self.alert(&triggered);
+        }
+    }
+
+    fn newly_matched(&mut self, summary: &Summary) -> Vec<SummaryFilter> {
+        self.rules
+            .iter_mut()
+            .filter_map(|(rule, matching)| {
+                let was_matching = *matching;
+                *matching = rule.matches(summary);
+                (*matching && !was_matching).then(|| rule.clone())
+            })
+            .collect()
+    }
+
+    fn alert(&self, rule: &SummaryFilter) {
+        // Ring the terminal bell along with the message
+        println!("\x07Alert: {rule}");
+
+        if let Some(exec) = &self.exec {
+            let result = Command::new("sh")
+                .arg("-c")
+                .arg(exec)
+                .env("ALERT_RULE", rule.to_string())
+                .spawn();
+            if let Err(err) = result {
+                eprintln!("Unable to run alert command: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::{Level, Summary};
+
+    use super::AlertMonitor;
+
+    fn summary(spread: f64, best_bid: f64) -> Summary {
+        Summary {
+            spread,
+            bids: vec![Level::new("Binance", best_bid, 1.0)],
+            asks: vec![Level::new("Bitstamp", best_bid + spread, 1.0)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_only_trigger_when_a_rule_starts_matching() {
+        let mut monitor = AlertMonitor::new(vec!["spread < 0".parse().unwrap()], None);
+
+        assert_eq!(monitor.newly_matched(&summary(-1.0, 1.0)).len(), 1);
+        assert!(monitor.newly_matched(&summary(-1.0, 1.0)).is_empty());
+        assert!(monitor.newly_matched(&summary(1.0, 1.0)).is_empty());
+        assert_eq!(monitor.newly_matched(&summary(-1.0, 1.0)).len(), 1);
+    }
+}
@@ -1,49 +1,446 @@
-use std::time::Duration;
+mod alerts;
+mod recording;
+mod table;
 
-use clap::Parser;
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Parser, Subcommand};
 use tokio_stream::StreamExt;
 use url::Url;
 
-use order_book_service_client::{connect_to_summary_service, ConnectionSettings};
-use order_book_service_types::proto::TradedPair;
+use order_book_service_client::{
+    connect_to_summary_service, list_supported_pairs, ConnectionSettings, PairMatrix,
+};
+use order_book_service_types::{
+    filter::SummaryFilter,
+    proto::{
+        orderbook_admin_client::OrderbookAdminClient,
+        orderbook_aggregator_client::OrderbookAggregatorClient, DrainRequest, Empty,
+        SetLogLevelRequest, TradedPair,
+    },
+};
+
+use crate::{alerts::AlertMonitor, recording::RecordingFormat, table::NumberFormat};
 
-/// Subscribe to the order book service for a traded pair
+/// Subscribe to and operate the order book service
 #[derive(Parser)]
 struct Cli {
-    /// Server address to bind
-    address: String,
-    /// The first symbol of the desired pair
-    first: String,
-    /// The second symbol of the desired pair
-    second: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Subscribe to summaries for a traded pair
+    Subscribe {
+        /// Server address to bind
+        address: String,
+        /// The first symbol of the desired pair
+        first: String,
+        /// The second symbol of the desired pair
+        second: String,
+        /// Print each summary as a table of levels rather than JSON
+        #[arg(long)]
+        table: bool,
+        /// Locale the table's numbers are formatted for, e.g. `de_DE`, defaults to the environment's locale
+        #[arg(long, requires = "table")]
+        locale: Option<NumberFormat>,
+        /// Digits after the decimal point in the table, as many as each number needs by default
+        #[arg(long, requires = "table")]
+        precision: Option<usize>,
+    },
+    /// Watch summaries for a traded pair, alerting when a rule starts matching
+    Alerts {
+        /// Server address to bind
+        address: String,
+        /// The first symbol of the desired pair
+        first: String,
+        /// The second symbol of the desired pair
+        second: String,
+        /// A rule such as `spread < 0` or `best_bid > 0.07 and best_bid_amount >= 10`, can be repeated
+        #[arg(long = "alert", required = true)]
+        alerts: Vec<SummaryFilter>,
+        /// Shell command run on each alert, e.g. `notify-send "$ALERT_RULE"`
+        #[arg(long)]
+        exec: Option<String>,
+    },
+    /// Save the summary stream for a traded pair to a file, e.g. to capture a market move for a bug report
+    Record {
+        /// Server address to bind
+        address: String,
+        /// The first symbol of the desired pair
+        first: String,
+        /// The second symbol of the desired pair
+        second: String,
+        /// File to write the summaries to
+        #[arg(long)]
+        out: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: RecordingFormat,
+        /// Compress the recording with zstd, replay and convert detect it
+        #[arg(long)]
+        compress: bool,
+    },
+    /// Print a recorded summary stream with its original timing
+    Replay {
+        /// A file written by `record`, in any format
+        #[arg(long = "in")]
+        input: PathBuf,
+        /// How much faster than recorded to replay, e.g. `2x`
+        #[arg(long, default_value = "1x", value_parser = recording::parse_speed)]
+        speed: f64,
+    },
+    /// Rewrite a recording in another format, e.g. a binary capture as JSON
+    Convert {
+        /// A file written by `record`, in any format
+        #[arg(long = "in")]
+        input: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: RecordingFormat,
+        /// Compress the rewritten recording with zstd
+        #[arg(long)]
+        compress: bool,
+    },
+    /// List the pairs each exchange offers and which can be subscribed to
+    Pairs {
+        /// Server address to bind
+        address: String,
+        /// Print a matrix of which exchanges list each pair instead
+        #[arg(long)]
+        matrix: bool,
+        /// Print the matrix as CSV
+        #[arg(long, requires = "matrix")]
+        csv: bool,
+    },
+    /// Print a Markdown reference of the server's API
+    Describe {
+        /// Server address to bind
+        address: String,
+        /// Also save the API's file descriptor set, e.g. for `grpcurl -protoset`
+        #[arg(long)]
+        descriptor_set: Option<PathBuf>,
+    },
+    /// Watch connection events from the server's exchange connectors
+    Status {
+        /// Server address to bind
+        address: String,
+    },
+    /// Change which logs the server writes without restarting it
+    LogLevel {
+        /// Address of the server's admin service, e.g. `http://127.0.0.1:3031`
+        address: String,
+        /// e.g. `info,order_book_service_server::exchanges::binance=debug`, the configured filter is restored when omitted
+        filter: Option<String>,
+        /// The server's admin `api_key`, or the key of an admin tenant
+        #[arg(long)]
+        api_key: String,
+    },
+    /// Save a snapshot of the server's key metrics to its metrics snapshot file now, e.g. before attaching it to a bug report
+    DumpMetrics {
+        /// Address of the server's admin service, e.g. `http://127.0.0.1:3031`
+        address: String,
+        /// The server's admin `api_key`, or the key of an admin tenant
+        #[arg(long)]
+        api_key: String,
+    },
+    /// Drain the server ahead of a deploy, pointing its clients at the configured alternative server, then shut it down
+    Drain {
+        /// Address of the server's admin service, e.g. `http://127.0.0.1:3031`
+        address: String,
+        /// Seconds to wait for subscriptions to close before shutting down, the server's configured deadline by default
+        #[arg(long, default_value_t = 0)]
+        deadline_secs: u32,
+        /// The server's admin `api_key`, or the key of an admin tenant
+        #[arg(long)]
+        api_key: String,
+    },
 }
 
 #[tokio::main]
 async fn main() {
     println!("Orderbook Service CLI");
 
-    let Cli {
-        address,
-        first,
-        second,
-    } = Cli::parse();
+    match Cli::parse().command {
+        Command::Subscribe {
+            address,
+            first,
+            second,
+            table,
+            locale,
+            precision,
+        } => {
+            let table_format = table.then(|| {
+                locale
+                    .unwrap_or_else(NumberFormat::from_env)
+                    .with_precision(precision)
+            });
+            subscribe(address, TradedPair { first, second }, table_format).await
+        }
+        Command::Alerts {
+            address,
+            first,
+            second,
+            alerts,
+            exec,
+        } => watch_alerts(address, TradedPair { first, second }, alerts, exec).await,
+        Command::Record {
+            address,
+            first,
+            second,
+            out,
+            format,
+            compress,
+        } => {
+            let summary_stream = connect_to_summary_service(connection_settings(
+                address,
+                TradedPair { first, second },
+            ))
+            .await;
+            if let Err(err) = recording::record(summary_stream, &out, format, compress).await {
+                eprintln!("Error recording to {}: {err}", out.display());
+            }
+        }
+        Command::Replay { input, speed } => {
+            if let Err(err) = recording::replay(&input, speed).await {
+                eprintln!("Error replaying {}: {err}", input.display());
+            }
+        }
+        Command::Convert {
+            input,
+            out,
+            format,
+            compress,
+        } => match recording::convert(&input, &out, format, compress) {
+            Ok(converted) => println!("Converted {converted} summaries to {}", out.display()),
+            Err(err) => eprintln!("Error converting {}: {err}", input.display()),
+        },
+        Command::Pairs {
+            address,
+            matrix,
+            csv,
+        } => list_pairs(address, matrix, csv).await,
+        Command::Describe {
+            address,
+            descriptor_set,
+        } => describe(address, descriptor_set).await,
+        Command::Status { address } => watch_status(address).await,
+        Command::LogLevel {
+            address,
+            filter,
+            api_key,
+        } => set_log_level(address, filter.unwrap_or_default(), api_key).await,
+        Command::DumpMetrics { address, api_key } => dump_metrics(address, api_key).await,
+        Command::Drain {
+            address,
+            deadline_secs,
+            api_key,
+        } => drain(address, deadline_secs, api_key).await,
+    }
+}
 
-    let traded_pair = TradedPair { first, second };
+fn connection_settings(address: String, traded_pair: TradedPair) -> ConnectionSettings {
     let server_address = Url::parse(&address).expect("Provided URL was not valid");
 
-    let connection_settings = ConnectionSettings {
-        server_address,
-        traded_pair,
-        max_attempts: 10,
-        delay_between_attempts: Duration::from_millis(500),
-    };
+    ConnectionSettings::new(server_address, traded_pair, 10, Duration::from_millis(500))
+}
+
+async fn subscribe(address: String, traded_pair: TradedPair, table_format: Option<NumberFormat>) {
+    let mut summary_stream =
+        connect_to_summary_service(connection_settings(address, traded_pair)).await;
 
-    let mut summary_stream = connect_to_summary_service(connection_settings).await;
+    while let Some(summary_res) = summary_stream.next().await {
+        match (summary_res, &table_format) {
+            (Ok(summary), Some(format)) if !summary.is_heartbeat() => {
+                println!("{}", table::render(&summary, format))
+            }
+            (Ok(summary), _) => println!("{summary}"),
+            (Err(status), _) => eprintln!("Error: {status:#?}"),
+        }
+    }
+}
+
+async fn watch_alerts(
+    address: String,
+    traded_pair: TradedPair,
+    alerts: Vec<SummaryFilter>,
+    exec: Option<String>,
+) {
+    let mut monitor = AlertMonitor::new(alerts, exec);
+    let mut summary_stream =
+        connect_to_summary_service(connection_settings(address, traded_pair)).await;
 
     while let Some(summary_res) = summary_stream.next().await {
         match summary_res {
-            Ok(summary) => println!("{summary}"),
+            Ok(summary) if summary.is_heartbeat() => {}
+            Ok(summary) => monitor.check(&summary),
             Err(status) => eprintln!("Error: {status:#?}"),
         }
     }
 }
+
+async fn list_pairs(address: String, matrix: bool, csv: bool) {
+    let server_address = Url::parse(&address).expect("Provided URL was not valid");
+
+    let supported_pairs = match list_supported_pairs(&server_address).await {
+        Ok(supported_pairs) => supported_pairs,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            return;
+        }
+    };
+
+    if matrix {
+        let matrix = PairMatrix::new(&supported_pairs);
+        if csv {
+            print!("{}", matrix.to_csv());
+        } else {
+            print!("{matrix}");
+        }
+        return;
+    }
+
+    for exchange in supported_pairs.exchanges {
+        if exchange.error.is_empty() {
+            println!("{}: {} pairs", exchange.exchange, exchange.pairs.len());
+        } else {
+            println!("{}: unavailable ({})", exchange.exchange, exchange.error);
+        }
+    }
+
+    println!("Pairs which can be subscribed to:");
+    for pair in supported_pairs.pairs {
+        println!("\t{pair}");
+    }
+}
+
+async fn describe(address: String, descriptor_set: Option<PathBuf>) {
+    let mut client = OrderbookAggregatorClient::connect(address)
+        .await
+        .expect("Unable to connect to server");
+
+    let descriptor = match client.get_api_descriptor(Empty {}).await {
+        Ok(response) => response.into_inner(),
+        Err(status) => {
+            eprintln!("Error: {}", status.message());
+            return;
+        }
+    };
+
+    if let Some(path) = descriptor_set {
+        if let Err(err) = std::fs::write(&path, &descriptor.file_descriptor_set) {
+            eprintln!(
+                "Unable to save the descriptor set to {}: {err}",
+                path.display()
+            );
+            return;
+        }
+    }
+    print!("{}", descriptor.to_markdown());
+}
+
+async fn watch_status(address: String) {
+    let mut client = OrderbookAggregatorClient::connect(address)
+        .await
+        .expect("Unable to connect to server");
+
+    let mut status_stream = client
+        .watch_exchange_status(Empty {})
+        .await
+        .expect("Unable to watch exchange status")
+        .into_inner();
+
+    loop {
+        match status_stream.message().await {
+            Ok(Some(status)) => println!("{status}"),
+            Ok(None) => break,
+            Err(status) => {
+                eprintln!("Error: {status:#?}");
+                break;
+            }
+        }
+    }
+}
+
+async fn set_log_level(address: String, filter: String, api_key: String) {
+    let mut client = OrderbookAdminClient::connect(address)
+        .await
+        .expect("Unable to connect to server");
+
+    let Some(request) = admin_request(SetLogLevelRequest { filter }, api_key) else {
+        return;
+    };
+
+    match client.set_log_level(request).await {
+        Ok(response) => {
+            let log_filter = response.into_inner();
+            println!(
+                "Log filter changed from {} to {}",
+                log_filter.previous_filter, log_filter.filter
+            );
+        }
+        Err(status) => eprintln!("Error: {}", status.message()),
+    }
+}
+
+async fn dump_metrics(address: String, api_key: String) {
+    let mut client = OrderbookAdminClient::connect(address)
+        .await
+        .expect("Unable to connect to server");
+
+    let Some(request) = admin_request(Empty {}, api_key) else {
+        return;
+    };
+
+    match client.dump_metrics(request).await {
+        Ok(response) => {
+            let dump = response.into_inner();
+            println!(
+                "Saved a snapshot to {}, which now holds {}",
+                dump.path, dump.snapshots
+            );
+        }
+        Err(status) => eprintln!("Error: {}", status.message()),
+    }
+}
+
+async fn drain(address: String, deadline_secs: u32, api_key: String) {
+    let mut client = OrderbookAdminClient::connect(address)
+        .await
+        .expect("Unable to connect to server");
+
+    let Some(request) = admin_request(DrainRequest { deadline_secs }, api_key) else {
+        return;
+    };
+
+    match client.drain(request).await {
+        Ok(response) => {
+            let result = response.into_inner();
+            let alternative = match result.alternative_address.as_str() {
+                "" => "no alternative server".to_string(),
+                address => address.to_string(),
+            };
+            println!(
+                "Drained {} subscriptions to {alternative}, {} were still open at shutdown",
+                result.notified, result.remaining
+            );
+        }
+        Err(status) => eprintln!("Error: {}", status.message()),
+    }
+}
+
+/// An admin request authenticated with `api_key`, `None` after reporting a key which isn't valid metadata.
+fn admin_request<T>(message: T, api_key: String) -> Option<tonic::Request<T>> {
+    let mut request = tonic::Request::new(message);
+    match api_key.parse() {
+        Ok(api_key) => {
+            request.metadata_mut().insert("x-api-key", api_key);
+        }
+        Err(_) => {
+            eprintln!("Error: the API key isn't valid metadata");
+            return None;
+        }
+    }
+    Some(request)
+}
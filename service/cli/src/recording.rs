@@ -0,0 +1,691 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::ValueEnum;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tokio_stream::{Stream, StreamExt};
+use tonic::Status;
+
+use order_book_service_types::{
+    integrity::SummaryChain,
+    proto::{Level, Summary},
+};
+
+/// Starts a binary recording, JSON recordings start with `{`.
+const BINARY_MAGIC: &[u8; 8] = b"OBSREC\x00\x01";
+/// Starts a delta recording, framed as a binary recording but each frame holding a [SummaryDelta].
+const DELTA_MAGIC: &[u8; 8] = b"OBSREC\x00\x03";
+/// Starts a recording compressed with zstd, which is a recording in any other format once decompressed.
+const ZSTD_MAGIC: &[u8; 8] = b"OBSREC\x00\x04";
+/// zstd's default, higher levels cost far more time for little gain on summaries.
+const ZSTD_LEVEL: i32 = 3;
+/// Every this many frames of a delta recording is a keyframe, holding the whole summary.
+const KEYFRAME_INTERVAL: usize = 100;
+/// Frames are refused beyond gRPC's default message limit, no summary the service sent could be larger, so a corrupt
+/// length can't allocate without bound.
+const MAX_FRAME_BYTES: usize = 4 * 1024 * 1024;
+
+/// A summary along with when it was received, one is written per line of a recording.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct RecordedSummary {
+    received_millis: u64,
+    summary: Summary,
+}
+
+/// How summaries are written to a recording.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub(crate) enum RecordingFormat {
+    /// One JSON summary per line, readable with standard tools
+    #[default]
+    Json,
+    /// Length prefixed protobuf frames, a fraction of the size for long captures of busy pairs
+    Binary,
+    /// Binary frames holding only the levels changed since the previous summary, far smaller for long captures of
+    /// quiet pairs
+    Delta,
+}
+
+/// A summary as the changes from the one before it in a recording, or from an empty summary for a keyframe.
+///
+/// The first frame is a keyframe, and one follows every [KEYFRAME_INTERVAL] frames so a recording can be rebuilt from
+/// any keyframe on, e.g. past a damaged frame.
+#[derive(Clone, PartialEq, Message)]
+struct SummaryDelta {
+    /// Every field of the summary but its levels
+    #[prost(message, optional, tag = "1")]
+    summary: Option<Summary>,
+    #[prost(message, optional, tag = "2")]
+    bids: Option<LevelsDelta>,
+    #[prost(message, optional, tag = "3")]
+    asks: Option<LevelsDelta>,
+    #[prost(bool, tag = "4")]
+    keyframe: bool,
+}
+
+/// One side's levels as the previous summary's levels which are kept, and those which are new or changed.
+///
+/// Levels are matched by their exchange and price rather than their position, so a level added near the top of the
+/// book doesn't change every level below it.
+#[derive(Clone, PartialEq, Message)]
+struct LevelsDelta {
+    #[prost(uint32, tag = "1")]
+    length: u32,
+    /// In order of position, the positions between them are filled by the kept levels in order. A changed level
+    /// replaces the next kept level when it's at the same price on the same exchange.
+    #[prost(message, repeated, tag = "2")]
+    changed: Vec<ChangedLevel>,
+    /// The positions in the previous summary of the levels which aren't kept, in order
+    #[prost(uint32, repeated, tag = "3")]
+    removed: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ChangedLevel {
+    #[prost(uint32, tag = "1")]
+    position: u32,
+    #[prost(message, optional, tag = "2")]
+    level: Option<Level>,
+}
+
+impl LevelsDelta {
+    fn between(previous: &[Level], current: &[Level]) -> Self {
+        let mut delta = Self {
+            length: current.len() as u32,
+            ..Default::default()
+        };
+        // The previous levels from here on may still be kept, sides are ordered by price so kept levels stay in order
+        let mut next = 0;
+        for (position, level) in current.iter().enumerate() {
+            let matched = previous[next..]
+                .iter()
+                .position(|kept| same_price(kept, level));
+            if let Some(skipped) = matched {
+                delta
+                    .removed
+                    .extend((next..next + skipped).map(|removed| removed as u32));
+                next += skipped + 1;
+            }
+            // Anything but a level kept as it was
+            if matched.is_none() || previous[next - 1] != *level {
+                delta.changed.push(ChangedLevel {
+                    position: position as u32,
+                    level: Some(level.clone()),
+                });
+            }
+        }
+        delta
+            .removed
+            .extend((next..previous.len()).map(|removed| removed as u32));
+        delta
+    }
+
+    fn apply(self, previous: &[Level]) -> io::Result<Vec<Level>> {
+        let mut removed = self.removed.into_iter().peekable();
+        let mut kept = Vec::with_capacity(previous.len());
+        for (position, level) in previous.iter().enumerate() {
+            if removed.next_if_eq(&(position as u32)).is_none() {
+                kept.push(level);
+            }
+        }
+        if removed.next().is_some() {
+            return Err(invalid_delta("removed levels out of order or past the end"));
+        }
+
+        let mut kept = kept.into_iter().peekable();
+        let mut changed = self.changed.into_iter().peekable();
+        let levels = (0..self.length)
+            .map(
+                |position| match changed.next_if(|changed| changed.position == position) {
+                    Some(changed) => {
+                        let level = changed.level.unwrap_or_default();
+                        kept.next_if(|kept| same_price(kept, &level));
+                        Ok(level)
+                    }
+                    None => kept
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| invalid_delta("fewer levels than its length")),
+                },
+            )
+            .collect::<io::Result<Vec<_>>>()?;
+        if changed.next().is_some() || kept.next().is_some() {
+            return Err(invalid_delta("more levels than its length"));
+        }
+        Ok(levels)
+    }
+}
+
+impl SummaryDelta {
+    fn between(previous: &Summary, current: &Summary) -> Self {
+        Self {
+            summary: Some(Summary {
+                bids: Vec::new(),
+                asks: Vec::new(),
+                ..current.clone()
+            }),
+            bids: Some(LevelsDelta::between(&previous.bids, &current.bids)),
+            asks: Some(LevelsDelta::between(&previous.asks, &current.asks)),
+            keyframe: false,
+        }
+    }
+
+    fn keyframe(current: &Summary) -> Self {
+        Self {
+            keyframe: true,
+            ..Self::between(&Summary::default(), current)
+        }
+    }
+
+    fn apply(self, previous: &Summary) -> io::Result<Summary> {
+        Ok(Summary {
+            bids: self.bids.unwrap_or_default().apply(&previous.bids)?,
+            asks: self.asks.unwrap_or_default().apply(&previous.asks)?,
+            ..self.summary.unwrap_or_default()
+        })
+    }
+}
+
+/// Whether the levels are at the same price on the same exchange, whatever their amounts.
+fn same_price(a: &Level, b: &Level) -> bool {
+    a.exchange == b.exchange && a.price == b.price
+}
+
+fn invalid_delta(problem: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Summary delta has {problem}"),
+    )
+}
+
+/// Writes recorded summaries in any format.
+struct RecordingWriter<W: Write> {
+    writer: W,
+    format: RecordingFormat,
+    /// The summary written last, which a delta is taken from
+    previous: Summary,
+    written: usize,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    fn new(mut writer: W, format: RecordingFormat) -> io::Result<Self> {
+        match format {
+            RecordingFormat::Json => {}
+            RecordingFormat::Binary => writer.write_all(BINARY_MAGIC)?,
+            RecordingFormat::Delta => writer.write_all(DELTA_MAGIC)?,
+        }
+        Ok(Self {
+            writer,
+            format,
+            previous: Summary::default(),
+            written: 0,
+        })
+    }
+
+    fn write(&mut self, recorded: &RecordedSummary) -> io::Result<()> {
+        match self.format {
+            RecordingFormat::Json => {
+                serde_json::to_writer(&mut self.writer, recorded)?;
+                writeln!(self.writer)?;
+            }
+            RecordingFormat::Binary => {
+                self.write_frame(recorded.received_millis, &recorded.summary.encode_to_vec())?
+            }
+            RecordingFormat::Delta => {
+                let delta = if self.written.is_multiple_of(KEYFRAME_INTERVAL) {
+                    SummaryDelta::keyframe(&recorded.summary)
+                } else {
+                    SummaryDelta::between(&self.previous, &recorded.summary)
+                };
+                self.write_frame(recorded.received_millis, &delta.encode_to_vec())?;
+                self.previous = recorded.summary.clone();
+            }
+        }
+        self.written += 1;
+        // Keep the file usable if the recording is interrupted
+        self.writer.flush()
+    }
+
+    /// Each frame is the receipt time, then the encoded message's length and bytes, all little endian.
+    fn write_frame(&mut self, received_millis: u64, encoded: &[u8]) -> io::Result<()> {
+        self.writer.write_all(&received_millis.to_le_bytes())?;
+        self.writer
+            .write_all(&(encoded.len() as u32).to_le_bytes())?;
+        self.writer.write_all(encoded)
+    }
+}
+
+/// Start a compressed recording on `writer`, which the recording in any format is then written to.
+///
+/// Each flush ends a zstd block, so a recording interrupted before the encoder is finished can still be read up to
+/// the last summary written.
+fn compressed<W: Write>(mut writer: W) -> io::Result<zstd::Encoder<'static, W>> {
+    writer.write_all(ZSTD_MAGIC)?;
+    zstd::Encoder::new(writer, ZSTD_LEVEL)
+}
+
+/// Create a recording at `out`, compressed when `compress` is set.
+fn create_recording(
+    out: &Path,
+    format: RecordingFormat,
+    compress: bool,
+) -> io::Result<RecordingWriter<Box<dyn Write>>> {
+    let file = BufWriter::new(File::create(out)?);
+    let writer: Box<dyn Write> = if compress {
+        Box::new(compressed(file)?.auto_finish())
+    } else {
+        Box::new(file)
+    };
+    RecordingWriter::new(writer, format)
+}
+
+/// A compressed recording which ends where it was interrupted, at the last block the encoder flushed, rather than
+/// failing on its unfinished frame. Recording is usually stopped by interrupting it.
+struct Interrupted<R>(R);
+
+impl<R: Read> Read for Interrupted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            Err(read_err) if read_err.kind() == ErrorKind::UnexpectedEof => Ok(0),
+            read => read,
+        }
+    }
+}
+
+/// Read the summaries of a recording in any format, compressed or not, which is detected from its start.
+fn read_recording<R: BufRead + 'static>(
+    mut reader: R,
+) -> io::Result<Box<dyn Iterator<Item = io::Result<RecordedSummary>>>> {
+    let start = reader.fill_buf()?;
+    if start.starts_with(ZSTD_MAGIC) {
+        reader.consume(ZSTD_MAGIC.len());
+        let decompressed: Box<dyn BufRead> = Box::new(BufReader::new(Interrupted(
+            zstd::Decoder::with_buffer(reader)?,
+        )));
+        return read_recording(decompressed);
+    }
+    if start.starts_with(DELTA_MAGIC) {
+        reader.consume(DELTA_MAGIC.len());
+        // Each summary is rebuilt from the one before, after one can't be the rest are skipped up to the next keyframe
+        let mut previous = Summary::default();
+        let mut resyncing = false;
+        return Ok(Box::new(std::iter::from_fn(move || loop {
+            let (received_millis, delta) = match read_frame::<SummaryDelta>(&mut reader) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return None,
+                Err(read_err) => return Some(Err(read_err)),
+            };
+            if delta.keyframe {
+                (previous, resyncing) = (Summary::default(), false);
+            } else if resyncing {
+                continue;
+            }
+
+            return Some(match delta.apply(&previous) {
+                Ok(summary) => {
+                    previous = summary.clone();
+                    Ok(RecordedSummary {
+                        received_millis,
+                        summary,
+                    })
+                }
+                Err(delta_err) => {
+                    resyncing = true;
+                    Err(delta_err)
+                }
+            });
+        })));
+    }
+    if !start.starts_with(BINARY_MAGIC) {
+        return Ok(Box::new(
+            reader
+                .lines()
+                .map(|line| Ok(serde_json::from_str::<RecordedSummary>(&line?)?)),
+        ));
+    }
+
+    reader.consume(BINARY_MAGIC.len());
+    Ok(Box::new(std::iter::from_fn(move || {
+        read_frame::<Summary>(&mut reader)
+            .map(|frame| {
+                frame.map(|(received_millis, summary)| RecordedSummary {
+                    received_millis,
+                    summary,
+                })
+            })
+            .transpose()
+    })))
+}
+
+/// The receipt time and message of the next frame of a binary recording, `None` at the end of the recording.
+fn read_frame<M: Message + Default>(reader: &mut impl BufRead) -> io::Result<Option<(u64, M)>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut received_millis = [0; 8];
+    reader.read_exact(&mut received_millis)?;
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Frame of {len} bytes is larger than the {MAX_FRAME_BYTES} allowed"),
+        ));
+    }
+    let mut encoded = vec![0; len];
+    reader.read_exact(&mut encoded)?;
+
+    Ok(Some((
+        u64::from_le_bytes(received_millis),
+        M::decode(encoded.as_slice()).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?,
+    )))
+}
+
+/// Write each summary from the stream to `out` until the stream ends.
+/// Heartbeats are left out as they carry no market data, and summaries sealed by the server have their chain verified.
+pub(crate) async fn record(
+    mut summary_stream: impl Stream<Item = Result<Summary, Status>> + Unpin,
+    out: &Path,
+    format: RecordingFormat,
+    compress: bool,
+) -> io::Result<()> {
+    let mut writer = create_recording(out, format, compress)?;
+    // Summaries from a server configured for integrity are checked as they arrive
+    let mut chain = SummaryChain::default();
+
+    while let Some(summary_res) = summary_stream.next().await {
+        match summary_res {
+            Ok(summary) if summary.is_heartbeat() => {}
+            Ok(summary) => {
+                if summary.integrity.is_some() {
+                    if let Err(chain_err) = chain.verify(&summary) {
+                        eprintln!("Integrity error: {chain_err}");
+                    }
+                }
+                let received_millis = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                writer.write(&RecordedSummary {
+                    received_millis,
+                    summary,
+                })?;
+            }
+            Err(status) => eprintln!("Error: {status:#?}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Print each summary from a recording, waiting between them as long as they were originally apart divided by `speed`.
+pub(crate) async fn replay(recording: &Path, speed: f64) -> io::Result<()> {
+    let mut previous_millis = None;
+
+    for recorded in read_recording(BufReader::new(File::open(recording)?))? {
+        let recorded = recorded?;
+
+        if let Some(previous_millis) = previous_millis {
+            sleep(replay_delay(
+                previous_millis,
+                recorded.received_millis,
+                speed,
+            ))
+            .await;
+        }
+        previous_millis = Some(recorded.received_millis);
+
+        println!("{}", recorded.summary);
+    }
+
+    Ok(())
+}
+
+/// Rewrite a recording in any format as `format`, e.g. a binary capture as JSON for inspection.
+pub(crate) fn convert(
+    input: &Path,
+    out: &Path,
+    format: RecordingFormat,
+    compress: bool,
+) -> io::Result<usize> {
+    let recording = read_recording(BufReader::new(File::open(input)?))?;
+    let mut writer = create_recording(out, format, compress)?;
+
+    let mut converted = 0;
+    for recorded in recording {
+        writer.write(&recorded?)?;
+        converted += 1;
+    }
+    Ok(converted)
+}
+
+fn replay_delay(previous_millis: u64, received_millis: u64, speed: f64) -> Duration {
+    Duration::from_millis(received_millis.saturating_sub(previous_millis)).div_f64(speed)
+}
+
+/// Parse a replay speed such as `2x` or `0.5`.
+pub(crate) fn parse_speed(speed: &str) -> Result<f64, String> {
+    let speed = speed
+        .strip_suffix('x')
+        .unwrap_or(speed)
+        .parse::<f64>()
+        .map_err(|err| err.to_string())?;
+
+    if speed.is_finite() && speed > 0.0 {
+        Ok(speed)
+    } else {
+        Err("Speed must be greater than 0".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, time::Duration};
+
+    use order_book_service_types::proto::{Level, Summary};
+
+    use super::{
+        compressed, parse_speed, read_recording, replay_delay, LevelsDelta, RecordedSummary,
+        RecordingFormat, RecordingWriter, BINARY_MAGIC, DELTA_MAGIC, KEYFRAME_INTERVAL, ZSTD_MAGIC,
+    };
+
+    #[test]
+    fn should_read_recordings_in_either_format() {
+        let recorded = || {
+            (0..3).map(|index| RecordedSummary {
+                received_millis: 1_000 + index,
+                summary: Summary {
+                    spread: index as f64,
+                    bids: vec![Level::new("Binance", 0.07, 1.5)],
+                    ..Default::default()
+                },
+            })
+        };
+
+        let mut sizes = Vec::new();
+        for format in [
+            RecordingFormat::Json,
+            RecordingFormat::Binary,
+            RecordingFormat::Delta,
+        ] {
+            let mut writer = RecordingWriter::new(Vec::new(), format).unwrap();
+            for summary in recorded() {
+                writer.write(&summary).unwrap();
+            }
+            let bytes = writer.writer;
+            sizes.push(bytes.len());
+
+            let read = read_recording(Cursor::new(bytes))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(read, recorded().collect::<Vec<_>>());
+        }
+        assert!(sizes[1] * 2 < sizes[0]);
+
+        // A corrupt length is refused rather than allocated
+        let mut corrupt = BINARY_MAGIC.to_vec();
+        corrupt.extend(1_000_u64.to_le_bytes());
+        corrupt.extend(u32::MAX.to_le_bytes());
+        let mut read = read_recording(Cursor::new(corrupt)).unwrap();
+        assert!(read.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn should_store_only_changed_levels_in_delta_recordings() {
+        // A quiet pair, where each summary moves a single level of a deep book
+        let recorded = (0..100)
+            .map(|index| {
+                let mut bids = (0..20)
+                    .map(|level| Level::new("Binance", 0.07 - level as f64 * 0.0001, 1.5))
+                    .collect::<Vec<_>>();
+                bids[index % 20].amount = 2.0 + index as f64;
+                let asks = (0..index % 3)
+                    .map(|level| Level::new("Bitstamp", 0.071 + level as f64 * 0.0001, 0.5))
+                    .collect();
+                RecordedSummary {
+                    received_millis: 1_000 + index as u64,
+                    summary: Summary {
+                        spread: 0.001,
+                        bids,
+                        asks,
+                        ..Default::default()
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut sizes = Vec::new();
+        for format in [RecordingFormat::Binary, RecordingFormat::Delta] {
+            let mut writer = RecordingWriter::new(Vec::new(), format).unwrap();
+            for summary in &recorded {
+                writer.write(summary).unwrap();
+            }
+            sizes.push(writer.writer.len());
+
+            // Levels added, changed and removed are all rebuilt
+            let read = read_recording(Cursor::new(writer.writer))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(read, recorded);
+        }
+        assert!(sizes[1] * 5 < sizes[0], "{sizes:?}");
+
+        // A level added at the top leaves those below it unchanged
+        let previous = &recorded[0].summary.bids;
+        let mut current = previous.clone();
+        current.insert(0, Level::new("Bitstamp", 0.0701, 1.0));
+        let delta = LevelsDelta::between(previous, &current);
+        assert_eq!((delta.changed.len(), delta.removed.len()), (1, 0));
+        assert_eq!(delta.apply(previous).unwrap(), current);
+    }
+
+    #[test]
+    fn should_rebuild_delta_recordings_from_the_next_keyframe() {
+        let recorded = (0..KEYFRAME_INTERVAL + 10)
+            .map(|index| RecordedSummary {
+                received_millis: 1_000 + index as u64,
+                summary: Summary {
+                    bids: (0..5)
+                        .map(|level| Level::new("Binance", 200.0 - (index + level) as f64, 1.0))
+                        .collect(),
+                    ..Default::default()
+                },
+            })
+            .collect::<Vec<_>>();
+        let mut writer = RecordingWriter::new(Vec::new(), RecordingFormat::Delta).unwrap();
+        for summary in &recorded {
+            writer.write(summary).unwrap();
+        }
+
+        // Lose the first frames, as if the start of the recording were damaged
+        let bytes = writer.writer;
+        let mut start = DELTA_MAGIC.len();
+        for _ in 0..10 {
+            let len = u32::from_le_bytes(bytes[start + 8..start + 12].try_into().unwrap());
+            start += 12 + len as usize;
+        }
+        let mut damaged = DELTA_MAGIC.to_vec();
+        damaged.extend(&bytes[start..]);
+
+        let mut read = read_recording(Cursor::new(damaged)).unwrap();
+        assert!(read.next().unwrap().is_err());
+        let read = read.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(read, recorded[KEYFRAME_INTERVAL..]);
+    }
+
+    #[test]
+    fn should_read_compressed_recordings_in_any_format() {
+        let recorded = (0..500)
+            .map(|index| RecordedSummary {
+                received_millis: 1_000 + index,
+                summary: Summary {
+                    spread: 0.001,
+                    bids: (0..20)
+                        .map(|level| Level::new("Binance", 0.07 - level as f64 * 0.0001, 1.5))
+                        .collect(),
+                    ..Default::default()
+                },
+            })
+            .collect::<Vec<_>>();
+
+        for format in [
+            RecordingFormat::Json,
+            RecordingFormat::Binary,
+            RecordingFormat::Delta,
+        ] {
+            let mut uncompressed = RecordingWriter::new(Vec::new(), format).unwrap();
+            let mut writer = RecordingWriter::new(compressed(Vec::new()).unwrap(), format).unwrap();
+            for summary in &recorded {
+                uncompressed.write(summary).unwrap();
+                writer.write(summary).unwrap();
+            }
+            let bytes = writer.writer.finish().unwrap();
+            assert!(bytes.starts_with(ZSTD_MAGIC));
+            assert!(
+                bytes.len() * 2 < uncompressed.writer.len(),
+                "{format:?} {} {}",
+                bytes.len(),
+                uncompressed.writer.len()
+            );
+
+            let read = read_recording(Cursor::new(bytes))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(read, recorded, "{format:?}");
+        }
+
+        // Interrupted before the encoder was finished, the summaries flushed so far are still read
+        let mut writer =
+            RecordingWriter::new(compressed(Vec::new()).unwrap(), RecordingFormat::Binary).unwrap();
+        for summary in &recorded[..10] {
+            writer.write(summary).unwrap();
+        }
+        let interrupted = writer.writer.get_ref().clone();
+        let read = read_recording(Cursor::new(interrupted))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, recorded[..10]);
+    }
+
+    #[test]
+    fn should_scale_replay_delay_by_speed() {
+        assert_eq!(parse_speed("2x"), Ok(2.0));
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+        assert!(parse_speed("0x").is_err());
+
+        assert_eq!(replay_delay(1_000, 1_500, 2.0), Duration::from_millis(250));
+        // Out of order timestamps shouldn't wait at all
+        assert_eq!(replay_delay(1_500, 1_000, 1.0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,208 @@
+use std::{env, fmt::Write, str::FromStr};
+
+use order_book_service_types::proto::{Level, Summary};
+
+/// How numbers in the table are written, e.g. `1,234,567.5` in English or `1.234.567,5` in German.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct NumberFormat {
+    grouping: Option<char>,
+    decimal: char,
+    /// Digits after the decimal point, as many as are needed when `None`
+    precision: Option<usize>,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            grouping: Some(','),
+            decimal: '.',
+            precision: None,
+        }
+    }
+}
+
+impl NumberFormat {
+    /// The format for the locale in `LC_ALL`, `LC_NUMERIC` or `LANG`, English when none are set.
+    pub(crate) fn from_env() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| locale.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn with_precision(self, precision: Option<usize>) -> Self {
+        Self { precision, ..self }
+    }
+
+    pub(crate) fn format(&self, value: f64) -> String {
+        let plain = match self.precision {
+            Some(precision) => format!("{:.*}", precision, value.abs()),
+            None => value.abs().to_string(),
+        };
+        let (integer, fraction) = plain.split_once('.').unwrap_or((&plain, ""));
+
+        let mut formatted = String::new();
+        if value.is_sign_negative() && value != 0.0 {
+            formatted.push('-');
+        }
+        for (index, digit) in integer.chars().enumerate() {
+            let remaining = integer.len() - index;
+            if index > 0 && remaining % 3 == 0 {
+                formatted.extend(self.grouping);
+            }
+            formatted.push(digit);
+        }
+        if !fraction.is_empty() {
+            formatted.push(self.decimal);
+            formatted.push_str(fraction);
+        }
+        formatted
+    }
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    /// A locale such as `de_DE.UTF-8` or `fr-FR`, only the language and region are considered.
+    fn from_str(locale: &str) -> Result<Self, Self::Err> {
+        let tag = locale
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .replace('-', "_");
+        let (language, region) = tag.split_once('_').unwrap_or((&tag, ""));
+
+        let (grouping, decimal) = match (language, region) {
+            ("C" | "POSIX", _) => (None, '.'),
+            ("de" | "it" | "es", "CH") => (Some('\''), '.'),
+            ("de" | "nl" | "it" | "es" | "pt" | "da" | "id" | "tr", _) => (Some('.'), ','),
+            // A narrow no-break space, as French and Nordic locales group thousands
+            ("fr" | "sv" | "nb" | "fi" | "pl" | "cs" | "ru" | "uk", _) => (Some('\u{202f}'), ','),
+            ("en" | "ja" | "zh" | "ko" | "he" | "th", _) => (Some(','), '.'),
+            _ => return Err(format!("Unsupported locale {locale}")),
+        };
+
+        Ok(Self {
+            grouping,
+            decimal,
+            precision: None,
+        })
+    }
+}
+
+/// The summary as a table of levels, bids beside asks, best first.
+pub(crate) fn render(summary: &Summary, format: &NumberFormat) -> String {
+    let exchange = |level: Option<&Level>| level.map(|level| level.exchange.clone());
+    let number = |value: Option<f64>| value.map(|value| format.format(value));
+    let rows = summary.bids.len().max(summary.asks.len());
+
+    let mut table = vec![[
+        "Exchange".to_string(),
+        "Amount".to_string(),
+        "Bid".to_string(),
+        "Ask".to_string(),
+        "Amount".to_string(),
+        "Exchange".to_string(),
+    ]];
+    for row in 0..rows {
+        let (bid, ask) = (summary.bids.get(row), summary.asks.get(row));
+        table.push(
+            [
+                exchange(bid),
+                number(bid.map(|level| level.amount)),
+                number(bid.map(|level| level.price)),
+                number(ask.map(|level| level.price)),
+                number(ask.map(|level| level.amount)),
+                exchange(ask),
+            ]
+            .map(Option::unwrap_or_default),
+        );
+    }
+
+    let widths = (0..6)
+        .map(|column| {
+            table
+                .iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    let mut rendered = format!("Spread: {}\n", format.format(summary.spread));
+    for row in table {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .enumerate()
+            .map(|(column, (value, width))| {
+                // Numbers are right aligned so their digits line up
+                let padding = " ".repeat(width - value.chars().count());
+                if column == 0 || column == 5 {
+                    format!("{value}{padding}")
+                } else {
+                    format!("{padding}{value}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let _ = writeln!(rendered, "{}", line.trim_end());
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::{Level, Summary};
+
+    use super::{render, NumberFormat};
+
+    #[test]
+    fn should_format_numbers_for_the_locale() {
+        let english = NumberFormat::default();
+        assert_eq!(english.format(1234567.5), "1,234,567.5");
+        assert_eq!(english.format(-999.0), "-999");
+        assert_eq!(
+            english.clone().with_precision(Some(2)).format(0.000012),
+            "0.00"
+        );
+
+        let german: NumberFormat = "de_DE.UTF-8".parse().unwrap();
+        assert_eq!(german.format(1234567.25), "1.234.567,25");
+
+        let swiss: NumberFormat = "de-CH".parse().unwrap();
+        assert_eq!(swiss.format(1234.5), "1'234.5");
+
+        let posix: NumberFormat = "C".parse().unwrap();
+        assert_eq!(posix.format(1234.5), "1234.5");
+
+        assert!("xx_XX".parse::<NumberFormat>().is_err());
+    }
+
+    #[test]
+    fn should_render_levels_side_by_side() {
+        let summary = Summary {
+            spread: 1.0,
+            bids: vec![
+                Level::new("Binance", 25000.0, 1500000.0),
+                Level::new("Bitstamp", 24999.0, 2.0),
+            ],
+            asks: vec![Level::new("Bitstamp", 25001.0, 3.0)],
+            ..Default::default()
+        };
+
+        let rendered = render(&summary, &NumberFormat::default());
+        let lines = rendered.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "Spread: 1");
+        assert_eq!(
+            lines[2],
+            "Binance  | 1,500,000 | 25,000 | 25,001 |      3 | Bitstamp"
+        );
+        assert_eq!(
+            lines[3],
+            "Bitstamp |         2 | 24,999 |        |        |"
+        );
+    }
+}
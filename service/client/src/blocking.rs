@@ -0,0 +1,68 @@
+use std::io;
+
+use tokio::runtime::Runtime;
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+use tonic::Status;
+
+use order_book_service_types::proto::Summary;
+
+use crate::{connect_to_summary_service, ConnectionSettings};
+
+/// A summary subscription for synchronous code, streamed in the background on a runtime of its own.
+///
+/// Iterating blocks until the next summary, or error status, is received. Dropping it stops the subscription.
+pub struct BlockingSubscription {
+    runtime: Runtime,
+    summaries: ReceiverStream<Result<Summary, Status>>,
+}
+
+impl BlockingSubscription {
+    /// Subscribe as [connect_to_summary_service] does, failing only if the runtime can't be started.
+    pub fn connect(settings: ConnectionSettings) -> Result<Self, io::Error> {
+        let runtime = Runtime::new()?;
+        let summaries = runtime.block_on(connect_to_summary_service(settings));
+
+        Ok(Self { runtime, summaries })
+    }
+}
+
+impl Iterator for BlockingSubscription {
+    type Item = Result<Summary, Status>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.summaries.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use url::Url;
+
+    use order_book_service_types::proto::TradedPair;
+
+    use crate::ConnectionSettings;
+
+    use super::BlockingSubscription;
+
+    #[test]
+    fn should_end_once_attempts_are_exhausted() {
+        let mut settings = ConnectionSettings::new(
+            // Nothing listens on port 1
+            Url::parse("http://127.0.0.1:1").unwrap(),
+            TradedPair::new("ETH", "BTC"),
+            1,
+            Duration::ZERO,
+        );
+        settings.connect_timeout = Duration::from_millis(100);
+        let subscription = BlockingSubscription::connect(settings).unwrap();
+
+        let received = subscription.collect::<Vec<_>>();
+        assert_eq!(received.len(), 1);
+        assert_eq!(
+            received[0].as_ref().unwrap_err().message(),
+            "The service is unavailable"
+        );
+    }
+}
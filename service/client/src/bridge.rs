@@ -0,0 +1,106 @@
+use std::pin::Pin;
+
+use prost::Message;
+use tokio_stream::{Stream, StreamExt};
+use tonic::Status;
+
+use order_book_service_types::proto::{Summary, TradedPair};
+
+use crate::multi_pair::MultiPairClient;
+
+/// Encodes summaries in another schema, so they can be republished on an existing market-data bus.
+///
+/// Adapter crates implement this for their firm's own messages, the service itself needn't be forked.
+pub trait SummaryEncoder: Send + 'static {
+    /// A message of the firm's schema, its bytes are given by [Message::encode_to_vec].
+    type Message: Message + 'static;
+
+    /// Encode a summary, `None` to skip it e.g. heartbeats on a bus which has its own keep-alive.
+    fn encode(&self, summary: &Summary) -> Option<Self::Message>;
+}
+
+/// Summaries for a pair encoded by a [SummaryEncoder], in the order they were received.
+pub type EncodedStream<M> = Pin<Box<dyn Stream<Item = Result<M, Status>> + Send>>;
+
+/// Subscribe to `traded_pair` over the client's shared connection, encoding each summary with `encoder`.
+pub async fn bridge<E: SummaryEncoder>(
+    client: &MultiPairClient,
+    traded_pair: TradedPair,
+    encoder: E,
+) -> Result<EncodedStream<E::Message>, Status> {
+    let summary_stream = client.subscribe(traded_pair).await?;
+    Ok(encode_stream(summary_stream, encoder))
+}
+
+fn encode_stream<E: SummaryEncoder>(
+    summary_stream: impl Stream<Item = Result<Summary, Status>> + Send + 'static,
+    encoder: E,
+) -> EncodedStream<E::Message> {
+    Box::pin(summary_stream.filter_map(move |summary| match summary {
+        Ok(summary) => encoder.encode(&summary).map(Ok),
+        Err(status) => Some(Err(status)),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use prost::Message;
+    use tokio_stream::{iter, StreamExt};
+    use tonic::Status;
+
+    use order_book_service_types::proto::{Level, Summary};
+
+    use super::{encode_stream, SummaryEncoder};
+
+    /// A firm's own top of book message.
+    #[derive(Clone, PartialEq, Message)]
+    struct TopOfBook {
+        #[prost(double, tag = "1")]
+        bid: f64,
+        #[prost(double, tag = "2")]
+        ask: f64,
+    }
+
+    struct TopOfBookEncoder;
+
+    impl SummaryEncoder for TopOfBookEncoder {
+        type Message = TopOfBook;
+
+        fn encode(&self, summary: &Summary) -> Option<TopOfBook> {
+            Some(TopOfBook {
+                bid: summary.bids.first()?.price,
+                ask: summary.asks.first()?.price,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn should_encode_summaries_and_pass_on_errors() {
+        let summary = Summary {
+            bids: vec![Level::new("Binance", 1.0, 2.0)],
+            asks: vec![Level::new("Bitstamp", 1.5, 3.0)],
+            ..Default::default()
+        };
+        let summaries = iter([
+            Ok(summary),
+            Ok(Summary::heartbeat(Duration::ZERO)),
+            Err(Status::unavailable("Gone")),
+        ]);
+
+        let encoded = encode_stream(summaries, TopOfBookEncoder)
+            .collect::<Vec<_>>()
+            .await;
+
+        // The heartbeat has no levels so is skipped
+        assert_eq!(encoded.len(), 2);
+        let top = encoded[0].as_ref().unwrap();
+        assert_eq!(*top, TopOfBook { bid: 1.0, ask: 1.5 });
+        assert_eq!(
+            TopOfBook::decode(top.encode_to_vec().as_slice()).unwrap(),
+            *top
+        );
+        assert!(encoded[1].is_err());
+    }
+}
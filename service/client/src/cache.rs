@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use tokio_stream::{Stream, StreamExt};
+
+use order_book_service_types::proto::{Summary, TradedPair};
+
+/// The last summary received for each pair, for applications which hold a summary stream open but also need to look up
+/// the current book on demand.
+///
+/// Clones share the same cache, so one can feed it from the stream while others read from it.
+#[derive(Clone, Debug, Default)]
+pub struct SummaryCache {
+    latest: Arc<Mutex<HashMap<TradedPair, (Summary, Instant)>>>,
+}
+
+impl SummaryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retain `summary` as the latest for `traded_pair`, heartbeats are ignored as they carry no levels.
+    pub fn record(&self, traded_pair: &TradedPair, summary: &Summary) {
+        if summary.is_heartbeat() {
+            return;
+        }
+        self.latest
+            .lock()
+            .expect("Should lock")
+            .insert(traded_pair.canonical(), (summary.clone(), Instant::now()));
+    }
+
+    /// The latest summary for `traded_pair`, in any case, and how long ago it was received, `None` until one has been.
+    pub fn latest(&self, traded_pair: &TradedPair) -> Option<(Summary, Duration)> {
+        self.latest
+            .lock()
+            .expect("Should lock")
+            .get(&traded_pair.canonical())
+            .map(|(summary, received)| (summary.clone(), received.elapsed()))
+    }
+
+    /// Pass the summaries of `stream` through unchanged, recording each as the latest for `traded_pair`.
+    pub fn cache_stream<S, E>(
+        &self,
+        traded_pair: TradedPair,
+        stream: S,
+    ) -> impl Stream<Item = Result<Summary, E>>
+    where
+        S: Stream<Item = Result<Summary, E>>,
+    {
+        let cache = self.clone();
+        stream.map(move |summary_res| {
+            if let Ok(summary) = &summary_res {
+                cache.record(&traded_pair, summary);
+            }
+            summary_res
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::advance;
+    use tokio_stream::{iter, StreamExt};
+    use tonic::Status;
+
+    use order_book_service_types::proto::{Summary, TradedPair};
+
+    use super::SummaryCache;
+
+    #[tokio::test(start_paused = true)]
+    async fn should_return_the_latest_summary_with_its_age() {
+        let cache = SummaryCache::new();
+        let pair = TradedPair::new("ETH", "BTC");
+        assert!(cache.latest(&pair).is_none());
+
+        let summaries = vec![
+            Ok(Summary {
+                spread: 1.0,
+                ..Default::default()
+            }),
+            Err(Status::unavailable("Reconnecting")),
+            Ok(Summary {
+                spread: 2.0,
+                ..Default::default()
+            }),
+            Ok(Summary::heartbeat(Duration::from_secs(5))),
+        ];
+        let passed = cache
+            .cache_stream(pair.clone(), iter(summaries))
+            .collect::<Vec<_>>()
+            .await;
+        // Everything is passed through, heartbeats and errors included
+        assert_eq!(passed.len(), 4);
+
+        advance(Duration::from_millis(1500)).await;
+        let (summary, age) = cache.latest(&pair).expect("Should be cached");
+        assert_eq!(summary.spread, 2.0);
+        assert_eq!(age, Duration::from_millis(1500));
+        assert!(cache.latest(&TradedPair::new("BTC", "USD")).is_none());
+    }
+}
@@ -0,0 +1,71 @@
+use tonic::{transport::Channel, Code, Status};
+
+use order_book_service_types::proto::{
+    orderbook_aggregator_client::OrderbookAggregatorClient, Capability, Empty, ServerInfo,
+};
+
+/// What a server supports, as reported by its GetServerInfo RPC.
+///
+/// Servers which predate GetServerInfo report nothing, so clients fall back to the features every server has.
+#[derive(Clone, Debug, Default)]
+pub struct ServerCapabilities {
+    info: Option<ServerInfo>,
+}
+
+impl ServerCapabilities {
+    /// Ask the server what it supports.
+    pub async fn negotiate(
+        client: &mut OrderbookAggregatorClient<Channel>,
+    ) -> Result<Self, Status> {
+        match client.get_server_info(Empty {}).await {
+            Ok(response) => Ok(Self {
+                info: Some(response.into_inner()),
+            }),
+            Err(status) if status.code() == Code::Unimplemented => Ok(Self::default()),
+            Err(status) => Err(status),
+        }
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.info
+            .as_ref()
+            .is_some_and(|info| info.capabilities().any(|supported| supported == capability))
+    }
+
+    /// The server's version, `None` for servers which predate GetServerInfo.
+    pub fn server_version(&self) -> Option<&str> {
+        self.info.as_ref().map(|info| info.version.as_str())
+    }
+
+    /// The exchanges the server aggregates from, empty when unknown.
+    pub fn exchanges(&self) -> &[String] {
+        self.info
+            .as_ref()
+            .map_or(&[], |info| info.exchanges.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::{Capability, ServerInfo};
+
+    use super::ServerCapabilities;
+
+    #[test]
+    fn should_support_nothing_without_server_info() {
+        let legacy = ServerCapabilities::default();
+        assert!(!legacy.supports(Capability::Depth));
+        assert_eq!(legacy.server_version(), None);
+
+        let current = ServerCapabilities {
+            info: Some(ServerInfo {
+                version: "0.1.0".to_string(),
+                capabilities: vec![Capability::Depth as i32],
+                exchanges: vec!["Binance".to_string()],
+            }),
+        };
+        assert!(current.supports(Capability::Depth));
+        assert!(!current.supports(Capability::Deltas));
+        assert_eq!(current.exchanges(), ["Binance".to_string()]);
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::sync::watch;
+use tokio_stream::{wrappers::WatchStream, Stream, StreamExt};
+use tonic::Status;
+
+use order_book_service_types::proto::Summary;
+
+/// A stream yielding only the latest summary each time it's polled, those received since it was last polled are
+/// dropped. Error statuses are conflated alike, the stream's final item is always yielded.
+///
+/// Keeps a consumer which polls slowly, e.g. a GUI redrawing at its frame rate, from queueing ever more stale summaries.
+pub struct Conflated {
+    // `None` until the first summary is received
+    latest: WatchStream<Option<Result<Summary, Status>>>,
+}
+
+impl Conflated {
+    /// Conflate `summaries`, which are received in the background until this is dropped or they end.
+    pub fn new(
+        mut summaries: impl Stream<Item = Result<Summary, Status>> + Send + Unpin + 'static,
+    ) -> Self {
+        let (latest_tx, latest_rx) = watch::channel(None);
+        tokio::spawn(async move {
+            while let Some(summary) = summaries.next().await {
+                // Only fails once the receiver is dropped
+                if latest_tx.send(Some(summary)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            latest: WatchStream::new(latest_rx),
+        }
+    }
+}
+
+impl Stream for Conflated {
+    type Item = Result<Summary, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match Pin::new(&mut self.latest).poll_next(cx) {
+                Poll::Ready(Some(None)) => continue,
+                Poll::Ready(Some(Some(summary))) => return Poll::Ready(Some(summary)),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{sync::mpsc, time::sleep};
+    use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+    use tonic::Status;
+
+    use order_book_service_types::proto::Summary;
+
+    use super::Conflated;
+
+    fn summary(spread: f64) -> Summary {
+        Summary {
+            spread,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_only_yield_the_latest_summary() {
+        let (summary_tx, summary_rx) = mpsc::channel(10);
+        let mut conflated = Conflated::new(ReceiverStream::new(summary_rx));
+
+        summary_tx.send(Ok(summary(1.0))).await.unwrap();
+        assert_eq!(conflated.next().await.unwrap().unwrap().spread, 1.0);
+
+        // Received while the consumer wasn't polling
+        for spread in [2.0, 3.0, 4.0] {
+            summary_tx.send(Ok(summary(spread))).await.unwrap();
+        }
+        summary_tx
+            .send(Err(Status::unavailable("The service is unavailable")))
+            .await
+            .unwrap();
+        drop(summary_tx);
+        // Paused time only advances once the summaries have all been forwarded
+        sleep(Duration::from_millis(1)).await;
+
+        let remaining = conflated.collect::<Vec<_>>().await;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(
+            remaining[0].as_ref().unwrap_err().message(),
+            "The service is unavailable"
+        );
+    }
+}
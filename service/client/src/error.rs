@@ -0,0 +1,103 @@
+use std::fmt::{Display, Formatter};
+
+use tonic::Status;
+use url::Url;
+
+use order_book_service_types::proto::CloseReason;
+
+/// Why the service ended a stream, decoded from the `StreamClosed` details of the status it ended with.
+#[derive(Clone, Debug)]
+pub enum StreamError {
+    ServerShutdown(Status),
+    PairRetired(Status),
+    QuotaExceeded(Status),
+    UpstreamUnavailable(Status),
+    /// The service gave no reason, e.g. it predates close reasons or the connection failed
+    Other(Status),
+}
+
+impl StreamError {
+    /// Whether reconnecting may succeed, a retired pair or an exhausted quota won't change by retrying.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            StreamError::PairRetired(_) | StreamError::QuotaExceeded(_)
+        )
+    }
+
+    /// The server to reconnect to instead, given when the service ended the stream because it's draining.
+    pub fn alternative_address(&self) -> Option<Url> {
+        CloseReason::alternative_address(self.status())
+            .and_then(|address| Url::parse(&address).ok())
+    }
+
+    pub fn status(&self) -> &Status {
+        match self {
+            StreamError::ServerShutdown(status)
+            | StreamError::PairRetired(status)
+            | StreamError::QuotaExceeded(status)
+            | StreamError::UpstreamUnavailable(status)
+            | StreamError::Other(status) => status,
+        }
+    }
+}
+
+impl From<Status> for StreamError {
+    fn from(status: Status) -> Self {
+        match CloseReason::of(&status) {
+            CloseReason::ServerShutdown => StreamError::ServerShutdown(status),
+            CloseReason::PairRetired => StreamError::PairRetired(status),
+            CloseReason::QuotaExceeded => StreamError::QuotaExceeded(status),
+            CloseReason::UpstreamUnavailable => StreamError::UpstreamUnavailable(status),
+            CloseReason::Unspecified => StreamError::Other(status),
+        }
+    }
+}
+
+impl Display for StreamError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.status().message())
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+#[cfg(test)]
+mod tests {
+    use tonic::{Code, Status};
+
+    use order_book_service_types::proto::CloseReason;
+
+    use super::StreamError;
+
+    #[test]
+    fn should_convert_close_reasons_into_errors() {
+        let quota = StreamError::from(
+            CloseReason::QuotaExceeded.status(Code::ResourceExhausted, "Tenant at its limit"),
+        );
+        assert!(matches!(quota, StreamError::QuotaExceeded(_)));
+        assert!(!quota.is_retryable());
+        assert_eq!(quota.to_string(), "Tenant at its limit");
+
+        let upstream = StreamError::from(
+            CloseReason::UpstreamUnavailable.status(Code::Unavailable, "Exchange disconnected"),
+        );
+        assert!(upstream.is_retryable());
+
+        let other = StreamError::from(Status::unavailable("Connection reset"));
+        assert!(matches!(other, StreamError::Other(_)));
+        assert!(other.is_retryable());
+        assert_eq!(other.alternative_address(), None);
+
+        let draining = StreamError::from(CloseReason::server_shutdown(
+            "Draining",
+            Some("http://standby:3030"),
+        ));
+        assert!(matches!(draining, StreamError::ServerShutdown(_)));
+        assert!(draining.is_retryable());
+        assert_eq!(
+            draining.alternative_address().unwrap().as_str(),
+            "http://standby:3030/"
+        );
+    }
+}
@@ -0,0 +1,523 @@
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    future::pending,
+    mem::take,
+    ptr::{null, null_mut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver as StoppedReceiver, SyncSender as StoppedSender},
+        Arc, Mutex, Once,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use libc::{atexit, c_char, c_double, c_int, size_t};
+use once_cell::sync::Lazy;
+use tokio::{
+    runtime::Runtime,
+    select,
+    sync::oneshot::{channel as oneshot_channel, Receiver as StopReceiver, Sender as StopSender},
+};
+use tokio_stream::{Stream, StreamExt};
+use url::Url;
+
+use order_book_service_types::proto::{Level, Summary, TradedPair};
+
+use crate::{cache::SummaryCache, ConnectionSettings};
+
+/// [CSummary::status] of a summary.
+pub const OBS_STATUS_OK: c_int = 0;
+/// [CSummary::status] of the final callback once the stream has ended, its reconnect attempts exhausted or refused.
+pub const OBS_STATUS_ENDED: c_int = 1;
+/// [CSummary::status] of the final callback when the process exits while the stream is active.
+pub const OBS_STATUS_SHUTDOWN: c_int = 2;
+
+/// How long exiting waits for the active streams to stop and their runtimes to shut down, so a stuck callback can't
+/// hang the host.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Subscriptions which are still streaming, stopped by [shutdown_at_exit] if the process exits first.
+static SUBSCRIPTIONS: Lazy<Mutex<HashMap<u64, ActiveSubscription>>> = Lazy::new(Default::default);
+static NEXT_SUBSCRIPTION: AtomicU64 = AtomicU64::new(0);
+static REGISTER_AT_EXIT: Once = Once::new();
+
+#[repr(C)]
+pub struct CLevel {
+    exchange: *const c_char,
+    price: c_double,
+    amount: c_double,
+}
+
+#[repr(C)]
+pub struct CSummary {
+    spread: c_double,
+    bids: *const CLevel,
+    bids_length: size_t,
+    asks: *const CLevel,
+    asks_length: size_t,
+    /// [OBS_STATUS_OK] for a summary, otherwise the stream's final callback, without levels, saying why it ended
+    status: c_int,
+}
+
+impl CSummary {
+    fn terminal(status: c_int) -> Self {
+        Self {
+            spread: 0.0,
+            bids: null(),
+            bids_length: 0,
+            asks: null(),
+            asks_length: 0,
+            status,
+        }
+    }
+}
+
+/// Called with each summary received, the [CSummary] is only valid until the callback returns.
+pub type SummaryCallback = extern "C" fn(*const CSummary);
+
+/// A summary converted for C, owning the levels and exchange names its [CSummary] points to.
+struct OwnedCSummary {
+    spread: c_double,
+    bids: Vec<CLevel>,
+    asks: Vec<CLevel>,
+    _exchanges: Vec<CString>,
+}
+
+impl OwnedCSummary {
+    fn new(summary: &Summary) -> Self {
+        let mut exchanges = Vec::new();
+        let mut to_clevels = |levels: &[Level]| {
+            levels
+                .iter()
+                .map(|level| {
+                    let exchange = CString::new(level.exchange.as_str()).unwrap_or_default();
+                    // The name's buffer stays put when the CString is moved into `exchanges`
+                    let clevel = CLevel {
+                        exchange: exchange.as_ptr(),
+                        price: level.price,
+                        amount: level.amount,
+                    };
+                    exchanges.push(exchange);
+                    clevel
+                })
+                .collect::<Vec<_>>()
+        };
+        let bids = to_clevels(&summary.bids);
+        let asks = to_clevels(&summary.asks);
+
+        Self {
+            spread: summary.spread,
+            bids,
+            asks,
+            _exchanges: exchanges,
+        }
+    }
+
+    fn as_csummary(&self) -> CSummary {
+        CSummary {
+            spread: self.spread,
+            bids: self.bids.as_ptr(),
+            bids_length: self.bids.len(),
+            asks: self.asks.as_ptr(),
+            asks_length: self.asks.len(),
+            status: OBS_STATUS_OK,
+        }
+    }
+}
+
+/// A stream which is stopped when the process exits.
+struct ActiveSubscription {
+    stop: StopSender<()>,
+    /// Disconnects once the stream's final callback has returned
+    stopped: StoppedReceiver<()>,
+    /// Shut down after the stream has stopped, `None` when the runtime belongs to the caller's thread
+    runtime: Option<Arc<Mutex<Option<Runtime>>>>,
+}
+
+/// Track a stream until it's disconnected, returning its id along with the signal to stop it and the sender to drop
+/// once it has.
+fn register_subscription(
+    runtime: Option<Arc<Mutex<Option<Runtime>>>>,
+) -> (u64, StopReceiver<()>, StoppedSender<()>) {
+    REGISTER_AT_EXIT.call_once(|| unsafe {
+        atexit(shutdown_at_exit);
+    });
+
+    let id = NEXT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+    let (stop, stop_rx) = oneshot_channel();
+    let (stopped_tx, stopped) = sync_channel(0);
+    SUBSCRIPTIONS.lock().expect("Should lock").insert(
+        id,
+        ActiveSubscription {
+            stop,
+            stopped,
+            runtime,
+        },
+    );
+    (id, stop_rx, stopped_tx)
+}
+
+extern "C" fn shutdown_at_exit() {
+    // The exiting thread's thread locals may already be destroyed, which the runtimes rely on
+    let _ = thread::spawn(|| {
+        let subscriptions = take(&mut *SUBSCRIPTIONS.lock().expect("Should lock"));
+        shutdown_subscriptions(subscriptions.into_values());
+    })
+    .join();
+}
+
+/// Stop the streams, waiting for their final callbacks, then shut down their runtimes, all within [EXIT_TIMEOUT].
+fn shutdown_subscriptions(subscriptions: impl IntoIterator<Item = ActiveSubscription>) {
+    let deadline = Instant::now() + EXIT_TIMEOUT;
+
+    let mut stopping = Vec::new();
+    for subscription in subscriptions {
+        let _ = subscription.stop.send(());
+        stopping.push((subscription.stopped, subscription.runtime));
+    }
+    for (stopped, runtime) in stopping {
+        let _ = stopped.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        let runtime = runtime.and_then(|runtime| runtime.lock().expect("Should lock").take());
+        if let Some(runtime) = runtime {
+            runtime.shutdown_timeout(deadline.saturating_duration_since(Instant::now()));
+        }
+    }
+}
+
+/// Pass each summary to `on_summary` until the stream ends or is stopped, then make the final callback, if any, with
+/// the status saying which.
+async fn stream_summaries<E>(
+    summaries: impl Stream<Item = Result<Summary, E>>,
+    stop: StopReceiver<()>,
+    mut on_summary: impl FnMut(&Summary),
+    final_callback: impl FnOnce() -> Option<SummaryCallback>,
+) {
+    // Only a sent stop ends the stream, not its sender being dropped
+    let stopped = async {
+        if stop.await.is_err() {
+            pending::<()>().await;
+        }
+    };
+    tokio::pin!(summaries, stopped);
+    let status = loop {
+        let next = select! {
+            () = &mut stopped => break OBS_STATUS_SHUTDOWN,
+            next = summaries.next() => next,
+        };
+        match next {
+            // Heartbeats carry no levels
+            Some(Ok(summary)) if !summary.is_heartbeat() => on_summary(&summary),
+            // Errors are followed by reconnects, the stream only ends once they're exhausted
+            Some(_) => continue,
+            None => break OBS_STATUS_ENDED,
+        }
+    };
+
+    if let Some(callback) = final_callback() {
+        callback(&CSummary::terminal(status) as *const CSummary);
+    }
+}
+
+/// A subscription started by [obs_connect], streaming in the background on its own runtime.
+pub struct ObsHandle {
+    id: u64,
+    /// Taken when the subscription is disconnected, or shut down as the process exits
+    runtime: Arc<Mutex<Option<Runtime>>>,
+    traded_pair: TradedPair,
+    cache: SummaryCache,
+    callback: Arc<Mutex<Option<SummaryCallback>>>,
+    /// The summary last returned by [obs_get_latest_summary], which the caller's [CSummary] points into
+    latest: Mutex<Option<OwnedCSummary>>,
+}
+
+/// Subscribe to the summaries of a pair in the background, returning a handle for [obs_get_latest_summary] and
+/// [obs_set_summary_callback], or null if the arguments are invalid, e.g. `max_attempts` is below 1. Release it with
+/// [obs_disconnect].
+///
+/// If the process exits first, the stream is stopped with a final callback of [OBS_STATUS_SHUTDOWN] and its runtime is
+/// shut down.
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn obs_connect(
+    server_address: *const c_char,
+    token_one_symbol: *const c_char,
+    token_two_symbol: *const c_char,
+    max_attempts: c_int,
+    delay_between_attempts_millis: c_int,
+) -> *mut ObsHandle {
+    let (Ok(server_address), Ok(token_one), Ok(token_two)) = (
+        convert_to_string(server_address),
+        convert_to_string(token_one_symbol),
+        convert_to_string(token_two_symbol),
+    ) else {
+        return null_mut();
+    };
+    // Without an attempt the subscription would never connect
+    if max_attempts < 1 {
+        return null_mut();
+    }
+    let (Ok(server_address), Ok(runtime)) = (Url::parse(server_address), Runtime::new()) else {
+        return null_mut();
+    };
+    let traded_pair = TradedPair::new(token_one, token_two);
+
+    let connection_settings = ConnectionSettings::new(
+        server_address,
+        traded_pair.clone(),
+        max_attempts as usize,
+        Duration::from_millis(delay_between_attempts_millis as u64),
+    );
+    let cache = SummaryCache::new();
+    let callback = Arc::new(Mutex::new(None::<SummaryCallback>));
+    let runtime = Arc::new(Mutex::new(Some(runtime)));
+    let (id, stop, stopped) = register_subscription(Some(runtime.clone()));
+
+    if let Some(runtime) = runtime.lock().expect("Should lock").as_ref() {
+        let cache = cache.clone();
+        let callback = callback.clone();
+        let traded_pair = traded_pair.clone();
+        runtime.spawn(async move {
+            let summaries = crate::connect_to_summary_service(connection_settings).await;
+            let summaries = cache.cache_stream(traded_pair, summaries);
+            // Copied out so the callback can replace itself without deadlocking
+            let current_callback = || *callback.lock().expect("Should lock");
+
+            stream_summaries(
+                summaries,
+                stop,
+                |summary| {
+                    if let Some(callback) = current_callback() {
+                        let c_summary = OwnedCSummary::new(summary);
+                        callback(&c_summary.as_csummary() as *const CSummary);
+                    }
+                },
+                current_callback,
+            )
+            .await;
+            drop(stopped);
+        });
+    }
+
+    Box::into_raw(Box::new(ObsHandle {
+        id,
+        runtime,
+        traded_pair,
+        cache,
+        callback,
+        latest: Mutex::new(None),
+    }))
+}
+
+/// Set the callback invoked with each summary from the handle's runtime thread, or stop invoking one with null.
+///
+/// Returns 1 if the handle is null.
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn obs_set_summary_callback(
+    handle: *mut ObsHandle,
+    callback: Option<SummaryCallback>,
+) -> c_int {
+    let Some(handle) = handle.as_ref() else {
+        return 1;
+    };
+    *handle.callback.lock().expect("Should lock") = callback;
+    0
+}
+
+/// Fill `summary` with the latest summary received, for consumers polling e.g. once a frame.
+///
+/// The levels it points to are valid until the next call with the same handle or [obs_disconnect].
+/// Returns 1 if either pointer is null and 2 if no summary has been received yet.
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn obs_get_latest_summary(
+    handle: *mut ObsHandle,
+    summary: *mut CSummary,
+) -> c_int {
+    let (Some(handle), false) = (handle.as_ref(), summary.is_null()) else {
+        return 1;
+    };
+    let Some((latest, _)) = handle.cache.latest(&handle.traded_pair) else {
+        return 2;
+    };
+
+    let mut retained = handle.latest.lock().expect("Should lock");
+    *summary = retained.insert(OwnedCSummary::new(&latest)).as_csummary();
+    0
+}
+
+/// Stop the subscription and release the handle, which must not be called from within the summary callback.
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn obs_disconnect(handle: *mut ObsHandle) {
+    if !handle.is_null() {
+        let handle = Box::from_raw(handle);
+        SUBSCRIPTIONS
+            .lock()
+            .expect("Should lock")
+            .remove(&handle.id);
+        let runtime = handle.runtime.lock().expect("Should lock").take();
+        if let Some(runtime) = runtime {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Stream the summaries of a pair to `callback` on the calling thread, returning once the stream ends.
+///
+/// If the process exits first, the stream is stopped with a final callback of [OBS_STATUS_SHUTDOWN].
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn connect_to_summary_service(
+    server_address: *const c_char,
+    token_one_symbol: *const c_char,
+    token_two_symbol: *const c_char,
+    max_attempts: c_int,
+    delay_between_attempts_millis: c_int,
+    callback: SummaryCallback,
+) -> c_int {
+    if let Ok(runtime) = Runtime::new() {
+        let server_address_str =
+            convert_to_string(server_address).expect("Should convert to string");
+        let url = Url::parse(server_address_str).expect("Should parse url");
+        let traded_pair = TradedPair::new(
+            convert_to_string(token_one_symbol).expect("Should convert to string"),
+            convert_to_string(token_two_symbol).expect("Should convert to string"),
+        );
+        let max_attempts = max_attempts as usize;
+        let delay_between_attempts = Duration::from_millis(delay_between_attempts_millis as u64);
+
+        let connection_settings =
+            ConnectionSettings::new(url, traded_pair, max_attempts, delay_between_attempts);
+
+        // The runtime is dropped by this thread once the stream has stopped
+        let (id, stop, _stopped) = register_subscription(None);
+        runtime.block_on(async move {
+            let summaries = crate::connect_to_summary_service(connection_settings).await;
+            stream_summaries(
+                summaries,
+                stop,
+                |summary| {
+                    let c_summary = OwnedCSummary::new(summary);
+                    callback(&c_summary.as_csummary() as *const CSummary)
+                },
+                || Some(callback),
+            )
+            .await;
+        });
+
+        SUBSCRIPTIONS.lock().expect("Should lock").remove(&id);
+        0
+    } else {
+        3
+    }
+}
+
+unsafe fn convert_to_string(c_string: *const c_char) -> Result<&'static str, u8> {
+    if c_string.is_null() {
+        return Err(1);
+    }
+    let raw = CStr::from_ptr(c_string);
+    let str = match raw.to_str() {
+        Ok(s) => s,
+        Err(_) => return Err(1),
+    };
+
+    Ok(str)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{CStr, CString},
+        ptr::{null, null_mut},
+        slice,
+        sync::atomic::{AtomicI32, Ordering},
+    };
+
+    use order_book_service_types::proto::{Level, Summary, TradedPair};
+
+    use super::{
+        obs_connect, obs_disconnect, obs_get_latest_summary, obs_set_summary_callback,
+        shutdown_subscriptions, CSummary, OBS_STATUS_OK, OBS_STATUS_SHUTDOWN, SUBSCRIPTIONS,
+    };
+
+    extern "C" fn ignore_summary(_: *const CSummary) {}
+
+    static LAST_STATUS: AtomicI32 = AtomicI32::new(-1);
+
+    extern "C" fn record_status(summary: *const CSummary) {
+        LAST_STATUS.store(unsafe { (*summary).status }, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn should_fill_the_latest_summary_from_the_cache() {
+        let address = CString::new("http://127.0.0.1:1").unwrap();
+        let (eth, btc) = (CString::new("ETH").unwrap(), CString::new("BTC").unwrap());
+        let mut c_summary = CSummary {
+            spread: 0.0,
+            bids: null(),
+            bids_length: 0,
+            asks: null(),
+            asks_length: 0,
+            status: OBS_STATUS_OK,
+        };
+
+        unsafe {
+            let handle = obs_connect(address.as_ptr(), eth.as_ptr(), btc.as_ptr(), 1, 0);
+            assert!(!handle.is_null());
+            assert_eq!(obs_get_latest_summary(handle, &mut c_summary), 2);
+            assert_eq!(obs_set_summary_callback(handle, Some(ignore_summary)), 0);
+
+            (*handle).cache.record(
+                &TradedPair::new("ETH", "BTC"),
+                &Summary {
+                    spread: 0.5,
+                    bids: vec![Level::new("Binance", 1.0, 2.0)],
+                    ..Default::default()
+                },
+            );
+            assert_eq!(obs_get_latest_summary(handle, &mut c_summary), 0);
+            assert_eq!(c_summary.spread, 0.5);
+            assert_eq!(c_summary.asks_length, 0);
+            let bids = slice::from_raw_parts(c_summary.bids, c_summary.bids_length);
+            assert_eq!(CStr::from_ptr(bids[0].exchange).to_str(), Ok("Binance"));
+            assert_eq!(bids[0].amount, 2.0);
+
+            obs_disconnect(handle);
+            assert_eq!(obs_get_latest_summary(null_mut(), &mut c_summary), 1);
+
+            for max_attempts in [0, -1] {
+                let handle = obs_connect(
+                    address.as_ptr(),
+                    eth.as_ptr(),
+                    btc.as_ptr(),
+                    max_attempts,
+                    0,
+                );
+                assert!(handle.is_null());
+            }
+        }
+    }
+
+    #[test]
+    fn should_stop_active_streams_with_a_final_callback_on_exit() {
+        let address = CString::new("http://127.0.0.1:1").unwrap();
+        let (eth, btc) = (CString::new("ETH").unwrap(), CString::new("BTC").unwrap());
+
+        unsafe {
+            // Still retrying when the process exits
+            let handle = obs_connect(address.as_ptr(), eth.as_ptr(), btc.as_ptr(), 100, 1000);
+            assert_eq!(obs_set_summary_callback(handle, Some(record_status)), 0);
+
+            // Only this test's stream, leaving those of tests running alongside it
+            let subscription = SUBSCRIPTIONS.lock().unwrap().remove(&(*handle).id);
+            shutdown_subscriptions(subscription);
+            assert_eq!(LAST_STATUS.load(Ordering::SeqCst), OBS_STATUS_SHUTDOWN);
+            assert!((*handle).runtime.lock().unwrap().is_none());
+
+            obs_disconnect(handle);
+        }
+    }
+}
@@ -1,233 +1,298 @@
 extern crate core;
 
+// Subscriptions over HTTP/2 connections, browsers subscribe through the wasm module instead
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bridge;
+pub mod cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod capabilities;
+pub mod conflate;
+pub mod error;
+#[cfg(all(feature = "ffi", not(target_arch = "wasm32")))]
+pub mod ffi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod middleware;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod multi_pair;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pairs;
+#[cfg(all(feature = "python", not(target_arch = "wasm32")))]
+pub mod python;
+pub mod retry;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod selection;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod service;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod transport;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
 use std::time::Duration;
 
-use anyhow::{Context, Error};
-use tokio::sync::mpsc;
-use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Status, Streaming};
-use url::Url;
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    anyhow::{Context, Error},
+    order_book_service_types::proto::{
+        orderbook_aggregator_client::OrderbookAggregatorClient, Summary, TradedPair,
+    },
+    tokio::{sync::mpsc, time::timeout},
+    tokio_stream::wrappers::ReceiverStream,
+    tonic::{Status, Streaming},
+    url::Url,
+};
 
-use order_book_service_types::proto::{
-    orderbook_aggregator_client::OrderbookAggregatorClient, Summary, TradedPair,
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    conflate::Conflated,
+    error::StreamError,
+    middleware::MiddlewareChain,
+    retry::{Retry, RetryBudget},
+    selection::{ServerSelection, ServerSelector},
+    transport::CustomTransport,
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::pairs::{list_supported_pairs, resolve_pair, PairMatrix};
+
+#[cfg(not(target_arch = "wasm32"))]
 type SummaryResult = Result<Summary, Status>;
 
+/// A reasonable [ConnectionSettings::connect_timeout] for most deployments.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// A reasonable [ConnectionSettings::request_timeout] for most deployments.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Sets out how the client should connect to the service.  
 /// If the client is unable to connect then it will act according to the below:
 /// - `max_attempts` is how many times the client should attempt to connect.
 /// - `delay_between_attempts` is how long to wait before making a new attempt to connect.
+/// - `connect_timeout` is how long each attempt waits for a connection to the server.
+/// - `request_timeout` is how long each attempt then waits for the summary stream to be established.
+///
+/// - `retry_budget` limits reconnects collectively across the subscriptions sharing it, usually [RetryBudget::global].
+///
+/// The `middleware` is invoked on connect, on each summary and on errors.
+///
+/// A `custom_transport` replaces or adjusts the connection made to `server_address`, e.g. to go through an HTTP proxy.
+///
+/// Any `fallback_addresses` are servers of the same deployment, each attempt picks one of them or `server_address` by
+/// the `server_selection` policy, so a failed stream fails over to another server.
+///
+/// Built with [ConnectionSettings::new], then adjusted through its fields, so settings added later don't break callers.
+#[cfg(not(target_arch = "wasm32"))]
+#[non_exhaustive]
 pub struct ConnectionSettings {
     pub server_address: Url,
+    pub fallback_addresses: Vec<Url>,
+    pub server_selection: ServerSelection,
     pub traded_pair: TradedPair,
     pub max_attempts: usize,
     pub delay_between_attempts: Duration,
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+    pub retry_budget: RetryBudget,
+    pub middleware: MiddlewareChain,
+    pub custom_transport: Option<CustomTransport>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ConnectionSettings {
+    /// Settings for subscribing to `traded_pair` on `server_address` alone, with the default timeouts, the
+    /// [global](RetryBudget::global) retry budget and no middleware.
+    pub fn new(
+        server_address: Url,
+        traded_pair: TradedPair,
+        max_attempts: usize,
+        delay_between_attempts: Duration,
+    ) -> Self {
+        Self {
+            server_address,
+            fallback_addresses: Vec::new(),
+            server_selection: ServerSelection::default(),
+            traded_pair,
+            max_attempts,
+            delay_between_attempts,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            retry_budget: RetryBudget::global(),
+            middleware: MiddlewareChain::new(),
+            custom_transport: None,
+        }
+    }
 }
 
 /// Connect to the service, returning a Stream of [Summary]s (or [Status] in the Err case).
 /// Will make repeated attempts to connect as per the [`settings`](ConnectionSettings) provided.  
 ///
 /// Once the internal sender hangs up or the `max_attempts` are exhausted, an error status is sent to the client receiver.
+/// Attempts also stop once the service ends the stream for a reason retrying won't fix, see [StreamError::is_retryable].
+/// A service which is draining points the client at another server, which the next attempt connects to instead.
+#[cfg(not(target_arch = "wasm32"))]
 pub async fn connect_to_summary_service(
     settings: ConnectionSettings,
 ) -> ReceiverStream<SummaryResult> {
-    let mut attempts = 0;
-
     let (summary_tx, summary_rx) = mpsc::channel(300);
 
     tokio::spawn(async move {
-        while attempts < settings.max_attempts {
-            attempts += 1;
+        // Every attempt after the first is a reconnect and waits for the shared budget
+        let mut retry = Retry::fixed(settings.delay_between_attempts)
+            .with_max_attempts(settings.max_attempts)
+            .with_budget(settings.retry_budget.clone());
+        let mut selector = ServerSelector::new(
+            settings.server_address.clone(),
+            &settings.fallback_addresses,
+            settings.server_selection,
+        );
+
+        while retry.next_attempt().await {
+            let server_address = selector
+                .select(settings.connect_timeout, settings.custom_transport.as_ref())
+                .await;
             println!(
-                "Attempting to connect...\t({attempts}/{})",
+                "Attempting to connect...\t({}/{})",
+                retry.attempts(),
                 settings.max_attempts
             );
 
-            match connect_to_server_for_pair(
-                settings.server_address.clone(),
-                settings.traded_pair.clone(),
-            )
-            .await
-            {
-                Ok(mut summary_stream) => loop {
-                    let msg_result = summary_stream.message().await;
-                    match msg_result {
-                        Ok(Some(summary)) => {
-                            attempts = 0;
-                            let _ = summary_tx.send(Ok(summary)).await;
-                        }
-                        Ok(None) => {
-                            // Ok(None) means the sender has closed the connection
-                            break;
-                        }
-                        Err(status) => {
-                            let _ = summary_tx.send(Err(status)).await;
+            match connect_to_server_for_pair(&server_address, &settings).await {
+                Ok(mut summary_stream) => {
+                    settings
+                        .middleware
+                        .on_connect(&server_address, &settings.traded_pair)
+                        .await;
+
+                    loop {
+                        let msg_result = summary_stream.message().await;
+                        match msg_result {
+                            Ok(Some(summary)) => {
+                                retry.reset();
+                                if let Some(summary) = settings.middleware.on_summary(summary).await
+                                {
+                                    let _ = summary_tx.send(Ok(summary)).await;
+                                }
+                            }
+                            Ok(None) => {
+                                // Ok(None) means the sender has closed the connection
+                                break;
+                            }
+                            Err(status) => {
+                                settings.middleware.on_error(&status).await;
+                                let error = StreamError::from(status.clone());
+                                if let Some(address) = error.alternative_address() {
+                                    selector.redirect(address);
+                                }
+                                let retryable = error.is_retryable();
+                                let _ = summary_tx.send(Err(status)).await;
+                                if !retryable {
+                                    return;
+                                }
+                            }
                         }
                     }
-                },
+                }
                 Err(grpc_error) => {
                     eprintln!("Error connecting to server: {grpc_error}");
-                    tokio::time::sleep(settings.delay_between_attempts).await;
+                    // The service may have refused the subscription, e.g. when a tenant is at its quota
+                    if let Some(status) = grpc_error.downcast_ref::<Status>() {
+                        let error = StreamError::from(status.clone());
+                        if let Some(address) = error.alternative_address() {
+                            selector.redirect(address);
+                        }
+                        if !error.is_retryable() {
+                            settings.middleware.on_error(status).await;
+                            let _ = summary_tx.send(Err(status.clone())).await;
+                            return;
+                        }
+                    }
+                    settings
+                        .middleware
+                        .on_error(&Status::unavailable(format!("{grpc_error:#}")))
+                        .await;
                 }
             }
         }
 
-        let _ = summary_tx
-            .send(Err(Status::unavailable("The service is unavailable")))
-            .await;
+        let status = Status::unavailable("The service is unavailable");
+        settings.middleware.on_error(&status).await;
+        let _ = summary_tx.send(Err(status)).await;
     });
 
     summary_rx.into()
 }
 
+/// As [connect_to_summary_service], but only the latest summary is yielded when the stream is polled, see [Conflated].
+///
+/// For consumers which poll slowly, e.g. a GUI redrawing at its frame rate, rather than queueing ever more stale summaries.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn connect_to_conflated_summary_service(settings: ConnectionSettings) -> Conflated {
+    Conflated::new(connect_to_summary_service(settings).await)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 async fn connect_to_server_for_pair(
-    server_address: Url,
-    traded_pair: TradedPair,
+    server_address: &Url,
+    settings: &ConnectionSettings,
 ) -> Result<Streaming<Summary>, Error> {
-    let mut client = OrderbookAggregatorClient::connect(server_address.to_string())
-        .await
-        .context("Error making initial connection to server")?;
-
-    let orderbook_stream = client
-        .book_summary(traded_pair)
-        .await
-        .context("Error calling the BookSummary RPC")?
-        .into_inner();
+    // A black-holed address would otherwise leave the attempt hanging
+    let channel = timeout(
+        settings.connect_timeout,
+        transport::connect_with(server_address, settings.custom_transport.as_ref()),
+    )
+    .await
+    .context("Timed out making initial connection to server")??;
+    let mut client = OrderbookAggregatorClient::new(channel);
+
+    let orderbook_stream = timeout(
+        settings.request_timeout,
+        client.book_summary(settings.traded_pair.clone()),
+    )
+    .await
+    .context("Timed out calling the BookSummary RPC")?
+    .context("Error calling the BookSummary RPC")?
+    .into_inner();
 
     Ok(orderbook_stream)
 }
 
-pub mod ffi {
-    use std::{ffi::CStr, sync::Mutex, time::Duration};
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::time::{Duration, Instant};
 
-    use libc::{c_char, c_double, c_int, size_t};
-    use once_cell::sync::Lazy;
-    use tokio::runtime::Runtime;
-    use tokio_stream::StreamExt;
+    use tokio::net::TcpListener;
     use url::Url;
 
-    use order_book_service_types::proto::{Level, TradedPair};
-
-    use crate::ConnectionSettings;
-
-    static RUNTIME: Lazy<Mutex<Option<Runtime>>> = Lazy::new(|| Mutex::new(None));
-
-    #[repr(C)]
-    pub struct CLevel {
-        exchange: *const c_char,
-        price: c_double,
-        amount: c_double,
-    }
-
-    #[repr(C)]
-    pub struct CSummary {
-        spread: c_double,
-        bids: *const CLevel,
-        bids_length: size_t,
-        asks: *const CLevel,
-        asks_length: size_t,
-    }
-
-    fn setup_runtime() {
-        let mut runtime = RUNTIME.lock().expect("Should lock");
-        *runtime = Some(Runtime::new().expect("Should create tokio runtime"));
-    }
+    use order_book_service_types::proto::TradedPair;
 
-    fn teardown_runtime() {
-        let mut runtime = RUNTIME.lock().expect("should lock");
-        *runtime = None;
-    }
+    use crate::{connect_to_server_for_pair, ConnectionSettings};
 
-    #[allow(clippy::missing_safety_doc)]
-    pub unsafe extern "C" fn connect_to_summary_service(
-        server_address: *const c_char,
-        token_one_symbol: *const c_char,
-        token_two_symbol: *const c_char,
-        max_attempts: c_int,
-        delay_between_attempts_millis: c_int,
-        callback: extern "C" fn(*const CSummary),
-    ) -> c_int {
-        setup_runtime();
-
-        if let Some(runtime) = RUNTIME.lock().expect("Should lock").as_mut() {
-            let server_address_str =
-                convert_to_string(server_address).expect("Should convert to string");
-            let url = Url::parse(server_address_str).expect("Should parse url");
-            let traded_pair = TradedPair::new(
-                convert_to_string(token_one_symbol).expect("Should convert to string"),
-                convert_to_string(token_two_symbol).expect("Should convert to string"),
-            );
-            let max_attempts = max_attempts as usize;
-            let delay_between_attempts =
-                Duration::from_millis(delay_between_attempts_millis as u64);
-
-            let connection_settings = ConnectionSettings {
-                server_address: url,
-                traded_pair,
-                max_attempts,
-                delay_between_attempts,
-            };
-
-            runtime.block_on(async move {
-                let mut recv_stream = super::connect_to_summary_service(connection_settings).await;
-
-                while let Some(Ok(summary)) = recv_stream.next().await {
-                    let c_level_bids = summary
-                        .bids
-                        .into_iter()
-                        .map(level_to_clevel)
-                        .collect::<Vec<CLevel>>();
-
-                    let c_level_asks = summary
-                        .asks
-                        .into_iter()
-                        .map(level_to_clevel)
-                        .collect::<Vec<CLevel>>();
-
-                    let c_summary = CSummary {
-                        spread: summary.spread as c_double,
-                        bids: c_level_bids.as_ptr(),
-                        bids_length: c_level_bids.len(),
-                        asks: c_level_asks.as_ptr(),
-                        asks_length: c_level_asks.len(),
-                    };
-
-                    callback(&c_summary as *const CSummary)
-                }
-            });
-
-            teardown_runtime();
-            0
-        } else {
-            teardown_runtime();
-            3
-        }
-    }
-
-    fn level_to_clevel(mut level: Level) -> CLevel {
-        // Append nul character for CStr
-        level.exchange.push(0.into());
-
-        CLevel {
-            exchange: CStr::from_bytes_with_nul(level.exchange.as_ref())
-                .expect("Should parse to CString")
-                .as_ptr(),
-            price: level.price,
-            amount: level.amount,
-        }
-    }
-
-    unsafe fn convert_to_string(c_string: *const c_char) -> Result<&'static str, u8> {
-        if c_string.is_null() {
-            return Err(1);
-        }
-        let raw = CStr::from_ptr(c_string);
-        let str = match raw.to_str() {
-            Ok(s) => s,
-            Err(_) => return Err(1),
-        };
-
-        Ok(str)
+    #[tokio::test]
+    async fn should_time_out_on_unresponsive_server() {
+        // Accepts connections but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+
+        let mut settings = ConnectionSettings::new(
+            Url::parse(&format!("http://{address}")).unwrap(),
+            TradedPair::new("ETH", "BTC"),
+            1,
+            Duration::ZERO,
+        );
+        settings.connect_timeout = Duration::from_millis(100);
+        settings.request_timeout = Duration::from_millis(100);
+
+        let started = Instant::now();
+        let result = connect_to_server_for_pair(&settings.server_address, &settings).await;
+
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(1));
     }
 }
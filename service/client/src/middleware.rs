@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use tonic::Status;
+use url::Url;
+
+use order_book_service_types::proto::{Summary, TradedPair};
+
+/// Hooks into the lifecycle of a summary subscription, e.g. for custom metrics, logging or transformations.
+/// Every method has a no-op default so implementations only need to provide the hooks they're interested in.
+#[tonic::async_trait]
+pub trait Middleware: Send + Sync {
+    /// Called each time a connection to the server is established.
+    async fn on_connect(&self, _server_address: &Url, _traded_pair: &TradedPair) {}
+
+    /// Called with each summary before it is passed on, returning `None` drops the summary.
+    async fn on_summary(&self, summary: Summary) -> Option<Summary> {
+        Some(summary)
+    }
+
+    /// Called with each error, both failures to connect and statuses returned by the server.
+    async fn on_error(&self, _status: &Status) {}
+}
+
+/// Middlewares applied in the order they were registered, like a stack of tower layers.
+#[derive(Clone, Default)]
+pub struct MiddlewareChain(Vec<Arc<dyn Middleware>>);
+
+impl MiddlewareChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a middleware, it will be invoked after any already registered.
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.0.push(Arc::new(middleware));
+        self
+    }
+
+    pub(crate) async fn on_connect(&self, server_address: &Url, traded_pair: &TradedPair) {
+        for middleware in self.0.iter() {
+            middleware.on_connect(server_address, traded_pair).await;
+        }
+    }
+
+    pub(crate) async fn on_summary(&self, mut summary: Summary) -> Option<Summary> {
+        for middleware in self.0.iter() {
+            summary = middleware.on_summary(summary).await?;
+        }
+        Some(summary)
+    }
+
+    pub(crate) async fn on_error(&self, status: &Status) {
+        for middleware in self.0.iter() {
+            middleware.on_error(status).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct AddToSpread(f64);
+
+    #[tonic::async_trait]
+    impl Middleware for AddToSpread {
+        async fn on_summary(&self, mut summary: Summary) -> Option<Summary> {
+            summary.spread += self.0;
+            Some(summary)
+        }
+    }
+
+    struct DropNegativeSpread;
+
+    #[tonic::async_trait]
+    impl Middleware for DropNegativeSpread {
+        async fn on_summary(&self, summary: Summary) -> Option<Summary> {
+            (summary.spread >= 0.0).then_some(summary)
+        }
+    }
+
+    #[derive(Default)]
+    struct CountErrors(Arc<AtomicUsize>);
+
+    #[tonic::async_trait]
+    impl Middleware for CountErrors {
+        async fn on_error(&self, _status: &Status) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_apply_middleware_in_registration_order() {
+        let chain = MiddlewareChain::new()
+            .with(AddToSpread(-2.0))
+            .with(DropNegativeSpread)
+            .with(AddToSpread(10.0));
+
+        let kept = chain
+            .on_summary(Summary {
+                spread: 3.0,
+                ..Default::default()
+            })
+            .await;
+        let dropped = chain
+            .on_summary(Summary {
+                spread: 1.0,
+                ..Default::default()
+            })
+            .await;
+
+        assert_eq!(kept.map(|summary| summary.spread), Some(11.0));
+        assert!(dropped.is_none());
+    }
+
+    #[tokio::test]
+    async fn should_invoke_every_error_hook() {
+        let errors = Arc::new(AtomicUsize::new(0));
+        let chain = MiddlewareChain::new()
+            .with(CountErrors(errors.clone()))
+            .with(CountErrors(errors.clone()));
+
+        chain.on_error(&Status::internal("Example")).await;
+
+        assert_eq!(errors.load(Ordering::SeqCst), 2);
+    }
+}
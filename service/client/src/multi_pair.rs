@@ -0,0 +1,170 @@
+use std::{pin::Pin, time::Duration};
+
+use anyhow::Error;
+use tokio::{
+    sync::{
+        mpsc::channel,
+        watch::{channel as watch_channel, Receiver as WatchReceiver, Sender as WatchSender},
+    },
+    time::interval,
+};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{transport::Channel, Status, Streaming};
+use tonic_health::proto::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+use url::Url;
+
+use order_book_service_types::{
+    batch::batch_summaries,
+    proto::{
+        orderbook_aggregator_client::OrderbookAggregatorClient, BatchedRequest, Capability,
+        Summary, SummaryBatch, TradedPair,
+    },
+};
+
+use crate::{capabilities::ServerCapabilities, transport};
+
+/// Batches of summaries, whether batched by the server or the client.
+pub type BatchStream = Pin<Box<dyn Stream<Item = Result<SummaryBatch, Status>> + Send>>;
+
+/// The name the summary service is registered under with the server's health service.
+pub(crate) const SUMMARY_SERVICE_NAME: &str = "orderbook.OrderbookAggregator";
+
+/// A client which shares a single connection to the server across subscriptions for many pairs,
+/// rather than making a new connection per subscription.
+///
+/// The health of the shared connection is checked periodically in the background.
+/// The server's capabilities are negotiated on connect, features it lacks are provided client-side where possible.
+pub struct MultiPairClient {
+    client: OrderbookAggregatorClient<Channel>,
+    healthy: WatchReceiver<bool>,
+    capabilities: ServerCapabilities,
+}
+
+impl MultiPairClient {
+    /// Connect to the server, checking the health of the connection every `health_check_interval`.
+    pub async fn connect(
+        server_address: Url,
+        health_check_interval: Duration,
+    ) -> Result<Self, Error> {
+        let channel = transport::connect(&server_address).await?;
+        Self::with_channel(channel, health_check_interval).await
+    }
+
+    /// As [MultiPairClient::connect], over an existing channel e.g. to an in-process server.
+    pub async fn with_channel(
+        channel: Channel,
+        health_check_interval: Duration,
+    ) -> Result<Self, Error> {
+        let mut client = OrderbookAggregatorClient::new(channel.clone());
+        let capabilities = ServerCapabilities::negotiate(&mut client).await?;
+
+        let (healthy_tx, healthy) = watch_channel(true);
+        tokio::spawn(check_health(
+            HealthClient::new(channel),
+            healthy_tx,
+            health_check_interval,
+        ));
+
+        Ok(Self {
+            client,
+            healthy,
+            capabilities,
+        })
+    }
+
+    /// What the server reported it supports when connecting.
+    pub fn capabilities(&self) -> &ServerCapabilities {
+        &self.capabilities
+    }
+
+    /// Whether the last health check of the shared connection succeeded.
+    pub fn is_healthy(&self) -> bool {
+        *self.healthy.borrow()
+    }
+
+    /// Subscribe to summaries for `traded_pair` over the shared connection.
+    pub async fn subscribe(&self, traded_pair: TradedPair) -> Result<Streaming<Summary>, Status> {
+        if !self.is_healthy() {
+            return Err(Status::unavailable(
+                "The connection to the server is unhealthy",
+            ));
+        }
+
+        // Cloning the client shares the underlying connection
+        let summary_stream = self
+            .client
+            .clone()
+            .book_summary(traded_pair)
+            .await?
+            .into_inner();
+
+        Ok(summary_stream)
+    }
+
+    /// Receive every summary for `traded_pair` in batches, one per `window`.
+    ///
+    /// Servers without the BookSummaryBatched RPC are subscribed to as normal, with the batching done client-side.
+    pub async fn subscribe_batched(
+        &self,
+        traded_pair: TradedPair,
+        window: Duration,
+    ) -> Result<BatchStream, Status> {
+        if !self.capabilities.supports(Capability::BatchedSummaries) {
+            let summary_stream = self.subscribe(traded_pair).await?;
+            let (batch_tx, batch_rx) = channel(1);
+            tokio::spawn(batch_summaries(
+                summary_stream,
+                window,
+                async move |batch| batch_tx.send(batch).await.is_ok(),
+            ));
+            return Ok(Box::pin(ReceiverStream::new(batch_rx)));
+        }
+
+        if !self.is_healthy() {
+            return Err(Status::unavailable(
+                "The connection to the server is unhealthy",
+            ));
+        }
+
+        let batch_stream = self
+            .client
+            .clone()
+            .book_summary_batched(BatchedRequest {
+                subscription: Some(traded_pair.into()),
+                window_secs: window.as_secs().max(1) as u32,
+            })
+            .await?
+            .into_inner();
+
+        Ok(Box::pin(batch_stream))
+    }
+}
+
+/// Poll the server's health service until the [MultiPairClient] is dropped.
+async fn check_health(
+    mut health_client: HealthClient<Channel>,
+    healthy_tx: WatchSender<bool>,
+    health_check_interval: Duration,
+) {
+    let mut health_check_interval = interval(health_check_interval);
+
+    while !healthy_tx.is_closed() {
+        health_check_interval.tick().await;
+
+        let healthy = health_client
+            .check(HealthCheckRequest {
+                service: SUMMARY_SERVICE_NAME.to_string(),
+            })
+            .await
+            .map(|response| response.into_inner().status() == ServingStatus::Serving)
+            .unwrap_or(false);
+
+        healthy_tx.send_if_modified(|current| {
+            let changed = *current != healthy;
+            *current = healthy;
+            changed
+        });
+    }
+}
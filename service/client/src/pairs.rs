@@ -0,0 +1,261 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+
+use anyhow::{Context, Error};
+use url::Url;
+
+use order_book_service_types::proto::{
+    orderbook_aggregator_client::OrderbookAggregatorClient, Empty, SupportedPairs, TradedPair,
+};
+
+use crate::transport;
+
+/// Separators accepted between the two symbols of a pair, e.g. `ETH/BTC` or `ETH-BTC`.
+const SEPARATORS: [char; 4] = ['/', '-', '_', ':'];
+
+/// Query the server for the pairs each exchange offers, see [SupportedPairs::pairs] for those which can be subscribed to.
+pub async fn list_supported_pairs(server_address: &Url) -> Result<SupportedPairs, Error> {
+    let mut client = OrderbookAggregatorClient::new(transport::connect(server_address).await?);
+
+    let supported_pairs = client
+        .list_supported_pairs(Empty {})
+        .await
+        .context("Error calling the ListSupportedPairs RPC")?
+        .into_inner();
+
+    Ok(supported_pairs)
+}
+
+/// Resolve a pair written as e.g. `ethbtc`, `ETH/BTC` or `ETH-BTC` to one of the `supported` pairs,
+/// matching regardless of case so the result has the casing the server expects.
+pub fn resolve_pair(pair: &str, supported: &[TradedPair]) -> Result<TradedPair, Error> {
+    let pair = pair.trim();
+
+    let matches = |candidate: &&TradedPair| match pair.split_once(SEPARATORS) {
+        Some((first, second)) => {
+            candidate.first.eq_ignore_ascii_case(first.trim())
+                && candidate.second.eq_ignore_ascii_case(second.trim())
+        }
+        // Without a separator the pair can only be split by comparing against the supported pairs
+        None => format!("{}{}", candidate.first, candidate.second).eq_ignore_ascii_case(pair),
+    };
+
+    supported
+        .iter()
+        .find(matches)
+        .cloned()
+        .ok_or_else(|| Error::msg(format!("Pair {pair} is not supported by the server")))
+}
+
+/// Which exchanges list each pair, to help pick pairs which will aggregate across several venues.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PairMatrix {
+    /// The exchanges queried, the matrix's columns
+    pub exchanges: Vec<String>,
+    /// Exchanges which couldn't be queried, they have no column
+    pub unavailable: Vec<String>,
+    /// Every pair listed by any exchange, those listed by the most exchanges first
+    pub rows: Vec<PairMatrixRow>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PairMatrixRow {
+    pub pair: TradedPair,
+    /// Whether each of the [PairMatrix::exchanges] lists the pair, in the same order
+    pub listed: Vec<bool>,
+    /// Whether the pair can be subscribed to. The server may source a pair from an exchange in another quote currency,
+    /// so this can differ from the listings alone
+    pub aggregatable: bool,
+}
+
+impl PairMatrixRow {
+    /// How many exchanges list the pair.
+    pub fn venues(&self) -> usize {
+        self.listed.iter().filter(|listed| **listed).count()
+    }
+}
+
+impl PairMatrix {
+    pub fn new(supported: &SupportedPairs) -> Self {
+        let (available, unavailable): (Vec<_>, Vec<_>) = supported
+            .exchanges
+            .iter()
+            .partition(|exchange| exchange.error.is_empty());
+
+        // Keyed by the canonical pair, so exchanges listing it in different cases share a row
+        let mut listings = BTreeMap::<String, (TradedPair, Vec<bool>)>::new();
+        for (column, exchange) in available.iter().enumerate() {
+            for pair in &exchange.pairs {
+                let pair = pair.canonical();
+                listings
+                    .entry(pair.to_string())
+                    .or_insert_with(|| (pair, vec![false; available.len()]))
+                    .1[column] = true;
+            }
+        }
+
+        let mut rows = listings
+            .into_values()
+            .map(|(pair, listed)| PairMatrixRow {
+                aggregatable: supported
+                    .pairs
+                    .iter()
+                    .any(|aggregatable| aggregatable.eq_ignore_case(&pair)),
+                pair,
+                listed,
+            })
+            .collect::<Vec<_>>();
+        // Stable, so pairs listed by as many exchanges stay in alphabetical order
+        rows.sort_by_key(|row| std::cmp::Reverse(row.venues()));
+
+        Self {
+            exchanges: available
+                .iter()
+                .map(|exchange| exchange.exchange.clone())
+                .collect(),
+            unavailable: unavailable
+                .iter()
+                .map(|exchange| exchange.exchange.clone())
+                .collect(),
+            rows,
+        }
+    }
+
+    /// The pairs listed by every exchange queried.
+    pub fn listed_by_all(&self) -> impl Iterator<Item = &TradedPair> {
+        self.rows
+            .iter()
+            .filter(|row| row.venues() == self.exchanges.len())
+            .map(|row| &row.pair)
+    }
+
+    /// The matrix as CSV, with a column per exchange holding `true` where it lists the pair.
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("pair,{},venues,aggregatable\n", self.exchanges.join(","));
+        for row in &self.rows {
+            let listed = row
+                .listed
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                row.pair,
+                listed.join(","),
+                row.venues(),
+                row.aggregatable
+            ));
+        }
+        csv
+    }
+}
+
+/// An aligned table marking where each exchange lists a pair, followed by the exchanges which couldn't be queried.
+impl Display for PairMatrix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let pair_width = self
+            .rows
+            .iter()
+            .map(|row| row.pair.to_string().len())
+            .chain(["Pair".len()])
+            .max()
+            .unwrap_or_default();
+
+        write!(f, "{:pair_width$}", "Pair")?;
+        for exchange in &self.exchanges {
+            write!(f, "  {exchange}")?;
+        }
+        writeln!(f, "  Venues  Aggregates")?;
+        for row in &self.rows {
+            write!(f, "{:pair_width$}", row.pair.to_string())?;
+            for (exchange, listed) in self.exchanges.iter().zip(&row.listed) {
+                write!(
+                    f,
+                    "  {:^1$}",
+                    if *listed { "x" } else { "-" },
+                    exchange.len()
+                )?;
+            }
+            writeln!(
+                f,
+                "  {:>6}  {}",
+                row.venues(),
+                if row.aggregatable { "yes" } else { "no" }
+            )?;
+        }
+        if !self.unavailable.is_empty() {
+            writeln!(f, "Unavailable: {}", self.unavailable.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::{ExchangePairs, SupportedPairs, TradedPair};
+
+    use super::{resolve_pair, PairMatrix};
+
+    #[test]
+    fn should_resolve_pair_aliases() {
+        let supported = vec![TradedPair::new("ETH", "BTC"), TradedPair::new("BTC", "USD")];
+
+        for alias in ["ethbtc", "ETH/BTC", "eth-btc", " ETH_btc "] {
+            assert_eq!(
+                resolve_pair(alias, &supported).unwrap(),
+                TradedPair::new("ETH", "BTC")
+            );
+        }
+        assert!(resolve_pair("BTC/ETH", &supported).is_err());
+        assert!(resolve_pair("ethusd", &supported).is_err());
+    }
+
+    #[test]
+    fn should_build_the_matrix_of_pairs_each_exchange_lists() {
+        let supported = SupportedPairs {
+            pairs: vec![TradedPair::new("ETH", "BTC"), TradedPair::new("BTC", "USD")],
+            exchanges: vec![
+                ExchangePairs {
+                    exchange: "Binance".to_string(),
+                    pairs: vec![TradedPair::new("ETH", "BTC"), TradedPair::new("BNB", "BTC")],
+                    error: String::new(),
+                },
+                ExchangePairs {
+                    exchange: "Bitstamp".to_string(),
+                    pairs: vec![TradedPair::new("eth", "btc"), TradedPair::new("BTC", "USD")],
+                    error: String::new(),
+                },
+                ExchangePairs {
+                    exchange: "Kraken".to_string(),
+                    pairs: Vec::new(),
+                    error: "Timed out".to_string(),
+                },
+            ],
+        };
+
+        let matrix = PairMatrix::new(&supported);
+        assert_eq!(matrix.exchanges, vec!["Binance", "Bitstamp"]);
+        assert_eq!(matrix.unavailable, vec!["Kraken"]);
+        assert_eq!(
+            matrix.listed_by_all().collect::<Vec<_>>(),
+            vec![&TradedPair::new("ETH", "BTC")]
+        );
+        assert_eq!(
+            matrix.to_string(),
+            "Pair     Binance  Bitstamp  Venues  Aggregates\n\
+             ETH-BTC     x        x           2  yes\n\
+             BNB-BTC     x        -           1  no\n\
+             BTC-USD     -        x           1  yes\n\
+             Unavailable: Kraken\n"
+        );
+        assert_eq!(
+            matrix.to_csv(),
+            "pair,Binance,Bitstamp,venues,aggregatable\n\
+             ETH-BTC,true,true,2,true\n\
+             BNB-BTC,true,false,1,false\n\
+             BTC-USD,false,true,1,true\n"
+        );
+    }
+}
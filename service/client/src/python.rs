@@ -0,0 +1,183 @@
+//! A Python module of summary subscriptions, iterated like any other Python iterator.
+//!
+//! Build and install into the active environment with `maturin develop` from the client's directory, see the README.
+
+use std::time::Duration;
+
+use pyo3::{
+    exceptions::{PyConnectionError, PyValueError},
+    prelude::*,
+    types::{PyDict, PyList},
+};
+use url::Url;
+
+use order_book_service_types::proto::{Level, Summary, TradedPair};
+
+use crate::{blocking::BlockingSubscription, ConnectionSettings};
+
+/// Summaries of a traded pair, yielded as dicts of `spread`, `bids` and `asks`, each level a dict of `exchange`, `price`
+/// and `amount`.
+///
+/// Reconnects are made in the background, once they're exhausted or the service refuses the subscription iteration
+/// raises `ConnectionError` with the reason.
+#[pyclass(module = "order_book_service_client")]
+pub struct Subscription {
+    summaries: BlockingSubscription,
+    /// Error of the last item received, raised if the stream then ends
+    last_error: Option<String>,
+}
+
+#[pymethods]
+impl Subscription {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        loop {
+            // Other Python threads run while waiting for the next summary
+            let next = py.allow_threads(|| self.summaries.next());
+            match next {
+                Some(Ok(summary)) => {
+                    self.last_error = None;
+                    return summary_to_dict(py, &summary).map(Some);
+                }
+                // A reconnect follows, unless the stream ends next
+                Some(Err(status)) => self.last_error = Some(status.message().to_string()),
+                None => {
+                    return match self.last_error.take() {
+                        Some(error) => Err(PyConnectionError::new_err(error)),
+                        None => Ok(None),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Subscribe to the summaries of `first`-`second` from `server_address`, as `connect_to_summary_service` does.
+#[pyfunction]
+#[pyo3(signature = (server_address, first, second, max_attempts = 5, delay_between_attempts_millis = 1000))]
+fn subscribe(
+    server_address: &str,
+    first: String,
+    second: String,
+    max_attempts: usize,
+    delay_between_attempts_millis: u64,
+) -> PyResult<Subscription> {
+    let server_address = Url::parse(server_address)
+        .map_err(|err| PyValueError::new_err(format!("Invalid server address: {err}")))?;
+    // Without an attempt the subscription would never connect
+    if max_attempts < 1 {
+        return Err(PyValueError::new_err("max_attempts must be at least 1"));
+    }
+    let settings = ConnectionSettings::new(
+        server_address,
+        TradedPair { first, second }.canonical(),
+        max_attempts,
+        Duration::from_millis(delay_between_attempts_millis),
+    );
+
+    Ok(Subscription {
+        summaries: BlockingSubscription::connect(settings)?,
+        last_error: None,
+    })
+}
+
+fn summary_to_dict(py: Python<'_>, summary: &Summary) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("spread", summary.spread)?;
+    dict.set_item("bids", levels_to_list(py, &summary.bids)?)?;
+    dict.set_item("asks", levels_to_list(py, &summary.asks)?)?;
+    Ok(dict.into_any().unbind())
+}
+
+fn levels_to_list<'py>(py: Python<'py>, levels: &[Level]) -> PyResult<Bound<'py, PyList>> {
+    let list = PyList::empty(py);
+    for level in levels {
+        let dict = PyDict::new(py);
+        dict.set_item("exchange", &level.exchange)?;
+        dict.set_item("price", level.price)?;
+        dict.set_item("amount", level.amount)?;
+        list.append(dict)?;
+    }
+    Ok(list)
+}
+
+#[pymodule]
+fn order_book_service_client(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Subscription>()?;
+    module.add_function(wrap_pyfunction!(subscribe, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+
+    use order_book_service_types::proto::{Level, Summary};
+
+    use super::{subscribe, summary_to_dict};
+
+    #[test]
+    fn should_convert_summaries_into_dicts() {
+        pyo3::prepare_freethreaded_python();
+        let summary = Summary {
+            spread: 0.5,
+            bids: vec![Level::new("Binance", 10.0, 2.0)],
+            asks: vec![
+                Level::new("Bitstamp", 10.5, 1.0),
+                Level::new("Binance", 11.0, 3.0),
+            ],
+            ..Default::default()
+        };
+
+        Python::with_gil(|py| {
+            let dict = summary_to_dict(py, &summary).unwrap();
+            let dict = dict.downcast_bound::<PyDict>(py).unwrap();
+
+            assert_eq!(
+                dict.get_item("spread")
+                    .unwrap()
+                    .unwrap()
+                    .extract::<f64>()
+                    .unwrap(),
+                0.5
+            );
+            let best_bid = dict.get_item("bids").unwrap().unwrap().get_item(0).unwrap();
+            assert_eq!(
+                best_bid
+                    .get_item("exchange")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "Binance"
+            );
+            assert_eq!(
+                best_bid
+                    .get_item("amount")
+                    .unwrap()
+                    .extract::<f64>()
+                    .unwrap(),
+                2.0
+            );
+            assert_eq!(dict.get_item("asks").unwrap().unwrap().len().unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn should_refuse_subscriptions_which_can_never_connect() {
+        pyo3::prepare_freethreaded_python();
+
+        Python::with_gil(|py| {
+            for (server_address, max_attempts) in [("not a url", 1), ("http://127.0.0.1:1", 0)] {
+                let Err(error) =
+                    subscribe(server_address, "ETH".into(), "BTC".into(), max_attempts, 0)
+                else {
+                    panic!("Should refuse {server_address} with {max_attempts} attempts");
+                };
+                assert!(error.is_instance_of::<PyValueError>(py));
+            }
+        });
+    }
+}
@@ -0,0 +1,2 @@
+// Retrying is shared with the server, these are re-exported so clients can keep using them from here
+pub use order_book_service_types::retry::{Backoff, BudgetError, Retry, RetryBudget};
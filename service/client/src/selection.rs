@@ -0,0 +1,227 @@
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use tokio::time::{timeout, Instant};
+use tonic_health::proto::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+use url::Url;
+
+use crate::{
+    multi_pair::SUMMARY_SERVICE_NAME,
+    transport::{self, CustomTransport},
+};
+
+/// How each connection attempt picks a server when a subscription is given more than one, see
+/// [ConnectionSettings::fallback_addresses](crate::ConnectionSettings::fallback_addresses).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ServerSelection {
+    /// The first server, in the order given, whose health check passes, so the primary is preferred whenever it's up
+    #[default]
+    FirstHealthy,
+    /// The next server in turn on every attempt, without checking health first
+    RoundRobin,
+    /// The healthy server answering its health check quickest, probing every server on each attempt
+    LowestLatency,
+}
+
+/// Chooses the server for each attempt of a subscription. Failing over is choosing again once a stream fails.
+pub(crate) struct ServerSelector {
+    addresses: Vec<Url>,
+    policy: ServerSelection,
+    /// The index of the address chosen by the last attempt
+    current: Option<usize>,
+    /// A server the subscription was told to move to, used for the next attempt whatever the policy
+    redirect: Option<Url>,
+}
+
+impl ServerSelector {
+    pub(crate) fn new(primary: Url, fallbacks: &[Url], policy: ServerSelection) -> Self {
+        let mut addresses = vec![primary];
+        for fallback in fallbacks {
+            if !addresses.contains(fallback) {
+                addresses.push(fallback.clone());
+            }
+        }
+        Self {
+            addresses,
+            policy,
+            current: None,
+            redirect: None,
+        }
+    }
+
+    /// Use `address` for the next attempt, as when a draining server names its alternative. It's added to the servers
+    /// chosen from if it isn't one of them.
+    pub(crate) fn redirect(&mut self, address: Url) {
+        if !self.addresses.contains(&address) {
+            self.addresses.push(address.clone());
+        }
+        self.redirect = Some(address);
+    }
+
+    /// The server to attempt next. Servers are probed at once, so health checks wait up to `probe_timeout` in all.
+    pub(crate) async fn select(
+        &mut self,
+        probe_timeout: Duration,
+        custom_transport: Option<&CustomTransport>,
+    ) -> Url {
+        let index = match (self.redirect.take(), self.policy) {
+            (Some(address), _) => self.index_of(&address),
+            // Nothing to choose between
+            _ if self.addresses.len() == 1 => 0,
+            (None, ServerSelection::RoundRobin) => self
+                .current
+                .map_or(0, |current| (current + 1) % self.addresses.len()),
+            (None, ServerSelection::FirstHealthy) => self
+                .probe_all(probe_timeout, custom_transport)
+                .await
+                .iter()
+                .position(Option::is_some)
+                .unwrap_or(0),
+            (None, ServerSelection::LowestLatency) => self
+                .probe_all(probe_timeout, custom_transport)
+                .await
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, latency)| Some((index, latency?)))
+                .min_by_key(|(_, latency)| *latency)
+                .map_or(0, |(index, _)| index),
+        };
+
+        self.current = Some(index);
+        self.addresses[index].clone()
+    }
+
+    /// Probe every server concurrently, giving each one's latency in the order of the addresses.
+    async fn probe_all(
+        &self,
+        probe_timeout: Duration,
+        custom_transport: Option<&CustomTransport>,
+    ) -> Vec<Option<Duration>> {
+        join_all(
+            self.addresses
+                .iter()
+                .map(|address| probe(address, probe_timeout, custom_transport)),
+        )
+        .await
+    }
+
+    fn index_of(&self, address: &Url) -> usize {
+        self.addresses
+            .iter()
+            .position(|candidate| candidate == address)
+            .expect("Redirects should be added to the addresses")
+    }
+}
+
+/// How long the server at `address` took to report the summary service as serving, `None` if it didn't within
+/// `probe_timeout`.
+async fn probe(
+    address: &Url,
+    probe_timeout: Duration,
+    custom_transport: Option<&CustomTransport>,
+) -> Option<Duration> {
+    let started = Instant::now();
+    let check = async {
+        let channel = transport::connect_with(address, custom_transport)
+            .await
+            .ok()?;
+        let response = HealthClient::new(channel)
+            .check(HealthCheckRequest {
+                service: SUMMARY_SERVICE_NAME.to_string(),
+            })
+            .await
+            .ok()?;
+        (response.into_inner().status() == ServingStatus::Serving).then(|| started.elapsed())
+    };
+    timeout(probe_timeout, check).await.ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{net::TcpListener, time::Instant};
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+    use tonic_health::server::health_reporter;
+    use url::Url;
+
+    use super::{ServerSelection, ServerSelector, SUMMARY_SERVICE_NAME};
+
+    fn url(address: &str) -> Url {
+        Url::parse(address).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_take_turns_and_follow_redirects() {
+        let (one, two) = (url("http://one:3030"), url("http://two:3030"));
+        let mut selector = ServerSelector::new(
+            one.clone(),
+            &[two.clone(), one.clone()],
+            ServerSelection::RoundRobin,
+        );
+        for expected in [&one, &two, &one] {
+            assert_eq!(&selector.select(Duration::ZERO, None).await, expected);
+        }
+
+        let standby = url("http://standby:3030");
+        selector.redirect(standby.clone());
+        assert_eq!(selector.select(Duration::ZERO, None).await, standby);
+        // Carrying on in turn from the redirect
+        assert_eq!(selector.select(Duration::ZERO, None).await, one);
+    }
+
+    #[tokio::test]
+    async fn should_fail_over_to_a_healthy_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let healthy = url(&format!("http://{}", listener.local_addr().unwrap()));
+        let (mut reporter, health_svc) = health_reporter();
+        reporter
+            .set_service_status(SUMMARY_SERVICE_NAME, tonic_health::ServingStatus::Serving)
+            .await;
+        tokio::spawn(
+            Server::builder()
+                .add_service(health_svc)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        // Nothing is listening on the primary
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary = url(&format!("http://{}", closed.local_addr().unwrap()));
+        drop(closed);
+
+        let fallbacks = [healthy.clone()];
+        for policy in [
+            ServerSelection::FirstHealthy,
+            ServerSelection::LowestLatency,
+        ] {
+            let mut selector = ServerSelector::new(primary.clone(), &fallbacks, policy);
+            assert_eq!(selector.select(Duration::from_secs(1), None).await, healthy);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_probe_servers_concurrently() {
+        // Listening but never answering, so each probe waits out its timeout
+        let (mut listeners, mut hanging) = (Vec::new(), Vec::new());
+        for _ in 0..3 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            hanging.push(url(&format!("http://{}", listener.local_addr().unwrap())));
+            listeners.push(listener);
+        }
+
+        let mut selector = ServerSelector::new(
+            hanging[0].clone(),
+            &hanging[1..],
+            ServerSelection::LowestLatency,
+        );
+        let started = Instant::now();
+        assert_eq!(
+            selector.select(Duration::from_millis(500), None).await,
+            hanging[0]
+        );
+        assert!(started.elapsed() < Duration::from_millis(1000));
+    }
+}
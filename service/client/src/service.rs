@@ -0,0 +1,100 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use anyhow::Error;
+use tonic::{transport::Channel, Status, Streaming};
+use tower::Service;
+use url::Url;
+
+use order_book_service_types::proto::{
+    orderbook_aggregator_client::OrderbookAggregatorClient, Summary, TradedPair,
+};
+
+use crate::transport;
+
+/// A [tower::Service] which subscribes to summaries for each [TradedPair] it is called with.
+///
+/// This allows standard tower middleware, e.g. retries, timeouts and load shedding, to be composed around
+/// subscriptions as an alternative to the retry loop of [connect_to_summary_service](crate::connect_to_summary_service).
+#[derive(Clone, Debug)]
+pub struct SummaryService {
+    channel: Channel,
+    client: OrderbookAggregatorClient<Channel>,
+}
+
+impl SummaryService {
+    /// Subscriptions are made over `channel`, which may be shared with other clients.
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            client: OrderbookAggregatorClient::new(channel.clone()),
+            channel,
+        }
+    }
+
+    /// Connect to the server lazily, on the first subscription, so connection errors surface from the service.
+    pub fn connect_lazy(server_address: &Url) -> Result<Self, Error> {
+        Ok(Self::new(transport::connect_lazy(server_address)?))
+    }
+}
+
+impl Service<TradedPair> for SummaryService {
+    type Response = Streaming<Summary>;
+    type Error = Status;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // The service is ready when the underlying connection is
+        Service::poll_ready(&mut self.channel, cx)
+            .map_err(|err| Status::unavailable(format!("Connection error: {err}")))
+    }
+
+    fn call(&mut self, traded_pair: TradedPair) -> Self::Future {
+        let mut client = self.client.clone();
+
+        Box::pin(async move {
+            let summary_stream = client.book_summary(traded_pair).await?.into_inner();
+            Ok(summary_stream)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use tower::{ServiceBuilder, ServiceExt};
+    use url::Url;
+
+    use order_book_service_types::proto::TradedPair;
+
+    use super::SummaryService;
+
+    #[tokio::test]
+    async fn should_compose_with_tower_middleware() {
+        // Accepts connections but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+
+        let summary_service =
+            SummaryService::connect_lazy(&Url::parse(&format!("http://{address}")).unwrap())
+                .unwrap();
+        let service = ServiceBuilder::new()
+            .timeout(Duration::from_millis(100))
+            .service(summary_service);
+
+        let result = service.oneshot(TradedPair::new("ETH", "BTC")).await;
+
+        let err = result.expect_err("The subscription should time out");
+        assert!(err.is::<tower::timeout::error::Elapsed>());
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+
+use anyhow::{Context, Error};
+use tonic::transport::{Channel, Endpoint};
+use url::Url;
+#[cfg(unix)]
+use {tokio::net::UnixStream, tonic::transport::Uri, tower::service_fn};
+
+/// Scheme of server addresses which are Unix domain sockets, e.g. `unix:///tmp/orderbook.sock`.
+pub const UNIX_SCHEME: &str = "unix";
+
+/// Reaches the server in a way its address alone can't describe, e.g. through an HTTP proxy, a custom DNS resolver or a
+/// pooled connection.
+#[derive(Clone)]
+pub enum CustomTransport {
+    /// A channel built by the caller, e.g. with [Endpoint::connect_with_connector], which is used for every attempt
+    /// rather than connecting to the server address.
+    Channel(Channel),
+    /// Adjusts the endpoint built from the server address before each connection, e.g. to set keep-alives or TLS.
+    Endpoint(Arc<dyn Fn(Endpoint) -> Endpoint + Send + Sync>),
+}
+
+impl CustomTransport {
+    pub fn endpoint(customise: impl Fn(Endpoint) -> Endpoint + Send + Sync + 'static) -> Self {
+        Self::Endpoint(Arc::new(customise))
+    }
+}
+
+impl Debug for CustomTransport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CustomTransport::Channel(channel) => f.debug_tuple("Channel").field(channel).finish(),
+            CustomTransport::Endpoint(_) => f.write_str("Endpoint"),
+        }
+    }
+}
+
+/// Connect to the server at `server_address`, either `http://host:port` or `unix:///path/to/socket`.
+pub async fn connect(server_address: &Url) -> Result<Channel, Error> {
+    connect_with(server_address, None).await
+}
+
+/// As [connect], but through the `custom` transport when one is given.
+pub async fn connect_with(
+    server_address: &Url,
+    custom: Option<&CustomTransport>,
+) -> Result<Channel, Error> {
+    let endpoint = match custom {
+        Some(CustomTransport::Channel(channel)) => return Ok(channel.clone()),
+        Some(CustomTransport::Endpoint(customise)) => customise(endpoint(server_address)?),
+        None => endpoint(server_address)?,
+    };
+
+    let channel = match socket_path(server_address) {
+        #[cfg(unix)]
+        Some(path) => {
+            endpoint
+                .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect(path.clone())))
+                .await
+        }
+        _ => endpoint.connect().await,
+    };
+
+    channel.context("Error making initial connection to server")
+}
+
+/// As [connect], but the connection is only made when the channel is first used.
+pub fn connect_lazy(server_address: &Url) -> Result<Channel, Error> {
+    let endpoint = endpoint(server_address)?;
+
+    let channel = match socket_path(server_address) {
+        #[cfg(unix)]
+        Some(path) => endpoint.connect_with_connector_lazy(service_fn(move |_: Uri| {
+            UnixStream::connect(path.clone())
+        })),
+        _ => endpoint.connect_lazy(),
+    };
+
+    Ok(channel)
+}
+
+fn endpoint(server_address: &Url) -> Result<Endpoint, Error> {
+    // The URI of a Unix domain socket endpoint is unused by the connector but must still be valid HTTP
+    let uri = match socket_path(server_address) {
+        #[cfg(unix)]
+        Some(_) => "http://localhost".to_string(),
+        #[cfg(not(unix))]
+        Some(_) => return Err(Error::msg("Unix domain sockets are only supported on Unix")),
+        None => server_address.to_string(),
+    };
+
+    Endpoint::from_shared(uri).context("Invalid server address")
+}
+
+fn socket_path(server_address: &Url) -> Option<String> {
+    (server_address.scheme() == UNIX_SCHEME).then(|| server_address.path().to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio_stream::wrappers::UnixListenerStream;
+    use tonic::transport::{Endpoint, Server, Uri};
+    use tonic_health::{
+        proto::{health_client::HealthClient, HealthCheckRequest},
+        server::health_reporter,
+    };
+    use tower::service_fn;
+    use url::Url;
+
+    use super::{connect, connect_with, CustomTransport};
+
+    #[tokio::test]
+    async fn should_connect_over_unix_domain_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("orderbook.sock");
+
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let (_health_reporter, health_svc) = health_reporter();
+        tokio::spawn(
+            Server::builder()
+                .add_service(health_svc)
+                .serve_with_incoming(UnixListenerStream::new(listener)),
+        );
+
+        let server_address = Url::parse(&format!("unix://{}", socket_path.display())).unwrap();
+        let channel = connect(&server_address).await.unwrap();
+
+        let response = HealthClient::new(channel)
+            .check(HealthCheckRequest {
+                service: String::new(),
+            })
+            .await;
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_connect_through_a_custom_channel() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("orderbook.sock");
+
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let (_health_reporter, health_svc) = health_reporter();
+        tokio::spawn(
+            Server::builder()
+                .add_service(health_svc)
+                .serve_with_incoming(UnixListenerStream::new(listener)),
+        );
+
+        // The channel's own connector is used, so the address is never resolved
+        let channel = Endpoint::from_static("http://orderbook.invalid")
+            .connect_with_connector_lazy(service_fn(move |_: Uri| {
+                UnixStream::connect(socket_path.clone())
+            }));
+        let server_address = Url::parse("http://orderbook.invalid:3030").unwrap();
+        let channel = connect_with(&server_address, Some(&CustomTransport::Channel(channel)))
+            .await
+            .unwrap();
+
+        let response = HealthClient::new(channel)
+            .check(HealthCheckRequest {
+                service: String::new(),
+            })
+            .await;
+        assert!(response.is_ok());
+    }
+}
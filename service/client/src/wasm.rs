@@ -0,0 +1,43 @@
+//! Summary subscriptions for the browser, over grpc-web as browsers can't make HTTP/2 requests of their own.
+//!
+//! Build with `wasm-pack build service/client --target web -- --features wasm`, see the README.
+
+use js_sys::Function;
+use tonic::Status;
+use tonic_web_wasm_client::Client;
+use wasm_bindgen::prelude::*;
+
+use order_book_service_types::proto::{
+    orderbook_aggregator_client::OrderbookAggregatorClient, TradedPair,
+};
+
+/// Subscribe to the summaries of `first`-`second` from the grpc-web `server_address`, calling `on_summary` with each
+/// summary as a plain object until the service ends the stream.
+///
+/// Unlike [connect_to_summary_service](crate::connect_to_summary_service) there are no reconnect attempts, the returned
+/// promise rejects with the service's reason and the page decides whether to subscribe again.
+#[wasm_bindgen(js_name = subscribeToSummaries)]
+pub async fn subscribe_to_summaries(
+    server_address: String,
+    first: String,
+    second: String,
+    on_summary: Function,
+) -> Result<(), JsValue> {
+    let mut client = OrderbookAggregatorClient::new(Client::new(server_address));
+    let traded_pair = TradedPair { first, second }.canonical();
+
+    let mut summaries = client
+        .book_summary(traded_pair)
+        .await
+        .map_err(status_error)?
+        .into_inner();
+    while let Some(summary) = summaries.message().await.map_err(status_error)? {
+        let summary = serde_wasm_bindgen::to_value(&summary)?;
+        on_summary.call1(&JsValue::NULL, &summary)?;
+    }
+    Ok(())
+}
+
+fn status_error(status: Status) -> JsValue {
+    JsError::new(status.message()).into()
+}
@@ -1,4 +1,60 @@
+/// Messages of only scalar fields, which are cheap enough to copy.
+const COPY_MESSAGES: [&str; 4] = [
+    "orderbook.Empty",
+    "orderbook.Heartbeat",
+    "orderbook.ModifyCommand",
+    "orderbook.DepthBand",
+];
+
+/// Set to regenerate the checked-in code, e.g. after changing the schema.
+const REGENERATE: &str = "REGENERATE_PROTOS";
+
 fn main() {
-    tonic_build::compile_protos("protos/orderbook.proto")
+    // Generated into the source tree and checked in, so the types can be read and reviewed without building and
+    // building doesn't need protoc. CI regenerates it and fails when it differs from what's checked in.
+    println!("cargo:rerun-if-env-changed={REGENERATE}");
+    if std::env::var_os(REGENERATE).is_none() {
+        return;
+    }
+
+    // Comments in the proto become the generated types' doc comments
+    let mut builder = tonic_build::configure().out_dir("src/generated");
+    for message in COPY_MESSAGES {
+        builder = builder.type_attribute(message, "#[derive(Copy)]");
+    }
+
+    builder
+        // Allows summaries to be saved and loaded, e.g. to record a stream for a bug report
+        .type_attribute(
+            ".",
+            "#[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]",
+        )
+        // Recordings made before a field was added can still be loaded
+        .type_attribute(
+            "orderbook.Summary",
+            "#[cfg_attr(feature = \"serde\", serde(default))]",
+        )
+        .type_attribute(
+            "orderbook.Level",
+            "#[cfg_attr(feature = \"serde\", serde(default))]",
+        )
+        .type_attribute(
+            "orderbook.SummaryMetadata",
+            "#[cfg_attr(feature = \"serde\", serde(default))]",
+        )
+        .type_attribute(
+            "orderbook.SummaryIntegrity",
+            "#[cfg_attr(feature = \"serde\", serde(default))]",
+        )
+        // Summaries make up nearly all of a managed subscription's payloads, boxing them would only add allocations
+        .type_attribute(
+            "orderbook.TaggedSummary.payload",
+            "#[allow(clippy::large_enum_variant)]",
+        )
+        // Written by hand in lib.rs, as the transport they connect over doesn't build for wasm32
+        .build_transport(false)
+        // Served by the GetApiDescriptor RPC, with the proto's comments
+        .file_descriptor_set_path("src/generated/orderbook_descriptor.bin")
+        .compile(&["protos/orderbook.proto"], &["protos"])
         .unwrap_or_else(|err| panic!("Failed to compile protos {err}"));
 }
@@ -0,0 +1,124 @@
+use std::{mem::take, time::Duration};
+
+use tokio::{
+    select,
+    time::{interval_at, Instant},
+};
+use tokio_stream::{Stream, StreamExt};
+use tonic::Status;
+
+use crate::proto::{Summary, SummaryBatch};
+
+/// Collect a subscription's summaries into a batch for each `window`, sent even when empty, as BookSummaryBatched
+/// delivers them.
+///
+/// Heartbeats are dropped as the batches already show the stream is alive. The summaries received before the stream
+/// ends or fails are sent as a last batch, and batching stops once `send` returns false, e.g. as the receiver has gone.
+pub async fn batch_summaries(
+    mut summaries: impl Stream<Item = Result<Summary, Status>> + Unpin,
+    window: Duration,
+    mut send: impl AsyncFnMut(Result<SummaryBatch, Status>) -> bool,
+) {
+    let mut windows = interval_at(Instant::now() + window, window);
+    let mut batch = Vec::new();
+
+    loop {
+        select! {
+            received = summaries.next() => match received {
+                Some(Ok(summary)) if summary.is_heartbeat() => {}
+                Some(Ok(summary)) => batch.push(summary),
+                Some(Err(status)) => {
+                    if send(Ok(SummaryBatch::new(take(&mut batch)))).await {
+                        send(Err(status)).await;
+                    }
+                    return;
+                }
+                None => {
+                    if !batch.is_empty() {
+                        send(Ok(SummaryBatch::new(batch))).await;
+                    }
+                    return;
+                }
+            },
+            _ = windows.tick() => {
+                if !send(Ok(SummaryBatch::new(take(&mut batch)))).await {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::channel;
+    use tokio_stream::wrappers::ReceiverStream;
+    use tonic::{Code, Status};
+
+    use crate::proto::Summary;
+
+    use super::batch_summaries;
+
+    fn summary(spread: f64) -> Summary {
+        Summary {
+            spread,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_batch_summaries_by_window() {
+        let (summary_tx, summary_rx) = channel(10);
+        let (batch_tx, mut batch_rx) = channel(10);
+        tokio::spawn(batch_summaries(
+            ReceiverStream::new(summary_rx),
+            Duration::from_secs(1),
+            async move |batch| batch_tx.send(batch).await.is_ok(),
+        ));
+
+        summary_tx.send(Ok(summary(1.0))).await.unwrap();
+        summary_tx
+            .send(Ok(Summary::heartbeat(Duration::ZERO)))
+            .await
+            .unwrap();
+        summary_tx.send(Ok(summary(2.0))).await.unwrap();
+
+        let batch = batch_rx.recv().await.unwrap().unwrap();
+        let spreads: Vec<f64> = batch.summaries.iter().map(|s| s.spread).collect();
+        assert_eq!(spreads, vec![1.0, 2.0]);
+
+        // Quiet windows still produce a batch
+        let batch = batch_rx.recv().await.unwrap().unwrap();
+        assert!(batch.summaries.is_empty());
+
+        summary_tx
+            .send(Err(Status::unavailable("Stopped")))
+            .await
+            .unwrap();
+        assert!(batch_rx.recv().await.unwrap().unwrap().summaries.is_empty());
+        assert_eq!(
+            batch_rx.recv().await.unwrap().unwrap_err().code(),
+            Code::Unavailable
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_send_the_last_batch_when_the_stream_ends() {
+        let (summary_tx, summary_rx) = channel(10);
+        let (batch_tx, mut batch_rx) = channel(10);
+        tokio::spawn(batch_summaries(
+            ReceiverStream::new(summary_rx),
+            Duration::from_secs(60),
+            async move |batch| batch_tx.send(batch).await.is_ok(),
+        ));
+
+        summary_tx.send(Ok(summary(1.0))).await.unwrap();
+        drop(summary_tx);
+
+        let batch = batch_rx.recv().await.unwrap().unwrap();
+        assert_eq!(batch.summaries, vec![summary(1.0)]);
+        assert!(batch_rx.recv().await.is_none());
+    }
+}
@@ -0,0 +1,134 @@
+//! Checks summaries encoded by earlier versions still decode, against golden files in `golden/`.
+//!
+//! A golden file is never regenerated once committed, it stands for data already recorded or sent to deployed clients.
+//! When the schema gains fields add a new version alongside, written by running the tests with `UPDATE_GOLDEN` set.
+
+use std::{env, fs, path::PathBuf};
+
+use prost::Message;
+
+use crate::proto::{
+    Heartbeat, Level, QuoteConversion, SourceTimestamp, Summary, SummaryIntegrity, SummaryMetadata,
+    TradedPair,
+};
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(name)
+}
+
+/// The golden file's contents, writing `current` as the file first if `UPDATE_GOLDEN` is set and it doesn't exist.
+fn golden(name: &str, current: &[u8]) -> Vec<u8> {
+    let path = golden_path(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() && !path.exists() {
+        fs::write(&path, current).expect("Should write golden file");
+    }
+    fs::read(&path).unwrap_or_else(|err| panic!("Unable to read {}: {err}", path.display()))
+}
+
+/// A summary using every field as of the first golden version.
+fn summary_v1() -> Summary {
+    Summary {
+        spread: 0.000_12,
+        bids: vec![
+            Level::new("Binance", 0.071_23, 12.5),
+            Level::new("Kraken", 0.071_2, 3.0),
+        ],
+        asks: vec![Level {
+            contributing_exchanges: vec!["Binance".to_string(), "Bitstamp".to_string()],
+            ..Level::new("Bitstamp", 0.071_35, 0.25)
+        }],
+        exchanges_in_maintenance: vec!["Bitstamp".to_string()],
+        metadata: Some(SummaryMetadata {
+            quote_conversions: vec![QuoteConversion {
+                exchange: "Binance".to_string(),
+                source_pair: Some(TradedPair::new("ETH", "USDT")),
+                rate: 0.999_8,
+                rate_source: "fixed".to_string(),
+            }],
+            excluded_exchanges: vec!["Kraken".to_string()],
+            effective_prices: true,
+            source_timestamps: vec![SourceTimestamp {
+                exchange: "Binance".to_string(),
+                exchange_timestamp_micros: 1_700_000_000_000_000,
+                corrected_timestamp_micros: 1_700_000_000_001_500,
+            }],
+            stale_exchanges: vec!["Bitstamp".to_string()],
+            merge_strategy: 1,
+            // Fields added since version 1 are left unset
+            ..Default::default()
+        }),
+        heartbeat: Some(Heartbeat {
+            last_update_age_millis: 1_500,
+        }),
+        ask_notional: 0.017_837_5,
+        bid_notional: 1.104_975,
+        integrity: Some(SummaryIntegrity {
+            digest: vec![1, 2, 3, 4],
+            previous_digest: vec![5, 6, 7, 8],
+            sequence: 42,
+            // Fields added since version 1 are left unset
+            ..Default::default()
+        }),
+        // Fields added since version 1 are left unset
+        ..Default::default()
+    }
+}
+
+/// The first fields of a summary, as a client built before the rest were added would decode it.
+#[derive(Clone, PartialEq, Message)]
+struct SummaryBeforeMetadata {
+    #[prost(double, tag = "1")]
+    spread: f64,
+    #[prost(message, repeated, tag = "2")]
+    bids: Vec<Level>,
+    #[prost(message, repeated, tag = "3")]
+    asks: Vec<Level>,
+}
+
+#[test]
+fn should_decode_golden_protobuf_summaries() {
+    let golden = golden("summary_v1.pb", &summary_v1().encode_to_vec());
+
+    let decoded = Summary::decode(golden.as_slice()).expect("Golden summary should decode");
+    assert_eq!(decoded, summary_v1());
+    // Fields keep their numbers and types, so the same summary encodes identically
+    assert_eq!(summary_v1().encode_to_vec(), golden);
+}
+
+#[test]
+fn should_decode_summaries_with_fields_older_clients_do_not_know() {
+    let golden = golden("summary_v1.pb", &summary_v1().encode_to_vec());
+
+    let older = SummaryBeforeMetadata::decode(golden.as_slice())
+        .expect("Older clients should skip newer fields");
+    assert_eq!(older.spread, summary_v1().spread);
+    assert_eq!(older.asks, summary_v1().asks);
+
+    // A field added after this version, as sent by a newer server
+    let mut newer = golden;
+    prost::encoding::string::encode(1000, &"added later".to_string(), &mut newer);
+    assert_eq!(
+        Summary::decode(newer.as_slice()).expect("Unknown fields should be skipped"),
+        summary_v1()
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn should_load_golden_json_summaries() {
+    let current = serde_json::to_vec_pretty(&summary_v1()).expect("Should serialize");
+    let golden = golden("summary_v1.json", &current);
+
+    let loaded: Summary = serde_json::from_slice(&golden).expect("Golden summary should load");
+    assert_eq!(loaded, summary_v1());
+
+    // Recordings made before the metadata and notional fields were added
+    let before_metadata: Summary = serde_json::from_str(
+        r#"{"spread": 1.0, "bids": [{"exchange": "Binance", "price": 0.07, "amount": 2.0}], "asks": []}"#,
+    )
+    .expect("Missing fields should default");
+    assert_eq!(before_metadata.bids[0].amount, 2.0);
+    assert_eq!(before_metadata.metadata, None);
+}
@@ -0,0 +1,248 @@
+use std::{collections::HashMap, fmt::Write};
+
+use prost::Message;
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, FileDescriptorSet,
+};
+
+use crate::proto::{ApiDescriptor, FieldDescription, RpcDescription};
+
+/// The serialized descriptors of the API, generated from the proto with its comments.
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("generated/orderbook_descriptor.bin");
+
+// Field numbers within the descriptor messages, which make up the paths of the comments' locations
+const FILE_MESSAGE_TYPE: i32 = 4;
+const FILE_SERVICE: i32 = 6;
+const MESSAGE_FIELD: i32 = 2;
+const MESSAGE_NESTED_TYPE: i32 = 3;
+const SERVICE_METHOD: i32 = 2;
+
+/// Describe the API from its descriptors, listing every RPC and field with its comments.
+pub fn api_descriptor() -> ApiDescriptor {
+    let descriptor_set = FileDescriptorSet::decode(FILE_DESCRIPTOR_SET)
+        .expect("The generated file descriptor set should decode");
+    let mut rpcs = Vec::new();
+    let mut fields = Vec::new();
+
+    for file in &descriptor_set.file {
+        let comments = file
+            .source_code_info
+            .iter()
+            .flat_map(|info| &info.location)
+            .filter_map(|location| {
+                let comments = location.leading_comments.as_deref()?;
+                Some((location.path.clone(), tidy_comments(comments)))
+            })
+            .collect::<HashMap<_, _>>();
+        let comments_at = |path: &[i32]| comments.get(path).cloned().unwrap_or_default();
+
+        for (service_index, service) in file.service.iter().enumerate() {
+            let service_name = qualified(file.package(), service.name());
+            for (method_index, method) in service.method.iter().enumerate() {
+                rpcs.push(RpcDescription {
+                    service: service_name.clone(),
+                    name: method.name().to_string(),
+                    input_type: method.input_type().trim_start_matches('.').to_string(),
+                    output_type: method.output_type().trim_start_matches('.').to_string(),
+                    client_streaming: method.client_streaming(),
+                    server_streaming: method.server_streaming(),
+                    comments: comments_at(&[
+                        FILE_SERVICE,
+                        service_index as i32,
+                        SERVICE_METHOD,
+                        method_index as i32,
+                    ]),
+                });
+            }
+        }
+
+        for (message_index, message) in file.message_type.iter().enumerate() {
+            describe_fields(
+                message,
+                file.package(),
+                vec![FILE_MESSAGE_TYPE, message_index as i32],
+                &comments_at,
+                &mut fields,
+            );
+        }
+    }
+
+    ApiDescriptor {
+        file_descriptor_set: FILE_DESCRIPTOR_SET.to_vec(),
+        rpcs,
+        fields,
+    }
+}
+
+/// Add the fields of `message` and its nested messages, whose location is at `path`.
+fn describe_fields(
+    message: &DescriptorProto,
+    scope: &str,
+    path: Vec<i32>,
+    comments_at: &impl Fn(&[i32]) -> String,
+    fields: &mut Vec<FieldDescription>,
+) {
+    let message_name = qualified(scope, message.name());
+    for (field_index, field) in message.field.iter().enumerate() {
+        let mut field_path = path.clone();
+        field_path.extend([MESSAGE_FIELD, field_index as i32]);
+        let type_name = match field.r#type() {
+            Type::Message | Type::Enum => field.type_name().trim_start_matches('.').to_string(),
+            scalar => scalar
+                .as_str_name()
+                .trim_start_matches("TYPE_")
+                .to_lowercase(),
+        };
+        fields.push(FieldDescription {
+            message: message_name.clone(),
+            name: field.name().to_string(),
+            number: field.number() as u32,
+            type_name,
+            repeated: field.label() == Label::Repeated,
+            comments: comments_at(&field_path),
+        });
+    }
+
+    for (nested_index, nested) in message.nested_type.iter().enumerate() {
+        // Map fields are described as their generated entry messages
+        if nested
+            .options
+            .as_ref()
+            .is_some_and(|options| options.map_entry())
+        {
+            continue;
+        }
+        let mut nested_path = path.clone();
+        nested_path.extend([MESSAGE_NESTED_TYPE, nested_index as i32]);
+        describe_fields(nested, &message_name, nested_path, comments_at, fields);
+    }
+}
+
+fn qualified(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{scope}.{name}")
+    }
+}
+
+/// Comment lines keep the space following `//`, which isn't part of the comment.
+fn tidy_comments(comments: &str) -> String {
+    comments
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+impl ApiDescriptor {
+    /// A Markdown reference of the API's RPCs, grouped by service, and its messages' fields.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("# API Reference\n");
+
+        let mut service = None;
+        for rpc in &self.rpcs {
+            if service != Some(&rpc.service) {
+                let _ = write!(markdown, "\n## {}\n\n", rpc.service);
+                let _ = writeln!(markdown, "| RPC | Request | Response | Description |");
+                let _ = writeln!(markdown, "| --- | --- | --- | --- |");
+                service = Some(&rpc.service);
+            }
+            let _ = writeln!(
+                markdown,
+                "| {} | {} | {} | {} |",
+                rpc.name,
+                streamed(&rpc.input_type, rpc.client_streaming),
+                streamed(&rpc.output_type, rpc.server_streaming),
+                table_cell(&rpc.comments)
+            );
+        }
+
+        let mut message = None;
+        for field in &self.fields {
+            if message != Some(&field.message) {
+                let _ = write!(markdown, "\n### {}\n\n", field.message);
+                let _ = writeln!(markdown, "| Field | Number | Type | Description |");
+                let _ = writeln!(markdown, "| --- | --- | --- | --- |");
+                message = Some(&field.message);
+            }
+            let type_name = if field.repeated {
+                format!("repeated {}", field.type_name)
+            } else {
+                field.type_name.clone()
+            };
+            let _ = writeln!(
+                markdown,
+                "| {} | {} | {type_name} | {} |",
+                field.name,
+                field.number,
+                table_cell(&field.comments)
+            );
+        }
+
+        markdown
+    }
+}
+
+fn streamed(type_name: &str, streaming: bool) -> String {
+    if streaming {
+        format!("stream {type_name}")
+    } else {
+        type_name.to_string()
+    }
+}
+
+/// Comments can span several lines, which a table row can't.
+fn table_cell(comments: &str) -> String {
+    comments.replace('\n', " ").replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::api_descriptor;
+
+    #[test]
+    fn should_describe_rpcs_and_fields_with_their_comments() {
+        let descriptor = api_descriptor();
+
+        let book_summary = descriptor
+            .rpcs
+            .iter()
+            .find(|rpc| rpc.name == "BookSummaryBatched")
+            .unwrap();
+        assert_eq!(book_summary.service, "orderbook.OrderbookAggregator");
+        assert_eq!(book_summary.input_type, "orderbook.BatchedRequest");
+        assert!(book_summary.server_streaming);
+        assert!(!book_summary.client_streaming);
+        assert_eq!(
+            book_summary.comments,
+            "Every summary produced in each window, delivered together at the end of the window"
+        );
+        assert!(descriptor
+            .rpcs
+            .iter()
+            .any(|rpc| rpc.service == "orderbook.OrderbookAdmin"));
+
+        let exchanges = descriptor
+            .fields
+            .iter()
+            .find(|field| field.message == "orderbook.ServerInfo" && field.name == "exchanges")
+            .unwrap();
+        assert_eq!(exchanges.number, 3);
+        assert_eq!(exchanges.type_name, "string");
+        assert!(exchanges.repeated);
+        assert_eq!(
+            exchanges.comments,
+            "Exchanges summaries are aggregated from"
+        );
+
+        let markdown = descriptor.to_markdown();
+        assert!(markdown.contains(
+            "| BookSummaryBatched | orderbook.BatchedRequest | stream orderbook.SummaryBatch | Every summary"
+        ));
+        assert!(markdown.contains("### orderbook.ServerInfo"));
+    }
+}
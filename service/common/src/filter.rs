@@ -0,0 +1,254 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::proto::Summary;
+
+/// A value derived from a summary which filters can compare against.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Spread,
+    BestBid,
+    BestAsk,
+    MidPrice,
+    BestBidAmount,
+    BestAskAmount,
+    BestBidExchange,
+    BestAskExchange,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl Field {
+    /// `None` when the side the field is taken from is empty.
+    fn value(&self, summary: &Summary) -> Option<Value> {
+        let best_bid = summary.bids.first();
+        let best_ask = summary.asks.first();
+
+        let number = match self {
+            Field::Spread => Some(summary.spread),
+            Field::BestBid => best_bid.map(|level| level.price),
+            Field::BestAsk => best_ask.map(|level| level.price),
+            Field::MidPrice => Some((best_bid?.price + best_ask?.price) / 2.0),
+            Field::BestBidAmount => best_bid.map(|level| level.amount),
+            Field::BestAskAmount => best_ask.map(|level| level.amount),
+            Field::BestBidExchange => {
+                return best_bid.map(|level| Value::Text(level.exchange.clone()))
+            }
+            Field::BestAskExchange => {
+                return best_ask.map(|level| Value::Text(level.exchange.clone()))
+            }
+        };
+        number.map(Value::Number)
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self, Field::BestBidExchange | Field::BestAskExchange)
+    }
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(field: &str) -> Result<Self, Self::Err> {
+        match field {
+            "spread" => Ok(Field::Spread),
+            "best_bid" => Ok(Field::BestBid),
+            "best_ask" => Ok(Field::BestAsk),
+            "mid_price" => Ok(Field::MidPrice),
+            "best_bid_amount" => Ok(Field::BestBidAmount),
+            "best_ask_amount" => Ok(Field::BestAskAmount),
+            "best_bid_exchange" => Ok(Field::BestBidExchange),
+            "best_ask_exchange" => Ok(Field::BestAskExchange),
+            _ => Err(format!(
+                "Unknown field {field}, expected one of spread, best_bid, best_ask, mid_price, best_bid_amount, best_ask_amount, best_bid_exchange or best_ask_exchange"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    LessThan,
+    LessOrEqual,
+    GreaterThan,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Operator {
+    fn compare(&self, left: &Value, right: &Value) -> bool {
+        match (left, right) {
+            (Value::Number(left), Value::Number(right)) => match self {
+                Operator::LessThan => left < right,
+                Operator::LessOrEqual => left <= right,
+                Operator::GreaterThan => left > right,
+                Operator::GreaterOrEqual => left >= right,
+                Operator::Equal => left == right,
+                Operator::NotEqual => left != right,
+            },
+            (Value::Text(left), Value::Text(right)) => match self {
+                Operator::Equal => left == right,
+                Operator::NotEqual => left != right,
+                // Ordering of text is rejected when parsing
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Operator {
+    type Err = String;
+
+    fn from_str(operator: &str) -> Result<Self, Self::Err> {
+        match operator {
+            "<" => Ok(Operator::LessThan),
+            "<=" => Ok(Operator::LessOrEqual),
+            ">" => Ok(Operator::GreaterThan),
+            ">=" => Ok(Operator::GreaterOrEqual),
+            "==" => Ok(Operator::Equal),
+            "!=" => Ok(Operator::NotEqual),
+            _ => Err(format!("Unknown operator {operator}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Comparison {
+    field: Field,
+    operator: Operator,
+    threshold: Value,
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(comparison: &str) -> Result<Self, Self::Err> {
+        let tokens = comparison.split_whitespace().collect::<Vec<_>>();
+        let [field, operator, threshold] = tokens.as_slice() else {
+            return Err(format!(
+                "Expected `<field> <operator> <value>`, got `{comparison}`"
+            ));
+        };
+        let field = field.parse::<Field>()?;
+        let operator = operator.parse::<Operator>()?;
+
+        let threshold = if field.is_text() {
+            if !matches!(operator, Operator::Equal | Operator::NotEqual) {
+                return Err(format!("{comparison} can only use == or !="));
+            }
+            let quoted = |quote| threshold.strip_prefix(quote)?.strip_suffix(quote);
+            let text = quoted('\'')
+                .or_else(|| quoted('"'))
+                .ok_or_else(|| format!("Expected a quoted exchange, got {threshold}"))?;
+            Value::Text(text.to_string())
+        } else {
+            Value::Number(
+                threshold
+                    .parse()
+                    .map_err(|_| format!("Invalid value {threshold}"))?,
+            )
+        };
+
+        Ok(Self {
+            field,
+            operator,
+            threshold,
+        })
+    }
+}
+
+/// An expression such as `spread < 0` or `best_bid > 0.07 and best_bid_exchange == 'Binance'`, matching when every
+/// comparison holds.
+///
+/// Used by the server to filter a subscription's summaries and by the CLI's alert rules.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SummaryFilter {
+    expression: String,
+    comparisons: Vec<Comparison>,
+}
+
+impl SummaryFilter {
+    /// Whether the summary satisfies the filter, a comparison on an empty side never matches.
+    pub fn matches(&self, summary: &Summary) -> bool {
+        self.comparisons.iter().all(|comparison| {
+            comparison
+                .field
+                .value(summary)
+                .is_some_and(|value| comparison.operator.compare(&value, &comparison.threshold))
+        })
+    }
+}
+
+impl FromStr for SummaryFilter {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let comparisons = expression
+            .split(" and ")
+            .map(Comparison::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            comparisons,
+        })
+    }
+}
+
+impl Display for SummaryFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::{Level, Summary};
+
+    use super::SummaryFilter;
+
+    fn summary(spread: f64, best_bid: f64) -> Summary {
+        Summary {
+            spread,
+            bids: vec![Level::new("Binance", best_bid, 1.0)],
+            asks: vec![Level::new("Bitstamp", best_bid + spread, 1.0)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_parse_and_evaluate_filters() {
+        let filter: SummaryFilter = "best_bid > 0.07 and spread <= 0.001".parse().unwrap();
+
+        assert!(filter.matches(&summary(0.001, 0.071)));
+        assert!(!filter.matches(&summary(0.002, 0.071)));
+        assert!(!filter.matches(&Summary::default()));
+
+        assert!("spread <".parse::<SummaryFilter>().is_err());
+        assert!("depth > 1".parse::<SummaryFilter>().is_err());
+    }
+
+    #[test]
+    fn should_compare_exchanges() {
+        let filter: SummaryFilter =
+            "best_bid_exchange == 'Binance' and best_ask_exchange != \"Binance\""
+                .parse()
+                .unwrap();
+        assert!(filter.matches(&summary(0.001, 0.071)));
+
+        let filter: SummaryFilter = "best_ask_exchange == 'Binance'".parse().unwrap();
+        assert!(!filter.matches(&summary(0.001, 0.071)));
+
+        assert!("best_bid_exchange > 'Binance'"
+            .parse::<SummaryFilter>()
+            .is_err());
+        assert!("best_bid_exchange == Binance"
+            .parse::<SummaryFilter>()
+            .is_err());
+    }
+}
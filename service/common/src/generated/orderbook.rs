@@ -0,0 +1,2749 @@
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Request {
+    #[prost(message, optional, tag = "1")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    /// Levels of each side to include in summaries, defaults to 10 when 0
+    #[prost(uint32, tag = "2")]
+    pub depth: u32,
+    /// Adjust prices by each exchange's taker fee, the spread is computed from the adjusted levels
+    #[prost(bool, tag = "3")]
+    pub effective_prices: bool,
+    /// Only send summaries matching this expression, e.g. `spread > 0.0001 and best_bid_exchange == 'Binance'`,
+    /// every summary is sent when empty. Heartbeats are always sent
+    #[prost(string, tag = "4")]
+    pub filter: ::prost::alloc::string::String,
+    /// How levels are chosen for the summary from the merged book
+    #[prost(enumeration = "MergeStrategy", tag = "5")]
+    pub merge_strategy: i32,
+    /// Levels of each side reserved for every contributing exchange with VENUE_FAIR, defaults to 1 when 0
+    #[prost(uint32, tag = "6")]
+    pub min_levels_per_venue: u32,
+    /// The units level amounts are given in
+    #[prost(enumeration = "AmountDenomination", tag = "7")]
+    pub amount_denomination: i32,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BatchedRequest {
+    #[prost(message, optional, tag = "1")]
+    pub subscription: ::core::option::Option<Request>,
+    /// Length of each window, defaults to 5 seconds when 0 and can be at most 60
+    #[prost(uint32, tag = "2")]
+    pub window_secs: u32,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscriptionCommand {
+    /// Chosen by the client, unique among its open subscriptions on the stream
+    #[prost(string, tag = "1")]
+    pub subscription_id: ::prost::alloc::string::String,
+    #[prost(oneof = "subscription_command::Command", tags = "2, 3, 4")]
+    pub command: ::core::option::Option<subscription_command::Command>,
+}
+/// Nested message and enum types in `SubscriptionCommand`.
+pub mod subscription_command {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Command {
+        #[prost(message, tag = "2")]
+        Subscribe(super::SubscribeCommand),
+        #[prost(message, tag = "3")]
+        Unsubscribe(super::Empty),
+        #[prost(message, tag = "4")]
+        Modify(super::ModifyCommand),
+    }
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscribeCommand {
+    #[prost(message, optional, tag = "1")]
+    pub request: ::core::option::Option<Request>,
+    /// Minimum time between summaries, only the latest summary in each interval is sent, 0 sends every summary
+    #[prost(uint32, tag = "2")]
+    pub throttle_millis: u32,
+}
+/// Replaces the subscription's depth and throttle, a change of depth restarts its integrity chain
+#[derive(Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ModifyCommand {
+    #[prost(uint32, tag = "1")]
+    pub depth: u32,
+    #[prost(uint32, tag = "2")]
+    pub throttle_millis: u32,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaggedSummary {
+    #[prost(string, tag = "1")]
+    pub subscription_id: ::prost::alloc::string::String,
+    #[prost(oneof = "tagged_summary::Payload", tags = "2, 3")]
+    pub payload: ::core::option::Option<tagged_summary::Payload>,
+}
+/// Nested message and enum types in `TaggedSummary`.
+pub mod tagged_summary {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[allow(clippy::large_enum_variant)]
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Payload {
+        #[prost(message, tag = "2")]
+        Summary(super::Summary),
+        /// The command failed or the subscription ended, no more summaries will be sent for it
+        #[prost(message, tag = "3")]
+        Error(super::SubscriptionError),
+    }
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscriptionError {
+    /// A gRPC status code
+    #[prost(int32, tag = "1")]
+    pub code: i32,
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+    /// Why the subscription ended, when it did
+    #[prost(enumeration = "CloseReason", tag = "3")]
+    pub reason: i32,
+}
+/// Sent in the details of the status ending a stream, so that clients can tell whether to reconnect
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StreamClosed {
+    #[prost(enumeration = "CloseReason", tag = "1")]
+    pub reason: i32,
+    /// Another server to reconnect to, given when this one is draining before shutting down
+    #[prost(string, tag = "2")]
+    pub alternative_address: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SummaryBatch {
+    /// In the order they were produced, empty when there were no updates in the window
+    #[prost(message, repeated, tag = "1")]
+    pub summaries: ::prost::alloc::vec::Vec<Summary>,
+    /// When the window closed
+    #[prost(uint64, tag = "2")]
+    pub timestamp_millis: u64,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TradedPair {
+    #[prost(string, tag = "1")]
+    pub first: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub second: ::prost::alloc::string::String,
+}
+#[derive(Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Empty {}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Summary {
+    #[prost(double, tag = "1")]
+    pub spread: f64,
+    #[prost(message, repeated, tag = "2")]
+    pub bids: ::prost::alloc::vec::Vec<Level>,
+    #[prost(message, repeated, tag = "3")]
+    pub asks: ::prost::alloc::vec::Vec<Level>,
+    /// Contributing exchanges which are currently reporting maintenance
+    #[prost(string, repeated, tag = "4")]
+    pub exchanges_in_maintenance: ::prost::alloc::vec::Vec<
+        ::prost::alloc::string::String,
+    >,
+    #[prost(message, optional, tag = "5")]
+    pub metadata: ::core::option::Option<SummaryMetadata>,
+    /// Only set on heartbeats, which are sent when there have been no summaries for a while and carry no levels
+    #[prost(message, optional, tag = "6")]
+    pub heartbeat: ::core::option::Option<Heartbeat>,
+    /// Total value (price x amount) of the asks and of the bids in the summary, in units of the second token
+    #[prost(double, tag = "7")]
+    pub ask_notional: f64,
+    #[prost(double, tag = "8")]
+    pub bid_notional: f64,
+    /// Only set when the server is configured for integrity, chains each summary on a stream to the one before it
+    #[prost(message, optional, tag = "9")]
+    pub integrity: ::core::option::Option<SummaryIntegrity>,
+    /// Only set when the server is configured for degraded mode, the summary comes from a single exchange as the others
+    /// have disconnected, clients decide whether single venue data is acceptable
+    #[prost(bool, tag = "10")]
+    pub degraded: bool,
+    /// Exchanges which were aggregated for the pair but have since disconnected or delisted it
+    #[prost(string, repeated, tag = "11")]
+    pub missing_exchanges: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Levels actually returned on each side, fewer than requested when the merged book is thinner. A side may be empty,
+    /// in which case `spread` is 0
+    #[prost(uint32, tag = "12")]
+    pub ask_depth: u32,
+    #[prost(uint32, tag = "13")]
+    pub bid_depth: u32,
+    /// How far the summary's data can be trusted, from 0 to 1: the share of the pair's exchanges contributing, scaled down
+    /// as the oldest book ages past a second and as the exchanges' mid prices disagree, see `metadata` for the inputs
+    #[prost(double, tag = "14")]
+    pub quality_score: f64,
+    /// `quality_score` graded, GOOD from 0.8 and DEGRADED from 0.5
+    #[prost(enumeration = "Quality", tag = "15")]
+    pub quality: i32,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SummaryIntegrity {
+    /// SHA-256 of `previous_digest`, `sequence` as big-endian bytes, and the summary's fields as listed for `version`
+    #[prost(bytes = "vec", tag = "1")]
+    pub digest: ::prost::alloc::vec::Vec<u8>,
+    /// Empty for the first summary on a stream
+    #[prost(bytes = "vec", tag = "2")]
+    pub previous_digest: ::prost::alloc::vec::Vec<u8>,
+    /// Counts up from 1 for the first summary on a stream, heartbeats aren't counted
+    #[prost(uint64, tag = "3")]
+    pub sequence: u64,
+    /// Which fields `digest` covers and how they're written, so a summary gaining fields doesn't change its digest.
+    /// Version 1 covers `spread`, then each bid and each ask, `ask_notional`, `bid_notional`, `degraded`,
+    /// `exchanges_in_maintenance` and `missing_exchanges`, see order_book_service_types::integrity
+    #[prost(uint32, tag = "4")]
+    pub version: u32,
+}
+#[derive(Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Heartbeat {
+    /// Time since the last summary was sent on the stream, or since subscribing if there hasn't been one
+    #[prost(uint64, tag = "1")]
+    pub last_update_age_millis: u64,
+}
+/// Details of how a summary was produced
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SummaryMetadata {
+    /// Sources whose prices were converted from a different quote currency before merging
+    #[prost(message, repeated, tag = "1")]
+    pub quote_conversions: ::prost::alloc::vec::Vec<QuoteConversion>,
+    /// Sources left out of the summary because their mid price deviated from the other sources
+    #[prost(string, repeated, tag = "2")]
+    pub excluded_exchanges: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Prices have been adjusted by each exchange's taker fee
+    #[prost(bool, tag = "3")]
+    pub effective_prices: bool,
+    /// When each contributing exchange produced its book
+    #[prost(message, repeated, tag = "4")]
+    pub source_timestamps: ::prost::alloc::vec::Vec<SourceTimestamp>,
+    /// Sources left out of the summary because their book trailed the freshest by more than the timestamp tolerance
+    #[prost(string, repeated, tag = "5")]
+    pub stale_exchanges: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// How the levels were chosen, VENUE_FAIR summaries may leave out better levels to include other exchanges
+    #[prost(enumeration = "MergeStrategy", tag = "6")]
+    pub merge_strategy: i32,
+    /// The units the level amounts are given in, as requested
+    #[prost(enumeration = "AmountDenomination", tag = "7")]
+    pub amount_denomination: i32,
+    /// The pair's top bids or asks hold less than its configured minimum amount
+    #[prost(bool, tag = "8")]
+    pub low_bid_liquidity: bool,
+    #[prost(bool, tag = "9")]
+    pub low_ask_liquidity: bool,
+    /// Exchanges whose books were merged into the summary, after leaving out stale and excluded ones
+    #[prost(uint32, tag = "10")]
+    pub contributing_exchanges: u32,
+    /// Time taken on the server to merge the books and prepare the summary for publishing
+    #[prost(uint64, tag = "11")]
+    pub merge_duration_micros: u64,
+    /// Age of the oldest contributing book when it was merged, by its corrected timestamp
+    #[prost(uint64, tag = "12")]
+    pub max_source_age_micros: u64,
+    /// Largest relative distance of a contributing exchange's mid price from the median mid price, e.g. 0.01 is 1%
+    #[prost(double, tag = "13")]
+    pub mid_dispersion: f64,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SourceTimestamp {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    /// As stamped by the exchange on its own clock, unset for exchanges which don't timestamp their messages
+    #[prost(uint64, tag = "2")]
+    pub exchange_timestamp_micros: u64,
+    /// On the service's clock: the exchange's timestamp corrected by its estimated clock skew, or the receipt time when
+    /// the exchange doesn't timestamp its messages
+    #[prost(uint64, tag = "3")]
+    pub corrected_timestamp_micros: u64,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QuoteConversion {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    /// The pair that was streamed from the exchange, e.g. BTC-USDT
+    #[prost(message, optional, tag = "2")]
+    pub source_pair: ::core::option::Option<TradedPair>,
+    /// Multiplier applied to prices from the source pair
+    #[prost(double, tag = "3")]
+    pub rate: f64,
+    /// Where the rate came from, e.g. "fixed" or "Bitstamp USDT-USD mid"
+    #[prost(string, tag = "4")]
+    pub rate_source: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Level {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(double, tag = "2")]
+    pub price: f64,
+    #[prost(double, tag = "3")]
+    pub amount: f64,
+    /// OTHER when the exchange is only identified by its name in `exchange`
+    #[prost(enumeration = "KnownExchange", tag = "4")]
+    pub known_exchange: i32,
+    /// Only set when levels are consolidated, every exchange with liquidity at the level
+    #[prost(string, repeated, tag = "5")]
+    pub contributing_exchanges: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DepthSnapshotRequest {
+    #[prost(message, optional, tag = "1")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    /// Levels of each side to include, every known level when 0
+    #[prost(uint32, tag = "2")]
+    pub depth: u32,
+    /// Adjust prices by each exchange's taker fee, the spread is computed from the adjusted levels
+    #[prost(bool, tag = "3")]
+    pub effective_prices: bool,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SlippageRequest {
+    #[prost(message, optional, tag = "1")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    #[prost(enumeration = "Side", tag = "2")]
+    pub side: i32,
+    /// The order size, in units of the first token
+    #[prost(double, tag = "3")]
+    pub amount: f64,
+    /// Walk prices adjusted by each exchange's taker fee
+    #[prost(bool, tag = "4")]
+    pub effective_prices: bool,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SlippageEstimate {
+    /// Volume weighted average price across all fills
+    #[prost(double, tag = "1")]
+    pub average_price: f64,
+    /// Price of the last level that would be touched
+    #[prost(double, tag = "2")]
+    pub worst_price: f64,
+    /// Less than the requested amount when the known book is too thin to fill the order
+    #[prost(double, tag = "3")]
+    pub filled_amount: f64,
+    #[prost(message, repeated, tag = "4")]
+    pub fills: ::prost::alloc::vec::Vec<ExchangeFill>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExchangeFill {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(double, tag = "2")]
+    pub amount: f64,
+    #[prost(double, tag = "3")]
+    pub average_price: f64,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DepthHistogramRequest {
+    #[prost(message, optional, tag = "1")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    /// Width of each band as a fraction of the mid price, e.g. 0.001 is 0.1%, defaults to 0.001 when 0
+    #[prost(double, tag = "2")]
+    pub band_width: f64,
+    /// Bands each side of the mid price, defaults to 10 when 0 and can be at most 100
+    #[prost(uint32, tag = "3")]
+    pub band_count: u32,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DepthHistogram {
+    #[prost(double, tag = "1")]
+    pub mid_price: f64,
+    /// Nearest the mid price first, there are always `band_count` of each, empty bands have no quantity
+    #[prost(message, repeated, tag = "2")]
+    pub bids: ::prost::alloc::vec::Vec<DepthBand>,
+    #[prost(message, repeated, tag = "3")]
+    pub asks: ::prost::alloc::vec::Vec<DepthBand>,
+}
+#[derive(Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DepthBand {
+    /// The band covers prices from `lower_price` up to but excluding `upper_price`
+    #[prost(double, tag = "1")]
+    pub lower_price: f64,
+    #[prost(double, tag = "2")]
+    pub upper_price: f64,
+    /// Total quantity of the levels in the band, in units of the first token
+    #[prost(double, tag = "3")]
+    pub amount: f64,
+    /// Total value of the levels in the band, in units of the second token
+    #[prost(double, tag = "4")]
+    pub notional: f64,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ServerInfo {
+    /// Version of the server crate, e.g. "0.1.0"
+    #[prost(string, tag = "1")]
+    pub version: ::prost::alloc::string::String,
+    #[prost(enumeration = "Capability", repeated, tag = "2")]
+    pub capabilities: ::prost::alloc::vec::Vec<i32>,
+    /// Exchanges summaries are aggregated from
+    #[prost(string, repeated, tag = "3")]
+    pub exchanges: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ApiDescriptor {
+    /// A serialized google.protobuf.FileDescriptorSet of the API, including its source comments
+    #[prost(bytes = "vec", tag = "1")]
+    pub file_descriptor_set: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, repeated, tag = "2")]
+    pub rpcs: ::prost::alloc::vec::Vec<RpcDescription>,
+    /// Every field of every message
+    #[prost(message, repeated, tag = "3")]
+    pub fields: ::prost::alloc::vec::Vec<FieldDescription>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RpcDescription {
+    /// Fully qualified, e.g. "orderbook.OrderbookAggregator"
+    #[prost(string, tag = "1")]
+    pub service: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+    /// Fully qualified message names
+    #[prost(string, tag = "3")]
+    pub input_type: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub output_type: ::prost::alloc::string::String,
+    #[prost(bool, tag = "5")]
+    pub client_streaming: bool,
+    #[prost(bool, tag = "6")]
+    pub server_streaming: bool,
+    /// The comments above the RPC in the proto, empty when it has none
+    #[prost(string, tag = "7")]
+    pub comments: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FieldDescription {
+    /// Fully qualified, e.g. "orderbook.Summary"
+    #[prost(string, tag = "1")]
+    pub message: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "3")]
+    pub number: u32,
+    /// The scalar type, or the fully qualified message or enum name
+    #[prost(string, tag = "4")]
+    pub type_name: ::prost::alloc::string::String,
+    #[prost(bool, tag = "5")]
+    pub repeated: bool,
+    /// The comments above the field in the proto, empty when it has none
+    #[prost(string, tag = "6")]
+    pub comments: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SetFrameTapRequest {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub enabled: bool,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SetLogLevelRequest {
+    /// Comma separated `target=level` directives, a bare level applies to every other target. Empty restores the configured filter
+    #[prost(string, tag = "1")]
+    pub filter: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LogFilter {
+    #[prost(string, tag = "1")]
+    pub filter: ::prost::alloc::string::String,
+    /// The filter which was replaced
+    #[prost(string, tag = "2")]
+    pub previous_filter: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MetricsDump {
+    /// The ring file the snapshot was saved to
+    #[prost(string, tag = "1")]
+    pub path: ::prost::alloc::string::String,
+    /// Snapshots now held in the file, including this one
+    #[prost(uint32, tag = "2")]
+    pub snapshots: u32,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExchangeList {
+    #[prost(message, repeated, tag = "1")]
+    pub exchanges: ::prost::alloc::vec::Vec<ExchangeDetails>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExchangeDetails {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(enumeration = "KnownExchange", tag = "2")]
+    pub known_exchange: i32,
+    /// The venue's published fees as fractions, unset when not known. A configured taker fee takes precedence
+    #[prost(double, optional, tag = "3")]
+    pub maker_fee: ::core::option::Option<f64>,
+    #[prost(double, optional, tag = "4")]
+    pub taker_fee: ::core::option::Option<f64>,
+    /// The venue's published limits, 0 when not published
+    #[prost(uint32, tag = "5")]
+    pub max_connections_per_minute: u32,
+    #[prost(uint32, tag = "6")]
+    pub max_streams_per_connection: u32,
+    #[prost(uint64, tag = "7")]
+    pub min_message_interval_millis: u64,
+    /// Empty when the exchange isn't streamed over a websocket
+    #[prost(string, tag = "8")]
+    pub websocket_url: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DrainRequest {
+    /// How long to wait for subscriptions to close before shutting down, the configured deadline when 0
+    #[prost(uint32, tag = "1")]
+    pub deadline_secs: u32,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DrainResult {
+    /// Subscriptions open when the drain started, each was told to reconnect elsewhere
+    #[prost(uint32, tag = "1")]
+    pub notified: u32,
+    /// Subscriptions still open at the deadline, ended by the shutdown
+    #[prost(uint32, tag = "2")]
+    pub remaining: u32,
+    /// The server clients were pointed at, empty when none is configured
+    #[prost(string, tag = "3")]
+    pub alternative_address: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FrameTapStatus {
+    /// Exchanges which currently have their frames tapped
+    #[prost(string, repeated, tag = "1")]
+    pub enabled_exchanges: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExchangeLatencies {
+    /// Only exchanges which timestamp their messages are included
+    #[prost(message, repeated, tag = "1")]
+    pub exchanges: ::prost::alloc::vec::Vec<ExchangeLatency>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExchangeLatency {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub mean_micros: u64,
+    #[prost(uint64, tag = "3")]
+    pub max_micros: u64,
+    /// How many recent messages the stats are taken over
+    #[prost(uint32, tag = "4")]
+    pub samples: u32,
+    /// Smoothed offset of the service's clock from the exchange's, including the lowest network latency seen
+    #[prost(int64, tag = "5")]
+    pub skew_micros: i64,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Subscribers {
+    /// Oldest first
+    #[prost(message, repeated, tag = "1")]
+    pub subscribers: ::prost::alloc::vec::Vec<Subscriber>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Subscriber {
+    /// Unique for the life of the server
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(message, optional, tag = "2")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    #[prost(uint32, tag = "3")]
+    pub depth: u32,
+    /// From the `client-name` and `client-version` metadata of the subscribing request, empty when not sent
+    #[prost(string, tag = "4")]
+    pub client_name: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub client_version: ::prost::alloc::string::String,
+    /// Empty when tenancy is disabled
+    #[prost(string, tag = "6")]
+    pub tenant: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "7")]
+    pub open_for_millis: u64,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscriptionDescription {
+    #[prost(message, optional, tag = "1")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    /// Levels of each side included in summaries
+    #[prost(uint32, tag = "2")]
+    pub depth: u32,
+    #[prost(uint64, tag = "3")]
+    pub heartbeat_interval_millis: u64,
+    #[prost(message, repeated, tag = "4")]
+    pub sources: ::prost::alloc::vec::Vec<SubscriptionSource>,
+    /// Sources whose mid price deviates from the reference by more than this are excluded from summaries
+    #[prost(double, tag = "5")]
+    pub max_mid_deviation: f64,
+    /// Transforms applied to the merged book before summaries are built, in order
+    #[prost(string, repeated, tag = "6")]
+    pub transforms: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscriptionSource {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    /// The pair streamed from the exchange, which differs from the requested pair when quotes are converted
+    #[prost(message, optional, tag = "2")]
+    pub source_pair: ::core::option::Option<TradedPair>,
+    /// The exchange's own symbol for the source pair
+    #[prost(string, tag = "3")]
+    pub symbol: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ServiceEvent {
+    #[prost(uint64, tag = "1")]
+    pub timestamp_millis: u64,
+    #[prost(oneof = "service_event::Event", tags = "2, 3, 4, 5")]
+    pub event: ::core::option::Option<service_event::Event>,
+}
+/// Nested message and enum types in `ServiceEvent`.
+pub mod service_event {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "2")]
+        ConsistencyAlert(super::ConsistencyAlert),
+        #[prost(message, tag = "3")]
+        AggregatorStall(super::AggregatorStall),
+        #[prost(message, tag = "4")]
+        LiquidityAlert(super::LiquidityAlert),
+        #[prost(message, tag = "5")]
+        FeedQuarantine(super::FeedQuarantine),
+    }
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SupportedPairs {
+    /// Pairs offered by at least two exchanges, so can be aggregated
+    #[prost(message, repeated, tag = "1")]
+    pub pairs: ::prost::alloc::vec::Vec<TradedPair>,
+    #[prost(message, repeated, tag = "2")]
+    pub exchanges: ::prost::alloc::vec::Vec<ExchangePairs>,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExchangePairs {
+    #[prost(string, tag = "1")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub pairs: ::prost::alloc::vec::Vec<TradedPair>,
+    /// Set when the exchange couldn't be queried, `pairs` is then empty
+    #[prost(string, tag = "3")]
+    pub error: ::prost::alloc::string::String,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConnectorStatus {
+    #[prost(uint64, tag = "1")]
+    pub timestamp_millis: u64,
+    #[prost(string, tag = "2")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    #[prost(enumeration = "ConnectorEvent", tag = "4")]
+    pub event: i32,
+    /// Further detail, e.g. the reason for a disconnect
+    #[prost(string, tag = "5")]
+    pub detail: ::prost::alloc::string::String,
+}
+/// Raised when an exchange's mid price starts or stops deviating from the other exchanges for a pair
+/// Raised when an aggregator with subscribers hasn't merged a book for longer than its stall timeout,
+/// e.g. because its websockets are open but idle, after it has reconnected to its exchanges
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AggregatorStall {
+    #[prost(message, optional, tag = "1")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    #[prost(uint64, tag = "2")]
+    pub quiet_for_millis: u64,
+    /// False when too few exchanges could be reconnected to, the aggregator then stops
+    #[prost(bool, tag = "3")]
+    pub reconnected: bool,
+}
+/// Sent when a side of a pair's book falls below its configured minimum amount, and again once both sides are above it
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LiquidityAlert {
+    #[prost(message, optional, tag = "1")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    /// Levels of each side the amounts are totalled over
+    #[prost(uint32, tag = "2")]
+    pub levels: u32,
+    /// In units of the pair's first token
+    #[prost(double, tag = "3")]
+    pub bid_amount: f64,
+    #[prost(double, tag = "4")]
+    pub ask_amount: f64,
+    /// Whether each side is below its minimum, neither once the book has recovered
+    #[prost(bool, tag = "5")]
+    pub low_bids: bool,
+    #[prost(bool, tag = "6")]
+    pub low_asks: bool,
+}
+/// An exchange's feed of a pair left out of its merges for sending too many invalid books, e.g. crossed or unsorted
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FeedQuarantine {
+    #[prost(message, optional, tag = "1")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    #[prost(string, tag = "2")]
+    pub exchange: ::prost::alloc::string::String,
+    /// Invalid books received within the window, including the one which led to the quarantine
+    #[prost(uint32, tag = "3")]
+    pub invalid_books: u32,
+    /// Why the last book was invalid, e.g. "crossed"
+    #[prost(string, tag = "4")]
+    pub violation: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "5")]
+    pub quarantine_millis: u64,
+    /// Set once the quarantine has ended and the exchange's books are merged again
+    #[prost(bool, tag = "6")]
+    pub resolved: bool,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConsistencyAlert {
+    #[prost(message, optional, tag = "1")]
+    pub traded_pair: ::core::option::Option<TradedPair>,
+    #[prost(string, tag = "2")]
+    pub exchange: ::prost::alloc::string::String,
+    #[prost(double, tag = "3")]
+    pub mid_price: f64,
+    /// The mid price the exchange was compared against
+    #[prost(double, tag = "4")]
+    pub reference_mid_price: f64,
+    /// Relative difference between the two mid prices, e.g. 0.01 is 1%
+    #[prost(double, tag = "5")]
+    pub deviation: f64,
+    /// True once the exchange is back within the threshold and included in summaries again
+    #[prost(bool, tag = "6")]
+    pub resolved: bool,
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum AmountDenomination {
+    /// Units of the first token
+    Base = 0,
+    /// Units of the second token, each level's price x amount, for consumers reasoning in notional terms
+    Quote = 1,
+}
+impl AmountDenomination {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            AmountDenomination::Base => "BASE",
+            AmountDenomination::Quote => "QUOTE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "BASE" => Some(Self::Base),
+            "QUOTE" => Some(Self::Quote),
+            _ => None,
+        }
+    }
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MergeStrategy {
+    /// The best levels whatever their exchange
+    BestPrice = 0,
+    /// At least `min_levels_per_venue` levels from each contributing exchange where it has them, then the best of the
+    /// rest. Levels are still ordered best first and the spread is unchanged
+    VenueFair = 1,
+}
+impl MergeStrategy {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            MergeStrategy::BestPrice => "BEST_PRICE",
+            MergeStrategy::VenueFair => "VENUE_FAIR",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "BEST_PRICE" => Some(Self::BestPrice),
+            "VENUE_FAIR" => Some(Self::VenueFair),
+            _ => None,
+        }
+    }
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum CloseReason {
+    /// The stream ended for another reason, or the server didn't give one
+    Unspecified = 0,
+    /// The server is shutting down, another instance or a restart will serve the stream
+    ServerShutdown = 1,
+    /// The pair is no longer offered
+    PairRetired = 2,
+    /// The tenant is at its limit of subscriptions
+    QuotaExceeded = 3,
+    /// Too few exchanges are streaming the pair, it may recover
+    UpstreamUnavailable = 4,
+}
+impl CloseReason {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            CloseReason::Unspecified => "UNSPECIFIED",
+            CloseReason::ServerShutdown => "SERVER_SHUTDOWN",
+            CloseReason::PairRetired => "PAIR_RETIRED",
+            CloseReason::QuotaExceeded => "QUOTA_EXCEEDED",
+            CloseReason::UpstreamUnavailable => "UPSTREAM_UNAVAILABLE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "UNSPECIFIED" => Some(Self::Unspecified),
+            "SERVER_SHUTDOWN" => Some(Self::ServerShutdown),
+            "PAIR_RETIRED" => Some(Self::PairRetired),
+            "QUOTA_EXCEEDED" => Some(Self::QuotaExceeded),
+            "UPSTREAM_UNAVAILABLE" => Some(Self::UpstreamUnavailable),
+            _ => None,
+        }
+    }
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Quality {
+    /// Not assessed, as for relayed summaries and heartbeats
+    Unspecified = 0,
+    Good = 1,
+    Degraded = 2,
+    Poor = 3,
+}
+impl Quality {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Quality::Unspecified => "QUALITY_UNSPECIFIED",
+            Quality::Good => "QUALITY_GOOD",
+            Quality::Degraded => "QUALITY_DEGRADED",
+            Quality::Poor => "QUALITY_POOR",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "QUALITY_UNSPECIFIED" => Some(Self::Unspecified),
+            "QUALITY_GOOD" => Some(Self::Good),
+            "QUALITY_DEGRADED" => Some(Self::Degraded),
+            "QUALITY_POOR" => Some(Self::Poor),
+            _ => None,
+        }
+    }
+}
+/// Exchanges the service knows about
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum KnownExchange {
+    Other = 0,
+    Binance = 1,
+    Bitstamp = 2,
+}
+impl KnownExchange {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            KnownExchange::Other => "OTHER",
+            KnownExchange::Binance => "BINANCE",
+            KnownExchange::Bitstamp => "BITSTAMP",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "OTHER" => Some(Self::Other),
+            "BINANCE" => Some(Self::Binance),
+            "BITSTAMP" => Some(Self::Bitstamp),
+            _ => None,
+        }
+    }
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Side {
+    /// Buying walks the asks
+    Buy = 0,
+    /// Selling walks the bids
+    Sell = 1,
+}
+impl Side {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "BUY" => Some(Self::Buy),
+            "SELL" => Some(Self::Sell),
+            _ => None,
+        }
+    }
+}
+/// Optional features a server may support, clients should check for one before relying on it
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Capability {
+    Unspecified = 0,
+    /// Requests can set `depth`
+    Depth = 1,
+    /// Requests can set `effective_prices`
+    EffectivePrices = 2,
+    /// Quiet streams are kept alive with heartbeat summaries
+    Heartbeats = 3,
+    /// Levels are consolidated by price and list their `contributing_exchanges`
+    Consolidation = 4,
+    /// Summaries can be streamed as changes from the previous summary, not yet supported by any server
+    Deltas = 5,
+    /// The BookSummaryBatched RPC
+    BatchedSummaries = 6,
+    /// The GetDepthSnapshot RPC
+    DepthSnapshots = 7,
+    /// Summaries include `ask_notional` and `bid_notional`
+    Notional = 8,
+    /// Summaries are sealed into a hash chain under `integrity`
+    Integrity = 9,
+    /// The ManageSubscriptions RPC
+    ManagedSubscriptions = 10,
+    /// Requests can set `filter`
+    Filters = 11,
+    /// The GetDepthHistogram RPC
+    DepthHistograms = 12,
+    /// Requests can set `merge_strategy`
+    MergeStrategies = 13,
+    /// Requests can set `amount_denomination`
+    AmountDenominations = 14,
+    /// The GetApiDescriptor RPC
+    ApiDescriptors = 15,
+}
+impl Capability {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Capability::Unspecified => "CAPABILITY_UNSPECIFIED",
+            Capability::Depth => "DEPTH",
+            Capability::EffectivePrices => "EFFECTIVE_PRICES",
+            Capability::Heartbeats => "HEARTBEATS",
+            Capability::Consolidation => "CONSOLIDATION",
+            Capability::Deltas => "DELTAS",
+            Capability::BatchedSummaries => "BATCHED_SUMMARIES",
+            Capability::DepthSnapshots => "DEPTH_SNAPSHOTS",
+            Capability::Notional => "NOTIONAL",
+            Capability::Integrity => "INTEGRITY",
+            Capability::ManagedSubscriptions => "MANAGED_SUBSCRIPTIONS",
+            Capability::Filters => "FILTERS",
+            Capability::DepthHistograms => "DEPTH_HISTOGRAMS",
+            Capability::MergeStrategies => "MERGE_STRATEGIES",
+            Capability::AmountDenominations => "AMOUNT_DENOMINATIONS",
+            Capability::ApiDescriptors => "API_DESCRIPTORS",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CAPABILITY_UNSPECIFIED" => Some(Self::Unspecified),
+            "DEPTH" => Some(Self::Depth),
+            "EFFECTIVE_PRICES" => Some(Self::EffectivePrices),
+            "HEARTBEATS" => Some(Self::Heartbeats),
+            "CONSOLIDATION" => Some(Self::Consolidation),
+            "DELTAS" => Some(Self::Deltas),
+            "BATCHED_SUMMARIES" => Some(Self::BatchedSummaries),
+            "DEPTH_SNAPSHOTS" => Some(Self::DepthSnapshots),
+            "NOTIONAL" => Some(Self::Notional),
+            "INTEGRITY" => Some(Self::Integrity),
+            "MANAGED_SUBSCRIPTIONS" => Some(Self::ManagedSubscriptions),
+            "FILTERS" => Some(Self::Filters),
+            "DEPTH_HISTOGRAMS" => Some(Self::DepthHistograms),
+            "MERGE_STRATEGIES" => Some(Self::MergeStrategies),
+            "AMOUNT_DENOMINATIONS" => Some(Self::AmountDenominations),
+            "API_DESCRIPTORS" => Some(Self::ApiDescriptors),
+            _ => None,
+        }
+    }
+}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ConnectorEvent {
+    Connected = 0,
+    Disconnected = 1,
+    /// The stream was re-established, e.g. to change depth
+    Resynced = 2,
+    /// Nothing has been received for longer than the staleness threshold
+    Stale = 3,
+    /// Messages have resumed after the stream went stale
+    Recovered = 4,
+}
+impl ConnectorEvent {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ConnectorEvent::Connected => "CONNECTED",
+            ConnectorEvent::Disconnected => "DISCONNECTED",
+            ConnectorEvent::Resynced => "RESYNCED",
+            ConnectorEvent::Stale => "STALE",
+            ConnectorEvent::Recovered => "RECOVERED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CONNECTED" => Some(Self::Connected),
+            "DISCONNECTED" => Some(Self::Disconnected),
+            "RESYNCED" => Some(Self::Resynced),
+            "STALE" => Some(Self::Stale),
+            "RECOVERED" => Some(Self::Recovered),
+            _ => None,
+        }
+    }
+}
+/// Generated client implementations.
+pub mod orderbook_aggregator_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    #[derive(Debug, Clone)]
+    pub struct OrderbookAggregatorClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl<T> OrderbookAggregatorClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> OrderbookAggregatorClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
+        {
+            OrderbookAggregatorClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        pub async fn book_summary(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Request>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::Summary>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/BookSummary",
+            );
+            self.inner.server_streaming(request.into_request(), path, codec).await
+        }
+        /// Every summary produced in each window, delivered together at the end of the window
+        pub async fn book_summary_batched(
+            &mut self,
+            request: impl tonic::IntoRequest<super::BatchedRequest>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::SummaryBatch>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/BookSummaryBatched",
+            );
+            self.inner.server_streaming(request.into_request(), path, codec).await
+        }
+        pub async fn estimate_slippage(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SlippageRequest>,
+        ) -> Result<tonic::Response<super::SlippageEstimate>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/EstimateSlippage",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Alerts about the health of the service and its data, e.g. a feed deviating from the others
+        pub async fn service_events(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::ServiceEvent>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/ServiceEvents",
+            );
+            self.inner.server_streaming(request.into_request(), path, codec).await
+        }
+        /// The effective parameters applied to a subscription for a pair
+        pub async fn describe_subscription(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Request>,
+        ) -> Result<tonic::Response<super::SubscriptionDescription>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/DescribeSubscription",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Connection events from every exchange connector, e.g. disconnects and streams going quiet
+        pub async fn watch_exchange_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::ConnectorStatus>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/WatchExchangeStatus",
+            );
+            self.inner.server_streaming(request.into_request(), path, codec).await
+        }
+        /// The pairs each exchange offers and which of them can be subscribed to
+        pub async fn list_supported_pairs(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::SupportedPairs>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/ListSupportedPairs",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// The latest merged book for a pair, without subscribing to a stream
+        pub async fn get_depth_snapshot(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DepthSnapshotRequest>,
+        ) -> Result<tonic::Response<super::Summary>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/GetDepthSnapshot",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Quantity in price bands either side of the mid price, for heatmaps without shipping every level
+        pub async fn get_depth_histogram(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DepthHistogramRequest>,
+        ) -> Result<tonic::Response<super::DepthHistogram>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/GetDepthHistogram",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// The server's version and optional features, so clients can adapt to older servers
+        pub async fn get_server_info(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::ServerInfo>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/GetServerInfo",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// The protobuf descriptors of the API served, with the comments on its RPCs and fields, so integrators can discover
+        /// the API from a running server
+        pub async fn get_api_descriptor(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::ApiDescriptor>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/GetApiDescriptor",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Subscribe to, modify and unsubscribe from pairs on the fly over a single stream, summaries are tagged with the
+        /// subscription they belong to
+        pub async fn manage_subscriptions(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<
+                Message = super::SubscriptionCommand,
+            >,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::TaggedSummary>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAggregator/ManageSubscriptions",
+            );
+            self.inner.streaming(request.into_streaming_request(), path, codec).await
+        }
+    }
+}
+/// Generated client implementations.
+pub mod orderbook_admin_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// Operational endpoints for debugging and managing the service
+    #[derive(Debug, Clone)]
+    pub struct OrderbookAdminClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl<T> OrderbookAdminClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> OrderbookAdminClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
+        {
+            OrderbookAdminClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Enable or disable teeing raw websocket frames from an exchange to file
+        pub async fn set_frame_tap(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetFrameTapRequest>,
+        ) -> Result<tonic::Response<super::FrameTapStatus>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAdmin/SetFrameTap",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Rolling latency of each exchange's messages, from the exchange's timestamp to receipt
+        pub async fn get_exchange_latencies(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::ExchangeLatencies>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAdmin/GetExchangeLatencies",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Every open summary subscription and the client which opened it, to find which application is responsible for load
+        pub async fn list_subscribers(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::Subscribers>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAdmin/ListSubscribers",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Replace the log filter without restarting, e.g. `info,order_book_service_server::exchanges::binance=debug`
+        pub async fn set_log_level(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetLogLevelRequest>,
+        ) -> Result<tonic::Response<super::LogFilter>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAdmin/SetLogLevel",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Save a snapshot of the key metrics to the metrics snapshot file now, rather than waiting for the next interval
+        pub async fn dump_metrics(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::MetricsDump>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAdmin/DumpMetrics",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Stop accepting subscriptions and end the open ones, pointing their clients at the configured alternative server,
+        /// then shut down once they've closed or the deadline passes. Responds just before the server shuts down
+        pub async fn drain(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DrainRequest>,
+        ) -> Result<tonic::Response<super::DrainResult>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAdmin/Drain",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Each aggregated exchange along with its published fees, connection limits and websocket endpoint
+        pub async fn list_exchanges(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::ExchangeList>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/orderbook.OrderbookAdmin/ListExchanges",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod orderbook_aggregator_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with OrderbookAggregatorServer.
+    #[async_trait]
+    pub trait OrderbookAggregator: Send + Sync + 'static {
+        /// Server streaming response type for the BookSummary method.
+        type BookSummaryStream: futures_core::Stream<
+                Item = Result<super::Summary, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn book_summary(
+            &self,
+            request: tonic::Request<super::Request>,
+        ) -> Result<tonic::Response<Self::BookSummaryStream>, tonic::Status>;
+        /// Server streaming response type for the BookSummaryBatched method.
+        type BookSummaryBatchedStream: futures_core::Stream<
+                Item = Result<super::SummaryBatch, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// Every summary produced in each window, delivered together at the end of the window
+        async fn book_summary_batched(
+            &self,
+            request: tonic::Request<super::BatchedRequest>,
+        ) -> Result<tonic::Response<Self::BookSummaryBatchedStream>, tonic::Status>;
+        async fn estimate_slippage(
+            &self,
+            request: tonic::Request<super::SlippageRequest>,
+        ) -> Result<tonic::Response<super::SlippageEstimate>, tonic::Status>;
+        /// Server streaming response type for the ServiceEvents method.
+        type ServiceEventsStream: futures_core::Stream<
+                Item = Result<super::ServiceEvent, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// Alerts about the health of the service and its data, e.g. a feed deviating from the others
+        async fn service_events(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<Self::ServiceEventsStream>, tonic::Status>;
+        /// The effective parameters applied to a subscription for a pair
+        async fn describe_subscription(
+            &self,
+            request: tonic::Request<super::Request>,
+        ) -> Result<tonic::Response<super::SubscriptionDescription>, tonic::Status>;
+        /// Server streaming response type for the WatchExchangeStatus method.
+        type WatchExchangeStatusStream: futures_core::Stream<
+                Item = Result<super::ConnectorStatus, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// Connection events from every exchange connector, e.g. disconnects and streams going quiet
+        async fn watch_exchange_status(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<Self::WatchExchangeStatusStream>, tonic::Status>;
+        /// The pairs each exchange offers and which of them can be subscribed to
+        async fn list_supported_pairs(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::SupportedPairs>, tonic::Status>;
+        /// The latest merged book for a pair, without subscribing to a stream
+        async fn get_depth_snapshot(
+            &self,
+            request: tonic::Request<super::DepthSnapshotRequest>,
+        ) -> Result<tonic::Response<super::Summary>, tonic::Status>;
+        /// Quantity in price bands either side of the mid price, for heatmaps without shipping every level
+        async fn get_depth_histogram(
+            &self,
+            request: tonic::Request<super::DepthHistogramRequest>,
+        ) -> Result<tonic::Response<super::DepthHistogram>, tonic::Status>;
+        /// The server's version and optional features, so clients can adapt to older servers
+        async fn get_server_info(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::ServerInfo>, tonic::Status>;
+        /// The protobuf descriptors of the API served, with the comments on its RPCs and fields, so integrators can discover
+        /// the API from a running server
+        async fn get_api_descriptor(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::ApiDescriptor>, tonic::Status>;
+        /// Server streaming response type for the ManageSubscriptions method.
+        type ManageSubscriptionsStream: futures_core::Stream<
+                Item = Result<super::TaggedSummary, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// Subscribe to, modify and unsubscribe from pairs on the fly over a single stream, summaries are tagged with the
+        /// subscription they belong to
+        async fn manage_subscriptions(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::SubscriptionCommand>>,
+        ) -> Result<tonic::Response<Self::ManageSubscriptionsStream>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct OrderbookAggregatorServer<T: OrderbookAggregator> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: OrderbookAggregator> OrderbookAggregatorServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for OrderbookAggregatorServer<T>
+    where
+        T: OrderbookAggregator,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/orderbook.OrderbookAggregator/BookSummary" => {
+                    #[allow(non_camel_case_types)]
+                    struct BookSummarySvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::Request>
+                    for BookSummarySvc<T> {
+                        type Response = super::Summary;
+                        type ResponseStream = T::BookSummaryStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Request>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).book_summary(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = BookSummarySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/BookSummaryBatched" => {
+                    #[allow(non_camel_case_types)]
+                    struct BookSummaryBatchedSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::BatchedRequest>
+                    for BookSummaryBatchedSvc<T> {
+                        type Response = super::SummaryBatch;
+                        type ResponseStream = T::BookSummaryBatchedStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::BatchedRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).book_summary_batched(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = BookSummaryBatchedSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/EstimateSlippage" => {
+                    #[allow(non_camel_case_types)]
+                    struct EstimateSlippageSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::SlippageRequest>
+                    for EstimateSlippageSvc<T> {
+                        type Response = super::SlippageEstimate;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SlippageRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).estimate_slippage(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = EstimateSlippageSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/ServiceEvents" => {
+                    #[allow(non_camel_case_types)]
+                    struct ServiceEventsSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::Empty>
+                    for ServiceEventsSvc<T> {
+                        type Response = super::ServiceEvent;
+                        type ResponseStream = T::ServiceEventsStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).service_events(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ServiceEventsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/DescribeSubscription" => {
+                    #[allow(non_camel_case_types)]
+                    struct DescribeSubscriptionSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::Request>
+                    for DescribeSubscriptionSvc<T> {
+                        type Response = super::SubscriptionDescription;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Request>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).describe_subscription(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DescribeSubscriptionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/WatchExchangeStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct WatchExchangeStatusSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::ServerStreamingService<super::Empty>
+                    for WatchExchangeStatusSvc<T> {
+                        type Response = super::ConnectorStatus;
+                        type ResponseStream = T::WatchExchangeStatusStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).watch_exchange_status(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = WatchExchangeStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/ListSupportedPairs" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListSupportedPairsSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::Empty>
+                    for ListSupportedPairsSvc<T> {
+                        type Response = super::SupportedPairs;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).list_supported_pairs(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListSupportedPairsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/GetDepthSnapshot" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetDepthSnapshotSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::DepthSnapshotRequest>
+                    for GetDepthSnapshotSvc<T> {
+                        type Response = super::Summary;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DepthSnapshotRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).get_depth_snapshot(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetDepthSnapshotSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/GetDepthHistogram" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetDepthHistogramSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::DepthHistogramRequest>
+                    for GetDepthHistogramSvc<T> {
+                        type Response = super::DepthHistogram;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DepthHistogramRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).get_depth_histogram(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetDepthHistogramSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/GetServerInfo" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetServerInfoSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::Empty> for GetServerInfoSvc<T> {
+                        type Response = super::ServerInfo;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).get_server_info(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetServerInfoSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/GetApiDescriptor" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetApiDescriptorSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::UnaryService<super::Empty>
+                    for GetApiDescriptorSvc<T> {
+                        type Response = super::ApiDescriptor;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).get_api_descriptor(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetApiDescriptorSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAggregator/ManageSubscriptions" => {
+                    #[allow(non_camel_case_types)]
+                    struct ManageSubscriptionsSvc<T: OrderbookAggregator>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAggregator,
+                    > tonic::server::StreamingService<super::SubscriptionCommand>
+                    for ManageSubscriptionsSvc<T> {
+                        type Response = super::TaggedSummary;
+                        type ResponseStream = T::ManageSubscriptionsStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                tonic::Streaming<super::SubscriptionCommand>,
+                            >,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).manage_subscriptions(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ManageSubscriptionsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        Ok(
+                            http::Response::builder()
+                                .status(200)
+                                .header("grpc-status", "12")
+                                .header("content-type", "application/grpc")
+                                .body(empty_body())
+                                .unwrap(),
+                        )
+                    })
+                }
+            }
+        }
+    }
+    impl<T: OrderbookAggregator> Clone for OrderbookAggregatorServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+            }
+        }
+    }
+    impl<T: OrderbookAggregator> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: OrderbookAggregator> tonic::server::NamedService
+    for OrderbookAggregatorServer<T> {
+        const NAME: &'static str = "orderbook.OrderbookAggregator";
+    }
+}
+/// Generated server implementations.
+pub mod orderbook_admin_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with OrderbookAdminServer.
+    #[async_trait]
+    pub trait OrderbookAdmin: Send + Sync + 'static {
+        /// Enable or disable teeing raw websocket frames from an exchange to file
+        async fn set_frame_tap(
+            &self,
+            request: tonic::Request<super::SetFrameTapRequest>,
+        ) -> Result<tonic::Response<super::FrameTapStatus>, tonic::Status>;
+        /// Rolling latency of each exchange's messages, from the exchange's timestamp to receipt
+        async fn get_exchange_latencies(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::ExchangeLatencies>, tonic::Status>;
+        /// Every open summary subscription and the client which opened it, to find which application is responsible for load
+        async fn list_subscribers(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::Subscribers>, tonic::Status>;
+        /// Replace the log filter without restarting, e.g. `info,order_book_service_server::exchanges::binance=debug`
+        async fn set_log_level(
+            &self,
+            request: tonic::Request<super::SetLogLevelRequest>,
+        ) -> Result<tonic::Response<super::LogFilter>, tonic::Status>;
+        /// Save a snapshot of the key metrics to the metrics snapshot file now, rather than waiting for the next interval
+        async fn dump_metrics(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::MetricsDump>, tonic::Status>;
+        /// Stop accepting subscriptions and end the open ones, pointing their clients at the configured alternative server,
+        /// then shut down once they've closed or the deadline passes. Responds just before the server shuts down
+        async fn drain(
+            &self,
+            request: tonic::Request<super::DrainRequest>,
+        ) -> Result<tonic::Response<super::DrainResult>, tonic::Status>;
+        /// Each aggregated exchange along with its published fees, connection limits and websocket endpoint
+        async fn list_exchanges(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::ExchangeList>, tonic::Status>;
+    }
+    /// Operational endpoints for debugging and managing the service
+    #[derive(Debug)]
+    pub struct OrderbookAdminServer<T: OrderbookAdmin> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: OrderbookAdmin> OrderbookAdminServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for OrderbookAdminServer<T>
+    where
+        T: OrderbookAdmin,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/orderbook.OrderbookAdmin/SetFrameTap" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetFrameTapSvc<T: OrderbookAdmin>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAdmin,
+                    > tonic::server::UnaryService<super::SetFrameTapRequest>
+                    for SetFrameTapSvc<T> {
+                        type Response = super::FrameTapStatus;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetFrameTapRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).set_frame_tap(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = SetFrameTapSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAdmin/GetExchangeLatencies" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetExchangeLatenciesSvc<T: OrderbookAdmin>(pub Arc<T>);
+                    impl<T: OrderbookAdmin> tonic::server::UnaryService<super::Empty>
+                    for GetExchangeLatenciesSvc<T> {
+                        type Response = super::ExchangeLatencies;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).get_exchange_latencies(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetExchangeLatenciesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAdmin/ListSubscribers" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListSubscribersSvc<T: OrderbookAdmin>(pub Arc<T>);
+                    impl<T: OrderbookAdmin> tonic::server::UnaryService<super::Empty>
+                    for ListSubscribersSvc<T> {
+                        type Response = super::Subscribers;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).list_subscribers(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListSubscribersSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAdmin/SetLogLevel" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetLogLevelSvc<T: OrderbookAdmin>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAdmin,
+                    > tonic::server::UnaryService<super::SetLogLevelRequest>
+                    for SetLogLevelSvc<T> {
+                        type Response = super::LogFilter;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetLogLevelRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).set_log_level(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = SetLogLevelSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAdmin/DumpMetrics" => {
+                    #[allow(non_camel_case_types)]
+                    struct DumpMetricsSvc<T: OrderbookAdmin>(pub Arc<T>);
+                    impl<T: OrderbookAdmin> tonic::server::UnaryService<super::Empty>
+                    for DumpMetricsSvc<T> {
+                        type Response = super::MetricsDump;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).dump_metrics(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DumpMetricsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAdmin/Drain" => {
+                    #[allow(non_camel_case_types)]
+                    struct DrainSvc<T: OrderbookAdmin>(pub Arc<T>);
+                    impl<
+                        T: OrderbookAdmin,
+                    > tonic::server::UnaryService<super::DrainRequest> for DrainSvc<T> {
+                        type Response = super::DrainResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DrainRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).drain(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DrainSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/orderbook.OrderbookAdmin/ListExchanges" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListExchangesSvc<T: OrderbookAdmin>(pub Arc<T>);
+                    impl<T: OrderbookAdmin> tonic::server::UnaryService<super::Empty>
+                    for ListExchangesSvc<T> {
+                        type Response = super::ExchangeList;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).list_exchanges(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListExchangesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        Ok(
+                            http::Response::builder()
+                                .status(200)
+                                .header("grpc-status", "12")
+                                .header("content-type", "application/grpc")
+                                .body(empty_body())
+                                .unwrap(),
+                        )
+                    })
+                }
+            }
+        }
+    }
+    impl<T: OrderbookAdmin> Clone for OrderbookAdminServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+            }
+        }
+    }
+    impl<T: OrderbookAdmin> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: OrderbookAdmin> tonic::server::NamedService for OrderbookAdminServer<T> {
+        const NAME: &'static str = "orderbook.OrderbookAdmin";
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    mem::take,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::proto::{Level, Summary, SummaryIntegrity};
+
+/// The [SummaryIntegrity] version written by [SummaryChain::seal], see [digest] for the fields it covers.
+pub const DIGEST_VERSION: u32 = 1;
+
+/// A hash chain through a stream of summaries.
+///
+/// Each sealed summary carries the digest of its contents, its sequence number and the previous summary's digest,
+/// so a consumer can tell if a summary was altered in transit or if any were missed. Heartbeats aren't sealed.
+#[derive(Clone, Debug, Default)]
+pub struct SummaryChain {
+    previous_digest: Vec<u8>,
+    sequence: u64,
+}
+
+/// Why a summary doesn't follow on from the previous one in its chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChainError {
+    /// The summary wasn't sealed
+    Unsealed,
+    /// The summary's contents don't match its digest
+    Tampered { sequence: u64 },
+    /// Summaries were missed between the last one verified and this one
+    Gap { expected: u64, received: u64 },
+    /// The summary was sealed with a digest version this chain can't check
+    UnsupportedVersion { version: u32 },
+}
+
+impl SummaryChain {
+    /// Set the summary's integrity, extending the chain with it.
+    pub fn seal(&mut self, summary: &mut Summary) {
+        self.sequence += 1;
+        let digest = digest(summary, &self.previous_digest, self.sequence);
+
+        summary.integrity = Some(SummaryIntegrity {
+            digest: digest.clone(),
+            previous_digest: take(&mut self.previous_digest),
+            sequence: self.sequence,
+            version: DIGEST_VERSION,
+        });
+        self.previous_digest = digest;
+    }
+
+    /// Check a summary from a sealed stream, the chain continues from it even after a [ChainError::Gap].
+    pub fn verify(&mut self, summary: &Summary) -> Result<(), ChainError> {
+        let Some(integrity) = &summary.integrity else {
+            return Err(ChainError::Unsealed);
+        };
+
+        if integrity.version != DIGEST_VERSION {
+            return Err(ChainError::UnsupportedVersion {
+                version: integrity.version,
+            });
+        }
+        if digest(summary, &integrity.previous_digest, integrity.sequence) != integrity.digest {
+            return Err(ChainError::Tampered {
+                sequence: integrity.sequence,
+            });
+        }
+
+        // The first summary verified can join the chain part way through
+        let expected = self.sequence + 1;
+        let follows_on = self.sequence == 0 || integrity.previous_digest == self.previous_digest;
+        self.previous_digest = integrity.digest.clone();
+        self.sequence = integrity.sequence;
+
+        if follows_on {
+            Ok(())
+        } else {
+            Err(ChainError::Gap {
+                expected,
+                received: integrity.sequence,
+            })
+        }
+    }
+}
+
+/// Hash an explicit set of the summary's fields rather than its encoding, which would change as fields are added to
+/// [Summary] and so fail to verify across versions. Numbers are written big-endian, and strings and lists are
+/// prefixed with their length. Changing the fields means bumping [DIGEST_VERSION].
+fn digest(summary: &Summary, previous_digest: &[u8], sequence: u64) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(previous_digest);
+    hasher.update(sequence.to_be_bytes());
+
+    hasher.update(summary.spread.to_be_bytes());
+    for levels in [&summary.bids, &summary.asks] {
+        hasher.update((levels.len() as u64).to_be_bytes());
+        levels
+            .iter()
+            .for_each(|level| update_level(&mut hasher, level));
+    }
+    hasher.update(summary.ask_notional.to_be_bytes());
+    hasher.update(summary.bid_notional.to_be_bytes());
+    hasher.update([summary.degraded as u8]);
+    for exchanges in [
+        &summary.exchanges_in_maintenance,
+        &summary.missing_exchanges,
+    ] {
+        hasher.update((exchanges.len() as u64).to_be_bytes());
+        exchanges
+            .iter()
+            .for_each(|exchange| update_str(&mut hasher, exchange));
+    }
+
+    hasher.finalize().to_vec()
+}
+
+fn update_level(hasher: &mut Sha256, level: &Level) {
+    update_str(hasher, &level.exchange);
+    hasher.update(level.price.to_be_bytes());
+    hasher.update(level.amount.to_be_bytes());
+}
+
+fn update_str(hasher: &mut Sha256, value: &str) {
+    hasher.update((value.len() as u64).to_be_bytes());
+    hasher.update(value.as_bytes());
+}
+
+impl Display for ChainError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChainError::Unsealed => write!(f, "Summary isn't sealed"),
+            ChainError::Tampered { sequence } => {
+                write!(f, "Summary {sequence} doesn't match its digest")
+            }
+            ChainError::Gap { expected, received } => {
+                write!(f, "Expected summary {expected} but received {received}")
+            }
+            ChainError::UnsupportedVersion { version } => {
+                write!(
+                    f,
+                    "Summary is sealed with unsupported digest version {version}"
+                )
+            }
+        }
+    }
+}
+
+impl Error for ChainError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::{Level, Summary};
+
+    use super::{ChainError, SummaryChain};
+
+    fn summaries(count: usize) -> Vec<Summary> {
+        let mut chain = SummaryChain::default();
+        (0..count)
+            .map(|index| {
+                let mut summary = Summary {
+                    asks: vec![Level::new("Binance", 2.0 + index as f64, 1.0)],
+                    bids: vec![Level::new("Bitstamp", 1.0, 1.0)],
+                    ..Default::default()
+                };
+                chain.seal(&mut summary);
+                summary
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_verify_an_unbroken_chain() {
+        let mut chain = SummaryChain::default();
+        for summary in summaries(3).iter() {
+            assert_eq!(chain.verify(summary), Ok(()));
+        }
+
+        assert_eq!(
+            SummaryChain::default().verify(&Summary::default()),
+            Err(ChainError::Unsealed)
+        );
+    }
+
+    #[test]
+    fn should_detect_tampering_and_missed_summaries() {
+        let mut sealed = summaries(4);
+
+        let mut chain = SummaryChain::default();
+        assert_eq!(chain.verify(&sealed[0]), Ok(()));
+        assert_eq!(
+            chain.verify(&sealed[2]),
+            Err(ChainError::Gap {
+                expected: 2,
+                received: 3
+            })
+        );
+
+        sealed[3].asks[0].amount = 100.0;
+        assert_eq!(
+            chain.verify(&sealed[3]),
+            Err(ChainError::Tampered { sequence: 4 })
+        );
+    }
+
+    #[test]
+    fn should_verify_summaries_with_fields_outside_the_digest() {
+        let mut sealed = summaries(2);
+        // As when a newer server sets fields an older client doesn't know of, or doesn't carry over when re-encoding
+        sealed[0].quality_score = 0.9;
+        sealed[1].metadata = Some(Default::default());
+
+        let mut chain = SummaryChain::default();
+        assert_eq!(chain.verify(&sealed[0]), Ok(()));
+        assert_eq!(chain.verify(&sealed[1]), Ok(()));
+
+        let mut unversioned = summaries(1).remove(0);
+        unversioned.integrity.as_mut().unwrap().version = 0;
+        assert_eq!(
+            SummaryChain::default().verify(&unversioned),
+            Err(ChainError::UnsupportedVersion { version: 0 })
+        );
+    }
+}
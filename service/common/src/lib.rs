@@ -1,3 +1,11 @@
+pub mod batch;
+#[cfg(test)]
+mod compatibility;
+pub mod descriptor;
+pub mod filter;
+pub mod integrity;
+pub mod retry;
+
 pub mod proto {
     pub mod orderbook {
         #[cfg(test)]
@@ -6,18 +14,22 @@ pub mod proto {
             cmp::Ordering,
             fmt::{Display, Formatter},
             hash::{Hash, Hasher},
+            time::{Duration, SystemTime, UNIX_EPOCH},
         };
 
-        use tonic::IntoRequest;
+        use prost::Message;
+        use tonic::{codegen::Bytes, Code, IntoRequest, Status};
 
         use crate::proto::OrderBookRequest;
 
-        tonic::include_proto!("orderbook");
+        include!("generated/orderbook.rs");
 
         // These impl blocks are to allow me to use the generated types from the proto schema.
         // The auto-generated types don't have these traits derived so I need to do it here.
 
-        #[allow(clippy::derive_hash_xor_eq)]
+        /// Hashed by the symbols exactly, as prost's derived equality compares them. Pairs made with
+        /// [new](TradedPair::new) are canonical, pairs decoded from requests or exchanges should be made
+        /// [canonical](TradedPair::canonical) before they're used as keys.
         impl Hash for TradedPair {
             fn hash<H: Hasher>(&self, state: &mut H) {
                 self.first.hash(state);
@@ -56,76 +68,265 @@ pub mod proto {
         }
 
         impl TradedPair {
+            /// The [canonical](TradedPair::canonical) pair of the symbols, whatever case they're given in.
             pub fn new(first: &'static str, second: &'static str) -> Self {
                 TradedPair {
-                    first: first.to_string(),
-                    second: second.to_string(),
+                    first: first.trim().to_uppercase(),
+                    second: second.trim().to_uppercase(),
                 }
             }
 
             pub fn symbol_lower(&self) -> String {
                 format!("{}{}", self.first, self.second).to_lowercase()
             }
+
+            /// The pair with both symbols trimmed and uppercase, the form the service identifies pairs by.
+            pub fn canonical(&self) -> Self {
+                TradedPair {
+                    first: self.first.trim().to_uppercase(),
+                    second: self.second.trim().to_uppercase(),
+                }
+            }
+
+            /// Whether the pairs have the same symbols, ignoring case.
+            pub fn eq_ignore_case(&self, other: &TradedPair) -> bool {
+                self.first.eq_ignore_ascii_case(&other.first)
+                    && self.second.eq_ignore_ascii_case(&other.second)
+            }
+        }
+
+        #[test]
+        fn should_canonicalise_traded_pairs() {
+            let requested = TradedPair {
+                first: "eth".to_string(),
+                second: "btc".to_string(),
+            };
+            assert_ne!(requested, TradedPair::new("ETH", "BTC"));
+            assert_eq!(requested.canonical(), TradedPair::new("ETH", "BTC"));
+            assert_eq!(
+                TradedPair::new("eth", " btc"),
+                TradedPair::new("ETH", "BTC")
+            );
+            assert!(requested.eq_ignore_case(&TradedPair::new("ETH", "BTC")));
+            assert!(!TradedPair::new("ETH", "BTC").eq_ignore_case(&TradedPair::new("BTC", "ETH")));
+        }
+
+        #[test]
+        fn should_find_traded_pairs_keyed_in_another_case() {
+            let mut aggregators = std::collections::HashMap::new();
+            aggregators.insert(TradedPair::new("eth", "btc"), "ETH-BTC aggregator");
+
+            let requested = TradedPair {
+                first: "Eth".to_string(),
+                second: "BTC".to_string(),
+            };
+            assert_eq!(
+                aggregators.get(&requested.canonical()),
+                Some(&"ETH-BTC aggregator")
+            );
+            assert_eq!(
+                aggregators.get(&TradedPair::new("ETH", "btc")),
+                Some(&"ETH-BTC aggregator")
+            );
+            assert_eq!(aggregators.get(&TradedPair::new("BTC", "ETH")), None);
+        }
+
+        /// Identifies an exchange, with an escape hatch for exchanges the service doesn't know about.
+        #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum ExchangeId {
+            Binance,
+            Bitstamp,
+            Other(String),
+        }
+
+        impl ExchangeId {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    ExchangeId::Binance => "Binance",
+                    ExchangeId::Bitstamp => "Bitstamp",
+                    ExchangeId::Other(name) => name,
+                }
+            }
+
+            pub fn known(&self) -> KnownExchange {
+                match self {
+                    ExchangeId::Binance => KnownExchange::Binance,
+                    ExchangeId::Bitstamp => KnownExchange::Bitstamp,
+                    ExchangeId::Other(_) => KnownExchange::Other,
+                }
+            }
+        }
+
+        /// Known exchanges are matched case-insensitively, anything else becomes [ExchangeId::Other].
+        impl From<&str> for ExchangeId {
+            fn from(name: &str) -> Self {
+                if name.eq_ignore_ascii_case("binance") {
+                    ExchangeId::Binance
+                } else if name.eq_ignore_ascii_case("bitstamp") {
+                    ExchangeId::Bitstamp
+                } else {
+                    ExchangeId::Other(name.to_string())
+                }
+            }
+        }
+
+        impl Display for ExchangeId {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.as_str())
+            }
+        }
+
+        #[test]
+        fn should_identify_exchanges_by_name() {
+            assert_eq!(ExchangeId::from("BINANCE"), ExchangeId::Binance);
+            assert_eq!(
+                ExchangeId::from("Kraken"),
+                ExchangeId::Other("Kraken".to_string())
+            );
+
+            let level = Level::new(ExchangeId::Bitstamp, 1.0, 1.0);
+            assert_eq!(level.exchange, "Bitstamp");
+            assert_eq!(
+                Level::try_new(ExchangeId::Bitstamp, 1.0, 1.0),
+                Some(level.clone())
+            );
+            assert_eq!(Level::try_new(ExchangeId::Bitstamp, f64::NAN, 1.0), None);
+            assert_eq!(
+                Level::try_new(ExchangeId::Bitstamp, 1.0, f64::INFINITY),
+                None
+            );
+            assert_eq!(level.known_exchange(), KnownExchange::Bitstamp);
+            assert_eq!(level.exchange_id(), ExchangeId::Bitstamp);
         }
 
         impl Level {
-            pub fn new(exchange: &str, price: f64, quantity: f64) -> Self {
+            pub fn new(exchange: impl Into<ExchangeId>, price: f64, quantity: f64) -> Self {
+                let exchange = exchange.into();
                 Self {
                     exchange: exchange.to_string(),
                     price,
                     amount: quantity,
+                    known_exchange: exchange.known() as i32,
+                    contributing_exchanges: Vec::new(),
                 }
             }
 
-            /// This will order the [Level]s Low->High by [price].
-            /// Where [price] of `self` and `other` are equal it is then ordered High->Low by [amount]
-            pub fn sort_as_asks(&self, other: &Self) -> Ordering {
-                // Compare `price`
-                if self.price < other.price {
-                    return Ordering::Less;
-                } else if self.price > other.price {
-                    return Ordering::Greater;
+            /// As [Level::new], but `None` when the price or quantity is NaN or infinite.
+            pub fn try_new(
+                exchange: impl Into<ExchangeId>,
+                price: f64,
+                quantity: f64,
+            ) -> Option<Self> {
+                (price.is_finite() && quantity.is_finite())
+                    .then(|| Self::new(exchange, price, quantity))
+            }
+
+            /// The exchange the level came from, falling back to its name for exchanges that aren't known.
+            pub fn exchange_id(&self) -> ExchangeId {
+                match self.known_exchange() {
+                    KnownExchange::Binance => ExchangeId::Binance,
+                    KnownExchange::Bitstamp => ExchangeId::Bitstamp,
+                    KnownExchange::Other => ExchangeId::from(self.exchange.as_str()),
+                }
+            }
+        }
+
+        /// Orders `a` before `b` when it's lower, or higher when `descending`, with NaN after every number either way
+        /// so a malformed level can't take the best place.
+        fn nan_last(a: f64, b: f64, descending: bool) -> Ordering {
+            a.is_nan().cmp(&b.is_nan()).then_with(|| {
+                if descending {
+                    b.total_cmp(&a)
+                } else {
+                    a.total_cmp(&b)
                 }
+            })
+        }
+
+        /// A [Level] ordered as an ask, best first: Low->High by `price` then High->Low by `amount`.
+        ///
+        /// Levels are equal when their price and amount are, whichever exchange they're from.
+        #[derive(Clone, Debug)]
+        pub struct AskLevel(pub Level);
+
+        impl AskLevel {
+            /// Orders asks by `price` alone, best first.
+            pub fn price_order(a: &Level, b: &Level) -> Ordering {
+                nan_last(a.price, b.price, false)
+            }
+
+            pub fn order(a: &Level, b: &Level) -> Ordering {
+                Self::price_order(a, b).then_with(|| nan_last(a.amount, b.amount, true))
+            }
+
+            /// Sort `levels` as asks, best first.
+            pub fn sort(levels: &mut [Level]) {
+                levels.sort_by(Self::order);
+            }
+        }
+
+        impl Ord for AskLevel {
+            fn cmp(&self, other: &Self) -> Ordering {
+                Self::order(&self.0, &other.0)
+            }
+        }
+
+        impl PartialOrd for AskLevel {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl PartialEq for AskLevel {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
 
-                // The `price` is equal, compare `amount`
-                // Note that the comparisons are counter to what is implied by the [Ordering] returned.
-                // This is because amount should always be ordered High->Low.
-                if self.amount > other.amount {
-                    return Ordering::Less;
-                } else if self.amount < other.amount {
-                    return Ordering::Greater;
-                };
+        impl Eq for AskLevel {}
 
-                // `price` and `amount` are equal
-                Ordering::Equal
+        /// A [Level] ordered as a bid, best first: High->Low by `price` then High->Low by `amount`.
+        ///
+        /// Levels are equal when their price and amount are, whichever exchange they're from.
+        #[derive(Clone, Debug)]
+        pub struct BidLevel(pub Level);
+
+        impl BidLevel {
+            /// Orders bids by `price` alone, best first.
+            pub fn price_order(a: &Level, b: &Level) -> Ordering {
+                nan_last(a.price, b.price, true)
+            }
+
+            pub fn order(a: &Level, b: &Level) -> Ordering {
+                Self::price_order(a, b).then_with(|| nan_last(a.amount, b.amount, true))
             }
 
-            /// This will order the [Level]s High->Low by [price].
-            /// Where [price] of `self` and `other` are equal it is then ordered High->Low by [amount]
-            pub fn sort_as_bids(&self, other: &Self) -> Ordering {
-                // Compare `price`
-                // Note that the comparisons are counter to what is implied by the [Ordering] returned.
-                // This is because `price` is being ordered High->Low.
-                if self.price > other.price {
-                    return Ordering::Less;
-                } else if self.price < other.price {
-                    return Ordering::Greater;
-                };
+            /// Sort `levels` as bids, best first.
+            pub fn sort(levels: &mut [Level]) {
+                levels.sort_by(Self::order);
+            }
+        }
 
-                // The `price` is equal, compare `amount`
-                // Note that the comparisons are counter to what is implied by the [Ordering] returned.
-                // This is because `amount` should always be ordered High->Low.
-                if self.amount > other.amount {
-                    return Ordering::Less;
-                } else if self.amount < other.amount {
-                    return Ordering::Greater;
-                };
+        impl Ord for BidLevel {
+            fn cmp(&self, other: &Self) -> Ordering {
+                Self::order(&self.0, &other.0)
+            }
+        }
 
-                // `price` and `amount` are equal
-                Ordering::Equal
+        impl PartialOrd for BidLevel {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
             }
         }
 
+        impl PartialEq for BidLevel {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
+
+        impl Eq for BidLevel {}
+
         #[test]
         fn should_sort_ask_levels_correctly() {
             let mut unsorted_levels = vec![
@@ -142,7 +343,7 @@ pub mod proto {
                 Level::new("Example", 10.0, 4.0),
             ];
 
-            unsorted_levels.sort_unstable_by(|a, b| a.sort_as_asks(b));
+            AskLevel::sort(&mut unsorted_levels);
 
             // Now sorted
             assert_eq!(unsorted_levels, expected);
@@ -164,12 +365,43 @@ pub mod proto {
                 Level::new("Example", 9.0, 4.0),
             ];
 
-            unsorted_levels.sort_unstable_by(|a, b| a.sort_as_bids(b));
+            BidLevel::sort(&mut unsorted_levels);
 
             // Now sorted
             assert_eq!(unsorted_levels, expected);
         }
 
+        #[test]
+        fn should_sort_nan_levels_last() {
+            let mut asks = vec![
+                Level::new("Example", f64::NAN, 1.0),
+                Level::new("Example", 10.0, f64::NAN),
+                Level::new("Example", 10.0, 1.0),
+            ];
+            let mut bids = asks.clone();
+
+            AskLevel::sort(&mut asks);
+            BidLevel::sort(&mut bids);
+            for levels in [asks, bids] {
+                assert_eq!(levels[0].amount, 1.0);
+                assert!(levels[1].amount.is_nan());
+                assert!(levels[2].price.is_nan());
+            }
+
+            assert!(
+                AskLevel(Level::new("Example", 9.0, 1.0))
+                    < AskLevel(Level::new("Example", 10.0, 1.0))
+            );
+            assert!(
+                BidLevel(Level::new("Example", 10.0, 1.0))
+                    < BidLevel(Level::new("Example", 9.0, 1.0))
+            );
+            assert_eq!(
+                AskLevel(Level::new("Binance", 9.0, 1.0)),
+                AskLevel(Level::new("Bitstamp", 9.0, 1.0))
+            );
+        }
+
         impl Display for Level {
             fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
                 write!(
@@ -185,9 +417,9 @@ pub mod proto {
         impl Display for Levels {
             fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
                 writeln!(f, "[")?;
-                self.0.iter().fold(Ok(()), |result, level| {
-                    result.and_then(|_| writeln!(f, "\t{level},"))
-                })?;
+                self.0
+                    .iter()
+                    .try_for_each(|level| writeln!(f, "\t{level},"))?;
                 write!(f, "]")
             }
         }
@@ -198,15 +430,187 @@ pub mod proto {
             }
         }
 
+        impl Summary {
+            /// A summary without levels, sent to show the stream is alive when there have been no updates.
+            pub fn heartbeat(last_update_age: Duration) -> Self {
+                Self {
+                    heartbeat: Some(Heartbeat {
+                        last_update_age_millis: last_update_age.as_millis() as u64,
+                    }),
+                    ..Default::default()
+                }
+            }
+
+            pub fn is_heartbeat(&self) -> bool {
+                self.heartbeat.is_some()
+            }
+
+            /// Recompute the notional and depth of each side, for after the levels have changed.
+            pub fn update_totals(&mut self) {
+                let notional =
+                    |levels: &[Level]| levels.iter().map(|level| level.price * level.amount).sum();
+                self.ask_notional = notional(&self.asks);
+                self.bid_notional = notional(&self.bids);
+                self.ask_depth = self.asks.len() as u32;
+                self.bid_depth = self.bids.len() as u32;
+            }
+
+            /// Express level amounts in units of the second token, price x amount. The notional is unchanged, so
+            /// it should be updated beforehand.
+            pub fn denominate_in_quote(&mut self) {
+                for level in self.asks.iter_mut().chain(self.bids.iter_mut()) {
+                    level.amount *= level.price;
+                }
+                self.metadata
+                    .get_or_insert_with(Default::default)
+                    .set_amount_denomination(AmountDenomination::Quote);
+            }
+        }
+
+        impl TaggedSummary {
+            /// Report that a subscription's command failed, or that the subscription has ended.
+            pub fn error(subscription_id: String, status: &Status) -> Self {
+                Self {
+                    subscription_id,
+                    payload: Some(tagged_summary::Payload::Error(SubscriptionError {
+                        code: status.code() as i32,
+                        message: status.message().to_string(),
+                        reason: CloseReason::of(status) as i32,
+                    })),
+                }
+            }
+        }
+
+        impl CloseReason {
+            /// A status ending a stream for this reason, which carries a [StreamClosed] in its details.
+            pub fn status(self, code: Code, message: impl Into<String>) -> Status {
+                let details = StreamClosed {
+                    reason: self as i32,
+                    ..Default::default()
+                }
+                .encode_to_vec();
+                Status::with_details(code, message, Bytes::from(details))
+            }
+
+            /// The reason in a status's [StreamClosed] details, `Unspecified` when it has none.
+            pub fn of(status: &Status) -> Self {
+                StreamClosed::decode(status.details())
+                    .map(|closed| closed.reason())
+                    .unwrap_or_default()
+            }
+
+            /// A status ending a stream as the server shuts down, pointing the client at `alternative_address` when
+            /// given.
+            pub fn server_shutdown(
+                message: impl Into<String>,
+                alternative_address: Option<&str>,
+            ) -> Status {
+                let details = StreamClosed {
+                    reason: CloseReason::ServerShutdown as i32,
+                    alternative_address: alternative_address.unwrap_or_default().to_string(),
+                }
+                .encode_to_vec();
+                Status::with_details(Code::Unavailable, message, Bytes::from(details))
+            }
+
+            /// The server a status's [StreamClosed] details point the client at, if any.
+            pub fn alternative_address(status: &Status) -> Option<String> {
+                StreamClosed::decode(status.details())
+                    .ok()
+                    .map(|closed| closed.alternative_address)
+                    .filter(|address| !address.is_empty())
+            }
+        }
+
+        #[test]
+        fn should_carry_close_reason_in_status_details() {
+            let status = CloseReason::QuotaExceeded.status(Code::ResourceExhausted, "At limit");
+            assert_eq!(status.code(), Code::ResourceExhausted);
+            assert_eq!(CloseReason::of(&status), CloseReason::QuotaExceeded);
+            assert_eq!(
+                CloseReason::of(&Status::unavailable("Gone")),
+                CloseReason::Unspecified
+            );
+
+            let draining = CloseReason::server_shutdown("Draining", Some("http://standby:3030"));
+            assert_eq!(CloseReason::of(&draining), CloseReason::ServerShutdown);
+            assert_eq!(
+                CloseReason::alternative_address(&draining).as_deref(),
+                Some("http://standby:3030")
+            );
+            assert_eq!(CloseReason::alternative_address(&status), None);
+        }
+
+        impl SummaryBatch {
+            /// A batch of the summaries from a window which is closing now.
+            pub fn new(summaries: Vec<Summary>) -> Self {
+                let timestamp_millis = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+
+                Self {
+                    summaries,
+                    timestamp_millis,
+                }
+            }
+        }
+
         impl Display for Summary {
             fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                if let Some(heartbeat) = &self.heartbeat {
+                    return write!(
+                        f,
+                        "{{ \"heartbeat\": {{ \"last_update_age_millis\": {} }} }}",
+                        heartbeat.last_update_age_millis
+                    );
+                }
+
                 write!(
                     f,
-                    "{{\n\t\"spread\": {},\n\t\"asks\": {},\n\"bids\": {} \n}}",
+                    "{{\n\t\"spread\": {},\n\t\"ask_notional\": {},\n\t\"bid_notional\": {},\n\t\"asks\": {},\n\"bids\": {}",
                     self.spread,
+                    self.ask_notional,
+                    self.bid_notional,
                     Levels::from(&self.asks),
                     Levels::from(&self.bids)
-                )
+                )?;
+                if !self.exchanges_in_maintenance.is_empty() {
+                    write!(
+                        f,
+                        ",\n\"exchanges_in_maintenance\": {:?}",
+                        self.exchanges_in_maintenance
+                    )?;
+                }
+                if self.degraded {
+                    write!(
+                        f,
+                        ",\n\"degraded\": true,\n\"missing_exchanges\": {:?}",
+                        self.missing_exchanges
+                    )?;
+                }
+                write!(f, " \n}}")
+            }
+        }
+
+        impl Display for ConnectorStatus {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                let traded_pair = self
+                    .traded_pair
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default();
+                write!(
+                    f,
+                    "{}\t{} {traded_pair}\t{:?}",
+                    self.timestamp_millis,
+                    self.exchange,
+                    self.event()
+                )?;
+                if !self.detail.is_empty() {
+                    write!(f, "\t{}", self.detail)?;
+                }
+                Ok(())
             }
         }
 
@@ -220,14 +624,53 @@ pub mod proto {
             fn from(value: TradedPair) -> Self {
                 Self {
                     traded_pair: Some(value),
+                    ..Default::default()
                 }
             }
         }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        impl orderbook_aggregator_client::OrderbookAggregatorClient<tonic::transport::Channel> {
+            /// Attempt to create a new client by connecting to a given endpoint.
+            pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+            where
+                D: TryInto<tonic::transport::Endpoint>,
+                D::Error: Into<tonic::codegen::StdError>,
+            {
+                let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+                Ok(Self::new(conn))
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        impl orderbook_admin_client::OrderbookAdminClient<tonic::transport::Channel> {
+            /// Attempt to create a new client by connecting to a given endpoint.
+            pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+            where
+                D: TryInto<tonic::transport::Endpoint>,
+                D::Error: Into<tonic::codegen::StdError>,
+            {
+                let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+                Ok(Self::new(conn))
+            }
+        }
     }
 
     // Re-export the types
     pub use orderbook::{
-        orderbook_aggregator_client, orderbook_aggregator_server, Empty, Level,
-        Request as OrderBookRequest, Summary, TradedPair,
+        orderbook_admin_client, orderbook_admin_server, orderbook_aggregator_client,
+        orderbook_aggregator_server, service_event, subscription_command, tagged_summary,
+        AggregatorStall, AmountDenomination, ApiDescriptor, AskLevel, BatchedRequest, BidLevel,
+        Capability, CloseReason, ConnectorEvent, ConnectorStatus, ConsistencyAlert, DepthBand,
+        DepthHistogram, DepthHistogramRequest, DepthSnapshotRequest, DrainRequest, DrainResult,
+        Empty, ExchangeDetails, ExchangeFill, ExchangeId, ExchangeLatencies, ExchangeLatency,
+        ExchangeList, ExchangePairs, FeedQuarantine, FieldDescription, FrameTapStatus, Heartbeat,
+        KnownExchange, Level, LiquidityAlert, LogFilter, MergeStrategy, MetricsDump, ModifyCommand,
+        Quality, QuoteConversion, Request as OrderBookRequest, RpcDescription, ServerInfo,
+        ServiceEvent, SetFrameTapRequest, SetLogLevelRequest, Side, SlippageEstimate,
+        SlippageRequest, SourceTimestamp, StreamClosed, SubscribeCommand, Subscriber, Subscribers,
+        SubscriptionCommand, SubscriptionDescription, SubscriptionError, SubscriptionSource,
+        Summary, SummaryBatch, SummaryIntegrity, SummaryMetadata, SupportedPairs, TaggedSummary,
+        TradedPair,
     };
 }
@@ -0,0 +1,356 @@
+use std::{
+    collections::hash_map::RandomState,
+    error::Error,
+    fmt::{Display, Formatter},
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+use tokio::time::{sleep, Instant};
+
+/// How long to wait before each retry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backoff {
+    /// The same delay before every retry
+    Fixed(Duration),
+    /// `initial` before the first retry, doubling before each one after it up to `max`
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Backoff {
+    /// The delay before the `retry`th retry, counting from 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => initial
+                .saturating_mul(1 << retry.saturating_sub(1).min(31))
+                .min(max),
+        }
+    }
+}
+
+/// Paces repeated attempts at an operation, e.g. connecting, by a [Backoff] plus an optional random jitter
+/// and [RetryBudget], giving up after an optional number of attempts.
+///
+/// ```ignore
+/// let mut retry = Retry::exponential(Duration::from_millis(100), Duration::from_secs(5)).with_max_attempts(5);
+/// while retry.next_attempt().await {
+///     if connect().await.is_ok() {
+///         break;
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Retry {
+    backoff: Backoff,
+    max_attempts: Option<usize>,
+    max_jitter: Duration,
+    budget: Option<RetryBudget>,
+    attempts: usize,
+    /// Whether any attempt has been made, every attempt after the first is a retry even after a reset
+    retrying: bool,
+}
+
+impl Retry {
+    /// Wait `delay` before each retry.
+    pub fn fixed(delay: Duration) -> Self {
+        Self::new(Backoff::Fixed(delay))
+    }
+
+    /// Wait `initial` before the first retry, doubling before each one after it up to `max`.
+    pub fn exponential(initial: Duration, max: Duration) -> Self {
+        Self::new(Backoff::Exponential { initial, max })
+    }
+
+    fn new(backoff: Backoff) -> Self {
+        Self {
+            backoff,
+            max_attempts: None,
+            max_jitter: Duration::ZERO,
+            budget: None,
+            attempts: 0,
+            retrying: false,
+        }
+    }
+
+    /// Give up after `max_attempts`, attempts are unlimited otherwise.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Add a random delay of up to `max_jitter` to each retry.
+    pub fn with_jitter(mut self, max_jitter: Duration) -> Self {
+        self.max_jitter = max_jitter;
+        self
+    }
+
+    /// Take a token from `budget` before each retry, limiting retries across everything sharing it.
+    pub fn with_budget(mut self, budget: RetryBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Wait until the next attempt can be made, `false` once every attempt has been made.
+    /// The first attempt is made straight away.
+    pub async fn next_attempt(&mut self) -> bool {
+        if self
+            .max_attempts
+            .is_some_and(|max_attempts| self.attempts >= max_attempts)
+        {
+            return false;
+        }
+
+        if self.retrying {
+            let retry = self.attempts.max(1) as u32;
+            sleep(self.backoff.delay(retry) + jitter(self.max_jitter)).await;
+            if let Some(budget) = &self.budget {
+                budget.acquire().await;
+            }
+        }
+        self.retrying = true;
+        self.attempts += 1;
+        true
+    }
+
+    /// The attempts made since starting or the last [Retry::reset].
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Count attempts from zero again, e.g. once a connection has succeeded.
+    /// The next attempt waits as the first retry would.
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+/// The budget used by subscriptions which don't provide their own, see [RetryBudget::global].
+static GLOBAL_BUDGET: Lazy<RetryBudget> = Lazy::new(|| {
+    RetryBudget::new(10, 2.0, Duration::from_millis(500)).expect("Global budget should be valid")
+});
+
+/// A token bucket limiting reconnect attempts collectively across every subscription sharing it,
+/// so that a server restart isn't met by every subscription in the process reconnecting at once.
+///
+/// Cloning the budget shares it.
+#[derive(Clone, Debug)]
+pub struct RetryBudget {
+    bucket: Arc<Mutex<TokenBucket>>,
+    max_jitter: Duration,
+}
+
+impl RetryBudget {
+    /// Allow bursts of up to `capacity` attempts, refilled at `refill_per_sec`,
+    /// with each attempt delayed by a random jitter of up to `max_jitter`.
+    pub fn new(
+        capacity: u32,
+        refill_per_sec: f64,
+        max_jitter: Duration,
+    ) -> Result<Self, BudgetError> {
+        if capacity == 0 {
+            return Err(BudgetError::ZeroCapacity);
+        }
+        if !refill_per_sec.is_finite() || refill_per_sec <= 0.0 {
+            return Err(BudgetError::InvalidRefill(refill_per_sec));
+        }
+        Ok(Self {
+            bucket: Arc::new(Mutex::new(TokenBucket::new(
+                capacity as f64,
+                refill_per_sec,
+            ))),
+            max_jitter,
+        })
+    }
+
+    /// The budget shared by every subscription in the process, allowing bursts of 10 reconnects and then 2 per second.
+    pub fn global() -> Self {
+        GLOBAL_BUDGET.clone()
+    }
+
+    /// Wait until the budget allows another attempt.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = self
+                .bucket
+                .lock()
+                .expect("Should lock")
+                .take(Instant::now());
+            match wait {
+                Ok(()) => break,
+                Err(wait) => sleep(wait).await,
+            }
+        }
+
+        // Spread out attempts which were allowed at the same time
+        sleep(jitter(self.max_jitter)).await;
+    }
+}
+
+/// Why a [RetryBudget] can't be built, it would never allow an attempt.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BudgetError {
+    ZeroCapacity,
+    /// The refill rate isn't a positive number
+    InvalidRefill(f64),
+}
+
+impl Display for BudgetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetError::ZeroCapacity => write!(f, "Retry budget capacity must be greater than 0"),
+            BudgetError::InvalidRefill(refill_per_sec) => write!(
+                f,
+                "Retry budget refill_per_sec must be greater than 0, not {refill_per_sec}"
+            ),
+        }
+    }
+}
+
+impl Error for BudgetError {}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, refill_per_sec: f64) -> Self {
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token, or return how long until one will be available.
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.refill_per_sec,
+            ))
+        }
+    }
+}
+
+/// A random duration of up to `max_jitter`.
+fn jitter(max_jitter: Duration) -> Duration {
+    if max_jitter.is_zero() {
+        return Duration::ZERO;
+    }
+    // A randomly seeded hasher is enough randomness for jitter
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_nanos(random % max_jitter.as_nanos() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{jitter, Backoff, BudgetError, Retry, RetryBudget, TokenBucket};
+
+    #[test]
+    fn should_double_exponential_backoff_up_to_max() {
+        let backoff = Backoff::Exponential {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+        };
+
+        assert_eq!(backoff.delay(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay(3), Duration::from_millis(400));
+        assert_eq!(backoff.delay(5), Duration::from_secs(1));
+        assert_eq!(backoff.delay(100), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_retry_until_attempts_are_exhausted_in_virtual_time() {
+        let mut retry = Retry::exponential(Duration::from_millis(100), Duration::from_secs(1))
+            .with_max_attempts(4);
+        let start = Instant::now();
+
+        let mut attempts = 0;
+        while retry.next_attempt().await {
+            attempts += 1;
+        }
+
+        assert_eq!(attempts, 4);
+        // 100ms, 200ms and 400ms before each retry
+        assert_eq!(start.elapsed(), Duration::from_millis(700));
+
+        // After a reset the backoff starts over
+        retry.reset();
+        assert!(retry.next_attempt().await);
+        assert_eq!(start.elapsed(), Duration::from_millis(800));
+
+        let mut budgeted = Retry::fixed(Duration::ZERO)
+            .with_budget(RetryBudget::new(1, 1.0, Duration::ZERO).unwrap());
+        assert!(budgeted.next_attempt().await);
+        assert!(budgeted.next_attempt().await);
+        assert!(budgeted.next_attempt().await);
+        // The budget's first token is taken by the first retry, the second waits for a refill
+        assert_eq!(start.elapsed(), Duration::from_millis(1_800));
+    }
+
+    #[test]
+    fn should_limit_attempts_to_refill_rate_after_a_burst() {
+        let mut bucket = TokenBucket::new(2.0, 4.0);
+        let start = Instant::now();
+        bucket.last_refill = start;
+
+        assert!(bucket.take(start).is_ok());
+        assert!(bucket.take(start).is_ok());
+        assert_eq!(bucket.take(start), Err(Duration::from_millis(250)));
+
+        assert!(bucket.take(start + Duration::from_millis(250)).is_ok());
+        assert!(bucket.take(start + Duration::from_millis(250)).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_wait_for_the_budget_in_virtual_time() {
+        let budget = RetryBudget::new(1, 2.0, Duration::ZERO).unwrap();
+        let start = Instant::now();
+
+        budget.acquire().await;
+        budget.acquire().await;
+
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn should_refuse_budgets_which_never_allow_an_attempt() {
+        assert_eq!(
+            RetryBudget::new(0, 2.0, Duration::ZERO).unwrap_err(),
+            BudgetError::ZeroCapacity
+        );
+        for refill_per_sec in [0.0, -1.0, f64::NAN] {
+            assert!(matches!(
+                RetryBudget::new(1, refill_per_sec, Duration::ZERO),
+                Err(BudgetError::InvalidRefill(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn should_keep_jitter_within_bounds() {
+        for _ in 0..100 {
+            assert!(jitter(Duration::from_millis(10)) < Duration::from_millis(10));
+        }
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+    }
+}
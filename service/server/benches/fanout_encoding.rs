@@ -0,0 +1,96 @@
+//! Compares the cost of fanning a summary out to subscribers when it is encoded for each subscriber, as the server does,
+//! against encoding it once per tick and sharing the bytes.
+//!
+//! Run with `cargo bench -p order-book-service-server --bench fanout_encoding`.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use prost::{bytes::Bytes, Message};
+
+use order_book_service_types::proto::{Level, Summary, SummaryMetadata};
+
+/// Subscriber counts each strategy is timed with.
+const SUBSCRIBERS: [usize; 4] = [1, 10, 100, 1000];
+/// Depths of the summaries encoded, the default and the most a subscription can request.
+const DEPTHS: [usize; 2] = [10, 100];
+/// Each measurement runs for at least this long.
+const MEASURE_FOR: Duration = Duration::from_millis(500);
+
+fn summary(depth: usize) -> Summary {
+    let level = |index: usize, offset: f64| {
+        let exchange = if index.is_multiple_of(2) {
+            "Binance"
+        } else {
+            "Bitstamp"
+        };
+        Level::new(
+            exchange,
+            0.07 + offset * index as f64,
+            1.0 + index as f64 / 10.0,
+        )
+    };
+    let mut summary = Summary {
+        spread: 0.000_01,
+        asks: (0..depth).map(|index| level(index, 0.000_01)).collect(),
+        bids: (0..depth).map(|index| level(index, -0.000_01)).collect(),
+        metadata: Some(SummaryMetadata {
+            excluded_exchanges: vec!["Kraken".to_string()],
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    summary.update_totals();
+    summary
+}
+
+/// Each subscriber is sent its own copy of the summary, encoded as it's written to the subscriber's stream.
+fn encode_per_subscriber(summary: &Summary, subscribers: usize) -> usize {
+    (0..subscribers)
+        .map(|_| {
+            let copy = summary.clone();
+            black_box(copy.encode_to_vec()).len()
+        })
+        .sum()
+}
+
+/// The summary is encoded once and each subscriber is sent a reference to the same bytes.
+fn encode_once(summary: &Summary, subscribers: usize) -> usize {
+    let encoded = Bytes::from(summary.encode_to_vec());
+    (0..subscribers)
+        .map(|_| black_box(encoded.clone()).len())
+        .sum()
+}
+
+/// The mean time taken by `fan_out` over as many runs as fit in [MEASURE_FOR].
+fn measure(fan_out: impl Fn() -> usize) -> Duration {
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < MEASURE_FOR {
+        black_box(fan_out());
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+fn main() {
+    println!(
+        "{:>5} {:>11} {:>16} {:>12} {:>8}",
+        "depth", "subscribers", "per subscriber", "encode once", "speedup"
+    );
+    for depth in DEPTHS {
+        let summary = summary(depth);
+        for subscribers in SUBSCRIBERS {
+            let per_subscriber = measure(|| encode_per_subscriber(&summary, subscribers));
+            let once = measure(|| encode_once(&summary, subscribers));
+            println!(
+                "{depth:>5} {subscribers:>11} {:>16?} {:>12?} {:>7.1}x",
+                per_subscriber,
+                once,
+                per_subscriber.as_secs_f64() / once.as_secs_f64()
+            );
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::time::Duration;
+
+use tonic::{Request, Response, Status};
+
+use order_book_service_types::proto::{
+    orderbook_admin_server::OrderbookAdmin, DrainRequest, DrainResult, Empty, ExchangeLatencies,
+    ExchangeList, FrameTapStatus, LogFilter, MetricsDump, SetFrameTapRequest, SetLogLevelRequest,
+    Subscribers as SubscriberList,
+};
+
+use crate::{
+    drain::Drain, exchange::ExchangeInfo, latency, snapshots::MetricsSnapshots,
+    subscribers::Subscribers, tap::FrameTap, telemetry, tenancy::AdminAuth,
+};
+
+/// Operational controls for the running service, separate from the market data service.
+pub(crate) struct AdminService {
+    pub(crate) frame_tap: FrameTap,
+    pub(crate) auth: AdminAuth,
+    pub(crate) latencies: latency::ExchangeLatencies,
+    /// Shared with the summary service, which registers each subscription it opens
+    pub(crate) subscribers: Subscribers,
+    pub(crate) metrics_snapshots: MetricsSnapshots,
+    /// Shared with the summary service, which refuses and ends subscriptions once draining
+    pub(crate) drain: Drain,
+    pub(crate) exchanges: Vec<ExchangeInfo>,
+}
+
+#[tonic::async_trait]
+impl OrderbookAdmin for AdminService {
+    async fn set_frame_tap(
+        &self,
+        request: Request<SetFrameTapRequest>,
+    ) -> Result<Response<FrameTapStatus>, Status> {
+        self.auth.authorize(&request)?;
+        let request = request.into_inner();
+        if request.exchange.is_empty() {
+            return Err(Status::invalid_argument("An exchange must be given"));
+        }
+
+        // Named as the exchange names itself, as a misspelled tap would otherwise never record anything
+        let exchange = self
+            .exchanges
+            .iter()
+            .find(|info| info.name.eq_ignore_ascii_case(&request.exchange))
+            .ok_or_else(|| {
+                Status::not_found(format!("{} isn't being aggregated", request.exchange))
+            })?;
+        self.frame_tap.set_enabled(exchange.name, request.enabled);
+
+        Ok(Response::new(FrameTapStatus {
+            enabled_exchanges: self.frame_tap.enabled_exchanges(),
+        }))
+    }
+
+    async fn get_exchange_latencies(
+        &self,
+        request: Request<Empty>,
+    ) -> Result<Response<ExchangeLatencies>, Status> {
+        self.auth.authorize(&request)?;
+
+        Ok(Response::new(ExchangeLatencies {
+            exchanges: self.latencies.stats(),
+        }))
+    }
+
+    async fn list_subscribers(
+        &self,
+        request: Request<Empty>,
+    ) -> Result<Response<SubscriberList>, Status> {
+        self.auth.authorize(&request)?;
+
+        Ok(Response::new(SubscriberList {
+            subscribers: self.subscribers.list(),
+        }))
+    }
+
+    async fn set_log_level(
+        &self,
+        request: Request<SetLogLevelRequest>,
+    ) -> Result<Response<LogFilter>, Status> {
+        self.auth.authorize(&request)?;
+        let filter = request.into_inner().filter;
+
+        let previous_filter = telemetry::set_log_filter(&filter)
+            .ok_or_else(|| {
+                Status::failed_precondition("Logging is managed by the embedding application")
+            })?
+            .map_err(|err| Status::invalid_argument(format!("{err:#}")))?;
+
+        Ok(Response::new(LogFilter {
+            filter: telemetry::log_filter().unwrap_or_default(),
+            previous_filter,
+        }))
+    }
+
+    async fn dump_metrics(&self, request: Request<Empty>) -> Result<Response<MetricsDump>, Status> {
+        self.auth.authorize(&request)?;
+
+        let dump = self
+            .metrics_snapshots
+            .dump()
+            .await
+            .ok_or_else(|| Status::failed_precondition("Metrics snapshots aren't configured"))?
+            .map_err(|err| Status::internal(format!("{err:#}")))?;
+        Ok(Response::new(dump))
+    }
+
+    async fn drain(&self, request: Request<DrainRequest>) -> Result<Response<DrainResult>, Status> {
+        self.auth.authorize(&request)?;
+
+        let deadline_secs = request.into_inner().deadline_secs;
+        let deadline = (deadline_secs > 0).then(|| Duration::from_secs(deadline_secs.into()));
+        let result = self.drain.drain(deadline, &self.subscribers).await?;
+        Ok(Response::new(result))
+    }
+
+    async fn list_exchanges(
+        &self,
+        request: Request<Empty>,
+    ) -> Result<Response<ExchangeList>, Status> {
+        self.auth.authorize(&request)?;
+
+        let exchanges = self.exchanges.iter().map(ExchangeInfo::to_proto).collect();
+        Ok(Response::new(ExchangeList { exchanges }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::{Code, Request};
+
+    use order_book_service_types::proto::{
+        orderbook_admin_server::OrderbookAdmin, DrainRequest, SetFrameTapRequest,
+        SetLogLevelRequest,
+    };
+
+    use crate::{
+        config::{DrainConfig, MetricsSnapshotConfig, TapConfig},
+        drain::Drain,
+        exchange::ExchangeInfo,
+        latency::ExchangeLatencies,
+        snapshots::MetricsSnapshots,
+        subscribers::Subscribers,
+        tap::FrameTap,
+        tenancy::{AdminAuth, Tenants},
+    };
+
+    use super::AdminService;
+
+    fn service() -> AdminService {
+        AdminService {
+            frame_tap: FrameTap::new(TapConfig::default()),
+            auth: AdminAuth::new(Tenants::default(), Some("admin-key".to_string())),
+            latencies: ExchangeLatencies::default(),
+            subscribers: Subscribers::default(),
+            metrics_snapshots: MetricsSnapshots::new(&MetricsSnapshotConfig::default()),
+            drain: Drain::new(&DrainConfig::default()),
+            exchanges: vec![ExchangeInfo::new("Bitstamp")],
+        }
+    }
+
+    fn admin_request<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request
+            .metadata_mut()
+            .insert("x-api-key", "admin-key".parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn should_toggle_frame_tap_per_exchange() {
+        let service = service();
+
+        let status = service
+            .set_frame_tap(admin_request(SetFrameTapRequest {
+                exchange: "bitstamp".to_string(),
+                enabled: true,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(status.enabled_exchanges, vec!["Bitstamp"]);
+
+        let missing_exchange = service
+            .set_frame_tap(admin_request(SetFrameTapRequest {
+                exchange: String::new(),
+                enabled: true,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(missing_exchange.code(), Code::InvalidArgument);
+
+        let unknown_exchange = service
+            .set_frame_tap(admin_request(SetFrameTapRequest {
+                exchange: "Bitstmap".to_string(),
+                enabled: true,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(unknown_exchange.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn should_refuse_log_levels_when_logging_is_embedded() {
+        let service = service();
+
+        // Tests don't install the service's subscriber, as an embedding application wouldn't
+        let status = service
+            .set_log_level(admin_request(SetLogLevelRequest {
+                filter: "order_book_service_server::exchanges::binance=debug".to_string(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn should_refuse_requests_without_the_admin_key() {
+        let status = service()
+            .set_log_level(Request::new(SetLogLevelRequest {
+                filter: "debug".to_string(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn should_refuse_drains_without_the_admin_key() {
+        let mut without_admin = service();
+        without_admin.auth = AdminAuth::new(Tenants::default(), None);
+
+        let mut wrong_key = Request::new(DrainRequest { deadline_secs: 1 });
+        wrong_key
+            .metadata_mut()
+            .insert("x-api-key", "not-the-admin-key".parse().unwrap());
+        for (admin, request, code) in [
+            (
+                service(),
+                Request::new(DrainRequest { deadline_secs: 1 }),
+                Code::Unauthenticated,
+            ),
+            (service(), wrong_key, Code::Unauthenticated),
+            (
+                without_admin,
+                admin_request(DrainRequest { deadline_secs: 1 }),
+                Code::PermissionDenied,
+            ),
+        ] {
+            let status = admin.drain(request).await.unwrap_err();
+            assert_eq!(status.code(), code);
+            // A refused drain mustn't start, new subscriptions are still admitted
+            assert!(admin.drain.admit().is_ok());
+        }
+    }
+}
@@ -1,95 +1,543 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::Error;
-use futures_util::{stream::SelectAll, StreamExt};
-use tokio::sync::broadcast::{channel as broadcast_channel, Sender as BroadcastSender};
+use futures_util::{
+    future::{ready, Future},
+    stream::{once, BoxStream, SelectAll},
+    StreamExt,
+};
+use tokio::{
+    select,
+    sync::{
+        broadcast::{channel as broadcast_channel, Sender as BroadcastSender},
+        mpsc::{channel as mpsc_channel, Sender as MpscSender},
+        watch::{channel as watch_channel, Receiver as WatchReceiver, Sender as WatchSender},
+    },
+    time::{sleep_until, Instant},
+};
 use tokio_stream::wrappers::ReceiverStream;
-use tracing::{debug, error, warn};
+use tracing::{debug, error, info_span, warn};
 
-use order_book_service_types::proto::{Summary, TradedPair};
+use order_book_service_types::{
+    proto::{
+        service_event::Event, AggregatorStall, AskLevel, BidLevel, ExchangeId, Level,
+        SourceTimestamp, SubscriptionDescription, SubscriptionSource, Summary, SummaryMetadata,
+        TradedPair,
+    },
+    retry::Retry,
+};
 
 use crate::{
-    exchange::{BoxedExchange, BoxedOrderbook},
+    clock::SharedClock,
+    config::{Config, QuoteConversionConfig},
+    consistency::ConsistencyMonitor,
+    conversion::{conversions_for_pair, source_pair_for_exchange, QuoteConversion},
+    error::{error_chain, AggregatorError, ExchangeError},
+    events::EventBus,
+    exchange::{BoxedExchange, BoxedOrderbook, DepthHint, ReceivedOrderbook},
+    exchange_status::{contributors_in_maintenance, MaintenanceReceiver},
     grpc_server::SummaryReceiver,
+    latency::ExchangeLatencies,
+    liquidity::LiquidityMonitor,
+    metrics::{
+        book_levels, book_levels_evicted, pair_label, summaries_published, summaries_suppressed,
+    },
+    pairs::{still_listed, PairsRequest},
+    quality::{mid_dispersion, QualityInputs},
+    shared_encoding::SummaryTick,
+    transform::{transforms_for_pair, SummaryTransform},
+    validation::FeedValidator,
 };
 
-type SummarySender = BroadcastSender<Result<Summary, Arc<Error>>>;
+/// How many levels of each side are included in a [Summary] when a subscription doesn't request a depth
+pub(crate) const SUMMARY_DEPTH: usize = 10;
+/// The most levels of each side a subscription can request, unless configured otherwise
+pub(crate) const MAX_SUMMARY_DEPTH: usize = 100;
+/// Attempts made to stream from each exchange before aggregating without it
+const MAX_CONNECT_ATTEMPTS: usize = 5;
+/// Wait before retrying to stream from an exchange, doubling for each retry up to the max
+const CONNECT_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_CONNECT_BACKOFF: Duration = Duration::from_secs(2);
+/// How often the exchanges' mean latencies, which order levels at the same price, are brought up to date.
+const LATENCY_REFRESH: Duration = Duration::from_secs(1);
+
+/// Each summary is sent with the full depth book it was built from.
+type SummarySender = BroadcastSender<Result<Arc<SummaryTick>, Arc<AggregatorError>>>;
+/// Each source exchange's books, merged into one stream.
+type SourceStreams = SelectAll<BoxStream<'static, SourceEvent>>;
+
+/// What a source exchange's stream yields to the aggregator.
+enum SourceEvent {
+    Orderbook(ReceivedOrderbook),
+    /// The exchange's stream ended, e.g. the socket dropped or the exchange rejected the subscription
+    Ended(ExchangeId),
+}
+/// The latest [MergedBook] produced by an aggregator, `None` until the first merge.
+pub(crate) type BookReceiver = WatchReceiver<Option<Arc<MergedBook>>>;
+
+/// The asks and bids from all source exchanges merged at full depth.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct MergedBook {
+    /// Ordered Low->High by price
+    pub(crate) asks: Vec<Level>,
+    /// Ordered High->Low by price
+    pub(crate) bids: Vec<Level>,
+}
+
+impl MergedBook {
+    /// Construct a [Summary] from up to the best `depth` levels of each side, a thinner side is returned whole.
+    pub(crate) fn summary(&self, depth: usize) -> Summary {
+        let asks = self.asks.iter().take(depth).cloned().collect::<Vec<_>>();
+        let bids = self.bids.iter().take(depth).cloned().collect::<Vec<_>>();
+
+        // There's no spread without both sides, e.g. a snapshot of a book a transform has emptied a side of
+        let spread = match (asks.first(), bids.first()) {
+            (Some(ask), Some(bid)) => ask.price - bid.price,
+            _ => 0.0,
+        };
+
+        let mut summary = Summary {
+            spread,
+            asks,
+            bids,
+            ..Default::default()
+        };
+        summary.update_totals();
+        summary
+    }
+
+    /// Evict levels beyond the best `max_levels` of each side, returning how many asks and bids were evicted.
+    pub(crate) fn truncate(&mut self, max_levels: usize) -> (usize, usize) {
+        let evicted = (
+            self.asks.len().saturating_sub(max_levels),
+            self.bids.len().saturating_sub(max_levels),
+        );
+        self.asks.truncate(max_levels);
+        self.bids.truncate(max_levels);
+        evicted
+    }
+}
+
+/// Everything required to consume the output of an aggregator.
+#[derive(Debug)]
+pub(crate) struct AggregatorHandle {
+    pub(crate) summary_receiver: SummaryReceiver,
+    pub(crate) book_receiver: BookReceiver,
+    /// The effective parameters of the aggregator
+    pub(crate) description: SubscriptionDescription,
+    pub(crate) depth_requests: DepthRequests,
+    /// Nothing is sent, it closes once the aggregator is dropped
+    running: WatchReceiver<()>,
+}
+
+impl AggregatorHandle {
+    /// Resolves once the aggregator has stopped, e.g. having retired its pair or lost too many exchanges.
+    pub(crate) fn stopped(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut running = self.running.clone();
+        async move { while running.changed().await.is_ok() {} }
+    }
+}
+
+impl Clone for AggregatorHandle {
+    fn clone(&self) -> Self {
+        Self {
+            summary_receiver: self.summary_receiver.resubscribe(),
+            book_receiver: self.book_receiver.clone(),
+            description: self.description.clone(),
+            depth_requests: self.depth_requests.clone(),
+            running: self.running.clone(),
+        }
+    }
+}
+
+/// The depth requested by each active subscription, the largest is passed to exchanges as a [DepthHint].
+#[derive(Clone, Debug)]
+pub(crate) struct DepthRequests {
+    requested: Arc<Mutex<Vec<usize>>>,
+    hint_sender: Arc<WatchSender<usize>>,
+}
+
+impl DepthRequests {
+    fn new() -> Self {
+        let (hint_sender, _) = watch_channel(SUMMARY_DEPTH);
+        Self {
+            requested: Arc::new(Mutex::new(Vec::new())),
+            hint_sender: Arc::new(hint_sender),
+        }
+    }
+
+    /// Register a subscription's depth, it is released when the returned [DepthRequest] is dropped.
+    pub(crate) fn request(&self, depth: usize) -> DepthRequest {
+        let mut requested = self.requested.lock().expect("Should lock");
+        requested.push(depth);
+        self.update_hint(&requested);
+
+        DepthRequest {
+            depth_requests: self.clone(),
+            depth,
+        }
+    }
+
+    fn release(&self, depth: usize) {
+        let mut requested = self.requested.lock().expect("Should lock");
+        if let Some(index) = requested.iter().position(|requested| *requested == depth) {
+            requested.swap_remove(index);
+        }
+        self.update_hint(&requested);
+    }
+
+    fn update_hint(&self, requested: &[usize]) {
+        let depth = requested.iter().max().copied().unwrap_or(SUMMARY_DEPTH);
+        if *self.hint_sender.borrow() != depth {
+            self.hint_sender.send_replace(depth);
+        }
+    }
+
+    /// Whether no subscriptions are currently open.
+    fn is_empty(&self) -> bool {
+        self.requested.lock().expect("Should lock").is_empty()
+    }
+
+    /// The largest depth currently requested.
+    fn current(&self) -> usize {
+        *self.hint_sender.borrow()
+    }
+
+    fn hint(&self) -> DepthHint {
+        self.hint_sender.subscribe()
+    }
+}
+
+/// A subscription's claim on a depth, see [DepthRequests::request].
+#[derive(Debug)]
+pub(crate) struct DepthRequest {
+    depth_requests: DepthRequests,
+    depth: usize,
+}
+
+impl Drop for DepthRequest {
+    fn drop(&mut self) {
+        self.depth_requests.release(self.depth);
+    }
+}
 
 pub(crate) struct OrderbookAggregator {
     source_exchanges: Vec<BoxedExchange>,
     traded_pair: TradedPair,
     summary_sender: SummarySender,
+    book_sender: WatchSender<Option<Arc<MergedBook>>>,
+    /// Held only so that handles can tell when the aggregator has stopped
+    running: WatchSender<()>,
+    maintenance_receiver: MaintenanceReceiver,
+    quote_conversions: Vec<QuoteConversionConfig>,
+    consistency_monitor: ConsistencyMonitor,
+    liquidity_monitor: LiquidityMonitor,
+    feed_validator: FeedValidator,
+    description: SubscriptionDescription,
+    suppress_duplicate_summaries: bool,
+    depth_requests: DepthRequests,
+    transforms: Vec<Box<dyn SummaryTransform>>,
+    latencies: ExchangeLatencies,
+    event_bus: EventBus,
+    /// Reconnect to the exchanges when nothing has been merged for this long while there are subscribers
+    stall_timeout: Option<Duration>,
+    /// Leave out books produced this long before the freshest
+    timestamp_tolerance: Option<Duration>,
+    /// Check a source still lists the pair once it has been silent for this long
+    delisting_check: Option<Duration>,
+    /// Most levels retained on each side of the merged book
+    max_book_levels: usize,
+    /// Fewest live sources the pair is aggregated from before stopping
+    min_sources: usize,
+    /// Read for when books are received and how long sources have been quiet
+    clock: SharedClock,
 }
 
 impl OrderbookAggregator {
-    pub(crate) fn new(source_exchanges: &[BoxedExchange], traded_pair: TradedPair) -> Self {
-        let (summary_sender, _) = broadcast_channel(100);
+    pub(crate) fn new(
+        source_exchanges: &[BoxedExchange],
+        traded_pair: TradedPair,
+        maintenance_receiver: MaintenanceReceiver,
+        event_bus: EventBus,
+        latencies: ExchangeLatencies,
+        clock: SharedClock,
+        config: &Config,
+    ) -> Self {
+        let (summary_sender, _) = broadcast_channel(config.channels.summaries);
+        let (book_sender, _) = watch_channel(None);
+
+        let sources = source_exchanges
+            .iter()
+            .map(|exchange| {
+                let source_pair = source_pair_for_exchange(
+                    &config.quote_conversions,
+                    &traded_pair,
+                    &exchange.id(),
+                );
+                SubscriptionSource {
+                    exchange: exchange.name().to_string(),
+                    symbol: exchange.symbol_for_pair(&source_pair),
+                    source_pair: Some(source_pair),
+                }
+            })
+            .collect();
+
+        let transforms = transforms_for_pair(config, &traded_pair);
+        let max_book_levels = config.aggregator.max_book_levels(&traded_pair);
+
+        let description = SubscriptionDescription {
+            traded_pair: Some(traded_pair.clone()),
+            depth: SUMMARY_DEPTH as u32,
+            heartbeat_interval_millis: config.heartbeat_interval().as_millis() as u64,
+            sources,
+            max_mid_deviation: config.consistency.max_mid_deviation,
+            transforms: transforms
+                .iter()
+                .map(|transform| transform.name().to_string())
+                .collect(),
+        };
+
+        let consistency_monitor = ConsistencyMonitor::new(
+            traded_pair.clone(),
+            config.consistency.max_mid_deviation,
+            event_bus.clone(),
+        );
+        let liquidity_monitor = LiquidityMonitor::new(
+            traded_pair.clone(),
+            config.liquidity_threshold(&traded_pair),
+            event_bus.clone(),
+        );
+        let feed_validator = FeedValidator::new(
+            traded_pair.clone(),
+            config.validation.clone(),
+            event_bus.clone(),
+            clock.clone(),
+        );
 
         Self {
             source_exchanges: source_exchanges.to_vec(),
             traded_pair,
             summary_sender,
+            book_sender,
+            running: watch_channel(()).0,
+            maintenance_receiver,
+            quote_conversions: config.quote_conversions.clone(),
+            consistency_monitor,
+            liquidity_monitor,
+            feed_validator,
+            description,
+            suppress_duplicate_summaries: config.aggregator.suppress_duplicate_summaries,
+            depth_requests: DepthRequests::new(),
+            transforms,
+            latencies,
+            event_bus,
+            stall_timeout: config.aggregator.stall_timeout(),
+            timestamp_tolerance: config.aggregator.timestamp_tolerance(),
+            delisting_check: config.aggregator.delisting_check(),
+            max_book_levels,
+            min_sources: config.aggregator.min_sources(),
+            clock,
         }
     }
 
-    pub(crate) async fn start(self) {
-        // Loop through each source exchange. For each try to connect and get a stream for the desired traded-pair.
-        // If the attempt fails retry for a number of times.
-        // If successful push the receiver and break out of the retry loop.
-        let mut last_error = None;
-        let mut orderbook_stream = SelectAll::new();
-        for exchange in self.source_exchanges.iter() {
-            let mut attempts = 0;
-            let max_attempts = 5;
-
-            while attempts < max_attempts {
-                attempts += 1;
-                match exchange.stream_order_book_for_pair(&self.traded_pair) {
-                    Ok(rx) => {
-                        orderbook_stream.push(ReceiverStream::new(rx));
-                        break;
-                    }
-                    Err(err) => {
-                        error!("{err}");
-                        last_error = Some(err);
-                        warn!(
-                            "Unable to connect to {} for pair {}. Retrying...({attempts}/{max_attempts})",
-                            exchange.name(),
-                            &self.traded_pair,
-                        )
-                    }
-                }
+    pub(crate) async fn start(mut self) {
+        // Some exchanges may need to be sourced from a pair with a different quote currency
+        let conversions = match conversions_for_pair(
+            &self.quote_conversions,
+            &self.traded_pair,
+            &self.source_exchanges,
+        ) {
+            Ok(conversions) => conversions,
+            Err(err) => {
+                error!("{err}");
+                let _ = self.summary_sender.send(Err(Arc::new(err)));
+                return;
             }
-        }
-
-        if orderbook_stream.len() < 2 {
-            let mut err_msg = format!(
-                "Unable to connect to more than one exchange, aggregation not possible for {}",
-                self.traded_pair
-            );
+        };
 
-            if let Some(error) = last_error {
-                let cause = format!("\nCaused by: {error}");
-                err_msg.push_str(&cause);
+        let (mut orderbook_stream, mut live_sources) = match connect_sources(
+            self.source_exchanges.clone(),
+            &self.traded_pair,
+            &conversions,
+            &self.depth_requests,
+            self.min_sources,
+        )
+        .await
+        {
+            Ok(connected) => connected,
+            Err(err) => {
+                error!("{}", error_chain(&err));
+                // Inform connected clients of the failure
+                let _ = self.summary_sender.send(Err(Arc::new(err)));
+                return;
             }
-
-            error!("{err_msg}");
-            // Inform connected clients of the failure
-            let _ = self.summary_sender.send(Err(Arc::new(Error::msg(err_msg))));
-            return;
-        }
+        };
 
         let mut orderbooks = HashMap::new();
+        let mut receipt_spans = HashMap::new();
+        let mut timestamps = HashMap::new();
+        let mut last_summary_hash = None;
+        let pair = pair_label(&self.traded_pair);
+        let summaries_suppressed = summaries_suppressed(&pair);
+        let summaries_published = summaries_published(&pair);
+        let (ask_levels, bid_levels) = (book_levels(&pair, "asks"), book_levels(&pair, "bids"));
+        let (asks_evicted, bids_evicted) = (
+            book_levels_evicted(&pair, "asks"),
+            book_levels_evicted(&pair, "bids"),
+        );
+        let mut last_merge = self.clock.now();
+        let mut merge_capacity = MergeCapacity::default();
+        let (mut latency_means, mut latency_means_at) = (self.latencies.means(), self.clock.now());
+        // Sources which no longer list the pair, they aren't reconnected to
+        let mut delisted = HashSet::new();
+        // Listings are checked in the background so merging carries on meanwhile, a check can take seconds
+        let (listing_sender, mut listing_checks) = mpsc_channel(self.source_exchanges.len().max(1));
+        let mut last_heard = heard_now(&live_sources, self.clock.now());
+        // Sources which have since dropped out are listed as missing from summaries
+        let mut aggregated = live_sources.clone();
 
         let mut print_reducer = 0;
-        while let Some((orderbook, received)) = orderbook_stream.next().await {
-            // Check that there is still more than one exchange sending orderbooks
-            if orderbook_stream.len() < 2 {
-                let err_msg = "Exchange disconnected, leaving only one connection - unable to aggregate, exiting";
-                error!("{err_msg}");
-                let _ = self.summary_sender.send(Err(Arc::new(Error::msg(err_msg))));
-                return;
+        loop {
+            let stall_deadline = last_merge + self.stall_timeout.unwrap_or_default();
+            let delisting_check = self.delisting_check.unwrap_or_default();
+            let delisting_deadline = last_heard
+                .values()
+                .min()
+                .map(|heard| *heard + delisting_check)
+                .unwrap_or_else(|| self.clock.now());
+            let next = select! {
+                next = orderbook_stream.next() => next,
+                _ = sleep_until(delisting_deadline), if self.delisting_check.is_some() => {
+                    // A delisted pair may just go quiet rather than the exchange ending the stream
+                    let silent = last_heard
+                        .iter()
+                        .filter(|(_, heard)| self.clock.now() - **heard >= delisting_check)
+                        .map(|(exchange, _)| exchange.clone())
+                        .collect::<Vec<_>>();
+                    for exchange in silent {
+                        warn!(
+                            "{exchange} has sent nothing for {} in {delisting_check:?}, checking it still lists the pair",
+                            self.traded_pair
+                        );
+                        last_heard.insert(exchange.clone(), self.clock.now());
+                        self.check_listing(exchange, &conversions, &listing_sender);
+                    }
+                    continue;
+                }
+                Some((exchange, still_listed)) = listing_checks.recv() => {
+                    if still_listed {
+                        continue;
+                    }
+                    live_sources.remove(&exchange);
+                    last_heard.remove(&exchange);
+                    orderbooks.remove(&exchange);
+                    timestamps.remove(&exchange);
+                    receipt_spans.remove(&exchange);
+                    delisted.insert(exchange);
+                    if live_sources.len() < self.min_sources {
+                        self.stop_for_delisting(&delisted);
+                        return;
+                    }
+                    continue;
+                }
+                _ = sleep_until(stall_deadline), if self.stall_timeout.is_some() => {
+                    // Without subscribers nothing is owed, e.g. a warmed up pair may legitimately be quiet
+                    if self.depth_requests.is_empty() {
+                        last_merge = self.clock.now();
+                        continue;
+                    }
+
+                    // Both websockets can be open but idle, so the streams are replaced rather than waited on
+                    let quiet_for = self.clock.now() - last_merge;
+                    warn!(
+                        "Aggregator for {} hasn't merged a book for {quiet_for:?}, reconnecting to its exchanges",
+                        self.traded_pair
+                    );
+                    drop(orderbook_stream);
+                    let reconnected = connect_sources(
+                        self.source_exchanges
+                            .iter()
+                            .filter(|exchange| !delisted.contains(&exchange.id()))
+                            .cloned()
+                            .collect(),
+                        &self.traded_pair,
+                        &conversions,
+                        &self.depth_requests,
+                        self.min_sources,
+                    )
+                    .await;
+                    self.event_bus.publish(Event::AggregatorStall(AggregatorStall {
+                        traded_pair: Some(self.traded_pair.clone()),
+                        quiet_for_millis: quiet_for.as_millis() as u64,
+                        reconnected: reconnected.is_ok(),
+                    }));
+
+                    (orderbook_stream, live_sources) = match reconnected {
+                        Ok(connected) => connected,
+                        Err(err) => {
+                            error!("{}", error_chain(&err));
+                            let _ = self.summary_sender.send(Err(Arc::new(err)));
+                            return;
+                        }
+                    };
+                    // Books from before the stall are too old to merge with fresh ones
+                    orderbooks.clear();
+                    receipt_spans.clear();
+                    timestamps.clear();
+                    last_heard = heard_now(&live_sources, self.clock.now());
+                    // Sources which didn't come back are still missing
+                    aggregated.extend(live_sources.iter().cloned());
+                    last_merge = self.clock.now();
+                    continue;
+                }
+            };
+            let (orderbook, received, receipt) = match next {
+                Some(SourceEvent::Orderbook(received)) => received,
+                Some(SourceEvent::Ended(exchange)) => {
+                    live_sources.remove(&exchange);
+                    last_heard.remove(&exchange);
+                    orderbooks.remove(&exchange);
+                    timestamps.remove(&exchange);
+                    receipt_spans.remove(&exchange);
+
+                    // Check that there is still more than one exchange sending orderbooks, or one in degraded mode
+                    if live_sources.len() < self.min_sources {
+                        // An exchange may end the stream when it delists the pair, rather than just disconnecting.
+                        // Nothing is left to merge, so the check is waited on to tell subscribers which it was
+                        if is_delisted(self.listing(&exchange, &conversions)).await {
+                            delisted.insert(exchange);
+                        }
+                        if !delisted.is_empty() {
+                            self.stop_for_delisting(&delisted);
+                        } else {
+                            let err =
+                                AggregatorError::ExchangeDisconnected(self.traded_pair.clone());
+                            error!("{err}");
+                            let _ = self.summary_sender.send(Err(Arc::new(err)));
+                        }
+                        return;
+                    }
+                    if let [remaining] = live_sources.iter().collect::<Vec<_>>()[..] {
+                        warn!(
+                            "Only {remaining} remains for {}, its summaries are flagged degraded",
+                            self.traded_pair
+                        );
+                    }
+                    // Delisted exchanges aren't reconnected to after a stall
+                    self.check_listing(exchange, &conversions, &listing_sender);
+                    continue;
+                }
+                None => break,
+            };
+            let source = orderbook.source();
+            if !live_sources.contains(&source) {
+                continue;
             }
+            last_heard.insert(source, self.clock.now());
 
             print_reducer += 1;
 
@@ -101,94 +549,595 @@ impl OrderbookAggregator {
                 );
             }
 
-            orderbooks.insert(orderbook.source(), (orderbook, received));
+            // Books sourced from a different quote currency are converted before merging
+            let orderbook = match conversions
+                .iter()
+                .find(|conversion| *conversion.exchange() == orderbook.source())
+            {
+                Some(conversion) => match conversion.convert(orderbook) {
+                    Some(converted) => converted,
+                    // There's no rate yet so the book can't be used
+                    None => continue,
+                },
+                None => orderbook,
+            };
+
+            let source = orderbook.source();
+            // The exchange's previous book has been replaced, so it's out of date even if this one can't be merged
+            if !self.feed_validator.admit(orderbook.as_ref()) {
+                orderbooks.remove(&source);
+                timestamps.remove(&source);
+                receipt_spans.remove(&source);
+                continue;
+            }
+            let received_at = self.clock.system_time() - (self.clock.now() - received);
+            let timestamp = match orderbook.exchange_timestamp() {
+                Some(produced) => {
+                    let latency = received_at.duration_since(produced).unwrap_or_default();
+                    self.latencies.record(&source, latency);
+                    SourceTimes {
+                        exchange: Some(produced),
+                        corrected: self.latencies.correct_skew(&source, produced, received_at),
+                    }
+                }
+                None => SourceTimes {
+                    exchange: None,
+                    corrected: received_at,
+                },
+            };
+            timestamps.insert(source.clone(), timestamp);
+            receipt_spans.insert(source.clone(), receipt);
+            orderbooks.insert(source, (orderbook, received));
 
             // If the buffer has more than one orderbook stored then we can generate a summary - this also clears the map to prevent stale data carrying over.
-            if orderbooks.keys().len() > 1 {
-                // todo check timestamps are within a specified tolerance
+            // Once degraded there's only one source left to have stored a book
+            let needed = live_sources.len().min(2);
+            if orderbooks.keys().len() >= needed {
+                let merge_started = self.clock.now();
+                // Leave out books which trail the freshest, the books will be merged again once they're updated
+                let stale = stale_sources(&timestamps, self.timestamp_tolerance);
+                if !stale.is_empty() {
+                    orderbooks.retain(|exchange, _| !stale.contains(exchange));
+                    timestamps.retain(|exchange, _| !stale.contains(exchange));
+                    receipt_spans.retain(|exchange, _| !stale.contains(exchange));
+                    if orderbooks.len() < needed {
+                        continue;
+                    }
+                }
+                let mut stale_exchanges =
+                    stale.iter().map(ExchangeId::to_string).collect::<Vec<_>>();
+                stale_exchanges.sort_unstable();
+
+                // Leave out any exchange whose mid price has drifted away from the others
+                let excluded = self.consistency_monitor.check(&orderbooks);
+                orderbooks.retain(|exchange, _| !excluded.contains(exchange));
+                // Too few agree to publish, as when too few are streaming
+                if orderbooks.len() < self.min_sources {
+                    continue;
+                }
+                let mut excluded_exchanges = excluded
+                    .iter()
+                    .map(ExchangeId::to_string)
+                    .collect::<Vec<_>>();
+                excluded_exchanges.sort_unstable();
+
+                // Link the merge to the exchange messages which contributed to it
+                let merge_span = info_span!("merge", pair = %self.traded_pair);
+                for (exchange, receipt) in receipt_spans.drain() {
+                    if orderbooks.contains_key(&exchange) {
+                        merge_span.follows_from(&receipt);
+                    }
+                }
+                let _merging = merge_span.enter();
+
+                // Annotate the summary with any contributing exchanges that are in maintenance
+                let exchanges_in_maintenance = contributors_in_maintenance(
+                    orderbooks.keys().map(ExchangeId::as_str),
+                    &self.maintenance_receiver.borrow(),
+                );
+
+                // Record any conversions applied to contributing exchanges
+                let quote_conversions = conversions
+                    .iter()
+                    .filter(|conversion| orderbooks.contains_key(conversion.exchange()))
+                    .map(|conversion| conversion.to_proto())
+                    .collect();
+
+                let contributing_exchanges = orderbooks.len() as u32;
+                let now = self.clock.system_time();
+                let max_source_age = timestamps
+                    .iter()
+                    .filter(|(exchange, _)| orderbooks.contains_key(*exchange))
+                    .map(|(_, times)| now.duration_since(times.corrected).unwrap_or_default())
+                    .max()
+                    .unwrap_or_default();
+                let mut mids = orderbooks
+                    .values()
+                    .filter_map(|(orderbook, _)| {
+                        Some((orderbook.best_ask()?.price + orderbook.best_bid()?.price) / 2.0)
+                    })
+                    .collect::<Vec<_>>();
+                let quality_inputs = QualityInputs {
+                    contributing: orderbooks.len(),
+                    aggregated: aggregated.len(),
+                    max_age: max_source_age,
+                    mid_dispersion: mid_dispersion(&mut mids),
+                };
+                let mut source_timestamps = timestamps
+                    .drain()
+                    .filter(|(exchange, _)| orderbooks.contains_key(exchange))
+                    .map(|(exchange, times)| times.to_proto(&exchange))
+                    .collect::<Vec<_>>();
+                source_timestamps.sort_unstable_by(|a, b| a.exchange.cmp(&b.exchange));
+
+                if self.clock.now() - latency_means_at >= LATENCY_REFRESH {
+                    (latency_means, latency_means_at) = (self.latencies.means(), self.clock.now());
+                }
+                // No source's levels beyond the cap could be retained, so they aren't merged at all
+                let mut merged_book = merge_orderbooks(
+                    orderbooks.drain().map(|(_, value)| value.0),
+                    self.max_book_levels,
+                    &latency_means,
+                    &mut merge_capacity,
+                );
+                last_merge = self.clock.now();
+
+                for transform in self.transforms.iter() {
+                    transform.apply(&mut merged_book);
+                }
 
-                let summary =
-                    merge_orderbooks_into_summary(orderbooks.drain().map(|(_, value)| value.0));
+                // Huge sparse books would otherwise grow the retained book without bound
+                let (asks, bids) = merged_book.truncate(self.max_book_levels);
+                asks_evicted.inc_by(asks as u64);
+                bids_evicted.inc_by(bids as u64);
+                ask_levels.set(merged_book.asks.len() as i64);
+                bid_levels.set(merged_book.bids.len() as i64);
+
+                // A transform may have removed every level from a side, leaving nothing to summarise
+                if merged_book.asks.is_empty() || merged_book.bids.is_empty() {
+                    warn!("Merged book for {} has an empty side", self.traded_pair);
+                    continue;
+                }
+
+                // Checked against the full book, the threshold may cover more levels than are sent
+                let low_liquidity = self.liquidity_monitor.check(&merged_book);
+
+                let mut summary = merged_book.summary(self.depth_requests.current());
+                summary.exchanges_in_maintenance = exchanges_in_maintenance;
+                // As few as one may be left streaming, or agreeing with the others
+                summary.degraded = live_sources.len() < 2 || contributing_exchanges < 2;
+                summary.missing_exchanges = aggregated
+                    .difference(&live_sources)
+                    .map(ExchangeId::to_string)
+                    .collect();
+                summary.missing_exchanges.sort_unstable();
+                let (quality_score, quality) =
+                    quality_inputs.assess(self.description.max_mid_deviation);
+                summary.quality_score = quality_score;
+                summary.set_quality(quality);
+                summary.metadata = Some(SummaryMetadata {
+                    quote_conversions,
+                    excluded_exchanges,
+                    source_timestamps,
+                    stale_exchanges,
+                    low_bid_liquidity: low_liquidity.bids,
+                    low_ask_liquidity: low_liquidity.asks,
+                    contributing_exchanges,
+                    max_source_age_micros: max_source_age.as_micros() as u64,
+                    mid_dispersion: quality_inputs.mid_dispersion,
+                    ..Default::default()
+                });
+
+                // Retain the full depth book for request/response style queries
+                let merged_book = Arc::new(merged_book);
+                self.book_sender.send_replace(Some(merged_book.clone()));
+
+                // Exchanges often resend identical books, there's no need to send the same summary again
+                if self.suppress_duplicate_summaries {
+                    let summary_hash = hash_summary(&summary);
+                    if last_summary_hash == Some(summary_hash) {
+                        summaries_suppressed.inc();
+                        continue;
+                    }
+                    last_summary_hash = Some(summary_hash);
+                }
+
+                // Left out of the hash, it differs between otherwise identical summaries
+                if let Some(metadata) = &mut summary.metadata {
+                    metadata.merge_duration_micros =
+                        (self.clock.now() - merge_started).as_micros() as u64;
+                }
 
                 // Send the summary to all subscribers
-                let _ = self.summary_sender.send(Ok(summary));
+                let tick = SummaryTick::new(summary, merged_book, merge_span.clone());
+                let _ = self.summary_sender.send(Ok(Arc::new(tick)));
+                summaries_published.inc();
             }
         }
     }
 
-    /// Subscribe to the aggregator, returns a [SummaryReceiver].
-    pub(crate) fn subscribe(&self) -> SummaryReceiver {
-        self.summary_sender.subscribe()
+    /// A request for `exchange`'s pairs, along with the pair it's streamed for this aggregation.
+    fn listing(
+        &self,
+        exchange: &ExchangeId,
+        conversions: &[QuoteConversion],
+    ) -> Option<(PairsRequest, TradedPair)> {
+        let request = self
+            .source_exchanges
+            .iter()
+            .find(|source| source.id() == *exchange)?
+            .supported_pairs();
+        let pair = pair_for_exchange(conversions, &self.traded_pair, exchange).clone();
+        Some((request, pair))
+    }
+
+    /// Check in the background whether `exchange` still lists the pair, the answer is sent to `checks`.
+    fn check_listing(
+        &self,
+        exchange: ExchangeId,
+        conversions: &[QuoteConversion],
+        checks: &MpscSender<(ExchangeId, bool)>,
+    ) {
+        let listing = self.listing(&exchange, conversions);
+        let checks = checks.clone();
+        tokio::spawn(async move {
+            let still_listed = !is_delisted(listing).await;
+            let _ = checks.send((exchange, still_listed)).await;
+        });
+    }
+
+    /// Tell subscribers the pair can no longer be aggregated because exchanges have delisted it.
+    fn stop_for_delisting(&self, delisted: &HashSet<ExchangeId>) {
+        let mut delisted = delisted
+            .iter()
+            .map(ExchangeId::to_string)
+            .collect::<Vec<_>>();
+        delisted.sort_unstable();
+        let err = AggregatorError::PairRetired {
+            pair: self.traded_pair.clone(),
+            delisted,
+        };
+        error!("{err}");
+        let _ = self.summary_sender.send(Err(Arc::new(err)));
+    }
+
+    pub(crate) fn traded_pair(&self) -> &TradedPair {
+        &self.traded_pair
+    }
+
+    /// Send a summary relayed from another server to subscribers, as though its levels had been merged here.
+    pub(crate) fn publish_relayed(&self, relayed: Summary) {
+        if relayed.asks.is_empty() || relayed.bids.is_empty() {
+            warn!("Relayed summary for {} has an empty side", self.traded_pair);
+            return;
+        }
+        let merged_book = Arc::new(MergedBook {
+            asks: relayed.asks,
+            bids: relayed.bids,
+        });
+        let mut summary = merged_book.summary(self.depth_requests.current());
+        summary.exchanges_in_maintenance = relayed.exchanges_in_maintenance;
+        summary.metadata = relayed.metadata;
+        summary.degraded = relayed.degraded;
+        summary.missing_exchanges = relayed.missing_exchanges;
+
+        self.book_sender.send_replace(Some(merged_book.clone()));
+        let relay_span = info_span!("relay", pair = %self.traded_pair);
+        let tick = SummaryTick::new(summary, merged_book, relay_span);
+        let _ = self.summary_sender.send(Ok(Arc::new(tick)));
+        summaries_published(&pair_label(&self.traded_pair)).inc();
+    }
+
+    /// Subscribe to the aggregator, returns an [AggregatorHandle].
+    pub(crate) fn subscribe(&self) -> AggregatorHandle {
+        AggregatorHandle {
+            summary_receiver: self.summary_sender.subscribe(),
+            book_receiver: self.book_sender.subscribe(),
+            description: self.description.clone(),
+            depth_requests: self.depth_requests.clone(),
+            running: self.running.subscribe(),
+        }
     }
 }
 
-/// Construct a [Summary] from a collection of [OrderBook]s
-fn merge_orderbooks_into_summary(orderbooks: impl Iterator<Item = BoxedOrderbook>) -> Summary {
-    let depth = 10;
-    // There has to be at least 2 orderbooks for the aggregator to work
-    let mut asks = Vec::with_capacity(2 * depth);
-    let mut bids = Vec::with_capacity(2 * depth);
+/// Stream the pair from each exchange, retrying with a backoff unless the exchange doesn't offer it.
+///
+/// Fails when fewer than `min_sources` exchanges could be streamed from, one in degraded mode and otherwise two as
+/// there would be nothing to aggregate.
+// Exchanges aren't Sync, so they're taken by value to be held across the waits between attempts
+async fn connect_sources(
+    exchanges: Vec<BoxedExchange>,
+    traded_pair: &TradedPair,
+    conversions: &[QuoteConversion],
+    depth_requests: &DepthRequests,
+    min_sources: usize,
+) -> Result<(SourceStreams, HashSet<ExchangeId>), AggregatorError> {
+    let mut last_error = None;
+    let mut orderbook_stream = SelectAll::new();
+    let mut connected = HashSet::new();
+    for exchange in exchanges {
+        let mut retry = Retry::exponential(CONNECT_BACKOFF, MAX_CONNECT_BACKOFF)
+            .with_max_attempts(MAX_CONNECT_ATTEMPTS)
+            .with_jitter(CONNECT_BACKOFF / 2);
+
+        let pair_to_stream = pair_for_exchange(conversions, traded_pair, &exchange.id());
+
+        while retry.next_attempt().await {
+            match exchange.stream_order_book_for_pair(pair_to_stream, depth_requests.hint()) {
+                Ok(rx) => {
+                    let ended = once(ready(SourceEvent::Ended(exchange.id())));
+                    orderbook_stream.push(
+                        ReceiverStream::new(rx)
+                            .map(SourceEvent::Orderbook)
+                            .chain(ended)
+                            .boxed(),
+                    );
+                    connected.insert(exchange.id());
+                    break;
+                }
+                Err(err) => {
+                    error!("{err}");
+                    let unsupported = matches!(err, ExchangeError::UnsupportedPair { .. });
+                    last_error = Some(err);
+                    if unsupported {
+                        break;
+                    }
+                    warn!(
+                        "Unable to connect to {} for pair {}. Retrying...({}/{MAX_CONNECT_ATTEMPTS})",
+                        exchange.name(),
+                        pair_to_stream,
+                        retry.attempts(),
+                    )
+                }
+            }
+        }
+    }
+
+    if orderbook_stream.len() < min_sources {
+        return Err(AggregatorError::TooFewExchanges {
+            pair: traded_pair.clone(),
+            source: last_error,
+        });
+    }
+    Ok((orderbook_stream, connected))
+}
+
+/// The pair streamed from `exchange`, some exchanges are sourced from a pair with a different quote currency.
+fn pair_for_exchange<'a>(
+    conversions: &'a [QuoteConversion],
+    traded_pair: &'a TradedPair,
+    exchange: &ExchangeId,
+) -> &'a TradedPair {
+    conversions
+        .iter()
+        .find(|conversion| conversion.exchange() == exchange)
+        .map_or(traded_pair, |conversion| conversion.source_pair())
+}
+
+/// Whether an exchange has stopped listing the pair, one which can't be asked is assumed to still list it.
+async fn is_delisted(listing: Option<(PairsRequest, TradedPair)>) -> bool {
+    let Some((request, pair)) = listing else {
+        return false;
+    };
+    still_listed(request, &pair).await == Some(false)
+}
+
+fn heard_now(sources: &HashSet<ExchangeId>, now: Instant) -> HashMap<ExchangeId, Instant> {
+    sources
+        .iter()
+        .map(|exchange| (exchange.clone(), now))
+        .collect()
+}
+
+/// Hash the content of a summary so that duplicates can be detected without keeping the previous summary.
+fn hash_summary(summary: &Summary) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    summary.spread.to_bits().hash(&mut hasher);
+    for level in summary.asks.iter().chain(summary.bids.iter()) {
+        level.exchange.hash(&mut hasher);
+        level.price.to_bits().hash(&mut hasher);
+        level.amount.to_bits().hash(&mut hasher);
+    }
+    summary.exchanges_in_maintenance.hash(&mut hasher);
+    summary.missing_exchanges.hash(&mut hasher);
+    if let Some(metadata) = &summary.metadata {
+        metadata.excluded_exchanges.hash(&mut hasher);
+        metadata.low_bid_liquidity.hash(&mut hasher);
+        metadata.low_ask_liquidity.hash(&mut hasher);
+        for conversion in metadata.quote_conversions.iter() {
+            conversion.rate.to_bits().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
 
-    // Loop through order books extending the above vecs with best asks and bids from each.
+/// When a source's latest book was produced.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SourceTimes {
+    /// As stamped by the exchange on its own clock
+    exchange: Option<SystemTime>,
+    /// On our clock, corrected for the exchange's skew or the receipt time when the exchange doesn't timestamp books
+    corrected: SystemTime,
+}
+
+impl SourceTimes {
+    fn to_proto(self, exchange: &ExchangeId) -> SourceTimestamp {
+        let micros = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros() as u64
+        };
+        SourceTimestamp {
+            exchange: exchange.to_string(),
+            exchange_timestamp_micros: self.exchange.map(micros).unwrap_or_default(),
+            corrected_timestamp_micros: micros(self.corrected),
+        }
+    }
+}
+
+/// Sources whose book was produced more than `tolerance` before the freshest, by their corrected timestamps.
+fn stale_sources(
+    timestamps: &HashMap<ExchangeId, SourceTimes>,
+    tolerance: Option<Duration>,
+) -> Vec<ExchangeId> {
+    let (Some(tolerance), Some(freshest)) = (
+        tolerance,
+        timestamps.values().map(|times| times.corrected).max(),
+    ) else {
+        return Vec::new();
+    };
+    timestamps
+        .iter()
+        .filter(|(_, times)| {
+            freshest
+                .duration_since(times.corrected)
+                .is_ok_and(|behind| behind > tolerance)
+        })
+        .map(|(exchange, _)| exchange.clone())
+        .collect()
+}
+
+/// The number of levels on each side of the last merge, before truncation.
+///
+/// Books change little from one update to the next, so each side of the next merge is allocated once at this size
+/// rather than growing as every exchange's levels are appended.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct MergeCapacity {
+    asks: usize,
+    bids: usize,
+}
+
+/// Construct a [MergedBook] from a collection of [OrderBook]s, keeping the best `depth` levels of each side of each.
+///
+/// Levels at the same price are ordered by their exchange's mean `latencies`, lowest first, as the fresher quote.
+/// Exchanges without a known latency are ranked at the average of those with one, neither favoured nor penalised.
+fn merge_orderbooks(
+    orderbooks: impl Iterator<Item = BoxedOrderbook>,
+    depth: usize,
+    latencies: &HashMap<String, Duration>,
+    capacity: &mut MergeCapacity,
+) -> MergedBook {
+    let mut asks = Vec::with_capacity(capacity.asks);
+    let mut bids = Vec::with_capacity(capacity.bids);
+
+    // Loop through order books filling the above vecs with all asks and bids from each.
     for ob in orderbooks {
-        asks.append(&mut ob.best_asks(depth));
-        bids.append(&mut ob.best_bids(depth));
+        ob.best_asks(depth, &mut asks);
+        ob.best_bids(depth, &mut bids);
     }
+    *capacity = MergeCapacity {
+        asks: asks.len(),
+        bids: bids.len(),
+    };
 
     // Sort the combined asks and bids
-    asks.sort_unstable_by(|a, b| a.sort_as_asks(b));
-    asks.truncate(depth);
-    bids.sort_unstable_by(|a, b| a.sort_as_bids(b));
-    bids.truncate(depth);
-
-    // This code panics if either of the vecs are empty - this shouldn't happen in practice but it
-    // could still be rewritten to propagate an error back to the caller -> client.
-    let spread = match (asks.first(), bids.first()) {
-        (Some(ask), Some(bid)) => ask.price - bid.price,
-        _ => panic!("Level vecs were empty"),
+    let neutral = match latencies.len() {
+        0 => Duration::ZERO,
+        known => latencies.values().sum::<Duration>() / known as u32,
     };
+    let latency = |level: &Level| latencies.get(&level.exchange).copied().unwrap_or(neutral);
+    asks.sort_unstable_by(|a, b| {
+        AskLevel::price_order(a, b)
+            .then_with(|| latency(a).cmp(&latency(b)))
+            .then_with(|| AskLevel::order(a, b))
+    });
+    bids.sort_unstable_by(|a, b| {
+        BidLevel::price_order(a, b)
+            .then_with(|| latency(a).cmp(&latency(b)))
+            .then_with(|| BidLevel::order(a, b))
+    });
 
-    Summary { spread, asks, bids }
+    MergedBook { asks, bids }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use futures::future::BoxFuture;
     use lazy_static::lazy_static;
+    use tokio::{
+        sync::{
+            mpsc::{channel, Receiver, Sender},
+            watch::channel as watch_channel,
+        },
+        time::{advance, timeout},
+    };
+    use tracing::Span;
 
-    use order_book_service_types::proto::{Level, Summary};
+    use order_book_service_types::proto::{ExchangeId, Level, Quality, Summary, TradedPair};
 
     use crate::{
-        aggregator::merge_orderbooks_into_summary,
-        exchange::{sort_orders_to_depth, BoxedOrderbook, Order, OrderBook, Ordering},
+        aggregator::{
+            hash_summary, merge_orderbooks, stale_sources, AggregatorHandle, DepthRequests,
+            MergeCapacity, OrderbookAggregator, SourceTimes, SUMMARY_DEPTH,
+        },
+        clock::{Clock, ManualClock},
+        config::Config,
+        error::ExchangeError,
+        events::EventBus,
+        exchange::{
+            sort_orders_to_depth, BoxedExchange, BoxedOrderbook, DepthHint, Exchange, ExchangeInfo,
+            Order, OrderBook, Ordering, ReceivedOrderbook,
+        },
+        latency::ExchangeLatencies,
     };
 
     struct TestOrderbook {
-        id: &'static str,
+        id: ExchangeId,
         asks: Vec<Order>,
         bids: Vec<Order>,
+        produced: Option<SystemTime>,
     }
 
     impl TestOrderbook {
-        fn new(id: &'static str, asks: Vec<Order>, bids: Vec<Order>) -> Self {
-            Self { id, asks, bids }
+        fn new(id: &str, asks: Vec<Order>, bids: Vec<Order>) -> Self {
+            Self {
+                id: ExchangeId::from(id),
+                asks,
+                bids,
+                produced: None,
+            }
+        }
+
+        /// Stamp the book with when the exchange produced it, on the exchange's clock.
+        fn produced_at(mut self, produced: SystemTime) -> Self {
+            self.produced = Some(produced);
+            self
         }
     }
 
     impl OrderBook for TestOrderbook {
-        fn source(&self) -> &'static str {
-            self.id
+        fn source(&self) -> ExchangeId {
+            self.id.clone()
         }
 
-        fn spread(&self) -> f64 {
-            self.best_asks(1)[0].price - self.best_bids(1)[0].price
+        fn exchange_timestamp(&self) -> Option<SystemTime> {
+            self.produced
         }
 
-        fn best_asks(&self, depth: usize) -> Vec<Level> {
-            sort_orders_to_depth(self.asks.clone(), Ordering::LowToHigh, depth, self.source())
+        fn best_asks(&self, depth: usize, levels: &mut Vec<Level>) {
+            sort_orders_to_depth(
+                &self.asks,
+                Ordering::LowToHigh,
+                depth,
+                &self.source(),
+                levels,
+            )
         }
 
-        fn best_bids(&self, depth: usize) -> Vec<Level> {
-            sort_orders_to_depth(self.bids.clone(), Ordering::HighToLow, depth, self.source())
+        fn best_bids(&self, depth: usize, levels: &mut Vec<Level>) {
+            sort_orders_to_depth(
+                &self.bids,
+                Ordering::HighToLow,
+                depth,
+                &self.source(),
+                levels,
+            )
         }
     }
 
@@ -233,7 +1182,13 @@ mod tests {
         let test_orderbooks: Vec<BoxedOrderbook> =
             vec![Box::new(test_orderbook_one), Box::new(test_orderbook_two)];
 
-        let merged_orderbook = merge_orderbooks_into_summary(test_orderbooks.into_iter());
+        let merged_orderbook = merge_orderbooks(
+            test_orderbooks.into_iter(),
+            usize::MAX,
+            &HashMap::new(),
+            &mut MergeCapacity::default(),
+        )
+        .summary(SUMMARY_DEPTH);
 
         let expected_summary = Summary {
             // The difference between the best ask (1.5) and the best bid (10.0)
@@ -264,8 +1219,394 @@ mod tests {
                 Level::new("TWO", 5.0, 2.0),
                 Level::new("ONE", 5.0, 1.0),
             ],
+            // Each price has 3 across both exchanges
+            ask_notional: 3.0 * (1.0 + 2.0 + 3.0 + 4.0 + 5.0),
+            bid_notional: 3.0 * (10.0 + 9.0 + 8.0 + 7.0 + 6.0),
+            ask_depth: 10,
+            bid_depth: 10,
+            ..Default::default()
         };
 
         assert_eq!(merged_orderbook, expected_summary);
     }
+
+    #[test]
+    fn should_retain_full_depth_when_merging() {
+        let test_orderbooks: Vec<BoxedOrderbook> = vec![
+            Box::new(TestOrderbook::new(
+                "ONE",
+                ORDERS_WHOLE_LEVELS_AT_ONE.clone(),
+                ORDERS_WHOLE_LEVELS_AT_ONE.clone(),
+            )),
+            Box::new(TestOrderbook::new(
+                "TWO",
+                ORDERS_WHOLE_LEVELS_AT_TWO.clone(),
+                ORDERS_WHOLE_LEVELS_AT_TWO.clone(),
+            )),
+        ];
+
+        let mut capacity = MergeCapacity::default();
+        let merged_book = merge_orderbooks(
+            test_orderbooks.into_iter(),
+            usize::MAX,
+            &HashMap::new(),
+            &mut capacity,
+        );
+
+        // Every level from both books is kept, beyond the depth of a summary
+        assert_eq!(capacity, MergeCapacity { asks: 20, bids: 20 });
+        assert_eq!(merged_book.asks.len(), 20);
+        assert_eq!(merged_book.bids.len(), 20);
+        assert_eq!(merged_book.asks.last(), Some(&Level::new("ONE", 10.0, 1.0)));
+        assert_eq!(merged_book.bids.last(), Some(&Level::new("ONE", 1.0, 1.0)));
+    }
+
+    #[test]
+    fn should_evict_the_far_tail_beyond_the_level_cap() {
+        let mut merged_book = merge_orderbooks(
+            [Box::new(TestOrderbook::new(
+                "ONE",
+                ORDERS_WHOLE_LEVELS_AT_ONE.clone(),
+                ORDERS_WHOLE_LEVELS_AT_ONE[..4].to_vec(),
+            )) as BoxedOrderbook]
+            .into_iter(),
+            usize::MAX,
+            &HashMap::new(),
+            &mut MergeCapacity::default(),
+        );
+
+        assert_eq!(merged_book.truncate(5), (5, 0));
+        assert_eq!(merged_book.asks.len(), 5);
+        assert_eq!(merged_book.asks.last(), Some(&Level::new("ONE", 5.0, 1.0)));
+        assert_eq!(merged_book.bids.len(), 4);
+
+        // The tail of each source is left out before merging
+        let mut merged_book = merge_orderbooks(
+            [Box::new(TestOrderbook::new(
+                "ONE",
+                ORDERS_WHOLE_LEVELS_AT_ONE.clone(),
+                ORDERS_WHOLE_LEVELS_AT_ONE[..4].to_vec(),
+            )) as BoxedOrderbook]
+            .into_iter(),
+            5,
+            &HashMap::new(),
+            &mut MergeCapacity::default(),
+        );
+        assert_eq!(merged_book.asks.last(), Some(&Level::new("ONE", 5.0, 1.0)));
+        assert_eq!(merged_book.truncate(5), (0, 0));
+    }
+
+    #[test]
+    fn should_summarise_books_thinner_than_the_depth() {
+        let merged_book = merge_orderbooks(
+            [Box::new(TestOrderbook::new(
+                "ONE",
+                ORDERS_WHOLE_LEVELS_AT_ONE[..3].to_vec(),
+                Vec::new(),
+            )) as BoxedOrderbook]
+            .into_iter(),
+            usize::MAX,
+            &HashMap::new(),
+            &mut MergeCapacity::default(),
+        );
+
+        let summary = merged_book.summary(SUMMARY_DEPTH);
+        assert_eq!(summary.asks.len(), 3);
+        assert_eq!((summary.ask_depth, summary.bid_depth), (3, 0));
+        // Without bids there's no spread
+        assert_eq!(summary.spread, 0.0);
+    }
+
+    #[test]
+    fn should_prefer_lower_latency_exchanges_at_equal_prices() {
+        let test_orderbooks: Vec<BoxedOrderbook> = vec![
+            Box::new(TestOrderbook::new(
+                "ONE",
+                ORDERS_WHOLE_LEVELS_AT_ONE.clone(),
+                ORDERS_WHOLE_LEVELS_AT_ONE.clone(),
+            )),
+            Box::new(TestOrderbook::new(
+                "TWO",
+                ORDERS_WHOLE_LEVELS_AT_TWO.clone(),
+                ORDERS_WHOLE_LEVELS_AT_TWO.clone(),
+            )),
+            // Yet to report a latency
+            Box::new(TestOrderbook::new(
+                "THREE",
+                ORDERS_WHOLE_LEVELS_AT_ONE.clone(),
+                ORDERS_WHOLE_LEVELS_AT_ONE.clone(),
+            )),
+        ];
+        let latencies = HashMap::from([
+            ("ONE".to_string(), Duration::from_millis(5)),
+            ("TWO".to_string(), Duration::from_millis(80)),
+        ]);
+
+        let merged_book = merge_orderbooks(
+            test_orderbooks.into_iter(),
+            usize::MAX,
+            &latencies,
+            &mut MergeCapacity::default(),
+        );
+
+        // ONE is ahead despite its smaller amounts, prices are still ordered first. THREE is ranked between them
+        assert_eq!(
+            merged_book.asks[..3],
+            [
+                Level::new("ONE", 1.0, 1.0),
+                Level::new("THREE", 1.0, 1.0),
+                Level::new("TWO", 1.0, 2.0)
+            ]
+        );
+        assert_eq!(
+            merged_book.bids[..3],
+            [
+                Level::new("ONE", 10.0, 1.0),
+                Level::new("THREE", 10.0, 1.0),
+                Level::new("TWO", 10.0, 2.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn should_find_sources_trailing_the_freshest() {
+        let at = |millis| SourceTimes {
+            exchange: None,
+            corrected: UNIX_EPOCH + Duration::from_millis(millis),
+        };
+        let timestamps = HashMap::from([
+            (ExchangeId::Binance, at(10_000)),
+            (ExchangeId::Bitstamp, at(10_400)),
+            (ExchangeId::from("Kraken"), at(10_600)),
+        ]);
+
+        assert_eq!(
+            stale_sources(&timestamps, Some(Duration::from_millis(500))),
+            vec![ExchangeId::Binance]
+        );
+        assert!(stale_sources(&timestamps, Some(Duration::from_secs(1))).is_empty());
+        assert!(stale_sources(&timestamps, None).is_empty());
+    }
+
+    #[test]
+    fn should_hash_equal_summaries_equally() {
+        let summary = |amount: f64| Summary {
+            spread: 1.0,
+            asks: vec![Level::new("ONE", 2.0, amount)],
+            bids: vec![Level::new("TWO", 1.0, 1.0)],
+            ..Default::default()
+        };
+
+        assert_eq!(hash_summary(&summary(1.0)), hash_summary(&summary(1.0)));
+        assert_ne!(hash_summary(&summary(1.0)), hash_summary(&summary(1.5)));
+    }
+
+    #[test]
+    fn should_hint_largest_requested_depth() {
+        let depth_requests = DepthRequests::new();
+        let hint = depth_requests.hint();
+
+        let shallow = depth_requests.request(5);
+        assert_eq!(*hint.borrow(), 5);
+
+        let deep = depth_requests.request(20);
+        assert_eq!(*hint.borrow(), 20);
+
+        drop(deep);
+        assert_eq!(*hint.borrow(), 5);
+
+        // The default is used once there are no subscriptions
+        drop(shallow);
+        assert_eq!(*hint.borrow(), SUMMARY_DEPTH);
+    }
+
+    /// An exchange streaming the books a test sends it.
+    #[derive(Clone)]
+    struct FeedExchange {
+        name: &'static str,
+        feed: Arc<Mutex<Option<Receiver<ReceivedOrderbook>>>>,
+    }
+
+    /// A [FeedExchange] named `name`, along with the sender of its books.
+    fn feed_exchange(name: &'static str) -> (BoxedExchange, Sender<ReceivedOrderbook>) {
+        let (feed_tx, feed_rx) = channel(10);
+        let exchange = FeedExchange {
+            name,
+            feed: Arc::new(Mutex::new(Some(feed_rx))),
+        };
+        (Box::new(exchange), feed_tx)
+    }
+
+    impl Exchange for FeedExchange {
+        fn info(&self) -> ExchangeInfo {
+            ExchangeInfo::new(self.name)
+        }
+
+        fn stream_order_book_for_pair(
+            &self,
+            _traded_pair: &TradedPair,
+            _depth_hint: DepthHint,
+        ) -> Result<Receiver<ReceivedOrderbook>, ExchangeError> {
+            Ok(self
+                .feed
+                .lock()
+                .expect("Should lock")
+                .take()
+                .expect("Should only be streamed once"))
+        }
+
+        fn supported_pairs(&self) -> BoxFuture<'static, Result<Vec<TradedPair>, ExchangeError>> {
+            Box::pin(async { Ok(vec![TradedPair::new("ETH", "BTC")]) })
+        }
+
+        fn clone_dyn(&self) -> BoxedExchange {
+            Box::new(self.clone())
+        }
+    }
+
+    async fn next_summary(handle: &mut AggregatorHandle) -> Option<Summary> {
+        match timeout(Duration::from_secs(1), handle.summary_receiver.recv()).await {
+            Ok(Ok(Ok(tick))) => Some(tick.summary.clone()),
+            _ => None,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_aggregate_a_single_source_in_degraded_mode() {
+        let clock = ManualClock::starting_at(UNIX_EPOCH);
+        let (one, one_feed) = feed_exchange("ONE");
+        let config = Config::from_toml("[aggregator]\ndegraded_mode = true").unwrap();
+        let aggregator = OrderbookAggregator::new(
+            &[one],
+            TradedPair::new("ETH", "BTC"),
+            watch_channel(HashSet::new()).1,
+            EventBus::new(10),
+            ExchangeLatencies::default(),
+            clock.clone(),
+            &config,
+        );
+        let mut handle = aggregator.subscribe();
+        tokio::spawn(aggregator.start());
+
+        let orders = vec![Order::new(100.0, 1.0)];
+        let book: BoxedOrderbook = Box::new(TestOrderbook::new("ONE", orders.clone(), orders));
+        assert!(one_feed.try_send((book, clock.now(), Span::none())).is_ok());
+
+        let summary = next_summary(&mut handle).await.expect("Should merge");
+        assert!(summary.degraded);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_correct_skewed_clocks_and_leave_out_stale_books() {
+        let clock = ManualClock::starting_at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let (one, one_feed) = feed_exchange("ONE");
+        let (two, two_feed) = feed_exchange("TWO");
+        let config = Config::from_toml("[aggregator]\ntimestamp_tolerance_millis = 500").unwrap();
+        let aggregator = OrderbookAggregator::new(
+            &[one, two],
+            TradedPair::new("ETH", "BTC"),
+            watch_channel(HashSet::new()).1,
+            EventBus::new(10),
+            ExchangeLatencies::default(),
+            clock.clone(),
+            &config,
+        );
+        let mut handle = aggregator.subscribe();
+        tokio::spawn(aggregator.start());
+
+        let book = |id: &str, price: f64, produced: SystemTime| -> BoxedOrderbook {
+            let orders = vec![Order::new(price, 1.0)];
+            Box::new(TestOrderbook::new(id, orders.clone(), orders).produced_at(produced))
+        };
+        let send = |feed: &Sender<ReceivedOrderbook>, book, received| {
+            assert!(feed.try_send((book, received, Span::none())).is_ok())
+        };
+
+        // TWO's clock runs 2s behind, which is corrected for rather than its books being stale
+        let now = clock.system_time();
+        send(&one_feed, book("ONE", 100.0, now), clock.now());
+        send(
+            &two_feed,
+            book("TWO", 100.2, now - Duration::from_secs(2)),
+            clock.now(),
+        );
+        let summary = next_summary(&mut handle).await.expect("Should merge");
+        let micros = now.duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+        assert_eq!(summary.quality(), Quality::Good);
+        let metadata = summary.metadata.unwrap();
+        assert_eq!(metadata.contributing_exchanges, 2);
+        // Each mid is 0.1 from the median of 100.1, a tenth of the tolerated deviation
+        assert!((summary.quality_score - 0.9).abs() < 1e-3);
+        assert!((metadata.mid_dispersion - 0.1 / 100.1).abs() < 1e-9);
+        let timestamps = metadata.source_timestamps;
+        assert!(timestamps
+            .iter()
+            .all(|timestamp| timestamp.corrected_timestamp_micros == micros));
+
+        // A book from TWO which was delayed on its way to the aggregator is left out
+        let delayed = clock.now();
+        advance(Duration::from_secs(2)).await;
+        let now = clock.system_time();
+        send(&one_feed, book("ONE", 100.1, now), clock.now());
+        send(
+            &two_feed,
+            book("TWO", 100.3, now - Duration::from_secs(4)),
+            delayed,
+        );
+        assert_eq!(next_summary(&mut handle).await, None);
+
+        send(
+            &two_feed,
+            book("TWO", 100.4, now - Duration::from_secs(2)),
+            clock.now(),
+        );
+        let summary = next_summary(&mut handle).await.expect("Should merge");
+        assert_eq!(summary.asks[0].price, 100.1);
+        assert_eq!(summary.asks[1].price, 100.4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_only_publish_a_lone_consistent_source_in_degraded_mode() {
+        for degraded_mode in [false, true] {
+            let clock = ManualClock::starting_at(UNIX_EPOCH);
+            let (one, one_feed) = feed_exchange("ONE");
+            let (two, two_feed) = feed_exchange("TWO");
+            let config =
+                Config::from_toml(&format!("[aggregator]\ndegraded_mode = {degraded_mode}"))
+                    .unwrap();
+            let aggregator = OrderbookAggregator::new(
+                &[one, two],
+                TradedPair::new("ETH", "BTC"),
+                watch_channel(HashSet::new()).1,
+                EventBus::new(10),
+                ExchangeLatencies::default(),
+                clock.clone(),
+                &config,
+            );
+            let mut handle = aggregator.subscribe();
+            tokio::spawn(aggregator.start());
+
+            let book = |id: &str, price: f64| -> BoxedOrderbook {
+                let orders = vec![Order::new(price, 1.0)];
+                Box::new(TestOrderbook::new(id, orders.clone(), orders))
+            };
+            // TWO's book is the older, so it's the one excluded for deviating
+            assert!(two_feed
+                .try_send((book("TWO", 120.0), clock.now(), Span::none()))
+                .is_ok());
+            advance(Duration::from_millis(10)).await;
+            assert!(one_feed
+                .try_send((book("ONE", 100.0), clock.now(), Span::none()))
+                .is_ok());
+
+            let summary = next_summary(&mut handle).await;
+            if degraded_mode {
+                let summary = summary.expect("Should merge");
+                assert!(summary.degraded);
+                assert_eq!(summary.metadata.unwrap().excluded_exchanges, vec!["TWO"]);
+            } else {
+                assert_eq!(summary, None);
+            }
+        }
+    }
 }
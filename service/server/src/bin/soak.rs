@@ -0,0 +1,337 @@
+//! Runs the full service against simulated exchanges with clients continually subscribing and unsubscribing, sampling
+//! memory, open subscriptions, live tasks and channel depths to check they stay bounded over a long run.
+
+use std::{
+    fs,
+    process::exit,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Error};
+use clap::Parser;
+use futures::StreamExt;
+use prometheus::proto::{MetricFamily, MetricType};
+use tokio::{
+    runtime::Handle,
+    select,
+    sync::watch,
+    time::{interval, sleep, Instant, MissedTickBehavior},
+};
+use tonic::transport::Channel;
+
+use order_book_service_server::{start_simulated_in_process, Config};
+use order_book_service_types::proto::{
+    orderbook_aggregator_client::OrderbookAggregatorClient, BatchedRequest, DepthSnapshotRequest,
+    OrderBookRequest, TradedPair,
+};
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static ALLOCATOR: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+/// Pairs the simulated exchanges offer.
+const PAIRS: [(&str, &str); 3] = [("ETH", "BTC"), ("LTC", "BTC"), ("BTC", "USD")];
+/// Longest a client holds a subscription open before dropping it.
+const MAX_HOLD_MILLIS: u64 = 30_000;
+/// Time allowed for the service to release everything once the clients have stopped.
+const SETTLE_TIME: Duration = Duration::from_secs(10);
+
+/// Soak test the service, exiting with an error if resource usage grows beyond the limits
+#[derive(Parser)]
+struct Args {
+    /// How long to churn clients for
+    #[arg(long, default_value_t = 24 * 60 * 60)]
+    duration_secs: u64,
+    /// Clients churning subscriptions at once
+    #[arg(long, default_value_t = 50)]
+    clients: u64,
+    #[arg(long, default_value_t = 60)]
+    sample_interval_secs: u64,
+    /// Usage is compared against the first sample after this, once every pair's aggregator is running
+    #[arg(long, default_value_t = 300)]
+    warm_up_secs: u64,
+    /// Most the resident set can grow by after the warm-up
+    #[arg(long, default_value_t = 64)]
+    max_rss_growth_mb: u64,
+    /// Most the heap can grow by after the warm-up, only checked with the jemalloc feature
+    #[arg(long, default_value_t = 32)]
+    max_allocated_growth_mb: u64,
+}
+
+/// Resource usage at a point in the run.
+#[derive(Debug)]
+struct Sample {
+    rss_kb: u64,
+    /// Bytes allocated on the heap, when jemalloc's statistics are available
+    allocated: Option<u64>,
+    /// Tasks alive in the runtime, the service's and the clients'
+    tasks: usize,
+    /// Subscriptions holding a forwarding task open in the service
+    subscriptions: f64,
+    /// RPCs the service is serving, streams included
+    active_rpcs: f64,
+    /// Most messages queued in any one channel at once
+    channel_high_watermark: f64,
+    channel_dropped: f64,
+}
+
+impl Sample {
+    fn take() -> Result<Self, Error> {
+        let metrics = prometheus::gather();
+
+        Ok(Self {
+            rss_kb: resident_set_kb()?,
+            allocated: allocated_bytes()?,
+            tasks: Handle::current().metrics().num_alive_tasks(),
+            subscriptions: metric_total(&metrics, "orderbook_client_subscriptions"),
+            active_rpcs: metric_total(&metrics, "orderbook_rpc_active"),
+            channel_high_watermark: metric_max(&metrics, "orderbook_channel_high_watermark"),
+            channel_dropped: metric_total(&metrics, "orderbook_channel_dropped_total"),
+        })
+    }
+}
+
+/// The resident set of this process, the service's allocations included as it runs in process.
+fn resident_set_kb() -> Result<u64, Error> {
+    let status =
+        fs::read_to_string("/proc/self/status").context("Unable to read the process status")?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|rss| rss.trim().trim_end_matches("kB").trim().parse().ok())
+        .context("Process status should include VmRSS")
+}
+
+#[cfg(feature = "jemalloc")]
+fn allocated_bytes() -> Result<Option<u64>, Error> {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // jemalloc only refreshes its statistics when the epoch is advanced
+    epoch::advance().context("Unable to refresh jemalloc's statistics")?;
+    let allocated = stats::allocated::read().context("Unable to read jemalloc's statistics")?;
+    Ok(Some(allocated as u64))
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn allocated_bytes() -> Result<Option<u64>, Error> {
+    Ok(None)
+}
+
+fn metric_values<'a>(metrics: &'a [MetricFamily], name: &'a str) -> impl Iterator<Item = f64> + 'a {
+    metrics
+        .iter()
+        .filter(move |family| family.get_name() == name)
+        .flat_map(|family| {
+            let counter = family.get_field_type() == MetricType::COUNTER;
+            family.get_metric().iter().map(move |metric| {
+                if counter {
+                    metric.get_counter().get_value()
+                } else {
+                    metric.get_gauge().get_value()
+                }
+            })
+        })
+}
+
+fn metric_total(metrics: &[MetricFamily], name: &str) -> f64 {
+    metric_values(metrics, name).sum()
+}
+
+fn metric_max(metrics: &[MetricFamily], name: &str) -> f64 {
+    metric_values(metrics, name).fold(0.0, f64::max)
+}
+
+/// Requests made by the clients and how many failed.
+#[derive(Debug, Default)]
+struct Counts {
+    requests: AtomicU64,
+    failures: AtomicU64,
+}
+
+/// A small xorshift generator, so each client churns differently but every run is the same.
+struct Churn(u64);
+
+impl Churn {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+/// Subscribe to a random pair and hold the subscription for a random time, or take a snapshot, until told to stop.
+async fn churn_client(
+    seed: u64,
+    channel: Channel,
+    counts: Arc<Counts>,
+    mut stop: watch::Receiver<bool>,
+) {
+    let mut churn = Churn(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1);
+    let mut client = OrderbookAggregatorClient::new(channel);
+
+    while !*stop.borrow() {
+        let (base, quote) = PAIRS[churn.below(PAIRS.len() as u64) as usize];
+        let request = OrderBookRequest {
+            traded_pair: Some(TradedPair::new(base, quote)),
+            depth: churn.below(20) as u32 + 1,
+            ..Default::default()
+        };
+        let hold = sleep(Duration::from_millis(churn.below(MAX_HOLD_MILLIS) + 100));
+
+        let session = async {
+            match churn.below(4) {
+                0 => client
+                    .get_depth_snapshot(DepthSnapshotRequest {
+                        traded_pair: request.traded_pair,
+                        depth: request.depth,
+                        effective_prices: false,
+                    })
+                    .await
+                    .map(|_| ()),
+                1 => {
+                    let mut batches = client
+                        .book_summary_batched(BatchedRequest {
+                            subscription: Some(request),
+                            window_secs: 1,
+                        })
+                        .await?
+                        .into_inner();
+                    while batches.next().await.transpose()?.is_some() {}
+                    Ok(())
+                }
+                _ => {
+                    let mut summaries = client.book_summary(request).await?.into_inner();
+                    while summaries.next().await.transpose()?.is_some() {}
+                    Ok(())
+                }
+            }
+        };
+
+        counts.requests.fetch_add(1, Ordering::Relaxed);
+        select! {
+            result = session => {
+                if result.is_err() {
+                    counts.failures.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            // Streams are dropped part way through, as clients going away do
+            _ = hold => {}
+            _ = stop.changed() => break,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let server = start_simulated_in_process(Config::default());
+    let counts = Arc::new(Counts::default());
+    let (stop, stopped) = watch::channel(false);
+
+    let clients = (0..args.clients)
+        .map(|seed| {
+            tokio::spawn(churn_client(
+                seed,
+                server.channel(),
+                counts.clone(),
+                stopped.clone(),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let deadline = start + Duration::from_secs(args.duration_secs);
+    let mut samples = interval(Duration::from_secs(args.sample_interval_secs));
+    samples.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut baseline = None;
+    let mut peak_rss_kb = 0;
+    let mut peak_allocated = 0;
+
+    while Instant::now() < deadline {
+        samples.tick().await;
+        let sample = Sample::take()?;
+        println!(
+            "{:>6}s rss={}kB allocated={} tasks={} subscriptions={} active_rpcs={} channel_high_watermark={} channel_dropped={} requests={} failures={}",
+            start.elapsed().as_secs(),
+            sample.rss_kb,
+            sample
+                .allocated
+                .map_or_else(|| "-".to_string(), |allocated| format!("{allocated}B")),
+            sample.tasks,
+            sample.subscriptions,
+            sample.active_rpcs,
+            sample.channel_high_watermark,
+            sample.channel_dropped,
+            counts.requests.load(Ordering::Relaxed),
+            counts.failures.load(Ordering::Relaxed),
+        );
+
+        if start.elapsed() >= Duration::from_secs(args.warm_up_secs) {
+            baseline.get_or_insert((sample.rss_kb, sample.allocated, sample.tasks));
+            peak_rss_kb = peak_rss_kb.max(sample.rss_kb);
+            peak_allocated = peak_allocated.max(sample.allocated.unwrap_or_default());
+        }
+    }
+
+    let _ = stop.send(true);
+    for client in clients {
+        let _ = client.await;
+    }
+    sleep(SETTLE_TIME).await;
+    let settled = Sample::take()?;
+    println!("Settled after the clients stopped: {settled:?}");
+
+    let mut failures = Vec::new();
+    if let Some((baseline_rss_kb, baseline_allocated, baseline_tasks)) = baseline {
+        let growth_kb = peak_rss_kb.saturating_sub(baseline_rss_kb);
+        if growth_kb > args.max_rss_growth_mb * 1024 {
+            failures.push(format!(
+                "Resident set grew by {growth_kb}kB after the warm-up, from {baseline_rss_kb}kB"
+            ));
+        }
+        if let Some(baseline_allocated) = baseline_allocated {
+            let growth = peak_allocated.saturating_sub(baseline_allocated);
+            if growth > args.max_allocated_growth_mb * 1024 * 1024 {
+                failures.push(format!(
+                    "Heap grew by {growth}B after the warm-up, from {baseline_allocated}B"
+                ));
+            }
+        }
+        // The clients' own tasks have finished, so a leaked forwarding or stream task keeps the count up
+        if settled.tasks >= baseline_tasks {
+            failures.push(format!(
+                "{} tasks were still alive once every client had gone, {baseline_tasks} with them running",
+                settled.tasks
+            ));
+        }
+    } else {
+        failures.push("The run ended before the warm-up, nothing was compared".to_string());
+    }
+    // Anything left open is a forwarding task which didn't notice its client going away
+    if settled.subscriptions > 0.0 {
+        failures.push(format!(
+            "{} subscriptions were still open once every client had gone",
+            settled.subscriptions
+        ));
+    }
+    if settled.active_rpcs > 0.0 {
+        failures.push(format!(
+            "{} RPCs were still being served once every client had gone",
+            settled.active_rpcs
+        ));
+    }
+
+    if failures.is_empty() {
+        println!("Resource usage stayed bounded");
+        return Ok(());
+    }
+    for failure in failures {
+        eprintln!("{failure}");
+    }
+    exit(1);
+}
@@ -0,0 +1,103 @@
+use std::{fmt::Debug, sync::Arc, time::SystemTime};
+#[cfg(any(test, feature = "test-util"))]
+use std::{sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+
+/// The time source of the connectors and aggregators, so tests can simulate stale books and skewed exchange clocks
+/// without sleeping.
+///
+/// Deadlines taken from [Clock::now] are slept on with [tokio::time], so it must advance with tokio's clock.
+pub trait Clock: Debug + Send + Sync {
+    /// The monotonic time, for measuring how long ago books were received.
+    fn now(&self) -> Instant;
+
+    /// The wall clock time, for comparing with the times exchanges say their books were produced.
+    fn system_time(&self) -> SystemTime;
+}
+
+/// A [Clock] shared by everything which reads the time.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The real time, the default [Clock].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    pub fn shared() -> SharedClock {
+        Arc::new(Self)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A wall clock starting from a fixed time and advancing with tokio's clock, so when it's paused the times are the same
+/// on every run. The wall clock can also be stepped on its own, as when a host's clock is corrected.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug)]
+pub struct ManualClock {
+    started: Instant,
+    wall_start: SystemTime,
+    /// How far the wall clock has been stepped forward
+    stepped: Mutex<Duration>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl ManualClock {
+    pub fn starting_at(wall_start: SystemTime) -> Arc<Self> {
+        Arc::new(Self {
+            started: Instant::now(),
+            wall_start,
+            stepped: Mutex::default(),
+        })
+    }
+
+    /// Step the wall clock forward by `by`, leaving the monotonic time unchanged.
+    pub fn step_wall_clock(&self, by: Duration) {
+        *self.stepped.lock().expect("Should lock") += by;
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.wall_start
+            + (Instant::now() - self.started)
+            + *self.stepped.lock().expect("Should lock")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use tokio::time::advance;
+
+    use super::{Clock, ManualClock};
+
+    #[tokio::test(start_paused = true)]
+    async fn should_advance_the_wall_clock_with_tokio_time() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = ManualClock::starting_at(start);
+        let started = clock.now();
+
+        advance(Duration::from_secs(3)).await;
+        assert_eq!(clock.system_time(), start + Duration::from_secs(3));
+
+        clock.step_wall_clock(Duration::from_secs(60));
+        assert_eq!(clock.system_time(), start + Duration::from_secs(63));
+        assert_eq!(clock.now() - started, Duration::from_secs(3));
+    }
+}
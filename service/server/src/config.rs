@@ -0,0 +1,1308 @@
+use std::{
+    collections::HashMap,
+    fs,
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Error};
+use serde::Deserialize;
+use tonic::{metadata::AsciiMetadataValue, transport::Endpoint};
+use tracing_subscriber::filter::Targets;
+
+use order_book_service_types::proto::{ExchangeId, TradedPair};
+
+use crate::{
+    aggregator::{MAX_SUMMARY_DEPTH, SUMMARY_DEPTH},
+    exchange::ExchangeInfo,
+};
+
+/// Server configuration, loaded from a TOML file.
+/// Every field has a default so an empty (or absent) file is a valid configuration.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    /// Port the gRPC server listens on
+    pub(crate) port: u16,
+    /// Serve the gRPC API on this Unix domain socket instead of `port`, for co-located consumers
+    pub(crate) unix_socket: Option<PathBuf>,
+    /// Port to serve Prometheus metrics on, metrics aren't served when absent
+    pub(crate) metrics_port: Option<u16>,
+    /// Address to serve metrics on, only this host by default as the metrics name the clients and pairs served
+    pub(crate) metrics_bind_address: IpAddr,
+    /// How long, in seconds, a summary stream can be quiet before a heartbeat is sent
+    pub(crate) heartbeat_interval_secs: u64,
+    pub(crate) depth: DepthConfig,
+    pub(crate) channels: ChannelConfig,
+    pub(crate) exchange_status: ExchangeStatusConfig,
+    pub(crate) rate_limits: RateLimitConfig,
+    pub(crate) quote_conversions: Vec<QuoteConversionConfig>,
+    pub(crate) tap: TapConfig,
+    pub(crate) consistency: ConsistencyConfig,
+    pub(crate) validation: ValidationConfig,
+    /// Minimum liquidity of each side of a pair's book keyed by pair e.g. "ETH-BTC", see
+    /// [LiquidityMonitor](crate::liquidity::LiquidityMonitor)
+    pub(crate) liquidity_alerts: HashMap<String, LiquidityThreshold>,
+    pub(crate) aggregator: AggregatorConfig,
+    pub(crate) transforms: Vec<TransformConfig>,
+    /// Taker fee of each exchange as a fraction, used for subscriptions requesting effective prices
+    pub(crate) taker_fees: HashMap<String, f64>,
+    /// Take the fee each exchange publishes for those missing from `taker_fees`, see [Config::add_published_taker_fees]
+    pub(crate) use_published_taker_fees: bool,
+    pub(crate) tracing: TracingConfig,
+    pub(crate) request_log: RequestLogConfig,
+    /// Only used when built with the `runtime-metrics` feature
+    pub(crate) runtime_metrics: RuntimeMetricsConfig,
+    /// Only used when built with the `dashboard` feature
+    pub(crate) dashboard: DashboardConfig,
+    pub(crate) admin: AdminConfig,
+    /// Clients allowed to use the service, any client is allowed when empty, see [tenancy](crate::tenancy)
+    pub(crate) tenants: Vec<TenantConfig>,
+    /// Pairs aggregated at startup e.g. "ETH-BTC", so their first subscribers don't wait on exchanges connecting
+    pub(crate) warm_up_pairs: Vec<String>,
+    /// Refuse to start when a warm-up pair isn't listed by enough exchanges to be aggregated, rather than only warning
+    pub(crate) strict_warm_up_pairs: bool,
+    /// Seal each subscription's summaries into a hash chain, see [SummaryChain](order_book_service_types::integrity::SummaryChain)
+    pub(crate) integrity: bool,
+    pub(crate) standby: StandbyConfig,
+    pub(crate) metrics_snapshots: MetricsSnapshotConfig,
+    pub(crate) threads: ThreadConfig,
+    pub(crate) drain: DrainConfig,
+    /// Set by [serve_demo](crate::serve_demo) rather than the config file
+    #[serde(skip)]
+    pub(crate) demo: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 3030,
+            unix_socket: None,
+            metrics_port: None,
+            metrics_bind_address: Ipv4Addr::LOCALHOST.into(),
+            heartbeat_interval_secs: 5,
+            depth: DepthConfig::default(),
+            channels: ChannelConfig::default(),
+            exchange_status: ExchangeStatusConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            quote_conversions: Vec::new(),
+            tap: TapConfig::default(),
+            consistency: ConsistencyConfig::default(),
+            validation: ValidationConfig::default(),
+            liquidity_alerts: HashMap::new(),
+            aggregator: AggregatorConfig::default(),
+            transforms: Vec::new(),
+            taker_fees: HashMap::new(),
+            use_published_taker_fees: false,
+            tracing: TracingConfig::default(),
+            request_log: RequestLogConfig::default(),
+            runtime_metrics: RuntimeMetricsConfig::default(),
+            dashboard: DashboardConfig::default(),
+            admin: AdminConfig::default(),
+            tenants: Vec::new(),
+            warm_up_pairs: Vec::new(),
+            strict_warm_up_pairs: false,
+            integrity: false,
+            standby: StandbyConfig::default(),
+            metrics_snapshots: MetricsSnapshotConfig::default(),
+            threads: ThreadConfig::default(),
+            drain: DrainConfig::default(),
+            demo: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read config file {}", path.display()))?;
+
+        Self::from_toml(&contents)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, Error> {
+        let config: Self = toml::from_str(contents).context("Unable to parse config")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub(crate) fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_interval_secs)
+    }
+
+    /// The pairs to aggregate before the server accepts requests.
+    pub(crate) fn warm_up_pairs(&self) -> Vec<TradedPair> {
+        self.warm_up_pairs
+            .iter()
+            .filter_map(|pair| parse_pair(pair))
+            .collect()
+    }
+
+    /// Take each exchange's published taker fee for effective prices, where `taker_fees` doesn't configure one.
+    pub(crate) fn add_published_taker_fees(&mut self, exchanges: &[ExchangeInfo]) {
+        for exchange in exchanges {
+            let configured = self
+                .taker_fees
+                .keys()
+                .any(|configured| ExchangeId::from(configured.as_str()) == exchange.id);
+            if let (false, Some(fee)) = (configured, exchange.taker_fee) {
+                self.taker_fees.insert(exchange.name.to_string(), fee);
+            }
+        }
+    }
+
+    /// The liquidity threshold configured for `traded_pair`, matched regardless of case.
+    pub(crate) fn liquidity_threshold(
+        &self,
+        traded_pair: &TradedPair,
+    ) -> Option<LiquidityThreshold> {
+        let pair = traded_pair.to_string();
+        self.liquidity_alerts
+            .iter()
+            .find(|(configured, _)| configured.eq_ignore_ascii_case(&pair))
+            .map(|(_, threshold)| *threshold)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.heartbeat_interval_secs == 0 {
+            return Err(Error::msg("heartbeat_interval_secs must be greater than 0"));
+        }
+        if cfg!(not(unix)) && self.unix_socket.is_some() {
+            return Err(Error::msg("unix_socket is only supported on Unix"));
+        }
+        self.channels.validate()?;
+        self.exchange_status.validate()?;
+        self.rate_limits.validate()?;
+        self.consistency.validate()?;
+        self.validation.validate()?;
+        for (pair, threshold) in self.liquidity_alerts.iter() {
+            threshold
+                .validate()
+                .with_context(|| format!("Invalid liquidity_alerts for {pair}"))?;
+            if parse_pair(pair).is_none() {
+                return Err(Error::msg(format!(
+                    "liquidity_alerts pair {pair} must be written as e.g. ETH-BTC"
+                )));
+            }
+        }
+        self.depth.validate()?;
+        self.aggregator.validate(self.depth.max)?;
+        self.request_log.validate()?;
+        self.runtime_metrics.validate()?;
+        self.dashboard.validate()?;
+        self.admin.validate()?;
+        self.standby.validate()?;
+        self.metrics_snapshots.validate()?;
+        self.threads.validate()?;
+        self.drain.validate()?;
+        validate_fees(&self.taker_fees)?;
+        validate_tenants(&self.tenants)?;
+        self.tracing
+            .log_filter
+            .parse::<Targets>()
+            .context("tracing log_filter should be comma separated target=level directives")?;
+        if let Some(pair) = self
+            .warm_up_pairs
+            .iter()
+            .find(|pair| parse_pair(pair).is_none())
+        {
+            return Err(Error::msg(format!(
+                "Warm-up pair {pair} should be a base and quote separated by '-' e.g. ETH-BTC"
+            )));
+        }
+        for transform in self.transforms.iter() {
+            transform.kind.validate()?;
+        }
+
+        for conversion in self.quote_conversions.iter() {
+            if conversion.rate.is_none() && conversion.rate_exchange.is_none() {
+                return Err(Error::msg(format!(
+                    "Quote conversion {} -> {} for {} needs either a rate or a rate_exchange",
+                    conversion.source_quote, conversion.quote, conversion.exchange
+                )));
+            }
+            if let Some(rate) = conversion
+                .rate
+                .filter(|rate| !(rate.is_finite() && *rate > 0.0))
+            {
+                return Err(Error::msg(format!(
+                    "Quote conversion {} -> {} for {} has a rate of {rate}, it must be a positive number",
+                    conversion.source_quote, conversion.quote, conversion.exchange
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Capacities of the channels used to pass data between tasks.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct ChannelConfig {
+    /// Requests from the gRPC server for new aggregators
+    pub(crate) new_subscriber: usize,
+    /// Summaries broadcast from each aggregator
+    pub(crate) summaries: usize,
+    /// Summaries queued for each client subscription
+    pub(crate) client_stream: usize,
+    /// Orderbooks from each exchange connection
+    pub(crate) exchange_orderbooks: usize,
+    /// Events broadcast to subscribers of the ServiceEvents RPC
+    pub(crate) service_events: usize,
+    /// Events broadcast to subscribers of the WatchExchangeStatus RPC
+    pub(crate) connector_status: usize,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            new_subscriber: 100,
+            summaries: 100,
+            client_stream: 100,
+            exchange_orderbooks: 100,
+            service_events: 100,
+            connector_status: 100,
+        }
+    }
+}
+
+impl ChannelConfig {
+    fn validate(&self) -> Result<(), Error> {
+        let capacities = [
+            ("new_subscriber", self.new_subscriber),
+            ("summaries", self.summaries),
+            ("client_stream", self.client_stream),
+            ("exchange_orderbooks", self.exchange_orderbooks),
+            ("service_events", self.service_events),
+            ("connector_status", self.connector_status),
+        ];
+
+        match capacities.iter().find(|(_, capacity)| *capacity == 0) {
+            Some((channel, _)) => Err(Error::msg(format!(
+                "Channel capacity for {channel} must be greater than 0"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Limits on the depth of the summaries subscriptions can request.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct DepthConfig {
+    /// Levels of each side sent to subscriptions which don't request a depth
+    pub(crate) default: usize,
+    /// Most levels of each side a subscription can request, deeper requests are rejected
+    pub(crate) max: usize,
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        Self {
+            default: SUMMARY_DEPTH,
+            max: MAX_SUMMARY_DEPTH,
+        }
+    }
+}
+
+impl DepthConfig {
+    fn validate(&self) -> Result<(), Error> {
+        if self.default == 0 || self.default > self.max {
+            return Err(Error::msg(
+                "depth default must be greater than 0 and at most the max",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Settings for each pair's aggregator.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct AggregatorConfig {
+    /// Skip broadcasting a summary identical to the previous one
+    pub(crate) suppress_duplicate_summaries: bool,
+    /// Levels with a quantity below the threshold are dropped before summaries are built, keyed by pair e.g. "ETH-BTC"
+    pub(crate) min_level_quantity: HashMap<String, f64>,
+    /// Reconnect to the exchanges when no book has been merged for this many seconds while there are subscribers,
+    /// disabled when omitted
+    pub(crate) stall_timeout_secs: Option<u64>,
+    /// Leave a book out of a merge when it was produced more than this many milliseconds before the freshest, compared
+    /// after correcting for each exchange's clock skew, disabled when omitted
+    pub(crate) timestamp_tolerance_millis: Option<u64>,
+    /// Check whether an exchange still lists the pair once it has sent nothing for this many seconds, removing it from
+    /// the aggregation if not, disabled when omitted
+    pub(crate) delisting_check_secs: Option<u64>,
+    /// Most levels of each side retained in a pair's merged book, levels beyond are evicted from the far tail
+    pub(crate) max_book_levels: usize,
+    /// Overrides `max_book_levels` for a pair e.g. "ETH-BTC"
+    pub(crate) max_book_levels_by_pair: HashMap<String, usize>,
+    /// Keep streaming a pair's summaries from its last exchange, flagged as degraded, rather than stopping once fewer
+    /// than two remain
+    pub(crate) degraded_mode: bool,
+}
+
+impl Default for AggregatorConfig {
+    fn default() -> Self {
+        Self {
+            suppress_duplicate_summaries: true,
+            min_level_quantity: HashMap::new(),
+            stall_timeout_secs: None,
+            timestamp_tolerance_millis: None,
+            delisting_check_secs: None,
+            max_book_levels: 1000,
+            max_book_levels_by_pair: HashMap::new(),
+            degraded_mode: false,
+        }
+    }
+}
+
+impl AggregatorConfig {
+    /// The threshold configured for `traded_pair`, matched regardless of case.
+    pub(crate) fn min_level_quantity(&self, traded_pair: &TradedPair) -> Option<f64> {
+        let pair = traded_pair.to_string();
+        self.min_level_quantity
+            .iter()
+            .find(|(configured, _)| configured.eq_ignore_ascii_case(&pair))
+            .map(|(_, min_quantity)| *min_quantity)
+    }
+
+    /// Fewest exchanges a pair is aggregated from, one in degraded mode and otherwise two.
+    pub(crate) fn min_sources(&self) -> usize {
+        if self.degraded_mode {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub(crate) fn stall_timeout(&self) -> Option<Duration> {
+        self.stall_timeout_secs.map(Duration::from_secs)
+    }
+
+    /// The cap configured for `traded_pair`, matched regardless of case, or the default cap.
+    pub(crate) fn max_book_levels(&self, traded_pair: &TradedPair) -> usize {
+        let pair = traded_pair.to_string();
+        self.max_book_levels_by_pair
+            .iter()
+            .find(|(configured, _)| configured.eq_ignore_ascii_case(&pair))
+            .map(|(_, max_levels)| *max_levels)
+            .unwrap_or(self.max_book_levels)
+    }
+
+    pub(crate) fn delisting_check(&self) -> Option<Duration> {
+        self.delisting_check_secs.map(Duration::from_secs)
+    }
+
+    pub(crate) fn timestamp_tolerance(&self) -> Option<Duration> {
+        self.timestamp_tolerance_millis.map(Duration::from_millis)
+    }
+
+    fn validate(&self, max_depth: usize) -> Result<(), Error> {
+        if self.stall_timeout_secs == Some(0) {
+            return Err(Error::msg("stall_timeout_secs must be greater than 0"));
+        }
+        if self.delisting_check_secs == Some(0) {
+            return Err(Error::msg("delisting_check_secs must be greater than 0"));
+        }
+        if self.timestamp_tolerance_millis == Some(0) {
+            return Err(Error::msg(
+                "timestamp_tolerance_millis must be greater than 0",
+            ));
+        }
+        // Summaries can request up to the max depth, so a smaller book would cut them short
+        if self.max_book_levels < max_depth {
+            return Err(Error::msg(format!(
+                "max_book_levels must be at least the max depth of {max_depth}"
+            )));
+        }
+        if let Some((pair, _)) = self
+            .max_book_levels_by_pair
+            .iter()
+            .find(|(_, max_levels)| **max_levels < max_depth)
+        {
+            return Err(Error::msg(format!(
+                "max_book_levels_by_pair for {pair} must be at least the max depth of {max_depth}"
+            )));
+        }
+        match self
+            .min_level_quantity
+            .iter()
+            .find(|(_, min_quantity)| !min_quantity.is_finite() || **min_quantity < 0.0)
+        {
+            Some((pair, _)) => Err(Error::msg(format!(
+                "min_level_quantity for {pair} must not be negative"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Settings for the cross-check of contributing exchanges' mid prices.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct ConsistencyConfig {
+    /// Relative deviation from the reference mid price above which an exchange is excluded, e.g. 0.01 is 1%
+    pub(crate) max_mid_deviation: f64,
+}
+
+impl Default for ConsistencyConfig {
+    fn default() -> Self {
+        Self {
+            max_mid_deviation: 0.01,
+        }
+    }
+}
+
+impl ConsistencyConfig {
+    fn validate(&self) -> Result<(), Error> {
+        if !self.max_mid_deviation.is_finite() || self.max_mid_deviation <= 0.0 {
+            return Err(Error::msg("max_mid_deviation must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+/// Settings for quarantining exchanges which repeatedly send invalid books, see
+/// [FeedValidator](crate::validation::FeedValidator).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct ValidationConfig {
+    /// Drop invalid books, quarantining an exchange's feed of a pair once it sends `max_invalid_books` within
+    /// `window_secs`, rather than only counting them
+    pub(crate) strict: bool,
+    pub(crate) max_invalid_books: usize,
+    pub(crate) window_secs: u64,
+    /// How long a quarantined exchange's books are left out of the pair's merges
+    pub(crate) quarantine_secs: u64,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            max_invalid_books: 5,
+            window_secs: 60,
+            quarantine_secs: 300,
+        }
+    }
+}
+
+impl ValidationConfig {
+    pub(crate) fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+
+    pub(crate) fn quarantine(&self) -> Duration {
+        Duration::from_secs(self.quarantine_secs)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.max_invalid_books == 0 || self.window_secs == 0 || self.quarantine_secs == 0 {
+            return Err(Error::msg(
+                "validation max_invalid_books, window_secs and quarantine_secs must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The least liquidity a pair's book should hold, in units of the pair's first token.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct LiquidityThreshold {
+    /// Levels of each side totalled
+    pub(crate) levels: usize,
+    /// Alert when the top bids hold less than this, bids aren't checked when omitted
+    pub(crate) min_bid_amount: Option<f64>,
+    /// Alert when the top asks hold less than this, asks aren't checked when omitted
+    pub(crate) min_ask_amount: Option<f64>,
+    /// A low side only recovers once it holds its minimum plus this fraction of it, so a book hovering around the
+    /// minimum doesn't alert on every merge
+    pub(crate) recovery_margin: f64,
+}
+
+impl Default for LiquidityThreshold {
+    fn default() -> Self {
+        Self {
+            levels: 10,
+            min_bid_amount: None,
+            min_ask_amount: None,
+            recovery_margin: 0.1,
+        }
+    }
+}
+
+impl LiquidityThreshold {
+    fn validate(&self) -> Result<(), Error> {
+        if self.levels == 0 {
+            return Err(Error::msg("levels must be greater than 0"));
+        }
+        if self.min_bid_amount.is_none() && self.min_ask_amount.is_none() {
+            return Err(Error::msg(
+                "At least one of min_bid_amount and min_ask_amount must be given",
+            ));
+        }
+        if [self.min_bid_amount, self.min_ask_amount]
+            .into_iter()
+            .flatten()
+            .any(|amount| !amount.is_finite() || amount <= 0.0)
+        {
+            return Err(Error::msg("Minimum amounts must be greater than 0"));
+        }
+        if !self.recovery_margin.is_finite() || self.recovery_margin < 0.0 {
+            return Err(Error::msg("recovery_margin must be 0 or greater"));
+        }
+        Ok(())
+    }
+}
+
+/// Settings for the exchange status monitor.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct ExchangeStatusConfig {
+    /// How often, in seconds, each exchange's status endpoint is queried
+    pub(crate) poll_interval_secs: u64,
+    /// Exchanges which should always be reported as in maintenance, regardless of what their status endpoint reports
+    pub(crate) maintenance: Vec<String>,
+    /// How long, in seconds, a connector can go without a message before it is reported stale
+    pub(crate) stale_after_secs: u64,
+}
+
+impl Default for ExchangeStatusConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 60,
+            maintenance: Vec::new(),
+            stale_after_secs: 10,
+        }
+    }
+}
+
+impl ExchangeStatusConfig {
+    pub(crate) fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs)
+    }
+
+    pub(crate) fn stale_after(&self) -> Duration {
+        Duration::from_secs(self.stale_after_secs)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.poll_interval_secs == 0 {
+            return Err(Error::msg("poll_interval_secs must be greater than 0"));
+        }
+        if self.stale_after_secs == 0 {
+            return Err(Error::msg("stale_after_secs must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+/// Limits on connecting to each exchange, shared by every pair streamed from it, see [crate::rate_limit].
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct RateLimitConfig {
+    /// Most connection attempts made to a single exchange in any minute
+    pub(crate) max_connections_per_minute: usize,
+    /// How long, in seconds, to stop connecting to an exchange which signals a rate limit without saying for how long
+    pub(crate) default_backoff_secs: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            // Binance allows 300 connections every 5 minutes per IP
+            max_connections_per_minute: 60,
+            default_backoff_secs: 60,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub(crate) fn default_backoff(&self) -> Duration {
+        Duration::from_secs(self.default_backoff_secs)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.max_connections_per_minute == 0 {
+            return Err(Error::msg(
+                "max_connections_per_minute must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A transform applied to the merged book before summaries are built, see [crate::transform].
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub(crate) struct TransformConfig {
+    /// The pair to transform e.g. "ETH-BTC", every pair is transformed when absent
+    pub(crate) pair: Option<String>,
+    #[serde(flatten)]
+    pub(crate) kind: TransformKind,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum TransformKind {
+    /// Adjust prices by each exchange's taker fee, as a fraction e.g. 0.001 is 0.1%
+    FeeAdjustment { fees: HashMap<String, f64> },
+    /// Drop levels with an amount below `min_amount`
+    DustFilter { min_amount: f64 },
+    /// Merge levels within each `tick_size` of price into one, across exchanges
+    Consolidation { tick_size: f64 },
+}
+
+impl TransformKind {
+    fn validate(&self) -> Result<(), Error> {
+        match self {
+            TransformKind::FeeAdjustment { fees } => validate_fees(fees),
+            TransformKind::DustFilter { min_amount }
+                if !min_amount.is_finite() || *min_amount < 0.0 =>
+            {
+                Err(Error::msg("min_amount must not be negative"))
+            }
+            TransformKind::DustFilter { .. } => Ok(()),
+            TransformKind::Consolidation { tick_size }
+                if !tick_size.is_finite() || *tick_size <= 0.0 =>
+            {
+                Err(Error::msg("tick_size must be positive"))
+            }
+            TransformKind::Consolidation { .. } => Ok(()),
+        }
+    }
+}
+
+fn validate_fees(fees: &HashMap<String, f64>) -> Result<(), Error> {
+    match fees.iter().find(|(_, fee)| !(0.0..1.0).contains(*fee)) {
+        Some((exchange, fee)) => Err(Error::msg(format!(
+            "Fee of {fee} for {exchange} must be at least 0 and less than 1"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Parse a pair written as "ETH-BTC".
+pub(crate) fn parse_pair(pair: &str) -> Option<TradedPair> {
+    match pair.split_once('-') {
+        Some((base, quote)) if !base.is_empty() && !quote.is_empty() && !quote.contains('-') => {
+            Some(TradedPair {
+                first: base.to_uppercase(),
+                second: quote.to_uppercase(),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn validate_tenants(tenants: &[TenantConfig]) -> Result<(), Error> {
+    for (index, tenant) in tenants.iter().enumerate() {
+        if tenant.id.is_empty() || tenant.api_key.is_empty() {
+            return Err(Error::msg("Each tenant needs an id and an api_key"));
+        }
+        if tenants[..index]
+            .iter()
+            .any(|other| other.id == tenant.id || other.api_key == tenant.api_key)
+        {
+            return Err(Error::msg(format!(
+                "Tenant {} shares its id or api_key with another tenant",
+                tenant.id
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Source pairs quoted in `source_quote` from `exchange` when `quote` is requested, converting prices before merging.
+/// e.g. merge BTC-USDT from Binance into the BTC-USD aggregation.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub(crate) struct QuoteConversionConfig {
+    pub(crate) exchange: String,
+    pub(crate) quote: String,
+    pub(crate) source_quote: String,
+    /// A fixed rate, one `source_quote` is worth `rate` of `quote`
+    pub(crate) rate: Option<f64>,
+    /// When there is no fixed rate it is derived from the mid price of `source_quote`-`quote` on this exchange
+    pub(crate) rate_exchange: Option<String>,
+}
+
+/// A client of the service, authenticated by the `x-api-key` it sends with each request.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub(crate) struct TenantConfig {
+    /// Used to label the tenant's metrics
+    pub(crate) id: String,
+    pub(crate) api_key: String,
+    /// Most BookSummary subscriptions the tenant can have open at once, unlimited when absent
+    pub(crate) max_subscriptions: Option<usize>,
+    /// Whether the tenant can use the admin service
+    #[serde(default)]
+    pub(crate) admin: bool,
+}
+
+/// Settings for exporting traces, see [telemetry](crate::telemetry).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct TracingConfig {
+    /// OTLP gRPC endpoint to export spans to, e.g. `http://localhost:4317`, spans aren't exported when absent
+    pub(crate) otlp_endpoint: Option<String>,
+    /// Reported as the `service.name` resource of exported spans
+    pub(crate) service_name: String,
+    /// Comma separated `target=level` directives, e.g. `info,order_book_service_server::exchanges::binance=debug`
+    pub(crate) log_filter: String,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "order-book-service".to_string(),
+            log_filter: "info".to_string(),
+        }
+    }
+}
+
+/// Settings for logging RPCs, see [RequestLogLayer](crate::request_log::RequestLogLayer).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct RequestLogConfig {
+    /// Fraction of RPCs whose start and stop are logged, failed RPCs are always logged
+    pub(crate) sample_rate: f64,
+}
+
+impl Default for RequestLogConfig {
+    fn default() -> Self {
+        Self { sample_rate: 1.0 }
+    }
+}
+
+impl RequestLogConfig {
+    fn validate(&self) -> Result<(), Error> {
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            return Err(Error::msg(
+                "request_log sample_rate must be between 0 and 1",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Settings for sampling the async runtime, see [runtime_metrics](crate::runtime_metrics).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct RuntimeMetricsConfig {
+    /// How often the runtime is sampled
+    pub(crate) interval_millis: u64,
+    /// Workers are reported as blocked when a task waits this long to be polled
+    pub(crate) stall_threshold_millis: u64,
+}
+
+impl Default for RuntimeMetricsConfig {
+    fn default() -> Self {
+        Self {
+            interval_millis: 1000,
+            stall_threshold_millis: 100,
+        }
+    }
+}
+
+impl RuntimeMetricsConfig {
+    fn validate(&self) -> Result<(), Error> {
+        if self.interval_millis == 0 || self.stall_threshold_millis == 0 {
+            return Err(Error::msg(
+                "runtime_metrics interval_millis and stall_threshold_millis must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Settings for the admin service, served apart from the market data API, see [AdminAuth](crate::tenancy::AdminAuth).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct AdminConfig {
+    /// Port to serve the admin service on, it isn't served when absent
+    pub(crate) port: Option<u16>,
+    /// Address to serve the admin service on, only this host by default
+    pub(crate) bind_address: IpAddr,
+    /// Admin requests must send this or an admin tenant's key as `x-api-key`, they're all refused when neither is set
+    pub(crate) api_key: Option<String>,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            port: None,
+            bind_address: Ipv4Addr::LOCALHOST.into(),
+            api_key: None,
+        }
+    }
+}
+
+impl AdminConfig {
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(api_key) = &self.api_key {
+            if api_key.is_empty() {
+                return Err(Error::msg("admin api_key shouldn't be empty"));
+            }
+            api_key
+                .parse::<AsciiMetadataValue>()
+                .context("admin api_key should be printable ASCII")?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings for the web dashboard, see [dashboard](crate::dashboard).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct DashboardConfig {
+    /// Port to serve the dashboard on, it isn't served when absent
+    pub(crate) port: Option<u16>,
+    /// Address to serve the dashboard on, only this host by default as the dashboard has no authentication of its own
+    pub(crate) bind_address: IpAddr,
+    /// Sent to the gRPC service as `x-api-key` when it has tenancy enabled
+    pub(crate) api_key: Option<String>,
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self {
+            port: None,
+            bind_address: Ipv4Addr::LOCALHOST.into(),
+            api_key: None,
+        }
+    }
+}
+
+impl DashboardConfig {
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(api_key) = &self.api_key {
+            api_key
+                .parse::<AsciiMetadataValue>()
+                .context("dashboard api_key should be printable ASCII")?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings for running as a warm standby of another server, see [standby](crate::standby).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct StandbyConfig {
+    /// The active server to relay summaries from e.g. `http://active:3030`, the server is active itself when absent
+    pub(crate) upstream: Option<String>,
+    /// Sent to the upstream as `x-api-key` when it has tenancy enabled
+    pub(crate) api_key: Option<String>,
+    /// Aggregate a pair from the exchanges once the upstream has sent nothing for it for this many seconds, should be
+    /// longer than the upstream's heartbeat interval
+    pub(crate) failover_after_secs: u64,
+}
+
+impl Default for StandbyConfig {
+    fn default() -> Self {
+        Self {
+            upstream: None,
+            api_key: None,
+            failover_after_secs: 15,
+        }
+    }
+}
+
+impl StandbyConfig {
+    pub(crate) fn failover_after(&self) -> Duration {
+        Duration::from_secs(self.failover_after_secs)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.failover_after_secs == 0 {
+            return Err(Error::msg(
+                "standby failover_after_secs must be greater than 0",
+            ));
+        }
+        if let Some(upstream) = &self.upstream {
+            Endpoint::from_shared(upstream.clone())
+                .with_context(|| format!("standby upstream {upstream} should be a URI"))?;
+        }
+        if let Some(api_key) = &self.api_key {
+            api_key
+                .parse::<AsciiMetadataValue>()
+                .context("standby api_key should be printable ASCII")?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings for periodically saving key metrics to a ring file, see [MetricsSnapshots](crate::snapshots::MetricsSnapshots).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct MetricsSnapshotConfig {
+    /// The ring file snapshots are saved to, snapshots are only taken when set
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) interval_secs: u64,
+    /// Most snapshots kept in the file, the oldest is dropped to make room
+    pub(crate) max_snapshots: usize,
+}
+
+impl Default for MetricsSnapshotConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            interval_secs: 60,
+            max_snapshots: 60,
+        }
+    }
+}
+
+impl MetricsSnapshotConfig {
+    pub(crate) fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.interval_secs == 0 {
+            return Err(Error::msg(
+                "metrics_snapshots interval_secs must be greater than 0",
+            ));
+        }
+        if self.max_snapshots == 0 {
+            return Err(Error::msg(
+                "metrics_snapshots max_snapshots must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Where the runtime's threads run, for co-located deployments sensitive to latency jitter, see
+/// [build_runtime](crate::build_runtime).
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct ThreadConfig {
+    /// Cores the runtime's threads are pinned to, with a worker per core. Threads run on any core when empty
+    pub(crate) cores: Vec<usize>,
+    /// Nice value of the runtime's threads, negative raises their priority and needs `CAP_SYS_NICE`
+    pub(crate) nice: Option<i32>,
+}
+
+impl ThreadConfig {
+    fn validate(&self) -> Result<(), Error> {
+        // The size of the kernel's CPU set
+        const MAX_CORES: usize = 1024;
+        if let Some(core) = self.cores.iter().find(|core| **core >= MAX_CORES) {
+            return Err(Error::msg(format!(
+                "threads cores must be below {MAX_CORES}, {core} is not"
+            )));
+        }
+        let mut cores = self.cores.clone();
+        cores.sort_unstable();
+        cores.dedup();
+        if cores.len() != self.cores.len() {
+            return Err(Error::msg("threads cores must not repeat a core"));
+        }
+        if let Some(nice) = self.nice.filter(|nice| !(-20..=19).contains(nice)) {
+            return Err(Error::msg(format!(
+                "threads nice must be from -20 to 19, not {nice}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Settings for draining the server ahead of a shutdown, see [Drain](crate::drain::Drain).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct DrainConfig {
+    /// The server clients are told to reconnect to e.g. `http://standby:3030`, usually the standby of this server
+    pub(crate) alternative_address: Option<String>,
+    /// How long to wait for subscriptions to close before shutting down, when the drain request doesn't say
+    pub(crate) deadline_secs: u64,
+}
+
+impl Default for DrainConfig {
+    fn default() -> Self {
+        Self {
+            alternative_address: None,
+            deadline_secs: 30,
+        }
+    }
+}
+
+impl DrainConfig {
+    pub(crate) fn deadline(&self) -> Duration {
+        Duration::from_secs(self.deadline_secs)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.deadline_secs == 0 {
+            return Err(Error::msg("drain deadline_secs must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+/// Settings for teeing raw websocket frames to files, see [FrameTap](crate::tap::FrameTap).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct TapConfig {
+    /// Where the tap files are written
+    pub(crate) directory: PathBuf,
+    /// Exchanges to tap from startup, taps can also be toggled via the admin service
+    pub(crate) exchanges: Vec<String>,
+    /// Frames longer than this are truncated
+    pub(crate) max_frame_bytes: usize,
+    /// Files are rotated once they reach this size
+    pub(crate) max_file_bytes: u64,
+    /// How many rotated files to keep per exchange
+    pub(crate) max_files: usize,
+    /// Values of these JSON keys are redacted before frames are written
+    pub(crate) redact_keys: Vec<String>,
+}
+
+impl Default for TapConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("tap"),
+            exchanges: Vec::new(),
+            max_frame_bytes: 64 * 1024,
+            max_file_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+            redact_keys: vec![
+                "apiKey".to_string(),
+                "listenKey".to_string(),
+                "signature".to_string(),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_config_should_use_defaults() {
+        let config = Config::from_toml("").expect("Empty config should parse");
+
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn should_parse_exchange_status_config() {
+        let config = Config::from_toml(
+            r#"
+            port = 4040
+
+            [exchange_status]
+            poll_interval_secs = 5
+            maintenance = ["Bitstamp"]
+            "#,
+        )
+        .expect("Config should parse");
+
+        assert_eq!(config.port, 4040);
+        assert_eq!(
+            config.exchange_status.poll_interval(),
+            Duration::from_secs(5)
+        );
+        assert_eq!(config.exchange_status.maintenance, vec!["Bitstamp"]);
+
+        let err = Config::from_toml("[exchange_status]\npoll_interval_secs = 0").unwrap_err();
+        assert_eq!(err.to_string(), "poll_interval_secs must be greater than 0");
+    }
+
+    #[test]
+    fn should_reject_zero_channel_capacity() {
+        let result = Config::from_toml(
+            r#"
+            [channels]
+            summaries = 0
+            "#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn should_validate_thread_settings() {
+        let config = Config::from_toml("[threads]\ncores = [2, 3]\nnice = -10").unwrap();
+        assert_eq!(config.threads.cores, vec![2, 3]);
+        assert_eq!(config.threads.nice, Some(-10));
+
+        assert!(Config::from_toml("[threads]\ncores = [2, 2]").is_err());
+        assert!(Config::from_toml("[threads]\nnice = -21").is_err());
+    }
+
+    #[test]
+    fn should_cap_book_levels_per_pair() {
+        let config = Config::from_toml(
+            r#"
+            [aggregator]
+            max_book_levels = 500
+            max_book_levels_by_pair = { "eth-btc" = 200 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config
+                .aggregator
+                .max_book_levels(&TradedPair::new("ETH", "BTC")),
+            200
+        );
+        assert_eq!(
+            config
+                .aggregator
+                .max_book_levels(&TradedPair::new("BTC", "USDT")),
+            500
+        );
+
+        let result = Config::from_toml(
+            r#"
+            [aggregator]
+            max_book_levels = 10
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn should_parse_warm_up_pairs() {
+        let config = Config::from_toml(r#"warm_up_pairs = ["ETH-BTC", "BTC-USDT"]"#)
+            .expect("Config should parse");
+        assert_eq!(
+            config.warm_up_pairs(),
+            vec![
+                TradedPair::new("ETH", "BTC"),
+                TradedPair::new("BTC", "USDT")
+            ]
+        );
+
+        assert!(Config::from_toml(r#"warm_up_pairs = ["ethbtc"]"#).is_err());
+    }
+
+    #[test]
+    fn should_validate_the_log_filter() {
+        let config = Config::from_toml(
+            r#"
+            [tracing]
+            log_filter = "warn,order_book_service_server::exchanges::binance=debug"
+            "#,
+        )
+        .expect("Config should parse");
+        assert_eq!(
+            config.tracing.log_filter,
+            "warn,order_book_service_server::exchanges::binance=debug"
+        );
+
+        let result = Config::from_toml(
+            r#"
+            [tracing]
+            log_filter = "exchanges=loud"
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn should_parse_transforms() {
+        let config = Config::from_toml(
+            r#"
+            [[transforms]]
+            pair = "ETH-BTC"
+            type = "fee_adjustment"
+            fees = { Binance = 0.001 }
+
+            [[transforms]]
+            type = "dust_filter"
+            min_amount = 0.5
+            "#,
+        )
+        .expect("Config should parse");
+
+        assert_eq!(config.transforms[0].pair.as_deref(), Some("ETH-BTC"));
+        assert_eq!(
+            config.transforms[1].kind,
+            TransformKind::DustFilter { min_amount: 0.5 }
+        );
+    }
+
+    #[test]
+    fn should_reject_conversion_without_rate_source() {
+        let result = Config::from_toml(
+            r#"
+            [[quote_conversions]]
+            exchange = "Binance"
+            quote = "USD"
+            source_quote = "USDT"
+            "#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn should_reject_conversion_rates_which_arent_positive_numbers() {
+        let with_rate = |rate: &str| {
+            Config::from_toml(&format!(
+                "[[quote_conversions]]\nexchange = \"Binance\"\nquote = \"USD\"\nsource_quote = \"USDT\"\nrate = {rate}"
+            ))
+        };
+
+        assert!(with_rate("0.999").is_ok());
+        for rate in ["nan", "inf", "-inf", "0.0", "-1.0"] {
+            assert!(with_rate(rate).is_err(), "{rate} should be rejected");
+        }
+    }
+
+    #[test]
+    fn should_only_take_published_taker_fees_not_configured() {
+        let mut config = Config::from_toml("[taker_fees]\nbinance = 0.0005").unwrap();
+        // Only taken when asked for
+        assert!(!config.use_published_taker_fees);
+
+        config.add_published_taker_fees(&[
+            ExchangeInfo {
+                taker_fee: Some(0.001),
+                ..ExchangeInfo::new("Binance")
+            },
+            ExchangeInfo {
+                taker_fee: Some(0.004),
+                ..ExchangeInfo::new("Bitstamp")
+            },
+            ExchangeInfo::new("InternalDesk"),
+        ]);
+
+        assert_eq!(
+            config.taker_fees,
+            HashMap::from([
+                ("binance".to_string(), 0.0005),
+                ("Bitstamp".to_string(), 0.004)
+            ])
+        );
+    }
+
+    #[test]
+    fn should_match_liquidity_thresholds_regardless_of_case() {
+        let config = Config::from_toml(
+            r#"
+            [liquidity_alerts."eth-btc"]
+            min_bid_amount = 50.0
+            "#,
+        )
+        .unwrap();
+
+        let threshold = config
+            .liquidity_threshold(&TradedPair::new("ETH", "BTC"))
+            .expect("Should match the pair");
+        assert_eq!(threshold.levels, 10);
+        assert_eq!(threshold.min_bid_amount, Some(50.0));
+        assert_eq!(threshold.min_ask_amount, None);
+        assert!(config
+            .liquidity_threshold(&TradedPair::new("LTC", "BTC"))
+            .is_none());
+
+        assert!(Config::from_toml("[liquidity_alerts.\"ETH-BTC\"]\nlevels = 5").is_err());
+        assert!(Config::from_toml("[liquidity_alerts.ETHBTC]\nmin_ask_amount = 1.0").is_err());
+    }
+}
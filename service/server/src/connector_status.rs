@@ -0,0 +1,142 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::broadcast::{channel as broadcast_channel, Receiver, Sender};
+
+use order_book_service_types::proto::{ConnectorEvent, ConnectorStatus, ExchangeId, TradedPair};
+
+use crate::metrics::connector_events;
+
+/// Broadcasts [ConnectorStatus] events from exchange connectors to subscribers of the WatchExchangeStatus RPC.
+#[derive(Clone, Debug)]
+pub(crate) struct ConnectorStatusBus {
+    sender: Sender<ConnectorStatus>,
+}
+
+impl ConnectorStatusBus {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast_channel(capacity);
+        Self { sender }
+    }
+
+    /// A reporter for a single stream of `traded_pair` from `exchange`.
+    pub(crate) fn reporter(&self, exchange: ExchangeId, traded_pair: TradedPair) -> StatusReporter {
+        StatusReporter {
+            bus: self.clone(),
+            exchange,
+            traded_pair,
+            stale: false,
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> Receiver<ConnectorStatus> {
+        self.sender.subscribe()
+    }
+
+    fn publish(&self, status: ConnectorStatus) {
+        // Dropped when there are no subscribers
+        let _ = self.sender.send(status);
+    }
+}
+
+/// Publishes the status of one connector stream, remembering whether it has been reported stale
+/// so that staleness is only reported once until messages resume.
+pub(crate) struct StatusReporter {
+    bus: ConnectorStatusBus,
+    exchange: ExchangeId,
+    traded_pair: TradedPair,
+    stale: bool,
+}
+
+impl StatusReporter {
+    pub(crate) fn connected(&mut self) {
+        self.stale = false;
+        self.publish(ConnectorEvent::Connected, String::new());
+    }
+
+    pub(crate) fn disconnected(&mut self, reason: impl Into<String>) {
+        self.publish(ConnectorEvent::Disconnected, reason.into());
+    }
+
+    /// The stream was re-established, e.g. to change depth.
+    pub(crate) fn resynced(&mut self, reason: impl Into<String>) {
+        self.stale = false;
+        self.publish(ConnectorEvent::Resynced, reason.into());
+    }
+
+    pub(crate) fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Nothing has been received for `quiet_for`.
+    pub(crate) fn stale(&mut self, quiet_for: Duration) {
+        if !self.stale {
+            self.stale = true;
+            self.publish(
+                ConnectorEvent::Stale,
+                format!("No messages for {}ms", quiet_for.as_millis()),
+            );
+        }
+    }
+
+    /// Call for each message received, reports recovery if the stream had gone stale.
+    pub(crate) fn received(&mut self) {
+        if self.stale {
+            self.stale = false;
+            self.publish(ConnectorEvent::Recovered, String::new());
+        }
+    }
+
+    fn publish(&self, event: ConnectorEvent, detail: String) {
+        connector_events(self.exchange.as_str(), event.as_str_name()).inc();
+        let timestamp_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        self.bus.publish(ConnectorStatus {
+            timestamp_millis,
+            exchange: self.exchange.to_string(),
+            traded_pair: Some(self.traded_pair.clone()),
+            event: event as i32,
+            detail,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use order_book_service_types::proto::{ConnectorEvent, ExchangeId, TradedPair};
+
+    use super::ConnectorStatusBus;
+
+    #[test]
+    fn should_report_staleness_once_until_recovered() {
+        let bus = ConnectorStatusBus::new(10);
+        let mut events = bus.subscribe();
+        let mut reporter = bus.reporter(ExchangeId::Binance, TradedPair::new("ETH", "BTC"));
+
+        reporter.connected();
+        reporter.received();
+        reporter.stale(Duration::from_secs(10));
+        reporter.stale(Duration::from_secs(20));
+        reporter.received();
+        reporter.disconnected("Stream ended");
+
+        let mut published = Vec::new();
+        while let Ok(status) = events.try_recv() {
+            published.push(status.event());
+        }
+
+        assert_eq!(
+            published,
+            vec![
+                ConnectorEvent::Connected,
+                ConnectorEvent::Stale,
+                ConnectorEvent::Recovered,
+                ConnectorEvent::Disconnected,
+            ]
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::{HashMap, HashSet};
+
+use tokio::time::Instant;
+use tracing::warn;
+
+use order_book_service_types::proto::{
+    service_event::Event, ConsistencyAlert, ExchangeId, TradedPair,
+};
+
+use crate::{events::EventBus, exchange::BoxedOrderbook};
+
+/// An exchange's mid price compared against the reference mid price for a merge.
+#[derive(Debug, PartialEq)]
+struct MidDeviation {
+    exchange: ExchangeId,
+    mid_price: f64,
+    reference_mid_price: f64,
+    deviation: f64,
+}
+
+/// Cross-checks the mid prices of the exchanges contributing to each merge, so that a stale or broken feed
+/// can be left out of the summary rather than corrupting it.
+pub(crate) struct ConsistencyMonitor {
+    traded_pair: TradedPair,
+    max_mid_deviation: f64,
+    event_bus: EventBus,
+    /// Exchanges currently excluded, alerts are only raised when an exchange enters or leaves this set
+    deviating: HashSet<ExchangeId>,
+}
+
+impl ConsistencyMonitor {
+    pub(crate) fn new(
+        traded_pair: TradedPair,
+        max_mid_deviation: f64,
+        event_bus: EventBus,
+    ) -> Self {
+        Self {
+            traded_pair,
+            max_mid_deviation,
+            event_bus,
+            deviating: HashSet::new(),
+        }
+    }
+
+    /// Check the orderbooks about to be merged, returning the exchanges which should be excluded.
+    pub(crate) fn check(
+        &mut self,
+        orderbooks: &HashMap<ExchangeId, (BoxedOrderbook, Instant)>,
+    ) -> HashSet<ExchangeId> {
+        let mids = orderbooks
+            .iter()
+            .filter_map(|(exchange, (orderbook, received))| {
+                let ask = orderbook.best_ask()?.price;
+                let bid = orderbook.best_bid()?.price;
+                Some((exchange.clone(), (ask + bid) / 2.0, *received))
+            })
+            .collect::<Vec<_>>();
+
+        let mut excluded = HashSet::new();
+        for mid_deviation in mid_deviations(&mids) {
+            let exchange = &mid_deviation.exchange;
+            let deviating = mid_deviation.deviation > self.max_mid_deviation;
+
+            if deviating {
+                excluded.insert(exchange.clone());
+            }
+
+            // Only alert when the exchange's state changes
+            let changed = if deviating {
+                self.deviating.insert(exchange.clone())
+            } else {
+                self.deviating.remove(exchange)
+            };
+
+            if changed {
+                if deviating {
+                    warn!(
+                        "{exchange} mid price {} deviates from {} for {}, excluding it",
+                        mid_deviation.mid_price,
+                        mid_deviation.reference_mid_price,
+                        self.traded_pair
+                    );
+                }
+
+                self.event_bus
+                    .publish(Event::ConsistencyAlert(ConsistencyAlert {
+                        traded_pair: Some(self.traded_pair.clone()),
+                        exchange: exchange.to_string(),
+                        mid_price: mid_deviation.mid_price,
+                        reference_mid_price: mid_deviation.reference_mid_price,
+                        deviation: mid_deviation.deviation,
+                        resolved: !deviating,
+                    }));
+            }
+        }
+
+        excluded
+    }
+}
+
+/// Compare each exchange's mid price against a reference.
+///
+/// With three or more exchanges the reference is the median mid price. With two there is no majority,
+/// so the most recently received book is taken as the reference and only the staler exchange can deviate.
+fn mid_deviations(mids: &[(ExchangeId, f64, Instant)]) -> Vec<MidDeviation> {
+    let reference_mid_price = match mids {
+        [] | [_] => return Vec::new(),
+        [(_, first, first_received), (_, second, second_received)] => {
+            if first_received >= second_received {
+                *first
+            } else {
+                *second
+            }
+        }
+        _ => {
+            let mut prices = mids.iter().map(|(_, price, _)| *price).collect::<Vec<_>>();
+            prices.sort_by(f64::total_cmp);
+            let middle = prices.len() / 2;
+            if prices.len() % 2 == 0 {
+                (prices[middle - 1] + prices[middle]) / 2.0
+            } else {
+                prices[middle]
+            }
+        }
+    };
+
+    mids.iter()
+        .map(|(exchange, mid_price, _)| MidDeviation {
+            exchange: exchange.clone(),
+            mid_price: *mid_price,
+            reference_mid_price,
+            deviation: ((mid_price - reference_mid_price) / reference_mid_price).abs(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use tokio::time::Instant;
+
+    use order_book_service_types::proto::{service_event::Event, ExchangeId, Level, TradedPair};
+
+    use crate::{
+        events::EventBus,
+        exchange::{BoxedOrderbook, OrderBook},
+    };
+
+    use super::{mid_deviations, ConsistencyMonitor};
+
+    struct MidOrderbook(ExchangeId, f64);
+
+    impl OrderBook for MidOrderbook {
+        fn source(&self) -> ExchangeId {
+            self.0.clone()
+        }
+
+        fn spread(&self) -> f64 {
+            2.0
+        }
+
+        fn best_asks(&self, _depth: usize, levels: &mut Vec<Level>) {
+            levels.push(Level::new(self.0.clone(), self.1 + 1.0, 1.0));
+        }
+
+        fn best_bids(&self, _depth: usize, levels: &mut Vec<Level>) {
+            levels.push(Level::new(self.0.clone(), self.1 - 1.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn should_compare_against_median_of_many_exchanges() {
+        let now = Instant::now();
+        let deviations = mid_deviations(&[
+            (ExchangeId::from("One"), 100.0, now),
+            (ExchangeId::from("Two"), 101.0, now),
+            (ExchangeId::from("Three"), 150.0, now),
+        ]);
+
+        assert_eq!(deviations[0].reference_mid_price, 101.0);
+        assert!(deviations[2].deviation > 0.4);
+    }
+
+    #[test]
+    fn should_compare_against_freshest_of_two_exchanges() {
+        let now = Instant::now();
+        let deviations = mid_deviations(&[
+            (ExchangeId::from("Stale"), 90.0, now),
+            (
+                ExchangeId::from("Fresh"),
+                100.0,
+                now + Duration::from_millis(10),
+            ),
+        ]);
+
+        assert_eq!(deviations[0].reference_mid_price, 100.0);
+        assert_eq!(deviations[0].deviation, 0.1);
+        assert_eq!(deviations[1].deviation, 0.0);
+    }
+
+    #[tokio::test]
+    async fn should_exclude_deviating_exchange_and_alert_on_changes() {
+        let event_bus = EventBus::new(10);
+        let mut events = event_bus.subscribe();
+        let mut monitor = ConsistencyMonitor::new(TradedPair::new("ETH", "BTC"), 0.01, event_bus);
+
+        let now = Instant::now();
+        let books = |stale_mid: f64| {
+            let stale: BoxedOrderbook = Box::new(MidOrderbook(ExchangeId::Binance, stale_mid));
+            let fresh: BoxedOrderbook = Box::new(MidOrderbook(ExchangeId::Bitstamp, 100.0));
+            HashMap::from([
+                (ExchangeId::Binance, (stale, now)),
+                (
+                    ExchangeId::Bitstamp,
+                    (fresh, now + Duration::from_millis(10)),
+                ),
+            ])
+        };
+
+        assert!(monitor.check(&books(95.0)).contains(&ExchangeId::Binance));
+        // Still deviating, so there's no new alert
+        assert!(monitor.check(&books(94.0)).contains(&ExchangeId::Binance));
+        assert!(monitor.check(&books(100.5)).is_empty());
+
+        let alerts = [events.try_recv().unwrap(), events.try_recv().unwrap()].map(|event| {
+            match event.event {
+                Some(Event::ConsistencyAlert(alert)) => (alert.exchange, alert.resolved),
+                _ => panic!("Expected a consistency alert"),
+            }
+        });
+        assert_eq!(
+            alerts,
+            [
+                ("Binance".to_string(), false),
+                ("Binance".to_string(), true)
+            ]
+        );
+        assert!(events.try_recv().is_err());
+    }
+}
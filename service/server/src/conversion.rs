@@ -0,0 +1,481 @@
+use std::{iter::once, time::Duration, time::SystemTime};
+
+use tokio::{
+    sync::{
+        mpsc::Receiver,
+        watch::{channel as watch_channel, Receiver as WatchReceiver, Sender as WatchSender},
+    },
+    time::sleep,
+};
+use tracing::warn;
+
+use order_book_service_types::proto::{self, ExchangeId, Level, TradedPair};
+
+use crate::{
+    config::QuoteConversionConfig,
+    error::{AggregatorError, ServerError},
+    exchange::{fixed_depth_hint, BoxedExchange, BoxedOrderbook, OrderBook, ReceivedOrderbook},
+};
+
+/// How long to wait before reopening a rate stream which ended.
+const RATE_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Where the rate for a [QuoteConversion] comes from.
+enum RateSource {
+    Fixed(f64),
+    /// The rate is the latest mid price of a pair streamed from an exchange
+    Derived {
+        description: String,
+        rate_receiver: WatchReceiver<Option<f64>>,
+    },
+}
+
+/// Converts orderbooks from an exchange quoted in one currency into another, so that they can be merged
+/// with books for the requested pair, e.g. BTC-USDT from Binance merged with BTC-USD from Bitstamp.
+pub(crate) struct QuoteConversion {
+    exchange: ExchangeId,
+    source_pair: TradedPair,
+    rate_source: RateSource,
+}
+
+impl QuoteConversion {
+    pub(crate) fn exchange(&self) -> &ExchangeId {
+        &self.exchange
+    }
+
+    /// The pair to stream from the exchange in place of the requested pair.
+    pub(crate) fn source_pair(&self) -> &TradedPair {
+        &self.source_pair
+    }
+
+    /// The current rate, `None` until a derived rate has been received or while its stream is down.
+    pub(crate) fn rate(&self) -> Option<f64> {
+        match &self.rate_source {
+            RateSource::Fixed(rate) => Some(*rate),
+            RateSource::Derived { rate_receiver, .. } => *rate_receiver.borrow(),
+        }
+    }
+
+    /// Wrap an orderbook from the source pair so its prices are in the requested quote currency.
+    /// Returns `None` if there is no rate to convert with yet.
+    pub(crate) fn convert(&self, orderbook: BoxedOrderbook) -> Option<BoxedOrderbook> {
+        let rate = self.rate()?;
+        Some(Box::new(ConvertedOrderbook {
+            inner: orderbook,
+            rate,
+        }))
+    }
+
+    /// Describe the conversion for the summary metadata.
+    pub(crate) fn to_proto(&self) -> proto::QuoteConversion {
+        let rate_source = match &self.rate_source {
+            RateSource::Fixed(_) => "fixed".to_string(),
+            RateSource::Derived { description, .. } => description.clone(),
+        };
+
+        proto::QuoteConversion {
+            exchange: self.exchange.to_string(),
+            source_pair: Some(self.source_pair.clone()),
+            rate: self.rate().unwrap_or_default(),
+            rate_source,
+        }
+    }
+}
+
+/// The pair to stream from `exchange` in place of `traded_pair`, which differs when a conversion applies.
+pub(crate) fn source_pair_for_exchange(
+    configs: &[QuoteConversionConfig],
+    traded_pair: &TradedPair,
+    exchange: &ExchangeId,
+) -> TradedPair {
+    configs
+        .iter()
+        .find(|config| {
+            ExchangeId::from(config.exchange.as_str()) == *exchange
+                && config.quote.eq_ignore_ascii_case(&traded_pair.second)
+        })
+        .map_or_else(
+            || traded_pair.clone(),
+            |config| TradedPair {
+                first: traded_pair.first.clone(),
+                second: config.source_quote.clone(),
+            },
+        )
+}
+
+/// Fail when a conversion names an exchange the service doesn't aggregate from, rather than ignoring the conversion.
+pub(crate) fn check_quote_conversions(
+    configs: &[QuoteConversionConfig],
+    exchanges: &[BoxedExchange],
+) -> Result<(), ServerError> {
+    for config in configs {
+        for name in once(&config.exchange).chain(&config.rate_exchange) {
+            let id = ExchangeId::from(name.as_str());
+            if !exchanges.iter().any(|exchange| exchange.id() == id) {
+                return Err(ServerError::UnknownConversionExchange(name.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the conversions which apply to `traded_pair`.
+/// For conversions without a fixed rate a task is spawned to track the rate from the configured exchange.
+pub(crate) fn conversions_for_pair(
+    configs: &[QuoteConversionConfig],
+    traded_pair: &TradedPair,
+    exchanges: &[BoxedExchange],
+) -> Result<Vec<QuoteConversion>, AggregatorError> {
+    configs
+        .iter()
+        .filter(|config| config.quote.eq_ignore_ascii_case(&traded_pair.second))
+        .map(|config| {
+            let source_pair = TradedPair {
+                first: traded_pair.first.clone(),
+                second: config.source_quote.clone(),
+            };
+
+            let rate_source = match (config.rate, &config.rate_exchange) {
+                (Some(rate), _) => RateSource::Fixed(rate),
+                (None, Some(rate_exchange)) => {
+                    let rate_pair = TradedPair {
+                        first: config.source_quote.clone(),
+                        second: config.quote.clone(),
+                    };
+                    let exchange = exchanges
+                        .iter()
+                        .find(|exchange| exchange.id() == ExchangeId::from(rate_exchange.as_str()))
+                        .ok_or_else(|| {
+                            AggregatorError::UnknownRateExchange(rate_exchange.clone())
+                        })?;
+
+                    let (rate_sender, rate_receiver) = watch_channel(None);
+                    let description = format!("{} {rate_pair} mid", exchange.name());
+                    tokio::spawn(track_mid_price(
+                        exchange
+                            .stream_order_book_for_pair(&rate_pair, fixed_depth_hint(1))
+                            .map_err(AggregatorError::RateStream)?,
+                        exchange.clone(),
+                        rate_pair,
+                        rate_sender,
+                    ));
+
+                    RateSource::Derived {
+                        description,
+                        rate_receiver,
+                    }
+                }
+                (None, None) => return Err(AggregatorError::NoRateSource(source_pair)),
+            };
+
+            Ok(QuoteConversion {
+                exchange: ExchangeId::from(config.exchange.as_str()),
+                source_pair,
+                rate_source,
+            })
+        })
+        .collect()
+}
+
+/// Publish the mid price of each orderbook received for `rate_pair` until the conversion is dropped.
+///
+/// The rate is withdrawn while the stream is down, so books aren't converted at a rate which may no longer hold, and the
+/// stream is reopened after [RATE_RECONNECT_DELAY].
+async fn track_mid_price(
+    mut orderbook_receiver: Receiver<ReceivedOrderbook>,
+    exchange: BoxedExchange,
+    rate_pair: TradedPair,
+    rate_sender: WatchSender<Option<f64>>,
+) {
+    loop {
+        while let Some((orderbook, _, _)) = orderbook_receiver.recv().await {
+            if rate_sender.is_closed() {
+                return;
+            }
+
+            match (orderbook.best_ask(), orderbook.best_bid()) {
+                (Some(ask), Some(bid)) => {
+                    rate_sender.send_replace(Some((ask.price + bid.price) / 2.0));
+                }
+                _ => warn!(
+                    "Unable to derive rate from empty {} book",
+                    orderbook.source()
+                ),
+            }
+        }
+
+        rate_sender.send_replace(None);
+        warn!(
+            "{} {rate_pair} rate stream ended, its conversions are unavailable until it reconnects",
+            exchange.name()
+        );
+        loop {
+            sleep(RATE_RECONNECT_DELAY).await;
+            if rate_sender.is_closed() {
+                return;
+            }
+            match exchange.stream_order_book_for_pair(&rate_pair, fixed_depth_hint(1)) {
+                Ok(receiver) => {
+                    orderbook_receiver = receiver;
+                    break;
+                }
+                Err(err) => warn!("Unable to reopen the {rate_pair} rate stream: {err}"),
+            }
+        }
+    }
+}
+
+/// An [OrderBook] with its prices multiplied by a conversion rate.
+struct ConvertedOrderbook {
+    inner: BoxedOrderbook,
+    rate: f64,
+}
+
+impl ConvertedOrderbook {
+    /// Convert the prices of the levels from `start` onwards, those before were already in the caller's buffer.
+    fn convert_levels(&self, levels: &mut Vec<Level>, start: usize) {
+        for level in &mut levels[start..] {
+            level.price *= self.rate;
+        }
+        // A rate derived from a degenerate book could overflow
+        let mut index = 0;
+        levels.retain(|level| {
+            index += 1;
+            index <= start || level.price.is_finite()
+        });
+    }
+}
+
+impl OrderBook for ConvertedOrderbook {
+    fn source(&self) -> ExchangeId {
+        self.inner.source()
+    }
+
+    fn spread(&self) -> f64 {
+        self.inner.spread() * self.rate
+    }
+
+    fn best_asks(&self, depth: usize, levels: &mut Vec<Level>) {
+        let start = levels.len();
+        self.inner.best_asks(depth, levels);
+        self.convert_levels(levels, start);
+    }
+
+    fn best_bids(&self, depth: usize, levels: &mut Vec<Level>) {
+        let start = levels.len();
+        self.inner.best_bids(depth, levels);
+        self.convert_levels(levels, start);
+    }
+
+    fn exchange_timestamp(&self) -> Option<SystemTime> {
+        self.inner.exchange_timestamp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use futures::future::BoxFuture;
+    use tokio::{
+        sync::mpsc::{channel, Receiver, Sender},
+        time::{sleep, Instant},
+    };
+    use tracing::Span;
+
+    use order_book_service_types::proto::{ExchangeId, Level, TradedPair};
+
+    use crate::{
+        config::QuoteConversionConfig,
+        error::{ExchangeError, ServerError},
+        exchange::{
+            sort_orders_to_depth, BoxedExchange, BoxedOrderbook, DepthHint, Exchange, ExchangeInfo,
+            Order, OrderBook, Ordering, ReceivedOrderbook,
+        },
+    };
+
+    use super::{
+        check_quote_conversions, conversions_for_pair, source_pair_for_exchange,
+        RATE_RECONNECT_DELAY,
+    };
+
+    struct TestOrderbook;
+
+    impl OrderBook for TestOrderbook {
+        fn source(&self) -> ExchangeId {
+            ExchangeId::Binance
+        }
+
+        fn spread(&self) -> f64 {
+            1.0
+        }
+
+        fn best_asks(&self, depth: usize, levels: &mut Vec<Level>) {
+            sort_orders_to_depth(
+                &[Order::new(101.0, 1.0)],
+                Ordering::LowToHigh,
+                depth,
+                &self.source(),
+                levels,
+            )
+        }
+
+        fn best_bids(&self, depth: usize, levels: &mut Vec<Level>) {
+            sort_orders_to_depth(
+                &[Order::new(100.0, 2.0)],
+                Ordering::HighToLow,
+                depth,
+                &self.source(),
+                levels,
+            )
+        }
+    }
+
+    /// An exchange streaming each of the feeds a test sends it in turn, one per stream opened.
+    #[derive(Clone)]
+    struct FeedsExchange {
+        feeds: Arc<Mutex<Vec<Receiver<ReceivedOrderbook>>>>,
+    }
+
+    /// A [FeedsExchange] named "Rates" with `count` feeds, along with their senders.
+    fn feeds_exchange(count: usize) -> (BoxedExchange, Vec<Sender<ReceivedOrderbook>>) {
+        let (senders, receivers) = (0..count).map(|_| channel(10)).unzip();
+        let exchange = FeedsExchange {
+            feeds: Arc::new(Mutex::new(receivers)),
+        };
+        (Box::new(exchange), senders)
+    }
+
+    impl Exchange for FeedsExchange {
+        fn info(&self) -> ExchangeInfo {
+            ExchangeInfo::new("Rates")
+        }
+
+        fn stream_order_book_for_pair(
+            &self,
+            _traded_pair: &TradedPair,
+            _depth_hint: DepthHint,
+        ) -> Result<Receiver<ReceivedOrderbook>, ExchangeError> {
+            Ok(self.feeds.lock().expect("Should lock").remove(0))
+        }
+
+        fn supported_pairs(&self) -> BoxFuture<'static, Result<Vec<TradedPair>, ExchangeError>> {
+            Box::pin(async { Ok(vec![TradedPair::new("USDT", "USD")]) })
+        }
+
+        fn clone_dyn(&self) -> BoxedExchange {
+            Box::new(self.clone())
+        }
+    }
+
+    fn usdt_to_usd(rate: f64) -> QuoteConversionConfig {
+        QuoteConversionConfig {
+            exchange: "Binance".to_string(),
+            quote: "USD".to_string(),
+            source_quote: "USDT".to_string(),
+            rate: Some(rate),
+            rate_exchange: None,
+        }
+    }
+
+    #[test]
+    fn should_only_apply_conversions_for_requested_quote() {
+        let configs = vec![usdt_to_usd(1.0)];
+
+        let for_usd = conversions_for_pair(&configs, &TradedPair::new("BTC", "USD"), &[]).unwrap();
+        let for_eur = conversions_for_pair(&configs, &TradedPair::new("BTC", "EUR"), &[]).unwrap();
+
+        assert_eq!(for_usd.len(), 1);
+        assert_eq!(for_usd[0].source_pair(), &TradedPair::new("BTC", "USDT"));
+        assert!(for_eur.is_empty());
+    }
+
+    #[test]
+    fn should_resolve_source_pair_per_exchange() {
+        let configs = vec![usdt_to_usd(1.0)];
+        let btc_usd = TradedPair::new("BTC", "USD");
+
+        assert_eq!(
+            source_pair_for_exchange(&configs, &btc_usd, &ExchangeId::Binance),
+            TradedPair::new("BTC", "USDT")
+        );
+        assert_eq!(
+            source_pair_for_exchange(&configs, &btc_usd, &ExchangeId::Bitstamp),
+            btc_usd
+        );
+    }
+
+    #[test]
+    fn should_convert_prices_but_not_amounts() {
+        let conversions =
+            conversions_for_pair(&[usdt_to_usd(0.5)], &TradedPair::new("BTC", "USD"), &[]).unwrap();
+
+        let orderbook: BoxedOrderbook = Box::new(TestOrderbook);
+        let converted = conversions[0].convert(orderbook).unwrap();
+
+        assert_eq!(converted.best_ask(), Some(Level::new("Binance", 50.5, 1.0)));
+        // Levels already in the buffer aren't converted again
+        let mut bids = vec![Level::new("Kraken", 100.0, 1.0)];
+        converted.best_bids(1, &mut bids);
+        assert_eq!(
+            bids,
+            vec![
+                Level::new("Kraken", 100.0, 1.0),
+                Level::new("Binance", 50.0, 2.0)
+            ]
+        );
+        assert_eq!(conversions[0].to_proto().rate_source, "fixed");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_withdraw_a_derived_rate_until_its_stream_reconnects() {
+        let (exchange, mut feeds) = feeds_exchange(2);
+        let config = QuoteConversionConfig {
+            rate: None,
+            rate_exchange: Some("Rates".to_string()),
+            ..usdt_to_usd(1.0)
+        };
+        let conversions =
+            conversions_for_pair(&[config], &TradedPair::new("BTC", "USD"), &[exchange]).unwrap();
+        let book =
+            || -> ReceivedOrderbook { (Box::new(TestOrderbook), Instant::now(), Span::none()) };
+
+        let first = feeds.remove(0);
+        assert!(first.send(book()).await.is_ok());
+        sleep(Duration::from_millis(1)).await;
+        assert_eq!(conversions[0].rate(), Some(100.5));
+
+        // Not converted at the last rate once the stream has ended
+        drop(first);
+        sleep(Duration::from_millis(1)).await;
+        assert_eq!(conversions[0].rate(), None);
+        assert!(conversions[0].convert(Box::new(TestOrderbook)).is_none());
+
+        sleep(RATE_RECONNECT_DELAY).await;
+        assert!(feeds[0].send(book()).await.is_ok());
+        sleep(Duration::from_millis(1)).await;
+        assert_eq!(conversions[0].rate(), Some(100.5));
+    }
+
+    #[test]
+    fn should_reject_conversions_for_unknown_exchanges() {
+        let (exchange, _feeds) = feeds_exchange(0);
+        let config = |exchange: &str, rate_exchange: Option<&str>| QuoteConversionConfig {
+            exchange: exchange.to_string(),
+            rate_exchange: rate_exchange.map(str::to_string),
+            ..usdt_to_usd(1.0)
+        };
+        let exchanges = [exchange];
+
+        assert!(check_quote_conversions(&[config("Rates", Some("Rates"))], &exchanges).is_ok());
+        for unknown in [config("Kraken", None), config("Rates", Some("Kraken"))] {
+            assert!(matches!(
+                check_quote_conversions(&[unknown], &exchanges),
+                Err(ServerError::UnknownConversionExchange(name)) if name == "Kraken"
+            ));
+        }
+    }
+}
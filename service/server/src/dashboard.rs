@@ -0,0 +1,364 @@
+//! A small web dashboard for evaluating the service, showing the live top of book, spread and exchange health of the
+//! pairs selected.
+//!
+//! The page is built into the binary. It's fed by a gateway which subscribes to the gRPC service like any other client
+//! and forwards its streams to the browser as server-sent events.
+
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use anyhow::{Context, Error};
+use hyper::{
+    body::{Bytes, Sender},
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode, Uri,
+};
+use serde_json::{json, Value};
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tonic::{
+    metadata::AsciiMetadataValue,
+    transport::{Channel, Endpoint},
+    Status, Streaming,
+};
+use tracing::{error, info, warn};
+use url::form_urlencoded;
+
+use order_book_service_types::proto::{
+    orderbook_aggregator_client::OrderbookAggregatorClient, ConnectorStatus, Empty, Level,
+    OrderBookRequest, Summary, TradedPair,
+};
+
+use crate::{
+    config::{parse_pair, DashboardConfig},
+    grpc_server::Transport,
+    tenancy::API_KEY_METADATA,
+};
+
+const INDEX: &str = include_str!("dashboard.html");
+/// Levels of each side shown for a pair.
+const DEPTH: u32 = 5;
+
+/// Subscribes to the gRPC service on behalf of the dashboard's browsers.
+#[derive(Clone, Debug)]
+struct Gateway {
+    channel: Channel,
+    api_key: Option<AsciiMetadataValue>,
+}
+
+impl Gateway {
+    fn client(&self) -> OrderbookAggregatorClient<Channel> {
+        OrderbookAggregatorClient::new(self.channel.clone())
+    }
+
+    fn request<T>(&self, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        if let Some(api_key) = &self.api_key {
+            request
+                .metadata_mut()
+                .insert(API_KEY_METADATA, api_key.clone());
+        }
+        request
+    }
+}
+
+/// Serve the dashboard in the background when a port is configured, through the gRPC service's own listener.
+pub(crate) fn spawn(config: &DashboardConfig, transport: &Transport) {
+    let Some(port) = config.port else {
+        return;
+    };
+    let grpc_target = match transport {
+        Transport::Tcp(grpc_addr) => GrpcTarget::Tcp(*grpc_addr),
+        #[cfg(unix)]
+        Transport::Unix(socket_path) => GrpcTarget::Unix(socket_path.to_string_lossy().into()),
+        Transport::InProcess(_) => {
+            warn!("The dashboard isn't served when the gRPC service is only reachable in process");
+            return;
+        }
+    };
+
+    let addr = SocketAddr::new(config.bind_address, port);
+    info!("Serving the dashboard on {addr}...");
+    let config = config.clone();
+    tokio::spawn(async move {
+        if let Err(err) = serve(addr, config, grpc_target).await {
+            error!("{err:#}");
+        }
+    });
+}
+
+/// Where the gateway reaches the gRPC service.
+enum GrpcTarget {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(String),
+}
+
+impl GrpcTarget {
+    fn connect_lazy(self) -> Result<Channel, Error> {
+        match self {
+            GrpcTarget::Tcp(mut grpc_addr) => {
+                // Listening on every interface, so loopback reaches it
+                if grpc_addr.ip().is_unspecified() {
+                    grpc_addr.set_ip(match grpc_addr.ip() {
+                        IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                        IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                    });
+                }
+                let endpoint = Endpoint::from_shared(format!("http://{grpc_addr}"))
+                    .context("Invalid gRPC address for the dashboard")?;
+                Ok(endpoint.connect_lazy())
+            }
+            #[cfg(unix)]
+            GrpcTarget::Unix(socket_path) => {
+                // The URI is unused by the connector but must still be valid HTTP
+                let endpoint = Endpoint::from_static("http://localhost");
+                Ok(
+                    endpoint.connect_with_connector_lazy(tower::service_fn(move |_: Uri| {
+                        UnixStream::connect(socket_path.clone())
+                    })),
+                )
+            }
+        }
+    }
+}
+
+/// Serve the dashboard on `addr`, subscribing to the gRPC service at `grpc_target`.
+async fn serve(
+    addr: SocketAddr,
+    config: DashboardConfig,
+    grpc_target: GrpcTarget,
+) -> Result<(), Error> {
+    let gateway = Gateway {
+        channel: grpc_target.connect_lazy()?,
+        api_key: config
+            .api_key
+            .as_ref()
+            .map(|api_key| api_key.parse().expect("API key should be validated")),
+    };
+
+    let make_service = make_service_fn(move |_| {
+        let gateway = gateway.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let gateway = gateway.clone();
+                async move { Ok::<_, Infallible>(route(&gateway, request).await) }
+            }))
+        }
+    });
+
+    Server::try_bind(&addr)
+        .context("Unable to bind dashboard port")?
+        .serve(make_service)
+        .await
+        .context("Dashboard server shutdown")
+}
+
+async fn route(gateway: &Gateway, request: Request<Body>) -> Response<Body> {
+    if request.method() != Method::GET {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    match request.uri().path() {
+        "/" => Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(INDEX))
+            .expect("Response should build"),
+        "/pairs" => {
+            let pairs = gateway
+                .client()
+                .list_supported_pairs(gateway.request(Empty {}))
+                .await;
+            match pairs {
+                Ok(response) => {
+                    let pairs = response
+                        .into_inner()
+                        .pairs
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>();
+                    Response::builder()
+                        .header(CONTENT_TYPE, "application/json")
+                        .body(Body::from(json!(pairs).to_string()))
+                        .expect("Response should build")
+                }
+                Err(status) => Response::builder()
+                    .status(StatusCode::BAD_GATEWAY)
+                    .body(Body::from(status.message().to_string()))
+                    .expect("Response should build"),
+            }
+        }
+        "/summaries" => {
+            let Some(traded_pair) = requested_pair(request.uri().query()) else {
+                return status(StatusCode::BAD_REQUEST);
+            };
+            let gateway = gateway.clone();
+            event_stream(|events| async move {
+                let request = gateway.request(OrderBookRequest {
+                    traded_pair: Some(traded_pair),
+                    depth: DEPTH,
+                    ..Default::default()
+                });
+                let summaries = gateway.client().book_summary(request).await;
+                forward(events, summaries, |summary| {
+                    // Heartbeats keep the browser's connection alive, and show whether it's still open
+                    (!summary.is_heartbeat()).then(|| top_of_book(&summary))
+                })
+                .await
+            })
+        }
+        "/status" => {
+            let gateway = gateway.clone();
+            event_stream(|events| async move {
+                let statuses = gateway
+                    .client()
+                    .watch_exchange_status(gateway.request(Empty {}))
+                    .await;
+                forward(events, statuses, |status| Some(connector_status(&status))).await
+            })
+        }
+        _ => status(StatusCode::NOT_FOUND),
+    }
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .body(Body::empty())
+        .expect("Response should build")
+}
+
+/// The pair in the query, written as e.g. `?pair=ETH-BTC`.
+fn requested_pair(query: Option<&str>) -> Option<TradedPair> {
+    form_urlencoded::parse(query?.as_bytes())
+        .find(|(key, _)| key == "pair")
+        .and_then(|(_, pair)| parse_pair(&pair))
+}
+
+/// A server-sent event stream, written to by `produce` in the background.
+fn event_stream<F, Fut>(produce: F) -> Response<Body>
+where
+    F: FnOnce(Sender) -> Fut,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let (events, body) = Body::channel();
+    tokio::spawn(produce(events));
+    Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(body)
+        .expect("Response should build")
+}
+
+/// Send each message of a gRPC stream to the browser as an event, until either of them closes the stream. A message
+/// mapped to `None` is sent as a comment.
+async fn forward<T>(
+    mut events: Sender,
+    stream: Result<tonic::Response<Streaming<T>>, Status>,
+    to_json: impl Fn(T) -> Option<Value>,
+) {
+    let mut stream = match stream {
+        Ok(response) => response.into_inner(),
+        Err(status) => {
+            let _ = events.send_data(error_event(&status)).await;
+            return;
+        }
+    };
+
+    loop {
+        let event = match stream.message().await {
+            Ok(Some(message)) => match to_json(message) {
+                Some(json) => Bytes::from(format!("data: {json}\n\n")),
+                None => Bytes::from_static(b":\n\n"),
+            },
+            Ok(None) => return,
+            Err(status) => {
+                let _ = events.send_data(error_event(&status)).await;
+                return;
+            }
+        };
+        // The browser has gone, dropping the stream unsubscribes
+        if events.send_data(event).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn error_event(status: &Status) -> Bytes {
+    Bytes::from(format!(
+        "event: error\ndata: {}\n\n",
+        json!(status.message())
+    ))
+}
+
+fn top_of_book(summary: &Summary) -> Value {
+    let levels = |levels: &[Level]| {
+        levels
+            .iter()
+            .map(|level| json!({"exchange": level.exchange, "price": level.price, "amount": level.amount}))
+            .collect::<Vec<_>>()
+    };
+    json!({
+        "spread": summary.spread,
+        "bids": levels(&summary.bids),
+        "asks": levels(&summary.asks),
+        "degraded": summary.degraded,
+        "missing_exchanges": summary.missing_exchanges,
+        "exchanges_in_maintenance": summary.exchanges_in_maintenance,
+    })
+}
+
+fn connector_status(status: &ConnectorStatus) -> Value {
+    json!({
+        "timestamp_millis": status.timestamp_millis,
+        "exchange": status.exchange,
+        "pair": status.traded_pair.as_ref().map(ToString::to_string),
+        "event": status.event().as_str_name(),
+        "detail": status.detail,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use order_book_service_types::proto::{Level, Summary, TradedPair};
+
+    use super::{requested_pair, top_of_book};
+
+    #[test]
+    fn should_parse_the_requested_pair() {
+        assert_eq!(
+            requested_pair(Some("pair=eth-btc")),
+            Some(TradedPair::new("ETH", "BTC"))
+        );
+        assert_eq!(requested_pair(Some("pair=ETHBTC")), None);
+        assert_eq!(requested_pair(None), None);
+    }
+
+    #[test]
+    fn should_render_the_top_of_book() {
+        let summary = Summary {
+            spread: 0.5,
+            bids: vec![Level::new("Binance", 10.0, 2.0)],
+            asks: vec![Level::new("Bitstamp", 10.5, 1.0)],
+            missing_exchanges: vec!["Kraken".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            top_of_book(&summary),
+            json!({
+                "spread": 0.5,
+                "bids": [{"exchange": "Binance", "price": 10.0, "amount": 2.0}],
+                "asks": [{"exchange": "Bitstamp", "price": 10.5, "amount": 1.0}],
+                "degraded": false,
+                "missing_exchanges": ["Kraken"],
+                "exchanges_in_maintenance": [],
+            })
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! A read-only demo of the full gRPC surface over synthetic books, for trying the client and CLI where the real
+//! exchanges can't be reached. See [serve_demo](crate::serve_demo).
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use tonic::{Request, Status};
+
+/// Requests each client address can make in a [WINDOW].
+pub(crate) const DEMO_REQUESTS_PER_WINDOW: usize = 60;
+/// The window requests are counted over.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits the requests each client address makes, so a public demo can't be used as a data feed.
+///
+/// Clients connecting over a Unix socket or in process have no address and share one limit.
+#[derive(Clone, Debug)]
+pub(crate) struct DemoRateLimit {
+    max_requests: usize,
+    requests: Arc<Mutex<HashMap<Option<IpAddr>, VecDeque<Instant>>>>,
+}
+
+impl DemoRateLimit {
+    pub(crate) fn new(max_requests: usize) -> Self {
+        Self {
+            max_requests,
+            requests: Arc::default(),
+        }
+    }
+
+    /// Count the request against its client's limit, refusing it once the limit is reached.
+    // Status is large but is returned straight to the client
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn check(&self, request: &Request<()>) -> Result<(), Status> {
+        let now = Instant::now();
+        let mut requests = self.requests.lock().expect("Should lock");
+        // Clients which haven't been seen for a window are forgotten
+        requests.retain(|_, times| {
+            while times.front().is_some_and(|time| now - *time >= WINDOW) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+
+        let times = requests
+            .entry(request.remote_addr().map(|addr| addr.ip()))
+            .or_default();
+        if times.len() >= self.max_requests {
+            return Err(Status::resource_exhausted(format!(
+                "The demo allows {} requests a minute",
+                self.max_requests
+            )));
+        }
+        times.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::advance;
+    use tonic::{Code, Request};
+
+    use super::DemoRateLimit;
+
+    #[tokio::test(start_paused = true)]
+    async fn should_limit_requests_over_a_sliding_window() {
+        let limit = DemoRateLimit::new(2);
+        assert!(limit.check(&Request::new(())).is_ok());
+        advance(Duration::from_secs(30)).await;
+        assert!(limit.check(&Request::new(())).is_ok());
+
+        let refused = limit.check(&Request::new(())).expect_err("Should refuse");
+        assert_eq!(refused.code(), Code::ResourceExhausted);
+
+        // The first request leaves the window
+        advance(Duration::from_secs(30)).await;
+        assert!(limit.check(&Request::new(())).is_ok());
+        assert!(limit.check(&Request::new(())).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+use std::{
+    fmt::{Display, Formatter},
+    net::SocketAddr,
+    path::Path,
+    time::Duration,
+};
+
+use futures::future::join_all;
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::{
+    net::TcpListener,
+    sync::broadcast::{error::TryRecvError, Receiver},
+    time::{timeout, Instant},
+};
+
+use order_book_service_types::proto::{ConnectorEvent, ConnectorStatus, TradedPair};
+
+use crate::{
+    config::Config,
+    connector_status::ConnectorStatusBus,
+    error::error_chain,
+    exchange::{fixed_depth_hint, BoxedExchange, ConnectorContext},
+    exchanges::live_exchanges,
+    tap::FrameTap,
+    Connectors,
+};
+
+/// How long each exchange has to connect and send its first orderbook
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Every exchange is probed with a pair they all offer.
+fn probe_pair() -> TradedPair {
+    TradedPair::new("ETH", "BTC")
+}
+
+/// The results of the checks run by [doctor].
+#[derive(Debug, Default)]
+pub struct DoctorReport {
+    checks: Vec<Check>,
+}
+
+#[derive(Debug)]
+struct Check {
+    name: String,
+    /// A description of what passed or why it failed
+    outcome: Result<String, String>,
+}
+
+impl DoctorReport {
+    pub fn is_healthy(&self) -> bool {
+        self.checks.iter().all(|check| check.outcome.is_ok())
+    }
+
+    fn record(&mut self, name: impl Into<String>, outcome: Result<String, String>) {
+        self.checks.push(Check {
+            name: name.into(),
+            outcome,
+        });
+    }
+}
+
+impl Display for DoctorReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .checks
+            .iter()
+            .map(|check| check.name.len())
+            .max()
+            .unwrap_or_default();
+
+        for check in self.checks.iter() {
+            let (label, detail) = match &check.outcome {
+                Ok(detail) => ("ok", detail),
+                Err(detail) => ("FAIL", detail),
+            };
+            writeln!(f, "[{label:^4}] {:width$}  {detail}", check.name)?;
+        }
+
+        let failed = self
+            .checks
+            .iter()
+            .filter(|check| check.outcome.is_err())
+            .count();
+        write!(f, "{} checks, {failed} failed", self.checks.len())
+    }
+}
+
+/// Check that the service could run with the config at `config_path`, or the defaults when there isn't one.
+///
+/// The config is validated, the gRPC server's address bound, and each exchange streamed from until its first orderbook.
+pub async fn doctor(config_path: Option<&Path>) -> DoctorReport {
+    let mut report = DoctorReport::default();
+
+    let config = match config_path {
+        Some(path) => Config::from_file(path).map(|config| (config, path.display().to_string())),
+        None => Ok((Config::default(), "defaults".to_string())),
+    };
+    match config {
+        Ok((config, source)) => {
+            report.record("config", Ok(format!("Loaded {source}")));
+            run_checks(&mut report, &config, live_exchanges).await;
+        }
+        // Nothing else can be checked without a config
+        Err(err) => report.record("config", Err(format!("{err:#}"))),
+    }
+
+    report
+}
+
+async fn run_checks(report: &mut DoctorReport, config: &Config, connectors: Connectors) {
+    let grpc_bind = match &config.unix_socket {
+        #[cfg(unix)]
+        Some(socket_path) => check_unix_socket(socket_path).await,
+        _ => check_tcp_port(SocketAddr::from(([0, 0, 0, 0], config.port))).await,
+    };
+    report.record("grpc_bind", grpc_bind);
+    if let Some(metrics_port) = config.metrics_port {
+        let address = SocketAddr::new(config.metrics_bind_address, metrics_port);
+        report.record("metrics_bind", check_tcp_port(address).await);
+    }
+    if let Some(admin_port) = config.admin.port {
+        let address = SocketAddr::new(config.admin.bind_address, admin_port);
+        report.record("admin_bind", check_tcp_port(address).await);
+    }
+
+    let status_bus = ConnectorStatusBus::new(config.channels.connector_status);
+    let exchanges = connectors(ConnectorContext::new(
+        config,
+        FrameTap::new(config.tap.clone()),
+        status_bus.clone(),
+    ));
+
+    let probes = exchanges
+        .iter()
+        .map(|exchange| probe_exchange(exchange, status_bus.subscribe()));
+    for (exchange, outcome) in exchanges.iter().zip(join_all(probes).await) {
+        report.record(exchange.name(), outcome);
+    }
+}
+
+async fn check_tcp_port(address: SocketAddr) -> Result<String, String> {
+    TcpListener::bind(address)
+        .await
+        .map(|_| format!("Able to bind {address}"))
+        .map_err(|err| format!("Unable to bind {address}: {err}"))
+}
+
+#[cfg(unix)]
+async fn check_unix_socket(socket_path: &Path) -> Result<String, String> {
+    let display = socket_path.display();
+    if let Ok(metadata) = std::fs::symlink_metadata(socket_path) {
+        if !metadata.file_type().is_socket() {
+            return Err(format!("{display} exists and isn't a socket"));
+        }
+        // The server replaces a stale socket but shouldn't take over one which is being served
+        return match UnixStream::connect(socket_path).await {
+            Ok(_) => Err(format!("{display} is in use by another process")),
+            Err(_) => Ok(format!("Stale socket {display} will be replaced")),
+        };
+    }
+
+    UnixListener::bind(socket_path).map_err(|err| format!("Unable to bind {display}: {err}"))?;
+    let _ = std::fs::remove_file(socket_path);
+    Ok(format!("Able to bind {display}"))
+}
+
+/// Stream the probe pair from an exchange, which requires connecting and receiving its first message.
+async fn probe_exchange(
+    exchange: &BoxedExchange,
+    mut statuses: Receiver<ConnectorStatus>,
+) -> Result<String, String> {
+    let started = Instant::now();
+    let pair = probe_pair();
+    let mut orderbooks = exchange
+        .stream_order_book_for_pair(&pair, fixed_depth_hint(1))
+        .map_err(|err| error_chain(&err))?;
+
+    match timeout(PROBE_TIMEOUT, orderbooks.recv()).await {
+        Ok(Some(_)) => Ok(format!(
+            "Received a {pair} orderbook after {}ms",
+            started.elapsed().as_millis()
+        )),
+        Ok(None) => Err(format!("The {pair} stream ended before an orderbook")),
+        Err(_) => {
+            // Connectors retry in the background, their last disconnect explains why nothing arrived
+            let mut last_disconnect = None;
+            loop {
+                match statuses.try_recv() {
+                    Ok(status)
+                        if status.exchange == exchange.name()
+                            && status.event() == ConnectorEvent::Disconnected =>
+                    {
+                        last_disconnect = Some(status.detail);
+                    }
+                    Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+                    Err(_) => break,
+                }
+            }
+
+            let waited = PROBE_TIMEOUT.as_secs();
+            Err(match last_disconnect {
+                Some(reason) => {
+                    format!("No {pair} orderbook within {waited}s, disconnected: {reason}")
+                }
+                None => format!("No {pair} orderbook within {waited}s"),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{config::Config, exchanges::simulated::simulated_exchanges};
+
+    use super::{doctor, run_checks, DoctorReport};
+
+    #[tokio::test(start_paused = true)]
+    async fn should_pass_when_exchanges_stream_and_the_port_binds() {
+        let config = Config {
+            port: 0,
+            ..Config::default()
+        };
+
+        let mut report = DoctorReport::default();
+        run_checks(&mut report, &config, simulated_exchanges).await;
+
+        assert!(report.is_healthy(), "{report}");
+        // The bind check and both simulated exchanges
+        assert_eq!(report.checks.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn should_fail_on_an_unreadable_config() {
+        let report = doctor(Some(Path::new("/nonexistent/config.toml"))).await;
+
+        assert!(!report.is_healthy());
+        assert!(report.to_string().ends_with("1 checks, 1 failed"));
+    }
+}
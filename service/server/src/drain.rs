@@ -0,0 +1,194 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    sync::watch,
+    time::{sleep, Instant},
+};
+use tonic::Status;
+use tracing::info;
+
+use order_book_service_types::proto::{CloseReason, DrainResult};
+
+use crate::{config::DrainConfig, subscribers::Subscribers};
+
+/// How often a drain checks whether the subscriptions have closed.
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum DrainState {
+    Serving,
+    Draining,
+    /// The server should shut down
+    Drained,
+}
+
+/// Drains the server ahead of a shutdown, so it can be replaced without its clients noticing, e.g. in a rolling deploy
+/// alongside a [standby](crate::standby).
+///
+/// Once draining new subscriptions are refused, and open ones are ended with a status pointing their clients at the
+/// configured alternative server. The drain finishes once they've all closed or the deadline passes.
+#[derive(Clone, Debug)]
+pub(crate) struct Drain {
+    state: Arc<watch::Sender<DrainState>>,
+    alternative_address: Option<String>,
+    deadline: Duration,
+}
+
+impl Drain {
+    pub(crate) fn new(config: &DrainConfig) -> Self {
+        Self {
+            state: Arc::new(watch::channel(DrainState::Serving).0),
+            alternative_address: config.alternative_address.clone(),
+            deadline: config.deadline(),
+        }
+    }
+
+    /// The status refusing or ending a subscription while draining.
+    pub(crate) fn status(&self) -> Status {
+        CloseReason::server_shutdown(
+            "The server is draining before shutting down",
+            self.alternative_address.as_deref(),
+        )
+    }
+
+    /// Refuse new subscriptions once draining.
+    // Status is large but is returned straight to the client
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn admit(&self) -> Result<(), Status> {
+        if *self.state.borrow() == DrainState::Serving {
+            Ok(())
+        } else {
+            Err(self.status())
+        }
+    }
+
+    /// Resolves once the server starts draining.
+    pub(crate) async fn started(&self) {
+        self.reached(DrainState::Draining).await
+    }
+
+    /// Resolves once the drain has finished and the server should shut down.
+    pub(crate) async fn finished(&self) {
+        self.reached(DrainState::Drained).await
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        *self.state.borrow() == DrainState::Drained
+    }
+
+    async fn reached(&self, state: DrainState) {
+        let mut changes = self.state.subscribe();
+        // The sender is held by self, so the channel can't close while waiting
+        while *changes.borrow_and_update() < state {
+            let _ = changes.changed().await;
+        }
+    }
+
+    /// Start draining, then wait for the open subscriptions to close, or `deadline` to pass, before finishing.
+    ///
+    /// The configured deadline is used when `deadline` is `None`.
+    pub(crate) async fn drain(
+        &self,
+        deadline: Option<Duration>,
+        subscribers: &Subscribers,
+    ) -> Result<DrainResult, Status> {
+        let started = self.state.send_if_modified(|state| {
+            let serving = *state == DrainState::Serving;
+            if serving {
+                *state = DrainState::Draining;
+            }
+            serving
+        });
+        if !started {
+            return Err(Status::failed_precondition(
+                "The server is already draining",
+            ));
+        }
+
+        let notified = subscribers.count();
+        info!(
+            "Draining {notified} subscriptions to {}",
+            self.alternative_address.as_deref().unwrap_or("any server")
+        );
+        let deadline = Instant::now() + deadline.unwrap_or(self.deadline);
+        while subscribers.count() > 0 && Instant::now() < deadline {
+            sleep(CLOSE_POLL_INTERVAL).await;
+        }
+        let remaining = subscribers.count();
+        info!("Drained, shutting down with {remaining} subscriptions still open");
+        self.state.send_replace(DrainState::Drained);
+
+        Ok(DrainResult {
+            notified: notified as u32,
+            remaining: remaining as u32,
+            alternative_address: self.alternative_address.clone().unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tonic::Code;
+
+    use order_book_service_types::proto::{CloseReason, TradedPair};
+
+    use crate::{config::DrainConfig, subscribers::Subscribers};
+
+    use super::Drain;
+
+    #[tokio::test(start_paused = true)]
+    async fn should_refuse_subscriptions_and_finish_once_subscriptions_close() {
+        let drain = Drain::new(&DrainConfig {
+            alternative_address: Some("http://standby:3030".to_string()),
+            deadline_secs: 30,
+        });
+        let subscribers = Subscribers::default();
+        let registration =
+            subscribers.register(TradedPair::new("ETH", "BTC"), 10, Default::default(), None);
+        assert!(drain.admit().is_ok());
+
+        let draining = tokio::spawn({
+            let drain = drain.clone();
+            let subscribers = subscribers.clone();
+            async move { drain.drain(None, &subscribers).await }
+        });
+        drain.started().await;
+        let refused = drain.admit().unwrap_err();
+        assert_eq!(refused.code(), Code::Unavailable);
+        assert_eq!(
+            CloseReason::alternative_address(&refused).as_deref(),
+            Some("http://standby:3030")
+        );
+        assert!(!drain.is_finished());
+
+        // The subscription's client reconnects elsewhere
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        drop(registration);
+        drain.finished().await;
+        let result = draining.await.unwrap().unwrap();
+        assert_eq!(result.notified, 1);
+        assert_eq!(result.remaining, 0);
+        assert_eq!(result.alternative_address, "http://standby:3030");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_finish_at_the_deadline_with_subscriptions_still_open() {
+        let drain = Drain::new(&DrainConfig::default());
+        let subscribers = Subscribers::default();
+        let _registration =
+            subscribers.register(TradedPair::new("ETH", "BTC"), 10, Default::default(), None);
+
+        let result = drain
+            .drain(Some(Duration::from_secs(5)), &subscribers)
+            .await
+            .unwrap();
+        assert_eq!(result.remaining, 1);
+        assert!(drain.is_finished());
+        assert_eq!(
+            drain.drain(None, &subscribers).await.unwrap_err().code(),
+            Code::FailedPrecondition
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use std::future::Future;
+
+use anyhow::Error;
+use futures::{future, FutureExt};
+
+use crate::{
+    clock::{SharedClock, SystemClock},
+    config::Config,
+    error::ServerError,
+    exchange::BoxedExchange,
+    exchanges::live_exchanges,
+    grpc_server::Transport,
+    in_process::{start_with_connectors, InProcessServer},
+    run, telemetry, Connectors,
+};
+
+/// The service along with any exchanges of your own to aggregate alongside the built-in ones, for adding proprietary or
+/// internal liquidity sources without changing the service.
+///
+/// ```ignore
+/// Aggregator::new(config)
+///     .with_exchange(Box::new(InternalDesk::new()))
+///     .serve()
+///     .await?;
+/// ```
+pub struct Aggregator {
+    config: Config,
+    connectors: Connectors,
+    custom: Vec<BoxedExchange>,
+    clock: SharedClock,
+}
+
+impl Aggregator {
+    /// Aggregates from the live exchanges, plus any added with [Aggregator::with_exchange].
+    pub fn new(config: Config) -> Self {
+        Self::with_connectors(config, live_exchanges)
+    }
+
+    pub(crate) fn with_connectors(config: Config, connectors: Connectors) -> Self {
+        Self {
+            config,
+            connectors,
+            custom: Vec::new(),
+            clock: SystemClock::shared(),
+        }
+    }
+
+    /// Aggregate books from `exchange` too, see [Exchange](crate::Exchange) for what it must uphold.
+    ///
+    /// Its name must differ from every other exchange's, the service fails to start otherwise.
+    pub fn with_exchange(mut self, exchange: BoxedExchange) -> Self {
+        self.custom.push(exchange);
+        self
+    }
+
+    /// Read the time from `clock` rather than the system's, e.g. a [ManualClock](crate::ManualClock) in tests.
+    ///
+    /// ```ignore
+    /// let clock = ManualClock::starting_at(UNIX_EPOCH);
+    /// let server = Aggregator::new(config).with_clock(clock.clone()).start_in_process();
+    /// clock.step_wall_clock(Duration::from_secs(60));
+    /// ```
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// As [serve](crate::serve), including the exchanges added.
+    pub async fn serve(self) -> Result<(), Error> {
+        self.serve_until(future::pending()).await
+    }
+
+    /// As [serve](Aggregator::serve), but once `stop` resolves the server is drained and then shuts down, e.g. when the
+    /// embedding application is asked to stop.
+    pub async fn serve_until(
+        self,
+        stop: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<(), Error> {
+        telemetry::init(&self.config.tracing)?;
+        #[cfg(feature = "runtime-metrics")]
+        crate::runtime_metrics::spawn_monitor(
+            tokio::runtime::Handle::current(),
+            self.config.runtime_metrics.clone(),
+        )?;
+
+        let transport = Transport::from_config(&self.config);
+        let err = run(
+            self.config,
+            transport,
+            self.connectors,
+            self.custom,
+            self.clock,
+            stop.boxed(),
+        )
+        .await;
+        telemetry::shutdown();
+        match err {
+            ServerError::Drained => Ok(()),
+            err => Err(err.into()),
+        }
+    }
+
+    /// As [start_in_process](crate::start_in_process), including the exchanges added.
+    pub fn start_in_process(self) -> InProcessServer {
+        start_with_connectors(self.config, self.connectors, self.custom, self.clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use futures::future::BoxFuture;
+    use futures_util::StreamExt;
+    use tokio::{
+        sync::mpsc::{channel, Receiver},
+        time::{sleep, Instant},
+    };
+    use tonic::Streaming;
+    use tracing::Span;
+
+    use order_book_service_types::proto::{
+        orderbook_aggregator_client::OrderbookAggregatorClient, ExchangeId, Level,
+        OrderBookRequest, Summary, TradedPair,
+    };
+
+    use crate::{
+        clock::ManualClock,
+        config::Config,
+        error::ExchangeError,
+        exchange::{
+            BoxedExchange, DepthHint, Exchange, ExchangeInfo, OrderBook, ReceivedOrderbook,
+        },
+        exchanges::simulated::simulated_exchanges,
+    };
+
+    use super::Aggregator;
+
+    /// An internal source quoting one level either side of ETH-BTC.
+    #[derive(Clone)]
+    struct InternalDesk;
+
+    struct DeskBook;
+
+    impl OrderBook for DeskBook {
+        fn source(&self) -> ExchangeId {
+            ExchangeId::from("InternalDesk")
+        }
+
+        fn best_asks(&self, depth: usize, levels: &mut Vec<Level>) {
+            levels.extend(
+                [Level::new("InternalDesk", 0.0705, 7.0)]
+                    .into_iter()
+                    .take(depth),
+            );
+        }
+
+        fn best_bids(&self, depth: usize, levels: &mut Vec<Level>) {
+            levels.extend(
+                [Level::new("InternalDesk", 0.0695, 7.0)]
+                    .into_iter()
+                    .take(depth),
+            );
+        }
+    }
+
+    impl Exchange for InternalDesk {
+        fn info(&self) -> ExchangeInfo {
+            ExchangeInfo::new("InternalDesk")
+        }
+
+        fn stream_order_book_for_pair(
+            &self,
+            traded_pair: &TradedPair,
+            _depth_hint: DepthHint,
+        ) -> Result<Receiver<ReceivedOrderbook>, ExchangeError> {
+            if *traded_pair != TradedPair::new("ETH", "BTC") {
+                return Err(ExchangeError::UnsupportedPair {
+                    exchange: self.id(),
+                    pair: traded_pair.clone(),
+                });
+            }
+            let (books, receiver) = channel::<ReceivedOrderbook>(1);
+            tokio::spawn(async move {
+                while books
+                    .send((Box::new(DeskBook), Instant::now(), Span::none()))
+                    .await
+                    .is_ok()
+                {
+                    sleep(Duration::from_millis(100)).await;
+                }
+            });
+            Ok(receiver)
+        }
+
+        fn supported_pairs(&self) -> BoxFuture<'static, Result<Vec<TradedPair>, ExchangeError>> {
+            Box::pin(async { Ok(vec![TradedPair::new("ETH", "BTC")]) })
+        }
+
+        fn clone_dyn(&self) -> BoxedExchange {
+            Box::new(self.clone())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_aggregate_custom_exchanges_alongside_the_built_in_ones() {
+        let server = Aggregator::with_connectors(Config::default(), simulated_exchanges)
+            .with_exchange(Box::new(InternalDesk))
+            .start_in_process();
+
+        let mut summaries = OrderbookAggregatorClient::new(server.channel())
+            .book_summary(OrderBookRequest {
+                traded_pair: Some(TradedPair::new("ETH", "BTC")),
+                depth: 50,
+                ..Default::default()
+            })
+            .await
+            .expect("Should subscribe")
+            .into_inner();
+
+        // The desk's levels are merged with those of the simulated exchanges' books received alongside
+        let summary = loop {
+            let summary = summaries
+                .next()
+                .await
+                .expect("Should stream")
+                .expect("Should summarise");
+            if summary
+                .asks
+                .iter()
+                .any(|level| level.exchange == "InternalDesk")
+            {
+                break summary;
+            }
+        };
+        assert!(summary
+            .asks
+            .iter()
+            .any(|level| level.exchange.starts_with("Simulated")));
+        assert!(summary
+            .asks
+            .iter()
+            .any(|level| level.exchange == "InternalDesk" && level.price == 0.0705));
+        assert!(summary
+            .bids
+            .iter()
+            .any(|level| level.exchange == "InternalDesk" && level.price == 0.0695));
+    }
+
+    /// The desk doesn't timestamp its books, so they're timed by when they were received.
+    async fn desk_received_at(summaries: &mut Streaming<Summary>) -> SystemTime {
+        loop {
+            let summary = summaries
+                .next()
+                .await
+                .expect("Should stream")
+                .expect("Should summarise");
+            let desk = summary.metadata.and_then(|metadata| {
+                metadata
+                    .source_timestamps
+                    .into_iter()
+                    .find(|timestamp| timestamp.exchange == "InternalDesk")
+            });
+            if let Some(desk) = desk {
+                break UNIX_EPOCH + Duration::from_micros(desk.corrected_timestamp_micros);
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_time_books_by_the_clock_given() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = ManualClock::starting_at(start);
+        let server = Aggregator::with_connectors(Config::default(), simulated_exchanges)
+            .with_exchange(Box::new(InternalDesk))
+            .with_clock(clock.clone())
+            .start_in_process();
+
+        let mut summaries = OrderbookAggregatorClient::new(server.channel())
+            .book_summary(OrderBookRequest {
+                traded_pair: Some(TradedPair::new("ETH", "BTC")),
+                depth: 50,
+                ..Default::default()
+            })
+            .await
+            .expect("Should subscribe")
+            .into_inner();
+
+        let received_at = desk_received_at(&mut summaries).await;
+        assert!(received_at >= start && received_at < start + Duration::from_secs(60));
+
+        let stepped = start + Duration::from_secs(3600);
+        clock.step_wall_clock(Duration::from_secs(3600));
+        let received_at = loop {
+            let received_at = desk_received_at(&mut summaries).await;
+            if received_at >= stepped {
+                break received_at;
+            }
+        };
+        assert!(received_at < stepped + Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn should_refuse_exchanges_named_like_another() {
+        let server = Aggregator::with_connectors(Config::default(), simulated_exchanges)
+            .with_exchange(Box::new(InternalDesk))
+            .with_exchange(Box::new(InternalDesk))
+            .start_in_process();
+
+        let err = server.stopped().await;
+        assert_eq!(
+            err.to_string(),
+            "Exchange InternalDesk clashes with InternalDesk, exchange names must be unique regardless of case"
+        );
+    }
+}
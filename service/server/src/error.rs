@@ -0,0 +1,215 @@
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::{error::Error as StdError, io, net::SocketAddr, time::Duration};
+
+use thiserror::Error;
+use tokio::task::JoinError;
+use tonic::{Code, Status};
+
+use order_book_service_types::proto::{CloseReason, ExchangeId, TradedPair};
+
+/// Errors raised by an exchange connector.
+#[derive(Debug, Error)]
+pub enum ExchangeError {
+    #[error("Requested traded pair {pair} is not supported by {exchange}")]
+    UnsupportedPair {
+        exchange: ExchangeId,
+        pair: TradedPair,
+    },
+    #[error("Error requesting {exchange} {resource}")]
+    Request {
+        exchange: ExchangeId,
+        resource: &'static str,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("Error parsing {exchange} {resource}")]
+    Parse {
+        exchange: ExchangeId,
+        resource: &'static str,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("Timed out requesting {exchange} {resource}")]
+    Timeout {
+        exchange: ExchangeId,
+        resource: &'static str,
+    },
+    /// Raised by an [Exchange](crate::Exchange) implemented outside the service
+    #[error("Error from {exchange}")]
+    Custom {
+        exchange: ExchangeId,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+}
+
+/// Reasons an aggregator stops, broadcast to every subscription for its pair.
+#[derive(Debug, Error)]
+pub(crate) enum AggregatorError {
+    #[error("Unknown rate exchange {0}")]
+    UnknownRateExchange(String),
+    #[error("No rate source for conversion from {0}")]
+    NoRateSource(TradedPair),
+    #[error("Unable to stream the rate for a quote conversion")]
+    RateStream(#[source] ExchangeError),
+    #[error("Unable to connect to enough exchanges, aggregation not possible for {pair}")]
+    TooFewExchanges {
+        pair: TradedPair,
+        /// The last error from an exchange which couldn't be connected to
+        #[source]
+        source: Option<ExchangeError>,
+    },
+    #[error("Exchange disconnected, leaving only one connection - unable to aggregate {0}")]
+    ExchangeDisconnected(TradedPair),
+    #[error("{pair} was delisted by {}, leaving too few exchanges to aggregate it", .delisted.join(", "))]
+    PairRetired {
+        pair: TradedPair,
+        delisted: Vec<String>,
+    },
+}
+
+/// Reasons a standby stops relaying a pair from its upstream, see [standby](crate::standby).
+#[derive(Debug, Error)]
+pub(crate) enum UpstreamError {
+    #[error("Unable to subscribe to the upstream")]
+    Subscribe(#[source] Status),
+    #[error("The upstream's stream failed")]
+    Stream(#[source] Status),
+    #[error("The upstream ended the stream")]
+    Ended,
+    #[error("The upstream has sent nothing for {0:?}")]
+    Silent(Duration),
+}
+
+/// Reasons the service stops serving.
+#[derive(Debug, Error)]
+pub(crate) enum ServerError {
+    #[cfg(unix)]
+    #[error("Unable to remove stale Unix socket {}", .path.display())]
+    StaleSocket {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Unable to bind {addr}")]
+    BindPort {
+        addr: SocketAddr,
+        #[source]
+        source: io::Error,
+    },
+    #[cfg(unix)]
+    #[error("{} exists and isn't a Unix socket, it's left in place rather than replaced", .path.display())]
+    NotASocket { path: PathBuf },
+    #[cfg(unix)]
+    #[error("Unable to bind Unix socket {}", .path.display())]
+    Bind {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("gRPC server shutdown")]
+    Transport(#[from] tonic::transport::Error),
+    #[error("A service task failed")]
+    Task(#[from] JoinError),
+    #[error(
+        "Exchange {name} clashes with {existing}, exchange names must be unique regardless of case"
+    )]
+    DuplicateExchange { name: String, existing: String },
+    #[error("Quote conversions name {0}, which isn't an exchange the service aggregates from")]
+    UnknownConversionExchange(String),
+    #[error("Warm-up pairs can't be aggregated: {0}")]
+    UnlistedWarmUpPairs(String),
+    #[error("Should only end due to error - exited on OK")]
+    UnexpectedExit,
+    /// Not a failure, the server shut down once drained by the admin service
+    #[error("The server was drained")]
+    Drained,
+}
+
+impl From<&ExchangeError> for Status {
+    fn from(err: &ExchangeError) -> Self {
+        let message = error_chain(err);
+        match err {
+            ExchangeError::UnsupportedPair { .. } => Status::not_found(message),
+            ExchangeError::Request { .. }
+            | ExchangeError::Parse { .. }
+            | ExchangeError::Timeout { .. }
+            | ExchangeError::Custom { .. } => Status::unavailable(message),
+        }
+    }
+}
+
+impl From<&AggregatorError> for Status {
+    fn from(err: &AggregatorError) -> Self {
+        let message = error_chain(err);
+        match err {
+            // The service is misconfigured for the pair
+            AggregatorError::UnknownRateExchange(_) | AggregatorError::NoRateSource(_) => {
+                Status::failed_precondition(message)
+            }
+            AggregatorError::RateStream(source)
+            | AggregatorError::TooFewExchanges {
+                source: Some(source),
+                ..
+            } => Status::new(Status::from(source).code(), message),
+            AggregatorError::TooFewExchanges { source: None, .. }
+            | AggregatorError::ExchangeDisconnected(_) => {
+                CloseReason::UpstreamUnavailable.status(Code::Unavailable, message)
+            }
+            // Retrying won't help until the pair is listed again
+            AggregatorError::PairRetired { .. } => {
+                CloseReason::PairRetired.status(Code::FailedPrecondition, message)
+            }
+        }
+    }
+}
+
+/// An error's message followed by each of its causes, as `anyhow` formats errors with `{:#}`.
+pub(crate) fn error_chain(err: &dyn StdError) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::{Code, Status};
+
+    use order_book_service_types::proto::{ExchangeId, TradedPair};
+
+    use super::{AggregatorError, ExchangeError};
+
+    #[test]
+    fn should_map_aggregator_errors_to_status_codes() {
+        let pair = TradedPair::new("ETH", "BTC");
+        let unsupported = AggregatorError::TooFewExchanges {
+            pair: pair.clone(),
+            source: Some(ExchangeError::UnsupportedPair {
+                exchange: ExchangeId::Bitstamp,
+                pair: pair.clone(),
+            }),
+        };
+
+        let status = Status::from(&unsupported);
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(
+            status.message(),
+            "Unable to connect to enough exchanges, aggregation not possible for ETH-BTC: \
+             Requested traded pair ETH-BTC is not supported by Bitstamp"
+        );
+
+        assert_eq!(
+            Status::from(&AggregatorError::ExchangeDisconnected(pair.clone())).code(),
+            Code::Unavailable
+        );
+        assert_eq!(
+            Status::from(&AggregatorError::NoRateSource(pair)).code(),
+            Code::FailedPrecondition
+        );
+    }
+}
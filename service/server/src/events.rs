@@ -0,0 +1,35 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::sync::broadcast::{channel as broadcast_channel, Receiver, Sender};
+
+use order_book_service_types::proto::{service_event::Event, ServiceEvent};
+
+/// Broadcasts [ServiceEvent]s from anywhere in the service to subscribers of the ServiceEvents RPC.
+#[derive(Clone, Debug)]
+pub(crate) struct EventBus {
+    sender: Sender<ServiceEvent>,
+}
+
+impl EventBus {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast_channel(capacity);
+        Self { sender }
+    }
+
+    /// Timestamp and publish an event, it is dropped if there are no subscribers.
+    pub(crate) fn publish(&self, event: Event) {
+        let timestamp_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let _ = self.sender.send(ServiceEvent {
+            timestamp_millis,
+            event: Some(event),
+        });
+    }
+
+    pub(crate) fn subscribe(&self) -> Receiver<ServiceEvent> {
+        self.sender.subscribe()
+    }
+}
@@ -1,13 +1,45 @@
-use std::{fmt::Debug, str::FromStr};
+use std::{
+    fmt::Debug,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
 
-use anyhow::Error;
+use futures::future::BoxFuture;
 use serde::{de, Deserialize, Deserializer};
-use tokio::{sync::mpsc::Receiver, time::Instant};
+use tokio::{
+    sync::{
+        mpsc::Receiver,
+        watch::{channel as watch_channel, Receiver as WatchReceiver},
+    },
+    time::Instant,
+};
+use tracing::Span;
 
-use order_book_service_types::proto::{Level, TradedPair};
+use order_book_service_types::proto::{
+    AskLevel, BidLevel, ExchangeDetails, ExchangeId, Level, TradedPair,
+};
 
-pub(crate) type BoxedOrderbook = Box<dyn OrderBook + Send>;
-pub(crate) type BoxedExchange = Box<dyn Exchange + Send>;
+use crate::{
+    clock::{SharedClock, SystemClock},
+    config::Config,
+    connector_status::ConnectorStatusBus,
+    error::ExchangeError,
+    rate_limit::RateLimits,
+    tap::FrameTap,
+};
+
+pub type BoxedOrderbook = Box<dyn OrderBook + Send>;
+pub type BoxedExchange = Box<dyn Exchange + Send>;
+/// An orderbook along with when it was received and the span recording its receipt,
+/// so later processing of the book can be linked back to the exchange message.
+pub type ReceivedOrderbook = (BoxedOrderbook, Instant, Span);
+/// The number of levels consumers currently need, exchanges may use it to choose which stream to subscribe to.
+pub type DepthHint = WatchReceiver<usize>;
+
+/// A [DepthHint] which never changes.
+pub(crate) fn fixed_depth_hint(depth: usize) -> DepthHint {
+    watch_channel(depth).1
+}
 
 impl Clone for BoxedExchange {
     fn clone(&self) -> Self {
@@ -15,30 +47,200 @@ impl Clone for BoxedExchange {
     }
 }
 
+/// Shared settings and facilities provided to each exchange connector.
+#[derive(Clone)]
+pub(crate) struct ConnectorContext {
+    /// Capacity of the channel orderbooks are sent on
+    pub(crate) channel_capacity: usize,
+    pub(crate) frame_tap: FrameTap,
+    /// Connection events are published here for the WatchExchangeStatus RPC
+    pub(crate) status_bus: ConnectorStatusBus,
+    /// How long a stream can go without a message before it is reported stale
+    pub(crate) stale_after: Duration,
+    /// Connection attempts are made through these so each exchange's limits hold across every pair
+    pub(crate) rate_limits: RateLimits,
+    /// Books are stamped with when they were received by this
+    pub(crate) clock: SharedClock,
+}
+
+impl ConnectorContext {
+    pub(crate) fn new(
+        config: &Config,
+        frame_tap: FrameTap,
+        status_bus: ConnectorStatusBus,
+    ) -> Self {
+        Self {
+            channel_capacity: config.channels.exchange_orderbooks,
+            frame_tap,
+            status_bus,
+            stale_after: config.exchange_status.stale_after(),
+            rate_limits: RateLimits::new(config.rate_limits.clone()),
+            clock: SystemClock::shared(),
+        }
+    }
+
+    pub(crate) fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+/// What's known about a venue, centralised so the components which need it, e.g. fee adjustment and rate limiting, read
+/// it from one place.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExchangeInfo {
+    /// Unique among the aggregated exchanges and stable, as levels are labelled with it and config is keyed by it, e.g.
+    /// `taker_fees`.
+    pub name: &'static str,
+    pub id: ExchangeId,
+    /// The venue's published fees as fractions, `None` when not known
+    pub maker_fee: Option<f64>,
+    /// Applied to effective prices with `use_published_taker_fees`, unless `taker_fees` configures the exchange's fee
+    pub taker_fee: Option<f64>,
+    pub rate_limit: RateLimitProfile,
+    /// Where books are streamed from, `None` when the exchange isn't streamed over a websocket
+    pub websocket_url: Option<&'static str>,
+}
+
+impl ExchangeInfo {
+    /// Info for an exchange which publishes nothing beyond its name.
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            id: ExchangeId::from(name),
+            maker_fee: None,
+            taker_fee: None,
+            rate_limit: RateLimitProfile::default(),
+            websocket_url: None,
+        }
+    }
+
+    pub(crate) fn to_proto(&self) -> ExchangeDetails {
+        ExchangeDetails {
+            name: self.name.to_string(),
+            known_exchange: self.id.known() as i32,
+            maker_fee: self.maker_fee,
+            taker_fee: self.taker_fee,
+            max_connections_per_minute: self
+                .rate_limit
+                .max_connections_per_minute
+                .unwrap_or_default() as u32,
+            max_streams_per_connection: self
+                .rate_limit
+                .max_streams_per_connection
+                .unwrap_or_default() as u32,
+            min_message_interval_millis: self.rate_limit.min_message_interval.as_millis() as u64,
+            websocket_url: self.websocket_url.unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// The limits a venue publishes on connecting to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateLimitProfile {
+    /// Most connections which may be opened in any minute, connections are only limited by `rate_limits` when `None`
+    pub max_connections_per_minute: Option<usize>,
+    /// Most streams subscribed to over a single connection
+    pub max_streams_per_connection: Option<usize>,
+    /// The least time between messages sent over a connection
+    pub min_message_interval: Duration,
+}
+
 /// [Exchange] is a unified interface which can be applied to any exchange
-pub(crate) trait Exchange {
-    fn name(&self) -> &'static str;
+///
+/// Implement it to aggregate a source of your own alongside the built-in exchanges, see
+/// [Aggregator::with_exchange](crate::Aggregator::with_exchange). The exchange is cloned for each pair aggregated from
+/// it, and for polling its status, so clones should share anything which must be shared e.g. a rate limit.
+pub trait Exchange {
+    /// The venue's name and published metadata, which shouldn't change once the exchange is built.
+    fn info(&self) -> ExchangeInfo;
 
+    /// The [name](ExchangeInfo::name) from the exchange's [info](Exchange::info).
+    fn name(&self) -> &'static str {
+        self.info().name
+    }
+
+    fn id(&self) -> ExchangeId {
+        self.info().id
+    }
+
+    /// Stream books for `traded_pair` until the receiver is dropped, called once by each pair's aggregator.
+    ///
+    /// Return without waiting for a connection, connecting and sending from a spawned task. Each book sent replaces the
+    /// exchange's previous one, so send snapshots of the top of the book rather than diffs. Dropping the sender tells
+    /// the aggregator the exchange has disconnected, it's left out from then on, so reconnect within the task where
+    /// possible. Errors are retried with a backoff, except [ExchangeError::UnsupportedPair] for pairs not offered.
+    ///
+    /// `depth_hint` is the most levels of each side any subscription currently needs, it changes as they come and go.
     fn stream_order_book_for_pair(
         &self,
         traded_pair: &TradedPair,
-    ) -> Result<Receiver<(BoxedOrderbook, Instant)>, Error>;
+        depth_hint: DepthHint,
+    ) -> Result<Receiver<ReceivedOrderbook>, ExchangeError>;
+
+    /// Query the exchange for its current operational status.
+    /// Exchanges without a status endpoint report [VenueStatus::Unknown].
+    fn fetch_status(&self) -> BoxFuture<'static, Result<VenueStatus, ExchangeError>> {
+        Box::pin(async { Ok(VenueStatus::Unknown) })
+    }
+
+    /// Query the exchange for the pairs it currently offers.
+    ///
+    /// Used to list the pairs which can be subscribed to, and to check a pair is still listed when its stream ends.
+    fn supported_pairs(&self) -> BoxFuture<'static, Result<Vec<TradedPair>, ExchangeError>>;
+
+    /// The exchange's own symbol for a pair.
+    fn symbol_for_pair(&self, traded_pair: &TradedPair) -> String {
+        traded_pair.symbol_lower()
+    }
 
     // This method is required to allow the trait object to be Clone
     fn clone_dyn(&self) -> BoxedExchange;
 }
 
+/// The operational state of an exchange as reported by its status endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VenueStatus {
+    Operational,
+    /// The exchange is up but deposits/withdrawals or trading may be suspended
+    Maintenance,
+    /// The exchange does not expose its status
+    Unknown,
+}
+
 /// [OrderBook] is a unified interface which can be applied to an order book
 /// from any exchange regardless of format
-pub(crate) trait OrderBook {
-    /// The name of the exchange that produced the orderbook
-    fn source(&self) -> &'static str;
-    /// The difference between the best ask and best bid
-    fn spread(&self) -> f64;
-    /// The best [depth] asks - ordered High -> Low
-    fn best_asks(&self, depth: usize) -> Vec<Level>;
-    /// The best [depth] bids - ordered Low -> High
-    fn best_bids(&self, depth: usize) -> Vec<Level>;
+///
+/// Levels should have finite, positive prices and amounts, each labelled with the [source](OrderBook::source) exchange.
+pub trait OrderBook {
+    /// The exchange that produced the orderbook, the [id](Exchange::id) of the exchange which sent it
+    fn source(&self) -> ExchangeId;
+    /// The difference between the best ask and best bid, `NaN` when either side is empty
+    #[allow(unused)]
+    fn spread(&self) -> f64 {
+        match (self.best_ask(), self.best_bid()) {
+            (Some(ask), Some(bid)) => ask.price - bid.price,
+            _ => f64::NAN,
+        }
+    }
+    /// Append the best [depth] asks to `levels` - ordered Low -> High
+    fn best_asks(&self, depth: usize, levels: &mut Vec<Level>);
+    /// Append the best [depth] bids to `levels` - ordered High -> Low
+    fn best_bids(&self, depth: usize, levels: &mut Vec<Level>);
+    fn best_ask(&self) -> Option<Level> {
+        let mut levels = Vec::with_capacity(1);
+        self.best_asks(1, &mut levels);
+        levels.pop()
+    }
+    fn best_bid(&self) -> Option<Level> {
+        let mut levels = Vec::with_capacity(1);
+        self.best_bids(1, &mut levels);
+        levels.pop()
+    }
+    /// When the exchange produced the orderbook, `None` for exchanges which don't timestamp their messages
+    fn exchange_timestamp(&self) -> Option<SystemTime> {
+        None
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
@@ -56,67 +258,74 @@ impl Order {
     }
 }
 
-// This is only sorting by `price`. To sort orders across multiple exchanges,
-// secondary ordering on `amount` should applied to handle the case where two exchanges share a `price` level.
-impl PartialOrd for Order {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if self.price < other.price {
-            return Some(std::cmp::Ordering::Less);
-        } else if self.price > other.price {
-            return Some(std::cmp::Ordering::Greater);
-        }
-        Some(std::cmp::Ordering::Equal)
-    }
-}
-
 pub(crate) enum Ordering {
     LowToHigh,
     HighToLow,
 }
 
-/// Helper to sort a collection of orders and return a depth-constrained sub-set.
+/// Helper to sort a collection of orders and append a depth-constrained sub-set to `levels`.
 pub(crate) fn sort_orders_to_depth(
-    mut orders: Vec<Order>,
+    orders: &[Order],
     ordering: Ordering,
     depth: usize,
-    exchange: &str,
-) -> Vec<Level> {
+    exchange: &ExchangeId,
+    levels: &mut Vec<Level>,
+) {
+    // Levels already in the buffer are left as they are, only the appended ones are sorted
+    let start = levels.len();
+    levels.extend(
+        orders
+            .iter()
+            .filter_map(|order| Level::try_new(exchange.clone(), order.price, order.quantity)),
+    );
     match ordering {
-        Ordering::LowToHigh => orders.sort_by(|a, b| a.partial_cmp(b).unwrap()),
-        Ordering::HighToLow => orders.sort_by(|a, b| b.partial_cmp(a).unwrap()),
+        Ordering::LowToHigh => AskLevel::sort(&mut levels[start..]),
+        Ordering::HighToLow => BidLevel::sort(&mut levels[start..]),
     };
 
-    let depth_slice = &orders[..depth];
+    // Exchanges may provide fewer orders than requested
+    levels.truncate(start.saturating_add(depth));
+}
+
+/// A type parsed from exchange data by [type_from_str], which can refuse values that parse but can't be used.
+pub(crate) trait ExchangeValue: FromStr {
+    fn is_usable(&self) -> bool {
+        true
+    }
+}
 
-    depth_slice
-        .iter()
-        .map(|order| Level {
-            exchange: exchange.to_string(),
-            price: order.price,
-            amount: order.quantity,
-        })
-        .collect()
+/// `NaN` and `inf` parse as floats, but a single one would poison the ordering of a book.
+impl ExchangeValue for f64 {
+    fn is_usable(&self) -> bool {
+        self.is_finite()
+    }
 }
 
+impl ExchangeValue for u64 {}
+
 /// Data returned from exchanges is often stringified, this helper aids in converting these to their Rust types.
-fn type_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+pub(crate) fn type_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 where
     D: Deserializer<'de>,
-    T: FromStr,
+    T: ExchangeValue,
     T::Err: Debug,
 {
     let s = <&str>::deserialize(deserializer)?;
-    s.parse::<T>().map_err(|from_str_err| {
+    let value = s.parse::<T>().map_err(|from_str_err| {
         let err = format!("{from_str_err:?}");
         de::Error::custom(err)
-    })
+    })?;
+    if !value.is_usable() {
+        return Err(de::Error::custom(format!("{s} is not a finite number")));
+    }
+    Ok(value)
 }
 
 #[cfg(test)]
 mod tests {
     use lazy_static::lazy_static;
 
-    use order_book_service_types::proto::Level;
+    use order_book_service_types::proto::{ExchangeId, Level};
 
     use super::{sort_orders_to_depth, Order, Ordering};
 
@@ -158,11 +367,13 @@ mod tests {
             .collect::<Vec<Level>>();
 
         // For this I've used the opposite sorting to what is expected as the input.
-        let actual = sort_orders_to_depth(
-            ORDERS_HIGH_TO_LOW.clone(),
+        let mut actual = Vec::new();
+        sort_orders_to_depth(
+            &ORDERS_HIGH_TO_LOW,
             Ordering::LowToHigh,
             10,
-            "EXAMPLE",
+            &ExchangeId::from("EXAMPLE"),
+            &mut actual,
         );
 
         assert_eq!(expected, actual);
@@ -177,13 +388,45 @@ mod tests {
             .collect::<Vec<Level>>();
 
         // For this I've used the opposite sorting to what is expected as the input.
-        let actual = sort_orders_to_depth(
-            ORDERS_LOW_TO_HIGH.clone(),
+        let mut actual = Vec::new();
+        sort_orders_to_depth(
+            &ORDERS_LOW_TO_HIGH,
             Ordering::HighToLow,
             10,
-            "EXAMPLE",
+            &ExchangeId::from("EXAMPLE"),
+            &mut actual,
         );
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn should_append_to_levels_already_in_the_buffer() {
+        let mut levels = vec![Level::new("Binance", 20.0, 1.0)];
+        sort_orders_to_depth(
+            &ORDERS_HIGH_TO_LOW,
+            Ordering::LowToHigh,
+            3,
+            &ExchangeId::from("EXAMPLE"),
+            &mut levels,
+        );
+
+        // The existing level is kept ahead of the best three appended
+        let prices = levels.iter().map(|level| level.price).collect::<Vec<_>>();
+        assert_eq!(prices, vec![20.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn should_reject_orders_which_are_not_finite() {
+        let order: Order = serde_json::from_str(r#"["0.0712", "1.5"]"#).expect("Should parse");
+        assert_eq!(order, Order::new(0.0712, 1.5));
+
+        for unusable in [
+            r#"["NaN", "1.5"]"#,
+            r#"["0.0712", "inf"]"#,
+            r#"["-infinity", "1"]"#,
+        ] {
+            assert!(serde_json::from_str::<Order>(unusable).is_err());
+        }
+    }
 }
@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+
+use futures::future::join_all;
+use tokio::{
+    sync::watch::{channel as watch_channel, Receiver as WatchReceiver, Sender as WatchSender},
+    time::interval,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::ExchangeStatusConfig,
+    exchange::{BoxedExchange, VenueStatus},
+};
+
+/// The names of the exchanges currently in maintenance.
+pub(crate) type MaintenanceReceiver = WatchReceiver<HashSet<String>>;
+
+/// The [ExchangeStatusMonitor] periodically queries each exchange's status endpoint and publishes
+/// the set of exchanges in maintenance, so that aggregators can annotate the summaries they produce.
+pub(crate) struct ExchangeStatusMonitor {
+    exchanges: Vec<BoxedExchange>,
+    settings: ExchangeStatusConfig,
+    maintenance_sender: WatchSender<HashSet<String>>,
+}
+
+impl ExchangeStatusMonitor {
+    pub(crate) fn new(exchanges: &[BoxedExchange], settings: ExchangeStatusConfig) -> Self {
+        // Exchanges configured manually are in maintenance from the outset
+        let (maintenance_sender, _) = watch_channel(settings.maintenance.iter().cloned().collect());
+
+        Self {
+            exchanges: exchanges.to_vec(),
+            settings,
+            maintenance_sender,
+        }
+    }
+
+    /// Subscribe to the monitor, returns a [MaintenanceReceiver].
+    pub(crate) fn subscribe(&self) -> MaintenanceReceiver {
+        self.maintenance_sender.subscribe()
+    }
+
+    pub(crate) async fn start(self) {
+        let mut poll_interval = interval(self.settings.poll_interval());
+
+        loop {
+            poll_interval.tick().await;
+
+            let mut in_maintenance = self
+                .settings
+                .maintenance
+                .iter()
+                .cloned()
+                .collect::<HashSet<_>>();
+
+            // The status futures are collected first as the exchanges themselves can't be held across an await
+            let status_requests = self
+                .exchanges
+                .iter()
+                .map(|exchange| {
+                    let name = exchange.name();
+                    let status_request = exchange.fetch_status();
+                    async move { (name, status_request.await) }
+                })
+                .collect::<Vec<_>>();
+
+            for (name, status) in join_all(status_requests).await {
+                match status {
+                    Ok(VenueStatus::Maintenance) => {
+                        in_maintenance.insert(name.to_string());
+                    }
+                    Ok(_) => {}
+                    // Failing to reach the status endpoint doesn't imply the exchange is down - leave it out
+                    Err(err) => warn!("Unable to fetch status for {name}: {err}"),
+                }
+            }
+
+            self.maintenance_sender.send_if_modified(|current| {
+                if *current == in_maintenance {
+                    return false;
+                }
+                info!("Exchanges in maintenance: {in_maintenance:?}");
+                *current = in_maintenance;
+                true
+            });
+        }
+    }
+}
+
+/// Returns the contributing exchanges which are in maintenance, sorted by name.
+pub(crate) fn contributors_in_maintenance<'a>(
+    contributors: impl Iterator<Item = &'a str>,
+    in_maintenance: &HashSet<String>,
+) -> Vec<String> {
+    let mut annotation = contributors
+        .filter(|exchange| in_maintenance.contains(*exchange))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    annotation.sort_unstable();
+    annotation
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::config::ExchangeStatusConfig;
+
+    use super::{contributors_in_maintenance, ExchangeStatusMonitor};
+
+    #[test]
+    fn should_seed_maintenance_from_config() {
+        let settings = ExchangeStatusConfig {
+            maintenance: vec!["Bitstamp".to_string()],
+            ..ExchangeStatusConfig::default()
+        };
+
+        let monitor = ExchangeStatusMonitor::new(&[], settings);
+
+        let expected = HashSet::from(["Bitstamp".to_string()]);
+        assert_eq!(*monitor.subscribe().borrow(), expected);
+    }
+
+    #[test]
+    fn should_only_annotate_contributing_exchanges() {
+        let in_maintenance = HashSet::from(["Bitstamp".to_string(), "Kraken".to_string()]);
+
+        let annotation =
+            contributors_in_maintenance(["Binance", "Bitstamp"].into_iter(), &in_maintenance);
+
+        assert_eq!(annotation, vec!["Bitstamp"]);
+    }
+}
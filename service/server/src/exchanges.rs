@@ -1,2 +1,115 @@
 pub(crate) mod binance;
 pub(crate) mod bitstamp;
+#[cfg(test)]
+pub(crate) mod chaos;
+pub(crate) mod simulated;
+pub(crate) mod spec;
+
+use crate::{
+    error::ServerError,
+    exchange::{BoxedExchange, ConnectorContext},
+};
+
+use self::{binance::Binance, bitstamp::Bitstamp};
+
+/// The exchanges connected to in production.
+pub(crate) fn live_exchanges(context: ConnectorContext) -> Vec<BoxedExchange> {
+    vec![
+        Box::new(Binance::new(context.clone())),
+        Box::new(Bitstamp::new(context)),
+    ]
+}
+
+/// The exchanges to aggregate from, the `built_in` connectors followed by those added by an embedder.
+///
+/// Books are keyed by their exchange's id, and levels and config by its name, so two exchanges sharing either would
+/// silently overwrite each other's books. Names are compared regardless of case, as config keys are.
+pub(crate) fn register_exchanges(
+    built_in: Vec<BoxedExchange>,
+    custom: Vec<BoxedExchange>,
+) -> Result<Vec<BoxedExchange>, ServerError> {
+    let mut exchanges: Vec<BoxedExchange> = Vec::with_capacity(built_in.len() + custom.len());
+    for exchange in built_in.into_iter().chain(custom) {
+        let info = exchange.info();
+        if let Some(existing) = exchanges
+            .iter()
+            .map(|existing| existing.info())
+            .find(|existing| {
+                existing.id == info.id || existing.name.eq_ignore_ascii_case(info.name)
+            })
+        {
+            return Err(ServerError::DuplicateExchange {
+                name: info.name.to_string(),
+                existing: existing.name.to_string(),
+            });
+        }
+        exchanges.push(exchange);
+    }
+    Ok(exchanges)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        config::{Config, TapConfig},
+        connector_status::ConnectorStatusBus,
+        error::ServerError,
+        exchange::{BoxedExchange, ConnectorContext},
+        tap::FrameTap,
+    };
+
+    use super::{register_exchanges, simulated::SimulatedExchange};
+
+    fn simulated(name: &'static str) -> BoxedExchange {
+        let context = ConnectorContext::new(
+            &Config::default(),
+            FrameTap::new(TapConfig::default()),
+            ConnectorStatusBus::new(10),
+        );
+        Box::new(SimulatedExchange::new(
+            name,
+            Duration::from_secs(1),
+            0.0,
+            context,
+        ))
+    }
+
+    #[test]
+    fn should_register_exchanges_with_unique_names() {
+        let exchanges =
+            register_exchanges(vec![simulated("Binance")], vec![simulated("Internal Desk")])
+                .unwrap();
+
+        let names = exchanges
+            .iter()
+            .map(|exchange| exchange.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Binance", "Internal Desk"]);
+    }
+
+    #[test]
+    fn should_refuse_exchanges_sharing_a_name_regardless_of_case() {
+        let err = register_exchanges(
+            vec![simulated("Binance"), simulated("Kraken")],
+            vec![simulated("kraken")],
+        )
+        .err()
+        .unwrap();
+
+        assert!(matches!(
+            &err,
+            ServerError::DuplicateExchange { name, existing } if name == "kraken" && existing == "Kraken"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Exchange kraken clashes with Kraken, exchange names must be unique regardless of case"
+        );
+        // Built-in exchanges are held to the same rule
+        assert!(
+            register_exchanges(vec![simulated("Binance"), simulated("BINANCE")], Vec::new())
+                .is_err()
+        );
+    }
+}
@@ -1,104 +1,263 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
 
-use anyhow::Error;
-use futures_util::StreamExt;
+use futures::future::BoxFuture;
 use serde::Deserialize;
-use tokio::{
-    sync::mpsc::{channel as mpsc_channel, Receiver},
-    time::Instant,
-};
-use tokio_tungstenite::connect_async;
-use tracing::{debug, error};
+use serde_json::{json, value::RawValue};
+use tokio::{select, sync::mpsc::Receiver, time::sleep_until};
+use tracing::{debug, error, info_span};
 use url::Url;
 
-use crate::exchange::{
-    sort_orders_to_depth, BoxedExchange, BoxedOrderbook, Exchange, Order, OrderBook, Ordering,
+use crate::{
+    error::ExchangeError,
+    exchange::{
+        sort_orders_to_depth, BoxedExchange, BoxedOrderbook, ConnectorContext, DepthHint, Exchange,
+        ExchangeInfo, Order, OrderBook, Ordering, RateLimitProfile, ReceivedOrderbook, VenueStatus,
+    },
+    metrics::{metered_channel, pair_label, ChannelMeter},
+    multiplex::{ControlFrame, MuxEvent, MuxProtocol, Routed, SharedConnections},
 };
-use order_book_service_types::proto::{Level, TradedPair};
+use order_book_service_types::proto::{ExchangeId, Level, TradedPair};
 
 const BINANCE: &str = "Binance";
-const BINANCE_WSS_URL: &str = "wss://stream.binance.com:9443/ws";
+/// The combined stream endpoint, which wraps each frame with the name of its stream
+const BINANCE_WSS_URL: &str = "wss://stream.binance.com:9443/stream";
+/// Binance allows up to 1024 streams per connection
+const MAX_STREAMS_PER_SOCKET: usize = 200;
+/// Binance disconnects clients sending more than 5 messages a second
+const MIN_MESSAGE_INTERVAL: Duration = Duration::from_millis(250);
+const BINANCE_STATUS_URL: &str = "https://api.binance.com/sapi/v1/system/status";
+const BINANCE_EXCHANGE_INFO_URL: &str = "https://api.binance.com/api/v3/exchangeInfo";
+
+/// Binance's published fees, at the lowest tier, and limits.
+fn binance_info() -> ExchangeInfo {
+    ExchangeInfo {
+        maker_fee: Some(0.001),
+        taker_fee: Some(0.001),
+        rate_limit: RateLimitProfile {
+            // 300 connections every 5 minutes per IP
+            max_connections_per_minute: Some(60),
+            max_streams_per_connection: Some(MAX_STREAMS_PER_SOCKET),
+            min_message_interval: MIN_MESSAGE_INTERVAL,
+        },
+        websocket_url: Some(BINANCE_WSS_URL),
+        ..ExchangeInfo::new(BINANCE)
+    }
+}
 
 #[derive(Clone)]
 pub(crate) struct Binance {
-    root_ws_endpoint: Url,
-    depth: Depth,
+    connections: SharedConnections<CombinedStreams>,
+    context: ConnectorContext,
+    status_endpoint: Url,
+    exchange_info_endpoint: Url,
     update_frequency: UpdateSpeed,
 }
 
 impl Binance {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(context: ConnectorContext) -> Self {
+        let connections = SharedConnections::new(
+            BINANCE,
+            Url::parse(BINANCE_WSS_URL).unwrap(),
+            CombinedStreams,
+            MAX_STREAMS_PER_SOCKET,
+            context.channel_capacity,
+            context.frame_tap.clone(),
+            context.rate_limits.venue(&binance_info()),
+        );
+
         Self {
-            root_ws_endpoint: Url::parse(BINANCE_WSS_URL).unwrap(),
-            depth: Depth::Ten,
+            connections,
+            context,
+            status_endpoint: Url::parse(BINANCE_STATUS_URL).unwrap(),
+            exchange_info_endpoint: Url::parse(BINANCE_EXCHANGE_INFO_URL).unwrap(),
             update_frequency: UpdateSpeed::Fast,
         }
     }
+
+    fn stream_name(&self, symbol: &str, depth: Depth) -> String {
+        format!("{symbol}@depth{depth}@{}ms", self.update_frequency)
+    }
 }
 
 impl Exchange for Binance {
-    fn name(&self) -> &'static str {
-        BINANCE
+    fn info(&self) -> ExchangeInfo {
+        binance_info()
     }
 
     fn stream_order_book_for_pair(
         &self,
         traded_pair: &TradedPair,
-    ) -> Result<Receiver<(BoxedOrderbook, Instant)>, Error> {
-        let (order_book_tx, order_book_rx) = mpsc_channel(100);
-
-        let order_book_url = Url::parse(
-            format!(
-                "{}/{}@depth{}@{}ms",
-                self.root_ws_endpoint,
-                traded_pair.symbol_lower(),
-                self.depth,
-                self.update_frequency
-            )
-            .as_str(),
-        )?;
+        mut depth_hint: DepthHint,
+    ) -> Result<Receiver<ReceivedOrderbook>, ExchangeError> {
+        let channel_capacity = self.context.channel_capacity;
+        let (order_book_tx, order_book_rx) = metered_channel(
+            channel_capacity,
+            ChannelMeter::new(
+                "binance_orderbooks",
+                &pair_label(traded_pair),
+                channel_capacity,
+            ),
+        );
+        let pair_label = traded_pair.to_string();
+        let mut status = self
+            .context
+            .status_bus
+            .reporter(self.id(), traded_pair.clone());
+        let stale_after = self.context.stale_after;
+        let clock = self.context.clock.clone();
+
+        let binance = self.clone();
+        let symbol = self.symbol_for_pair(traded_pair);
+        let mut depth = Depth::for_hint(*depth_hint.borrow_and_update());
 
         tokio::spawn(async move {
-            match connect_async(&order_book_url).await {
-                Ok((mut ws_stream, _)) => {
-                    while let Some(Ok(msg)) = ws_stream.next().await {
-                        let received = Instant::now();
-                        match serde_json::from_str::<PartialBookDepth>(&msg.to_string()) {
+            let mut events = binance
+                .connections
+                .subscribe(binance.stream_name(&symbol, depth));
+            let mut watching_depth_hint = true;
+            let mut resyncing = false;
+            let mut last_message = clock.now();
+
+            loop {
+                select! {
+                    event = events.recv() => {
+                        let frame = match event {
+                            Some(MuxEvent::Subscribed) => {
+                                debug!("Binance streaming {symbol} at depth {depth}");
+                                if resyncing {
+                                    status.resynced(format!("Resubscribed at depth {depth}"));
+                                } else {
+                                    status.connected();
+                                }
+                                last_message = clock.now();
+                                continue;
+                            }
+                            Some(MuxEvent::Frame(frame)) => frame,
+                            Some(MuxEvent::Disconnected(reason)) => {
+                                status.disconnected(reason);
+                                return;
+                            }
+                            None => {
+                                status.disconnected("Stream ended");
+                                return;
+                            }
+                        };
+
+                        let received = clock.now();
+                        last_message = received;
+                        status.received();
+                        let receipt = info_span!("exchange_message", exchange = BINANCE, pair = %pair_label);
+                        match receipt.in_scope(|| serde_json::from_str::<PartialBookDepth>(&frame)) {
                             Ok(order_book) => {
                                 let order_book: BoxedOrderbook = Box::new(order_book);
-                                let _ = order_book_tx.send((order_book, received)).await;
-                            }
-                            Err(serde_err) => {
-                                if msg.is_ping() {
-                                    debug!("Binance sent ping");
-                                } else {
-                                    error!("Serde Error: {serde_err}");
+                                if order_book_tx.send((order_book, received, receipt)).await.is_err() {
+                                    // The aggregator has stopped, dropping the events unsubscribes the stream
+                                    return;
                                 }
                             }
+                            Err(serde_err) => error!("Serde Error: {serde_err}"),
+                        }
+                    }
+                    changed = depth_hint.changed(), if watching_depth_hint => {
+                        if changed.is_err() {
+                            // The hint can no longer change, stay at the current depth
+                            watching_depth_hint = false;
+                            continue;
                         }
+
+                        // Subscribe to the stream for the new depth, the old one is unsubscribed once its events are dropped
+                        let new_depth = Depth::for_hint(*depth_hint.borrow_and_update());
+                        if new_depth != depth {
+                            depth = new_depth;
+                            events = binance.connections.subscribe(binance.stream_name(&symbol, depth));
+                            resyncing = true;
+                        }
+                    }
+                    _ = sleep_until(last_message + stale_after), if !status.is_stale() => {
+                        status.stale(last_message.elapsed());
                     }
                 }
-                Err(ws_err) => error!("\nWebsocket Error (Binance):\n{ws_err}"),
             }
         });
 
         Ok(order_book_rx)
     }
 
+    fn fetch_status(&self) -> BoxFuture<'static, Result<VenueStatus, ExchangeError>> {
+        let status_endpoint = self.status_endpoint.clone();
+
+        Box::pin(async move {
+            let resource = "system status";
+            let system_status = reqwest::get(status_endpoint)
+                .await
+                .map_err(|source| ExchangeError::Request {
+                    exchange: ExchangeId::Binance,
+                    resource,
+                    source,
+                })?
+                .json::<SystemStatus>()
+                .await
+                .map_err(|source| ExchangeError::Parse {
+                    exchange: ExchangeId::Binance,
+                    resource,
+                    source,
+                })?;
+
+            Ok(system_status.into())
+        })
+    }
+
+    fn supported_pairs(&self) -> BoxFuture<'static, Result<Vec<TradedPair>, ExchangeError>> {
+        let exchange_info_endpoint = self.exchange_info_endpoint.clone();
+
+        Box::pin(async move {
+            let resource = "exchange info";
+            let exchange_info = reqwest::get(exchange_info_endpoint)
+                .await
+                .map_err(|source| ExchangeError::Request {
+                    exchange: ExchangeId::Binance,
+                    resource,
+                    source,
+                })?
+                .json::<ExchangeInfoResponse>()
+                .await
+                .map_err(|source| ExchangeError::Parse {
+                    exchange: ExchangeId::Binance,
+                    resource,
+                    source,
+                })?;
+
+            Ok(exchange_info.trading_pairs())
+        })
+    }
+
     fn clone_dyn(&self) -> BoxedExchange {
         Box::new(self.clone())
     }
 }
 
 /// Refers to how many orders should be returned in the data set.
-#[derive(Clone)]
-#[allow(unused)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Depth {
     Five,
     Ten,
     Twenty,
 }
 
+impl Depth {
+    /// The smallest depth covering the hint, Binance's partial book streams offer at most 20 levels.
+    fn for_hint(depth_hint: usize) -> Self {
+        match depth_hint {
+            0..=5 => Depth::Five,
+            6..=10 => Depth::Ten,
+            _ => Depth::Twenty,
+        }
+    }
+}
+
 impl Display for Depth {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -129,6 +288,106 @@ impl Display for UpdateSpeed {
     }
 }
 
+/// Subscribes to streams with `SUBSCRIBE` requests on the combined stream endpoint,
+/// whose frames are wrapped as `{"stream": "<name>", "data": <payload>}`.
+struct CombinedStreams;
+
+/// A frame from the combined stream endpoint, the data is left unparsed for the stream's subscriber.
+#[derive(Deserialize)]
+struct CombinedStreamFrame<'a> {
+    stream: String,
+    #[serde(borrow)]
+    data: &'a RawValue,
+}
+
+impl MuxProtocol for CombinedStreams {
+    type Payload = String;
+
+    fn subscribe_message(&self, stream: &str) -> String {
+        json!({ "method": "SUBSCRIBE", "params": [stream], "id": 1 }).to_string()
+    }
+
+    fn unsubscribe_message(&self, stream: &str) -> String {
+        json!({ "method": "UNSUBSCRIBE", "params": [stream], "id": 1 }).to_string()
+    }
+
+    fn route(&self, frame: &str) -> Routed<String> {
+        if let Ok(stream_frame) = serde_json::from_str::<CombinedStreamFrame>(frame) {
+            return Routed::Stream(stream_frame.stream, stream_frame.data.get().to_string());
+        }
+        // Responses to requests, e.g. `{"result": null, "id": 1}`, aren't wrapped
+        match serde_json::from_str::<ErrorResponse>(frame) {
+            Ok(response) => Routed::Control(ControlFrame::Error(format!(
+                "{} (code {})",
+                response.error.msg, response.error.code
+            ))),
+            Err(_) => Routed::Ignored,
+        }
+    }
+
+    fn min_message_interval(&self) -> Duration {
+        MIN_MESSAGE_INTERVAL
+    }
+}
+
+/// The response to a rejected request, e.g. `{"error": {"code": 2, "msg": "Invalid request"}, "id": 1}`.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    code: i64,
+    msg: String,
+}
+
+/// Response from the system status endpoint, `status` is 0 when normal and 1 during maintenance.
+#[derive(Debug, Deserialize)]
+struct SystemStatus {
+    status: u8,
+    #[allow(unused)]
+    msg: String,
+}
+
+impl From<SystemStatus> for VenueStatus {
+    fn from(value: SystemStatus) -> Self {
+        match value.status {
+            0 => VenueStatus::Operational,
+            1 => VenueStatus::Maintenance,
+            _ => VenueStatus::Unknown,
+        }
+    }
+}
+
+/// Response from the exchange info endpoint, only the fields needed to list pairs are kept.
+#[derive(Debug, Deserialize)]
+struct ExchangeInfoResponse {
+    symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolInfo {
+    status: String,
+    base_asset: String,
+    quote_asset: String,
+}
+
+impl ExchangeInfoResponse {
+    /// Pairs which are currently trading, halted and delisted symbols are left out.
+    fn trading_pairs(self) -> Vec<TradedPair> {
+        self.symbols
+            .into_iter()
+            .filter(|symbol| symbol.status == "TRADING")
+            .map(|symbol| TradedPair {
+                first: symbol.base_asset,
+                second: symbol.quote_asset,
+            })
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 struct PartialBookDepth {
     #[serde(rename = "lastUpdateId")]
@@ -139,19 +398,101 @@ struct PartialBookDepth {
 }
 
 impl OrderBook for PartialBookDepth {
-    fn source(&self) -> &'static str {
-        BINANCE
+    fn source(&self) -> ExchangeId {
+        ExchangeId::Binance
     }
 
-    fn spread(&self) -> f64 {
-        self.best_asks(1)[0].price - self.best_bids(1)[0].price
+    fn best_asks(&self, depth: usize, levels: &mut Vec<Level>) {
+        sort_orders_to_depth(
+            &self.asks,
+            Ordering::LowToHigh,
+            depth,
+            &self.source(),
+            levels,
+        )
     }
 
-    fn best_asks(&self, depth: usize) -> Vec<Level> {
-        sort_orders_to_depth(self.asks.clone(), Ordering::LowToHigh, depth, self.source())
+    fn best_bids(&self, depth: usize, levels: &mut Vec<Level>) {
+        sort_orders_to_depth(
+            &self.bids,
+            Ordering::HighToLow,
+            depth,
+            &self.source(),
+            levels,
+        )
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::TradedPair;
+
+    use crate::{
+        exchange::VenueStatus,
+        multiplex::{ControlFrame, MuxProtocol, Routed},
+    };
+
+    use super::{CombinedStreams, Depth, ExchangeInfoResponse, SystemStatus};
+
+    #[test]
+    fn should_route_combined_stream_frames() {
+        assert_eq!(
+            CombinedStreams.route(
+                r#"{"stream":"ethbtc@depth5@100ms","data":{"lastUpdateId":1,"bids":[],"asks":[]}}"#,
+            ),
+            Routed::Stream(
+                "ethbtc@depth5@100ms".to_string(),
+                r#"{"lastUpdateId":1,"bids":[],"asks":[]}"#.to_string()
+            )
+        );
+        assert_eq!(
+            CombinedStreams.route(r#"{"result":null,"id":1}"#),
+            Routed::Ignored
+        );
+        assert_eq!(
+            CombinedStreams
+                .route(r#"{"error":{"code":2,"msg":"Invalid request: unknown variant"},"id":1}"#),
+            Routed::Control(ControlFrame::Error(
+                "Invalid request: unknown variant (code 2)".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn should_choose_smallest_depth_covering_hint() {
+        assert_eq!(Depth::for_hint(1), Depth::Five);
+        assert_eq!(Depth::for_hint(10), Depth::Ten);
+        assert_eq!(Depth::for_hint(11), Depth::Twenty);
+        assert_eq!(Depth::for_hint(100), Depth::Twenty);
+    }
+
+    #[test]
+    fn should_parse_system_status() {
+        let normal: SystemStatus =
+            serde_json::from_str(r#"{ "status": 0, "msg": "normal" }"#).unwrap();
+        let maintenance: SystemStatus =
+            serde_json::from_str(r#"{ "status": 1, "msg": "system maintenance" }"#).unwrap();
+
+        assert_eq!(VenueStatus::from(normal), VenueStatus::Operational);
+        assert_eq!(VenueStatus::from(maintenance), VenueStatus::Maintenance);
+    }
+
+    #[test]
+    fn should_only_list_trading_pairs() {
+        let exchange_info: ExchangeInfoResponse = serde_json::from_str(
+            r#"{
+                "timezone": "UTC",
+                "symbols": [
+                    { "symbol": "ETHBTC", "status": "TRADING", "baseAsset": "ETH", "quoteAsset": "BTC" },
+                    { "symbol": "LUNABTC", "status": "BREAK", "baseAsset": "LUNA", "quoteAsset": "BTC" }
+                ]
+            }"#,
+        )
+        .unwrap();
 
-    fn best_bids(&self, depth: usize) -> Vec<Level> {
-        sort_orders_to_depth(self.bids.clone(), Ordering::HighToLow, depth, self.source())
+        assert_eq!(
+            exchange_info.trading_pairs(),
+            vec![TradedPair::new("ETH", "BTC")]
+        );
     }
 }
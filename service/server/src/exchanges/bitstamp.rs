@@ -1,189 +1,90 @@
-use std::fmt::{Display, Formatter};
-
-use anyhow::Error;
-use futures_util::{SinkExt, StreamExt};
-use serde::{Deserialize, Serialize};
-use tokio::{
-    sync::mpsc::{channel as mpsc_channel, Receiver},
-    time::Instant,
-};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{debug, error};
-use url::Url;
-
-use crate::exchange::{
-    sort_orders_to_depth, BoxedExchange, BoxedOrderbook, Exchange, Order, OrderBook, Ordering,
-};
-use order_book_service_types::proto::{Level, TradedPair};
-
-const BITSTAMP: &str = "Bitstamp";
-const BITSTAMP_WSS_URL: &str = "wss://ws.bitstamp.net";
-const BTS_SUBSCRIBE: &str = "bts:subscribe";
-const ORDERBOOK_CHANNEL: &str = "order_book_";
-
-#[derive(Clone)]
-pub(crate) struct Bitstamp {
-    root_ws_endpoint: Url,
-}
+use serde::Deserialize;
+use serde_json::Value;
 
-impl Bitstamp {
-    pub(crate) fn new() -> Self {
-        Self {
-            root_ws_endpoint: Url::parse(BITSTAMP_WSS_URL).unwrap(),
-        }
-    }
-}
+use order_book_service_types::proto::TradedPair;
 
-impl Exchange for Bitstamp {
-    fn name(&self) -> &'static str {
-        BITSTAMP
-    }
+use crate::multiplex::ControlFrame;
 
-    fn stream_order_book_for_pair(
-        &self,
-        traded_pair: &TradedPair,
-    ) -> Result<Receiver<(BoxedOrderbook, Instant)>, Error> {
-        if !VALID_PAIRS.contains(&traded_pair.symbol_lower().as_str()) {
-            return Err(Error::msg(
-                "Requested traded pair is not supported by Bitstamp",
-            ));
-        }
+use super::spec::{ExchangeSpec, SpecExchange};
 
-        let (order_book_tx, order_book_rx) = mpsc_channel(100);
-
-        let ws_url = self.root_ws_endpoint.to_string();
-        let symbol = traded_pair.symbol_lower();
-
-        tokio::spawn(async move {
-            match connect_async(ws_url).await {
-                Ok((mut ws_stream, _)) => {
-                    let channel = Channel::new(format!("{ORDERBOOK_CHANNEL}{symbol}"));
-                    let channel_sub_request = ChannelSubscriptionRequest::new(channel.clone());
-
-                    ws_stream
-                        .send(Message::Text(
-                            serde_json::to_string(&channel_sub_request).unwrap(),
-                        ))
-                        .await
-                        .unwrap();
-
-                    // Handle initial response to subscription request
-                    if let Some(subscription_response) = ws_stream.next().await {
-                        match subscription_response {
-                            Ok(response) => {
-                                debug!("BITSTAMP ::Initial response: {response}");
-                            }
-                            Err(error) => error!("WS Error: {error}"),
-                        }
-                    }
-
-                    // Handle ongoing stream
-                    while let Some(Ok(msg)) = ws_stream.next().await {
-                        let received = Instant::now();
-                        match serde_json::from_str::<LiveOrderBookResponse>(&msg.to_string()) {
-                            Ok(order_book) => {
-                                let order_book: BoxedOrderbook = Box::new(order_book);
-                                let _ = order_book_tx.send((order_book, received)).await;
-                            }
-                            Err(serde_err) => {
-                                if msg.is_ping() {
-                                    debug!("Bitstamp sent ping");
-                                } else {
-                                    error!("\nSerde Error:\n{serde_err}")
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(ws_err) => error!("\nWebsocket Error (Bitstamp):\n{ws_err}"),
-            }
-        });
-
-        Ok(order_book_rx)
-    }
+/// Bitstamp's full orderbook channels, see https://www.bitstamp.net/websocket/v2/
+pub(crate) type Bitstamp = SpecExchange<BitstampSpec>;
 
-    fn clone_dyn(&self) -> BoxedExchange {
-        Box::new(self.clone())
-    }
-}
+pub(crate) struct BitstampSpec;
 
-#[derive(Clone, Debug, Serialize)]
-struct Channel {
-    channel: String,
-}
+impl ExchangeSpec for BitstampSpec {
+    const NAME: &'static str = "Bitstamp";
+    const WEBSOCKET_URL: &'static str = "wss://ws.bitstamp.net";
+    const PAIRS_URL: &'static str = "https://www.bitstamp.net/api/v2/trading-pairs-info/";
+
+    const SUBSCRIBE_TEMPLATE: &'static str =
+        r#"{"event":"bts:subscribe","data":{"channel":"{stream}"}}"#;
+    const UNSUBSCRIBE_TEMPLATE: &'static str =
+        r#"{"event":"bts:unsubscribe","data":{"channel":"{stream}"}}"#;
+    const STREAM_TEMPLATE: &'static str = "order_book_{symbol}";
+
+    const STREAM_POINTER: &'static str = "/channel";
+    const BIDS_POINTER: &'static str = "/data/bids";
+    const ASKS_POINTER: &'static str = "/data/asks";
+    const TIMESTAMP_MICROS_POINTER: Option<&'static str> = Some("/data/microtimestamp");
 
-impl Display for Channel {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.channel)
+    // At the lowest fee tier
+    const MAKER_FEE: Option<f64> = Some(0.003);
+    const TAKER_FEE: Option<f64> = Some(0.004);
+
+    type PairsResponse = Vec<PairInfo>;
+
+    fn listed_pairs(response: Self::PairsResponse) -> Vec<TradedPair> {
+        response
+            .into_iter()
+            .filter_map(PairInfo::into_streamable_pair)
+            .collect()
     }
-}
 
-impl Channel {
-    fn new(channel: String) -> Self {
-        Self { channel }
+    fn supports(traded_pair: &TradedPair) -> bool {
+        VALID_PAIRS.contains(&traded_pair.symbol_lower().as_str())
     }
-}
 
-#[derive(Debug, Serialize)]
-struct ChannelSubscriptionRequest {
-    event: String,
-    data: Channel,
-}
+    fn is_book_frame(frame: &Value) -> bool {
+        // Other events, e.g. `bts:subscription_succeeded`, acknowledge requests
+        frame["event"] == "data"
+    }
 
-impl ChannelSubscriptionRequest {
-    fn new(channel: Channel) -> Self {
-        Self {
-            event: BTS_SUBSCRIBE.to_string(),
-            data: channel,
+    fn control(frame: &Value) -> Option<ControlFrame> {
+        match frame["event"].as_str()? {
+            // Sent ahead of maintenance, the socket is closed shortly after
+            "bts:request_reconnect" => Some(ControlFrame::Reconnect),
+            "bts:error" => Some(ControlFrame::Error(
+                frame["data"]["message"]
+                    .as_str()
+                    .unwrap_or("No message")
+                    .to_string(),
+            )),
+            _ => None,
         }
     }
 }
 
+/// An entry from the trading pairs info endpoint, e.g. `{ "name": "BTC/USD", "url_symbol": "btcusd", "trading": "Enabled" }`.
 #[derive(Debug, Deserialize)]
-struct LiveOrderBookResponse {
-    data: LiveOrderBookData,
-}
-
-#[derive(Debug, Deserialize)]
-#[allow(unused)]
-struct LiveOrderBookData {
-    #[serde(skip)]
-    timestamp: u64,
-    #[serde(skip)]
-    microtimestamp: u64,
-    bids: Vec<Order>,
-    asks: Vec<Order>,
-    #[serde(skip)]
-    channel: String,
-    #[serde(skip)]
-    event: String,
+pub(crate) struct PairInfo {
+    name: String,
+    url_symbol: String,
+    trading: String,
 }
 
-impl OrderBook for LiveOrderBookResponse {
-    fn source(&self) -> &'static str {
-        BITSTAMP
-    }
-
-    fn spread(&self) -> f64 {
-        self.best_asks(1)[0].price - self.best_bids(1)[0].price
-    }
-
-    fn best_asks(&self, depth: usize) -> Vec<Level> {
-        sort_orders_to_depth(
-            self.data.asks.clone(),
-            Ordering::LowToHigh,
-            depth,
-            self.source(),
-        )
-    }
+impl PairInfo {
+    /// The pair, if it is enabled for trading and its orderbook can be streamed.
+    fn into_streamable_pair(self) -> Option<TradedPair> {
+        if self.trading != "Enabled" || !VALID_PAIRS.contains(&self.url_symbol.as_str()) {
+            return None;
+        }
 
-    fn best_bids(&self, depth: usize) -> Vec<Level> {
-        sort_orders_to_depth(
-            self.data.bids.clone(),
-            Ordering::HighToLow,
-            depth,
-            self.source(),
-        )
+        let (first, second) = self.name.split_once('/')?;
+        Some(TradedPair {
+            first: first.to_string(),
+            second: second.to_string(),
+        })
     }
 }
 
@@ -213,3 +114,70 @@ const VALID_PAIRS: [&str; 175] = [
     "soleur", "apeusd", "apeeur", "mplusd", "mpleur", "dotusd", "doteur", "nearusd", "neareur",
     "dogeusd", "dogeeur",
 ];
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use order_book_service_types::proto::{ExchangeId, Level};
+
+    use crate::{
+        exchange::OrderBook,
+        exchanges::spec::{SpecOrderBook, SpecProtocol},
+        multiplex::{ControlFrame, MuxProtocol, Routed},
+    };
+
+    use super::BitstampSpec;
+
+    #[test]
+    fn should_route_data_frames_by_channel() {
+        let protocol = SpecProtocol::<BitstampSpec>::new();
+        let frame =
+            r#"{"data":{"bids":[],"asks":[]},"channel":"order_book_ethbtc","event":"data"}"#;
+        assert_eq!(
+            protocol.route(frame),
+            Routed::Stream(
+                "order_book_ethbtc".to_string(),
+                Arc::new(serde_json::from_str(frame).unwrap())
+            )
+        );
+        assert_eq!(
+            protocol.route(
+                r#"{"event":"bts:subscription_succeeded","channel":"order_book_ethbtc","data":{}}"#
+            ),
+            Routed::Ignored
+        );
+        assert_eq!(
+            protocol.route(r#"{"event":"bts:request_reconnect","channel":"","data":""}"#),
+            Routed::Control(ControlFrame::Reconnect)
+        );
+        assert_eq!(
+            protocol.route(
+                r#"{"event":"bts:error","channel":"","data":{"code":null,"message":"Bad subscription string."}}"#
+            ),
+            Routed::Control(ControlFrame::Error("Bad subscription string.".to_string()))
+        );
+        assert_eq!(
+            protocol.subscribe_message("order_book_ethbtc"),
+            r#"{"event":"bts:subscribe","data":{"channel":"order_book_ethbtc"}}"#
+        );
+    }
+
+    #[test]
+    fn should_parse_live_order_books() {
+        let frame = r#"{"data":{"timestamp":"1700000000","microtimestamp":"1700000000123456","bids":[["0.0695","2.5"]],"asks":[["0.0705","1.25"]]},"channel":"order_book_ethbtc","event":"data"}"#;
+        let frame = serde_json::from_str(frame).unwrap();
+        let book = SpecOrderBook::parse::<BitstampSpec>(ExchangeId::Bitstamp, &frame).unwrap();
+
+        let mut asks = Vec::new();
+        book.best_asks(10, &mut asks);
+        assert_eq!(asks, vec![Level::new("Bitstamp", 0.0705, 1.25)]);
+        assert_eq!(
+            book.exchange_timestamp(),
+            Some(UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456))
+        );
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use futures::future::BoxFuture;
+use tokio::{
+    sync::mpsc::{channel, Receiver},
+    time::sleep,
+};
+
+use order_book_service_types::proto::{ExchangeId, Level, TradedPair};
+
+use crate::{
+    error::ExchangeError,
+    exchange::{
+        BoxedExchange, BoxedOrderbook, DepthHint, Exchange, ExchangeInfo, OrderBook,
+        ReceivedOrderbook,
+    },
+};
+
+/// A fault [ChaosExchange] can inject into a stream of orderbooks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Fault {
+    /// End the stream, as if the socket dropped
+    Disconnect,
+    /// End the stream and stop offering its pair, as if the exchange delisted it
+    Delist,
+    /// Hold the book back for a while before passing it on
+    Delay(Duration),
+    /// Pass the book on twice
+    Duplicate,
+    /// Pass the book on after the one following it
+    Reorder,
+}
+
+/// Which faults to inject and how often, each is rolled for every book from a generator seeded by `seed`,
+/// so a plan injects the same faults into the same stream every run.
+#[derive(Clone, Debug)]
+pub(crate) struct ChaosPlan {
+    seed: u64,
+    faults: Vec<(Fault, f64)>,
+}
+
+impl ChaosPlan {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            faults: Vec::new(),
+        }
+    }
+
+    /// Inject `fault` into each book with the given `probability`.
+    pub(crate) fn with(mut self, fault: Fault, probability: f64) -> Self {
+        self.faults.push((fault, probability));
+        self
+    }
+}
+
+/// Wraps an exchange, injecting faults into its orderbook streams according to a [ChaosPlan].
+#[derive(Clone)]
+pub(crate) struct ChaosExchange {
+    inner: BoxedExchange,
+    plan: ChaosPlan,
+    /// Pairs removed from the exchange's listing by [Fault::Delist]
+    delisted: Arc<Mutex<Vec<TradedPair>>>,
+}
+
+impl ChaosExchange {
+    pub(crate) fn new(inner: BoxedExchange, plan: ChaosPlan) -> Self {
+        Self {
+            inner,
+            plan,
+            delisted: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl Exchange for ChaosExchange {
+    fn info(&self) -> ExchangeInfo {
+        self.inner.info()
+    }
+
+    fn stream_order_book_for_pair(
+        &self,
+        traded_pair: &TradedPair,
+        depth_hint: DepthHint,
+    ) -> Result<Receiver<ReceivedOrderbook>, ExchangeError> {
+        let mut orderbooks = self
+            .inner
+            .stream_order_book_for_pair(traded_pair, depth_hint)?;
+        let (order_book_tx, order_book_rx) = channel(1);
+        let plan = self.plan.clone();
+        let delisted = self.delisted.clone();
+        let traded_pair = traded_pair.clone();
+
+        tokio::spawn(async move {
+            let mut rng = XorShift(plan.seed.max(1));
+            let mut held_back = None;
+
+            while let Some((orderbook, received, receipt)) = orderbooks.recv().await {
+                let snapshot = Snapshot::of(orderbook.as_ref());
+                let mut outgoing = vec![(snapshot.clone(), received, receipt.clone())];
+
+                for (fault, probability) in plan.faults.iter() {
+                    if rng.next_f64() >= *probability {
+                        continue;
+                    }
+                    match fault {
+                        Fault::Disconnect => return,
+                        Fault::Delist => {
+                            delisted.lock().expect("Should lock").push(traded_pair);
+                            return;
+                        }
+                        Fault::Delay(delay) => sleep(*delay).await,
+                        Fault::Duplicate => {
+                            outgoing.push((snapshot.clone(), received, receipt.clone()))
+                        }
+                        Fault::Reorder if held_back.is_none() => {
+                            held_back = outgoing.pop();
+                        }
+                        Fault::Reorder => {}
+                    }
+                }
+                // A book held back is sent once a later book has gone ahead of it
+                if !outgoing.is_empty() {
+                    outgoing.extend(held_back.take());
+                }
+
+                for (snapshot, received, receipt) in outgoing {
+                    let orderbook: BoxedOrderbook = Box::new(snapshot);
+                    if order_book_tx
+                        .send((orderbook, received, receipt))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(order_book_rx)
+    }
+
+    fn supported_pairs(&self) -> BoxFuture<'static, Result<Vec<TradedPair>, ExchangeError>> {
+        let request = self.inner.supported_pairs();
+        let delisted = self.delisted.clone();
+        Box::pin(async move {
+            let mut pairs = request.await?;
+            pairs.retain(|pair| !delisted.lock().expect("Should lock").contains(pair));
+            Ok(pairs)
+        })
+    }
+
+    fn symbol_for_pair(&self, traded_pair: &TradedPair) -> String {
+        self.inner.symbol_for_pair(traded_pair)
+    }
+
+    fn clone_dyn(&self) -> BoxedExchange {
+        Box::new(self.clone())
+    }
+}
+
+/// A copy of a book's levels, so that the book can be passed on more than once.
+#[derive(Clone)]
+struct Snapshot {
+    source: ExchangeId,
+    asks: Vec<Level>,
+    bids: Vec<Level>,
+    exchange_timestamp: Option<SystemTime>,
+}
+
+impl Snapshot {
+    fn of(orderbook: &(dyn OrderBook + Send)) -> Self {
+        let mut asks = Vec::new();
+        let mut bids = Vec::new();
+        orderbook.best_asks(usize::MAX, &mut asks);
+        orderbook.best_bids(usize::MAX, &mut bids);
+        Self {
+            source: orderbook.source(),
+            asks,
+            bids,
+            exchange_timestamp: orderbook.exchange_timestamp(),
+        }
+    }
+}
+
+impl OrderBook for Snapshot {
+    fn source(&self) -> ExchangeId {
+        self.source.clone()
+    }
+
+    fn best_asks(&self, depth: usize, levels: &mut Vec<Level>) {
+        levels.extend(self.asks.iter().take(depth).cloned());
+    }
+
+    fn best_bids(&self, depth: usize, levels: &mut Vec<Level>) {
+        levels.extend(self.bids.iter().take(depth).cloned());
+    }
+
+    fn exchange_timestamp(&self) -> Option<SystemTime> {
+        self.exchange_timestamp
+    }
+}
+
+/// A small seedable generator, the faults only need to be repeatable rather than unpredictable.
+struct XorShift(u64);
+
+impl XorShift {
+    /// The next value in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::Duration};
+
+    use tokio::{
+        sync::{broadcast::error::RecvError, watch::channel as watch_channel},
+        time::{timeout, Instant},
+    };
+
+    use tonic::{Code, Status};
+
+    use order_book_service_types::proto::{service_event::Event, CloseReason, Summary, TradedPair};
+
+    use crate::{
+        aggregator::{AggregatorHandle, OrderbookAggregator, SUMMARY_DEPTH},
+        clock::SystemClock,
+        config::{AggregatorConfig, Config, TapConfig},
+        connector_status::ConnectorStatusBus,
+        error::AggregatorError,
+        events::EventBus,
+        exchange::{BoxedExchange, ConnectorContext},
+        exchanges::simulated::simulated_exchanges,
+        latency::ExchangeLatencies,
+        tap::FrameTap,
+    };
+
+    use super::{ChaosExchange, ChaosPlan, Fault};
+
+    /// Aggregate ETH-BTC from the simulated exchanges, the first with `plan` applied.
+    fn aggregate_with_chaos(plan: ChaosPlan) -> AggregatorHandle {
+        aggregate_with_chaos_using(plan, Config::default(), EventBus::new(10))
+    }
+
+    fn aggregate_with_chaos_using(
+        plan: ChaosPlan,
+        config: Config,
+        event_bus: EventBus,
+    ) -> AggregatorHandle {
+        let context = ConnectorContext::new(
+            &config,
+            FrameTap::new(TapConfig::default()),
+            ConnectorStatusBus::new(10),
+        );
+        let mut exchanges = simulated_exchanges(context);
+        let chaotic: BoxedExchange = Box::new(ChaosExchange::new(exchanges.remove(0), plan));
+        exchanges.insert(0, chaotic);
+
+        let aggregator = OrderbookAggregator::new(
+            &exchanges,
+            TradedPair::new("ETH", "BTC"),
+            watch_channel(HashSet::new()).1,
+            event_bus,
+            ExchangeLatencies::default(),
+            SystemClock::shared(),
+            &config,
+        );
+        let handle = aggregator.subscribe();
+        tokio::spawn(aggregator.start());
+        handle
+    }
+
+    /// Receive summaries for `duration` of virtual time.
+    async fn summaries_for(handle: &mut AggregatorHandle, duration: Duration) -> Vec<Summary> {
+        let deadline = Instant::now() + duration;
+        let mut summaries = Vec::new();
+        while let Ok(Ok(Ok(tick))) =
+            timeout(deadline - Instant::now(), handle.summary_receiver.recv()).await
+        {
+            summaries.push(tick.summary.clone());
+        }
+        summaries
+    }
+
+    fn is_well_formed(summary: &Summary) -> bool {
+        summary
+            .asks
+            .windows(2)
+            .all(|asks| asks[0].price <= asks[1].price)
+            && summary
+                .bids
+                .windows(2)
+                .all(|bids| bids[0].price >= bids[1].price)
+            && summary.spread == summary.asks[0].price - summary.bids[0].price
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_stop_with_an_error_when_an_exchange_disconnects() {
+        let mut handle = aggregate_with_chaos(ChaosPlan::new(7).with(Fault::Disconnect, 0.1));
+
+        let outcome = loop {
+            match handle.summary_receiver.recv().await {
+                Ok(Ok(_)) | Err(RecvError::Lagged(_)) => continue,
+                Ok(Err(err)) => break err,
+                Err(RecvError::Closed) => panic!("The aggregator should report why it stopped"),
+            }
+        };
+
+        assert!(matches!(*outcome, AggregatorError::ExchangeDisconnected(_)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_flag_summaries_degraded_when_an_exchange_disconnects_in_degraded_mode() {
+        let config = Config {
+            aggregator: AggregatorConfig {
+                degraded_mode: true,
+                ..AggregatorConfig::default()
+            },
+            ..Config::default()
+        };
+        let mut handle = aggregate_with_chaos_using(
+            // Disconnects after its first ten books, so both exchanges are merged to begin with
+            ChaosPlan::new(0x9E37_79B9_7F4A_7C15).with(Fault::Disconnect, 0.1),
+            config,
+            EventBus::new(10),
+        );
+
+        let summaries = summaries_for(&mut handle, Duration::from_secs(60)).await;
+        let (healthy, degraded): (Vec<_>, Vec<_>) =
+            summaries.iter().partition(|summary| !summary.degraded);
+        assert!(!healthy.is_empty());
+        assert!(healthy
+            .iter()
+            .all(|summary| summary.missing_exchanges.is_empty()));
+        // The remaining exchange carries on alone rather than the aggregator stopping
+        assert!(!degraded.is_empty());
+        for summary in degraded {
+            assert_eq!(summary.missing_exchanges, vec!["SimulatedA"]);
+            assert!(summary
+                .asks
+                .iter()
+                .chain(&summary.bids)
+                .all(|level| level.exchange == "SimulatedB"));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_fail_precondition_when_an_exchange_delists_the_pair() {
+        let mut handle = aggregate_with_chaos(ChaosPlan::new(7).with(Fault::Delist, 0.1));
+
+        let outcome = loop {
+            match handle.summary_receiver.recv().await {
+                Ok(Ok(_)) | Err(RecvError::Lagged(_)) => continue,
+                Ok(Err(err)) => break err,
+                Err(RecvError::Closed) => panic!("The aggregator should report why it stopped"),
+            }
+        };
+
+        let AggregatorError::PairRetired { delisted, .. } = outcome.as_ref() else {
+            panic!("Expected the pair to be retired, got {outcome:?}");
+        };
+        assert_eq!(delisted.len(), 1);
+        let status = Status::from(outcome.as_ref());
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(CloseReason::of(&status), CloseReason::PairRetired);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_keep_aggregating_through_delays() {
+        let mut handle = aggregate_with_chaos(
+            ChaosPlan::new(11).with(Fault::Delay(Duration::from_millis(400)), 0.3),
+        );
+
+        let summaries = summaries_for(&mut handle, Duration::from_secs(10)).await;
+
+        assert!(summaries.len() > 20);
+        assert!(summaries.iter().all(is_well_formed));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_suppress_summaries_from_duplicated_books() {
+        let mut handle = aggregate_with_chaos(ChaosPlan::new(13).with(Fault::Duplicate, 0.5));
+
+        let summaries = summaries_for(&mut handle, Duration::from_secs(10)).await;
+
+        assert!(summaries.len() > 20);
+        assert!(summaries.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_reconnect_and_alert_when_merges_stall() {
+        let config = Config {
+            aggregator: AggregatorConfig {
+                stall_timeout_secs: Some(5),
+                ..AggregatorConfig::default()
+            },
+            ..Config::default()
+        };
+        let event_bus = EventBus::new(10);
+        let mut events = event_bus.subscribe();
+        // The first exchange's stream stays open but holds every book back, so nothing can be merged
+        let handle = aggregate_with_chaos_using(
+            ChaosPlan::new(19).with(Fault::Delay(Duration::from_secs(600)), 1.0),
+            config,
+            event_bus,
+        );
+        let _subscriber = handle.depth_requests.request(SUMMARY_DEPTH);
+
+        let start = Instant::now();
+        let event = events.recv().await.expect("Should raise an event");
+        let Some(Event::AggregatorStall(stall)) = event.event else {
+            panic!("Expected a stall alert, got {event:?}");
+        };
+        assert!(stall.reconnected);
+        assert_eq!(stall.traded_pair, Some(TradedPair::new("ETH", "BTC")));
+        assert!(stall.quiet_for_millis >= 5_000);
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_produce_well_formed_summaries_from_reordered_books() {
+        let mut handle = aggregate_with_chaos(ChaosPlan::new(17).with(Fault::Reorder, 0.3));
+
+        let summaries = summaries_for(&mut handle, Duration::from_secs(10)).await;
+
+        assert!(summaries.len() > 20);
+        assert!(summaries.iter().all(is_well_formed));
+    }
+}
@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use tokio::{
+    sync::mpsc::Receiver,
+    time::{interval, MissedTickBehavior},
+};
+use tracing::info_span;
+
+use order_book_service_types::proto::{ExchangeId, Level, TradedPair};
+
+use crate::{
+    error::ExchangeError,
+    exchange::{
+        sort_orders_to_depth, BoxedExchange, BoxedOrderbook, ConnectorContext, DepthHint, Exchange,
+        ExchangeInfo, Order, OrderBook, Ordering, ReceivedOrderbook,
+    },
+    metrics::{metered_channel, pair_label, ChannelMeter},
+};
+
+/// Pairs offered by simulated exchanges along with the mid price books are generated around, and the levels generated
+/// on each side of a book. DOGE-BTC is thin, its merged books are shallower than the default depth.
+const SIMULATED_MARKETS: [(&str, &str, f64, usize); 4] = [
+    ("ETH", "BTC", 0.07, 20),
+    ("LTC", "BTC", 0.004, 20),
+    ("BTC", "USD", 20_000.0, 20),
+    ("DOGE", "BTC", 0.000_003, 3),
+];
+
+/// Two simulated exchanges updating at different rates, for running the service without any network access.
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) fn simulated_exchanges(context: ConnectorContext) -> Vec<BoxedExchange> {
+    vec![
+        Box::new(SimulatedExchange::new(
+            "SimulatedA",
+            Duration::from_millis(100),
+            0.0,
+            context.clone(),
+        )),
+        Box::new(SimulatedExchange::new(
+            "SimulatedB",
+            Duration::from_millis(150),
+            0.000_2,
+            context,
+        )),
+    ]
+}
+
+/// Simulated exchanges for the demo, named so they can't be mistaken for real venues and updating slowly enough
+/// that the demo can't stand in for a real feed.
+pub(crate) fn demo_exchanges(context: ConnectorContext) -> Vec<BoxedExchange> {
+    vec![
+        Box::new(SimulatedExchange::new(
+            "DemoVenueA",
+            Duration::from_secs(1),
+            0.0,
+            context.clone(),
+        )),
+        Box::new(SimulatedExchange::new(
+            "DemoVenueB",
+            Duration::from_millis(1500),
+            0.000_2,
+            context,
+        )),
+    ]
+}
+
+/// An exchange which generates orderbooks locally on a fixed interval rather than connecting anywhere.
+///
+/// The books follow a deterministic pattern and are paced by [tokio::time], so when time is paused
+/// tests run through them without real sleeps.
+#[derive(Clone)]
+pub(crate) struct SimulatedExchange {
+    name: &'static str,
+    update_interval: Duration,
+    /// Fraction the exchange's prices are raised by, so that books from different exchanges interleave
+    price_offset: f64,
+    context: ConnectorContext,
+}
+
+impl SimulatedExchange {
+    pub(crate) fn new(
+        name: &'static str,
+        update_interval: Duration,
+        price_offset: f64,
+        context: ConnectorContext,
+    ) -> Self {
+        Self {
+            name,
+            update_interval,
+            price_offset,
+            context,
+        }
+    }
+}
+
+impl Exchange for SimulatedExchange {
+    fn info(&self) -> ExchangeInfo {
+        ExchangeInfo::new(self.name)
+    }
+
+    fn stream_order_book_for_pair(
+        &self,
+        traded_pair: &TradedPair,
+        // Every book is generated at full depth
+        _depth_hint: DepthHint,
+    ) -> Result<Receiver<ReceivedOrderbook>, ExchangeError> {
+        let (mid_price, depth) =
+            simulated_market(traded_pair).ok_or_else(|| ExchangeError::UnsupportedPair {
+                exchange: self.id(),
+                pair: traded_pair.clone(),
+            })?;
+        let mid_price = mid_price * (1.0 + self.price_offset);
+
+        let channel_capacity = self.context.channel_capacity;
+        let (order_book_tx, order_book_rx) = metered_channel(
+            channel_capacity,
+            ChannelMeter::new(
+                "simulated_orderbooks",
+                &pair_label(traded_pair),
+                channel_capacity,
+            ),
+        );
+        let mut status = self
+            .context
+            .status_bus
+            .reporter(self.id(), traded_pair.clone());
+        let source = self.id();
+        let pair_label = traded_pair.to_string();
+        let clock = self.context.clock.clone();
+        let mut ticks = interval(self.update_interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        tokio::spawn(async move {
+            status.connected();
+
+            for step in 0.. {
+                ticks.tick().await;
+
+                let received = clock.now();
+                let receipt =
+                    info_span!("exchange_message", exchange = %source, pair = %pair_label);
+                let order_book: BoxedOrderbook = Box::new(SimulatedOrderBook::generate(
+                    source.clone(),
+                    mid_price,
+                    depth,
+                    step,
+                ));
+
+                if order_book_tx
+                    .send((order_book, received, receipt))
+                    .await
+                    .is_err()
+                {
+                    // The aggregator has stopped
+                    status.disconnected("Stream ended");
+                    break;
+                }
+            }
+        });
+
+        Ok(order_book_rx)
+    }
+
+    fn supported_pairs(&self) -> BoxFuture<'static, Result<Vec<TradedPair>, ExchangeError>> {
+        Box::pin(async {
+            Ok(SIMULATED_MARKETS
+                .iter()
+                .map(|(first, second, _, _)| TradedPair::new(first, second))
+                .collect())
+        })
+    }
+
+    fn clone_dyn(&self) -> BoxedExchange {
+        Box::new(self.clone())
+    }
+}
+
+/// The mid price and depth of a simulated market.
+fn simulated_market(traded_pair: &TradedPair) -> Option<(f64, usize)> {
+    SIMULATED_MARKETS
+        .iter()
+        .find(|(first, second, _, _)| {
+            traded_pair.first.eq_ignore_ascii_case(first)
+                && traded_pair.second.eq_ignore_ascii_case(second)
+        })
+        .map(|(_, _, mid_price, depth)| (*mid_price, *depth))
+}
+
+struct SimulatedOrderBook {
+    source: ExchangeId,
+    asks: Vec<Order>,
+    bids: Vec<Order>,
+}
+
+impl SimulatedOrderBook {
+    /// The book for a `step` of the simulation with `depth` levels a side, the mid price oscillates within 0.1% of
+    /// `mid_price`.
+    fn generate(source: ExchangeId, mid_price: f64, depth: usize, step: u64) -> Self {
+        let mid = mid_price * (1.0 + 0.001 * (step as f64 / 10.0).sin());
+        let tick = mid_price * 0.000_1;
+
+        let quantity = |index: usize| 1.0 + ((step as usize + index) % 5) as f64;
+
+        let asks = (0..depth)
+            .map(|index| Order {
+                price: mid + tick * (index + 1) as f64,
+                quantity: quantity(index),
+            })
+            .collect();
+        let bids = (0..depth)
+            .map(|index| Order {
+                price: mid - tick * (index + 1) as f64,
+                quantity: quantity(index),
+            })
+            .collect();
+
+        Self { source, asks, bids }
+    }
+}
+
+impl OrderBook for SimulatedOrderBook {
+    fn source(&self) -> ExchangeId {
+        self.source.clone()
+    }
+
+    fn best_asks(&self, depth: usize, levels: &mut Vec<Level>) {
+        sort_orders_to_depth(&self.asks, Ordering::LowToHigh, depth, &self.source, levels)
+    }
+
+    fn best_bids(&self, depth: usize, levels: &mut Vec<Level>) {
+        sort_orders_to_depth(&self.bids, Ordering::HighToLow, depth, &self.source, levels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::ExchangeId;
+
+    use crate::exchange::OrderBook;
+
+    use super::SimulatedOrderBook;
+
+    #[test]
+    fn should_generate_deterministic_books() {
+        let source = ExchangeId::from("SimulatedA");
+        let book = SimulatedOrderBook::generate(source.clone(), 0.07, 20, 3);
+
+        let asks = |book: &SimulatedOrderBook, depth| {
+            let mut levels = Vec::new();
+            book.best_asks(depth, &mut levels);
+            levels
+        };
+        let bids = |book: &SimulatedOrderBook, depth| {
+            let mut levels = Vec::new();
+            book.best_bids(depth, &mut levels);
+            levels
+        };
+
+        assert!(book.spread() > 0.0);
+        assert_eq!(asks(&book, 5).len(), 5);
+        assert!(asks(&book, 2)[0].price < asks(&book, 2)[1].price);
+        assert!(bids(&book, 2)[0].price > bids(&book, 2)[1].price);
+
+        let same_step = SimulatedOrderBook::generate(source.clone(), 0.07, 20, 3);
+        let next_step = SimulatedOrderBook::generate(source, 0.07, 20, 4);
+        assert_eq!(bids(&book, 20), bids(&same_step, 20));
+        assert_ne!(bids(&book, 20), bids(&next_step, 20));
+    }
+}
@@ -0,0 +1,497 @@
+use std::{
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::{sync::mpsc::Receiver, time::timeout};
+use tracing::{error, info_span};
+use url::Url;
+
+use crate::{
+    error::ExchangeError,
+    exchange::{
+        sort_orders_to_depth, BoxedExchange, BoxedOrderbook, ConnectorContext, DepthHint, Exchange,
+        ExchangeInfo, Order, OrderBook, Ordering, RateLimitProfile, ReceivedOrderbook,
+    },
+    metrics::{metered_channel, pair_label, ChannelMeter},
+    multiplex::{ControlFrame, MuxEvent, MuxProtocol, Routed, SharedConnections},
+};
+use order_book_service_types::proto::{ExchangeId, Level, TradedPair};
+
+/// Describes a venue streaming full JSON orderbooks over a websocket, so it can be connected to by [SpecExchange]
+/// rather than a connector of its own.
+///
+/// Templates have their placeholder replaced, `{stream}` in the subscription messages and `{symbol}` in the stream.
+/// Pointers are [JSON pointers](https://www.rfc-editor.org/rfc/rfc6901) into each frame, e.g. `/data/bids`, to an
+/// array of `[price, quantity]` levels given as strings or numbers.
+pub(crate) trait ExchangeSpec: Send + Sync + 'static {
+    const NAME: &'static str;
+    const WEBSOCKET_URL: &'static str;
+    /// Where the pairs listed by the venue are fetched from, see [ExchangeSpec::listed_pairs]
+    const PAIRS_URL: &'static str;
+
+    const SUBSCRIBE_TEMPLATE: &'static str;
+    const UNSUBSCRIBE_TEMPLATE: &'static str;
+    /// The stream of a pair's orderbook
+    const STREAM_TEMPLATE: &'static str;
+
+    /// The stream a frame belongs to, frames without one are control messages
+    const STREAM_POINTER: &'static str;
+    const BIDS_POINTER: &'static str;
+    const ASKS_POINTER: &'static str;
+    /// When the venue produced the book, in microseconds since the epoch
+    const TIMESTAMP_MICROS_POINTER: Option<&'static str> = None;
+
+    /// Streams subscribed to over each socket
+    const MAX_STREAMS_PER_SOCKET: usize = 50;
+    /// The least time between messages sent to the venue
+    const MIN_MESSAGE_INTERVAL: Duration = Duration::ZERO;
+    /// Sent over each socket at the interval, for venues which close sockets they haven't heard from
+    const KEEPALIVE: Option<(Duration, &'static str)> = None;
+    /// The venue's published limit on new connections, see [RateLimitProfile]
+    const MAX_CONNECTIONS_PER_MINUTE: Option<usize> = None;
+    /// The venue's published fees as fractions, see [ExchangeInfo]
+    const MAKER_FEE: Option<f64> = None;
+    const TAKER_FEE: Option<f64> = None;
+
+    /// The body of the response from [ExchangeSpec::PAIRS_URL]
+    type PairsResponse: DeserializeOwned + Send;
+
+    /// The pairs in the response which can be streamed.
+    fn listed_pairs(response: Self::PairsResponse) -> Vec<TradedPair>;
+
+    fn symbol(traded_pair: &TradedPair) -> String {
+        traded_pair.symbol_lower()
+    }
+
+    /// Whether a pair is worth subscribing to, those which aren't are refused as unsupported.
+    fn supports(_traded_pair: &TradedPair) -> bool {
+        true
+    }
+
+    /// Whether a frame naming a stream carries its book rather than e.g. an acknowledgement.
+    fn is_book_frame(_frame: &Value) -> bool {
+        true
+    }
+
+    /// What a frame which isn't a book asks of the connection, e.g. to reconnect or reporting an error.
+    fn control(_frame: &Value) -> Option<ControlFrame> {
+        None
+    }
+}
+
+/// Connects to the venue described by `S`, sharing its sockets across pairs and reporting each stream's status.
+pub(crate) struct SpecExchange<S: ExchangeSpec> {
+    connections: SharedConnections<SpecProtocol<S>>,
+    pairs_endpoint: Url,
+    context: ConnectorContext,
+    spec: PhantomData<fn() -> S>,
+}
+
+impl<S: ExchangeSpec> Clone for SpecExchange<S> {
+    fn clone(&self) -> Self {
+        Self {
+            connections: self.connections.clone(),
+            pairs_endpoint: self.pairs_endpoint.clone(),
+            context: self.context.clone(),
+            spec: PhantomData,
+        }
+    }
+}
+
+impl<S: ExchangeSpec> SpecExchange<S> {
+    pub(crate) fn new(context: ConnectorContext) -> Self {
+        Self::connecting_to(
+            Url::parse(S::WEBSOCKET_URL).expect("Spec websocket URL should parse"),
+            context,
+        )
+    }
+
+    fn spec_info() -> ExchangeInfo {
+        ExchangeInfo {
+            maker_fee: S::MAKER_FEE,
+            taker_fee: S::TAKER_FEE,
+            rate_limit: RateLimitProfile {
+                max_connections_per_minute: S::MAX_CONNECTIONS_PER_MINUTE,
+                max_streams_per_connection: Some(S::MAX_STREAMS_PER_SOCKET),
+                min_message_interval: S::MIN_MESSAGE_INTERVAL,
+            },
+            websocket_url: Some(S::WEBSOCKET_URL),
+            ..ExchangeInfo::new(S::NAME)
+        }
+    }
+
+    fn connecting_to(websocket_url: Url, context: ConnectorContext) -> Self {
+        let connections = SharedConnections::new(
+            S::NAME,
+            websocket_url,
+            SpecProtocol::<S>::new(),
+            S::MAX_STREAMS_PER_SOCKET,
+            context.channel_capacity,
+            context.frame_tap.clone(),
+            context.rate_limits.venue(&Self::spec_info()),
+        );
+
+        Self {
+            connections,
+            pairs_endpoint: Url::parse(S::PAIRS_URL).expect("Spec pairs URL should parse"),
+            context,
+            spec: PhantomData,
+        }
+    }
+}
+
+impl<S: ExchangeSpec> Exchange for SpecExchange<S> {
+    fn info(&self) -> ExchangeInfo {
+        Self::spec_info()
+    }
+
+    fn stream_order_book_for_pair(
+        &self,
+        traded_pair: &TradedPair,
+        // Only full orderbook streams are described by a spec
+        _depth_hint: DepthHint,
+    ) -> Result<Receiver<ReceivedOrderbook>, ExchangeError> {
+        if !S::supports(traded_pair) {
+            return Err(ExchangeError::UnsupportedPair {
+                exchange: self.id(),
+                pair: traded_pair.clone(),
+            });
+        }
+
+        let channel_capacity = self.context.channel_capacity;
+        let (order_book_tx, order_book_rx) = metered_channel(
+            channel_capacity,
+            ChannelMeter::new(
+                &format!("{}_orderbooks", S::NAME.to_lowercase()),
+                &pair_label(traded_pair),
+                channel_capacity,
+            ),
+        );
+        let pair_label = traded_pair.to_string();
+        let mut status = self
+            .context
+            .status_bus
+            .reporter(self.id(), traded_pair.clone());
+        let stale_after = self.context.stale_after;
+        let clock = self.context.clock.clone();
+        let source = self.id();
+
+        let stream = S::STREAM_TEMPLATE.replace("{symbol}", &self.symbol_for_pair(traded_pair));
+        let mut events = self.connections.subscribe(stream);
+
+        tokio::spawn(async move {
+            // Handle the stream, reporting when it goes quiet
+            loop {
+                let frame = match timeout(stale_after, events.recv()).await {
+                    Ok(Some(MuxEvent::Subscribed)) => {
+                        status.connected();
+                        continue;
+                    }
+                    Ok(Some(MuxEvent::Frame(frame))) => frame,
+                    Ok(Some(MuxEvent::Disconnected(reason))) => {
+                        status.disconnected(reason);
+                        break;
+                    }
+                    Ok(None) => {
+                        status.disconnected("Stream ended");
+                        break;
+                    }
+                    Err(_) => {
+                        status.stale(stale_after);
+                        continue;
+                    }
+                };
+
+                status.received();
+                let received = clock.now();
+                let receipt =
+                    info_span!("exchange_message", exchange = S::NAME, pair = %pair_label);
+                match receipt.in_scope(|| SpecOrderBook::parse::<S>(source.clone(), &frame)) {
+                    Ok(order_book) => {
+                        let order_book: BoxedOrderbook = Box::new(order_book);
+                        if order_book_tx
+                            .send((order_book, received, receipt))
+                            .await
+                            .is_err()
+                        {
+                            // The aggregator has stopped, dropping the events unsubscribes the stream
+                            break;
+                        }
+                    }
+                    Err(parse_err) => error!("Unable to parse {} book: {parse_err}", S::NAME),
+                }
+            }
+        });
+
+        Ok(order_book_rx)
+    }
+
+    fn supported_pairs(&self) -> BoxFuture<'static, Result<Vec<TradedPair>, ExchangeError>> {
+        let pairs_endpoint = self.pairs_endpoint.clone();
+        let exchange = self.id();
+
+        Box::pin(async move {
+            let resource = "trading pairs";
+            let response = reqwest::get(pairs_endpoint)
+                .await
+                .map_err(|source| ExchangeError::Request {
+                    exchange: exchange.clone(),
+                    resource,
+                    source,
+                })?
+                .json::<S::PairsResponse>()
+                .await
+                .map_err(|source| ExchangeError::Parse {
+                    exchange,
+                    resource,
+                    source,
+                })?;
+
+            Ok(S::listed_pairs(response))
+        })
+    }
+
+    fn symbol_for_pair(&self, traded_pair: &TradedPair) -> String {
+        S::symbol(traded_pair)
+    }
+
+    fn clone_dyn(&self) -> BoxedExchange {
+        Box::new(self.clone())
+    }
+}
+
+/// Subscribes with the spec's templates and routes frames by the stream they name, passing each on parsed.
+pub(crate) struct SpecProtocol<S>(PhantomData<fn() -> S>);
+
+impl<S> SpecProtocol<S> {
+    pub(crate) fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<S: ExchangeSpec> MuxProtocol for SpecProtocol<S> {
+    type Payload = Arc<Value>;
+
+    fn subscribe_message(&self, stream: &str) -> String {
+        S::SUBSCRIBE_TEMPLATE.replace("{stream}", stream)
+    }
+
+    fn unsubscribe_message(&self, stream: &str) -> String {
+        S::UNSUBSCRIBE_TEMPLATE.replace("{stream}", stream)
+    }
+
+    fn route(&self, frame: &str) -> Routed<Arc<Value>> {
+        let Ok(value) = serde_json::from_str::<Value>(frame) else {
+            return Routed::Ignored;
+        };
+        let stream = value.pointer(S::STREAM_POINTER).and_then(Value::as_str);
+        match stream {
+            Some(stream) if S::is_book_frame(&value) => {
+                Routed::Stream(stream.to_string(), Arc::new(value))
+            }
+            _ => S::control(&value).map_or(Routed::Ignored, Routed::Control),
+        }
+    }
+
+    fn min_message_interval(&self) -> Duration {
+        S::MIN_MESSAGE_INTERVAL
+    }
+
+    fn keepalive(&self) -> Option<(Duration, String)> {
+        S::KEEPALIVE.map(|(interval, message)| (interval, message.to_string()))
+    }
+}
+
+/// A book read from a frame through the spec's pointers.
+#[derive(Debug)]
+pub(crate) struct SpecOrderBook {
+    source: ExchangeId,
+    bids: Vec<Order>,
+    asks: Vec<Order>,
+    timestamp: Option<SystemTime>,
+}
+
+impl SpecOrderBook {
+    pub(crate) fn parse<S: ExchangeSpec>(
+        source: ExchangeId,
+        frame: &Value,
+    ) -> Result<Self, String> {
+        let timestamp = S::TIMESTAMP_MICROS_POINTER
+            .and_then(|pointer| frame.pointer(pointer))
+            .and_then(number)
+            .filter(|micros| *micros > 0.0)
+            .map(|micros| UNIX_EPOCH + Duration::from_micros(micros as u64));
+
+        Ok(Self {
+            source,
+            bids: orders(frame, S::BIDS_POINTER)?,
+            asks: orders(frame, S::ASKS_POINTER)?,
+            timestamp,
+        })
+    }
+}
+
+/// The `[price, quantity]` levels at `pointer`, skipping any which aren't a pair of finite numbers.
+fn orders(frame: &Value, pointer: &str) -> Result<Vec<Order>, String> {
+    let levels = frame
+        .pointer(pointer)
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("No levels at {pointer}"))?;
+
+    Ok(levels
+        .iter()
+        .filter_map(|level| {
+            Some(Order {
+                price: number(level.get(0)?)?,
+                quantity: number(level.get(1)?)?,
+            })
+        })
+        .collect())
+}
+
+/// Venues give numbers as strings to keep their precision, or as plain JSON numbers.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(number) => number.parse().ok(),
+        other => other.as_f64(),
+    }
+    .filter(|number: &f64| number.is_finite())
+}
+
+impl OrderBook for SpecOrderBook {
+    fn source(&self) -> ExchangeId {
+        self.source.clone()
+    }
+
+    fn best_asks(&self, depth: usize, levels: &mut Vec<Level>) {
+        sort_orders_to_depth(&self.asks, Ordering::LowToHigh, depth, &self.source, levels)
+    }
+
+    fn best_bids(&self, depth: usize, levels: &mut Vec<Level>) {
+        sort_orders_to_depth(&self.bids, Ordering::HighToLow, depth, &self.source, levels)
+    }
+
+    fn exchange_timestamp(&self) -> Option<SystemTime> {
+        self.timestamp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{accept_async, tungstenite::Message};
+    use url::Url;
+
+    use order_book_service_types::proto::{ExchangeId, Level, TradedPair};
+
+    use crate::{
+        config::{Config, TapConfig},
+        connector_status::ConnectorStatusBus,
+        exchange::{fixed_depth_hint, ConnectorContext, Exchange},
+        tap::FrameTap,
+    };
+
+    use super::{ExchangeSpec, SpecExchange};
+
+    /// A venue in the style of many, levels as strings under `data` and a ping expected every few seconds.
+    struct TestVenue;
+
+    impl ExchangeSpec for TestVenue {
+        const NAME: &'static str = "TestVenue";
+        const WEBSOCKET_URL: &'static str = "ws://localhost";
+        const PAIRS_URL: &'static str = "http://localhost/pairs";
+        const SUBSCRIBE_TEMPLATE: &'static str = r#"{"op":"subscribe","args":["{stream}"]}"#;
+        const UNSUBSCRIBE_TEMPLATE: &'static str = r#"{"op":"unsubscribe","args":["{stream}"]}"#;
+        const STREAM_TEMPLATE: &'static str = "books.{symbol}";
+        const STREAM_POINTER: &'static str = "/topic";
+        const BIDS_POINTER: &'static str = "/data/b";
+        const ASKS_POINTER: &'static str = "/data/a";
+        const TIMESTAMP_MICROS_POINTER: Option<&'static str> = Some("/data/ts");
+        const KEEPALIVE: Option<(Duration, &'static str)> =
+            Some((Duration::from_millis(50), r#"{"op":"ping"}"#));
+
+        type PairsResponse = Vec<String>;
+
+        fn listed_pairs(response: Self::PairsResponse) -> Vec<TradedPair> {
+            response
+                .iter()
+                .filter_map(|name| name.split_once('-'))
+                .map(|(first, second)| TradedPair {
+                    first: first.to_string(),
+                    second: second.to_string(),
+                })
+                .collect()
+        }
+
+        fn symbol(traded_pair: &TradedPair) -> String {
+            format!("{}-{}", traded_pair.first, traded_pair.second)
+        }
+    }
+
+    #[tokio::test]
+    async fn should_stream_books_described_by_a_spec() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // Answers the subscription with a book, then a second once pinged
+        tokio::spawn(async move {
+            let (connection, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(connection).await.unwrap();
+            while let Some(Ok(Message::Text(request))) = ws_stream.next().await {
+                let book = match request.as_str() {
+                    r#"{"op":"subscribe","args":["books.ETH-BTC"]}"# => {
+                        r#"{"topic":"books.ETH-BTC","data":{"ts":1700000000000000,"b":[["0.0695","2.5"],["0.0697",1.0]],"a":[["0.0705","3"],["bad","1"]]}}"#
+                    }
+                    r#"{"op":"ping"}"# => r#"{"topic":"books.ETH-BTC","data":{"b":[],"a":[]}}"#,
+                    _ => continue,
+                };
+                ws_stream
+                    .send(Message::Text(book.to_string()))
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let context = ConnectorContext::new(
+            &Config::default(),
+            FrameTap::new(TapConfig::default()),
+            ConnectorStatusBus::new(10),
+        );
+        let venue = SpecExchange::<TestVenue>::connecting_to(
+            Url::parse(&format!("ws://{address}")).unwrap(),
+            context,
+        );
+        let mut books = venue
+            .stream_order_book_for_pair(&TradedPair::new("ETH", "BTC"), fixed_depth_hint(10))
+            .unwrap();
+
+        let (book, _, _) = books.recv().await.expect("Should stream a book");
+        assert_eq!(book.source(), ExchangeId::from("TestVenue"));
+        let mut bids = Vec::new();
+        book.best_bids(10, &mut bids);
+        assert_eq!(
+            bids,
+            vec![
+                Level::new("TestVenue", 0.0697, 1.0),
+                Level::new("TestVenue", 0.0695, 2.5)
+            ]
+        );
+        // Levels which don't parse are skipped
+        let mut asks = Vec::new();
+        book.best_asks(10, &mut asks);
+        assert_eq!(asks, vec![Level::new("TestVenue", 0.0705, 3.0)]);
+        assert!(book.exchange_timestamp().is_some());
+
+        // The keepalive is sent without any prompting
+        let (book, _, _) = books.recv().await.expect("Should be pinged");
+        assert_eq!(book.exchange_timestamp(), None);
+    }
+}
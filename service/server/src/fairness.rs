@@ -0,0 +1,84 @@
+use std::collections::{HashMap, HashSet};
+
+use order_book_service_types::proto::Level;
+
+/// The best `depth` of a side's `levels`, ordered best first, including at least `min_per_venue` levels from each
+/// exchange on the side where it has them.
+///
+/// The reservation shrinks to fit when the exchanges can't all have `min_per_venue` levels within `depth`. The best
+/// level is always included, so the spread is the same as with the best levels whatever their exchange.
+pub(crate) fn venue_fair_levels(
+    levels: &[Level],
+    depth: usize,
+    min_per_venue: usize,
+) -> Vec<Level> {
+    let venues = levels
+        .iter()
+        .map(|level| level.exchange.as_str())
+        .collect::<HashSet<_>>()
+        .len();
+    let reserved_per_venue = min_per_venue.min(depth / venues.max(1));
+
+    // Each exchange's best levels are reserved first, then the remaining places go to the best of the rest
+    let mut per_venue = HashMap::<&str, usize>::new();
+    let mut selected = vec![false; levels.len()];
+    let mut remaining = depth;
+    for (index, level) in levels.iter().enumerate() {
+        let count = per_venue.entry(level.exchange.as_str()).or_default();
+        if *count < reserved_per_venue && remaining > 0 {
+            *count += 1;
+            selected[index] = true;
+            remaining -= 1;
+        }
+    }
+    for (index, _) in levels.iter().enumerate() {
+        if remaining == 0 {
+            break;
+        }
+        if !selected[index] {
+            selected[index] = true;
+            remaining -= 1;
+        }
+    }
+
+    levels
+        .iter()
+        .zip(selected)
+        .filter(|(_, selected)| *selected)
+        .map(|(level, _)| level.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::Level;
+
+    use super::venue_fair_levels;
+
+    #[test]
+    fn should_reserve_levels_for_each_venue() {
+        let asks = vec![
+            Level::new("Binance", 1.0, 1.0),
+            Level::new("Binance", 2.0, 1.0),
+            Level::new("Binance", 3.0, 1.0),
+            Level::new("Binance", 4.0, 1.0),
+            Level::new("Bitstamp", 5.0, 1.0),
+            Level::new("Bitstamp", 6.0, 1.0),
+            Level::new("Kraken", 7.0, 1.0),
+        ];
+
+        let prices =
+            |levels: Vec<Level>| levels.iter().map(|level| level.price).collect::<Vec<_>>();
+        // Best first, with Bitstamp's best two and Kraken's only level ahead of Binance's fourth
+        assert_eq!(
+            prices(venue_fair_levels(&asks, 6, 2)),
+            vec![1.0, 2.0, 3.0, 5.0, 6.0, 7.0]
+        );
+        // Three venues can only have one reserved level each in a depth of five
+        assert_eq!(
+            prices(venue_fair_levels(&asks, 5, 2)),
+            vec![1.0, 2.0, 3.0, 5.0, 7.0]
+        );
+        assert_eq!(prices(venue_fair_levels(&asks, 3, 0)), vec![1.0, 2.0, 3.0]);
+    }
+}
@@ -1,89 +1,581 @@
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashMap, convert::Infallible, io, net::SocketAddr, sync::Arc, time::Duration,
+};
+#[cfg(unix)]
+use std::{
+    fs,
+    io::ErrorKind,
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context, Error};
 use tokio::sync::{
+    broadcast::error::RecvError as BroadcastRecvError,
     broadcast::Receiver as BroadcastReceiver,
-    mpsc::{channel as mpsc_channel, Sender as MpscSender},
+    mpsc::Receiver,
     oneshot::{channel as oneshot_channel, Sender as OneshotSender},
-    Mutex,
+    Mutex, OnceCell,
+};
+use tokio::{
+    io::DuplexStream,
+    net::TcpListener,
+    pin, select,
+    task::JoinHandle,
+    time::{sleep_until, timeout, Instant},
+};
+use tokio_stream::{
+    wrappers::{ReceiverStream, TcpListenerStream},
+    Stream, StreamExt,
+};
+use tonic::{
+    body::BoxBody,
+    codegen::{http, Body, BoxFuture, Context, InterceptedService, Poll, Service, StdError},
+    metadata::MetadataMap,
+    server::{Grpc, NamedService, ServerStreamingService},
+    service::Interceptor,
+    transport::Server,
+    Code, Request, Response, Status, Streaming,
+};
+use tonic_health::{
+    proto::health_server::{Health, HealthServer},
+    server::health_reporter,
+};
+use tracing::{info, info_span, warn, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+#[cfg(unix)]
+use {tokio::net::UnixListener, tokio_stream::wrappers::UnixListenerStream};
+
+use order_book_service_types::{
+    batch::batch_summaries,
+    descriptor::api_descriptor,
+    filter::SummaryFilter,
+    integrity::SummaryChain,
+    proto::{
+        orderbook_admin_server::OrderbookAdminServer,
+        orderbook_aggregator_server::{OrderbookAggregator, OrderbookAggregatorServer},
+        subscription_command::Command,
+        tagged_summary::Payload,
+        AmountDenomination, ApiDescriptor, BatchedRequest, Capability, CloseReason,
+        ConnectorStatus, DepthHistogram, DepthHistogramRequest, DepthSnapshotRequest, Empty,
+        MergeStrategy, OrderBookRequest, ServerInfo, ServiceEvent, Side, SlippageEstimate,
+        SlippageRequest, SubscriptionCommand, SubscriptionDescription, Summary, SummaryBatch,
+        SupportedPairs, TaggedSummary, TradedPair,
+    },
 };
-use tokio_stream::wrappers::ReceiverStream;
-use tonic::{transport::Server, Request, Response, Status};
 
-use order_book_service_types::proto::{
-    orderbook_aggregator_server::{OrderbookAggregator, OrderbookAggregatorServer},
-    OrderBookRequest, Summary, TradedPair,
+use crate::{
+    admin::AdminService,
+    aggregator::{AggregatorHandle, MergedBook},
+    config::{ChannelConfig, Config, DepthConfig, TransformKind},
+    connector_status::ConnectorStatusBus,
+    demo::{DemoRateLimit, DEMO_REQUESTS_PER_WINDOW},
+    drain::Drain,
+    error::{AggregatorError, ServerError},
+    events::EventBus,
+    fairness::venue_fair_levels,
+    histogram::depth_histogram,
+    metrics::{metered_channel, pair_label, ChannelMeter, MeteredSender},
+    pairs::PairDirectory,
+    request_log::{RequestLogLayer, TRADED_PAIR_METADATA},
+    shared_encoding::{EncodedSummary, EncodedSummaryCodec, Outgoing, SummaryTick},
+    slippage::estimate_slippage,
+    subscribers::{ClientIdentity, Subscribers},
+    telemetry,
+    tenancy::{SubscriptionGovernor, TenantId, TenantInterceptor, Tenants},
+    transform::FeeAdjustment,
 };
 
-pub(crate) type SummaryReceiver = BroadcastReceiver<Result<Summary, Arc<Error>>>;
-type NewSubscriberNotifier = MpscSender<(TradedPair, OneshotSender<SummaryReceiver>)>;
+/// Summaries along with the book and merge which produced them.
+pub(crate) type SummaryReceiver = BroadcastReceiver<Result<Arc<SummaryTick>, Arc<AggregatorError>>>;
+type NewSubscriberNotifier = MeteredSender<(TradedPair, OneshotSender<AggregatorHandle>)>;
+
+/// A pair's aggregator, empty while it is being created.
+type AggregatorCell = Arc<OnceCell<AggregatorHandle>>;
+
+/// Path of the BookSummary RPC, served by [SharedEncodingRoute].
+const BOOK_SUMMARY_PATH: &str = "/orderbook.OrderbookAggregator/BookSummary";
+/// How long a request/response RPC will wait for a new aggregator to produce its first book
+const FIRST_BOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Window used by BookSummaryBatched when the request doesn't specify one
+const DEFAULT_BATCH_WINDOW_SECS: u32 = 5;
+/// Longer windows risk batches exceeding the maximum gRPC message size
+const MAX_BATCH_WINDOW_SECS: u32 = 60;
+/// Histogram bands when the request doesn't specify them, 0.1% of the mid price wide
+const DEFAULT_BAND_WIDTH: f64 = 0.001;
+const DEFAULT_BAND_COUNT: u32 = 10;
+const MAX_BAND_COUNT: u32 = 100;
 
 /// The [OrderbookService]'s role is to emit a stream of Summary data.
 /// It does this by receiving a stream of Orderbooks and then parsing out the spread, top 10 asks and top 10 bids.
-#[derive(Debug)]
+// Cloned into the tasks serving ManageSubscriptions, which subscribe on behalf of the client after the RPC returns
+#[derive(Clone, Debug)]
 struct OrderbookService {
     new_subscriber_notifier: NewSubscriberNotifier,
     // Because the auto-generated trait signature for book_summary() takes `&self` not `&mut self` there needs to be a Mutex to guard the HashMap.
-    summary_receivers: Mutex<HashMap<TradedPair, SummaryReceiver>>,
+    // Each pair's cell is filled once its aggregator has been created, and removed once the aggregator stops.
+    aggregators: Arc<Mutex<HashMap<TradedPair, AggregatorCell>>>,
+    channels: ChannelConfig,
+    event_bus: EventBus,
+    status_bus: ConnectorStatusBus,
+    pair_directory: Arc<PairDirectory>,
+    heartbeat_interval: Duration,
+    depth_limits: DepthConfig,
+    /// Seal each subscription's summaries into a hash chain
+    integrity: bool,
+    /// Taker fees for subscriptions which request effective prices
+    fee_adjustment: Arc<FeeAdjustment>,
+    governor: SubscriptionGovernor,
+    /// Open subscriptions and the clients which opened them, listed by the admin service
+    subscribers: Subscribers,
+    /// Advertised by GetServerInfo
+    capabilities: Vec<Capability>,
+    /// Refuses new subscriptions and ends open ones once the admin service drains the server
+    drain: Drain,
+}
+
+impl OrderbookService {
+    /// Returns a handle to the aggregator for the requested pair, requesting a new aggregator if there isn't one.
+    ///
+    /// Requests for a pair whose aggregator is being created wait for it, without holding up requests for other pairs.
+    async fn aggregator_for_pair(
+        &self,
+        requested_pair: TradedPair,
+    ) -> Result<AggregatorHandle, Status> {
+        // The map is only locked to find the pair's cell, not while its aggregator is created
+        let cell = self
+            .aggregators
+            .lock()
+            .await
+            .entry(requested_pair.clone())
+            .or_default()
+            .clone();
+
+        cell.get_or_try_init(|| self.create_aggregator(requested_pair, cell.clone()))
+            .await
+            .cloned()
+    }
+
+    /// Request a new aggregator for the pair, forgetting its handle once it stops so that the next request for the pair,
+    /// e.g. after it has been relisted, creates another rather than being given the stopped one.
+    async fn create_aggregator(
+        &self,
+        requested_pair: TradedPair,
+        cell: AggregatorCell,
+    ) -> Result<AggregatorHandle, Status> {
+        let (new_request_tx, new_request_rx) = oneshot_channel();
+
+        let _ = self
+            .new_subscriber_notifier
+            .send((requested_pair.clone(), new_request_tx))
+            .await;
+
+        // Wait for the handle to the new aggregator or return the Status for the Err case
+        let handle = new_request_rx
+            .await
+            .map_err(|recv_err| Status::from_error(recv_err.into()))?;
+
+        let stopped = handle.stopped();
+        let aggregators = self.aggregators.clone();
+        tokio::spawn(async move {
+            stopped.await;
+            let mut aggregators = aggregators.lock().await;
+            // Unless it has already been replaced
+            if aggregators
+                .get(&requested_pair)
+                .is_some_and(|current| Arc::ptr_eq(current, &cell))
+            {
+                aggregators.remove(&requested_pair);
+            }
+        });
+
+        Ok(handle)
+    }
+
+    /// Start forwarding summaries for the requested pair to a new channel, for as long as its receiver is held.
+    async fn subscribe<T: Outgoing>(
+        &self,
+        request: Request<OrderBookRequest>,
+    ) -> Result<Receiver<Result<T, Status>>, Status> {
+        // Continue the client's trace, if it sent one
+        let remote_context = telemetry::remote_context(request.metadata());
+        let client = ClientIdentity::from_metadata(request.metadata());
+        let tenant = request.extensions().get::<TenantId>().cloned();
+        self.drain.admit()?;
+        // Counts towards the tenant's quota for as long as the subscription is open
+        let permit = self.governor.admit(tenant.as_ref())?;
+        let request = request.into_inner();
+        let depth = requested_depth(request.depth, &self.depth_limits)?;
+        let filter = requested_filter(&request.filter)?;
+        let venue_fair = requested_venue_fairness(&request, depth)?;
+        let fee_adjustment = request
+            .effective_prices
+            .then(|| self.fee_adjustment.clone());
+        let quote_amounts = request.amount_denomination() == AmountDenomination::Quote;
+        let requested_pair = requested_pair(request.traded_pair)?;
+
+        let pair_label = pair_label(&requested_pair);
+        let subscription_span = info_span!(
+            "book_summary",
+            pair = %requested_pair,
+            depth,
+            client_name = %client.name,
+            client_version = %client.version
+        );
+        subscription_span.set_parent(remote_context);
+
+        // Create a new subscription for the client
+        let handle = self.aggregator_for_pair(requested_pair.clone()).await?;
+        let registration = self
+            .subscribers
+            .register(requested_pair, depth, client, tenant);
+        // The aggregator produces summaries at the deepest depth requested, which each subscription cuts down to its own.
+        // Requested before receiving, so the summaries received are at least as deep as this subscription's
+        let depth_request = handle.depth_requests.request(depth);
+        let new_subscription = handle.summary_receiver.resubscribe();
+
+        // The receiving side of this channel will be returned to the client as a stream.
+        let (client_channel_tx, client_channel_rx) = metered_channel(
+            self.channels.client_stream,
+            ChannelMeter::new("client_stream", &pair_label, self.channels.client_stream),
+        );
+
+        // This task takes the sending side of the summary channel and populates it with Summary events as it receives OrderBooks from the server-side subscription.
+        let summaries_meter = ChannelMeter::new("summaries", &pair_label, self.channels.summaries);
+        let settings = SubscriptionSettings {
+            heartbeat_interval: self.heartbeat_interval,
+            depth,
+            fee_adjustment,
+            filter,
+            venue_fair,
+            quote_amounts,
+            integrity: self.integrity,
+            drain: self.drain.clone(),
+        };
+        tokio::spawn(
+            async move {
+                // The depth request, tenant's permit and registration are released once the subscription ends
+                let _depth_request = depth_request;
+                let _permit = permit;
+                let _registration = registration;
+                handle_subscription_stream(
+                    new_subscription,
+                    client_channel_tx,
+                    summaries_meter,
+                    settings,
+                )
+                .await
+            }
+            .instrument(subscription_span),
+        );
+
+        Ok(client_channel_rx)
+    }
+
+    /// Subscribe on behalf of a ManageSubscriptions client, forwarding the summaries to it tagged with `subscription_id`.
+    async fn open_managed(
+        &self,
+        subscription_id: &str,
+        request: OrderBookRequest,
+        throttle: Duration,
+        caller: &Caller,
+        tx: &MeteredSender<Result<TaggedSummary, Status>>,
+    ) -> Result<ManagedSubscription, Status> {
+        let summaries = self.subscribe(caller.request(request.clone())).await?;
+
+        Ok(ManagedSubscription {
+            request,
+            forwarder: tokio::spawn(forward_tagged(
+                subscription_id.to_string(),
+                summaries,
+                throttle,
+                tx.clone(),
+            )),
+        })
+    }
+
+    /// The BookSummary RPC, sending its summaries as either [Summary]s or [EncodedSummary]s.
+    async fn book_summary_stream<T: Outgoing>(
+        &self,
+        request: Request<OrderBookRequest>,
+    ) -> Result<Response<ReceiverStream<Result<T, Status>>>, Status> {
+        let pair = request
+            .get_ref()
+            .traded_pair
+            .as_ref()
+            .map(TradedPair::canonical);
+        let summaries = self.subscribe(request).await?;
+
+        Ok(with_pair_metadata(
+            Response::new(ReceiverStream::new(summaries)),
+            pair.as_ref(),
+        ))
+    }
+
+    /// The latest merged book for the requested pair, waiting for the first if its aggregator is new.
+    ///
+    /// Fails once the aggregator has stopped, rather than serving the last book it merged as though it were live.
+    async fn latest_book(&self, requested_pair: TradedPair) -> Result<Arc<MergedBook>, Status> {
+        let mut book_receiver = self
+            .aggregator_for_pair(requested_pair)
+            .await?
+            .book_receiver;
+        let stopped = |_| Status::unavailable("The aggregator for the requested pair has stopped");
+
+        // A newly created aggregator won't have produced a book yet
+        timeout(FIRST_BOOK_TIMEOUT, async {
+            loop {
+                // The last book is kept once the aggregator has dropped its sender
+                book_receiver.has_changed().map_err(stopped)?;
+                if let Some(book) = book_receiver.borrow_and_update().clone() {
+                    return Ok(book);
+                }
+                book_receiver.changed().await.map_err(stopped)?;
+            }
+        })
+        .await
+        .map_err(|_| Status::unavailable("No orderbook is available yet for the requested pair"))?
+    }
 }
 
 #[tonic::async_trait]
 impl OrderbookAggregator for OrderbookService {
     type BookSummaryStream = ReceiverStream<Result<Summary, Status>>;
 
-    /// This fn is called every time a client hits the BookSummary rpc.
+    /// The BookSummary RPC when the service is called directly, clients are served by [SharedEncodingRoute].
     async fn book_summary(
         &self,
         request: Request<OrderBookRequest>,
     ) -> Result<Response<Self::BookSummaryStream>, Status> {
-        let requested_pair = request.into_inner().traded_pair.ok_or_else(|| {
-            Status::invalid_argument("This RPC requires traded_pair to be provided")
+        self.book_summary_stream(request).await
+    }
+
+    type BookSummaryBatchedStream = ReceiverStream<Result<SummaryBatch, Status>>;
+
+    /// Subscribe to summaries as with BookSummary, but deliver every summary from each window together.
+    async fn book_summary_batched(
+        &self,
+        request: Request<BatchedRequest>,
+    ) -> Result<Response<Self::BookSummaryBatchedStream>, Status> {
+        let (metadata, extensions, request) = request.into_parts();
+        let window = batch_window(request.window_secs)?;
+        let subscription = request.subscription.ok_or_else(|| {
+            Status::invalid_argument("This RPC requires subscription to be provided")
         })?;
+        let subscription_pair = subscription.traded_pair.as_ref().map(TradedPair::canonical);
+        let pair_label = subscription_pair
+            .as_ref()
+            .map(pair_label)
+            .unwrap_or_default();
 
-        // Acquire a lock on the HashMap of receivers
-        let mut map_lock = self.summary_receivers.lock().await;
+        let summaries = self
+            .subscribe(Request::from_parts(metadata, extensions, subscription))
+            .await?;
 
-        // There is already a channel for the requested traded pair
-        if let Some(existing_summary_receiver) = map_lock.get(&requested_pair) {
-            let new_subscription = existing_summary_receiver.resubscribe();
+        let (client_channel_tx, client_channel_rx) = metered_channel(
+            self.channels.client_stream,
+            ChannelMeter::new("client_batches", &pair_label, self.channels.client_stream),
+        );
+        tokio::spawn(batch_summaries(
+            ReceiverStream::new(summaries),
+            window,
+            async move |batch| client_channel_tx.send(batch).await.is_ok(),
+        ));
+
+        Ok(with_pair_metadata(
+            Response::new(ReceiverStream::new(client_channel_rx)),
+            subscription_pair.as_ref(),
+        ))
+    }
 
-            // This channel is used between the service producing the Summary and the task that wraps it in a Result
-            let (summary_tx, summary_rx) = mpsc_channel(100);
+    /// Estimate the cost of filling an order against the latest merged book.
+    async fn estimate_slippage(
+        &self,
+        request: Request<SlippageRequest>,
+    ) -> Result<Response<SlippageEstimate>, Status> {
+        let request = request.into_inner();
+        let side = request.side();
 
-            tokio::spawn(handle_subscription_stream(new_subscription, summary_tx));
+        let requested_pair = requested_pair(request.traded_pair)?;
 
-            return Ok(Response::new(ReceiverStream::new(summary_rx)));
+        if !request.amount.is_finite() || request.amount <= 0.0 {
+            return Err(Status::invalid_argument(
+                "This RPC requires a positive amount",
+            ));
         }
 
-        // This is the first time the requested pair has been received
-        // The server needs to request that the service spins up a new aggregator to start providing Summarys
+        let merged_book = self.latest_book(requested_pair).await?;
 
-        let (new_request_tx, new_request_rx) = oneshot_channel();
+        // Walk the prices that would actually be paid or received once fees are taken
+        let mut merged_book = merged_book.as_ref().clone();
+        if request.effective_prices {
+            self.fee_adjustment
+                .adjust(&mut merged_book.asks, &mut merged_book.bids);
+        }
 
-        let _ = self
-            .new_subscriber_notifier
-            .send((requested_pair.clone(), new_request_tx))
-            .await;
+        let levels = match side {
+            Side::Buy => &merged_book.asks,
+            Side::Sell => &merged_book.bids,
+        };
 
-        // Subscribe to the existing Summary channel for the requested traded pair or return the Status for the Err case
-        let summary_receiver = new_request_rx
-            .await
-            .map_err(|recv_err| Status::from_error(recv_err.into()))?;
+        Ok(Response::new(estimate_slippage(levels, request.amount)))
+    }
 
-        // Create a new subscription for the client
-        let new_subscription = summary_receiver.resubscribe();
+    /// Return the latest merged book for a pair at the requested depth, creating its aggregator if necessary.
+    async fn get_depth_snapshot(
+        &self,
+        request: Request<DepthSnapshotRequest>,
+    ) -> Result<Response<Summary>, Status> {
+        let request = request.into_inner();
+        let requested_pair = requested_pair(request.traded_pair)?;
 
-        // Push the new receiver to the HashMap of summary receivers
-        map_lock.insert(requested_pair, summary_receiver);
+        let merged_book = self.latest_book(requested_pair).await?;
 
-        drop(map_lock);
+        // The full book is retained so any depth can be served, 0 asks for all of it
+        let depth = match request.depth {
+            0 => usize::MAX,
+            depth => depth as usize,
+        };
+        let mut summary = merged_book.summary(depth);
+        if request.effective_prices {
+            self.fee_adjustment.adjust_summary(&mut summary);
+        }
 
-        // The receiving side of this channel will be returned to the client as a stream.
-        let (client_channel_tx, client_channel_rx) = mpsc_channel(100);
+        Ok(Response::new(summary))
+    }
 
-        // This task takes the sending side of the summary channel and populates it with Summary events as it receives OrderBooks from the server-side subscription.
-        tokio::spawn(handle_subscription_stream(
-            new_subscription,
+    /// Bucket the latest merged book for a pair into price bands around its mid price.
+    async fn get_depth_histogram(
+        &self,
+        request: Request<DepthHistogramRequest>,
+    ) -> Result<Response<DepthHistogram>, Status> {
+        let request = request.into_inner();
+        let requested_pair = requested_pair(request.traded_pair)?;
+        let (band_width, band_count) = histogram_bands(request.band_width, request.band_count)?;
+
+        let merged_book = self.latest_book(requested_pair).await?;
+
+        Ok(Response::new(depth_histogram(
+            &merged_book,
+            band_width,
+            band_count,
+        )))
+    }
+
+    /// Describe the effective parameters of the subscription for a pair, creating its aggregator if necessary.
+    async fn describe_subscription(
+        &self,
+        request: Request<OrderBookRequest>,
+    ) -> Result<Response<SubscriptionDescription>, Status> {
+        let request = request.into_inner();
+        let depth = requested_depth(request.depth, &self.depth_limits)?;
+        let requested_pair = requested_pair(request.traded_pair)?;
+
+        let handle = self.aggregator_for_pair(requested_pair).await?;
+
+        Ok(Response::new(SubscriptionDescription {
+            depth: depth as u32,
+            ..handle.description
+        }))
+    }
+
+    type ServiceEventsStream = ReceiverStream<Result<ServiceEvent, Status>>;
+
+    /// Stream events raised by the service from the time of subscription.
+    async fn service_events(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ServiceEventsStream>, Status> {
+        let (client_channel_tx, client_channel_rx) = metered_channel(
+            self.channels.client_stream,
+            ChannelMeter::new("service_events_stream", "", self.channels.client_stream),
+        );
+
+        tokio::spawn(forward_events(
+            self.event_bus.subscribe(),
+            client_channel_tx,
+            ChannelMeter::new("service_events", "", self.channels.service_events),
+        ));
+
+        Ok(Response::new(ReceiverStream::new(client_channel_rx)))
+    }
+
+    type WatchExchangeStatusStream = ReceiverStream<Result<ConnectorStatus, Status>>;
+
+    /// Stream connection events from every exchange connector from the time of subscription.
+    async fn watch_exchange_status(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::WatchExchangeStatusStream>, Status> {
+        let (client_channel_tx, client_channel_rx) = metered_channel(
+            self.channels.client_stream,
+            ChannelMeter::new("connector_status_stream", "", self.channels.client_stream),
+        );
+
+        tokio::spawn(forward_events(
+            self.status_bus.subscribe(),
+            client_channel_tx,
+            ChannelMeter::new("connector_status", "", self.channels.connector_status),
+        ));
+
+        Ok(Response::new(ReceiverStream::new(client_channel_rx)))
+    }
+
+    async fn list_supported_pairs(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<SupportedPairs>, Status> {
+        Ok(Response::new(self.pair_directory.list().await))
+    }
+
+    async fn get_server_info(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<ServerInfo>, Status> {
+        Ok(Response::new(ServerInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            capabilities: self
+                .capabilities
+                .iter()
+                .map(|capability| *capability as i32)
+                .collect(),
+            exchanges: self
+                .pair_directory
+                .exchanges()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        }))
+    }
+
+    async fn get_api_descriptor(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<ApiDescriptor>, Status> {
+        Ok(Response::new(api_descriptor()))
+    }
+
+    type ManageSubscriptionsStream = ReceiverStream<Result<TaggedSummary, Status>>;
+
+    /// Open, modify and close subscriptions on the fly as the client sends commands, tagging each summary with its subscription.
+    async fn manage_subscriptions(
+        &self,
+        request: Request<Streaming<SubscriptionCommand>>,
+    ) -> Result<Response<Self::ManageSubscriptionsStream>, Status> {
+        // Each subscription counts towards the tenant's quota and is identified as though it were its own stream
+        let caller = Caller {
+            metadata: request.metadata().clone(),
+            tenant: request.extensions().get::<TenantId>().cloned(),
+        };
+        let commands = request.into_inner();
+
+        let (client_channel_tx, client_channel_rx) = metered_channel(
+            self.channels.client_stream,
+            ChannelMeter::new("managed_subscriptions", "", self.channels.client_stream),
+        );
+        tokio::spawn(manage_subscriptions(
+            self.clone(),
+            caller,
+            commands,
             client_channel_tx,
         ));
 
@@ -91,69 +583,879 @@ impl OrderbookAggregator for OrderbookService {
     }
 }
 
+/// The optional features this server supports with `config`, for the GetServerInfo RPC.
+fn capabilities(config: &Config) -> Vec<Capability> {
+    let mut capabilities = vec![
+        Capability::Depth,
+        Capability::EffectivePrices,
+        Capability::Heartbeats,
+        Capability::BatchedSummaries,
+        Capability::DepthSnapshots,
+        Capability::Notional,
+        Capability::ManagedSubscriptions,
+        Capability::Filters,
+        Capability::DepthHistograms,
+        Capability::MergeStrategies,
+        Capability::AmountDenominations,
+        Capability::ApiDescriptors,
+    ];
+    if config.integrity {
+        capabilities.push(Capability::Integrity);
+    }
+    if config
+        .transforms
+        .iter()
+        .any(|transform| matches!(transform.kind, TransformKind::Consolidation { .. }))
+    {
+        capabilities.push(Capability::Consolidation);
+    }
+    capabilities
+}
+
+/// Where the gRPC server accepts connections from.
+pub(crate) enum Transport {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+    /// Connections made from within the process, see [start_in_process](crate::start_in_process)
+    InProcess(Receiver<DuplexStream>),
+}
+
+impl Transport {
+    /// The Unix socket when one is configured, otherwise the TCP port.
+    pub(crate) fn from_config(config: &Config) -> Self {
+        match &config.unix_socket {
+            #[cfg(unix)]
+            Some(socket_path) => Transport::Unix(socket_path.clone()),
+            _ => Transport::Tcp(SocketAddr::from(([0, 0, 0, 0], config.port))),
+        }
+    }
+}
+
+/// Report the summary service as healthy for clients multiplexing subscriptions over one connection, or choosing between
+/// servers, until the server starts draining.
+async fn summary_health(drain: Drain) -> HealthServer<impl Health> {
+    let (mut health_reporter, health_svc) = health_reporter();
+    health_reporter
+        .set_serving::<OrderbookAggregatorServer<OrderbookService>>()
+        .await;
+    // Clients probing health then pass over the server, rather than connecting only to be refused
+    tokio::spawn(async move {
+        drain.started().await;
+        health_reporter
+            .set_not_serving::<OrderbookAggregatorServer<OrderbookService>>()
+            .await;
+    });
+    health_svc
+}
+
+// Each is a separate part of the service the server is wired into by run()
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn start_server(
+    transport: Transport,
     new_subscriber_notifier: NewSubscriberNotifier,
-    port: u16,
-) -> Result<(), Error> {
-    let server_addr = SocketAddr::from(([0, 0, 0, 0], port));
+    config: Config,
+    event_bus: EventBus,
+    status_bus: ConnectorStatusBus,
+    pair_directory: PairDirectory,
+    admin_service: AdminService,
+    listening: OneshotSender<()>,
+) -> Result<(), ServerError> {
+    // Requests are authenticated as a tenant when tenants are configured, and limited per client in the demo
+    let tenants = Tenants::new(&config.tenants);
+    let mut tenant_interceptor = TenantInterceptor::new(tenants.clone());
+    let demo_limit = config
+        .demo
+        .then(|| DemoRateLimit::new(DEMO_REQUESTS_PER_WINDOW));
+    // Status is large but is returned straight to the client
+    #[allow(clippy::result_large_err)]
+    let interceptor = move |request: Request<()>| {
+        if let Some(demo_limit) = &demo_limit {
+            demo_limit.check(&request)?;
+        }
+        tenant_interceptor.call(request)
+    };
 
+    let warm_up_pairs = config.warm_up_pairs();
     let order_book = OrderbookService {
         new_subscriber_notifier,
-        summary_receivers: Mutex::new(HashMap::new()),
+        aggregators: Arc::new(Mutex::new(HashMap::new())),
+        heartbeat_interval: config.heartbeat_interval(),
+        depth_limits: config.depth,
+        integrity: config.integrity,
+        fee_adjustment: Arc::new(FeeAdjustment::new(&config.taker_fees)),
+        governor: SubscriptionGovernor::new(tenants.clone()),
+        subscribers: admin_service.subscribers.clone(),
+        capabilities: capabilities(&config),
+        drain: admin_service.drain.clone(),
+        channels: config.channels.clone(),
+        event_bus,
+        status_bus,
+        pair_directory: Arc::new(pair_directory),
+    };
+
+    // The request handler creates aggregators for the warm-up pairs before any requests are accepted
+    for pair in warm_up_pairs {
+        match order_book.aggregator_for_pair(pair.clone()).await {
+            Ok(_) => info!("Warmed up aggregator for {pair}"),
+            Err(status) => warn!("Unable to warm up aggregator for {pair}: {status}"),
+        }
+    }
+
+    let svc = InterceptedService::new(
+        SharedEncodingRoute {
+            inner: OrderbookAggregatorServer::new(order_book.clone()),
+            service: order_book,
+        },
+        interceptor,
+    );
+
+    let health_svc = summary_health(admin_service.drain.clone()).await;
+
+    // In-flight requests are finished once drained, any streams still open are ended by run()
+    let drain = admin_service.drain.clone();
+    let drained = async move { drain.finished().await };
+
+    // Every RPC is counted in the metrics, and a sample of them logged
+    let router = Server::builder()
+        .layer(RequestLogLayer::new(config.request_log.sample_rate))
+        .add_service(health_svc)
+        .add_service(svc);
+
+    let admin = serve_admin(admin_service, &config);
+
+    let public = async move {
+        match transport {
+            Transport::Tcp(server_addr) => {
+                info!("Starting orderbook service on {server_addr}...");
+                // Bound here rather than by the router so readiness can be reported once connections are accepted
+                let listener = TcpListener::bind(server_addr).await.map_err(|source| {
+                    ServerError::BindPort {
+                        addr: server_addr,
+                        source,
+                    }
+                })?;
+                let _ = listening.send(());
+
+                router
+                    .serve_with_incoming_shutdown(TcpListenerStream::new(listener), drained)
+                    .await
+            }
+            #[cfg(unix)]
+            Transport::Unix(socket_path) => {
+                info!(
+                    "Starting orderbook service on Unix socket {}...",
+                    socket_path.display()
+                );
+                remove_stale_socket(&socket_path)?;
+                let listener =
+                    UnixListener::bind(&socket_path).map_err(|source| ServerError::Bind {
+                        path: socket_path.clone(),
+                        source,
+                    })?;
+                let _ = listening.send(());
+
+                router
+                    .serve_with_incoming_shutdown(UnixListenerStream::new(listener), drained)
+                    .await
+            }
+            Transport::InProcess(connections) => {
+                info!("Starting orderbook service in process...");
+                let _ = listening.send(());
+                router
+                    .serve_with_incoming_shutdown(
+                        ReceiverStream::new(connections).map(Ok::<_, io::Error>),
+                        drained,
+                    )
+                    .await
+            }
+        }
+        .map_err(ServerError::from)
     };
 
-    let svc = OrderbookAggregatorServer::new(order_book);
+    tokio::try_join!(public, admin).map(|_| ())
+}
+
+/// Serve the admin service on its own listener, only this host by default, until drained. It isn't served without an
+/// admin port, nor in the demo as the demo is read-only.
+async fn serve_admin(admin_service: AdminService, config: &Config) -> Result<(), ServerError> {
+    let Some(port) = config.admin.port.filter(|_| !config.demo) else {
+        return Ok(());
+    };
+    let addr = SocketAddr::new(config.admin.bind_address, port);
 
+    info!("Serving the admin service on {addr}...");
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|source| ServerError::BindPort { addr, source })?;
+
+    let drain = admin_service.drain.clone();
     Server::builder()
-        .add_service(svc)
-        .serve(server_addr)
+        .layer(RequestLogLayer::new(config.request_log.sample_rate))
+        .add_service(OrderbookAdminServer::new(admin_service))
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async move {
+            drain.finished().await
+        })
         .await
-        .context("gRPC server shutdown")
+        .map_err(ServerError::from)
+}
+
+/// Remove a socket left behind by a previous run, which would otherwise prevent binding. Anything else at the path is
+/// left alone and refused.
+#[cfg(unix)]
+fn remove_stale_socket(socket_path: &Path) -> Result<(), ServerError> {
+    let stale = |source| ServerError::StaleSocket {
+        path: socket_path.to_path_buf(),
+        source,
+    };
+    match fs::symlink_metadata(socket_path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            fs::remove_file(socket_path).map_err(stale)
+        }
+        Ok(_) => Err(ServerError::NotASocket {
+            path: socket_path.to_path_buf(),
+        }),
+        Err(source) if source.kind() == ErrorKind::NotFound => Ok(()),
+        Err(source) => Err(stale(source)),
+    }
+}
+
+/// Serves BookSummary with [EncodedSummaryCodec], so subscriptions share each summary's encoding, passing every other
+/// route to the generated `inner` server.
+#[derive(Clone)]
+struct SharedEncodingRoute<S> {
+    inner: S,
+    service: OrderbookService,
+}
+
+impl<S: NamedService> NamedService for SharedEncodingRoute<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, B> Service<http::Request<B>> for SharedEncodingRoute<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>, Error = Infallible>,
+    S::Future: Send + 'static,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        if request.uri().path() != BOOK_SUMMARY_PATH {
+            return Box::pin(self.inner.call(request));
+        }
+        let service = self.service.clone();
+        Box::pin(async move {
+            let mut grpc = Grpc::new(EncodedSummaryCodec);
+            Ok(grpc
+                .server_streaming(EncodedBookSummary(service), request)
+                .await)
+        })
+    }
+}
+
+/// BookSummary sending [EncodedSummary]s, for [SharedEncodingRoute].
+struct EncodedBookSummary(OrderbookService);
+
+impl ServerStreamingService<OrderBookRequest> for EncodedBookSummary {
+    type Response = EncodedSummary;
+    type ResponseStream = ReceiverStream<Result<EncodedSummary, Status>>;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<OrderBookRequest>) -> Self::Future {
+        let service = self.0.clone();
+        Box::pin(async move { service.book_summary_stream(request).await })
+    }
+}
+
+/// Name the subscription's pair in the response metadata, for the request log.
+fn with_pair_metadata<T>(mut response: Response<T>, pair: Option<&TradedPair>) -> Response<T> {
+    if let Some(pair) = pair.and_then(|pair| pair.to_string().parse().ok()) {
+        response.metadata_mut().insert(TRADED_PAIR_METADATA, pair);
+    }
+    response
+}
+
+/// The pair a request is for, in its canonical form so that e.g. "eth"-"btc" shares the aggregator for "ETH"-"BTC".
+// Status is large but is returned straight to the client
+#[allow(clippy::result_large_err)]
+fn requested_pair(traded_pair: Option<TradedPair>) -> Result<TradedPair, Status> {
+    traded_pair
+        .map(|traded_pair| traded_pair.canonical())
+        .ok_or_else(|| Status::invalid_argument("This RPC requires traded_pair to be provided"))
 }
 
-async fn handle_subscription_stream(
+/// The depth a subscription requested, or the configured default if it didn't request one.
+///
+/// An unset depth can't be told apart from 0, so 0 is taken as the default rather than rejected.
+// Status is large but is returned straight to the client
+#[allow(clippy::result_large_err)]
+fn requested_depth(depth: u32, limits: &DepthConfig) -> Result<usize, Status> {
+    match depth as usize {
+        0 => Ok(limits.default),
+        depth if depth <= limits.max => Ok(depth),
+        _ => Err(Status::invalid_argument(format!(
+            "The requested depth can be at most {}",
+            limits.max
+        ))),
+    }
+}
+
+/// The filter a subscription requested, if any.
+// Status is large but is returned straight to the client
+#[allow(clippy::result_large_err)]
+fn requested_filter(filter: &str) -> Result<Option<SummaryFilter>, Status> {
+    if filter.trim().is_empty() {
+        return Ok(None);
+    }
+    filter
+        .parse()
+        .map(Some)
+        .map_err(|err| Status::invalid_argument(format!("Invalid filter: {err}")))
+}
+
+/// The levels reserved for each exchange when a subscription asked for the venue fair merge strategy.
+// Status is large but is returned straight to the client
+#[allow(clippy::result_large_err)]
+fn requested_venue_fairness(
+    request: &OrderBookRequest,
+    depth: usize,
+) -> Result<Option<usize>, Status> {
+    match request.merge_strategy() {
+        MergeStrategy::BestPrice => Ok(None),
+        MergeStrategy::VenueFair => match request.min_levels_per_venue as usize {
+            0 => Ok(Some(1)),
+            min_levels if min_levels <= depth => Ok(Some(min_levels)),
+            _ => Err(Status::invalid_argument(
+                "min_levels_per_venue can be at most the requested depth",
+            )),
+        },
+    }
+}
+
+/// The band width and count a histogram requested, or the defaults where it didn't request them.
+// Status is large but is returned straight to the client
+#[allow(clippy::result_large_err)]
+fn histogram_bands(band_width: f64, band_count: u32) -> Result<(f64, usize), Status> {
+    let band_width = match band_width {
+        0.0 => DEFAULT_BAND_WIDTH,
+        width if width.is_finite() && width > 0.0 => width,
+        _ => {
+            return Err(Status::invalid_argument(
+                "The requested band width must be positive",
+            ))
+        }
+    };
+    let band_count = match band_count {
+        0 => DEFAULT_BAND_COUNT,
+        count if count <= MAX_BAND_COUNT => count,
+        _ => {
+            return Err(Status::invalid_argument(format!(
+                "The requested band count can be at most {MAX_BAND_COUNT}"
+            )))
+        }
+    };
+    Ok((band_width, band_count as usize))
+}
+
+/// The window a batched subscription requested, or the default if it didn't request one.
+// Status is large but is returned straight to the client
+#[allow(clippy::result_large_err)]
+fn batch_window(window_secs: u32) -> Result<Duration, Status> {
+    match window_secs {
+        0 => Ok(Duration::from_secs(DEFAULT_BATCH_WINDOW_SECS as u64)),
+        secs if secs <= MAX_BATCH_WINDOW_SECS => Ok(Duration::from_secs(secs as u64)),
+        _ => Err(Status::invalid_argument(format!(
+            "The requested window can be at most {MAX_BATCH_WINDOW_SECS}s"
+        ))),
+    }
+}
+
+/// A subscription opened over ManageSubscriptions, its summaries stop being forwarded once it's dropped.
+struct ManagedSubscription {
+    request: OrderBookRequest,
+    forwarder: JoinHandle<()>,
+}
+
+impl Drop for ManagedSubscription {
+    fn drop(&mut self) {
+        // Dropping the forwarder's receiver ends the underlying subscription, releasing its depth request and permit
+        self.forwarder.abort();
+    }
+}
+
+/// Who opened a ManageSubscriptions stream, applied to each subscription it opens.
+struct Caller {
+    metadata: MetadataMap,
+    tenant: Option<TenantId>,
+}
+
+impl Caller {
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        *request.metadata_mut() = self.metadata.clone();
+        if let Some(tenant) = &self.tenant {
+            request.extensions_mut().insert(tenant.clone());
+        }
+        request
+    }
+}
+
+/// Apply the client's commands until it stops sending them, reporting any which fail as errors tagged with the command's subscription.
+async fn manage_subscriptions(
+    service: OrderbookService,
+    caller: Caller,
+    mut commands: impl Stream<Item = Result<SubscriptionCommand, Status>> + Unpin,
+    tx: MeteredSender<Result<TaggedSummary, Status>>,
+) {
+    let mut subscriptions = HashMap::new();
+
+    while let Some(Ok(command)) = commands.next().await {
+        let subscription_id = command.subscription_id;
+        let applied = match command.command {
+            Some(Command::Subscribe(subscribe)) => match subscribe.request {
+                _ if subscriptions.contains_key(&subscription_id) => Err(Status::already_exists(
+                    format!("Subscription {subscription_id} is already open"),
+                )),
+                Some(request) => {
+                    let throttle = Duration::from_millis(subscribe.throttle_millis as u64);
+                    service
+                        .open_managed(&subscription_id, request, throttle, &caller, &tx)
+                        .await
+                        .map(|subscription| {
+                            subscriptions.insert(subscription_id.clone(), subscription);
+                        })
+                }
+                None => Err(Status::invalid_argument(
+                    "Subscribe requires request to be provided",
+                )),
+            },
+            Some(Command::Unsubscribe(_)) => subscriptions
+                .remove(&subscription_id)
+                .map(drop)
+                .ok_or_else(|| unknown_subscription(&subscription_id)),
+            Some(Command::Modify(modify)) => match subscriptions.remove(&subscription_id) {
+                Some(existing) => {
+                    let request = OrderBookRequest {
+                        depth: modify.depth,
+                        ..existing.request.clone()
+                    };
+                    // The existing subscription is closed first so that it doesn't count against the tenant's quota
+                    drop(existing);
+                    let throttle = Duration::from_millis(modify.throttle_millis as u64);
+                    service
+                        .open_managed(&subscription_id, request, throttle, &caller, &tx)
+                        .await
+                        .map(|subscription| {
+                            subscriptions.insert(subscription_id.clone(), subscription);
+                        })
+                }
+                None => Err(unknown_subscription(&subscription_id)),
+            },
+            None => Err(Status::invalid_argument("A command must be provided")),
+        };
+
+        if let Err(status) = applied {
+            if tx
+                .send(Ok(TaggedSummary::error(subscription_id, &status)))
+                .await
+                .is_err()
+            {
+                // The client has gone away, dropping its subscriptions
+                return;
+            }
+        }
+    }
+}
+
+fn unknown_subscription(subscription_id: &str) -> Status {
+    Status::not_found(format!("Subscription {subscription_id} isn't open"))
+}
+
+/// Forward a managed subscription's summaries tagged with its id, sending at most one every `throttle`.
+///
+/// Summaries which arrive within `throttle` of the last one sent are held back, replaced by any newer summary.
+async fn forward_tagged(
+    subscription_id: String,
+    mut summaries: Receiver<Result<Summary, Status>>,
+    throttle: Duration,
+    tx: MeteredSender<Result<TaggedSummary, Status>>,
+) {
+    let mut pending = None;
+    let mut next_send = Instant::now();
+
+    loop {
+        select! {
+            received = summaries.recv() => match received {
+                Some(Ok(summary)) => pending = Some(summary),
+                Some(Err(status)) => {
+                    let _ = tx.send(Ok(TaggedSummary::error(subscription_id, &status))).await;
+                    return;
+                }
+                None => return,
+            },
+            _ = sleep_until(next_send), if pending.is_some() => {}
+        }
+
+        if Instant::now() < next_send {
+            continue;
+        }
+        if let Some(summary) = pending.take() {
+            let tagged = TaggedSummary {
+                subscription_id: subscription_id.clone(),
+                payload: Some(Payload::Summary(summary)),
+            };
+            if tx.send(Ok(tagged)).await.is_err() {
+                return;
+            }
+            next_send = Instant::now() + throttle;
+        }
+    }
+}
+
+/// How summaries are tailored for a single subscription.
+struct SubscriptionSettings {
+    heartbeat_interval: Duration,
+    /// Levels of each side to send
+    depth: usize,
+    /// Applied when the subscription asked for effective prices
+    fee_adjustment: Option<Arc<FeeAdjustment>>,
+    /// Summaries which don't match aren't sent
+    filter: Option<SummaryFilter>,
+    /// Levels reserved for each exchange when the subscription asked for the venue fair merge strategy
+    venue_fair: Option<usize>,
+    /// Level amounts are sent in units of the second token
+    quote_amounts: bool,
+    /// Seal the summaries sent into a hash chain
+    integrity: bool,
+    /// Ends the subscription, pointing the client elsewhere, once the server starts draining
+    drain: Drain,
+}
+
+/// Forward summaries to a client, sending a heartbeat whenever nothing has been sent for `heartbeat_interval`
+/// so the client can tell a quiet market from a dead connection.
+async fn handle_subscription_stream<T: Outgoing>(
     mut rx: SummaryReceiver,
-    tx: MpscSender<Result<Summary, Status>>,
+    tx: MeteredSender<Result<T, Status>>,
+    summaries_meter: ChannelMeter,
+    settings: SubscriptionSettings,
 ) {
-    while let Ok(summary_res) = rx.recv().await {
+    let SubscriptionSettings {
+        heartbeat_interval,
+        depth,
+        fee_adjustment,
+        filter,
+        venue_fair,
+        quote_amounts,
+        integrity,
+        drain,
+    } = settings;
+
+    let mut chain = integrity.then(SummaryChain::default);
+    // Otherwise every subscription at the same depth is sent the same summary, encoded once between them
+    let tailored = venue_fair.is_some()
+        || fee_adjustment.is_some()
+        || quote_amounts
+        || filter.is_some()
+        || chain.is_some();
+    let mut last_update = Instant::now();
+    let mut last_sent = Instant::now();
+    let draining = drain.started();
+    pin!(draining);
+
+    loop {
+        let received = select! {
+            received = rx.recv() => received,
+            _ = &mut draining => {
+                let _ = tx.send(Err(drain.status())).await;
+                return;
+            }
+            _ = sleep_until(last_sent + heartbeat_interval) => {
+                let heartbeat = Summary::heartbeat(last_update.elapsed());
+                if tx.send(Ok(T::tailored(heartbeat))).await.is_err() {
+                    // The client has gone away
+                    return;
+                }
+                last_sent = Instant::now();
+                continue;
+            }
+        };
+
+        let summary_res = match received {
+            Ok(summary_res) => summary_res,
+            // This subscription fell behind the aggregator, the oldest summaries were dropped
+            Err(BroadcastRecvError::Lagged(missed)) => {
+                summaries_meter.record_dropped(missed);
+                continue;
+            }
+            Err(BroadcastRecvError::Closed) => break,
+        };
+
+        // Include the summary just received in the queue length
+        summaries_meter.record_len(rx.len() + 1);
+
         match summary_res {
-            Ok(summary) => {
-                let _ = tx.send(Ok(summary)).await;
+            Ok(tick) => {
+                // Link delivery to the client back to the merge which produced the summary
+                let forward_span = info_span!("forward_summary");
+                forward_span.follows_from(&tick.span);
+
+                let outgoing = forward_span.in_scope(|| {
+                    if !tailored {
+                        return Some(T::shared(&tick, depth));
+                    }
+                    let mut summary = tick.summary.clone();
+                    let book = &tick.book;
+                    // Chosen from the full book, as an exchange's reserved levels may be beyond the summary's depth
+                    if let Some(min_per_venue) = venue_fair {
+                        summary.asks = venue_fair_levels(&book.asks, depth, min_per_venue);
+                        summary.bids = venue_fair_levels(&book.bids, depth, min_per_venue);
+                        summary
+                            .metadata
+                            .get_or_insert_with(Default::default)
+                            .merge_strategy = MergeStrategy::VenueFair as i32;
+                    }
+                    if let Some(fee_adjustment) = &fee_adjustment {
+                        fee_adjustment.adjust_summary(&mut summary);
+                    }
+                    // Summaries are produced at the deepest requested depth
+                    summary.asks.truncate(depth);
+                    summary.bids.truncate(depth);
+                    summary.update_totals();
+                    if quote_amounts {
+                        summary.denominate_in_quote();
+                    }
+                    // Filtered on what would be sent, before sealing so the chain only covers sent summaries
+                    if filter
+                        .as_ref()
+                        .is_some_and(|filter| !filter.matches(&summary))
+                    {
+                        return None;
+                    }
+                    // Sealed last, the digest covers exactly what is sent
+                    if let Some(chain) = &mut chain {
+                        chain.seal(&mut summary);
+                    }
+                    Some(T::tailored(summary))
+                });
+                // Nothing was sent, so a heartbeat is still due if nothing matches for a while
+                let Some(outgoing) = outgoing else {
+                    continue;
+                };
+                last_update = Instant::now();
+                if tx
+                    .send(Ok(outgoing))
+                    .instrument(forward_span)
+                    .await
+                    .is_err()
+                {
+                    // The client has gone away, while the aggregator may keep broadcasting indefinitely
+                    return;
+                }
             }
             Err(err) => {
-                let _ = tx.send(Err(Status::internal(err.to_string()))).await;
+                if tx.send(Err(Status::from(err.as_ref()))).await.is_err() {
+                    return;
+                }
             }
         }
+        last_sent = Instant::now();
     }
+    // The aggregator has stopped
     let _ = tx
-        .send(Err(Status::unavailable(
+        .send(Err(CloseReason::UpstreamUnavailable.status(
+            Code::Unavailable,
             "The service failed to provide a response",
         )))
         .await;
 }
 
+/// Forward events to a client until either the client or the bus, e.g. the [EventBus], hangs up.
+async fn forward_events<T: Clone>(
+    mut rx: BroadcastReceiver<T>,
+    tx: MeteredSender<Result<T, Status>>,
+    events_meter: ChannelMeter,
+) {
+    loop {
+        match rx.recv().await {
+            Ok(event) => {
+                events_meter.record_len(rx.len() + 1);
+                if tx.send(Ok(event)).await.is_err() {
+                    break;
+                }
+            }
+            Err(BroadcastRecvError::Lagged(missed)) => events_meter.record_dropped(missed),
+            Err(BroadcastRecvError::Closed) => break,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use tokio::sync::broadcast::channel as broadcast_channel;
-    use tonic::Code;
+    use std::collections::HashSet;
+
+    use tokio::sync::{broadcast::channel as broadcast_channel, mpsc::Receiver, watch};
+    use tonic::{transport::Channel, Code};
+    use tonic_health::proto::{
+        health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+    };
+    use tracing::Span;
+
+    use order_book_service_types::proto::Level;
+
+    use crate::{
+        aggregator::{OrderbookAggregator as Aggregator, SUMMARY_DEPTH},
+        clock::SystemClock,
+        config::DrainConfig,
+        latency::ExchangeLatencies,
+    };
 
     use super::*;
 
+    fn test_settings() -> SubscriptionSettings {
+        SubscriptionSettings {
+            heartbeat_interval: Duration::from_secs(60),
+            depth: SUMMARY_DEPTH,
+            fee_adjustment: None,
+            filter: None,
+            venue_fair: None,
+            quote_amounts: false,
+            integrity: false,
+            drain: Drain::new(&DrainConfig::default()),
+        }
+    }
+
+    fn test_meter() -> ChannelMeter {
+        ChannelMeter::new("test_summaries", "", 100)
+    }
+
+    type SummaryResult = Result<Summary, Status>;
+
+    fn test_channel() -> (MeteredSender<SummaryResult>, Receiver<SummaryResult>) {
+        metered_channel(100, ChannelMeter::new("test_client_stream", "", 100))
+    }
+
+    /// A service without any exchanges, new aggregators are refused so only those added by [with_aggregator] are used.
+    fn test_service() -> OrderbookService {
+        let config = Config::default();
+        let tenants = Tenants::new(&config.tenants);
+        OrderbookService {
+            new_subscriber_notifier: metered_channel(
+                1,
+                ChannelMeter::new("test_new_subscriber", "", 1),
+            )
+            .0,
+            aggregators: Default::default(),
+            channels: config.channels.clone(),
+            event_bus: EventBus::new(10),
+            status_bus: ConnectorStatusBus::new(10),
+            pair_directory: Arc::new(PairDirectory::new(&[])),
+            heartbeat_interval: config.heartbeat_interval(),
+            depth_limits: config.depth,
+            integrity: false,
+            fee_adjustment: Arc::new(FeeAdjustment::new(&config.taker_fees)),
+            governor: SubscriptionGovernor::new(tenants),
+            subscribers: Subscribers::default(),
+            capabilities: capabilities(&config),
+            drain: Drain::new(&DrainConfig::default()),
+        }
+    }
+
+    /// A service whose requests for new aggregators are left to the test to answer.
+    fn service_with_requests() -> (
+        OrderbookService,
+        Receiver<(TradedPair, OneshotSender<AggregatorHandle>)>,
+    ) {
+        let (new_subscriber_notifier, requests) =
+            metered_channel(10, ChannelMeter::new("test_new_subscriber", "", 10));
+        let service = OrderbookService {
+            new_subscriber_notifier,
+            ..test_service()
+        };
+        (service, requests)
+    }
+
+    fn test_aggregator(pair: &TradedPair) -> Aggregator {
+        Aggregator::new(
+            &[],
+            pair.clone(),
+            watch::channel(HashSet::new()).1,
+            EventBus::new(10),
+            ExchangeLatencies::default(),
+            SystemClock::shared(),
+            &Config::default(),
+        )
+    }
+
+    /// An aggregator for `pair` which has merged a book, serving requests to `service` until it's dropped.
+    async fn with_aggregator(service: &OrderbookService, pair: &TradedPair) -> Aggregator {
+        let aggregator = test_aggregator(pair);
+        aggregator.publish_relayed(Summary {
+            bids: vec![Level::new("Binance", 99.0, 1.0)],
+            asks: vec![Level::new("Kraken", 101.0, 1.0)],
+            ..Default::default()
+        });
+        service.aggregators.lock().await.insert(
+            pair.clone(),
+            Arc::new(OnceCell::new_with(Some(aggregator.subscribe()))),
+        );
+        aggregator
+    }
+
+    #[test]
+    fn should_limit_requested_depth_to_the_configured_max() {
+        let limits = DepthConfig {
+            default: 5,
+            max: 20,
+        };
+
+        assert_eq!(requested_depth(0, &limits).unwrap(), 5);
+        assert_eq!(requested_depth(20, &limits).unwrap(), 20);
+        assert_eq!(
+            requested_depth(10_000, &limits).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn should_key_aggregators_by_the_canonical_pair() {
+        assert_eq!(
+            requested_pair(Some(TradedPair::new("eth", "Btc"))).unwrap(),
+            TradedPair::new("ETH", "BTC")
+        );
+        assert_eq!(
+            requested_pair(None).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+    }
+
     #[tokio::test]
     async fn should_return_summary() {
         let (summary_tx, summary_rx) = broadcast_channel(100);
-        let (fn_output_tx, mut fn_output_rx) = mpsc_channel(100);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
 
-        let _ = summary_tx.send(Ok(Summary {
-            spread: 1.0,
-            bids: vec![],
-            asks: vec![],
-        }));
+        let _ = summary_tx.send(Ok(Arc::new(SummaryTick::new(
+            Summary {
+                spread: 1.0,
+                bids: vec![],
+                asks: vec![],
+                ..Default::default()
+            },
+            Arc::default(),
+            Span::none(),
+        ))));
 
         // The sender needs to be dropped otherwise the handler will wait for more messages to be sent
         drop(summary_tx);
 
-        handle_subscription_stream(summary_rx, fn_output_tx).await;
+        handle_subscription_stream(summary_rx, fn_output_tx, test_meter(), test_settings()).await;
 
         let summary = fn_output_rx
             .recv()
@@ -165,18 +1467,270 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn should_return_status_due_to_internal_error() {
+    async fn should_end_subscriptions_pointing_at_the_alternative_server_when_draining() {
+        let (_summary_tx, summary_rx) = broadcast_channel(100);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
+        let drain = Drain::new(&DrainConfig {
+            alternative_address: Some("http://standby:3030".to_string()),
+            deadline_secs: 1,
+        });
+        let settings = SubscriptionSettings {
+            drain: drain.clone(),
+            ..test_settings()
+        };
+
+        let handler = tokio::spawn(handle_subscription_stream(
+            summary_rx,
+            fn_output_tx,
+            test_meter(),
+            settings,
+        ));
+        drain.drain(None, &Subscribers::default()).await.unwrap();
+        handler.await.unwrap();
+
+        let status = fn_output_rx.recv().await.unwrap().unwrap_err();
+        assert_eq!(CloseReason::of(&status), CloseReason::ServerShutdown);
+        assert_eq!(
+            CloseReason::alternative_address(&status).as_deref(),
+            Some("http://standby:3030")
+        );
+    }
+
+    #[tokio::test]
+    async fn should_report_not_serving_once_draining() {
+        let drain = Drain::new(&DrainConfig::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            Server::builder()
+                .add_service(summary_health(drain.clone()).await)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let channel = Channel::from_shared(address)
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = HealthClient::new(channel);
+        let mut status = async || {
+            client
+                .check(HealthCheckRequest {
+                    service: "orderbook.OrderbookAggregator".to_string(),
+                })
+                .await
+                .unwrap()
+                .into_inner()
+                .status()
+        };
+        assert_eq!(status().await, ServingStatus::Serving);
+
+        // Held open so the drain doesn't finish
+        let subscribers = Subscribers::default();
+        let _registration =
+            subscribers.register(TradedPair::new("ETH", "BTC"), 10, Default::default(), None);
+        tokio::spawn({
+            let drain = drain.clone();
+            async move { drain.drain(None, &subscribers).await }
+        });
+        drain.started().await;
+        let reported = timeout(Duration::from_secs(1), async {
+            while status().await != ServingStatus::NotServing {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await;
+        assert!(reported.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_recompute_notional_for_the_requested_depth() {
         let (summary_tx, summary_rx) = broadcast_channel(100);
-        let (fn_output_tx, mut fn_output_rx) = mpsc_channel(100);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
 
-        let _ = summary_tx.send(Err(Arc::new(Error::msg(
-            "Internal error, e.g. aggregator couldn't connect",
+        let mut summary = Summary {
+            spread: 1.0,
+            asks: vec![
+                Level::new("Binance", 11.0, 1.0),
+                Level::new("Binance", 12.0, 2.0),
+            ],
+            bids: vec![
+                Level::new("Binance", 10.0, 1.0),
+                Level::new("Binance", 9.0, 2.0),
+            ],
+            ..Default::default()
+        };
+        summary.update_totals();
+        assert_eq!(summary.ask_notional, 35.0);
+        let _ = summary_tx.send(Ok(Arc::new(SummaryTick::new(
+            summary,
+            Arc::default(),
+            Span::none(),
+        ))));
+        drop(summary_tx);
+
+        let settings = SubscriptionSettings {
+            depth: 1,
+            ..test_settings()
+        };
+        handle_subscription_stream(summary_rx, fn_output_tx, test_meter(), settings).await;
+
+        let summary = fn_output_rx.recv().await.unwrap().unwrap();
+        assert_eq!(summary.ask_notional, 11.0);
+        assert_eq!(summary.bid_notional, 10.0);
+    }
+
+    #[tokio::test]
+    async fn should_cut_a_shared_summary_down_to_each_subscriptions_depth() {
+        let (summary_tx, _) = broadcast_channel(100);
+        let mut subscriptions = Vec::new();
+        for depth in [1, 2] {
+            let (fn_output_tx, fn_output_rx) = test_channel();
+            let settings = SubscriptionSettings {
+                depth,
+                ..test_settings()
+            };
+            tokio::spawn(handle_subscription_stream(
+                summary_tx.subscribe(),
+                fn_output_tx,
+                test_meter(),
+                settings,
+            ));
+            subscriptions.push(fn_output_rx);
+        }
+
+        // Merged once at the deepest depth requested
+        let book = MergedBook {
+            asks: vec![
+                Level::new("Binance", 11.0, 1.0),
+                Level::new("Kraken", 12.0, 2.0),
+            ],
+            bids: vec![
+                Level::new("Kraken", 10.0, 1.0),
+                Level::new("Binance", 9.0, 2.0),
+            ],
+        };
+        let _ = summary_tx.send(Ok(Arc::new(SummaryTick::new(
+            book.summary(2),
+            Arc::new(book),
+            Span::none(),
+        ))));
+        drop(summary_tx);
+
+        for (depth, subscription) in [1, 2].into_iter().zip(subscriptions.iter_mut()) {
+            let summary = subscription.recv().await.unwrap().unwrap();
+            assert_eq!((summary.ask_depth, summary.bid_depth), (depth, depth));
+            assert_eq!(summary.spread, 1.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_denominate_amounts_in_the_quote_token() {
+        let (summary_tx, summary_rx) = broadcast_channel(100);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
+
+        let summary = Summary {
+            spread: 1.0,
+            asks: vec![Level::new("Binance", 11.0, 2.0)],
+            bids: vec![Level::new("Binance", 10.0, 0.5)],
+            ..Default::default()
+        };
+        let _ = summary_tx.send(Ok(Arc::new(SummaryTick::new(
+            summary,
+            Arc::default(),
+            Span::none(),
+        ))));
+        drop(summary_tx);
+
+        let settings = SubscriptionSettings {
+            quote_amounts: true,
+            ..test_settings()
+        };
+        handle_subscription_stream(summary_rx, fn_output_tx, test_meter(), settings).await;
+
+        let summary = fn_output_rx.recv().await.unwrap().unwrap();
+        assert_eq!(summary.asks[0].amount, 22.0);
+        assert_eq!(summary.bids[0].amount, 5.0);
+        // Prices and the notional are unchanged
+        assert_eq!(summary.asks[0].price, 11.0);
+        assert_eq!(summary.ask_notional, 22.0);
+        assert_eq!(
+            summary.metadata.unwrap().amount_denomination(),
+            AmountDenomination::Quote
+        );
+    }
+
+    #[tokio::test]
+    async fn should_include_every_venue_with_the_venue_fair_strategy() {
+        let (summary_tx, summary_rx) = broadcast_channel(100);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
+
+        let book = MergedBook {
+            asks: vec![
+                Level::new("Binance", 11.0, 1.0),
+                Level::new("Binance", 12.0, 1.0),
+                Level::new("Bitstamp", 13.0, 1.0),
+            ],
+            bids: vec![
+                Level::new("Binance", 10.0, 1.0),
+                Level::new("Bitstamp", 9.0, 1.0),
+                Level::new("Bitstamp", 8.0, 1.0),
+            ],
+        };
+        let summary = book.summary(2);
+        let _ = summary_tx.send(Ok(Arc::new(SummaryTick::new(
+            summary,
+            Arc::new(book),
+            Span::none(),
+        ))));
+        drop(summary_tx);
+
+        let request = OrderBookRequest {
+            depth: 2,
+            merge_strategy: MergeStrategy::VenueFair as i32,
+            ..Default::default()
+        };
+        let settings = SubscriptionSettings {
+            depth: 2,
+            venue_fair: requested_venue_fairness(&request, 2).unwrap(),
+            ..test_settings()
+        };
+        handle_subscription_stream(summary_rx, fn_output_tx, test_meter(), settings).await;
+
+        let summary = fn_output_rx.recv().await.unwrap().unwrap();
+        assert_eq!(
+            summary.asks,
+            vec![
+                Level::new("Binance", 11.0, 1.0),
+                Level::new("Bitstamp", 13.0, 1.0)
+            ]
+        );
+        assert_eq!(summary.bids.len(), 2);
+        assert_eq!(summary.spread, 1.0);
+        assert_eq!(
+            summary.metadata.unwrap().merge_strategy(),
+            MergeStrategy::VenueFair
+        );
+
+        let request = OrderBookRequest {
+            min_levels_per_venue: 3,
+            ..request
+        };
+        assert!(requested_venue_fairness(&request, 2).is_err());
+    }
+
+    #[tokio::test]
+    async fn should_return_status_due_to_aggregator_error() {
+        let (summary_tx, summary_rx) = broadcast_channel(100);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
+
+        let _ = summary_tx.send(Err(Arc::new(AggregatorError::ExchangeDisconnected(
+            TradedPair::new("ETH", "BTC"),
         ))));
 
         // The sender needs to be dropped otherwise the handler will wait for more messages to be sent
         drop(summary_tx);
 
-        handle_subscription_stream(summary_rx, fn_output_tx).await;
+        handle_subscription_stream(summary_rx, fn_output_tx, test_meter(), test_settings()).await;
 
         let status = fn_output_rx
             .recv()
@@ -185,8 +1739,8 @@ mod tests {
             .expect_err("Expected an Err to be returned from the handler.");
 
         let expected_status = Status::new(
-            Code::Internal,
-            "Internal error, e.g. aggregator couldn't connect",
+            Code::Unavailable,
+            "Exchange disconnected, leaving only one connection - unable to aggregate ETH-BTC",
         );
 
         assert_eq!(status.code(), expected_status.code());
@@ -196,9 +1750,9 @@ mod tests {
     #[tokio::test]
     async fn should_return_status_at_end_of_stream() {
         let (_, empty_rx) = broadcast_channel(100);
-        let (fn_output_tx, mut fn_output_rx) = mpsc_channel(100);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
 
-        handle_subscription_stream(empty_rx, fn_output_tx).await;
+        handle_subscription_stream(empty_rx, fn_output_tx, test_meter(), test_settings()).await;
 
         let status = fn_output_rx
             .recv()
@@ -214,4 +1768,296 @@ mod tests {
         assert_eq!(status.code(), expected_status.code());
         assert_eq!(status.message(), expected_status.message())
     }
+
+    #[tokio::test]
+    async fn should_continue_after_lagging_behind() {
+        let (summary_tx, summary_rx) = broadcast_channel(1);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
+
+        // The broadcast channel only holds one summary so the first will be dropped
+        for spread in [1.0, 2.0] {
+            let _ = summary_tx.send(Ok(Arc::new(SummaryTick::new(
+                Summary {
+                    spread,
+                    ..Default::default()
+                },
+                Arc::default(),
+                Span::none(),
+            ))));
+        }
+        drop(summary_tx);
+
+        handle_subscription_stream(summary_rx, fn_output_tx, test_meter(), test_settings()).await;
+
+        let summary = fn_output_rx
+            .recv()
+            .await
+            .expect("Expected a response from the handler")
+            .expect("Expected an Ok(Summary) to be returned from the handler.");
+
+        assert_eq!(summary.spread, 2.0)
+    }
+
+    #[tokio::test]
+    async fn should_send_heartbeat_when_quiet() {
+        let (summary_tx, summary_rx) = broadcast_channel(100);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
+
+        tokio::spawn(handle_subscription_stream(
+            summary_rx,
+            fn_output_tx,
+            test_meter(),
+            SubscriptionSettings {
+                heartbeat_interval: Duration::from_millis(20),
+                ..test_settings()
+            },
+        ));
+
+        let heartbeat = fn_output_rx
+            .recv()
+            .await
+            .expect("Expected a response from the handler")
+            .expect("Expected an Ok(Summary) to be returned from the handler.");
+
+        assert!(heartbeat.is_heartbeat());
+        assert!(heartbeat.bids.is_empty() && heartbeat.asks.is_empty());
+        drop(summary_tx);
+    }
+
+    #[tokio::test]
+    async fn should_stop_forwarding_once_the_client_has_gone() {
+        let (summary_tx, summary_rx) = broadcast_channel(100);
+        let (fn_output_tx, fn_output_rx) = test_channel();
+        drop(fn_output_rx);
+
+        let forwarding = tokio::spawn(handle_subscription_stream(
+            summary_rx,
+            fn_output_tx,
+            test_meter(),
+            test_settings(),
+        ));
+        let _ = summary_tx.send(Ok(Arc::new(SummaryTick::new(
+            Summary::default(),
+            Arc::default(),
+            Span::none(),
+        ))));
+
+        // The aggregator is still broadcasting, yet the forwarding task ends
+        timeout(Duration::from_secs(1), forwarding)
+            .await
+            .expect("Forwarding should stop")
+            .unwrap();
+        drop(summary_tx);
+    }
+
+    #[tokio::test]
+    async fn should_only_forward_summaries_matching_the_filter() {
+        let (summary_tx, summary_rx) = broadcast_channel(100);
+        let (fn_output_tx, mut fn_output_rx) = test_channel();
+
+        for spread in [0.5, 2.0, 0.1] {
+            let summary = Summary {
+                spread,
+                bids: vec![Level::new("Binance", 1.0, 1.0)],
+                asks: vec![Level::new("Bitstamp", 1.0 + spread, 1.0)],
+                ..Default::default()
+            };
+            let _ = summary_tx.send(Ok(Arc::new(SummaryTick::new(
+                summary,
+                Arc::default(),
+                Span::none(),
+            ))));
+        }
+        drop(summary_tx);
+
+        handle_subscription_stream(
+            summary_rx,
+            fn_output_tx,
+            test_meter(),
+            SubscriptionSettings {
+                filter: requested_filter("spread > 1").unwrap(),
+                ..test_settings()
+            },
+        )
+        .await;
+
+        let summary = fn_output_rx.recv().await.unwrap().unwrap();
+        assert_eq!(summary.spread, 2.0);
+        // Only the end of stream status follows
+        assert!(fn_output_rx.recv().await.unwrap().is_err());
+
+        assert_eq!(
+            requested_filter("spread >").unwrap_err().code(),
+            Code::InvalidArgument
+        );
+        assert!(requested_filter(" ").unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_only_remove_stale_sockets() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("orderbook.sock");
+        assert!(remove_stale_socket(&socket_path).is_ok());
+
+        drop(std::os::unix::net::UnixListener::bind(&socket_path).unwrap());
+        assert!(remove_stale_socket(&socket_path).is_ok());
+        assert!(!socket_path.exists());
+
+        fs::write(&socket_path, "not a socket").unwrap();
+        assert!(matches!(
+            remove_stale_socket(&socket_path),
+            Err(ServerError::NotASocket { .. })
+        ));
+        assert!(socket_path.exists());
+    }
+
+    #[tokio::test]
+    async fn should_refuse_slippage_estimates_once_the_aggregator_has_stopped() {
+        let service = test_service();
+        let pair = TradedPair::new("ETH", "BTC");
+        let request = || {
+            Request::new(SlippageRequest {
+                traded_pair: Some(pair.clone()),
+                side: Side::Buy as i32,
+                amount: 0.5,
+                effective_prices: false,
+            })
+        };
+
+        let aggregator = with_aggregator(&service, &pair).await;
+        let estimate = service.estimate_slippage(request()).await.unwrap();
+        assert_eq!(estimate.into_inner().worst_price, 101.0);
+
+        // Its last book is kept, but no longer live
+        drop(aggregator);
+        let status = service.estimate_slippage(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn should_refuse_depth_snapshots_once_the_aggregator_has_stopped() {
+        let service = test_service();
+        let pair = TradedPair::new("ETH", "BTC");
+        let request = || {
+            Request::new(DepthSnapshotRequest {
+                traded_pair: Some(pair.clone()),
+                depth: 0,
+                effective_prices: false,
+            })
+        };
+
+        let aggregator = with_aggregator(&service, &pair).await;
+        let snapshot = service.get_depth_snapshot(request()).await.unwrap();
+        assert_eq!(snapshot.into_inner().bids[0].price, 99.0);
+
+        drop(aggregator);
+        let status = service.get_depth_snapshot(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn should_refuse_depth_histograms_once_the_aggregator_has_stopped() {
+        let service = test_service();
+        let pair = TradedPair::new("ETH", "BTC");
+        let request = || {
+            Request::new(DepthHistogramRequest {
+                traded_pair: Some(pair.clone()),
+                band_width: 0.0,
+                band_count: 0,
+            })
+        };
+
+        let aggregator = with_aggregator(&service, &pair).await;
+        let histogram = service.get_depth_histogram(request()).await.unwrap();
+        assert_eq!(histogram.into_inner().mid_price, 100.0);
+
+        drop(aggregator);
+        let status = service.get_depth_histogram(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn should_create_a_new_aggregator_once_the_last_has_stopped() {
+        let (service, mut requests) = service_with_requests();
+        let pair = TradedPair::new("ETH", "BTC");
+
+        let (retiring, handles) = tokio::join!(
+            async {
+                let (pair, handle_sender) = requests.recv().await.unwrap();
+                let aggregator = test_aggregator(&pair);
+                let _ = handle_sender.send(aggregator.subscribe());
+                aggregator
+            },
+            async {
+                let first = service.aggregator_for_pair(pair.clone()).await.unwrap();
+                let shared = service.aggregator_for_pair(pair.clone()).await.unwrap();
+                (first, shared)
+            }
+        );
+        // Requests while it runs share the one aggregator
+        assert!(requests.try_recv().is_err());
+
+        // e.g. once its exchanges have delisted the pair
+        drop(retiring);
+        handles.0.stopped().await;
+        timeout(Duration::from_secs(1), async {
+            while service.aggregators.lock().await.contains_key(&pair) {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("The stopped aggregator should be forgotten");
+
+        let (relisted, handle) = tokio::join!(
+            async {
+                let (pair, handle_sender) = requests.recv().await.unwrap();
+                let aggregator = test_aggregator(&pair);
+                let _ = handle_sender.send(aggregator.subscribe());
+                aggregator
+            },
+            service.aggregator_for_pair(pair.clone())
+        );
+        let handle = handle.unwrap();
+        assert!(timeout(Duration::from_millis(10), handle.stopped())
+            .await
+            .is_err());
+        drop(relisted);
+    }
+
+    #[tokio::test]
+    async fn should_serve_other_pairs_while_an_aggregator_is_created() {
+        let (service, mut requests) = service_with_requests();
+
+        let creating = tokio::spawn({
+            let service = service.clone();
+            async move {
+                service
+                    .aggregator_for_pair(TradedPair::new("ETH", "BTC"))
+                    .await
+            }
+        });
+        // The request for ETH-BTC is left unanswered
+        let (_, eth_btc_sender) = requests.recv().await.unwrap();
+
+        let ltc_btc = TradedPair::new("LTC", "BTC");
+        let (aggregator, handle) = tokio::join!(
+            async {
+                let (pair, handle_sender) = requests.recv().await.unwrap();
+                let aggregator = test_aggregator(&pair);
+                let _ = handle_sender.send(aggregator.subscribe());
+                aggregator
+            },
+            timeout(
+                Duration::from_secs(1),
+                service.aggregator_for_pair(ltc_btc.clone())
+            )
+        );
+        assert!(handle.expect("Shouldn't wait for ETH-BTC").is_ok());
+        assert!(!creating.is_finished());
+
+        drop(eth_btc_sender);
+        assert!(creating.await.unwrap().is_err());
+        drop(aggregator);
+    }
 }
@@ -0,0 +1,129 @@
+use order_book_service_types::proto::{DepthBand, DepthHistogram, Level};
+
+use crate::aggregator::MergedBook;
+
+/// Sum the book's levels into `band_count` bands of `band_width` (a fraction of the mid price) each side of the mid price.
+///
+/// Levels beyond the last band are left out, as are any on the wrong side of the mid price should the book be crossed.
+pub(crate) fn depth_histogram(
+    book: &MergedBook,
+    band_width: f64,
+    band_count: usize,
+) -> DepthHistogram {
+    let (Some(best_ask), Some(best_bid)) = (book.asks.first(), book.bids.first()) else {
+        return DepthHistogram::default();
+    };
+    let mid_price = (best_ask.price + best_bid.price) / 2.0;
+    let width = mid_price * band_width;
+
+    // Bids are bucketed by how far below the mid price they are, a band includes its lower price. The best levels
+    // come first, so those crossing the mid price are skipped from the start.
+    let bids = bands(
+        book.bids.iter().skip_while(|level| level.price > mid_price),
+        band_count,
+        |index| DepthBand {
+            lower_price: mid_price - width * (index + 1) as f64,
+            upper_price: mid_price - width * index as f64,
+            ..Default::default()
+        },
+        |price| ((mid_price - price) / width).ceil() - 1.0,
+    );
+    let asks = bands(
+        book.asks.iter().skip_while(|level| level.price < mid_price),
+        band_count,
+        |index| DepthBand {
+            lower_price: mid_price + width * index as f64,
+            upper_price: mid_price + width * (index + 1) as f64,
+            ..Default::default()
+        },
+        |price| ((price - mid_price) / width).floor(),
+    );
+
+    DepthHistogram {
+        mid_price,
+        bids,
+        asks,
+    }
+}
+
+fn bands<'a>(
+    levels: impl Iterator<Item = &'a Level>,
+    band_count: usize,
+    band: impl Fn(usize) -> DepthBand,
+    band_index: impl Fn(f64) -> f64,
+) -> Vec<DepthBand> {
+    let mut bands = (0..band_count).map(band).collect::<Vec<_>>();
+    for level in levels {
+        // A level at the mid price falls in the first band
+        let index = band_index(level.price).max(0.0) as usize;
+        let Some(band) = bands.get_mut(index) else {
+            // Levels are ordered away from the mid price, so the rest are beyond the last band too
+            break;
+        };
+        band.amount += level.amount;
+        band.notional += level.amount * level.price;
+    }
+    bands
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::{DepthBand, Level};
+
+    use crate::aggregator::MergedBook;
+
+    use super::depth_histogram;
+
+    #[test]
+    fn should_sum_levels_into_bands_either_side_of_mid() {
+        let book = MergedBook {
+            asks: vec![
+                Level::new("Binance", 100.5, 1.0),
+                Level::new("Bitstamp", 100.9, 2.0),
+                Level::new("Binance", 101.5, 3.0),
+                Level::new("Binance", 150.0, 100.0),
+            ],
+            bids: vec![
+                Level::new("Bitstamp", 99.5, 1.0),
+                Level::new("Binance", 99.0, 4.0),
+                Level::new("Binance", 98.2, 5.0),
+            ],
+        };
+
+        // Bands 1.0 wide either side of a mid price of 100.0
+        let histogram = depth_histogram(&book, 0.01, 3);
+        assert_eq!(histogram.mid_price, 100.0);
+
+        let amounts =
+            |bands: &[DepthBand]| bands.iter().map(|band| band.amount).collect::<Vec<_>>();
+        assert_eq!(amounts(&histogram.asks), vec![3.0, 3.0, 0.0]);
+        // 99.0 is the lower price of the first band
+        assert_eq!(amounts(&histogram.bids), vec![5.0, 5.0, 0.0]);
+
+        assert_eq!(histogram.asks[1].lower_price, 101.0);
+        assert_eq!(histogram.asks[1].upper_price, 102.0);
+        assert_eq!(histogram.asks[1].notional, 304.5);
+        assert_eq!(histogram.bids[2].lower_price, 97.0);
+    }
+
+    #[test]
+    fn should_leave_out_levels_crossing_the_mid() {
+        let book = MergedBook {
+            asks: vec![
+                Level::new("Binance", 99.0, 1.0),
+                Level::new("Bitstamp", 100.5, 2.0),
+            ],
+            bids: vec![
+                Level::new("Bitstamp", 101.0, 3.0),
+                Level::new("Binance", 99.5, 4.0),
+            ],
+        };
+
+        // Crossed, the mid price of 100.0 is below the best bid and above the best ask
+        let histogram = depth_histogram(&book, 0.01, 2);
+        let amounts =
+            |bands: &[DepthBand]| bands.iter().map(|band| band.amount).collect::<Vec<_>>();
+        assert_eq!(amounts(&histogram.asks), vec![2.0, 0.0]);
+        assert_eq!(amounts(&histogram.bids), vec![4.0, 0.0]);
+    }
+}
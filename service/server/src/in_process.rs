@@ -0,0 +1,383 @@
+use std::io::{self, ErrorKind};
+
+use anyhow::Error;
+use futures::{future, FutureExt};
+use tokio::{
+    io::{duplex, DuplexStream},
+    sync::mpsc::{channel, Sender},
+    task::JoinHandle,
+};
+use tonic::transport::{Channel, Endpoint, Uri};
+use tower::service_fn;
+
+use crate::{
+    clock::SharedClock, config::Config, error::ServerError, exchange::BoxedExchange,
+    grpc_server::Transport, run, Aggregator, Connectors,
+};
+
+/// Size of the buffer in each direction of an in-process connection.
+const CONNECTION_BUFFER_SIZE: usize = 64 * 1024;
+/// Connections which can be waiting to be accepted by the server.
+const CONNECTION_BACKLOG: usize = 16;
+
+/// A running service reachable only from within the process, see [start_in_process].
+#[derive(Debug)]
+pub struct InProcessServer {
+    connections: Sender<DuplexStream>,
+    handle: JoinHandle<ServerError>,
+}
+
+/// Start the full service within the process, connected to through [InProcessServer::channel] rather than a socket.
+///
+/// Allows the aggregator to be embedded in another application, or tested end to end, without binding any ports.
+/// Telemetry isn't initialised, that is left to the embedding application.
+pub fn start_in_process(config: Config) -> InProcessServer {
+    Aggregator::new(config).start_in_process()
+}
+
+/// As [start_in_process], but aggregating from simulated exchanges which generate books locally.
+///
+/// The simulated exchanges are paced by [tokio::time], so with time paused, e.g. `#[tokio::test(start_paused = true)]`,
+/// the full stack runs deterministically and without real sleeps.
+#[cfg(any(test, feature = "test-util"))]
+pub fn start_simulated_in_process(config: Config) -> InProcessServer {
+    Aggregator::with_connectors(config, crate::exchanges::simulated::simulated_exchanges)
+        .start_in_process()
+}
+
+pub(crate) fn start_with_connectors(
+    config: Config,
+    connectors: Connectors,
+    custom: Vec<BoxedExchange>,
+    clock: SharedClock,
+) -> InProcessServer {
+    let (connections, incoming) = channel(CONNECTION_BACKLOG);
+    let handle = tokio::spawn(run(
+        config,
+        Transport::InProcess(incoming),
+        connectors,
+        custom,
+        clock,
+        future::pending().boxed(),
+    ));
+
+    InProcessServer {
+        connections,
+        handle,
+    }
+}
+
+impl InProcessServer {
+    /// A channel to the service for use with any of the generated clients, each connection it makes is an in-memory duplex stream.
+    pub fn channel(&self) -> Channel {
+        let connections = self.connections.clone();
+
+        // The URI is required but unused as the connector never touches the network
+        Endpoint::from_static("http://in-process").connect_with_connector_lazy(service_fn(
+            move |_: Uri| {
+                let connections = connections.clone();
+                async move {
+                    let (client, server) = duplex(CONNECTION_BUFFER_SIZE);
+                    connections.send(server).await.map_err(|_| {
+                        io::Error::new(ErrorKind::NotConnected, "In-process server has stopped")
+                    })?;
+                    Ok::<_, io::Error>(client)
+                }
+            },
+        ))
+    }
+
+    /// Wait for the service to stop, which only happens due to an error or once it's drained.
+    pub async fn stopped(self) -> Error {
+        match self.handle.await {
+            Ok(err) => Error::from(err),
+            Err(join_err) => Error::from(join_err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic_health::proto::{
+        health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+    };
+
+    use std::{collections::HashSet, time::Duration};
+
+    use futures_util::StreamExt;
+    use tokio::time::Instant;
+    use tower::ServiceExt;
+
+    use order_book_service_client::{multi_pair::MultiPairClient, service::SummaryService};
+    use tokio::sync::mpsc::channel;
+    use tokio_stream::wrappers::ReceiverStream;
+    use tonic::{Code, Streaming};
+
+    use order_book_service_types::proto::{
+        orderbook_aggregator_client::OrderbookAggregatorClient, subscription_command::Command,
+        tagged_summary::Payload, Capability, DepthSnapshotRequest, Empty, ModifyCommand,
+        OrderBookRequest, SubscribeCommand, SubscriptionCommand, TaggedSummary, TradedPair,
+    };
+
+    use crate::config::Config;
+
+    use super::start_simulated_in_process;
+
+    #[tokio::test]
+    async fn should_serve_without_binding_a_socket() {
+        // Simulated exchanges, so the test neither reaches out to live exchanges nor depends on them
+        let server = start_simulated_in_process(Config::default());
+
+        let response = HealthClient::new(server.channel())
+            .check(HealthCheckRequest {
+                service: "orderbook.OrderbookAggregator".to_string(),
+            })
+            .await
+            .expect("Health check should succeed")
+            .into_inner();
+
+        assert_eq!(response.status(), ServingStatus::Serving);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_provide_summaries_for_many_pairs_over_one_connection() {
+        let server = start_simulated_in_process(Config::default());
+        let channel = server.channel();
+        let client = MultiPairClient::with_channel(channel.clone(), Duration::from_secs(1))
+            .await
+            .expect("Should connect");
+
+        let mut eth_btc = client
+            .subscribe(TradedPair::new("ETH", "BTC"))
+            .await
+            .expect("Should subscribe to ETH-BTC");
+        let mut ltc_btc = client
+            .subscribe(TradedPair::new("LTC", "BTC"))
+            .await
+            .expect("Should subscribe to LTC-BTC");
+
+        // Both subscriptions receive summaries over the shared connection
+        for summaries in [&mut eth_btc, &mut ltc_btc] {
+            let summary = summaries
+                .next()
+                .await
+                .expect("Stream should stay open")
+                .expect("Should receive a summary");
+            assert!(!summary.bids.is_empty());
+        }
+        // Which the client's health checks, and the server's health service, report as serving
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(client.is_healthy());
+        let response = HealthClient::new(channel)
+            .check(HealthCheckRequest {
+                service: "orderbook.OrderbookAggregator".to_string(),
+            })
+            .await
+            .expect("Health check should succeed")
+            .into_inner();
+        assert_eq!(response.status(), ServingStatus::Serving);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_stream_simulated_summaries_in_virtual_time() {
+        let server = start_simulated_in_process(Config::default());
+        let start = Instant::now();
+
+        let mut summaries = SummaryService::new(server.channel())
+            .oneshot(TradedPair::new("ETH", "BTC"))
+            .await
+            .expect("Should subscribe");
+
+        let mut exchanges = HashSet::new();
+        for _ in 0..50 {
+            let summary = summaries
+                .next()
+                .await
+                .expect("Stream should stay open")
+                .expect("Should receive a summary");
+            exchanges.extend(summary.bids.into_iter().map(|level| level.exchange));
+        }
+        assert_eq!(exchanges.len(), 2);
+
+        // Several seconds of updates are run through without actually waiting for them
+        assert!(start.elapsed() >= Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_return_depth_snapshots_without_subscribing() {
+        let server = start_simulated_in_process(Config::default());
+        let mut client = OrderbookAggregatorClient::new(server.channel());
+
+        let snapshot = |depth| DepthSnapshotRequest {
+            traded_pair: Some(TradedPair::new("ETH", "BTC")),
+            depth,
+            effective_prices: false,
+        };
+
+        let top = client
+            .get_depth_snapshot(snapshot(5))
+            .await
+            .expect("Should return a snapshot")
+            .into_inner();
+        assert_eq!(top.bids.len(), 5);
+        assert_eq!(top.asks.len(), 5);
+
+        // Every known level, each simulated exchange provides 20 a side
+        let full = client
+            .get_depth_snapshot(snapshot(0))
+            .await
+            .expect("Should return a snapshot")
+            .into_inner();
+        assert!(full.bids.len() >= 20);
+        assert!(full.asks.len() >= 20);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_return_every_level_of_thin_books() {
+        let server = start_simulated_in_process(Config::default());
+        let mut client = OrderbookAggregatorClient::new(server.channel());
+
+        // Each simulated exchange provides 3 DOGE-BTC levels a side, fewer than the 10 requested by default
+        let summary = client
+            .book_summary(OrderBookRequest {
+                traded_pair: Some(TradedPair::new("DOGE", "BTC")),
+                ..Default::default()
+            })
+            .await
+            .expect("Should subscribe")
+            .into_inner()
+            .next()
+            .await
+            .expect("Stream should stay open")
+            .expect("Should receive a summary");
+        assert!(summary.asks.len() <= 6);
+        assert_eq!(summary.ask_depth as usize, summary.asks.len());
+        assert_eq!(summary.bid_depth as usize, summary.bids.len());
+        assert!(summary.spread > 0.0);
+
+        let snapshot = client
+            .get_depth_snapshot(DepthSnapshotRequest {
+                traded_pair: Some(TradedPair::new("DOGE", "BTC")),
+                depth: 50,
+                effective_prices: false,
+            })
+            .await
+            .expect("Should return a snapshot")
+            .into_inner();
+        assert!((3..=6).contains(&snapshot.ask_depth));
+        assert_eq!(snapshot.ask_depth as usize, snapshot.asks.len());
+        assert_eq!(snapshot.bid_depth as usize, snapshot.bids.len());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_negotiate_capabilities_on_connect() {
+        let server = start_simulated_in_process(Config::default());
+
+        let client = MultiPairClient::with_channel(server.channel(), Duration::from_secs(60))
+            .await
+            .expect("Should connect");
+        let capabilities = client.capabilities();
+        assert_eq!(
+            capabilities.server_version(),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+        assert!(capabilities.supports(Capability::BatchedSummaries));
+        // Consolidation isn't configured
+        assert!(!capabilities.supports(Capability::Consolidation));
+        assert_eq!(capabilities.exchanges(), ["SimulatedA", "SimulatedB"]);
+
+        let mut batches = client
+            .subscribe_batched(TradedPair::new("ETH", "BTC"), Duration::from_secs(1))
+            .await
+            .expect("Should subscribe");
+        let batch = batches
+            .next()
+            .await
+            .expect("Stream should stay open")
+            .expect("Should receive a batch");
+        assert!(!batch.summaries.is_empty());
+    }
+
+    async fn next_payload(tagged: &mut Streaming<TaggedSummary>) -> (String, Payload) {
+        let tagged = tagged
+            .next()
+            .await
+            .expect("Stream should stay open")
+            .expect("Should receive a tagged summary");
+        (
+            tagged.subscription_id,
+            tagged.payload.expect("Should be set"),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_manage_subscriptions_over_one_stream() {
+        let server = start_simulated_in_process(Config::default());
+        let mut client = OrderbookAggregatorClient::new(server.channel());
+
+        let command = |id: &str, command| SubscriptionCommand {
+            subscription_id: id.to_string(),
+            command: Some(command),
+        };
+        let subscribe = |depth| {
+            Command::Subscribe(SubscribeCommand {
+                request: Some(OrderBookRequest {
+                    traded_pair: Some(TradedPair::new("ETH", "BTC")),
+                    depth,
+                    ..Default::default()
+                }),
+                throttle_millis: 0,
+            })
+        };
+
+        let (commands, command_rx) = channel(10);
+        let mut tagged = client
+            .manage_subscriptions(ReceiverStream::new(command_rx))
+            .await
+            .expect("Should open the stream")
+            .into_inner();
+
+        commands.send(command("top", subscribe(3))).await.unwrap();
+        commands.send(command("top", subscribe(3))).await.unwrap();
+        let mut duplicate_rejected = false;
+        let mut summaries = 0;
+        while !duplicate_rejected || summaries < 5 {
+            match next_payload(&mut tagged).await {
+                (id, Payload::Summary(summary)) if id == "top" => {
+                    assert_eq!(summary.bids.len(), 3);
+                    summaries += 1;
+                }
+                (id, Payload::Error(error)) if id == "top" => {
+                    assert_eq!(error.code, Code::AlreadyExists as i32);
+                    duplicate_rejected = true;
+                }
+                unexpected => panic!("Unexpected payload {unexpected:?}"),
+            }
+        }
+
+        // Summaries at the old depth may still be in flight once the depth is changed
+        let modify = Command::Modify(ModifyCommand {
+            depth: 5,
+            throttle_millis: 0,
+        });
+        commands.send(command("top", modify)).await.unwrap();
+        loop {
+            let (_, payload) = next_payload(&mut tagged).await;
+            if matches!(payload, Payload::Summary(summary) if summary.bids.len() == 5) {
+                break;
+            }
+        }
+
+        commands
+            .send(command("other", Command::Unsubscribe(Empty {})))
+            .await
+            .unwrap();
+        loop {
+            if let (id, Payload::Error(error)) = next_payload(&mut tagged).await {
+                assert_eq!(id, "other");
+                assert_eq!(error.code, Code::NotFound as i32);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use order_book_service_types::proto::{ExchangeId, ExchangeLatency};
+
+use crate::metrics::exchange_latency;
+
+/// How many of each exchange's most recent messages the rolling stats are taken over.
+const WINDOW: usize = 100;
+/// How far the skew estimate moves towards a lower offset, and towards a higher one.
+const SKEW_FALL: f64 = 0.5;
+const SKEW_RISE: f64 = 0.02;
+
+/// Rolling latency of each exchange's messages, the time between the exchange stamping a message and its receipt.
+///
+/// Shared by every aggregator, so each exchange's stats cover all of the pairs streamed from it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ExchangeLatencies {
+    samples: Arc<Mutex<HashMap<ExchangeId, VecDeque<Duration>>>>,
+    skews: Arc<Mutex<HashMap<ExchangeId, ClockSkew>>>,
+}
+
+impl ExchangeLatencies {
+    pub(crate) fn record(&self, exchange: &ExchangeId, latency: Duration) {
+        let mut samples = self.samples.lock().expect("Should lock");
+        let window = samples.entry(exchange.clone()).or_default();
+        if window.len() == WINDOW {
+            window.pop_front();
+        }
+        window.push_back(latency);
+
+        let (mean, max) = mean_and_max(window);
+        exchange_latency(exchange.as_str(), "mean").set(mean.as_micros() as i64);
+        exchange_latency(exchange.as_str(), "max").set(max.as_micros() as i64);
+    }
+
+    /// Update the exchange's skew estimate with a message's timestamps and return when it was produced on our clock.
+    pub(crate) fn correct_skew(
+        &self,
+        exchange: &ExchangeId,
+        produced: SystemTime,
+        received: SystemTime,
+    ) -> SystemTime {
+        let offset = micros_since_epoch(received) - micros_since_epoch(produced);
+        let skew = self
+            .skews
+            .lock()
+            .expect("Should lock")
+            .entry(exchange.clone())
+            .or_default()
+            .update(offset);
+
+        let corrected = micros_since_epoch(produced) + skew;
+        UNIX_EPOCH + Duration::from_micros(corrected.max(0) as u64)
+    }
+
+    /// The mean latency of each exchange which has reported any, keyed by name.
+    pub(crate) fn means(&self) -> HashMap<String, Duration> {
+        self.samples
+            .lock()
+            .expect("Should lock")
+            .iter()
+            .map(|(exchange, window)| (exchange.to_string(), mean_and_max(window).0))
+            .collect()
+    }
+
+    /// The rolling stats of each exchange, sorted by name.
+    pub(crate) fn stats(&self) -> Vec<ExchangeLatency> {
+        let skews = self.skews.lock().expect("Should lock");
+        let mut stats = self
+            .samples
+            .lock()
+            .expect("Should lock")
+            .iter()
+            .map(|(exchange, window)| {
+                let (mean, max) = mean_and_max(window);
+                ExchangeLatency {
+                    exchange: exchange.to_string(),
+                    mean_micros: mean.as_micros() as u64,
+                    max_micros: max.as_micros() as u64,
+                    samples: window.len() as u32,
+                    skew_micros: skews
+                        .get(exchange)
+                        .map(|skew| skew.estimate.round() as i64)
+                        .unwrap_or_default(),
+                }
+            })
+            .collect::<Vec<_>>();
+        stats.sort_unstable_by(|a, b| a.exchange.cmp(&b.exchange));
+        stats
+    }
+}
+
+/// Smoothed offset of our clock from an exchange's, in microseconds.
+///
+/// Each message's offset is the skew plus its network latency, so the estimate follows the lowest offsets seen, falling
+/// quickly and only rising slowly should the exchange's clock drift back.
+#[derive(Clone, Copy, Debug, Default)]
+struct ClockSkew {
+    estimate: f64,
+    samples: u64,
+}
+
+impl ClockSkew {
+    fn update(&mut self, offset_micros: i64) -> i64 {
+        let offset = offset_micros as f64;
+        self.estimate = match self.samples {
+            0 => offset,
+            _ if offset < self.estimate => self.estimate + SKEW_FALL * (offset - self.estimate),
+            _ => self.estimate + SKEW_RISE * (offset - self.estimate),
+        };
+        self.samples += 1;
+        self.estimate.round() as i64
+    }
+}
+
+fn micros_since_epoch(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_micros() as i64,
+        Err(err) => -(err.duration().as_micros() as i64),
+    }
+}
+
+fn mean_and_max(window: &VecDeque<Duration>) -> (Duration, Duration) {
+    let total = window.iter().sum::<Duration>();
+    let mean = total / window.len().max(1) as u32;
+    let max = window.iter().max().copied().unwrap_or_default();
+    (mean, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use order_book_service_types::proto::ExchangeId;
+
+    use super::{ExchangeLatencies, WINDOW};
+
+    #[test]
+    fn should_keep_rolling_stats_per_exchange() {
+        let latencies = ExchangeLatencies::default();
+        latencies.record(&ExchangeId::Bitstamp, Duration::from_millis(500));
+        for _ in 0..WINDOW {
+            latencies.record(&ExchangeId::Bitstamp, Duration::from_millis(20));
+        }
+        latencies.record(&ExchangeId::Binance, Duration::from_millis(10));
+        latencies.record(&ExchangeId::Binance, Duration::from_millis(30));
+
+        let stats = latencies.stats();
+        assert_eq!(stats[0].exchange, "Binance");
+        assert_eq!(stats[0].mean_micros, 20_000);
+        assert_eq!(stats[0].max_micros, 30_000);
+        // The slow first message has rolled out of the window
+        assert_eq!(stats[1].max_micros, 20_000);
+        assert_eq!(stats[1].samples, WINDOW as u32);
+    }
+
+    #[test]
+    fn should_correct_timestamps_by_the_estimated_skew() {
+        let latencies = ExchangeLatencies::default();
+        let at = |millis| UNIX_EPOCH + Duration::from_millis(millis);
+
+        // The exchange's clock is 2s behind ours and messages take 10ms or more to arrive
+        assert_eq!(
+            latencies.correct_skew(&ExchangeId::Binance, at(10_000), at(12_010)),
+            at(12_010)
+        );
+        // A slow message barely moves the estimate
+        let corrected = latencies.correct_skew(&ExchangeId::Binance, at(11_000), at(13_500));
+        assert_eq!(corrected, UNIX_EPOCH + Duration::from_micros(13_019_800));
+        // A faster one brings it down quickly
+        for _ in 0..20 {
+            latencies.correct_skew(&ExchangeId::Binance, at(12_000), at(14_005));
+        }
+        let corrected = latencies.correct_skew(&ExchangeId::Binance, at(13_000), at(15_005));
+        assert_eq!(corrected, at(15_005));
+
+        // Clocks ahead of ours give a negative skew
+        latencies.correct_skew(&ExchangeId::Bitstamp, at(20_000), at(19_000));
+        latencies.record(&ExchangeId::Binance, Duration::from_millis(5));
+        latencies.record(&ExchangeId::Bitstamp, Duration::ZERO);
+        let stats = latencies.stats();
+        assert_eq!(stats[0].skew_micros, 2_005_000);
+        assert_eq!(stats[1].skew_micros, -1_000_000);
+    }
+}
@@ -0,0 +1,453 @@
+mod admin;
+mod aggregator;
+mod clock;
+mod config;
+mod connector_status;
+mod consistency;
+mod conversion;
+#[cfg(feature = "dashboard")]
+mod dashboard;
+mod demo;
+mod doctor;
+mod drain;
+mod embedding;
+mod error;
+mod events;
+mod exchange;
+mod exchange_status;
+mod exchanges;
+mod fairness;
+mod grpc_server;
+mod histogram;
+mod in_process;
+mod latency;
+mod liquidity;
+mod metrics;
+mod multiplex;
+mod pairs;
+mod quality;
+mod rate_limit;
+mod readiness;
+mod request_log;
+#[cfg(feature = "runtime-metrics")]
+mod runtime_metrics;
+mod shared_encoding;
+mod slippage;
+mod snapshots;
+mod standby;
+mod subscribers;
+mod tap;
+mod telemetry;
+mod tenancy;
+mod threads;
+mod transform;
+mod validation;
+#[cfg(windows)]
+mod windows;
+
+use std::time::Duration;
+
+use anyhow::Error;
+use futures::{
+    future::{self, BoxFuture},
+    FutureExt,
+};
+use tokio::{select, sync::oneshot, task::JoinHandle, time::sleep};
+use tracing::{debug, error, info};
+
+use crate::{
+    admin::AdminService,
+    aggregator::OrderbookAggregator,
+    connector_status::ConnectorStatusBus,
+    conversion::check_quote_conversions,
+    drain::Drain,
+    error::ServerError,
+    events::EventBus,
+    exchange::ConnectorContext,
+    exchange_status::ExchangeStatusMonitor,
+    exchanges::{register_exchanges, simulated::demo_exchanges},
+    grpc_server::{start_server, Transport},
+    latency::ExchangeLatencies,
+    metrics::{metered_channel, ChannelMeter},
+    pairs::{check_warm_up_pairs, PairDirectory},
+    readiness::ServiceNotifier,
+    snapshots::MetricsSnapshots,
+    standby::Upstream,
+    subscribers::Subscribers,
+    tap::FrameTap,
+    tenancy::{AdminAuth, Tenants},
+};
+
+pub use crate::{
+    clock::{Clock, SharedClock, SystemClock},
+    config::Config,
+    doctor::{doctor, DoctorReport},
+    embedding::Aggregator,
+    error::ExchangeError,
+    exchange::{
+        BoxedExchange, BoxedOrderbook, DepthHint, Exchange, ExchangeInfo, OrderBook,
+        RateLimitProfile, ReceivedOrderbook, VenueStatus,
+    },
+    in_process::{start_in_process, InProcessServer},
+    threads::build_runtime,
+};
+
+#[cfg(windows)]
+pub use crate::windows::run_windows_service;
+#[cfg(feature = "test-util")]
+pub use crate::{clock::ManualClock, in_process::start_simulated_in_process};
+
+/// Run the service with the given config until it fails or is drained, serving on its configured port or Unix socket.
+pub async fn serve(config: Config) -> Result<(), Error> {
+    Aggregator::new(config).serve().await
+}
+
+/// Run a read-only demo of the service over synthetic books until it fails, see the README's Demo section.
+///
+/// Nothing connects to a real exchange, the admin service isn't served and each client's requests are rate limited.
+pub async fn serve_demo(mut config: Config) -> Result<(), Error> {
+    telemetry::init(&config.tracing)?;
+    config.demo = true;
+
+    let transport = Transport::from_config(&config);
+    let err = run(
+        config,
+        transport,
+        demo_exchanges,
+        Vec::new(),
+        SystemClock::shared(),
+        future::pending().boxed(),
+    )
+    .await;
+    telemetry::shutdown();
+    Err(err.into())
+}
+
+/// How long a drained server waits for in-flight requests before shutting down regardless.
+const DRAINED_SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// Builds the exchanges the service aggregates from.
+type Connectors = fn(ConnectorContext) -> Vec<BoxedExchange>;
+
+/// Resolves when the service should stop, e.g. when a service manager asks it to.
+type StopSignal = BoxFuture<'static, ()>;
+
+/// Serve until failing, aggregating from the exchanges built by `connectors` and the `custom` exchanges embedders added.
+///
+/// The connectors and aggregators read the time from `clock`. Once `stop` resolves the server is drained, as by the
+/// admin service, and then shuts down.
+async fn run(
+    mut config: Config,
+    transport: Transport,
+    connectors: Connectors,
+    custom: Vec<BoxedExchange>,
+    clock: SharedClock,
+    stop: StopSignal,
+) -> ServerError {
+    // Expose metrics for operators
+    if let Some(metrics_port) = config.metrics_port {
+        let metrics_address = config.metrics_bind_address;
+        info!("Serving metrics on {metrics_address}:{metrics_port}...");
+        tokio::spawn(async move {
+            if let Err(err) = metrics::serve(metrics_address, metrics_port).await {
+                error!("{err:#}");
+            }
+        });
+    }
+    // An out of the box view of the service, fed through its own gRPC API
+    #[cfg(feature = "dashboard")]
+    dashboard::spawn(&config.dashboard, &transport);
+    // Keep recent metrics on disk for post-mortems, the admin service can also save them on demand
+    let metrics_snapshots = MetricsSnapshots::new(&config.metrics_snapshots);
+    tokio::spawn(metrics_snapshots.clone().run());
+
+    // Set up exchange instances
+    let frame_tap = FrameTap::new(config.tap.clone());
+    // Connectors report their connection events to clients of the WatchExchangeStatus RPC
+    let status_bus = ConnectorStatusBus::new(config.channels.connector_status);
+    let built_in = connectors(
+        ConnectorContext::new(&config, frame_tap.clone(), status_bus.clone())
+            .with_clock(clock.clone()),
+    );
+    let exchanges = match register_exchanges(built_in, custom) {
+        Ok(exchanges) => exchanges,
+        Err(err) => return err,
+    };
+    if let Err(err) = check_quote_conversions(&config.quote_conversions, &exchanges) {
+        return err;
+    }
+    let exchange_infos = exchanges
+        .iter()
+        .map(|exchange| exchange.info())
+        .collect::<Vec<_>>();
+    // Effective prices use each venue's published taker fee when asked to, unless one is configured
+    if config.use_published_taker_fees {
+        config.add_published_taker_fees(&exchange_infos);
+    }
+    let mut taker_fees = config.taker_fees.iter().collect::<Vec<_>>();
+    taker_fees.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    info!("Effective prices use taker fees {taker_fees:?}");
+
+    // Warm-up pairs which exchanges don't list are reported now, rather than by their first subscribers
+    let pair_directory = PairDirectory::new(&exchanges);
+    if let Err(err) = check_warm_up_pairs(&pair_directory, &config).await {
+        return err;
+    }
+
+    // Track which exchanges are in maintenance so summaries can be annotated
+    let status_monitor = ExchangeStatusMonitor::new(&exchanges, config.exchange_status.clone());
+    let maintenance_receiver = status_monitor.subscribe();
+    tokio::spawn(status_monitor.start());
+
+    // Creates a channel for the gRPC server to inform the process of new requests
+    let (new_subscriber_tx, mut new_subscriber_rx) = metered_channel(
+        config.channels.new_subscriber,
+        ChannelMeter::new("new_subscriber", "", config.channels.new_subscriber),
+    );
+
+    // Alerts raised by the service are streamed to clients over gRPC
+    let event_bus = EventBus::new(config.channels.service_events);
+    // Message latency is tracked per exchange across every pair, for prioritising levels and the admin service
+    let latencies = ExchangeLatencies::default();
+
+    // A service manager, e.g. systemd, is told once the service can actually serve, and kept aware that it's alive
+    let notifier = ServiceNotifier::from_env();
+    let (listening_tx, listening_rx) = oneshot::channel();
+    tokio::spawn(notifier.clone().notify_when_ready(
+        listening_rx,
+        status_bus.subscribe(),
+        !config.warm_up_pairs.is_empty(),
+    ));
+    tokio::spawn(notifier.keep_watchdog_alive());
+
+    // The admin service can drain the server for a rolling deploy, which then shuts down
+    let drain = Drain::new(&config.drain);
+    let subscribers = Subscribers::default();
+    tokio::spawn({
+        let drain = drain.clone();
+        let subscribers = subscribers.clone();
+        async move {
+            stop.await;
+            info!("Asked to stop, draining...");
+            // Already draining when the admin service got there first
+            let _ = drain.drain(None, &subscribers).await;
+        }
+    });
+
+    // Spin up the gRPC server
+    let grpc_server_handle = tokio::spawn(start_server(
+        transport,
+        new_subscriber_tx,
+        config.clone(),
+        event_bus.clone(),
+        status_bus,
+        pair_directory,
+        AdminService {
+            frame_tap,
+            auth: AdminAuth::new(Tenants::new(&config.tenants), config.admin.api_key.clone()),
+            latencies: latencies.clone(),
+            subscribers,
+            metrics_snapshots: metrics_snapshots.clone(),
+            drain: drain.clone(),
+            exchanges: exchange_infos,
+        },
+        listening_tx,
+    ));
+
+    // A standby relays each pair from its upstream until it has to aggregate the pair itself
+    let upstream = Upstream::from_config(&config);
+
+    // Handle requests from the gRPC server
+    let request_handler_handle = tokio::spawn(async move {
+        // Await new subscription requests
+        while let Some((requested_pair, aggregator_handle_sender)) = new_subscriber_rx.recv().await
+        {
+            debug!("New request for {requested_pair}");
+
+            // There is no aggregator for the requested pair - a new one needs to be created.
+            let new_aggregator = OrderbookAggregator::new(
+                &exchanges,
+                requested_pair.clone(),
+                maintenance_receiver.clone(),
+                event_bus.clone(),
+                latencies.clone(),
+                clock.clone(),
+                &config,
+            );
+
+            // Send a handle for the new aggregator back to the gRPC server to provide the orderbooks for the request.
+            // This handle will be cached in the gRPC server to minimise requests to the main process.
+            let _ = aggregator_handle_sender.send(new_aggregator.subscribe());
+
+            // Start the aggregator
+            match &upstream {
+                Some(upstream) => {
+                    tokio::spawn(upstream.clone().relay_then_aggregate(new_aggregator))
+                }
+                None => tokio::spawn(new_aggregator.start()),
+            };
+        }
+        Ok::<_, ServerError>(())
+    });
+
+    // Once drained the gRPC server shuts down gracefully, given a little time to finish in-flight requests such as the
+    // drain itself
+    let grpc_server = async {
+        select! {
+            result = flatten_handle(grpc_server_handle) => result?,
+            _ = async {
+                drain.finished().await;
+                sleep(DRAINED_SHUTDOWN_GRACE).await
+            } => {}
+        }
+        Err::<(), _>(if drain.is_finished() {
+            ServerError::Drained
+        } else {
+            ServerError::UnexpectedExit
+        })
+    };
+
+    // The request handler will only shutdown when the new_subscriber sender closes - as part of the gRPC server shutting down.
+    match tokio::try_join!(grpc_server, flatten_handle(request_handler_handle)) {
+        Err(error) => error,
+        _ => ServerError::UnexpectedExit,
+    }
+}
+
+async fn flatten_handle<T>(handle: JoinHandle<Result<T, ServerError>>) -> Result<T, ServerError> {
+    match handle.await {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(err)) => Err(err),
+        Err(join_err) => Err(ServerError::from(join_err)),
+    }
+}
+
+#[cfg(test)]
+mod smoke_tests {
+    use std::time::Duration;
+
+    use futures::{future, FutureExt};
+    use futures_util::StreamExt;
+    use tokio::sync::{mpsc, oneshot};
+    use url::Url;
+
+    use order_book_service_client::{
+        connect_to_summary_service, multi_pair::MultiPairClient, ConnectionSettings,
+    };
+    use order_book_service_types::proto::TradedPair;
+
+    use crate::{
+        clock::SystemClock,
+        config::Config,
+        error::ServerError,
+        exchanges::{live_exchanges, simulated::simulated_exchanges},
+        grpc_server::Transport,
+        run,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn should_drain_then_shut_down_once_asked_to_stop() {
+        let (_connections, incoming) = mpsc::channel(1);
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(run(
+            Config::default(),
+            Transport::InProcess(incoming),
+            simulated_exchanges,
+            Vec::new(),
+            SystemClock::shared(),
+            async {
+                let _ = stopped.await;
+            }
+            .boxed(),
+        ));
+
+        stop.send(()).unwrap();
+        assert!(matches!(server.await.unwrap(), ServerError::Drained));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn should_provide_summaries_via_grpc() {
+        let config = Config::default();
+        let port = config.port;
+
+        // Spin up server
+        let transport = Transport::from_config(&config);
+        tokio::spawn(run(
+            config,
+            transport,
+            live_exchanges,
+            Vec::new(),
+            SystemClock::shared(),
+            future::pending().boxed(),
+        ));
+
+        let url_str = format!("http://0.0.0.0:{port}");
+        let connection_settings = ConnectionSettings::new(
+            Url::parse(&url_str).unwrap(),
+            TradedPair::new("ETH", "BTC"),
+            10,
+            Duration::from_secs(1),
+        );
+
+        // Connect to server via the client library
+        let mut summary_receiver = connect_to_summary_service(connection_settings).await;
+        let mut count = 0;
+
+        let mut summaries_received = Vec::new();
+
+        // Listen to the receiver for what should be 5 summaries
+        while let Some(Ok(summary)) = summary_receiver.next().await {
+            count += 1;
+            summaries_received.push(summary);
+            if count >= 5 {
+                break;
+            }
+        }
+
+        // Check that the client did receive the summaries from the server
+        assert_eq!(summaries_received.len(), 5);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn should_provide_summaries_for_many_pairs_over_one_connection() {
+        let config = Config {
+            port: 3031,
+            ..Config::default()
+        };
+        let port = config.port;
+
+        // Spin up server
+        let transport = Transport::from_config(&config);
+        tokio::spawn(run(
+            config,
+            transport,
+            live_exchanges,
+            Vec::new(),
+            SystemClock::shared(),
+            future::pending().boxed(),
+        ));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let url_str = format!("http://0.0.0.0:{port}");
+        let client =
+            MultiPairClient::connect(Url::parse(&url_str).unwrap(), Duration::from_secs(1))
+                .await
+                .expect("Should connect to server");
+
+        let mut eth_btc = client
+            .subscribe(TradedPair::new("ETH", "BTC"))
+            .await
+            .expect("Should subscribe to ETH-BTC");
+        let mut ltc_btc = client
+            .subscribe(TradedPair::new("LTC", "BTC"))
+            .await
+            .expect("Should subscribe to LTC-BTC");
+
+        // Both subscriptions should receive summaries over the shared connection
+        assert!(matches!(eth_btc.next().await, Some(Ok(_))));
+        assert!(matches!(ltc_btc.next().await, Some(Ok(_))));
+        assert!(client.is_healthy());
+    }
+}
@@ -0,0 +1,181 @@
+use tracing::warn;
+
+use order_book_service_types::proto::{service_event::Event, Level, LiquidityAlert, TradedPair};
+
+use crate::{aggregator::MergedBook, config::LiquidityThreshold, events::EventBus};
+
+/// Which sides of a book are below their minimum amount.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct LowLiquidity {
+    pub(crate) bids: bool,
+    pub(crate) asks: bool,
+}
+
+/// Checks the liquidity at the top of each merged book against the pair's configured threshold, so monitoring can
+/// page on a thinning book before consumers notice.
+pub(crate) struct LiquidityMonitor {
+    traded_pair: TradedPair,
+    /// `None` when no threshold is configured for the pair
+    threshold: Option<LiquidityThreshold>,
+    event_bus: EventBus,
+    /// Alerts are only raised when this changes, a low side has to clear the recovery margin to change back
+    low: LowLiquidity,
+}
+
+impl LiquidityMonitor {
+    pub(crate) fn new(
+        traded_pair: TradedPair,
+        threshold: Option<LiquidityThreshold>,
+        event_bus: EventBus,
+    ) -> Self {
+        Self {
+            traded_pair,
+            threshold,
+            event_bus,
+            low: LowLiquidity::default(),
+        }
+    }
+
+    /// Check the merged book, returning which of its sides are below their minimum.
+    pub(crate) fn check(&mut self, book: &MergedBook) -> LowLiquidity {
+        let Some(threshold) = self.threshold else {
+            return LowLiquidity::default();
+        };
+        let bid_amount = top_amount(&book.bids, threshold.levels);
+        let ask_amount = top_amount(&book.asks, threshold.levels);
+        let below = |amount: f64, min_amount: Option<f64>, was_low: bool| {
+            min_amount.is_some_and(|min_amount| {
+                let margin = if was_low {
+                    min_amount * threshold.recovery_margin
+                } else {
+                    0.0
+                };
+                amount < min_amount + margin
+            })
+        };
+        let low = LowLiquidity {
+            bids: below(bid_amount, threshold.min_bid_amount, self.low.bids),
+            asks: below(ask_amount, threshold.min_ask_amount, self.low.asks),
+        };
+
+        if low != self.low {
+            if low.bids || low.asks {
+                warn!(
+                    "Liquidity of {} is low, the top {} levels hold {bid_amount} bid and {ask_amount} ask",
+                    self.traded_pair, threshold.levels
+                );
+            }
+            self.event_bus
+                .publish(Event::LiquidityAlert(LiquidityAlert {
+                    traded_pair: Some(self.traded_pair.clone()),
+                    levels: threshold.levels as u32,
+                    bid_amount,
+                    ask_amount,
+                    low_bids: low.bids,
+                    low_asks: low.asks,
+                }));
+            self.low = low;
+        }
+
+        low
+    }
+}
+
+fn top_amount(levels: &[Level], count: usize) -> f64 {
+    levels.iter().take(count).map(|level| level.amount).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::{service_event::Event, Level, TradedPair};
+
+    use crate::{aggregator::MergedBook, config::LiquidityThreshold, events::EventBus};
+
+    use super::{LiquidityMonitor, LowLiquidity};
+
+    fn book(bid_amounts: &[f64]) -> MergedBook {
+        MergedBook {
+            asks: vec![Level::new("Binance", 11.0, 100.0)],
+            bids: bid_amounts
+                .iter()
+                .enumerate()
+                .map(|(i, amount)| Level::new("Binance", 10.0 - i as f64, *amount))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn should_alert_when_a_side_falls_below_its_minimum_and_recovers() {
+        let event_bus = EventBus::new(10);
+        let mut events = event_bus.subscribe();
+        let mut monitor = LiquidityMonitor::new(
+            TradedPair::new("ETH", "BTC"),
+            Some(LiquidityThreshold {
+                levels: 2,
+                min_bid_amount: Some(5.0),
+                min_ask_amount: None,
+                recovery_margin: 0.0,
+            }),
+            event_bus,
+        );
+
+        assert_eq!(monitor.check(&book(&[3.0, 3.0])), LowLiquidity::default());
+        // Only the top two levels count
+        let low = monitor.check(&book(&[2.0, 2.0, 50.0]));
+        assert_eq!(
+            low,
+            LowLiquidity {
+                bids: true,
+                asks: false
+            }
+        );
+        // Still low, so not alerted again
+        monitor.check(&book(&[1.0, 1.0]));
+        monitor.check(&book(&[4.0, 4.0]));
+
+        let alerts = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| match event.event {
+                Some(Event::LiquidityAlert(alert)) => (alert.low_bids, alert.bid_amount),
+                other => panic!("Unexpected event {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(alerts, vec![(true, 4.0), (false, 8.0)]);
+    }
+
+    #[test]
+    fn should_only_recover_once_clear_of_the_margin() {
+        let event_bus = EventBus::new(10);
+        let mut events = event_bus.subscribe();
+        let mut monitor = LiquidityMonitor::new(
+            TradedPair::new("ETH", "BTC"),
+            Some(LiquidityThreshold {
+                levels: 1,
+                min_bid_amount: Some(10.0),
+                min_ask_amount: None,
+                recovery_margin: 0.2,
+            }),
+            event_bus,
+        );
+
+        // Hovering around the minimum, a low book only recovers from 12
+        for amount in [9.0, 10.5, 9.5, 11.9, 9.0, 12.0, 11.0] {
+            monitor.check(&book(&[amount]));
+        }
+
+        let alerts = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| match event.event {
+                Some(Event::LiquidityAlert(alert)) => (alert.low_bids, alert.bid_amount),
+                other => panic!("Unexpected event {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(alerts, vec![(true, 9.0), (false, 12.0)]);
+    }
+
+    #[test]
+    fn should_not_check_pairs_without_a_threshold() {
+        let mut monitor =
+            LiquidityMonitor::new(TradedPair::new("ETH", "BTC"), None, EventBus::new(10));
+
+        assert_eq!(monitor.check(&book(&[0.001])), LowLiquidity::default());
+    }
+}
@@ -1,124 +1,58 @@
-mod aggregator;
-mod exchange;
-mod exchanges;
-mod grpc_server;
+use std::{path::PathBuf, process::exit};
 
 use anyhow::Error;
-use tokio::{sync::mpsc::channel as mpsc_channel, task::JoinHandle};
-use tracing::{debug, info};
-
-use crate::{
-    aggregator::OrderbookAggregator,
-    exchange::BoxedExchange,
-    exchanges::{binance::Binance, bitstamp::Bitstamp},
-    grpc_server::start_server,
-};
-
-#[tokio::main]
-async fn main() -> Result<(), Error> {
-    tracing_subscriber::fmt::init();
-
-    let port_for_grpc = 3030;
-
-    Err(run(port_for_grpc).await)
+use clap::Parser;
+
+use tokio::runtime::Runtime;
+
+use order_book_service_server::{build_runtime, doctor, serve, serve_demo, Config};
+
+/// Aggregates orderbooks from exchanges and serves summaries over gRPC
+#[derive(Parser)]
+struct Args {
+    /// Path to a TOML config file, defaults are used when not provided
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Check the config, that the gRPC port can bind and that each exchange can be streamed from, then exit
+    #[arg(long)]
+    doctor: bool,
+    /// Serve a read-only, rate limited demo over synthetic books without connecting to any exchange
+    #[arg(long, conflicts_with = "doctor")]
+    demo: bool,
+    /// Run under the Windows service control manager, which drains the server when stopping it
+    #[cfg(windows)]
+    #[arg(long, conflicts_with_all = ["doctor", "demo"])]
+    windows_service: bool,
 }
 
-async fn run(port: u16) -> Error {
-    info!("Starting orderbook service on port :{port}...");
-
-    // Set up exchange instances
-    let binance = Binance::new();
-    let bitstamp = Bitstamp::new();
-
-    let exchanges: Vec<BoxedExchange> = vec![Box::new(binance), Box::new(bitstamp)];
-
-    // Creates a channel for the gRPC server to inform the process of new requests
-    let (new_subscriber_tx, mut new_subscriber_rx) = mpsc_channel(100);
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
 
-    // Spin up the gRPC server
-    let grpc_server_handle = tokio::spawn(start_server(new_subscriber_tx, port));
-
-    // Handle requests from the gRPC server
-    let request_handler_handle = tokio::spawn(async move {
-        // Await new subscription requests
-        while let Some((requested_pair, summary_receiver_sender)) = new_subscriber_rx.recv().await {
-            debug!("New request for {requested_pair}");
-
-            // There is no aggregator for the requested pair - a new one needs to be created.
-            let new_aggregator = OrderbookAggregator::new(&exchanges, requested_pair.clone());
-
-            // Send a receiver for the new aggregator back to the gRPC server to provide the orderbooks for the request.
-            // This receiver will be cached in the gRPC server to minimise requests to the main process.
-            let _ = summary_receiver_sender.send(new_aggregator.subscribe());
-
-            // Start the aggregator
-            tokio::spawn(new_aggregator.start());
+    if args.doctor {
+        // The doctor reports any problem loading the config itself
+        let report = Runtime::new()?.block_on(doctor(args.config.as_deref()));
+        println!("{report}");
+        if !report.is_healthy() {
+            exit(1);
         }
-        Ok(())
-    });
-
-    // The request handler will only shutdown when the new_subscriber sender closes - as part of the gRPC server shutting down.
-    match tokio::try_join!(
-        flatten_handle(grpc_server_handle),
-        flatten_handle(request_handler_handle)
-    ) {
-        Err(error) => error,
-        _ => Error::msg("Should only end due to error - exited on OK"),
-    }
-}
-
-async fn flatten_handle<T>(handle: JoinHandle<Result<T, Error>>) -> Result<T, Error> {
-    match handle.await {
-        Ok(Ok(result)) => Ok(result),
-        Ok(Err(err)) => Err(err),
-        Err(join_err) => Err(Error::from(join_err)),
+        return Ok(());
     }
-}
-
-#[cfg(test)]
-mod smoke_tests {
-    use std::time::Duration;
-
-    use futures_util::StreamExt;
-    use url::Url;
-
-    use order_book_service_client::{connect_to_summary_service, ConnectionSettings};
-    use order_book_service_types::proto::TradedPair;
-
-    use crate::run;
 
-    #[tokio::test]
-    #[ignore]
-    async fn should_provide_summaries_via_grpc() {
-        let port = 3030;
+    let config = match args.config {
+        Some(path) => Config::from_file(&path)?,
+        None => Config::default(),
+    };
 
-        // Spin up server
-        tokio::spawn(run(port));
-
-        let url_str = format!("http://0.0.0.0:{port}");
-        let connection_settings = ConnectionSettings {
-            server_address: Url::parse(&url_str).unwrap(),
-            traded_pair: TradedPair::new("ETH", "BTC"),
-            max_attempts: 10,
-            delay_between_attempts: Duration::from_secs(1),
-        };
-
-        // Connect to server via the client library
-        let mut summary_receiver = connect_to_summary_service(connection_settings).await;
-        let mut count = 0;
-
-        let mut summaries_received = Vec::new();
+    #[cfg(windows)]
+    if args.windows_service {
+        return order_book_service_server::run_windows_service(config);
+    }
 
-        // Listen to the receiver for what should be 5 summaries
-        while let Some(Ok(summary)) = summary_receiver.next().await {
-            count += 1;
-            summaries_received.push(summary);
-            if count >= 5 {
-                break;
-            }
+    // Built from the config so its threads can be pinned and prioritised
+    build_runtime(&config)?.block_on(async move {
+        if args.demo {
+            return serve_demo(config).await;
         }
-
-        // Check that the client did receive the summaries from the server
-        assert_eq!(summaries_received.len(), 5);
-    }
+        serve(config).await
+    })
 }
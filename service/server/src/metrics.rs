@@ -0,0 +1,560 @@
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Error};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Response, Server,
+};
+use once_cell::sync::Lazy;
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, register_int_gauge_vec,
+    Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    TextEncoder,
+};
+use tokio::sync::mpsc::{channel as mpsc_channel, error::SendError, Receiver, Sender};
+
+use order_book_service_types::proto::TradedPair;
+
+/// Distinct pairs given metric labels of their own, those beyond are counted together as `other` so clients requesting
+/// made up pairs can't grow the metrics without bound
+const MAX_LABELLED_PAIRS: usize = 200;
+const OTHER_PAIRS: &str = "other";
+
+static PAIR_LABELS: Lazy<PairLabels> = Lazy::new(|| PairLabels::new(MAX_LABELLED_PAIRS));
+
+static CHANNEL_CAPACITY: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_channel_capacity",
+        "Configured capacity of each channel",
+        &["channel", "pair"]
+    )
+    .expect("Metric should register")
+});
+
+static CHANNEL_HIGH_WATERMARK: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_channel_high_watermark",
+        "Most messages queued in each channel at once",
+        &["channel", "pair"]
+    )
+    .expect("Metric should register")
+});
+
+static CHANNEL_OVERFLOWS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_channel_overflows_total",
+        "Sends which found the channel full and had to wait",
+        &["channel", "pair"]
+    )
+    .expect("Metric should register")
+});
+
+static CHANNEL_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_channel_dropped_total",
+        "Messages dropped because a receiver lagged behind or hung up",
+        &["channel", "pair"]
+    )
+    .expect("Metric should register")
+});
+
+static SUMMARIES_SUPPRESSED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_summaries_suppressed_total",
+        "Summaries not broadcast because they were identical to the previous summary",
+        &["pair"]
+    )
+    .expect("Metric should register")
+});
+
+static EXCHANGE_LATENCY: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_exchange_latency_micros",
+        "Rolling mean and max time from an exchange timestamping a message to its receipt",
+        &["exchange", "stat"]
+    )
+    .expect("Metric should register")
+});
+
+static TENANT_SUBSCRIPTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_tenant_subscriptions",
+        "BookSummary subscriptions each tenant has open",
+        &["tenant"]
+    )
+    .expect("Metric should register")
+});
+
+static TENANT_REJECTED_SUBSCRIPTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_tenant_rejected_subscriptions_total",
+        "Subscriptions refused because the tenant was at its quota",
+        &["tenant"]
+    )
+    .expect("Metric should register")
+});
+
+static BOOK_LEVELS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_book_levels",
+        "Levels retained on each side of a pair's merged book",
+        &["pair", "side"]
+    )
+    .expect("Metric should register")
+});
+
+static BOOK_LEVELS_EVICTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_book_levels_evicted_total",
+        "Levels evicted from the far tail of a pair's merged book by its level cap",
+        &["pair", "side"]
+    )
+    .expect("Metric should register")
+});
+
+static CLIENT_SUBSCRIPTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_client_subscriptions",
+        "Subscriptions open by each client application, as identified by its request metadata",
+        &["client", "version"]
+    )
+    .expect("Metric should register")
+});
+
+static RPC_ACTIVE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_rpc_active",
+        "RPCs being served, including open streams",
+        &["method"]
+    )
+    .expect("Metric should register")
+});
+
+static RPC_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_rpc_requests_total",
+        "RPCs served, by the gRPC status they ended with",
+        &["method", "code"]
+    )
+    .expect("Metric should register")
+});
+
+static RPC_MESSAGES_SENT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_rpc_messages_sent_total",
+        "Response messages sent to clients",
+        &["method"]
+    )
+    .expect("Metric should register")
+});
+
+static RPC_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "orderbook_rpc_duration_seconds",
+        "How long RPCs took to serve, for streams how long they were open",
+        &["method"],
+        // From a millisecond up to a little over an hour
+        exponential_buckets(0.001, 4.0, 12).expect("Buckets should be valid")
+    )
+    .expect("Metric should register")
+});
+
+static SUMMARIES_PUBLISHED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_summaries_total",
+        "Summaries published by each pair's aggregator",
+        &["pair"]
+    )
+    .expect("Metric should register")
+});
+
+static CONNECTOR_EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_connector_events_total",
+        "Connection events of each exchange's connectors, e.g. DISCONNECTED and RESYNCED",
+        &["exchange", "event"]
+    )
+    .expect("Metric should register")
+});
+
+static INVALID_BOOKS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "orderbook_invalid_books_total",
+        "Books dropped because they were invalid, e.g. crossed or unsorted, by exchange and violation",
+        &["pair", "exchange", "violation"]
+    )
+    .expect("Metric should register")
+});
+
+static STANDBY_RELAYING: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_standby_relaying",
+        "1 while a standby relays a pair from its upstream, 0 once it has failed over to the exchanges",
+        &["pair"]
+    )
+    .expect("Metric should register")
+});
+
+/// Counts summaries suppressed as duplicates for `pair`.
+pub(crate) fn summaries_suppressed(pair: &str) -> IntCounter {
+    SUMMARIES_SUPPRESSED.with_label_values(&[pair])
+}
+
+/// Counts summaries published for `pair`.
+pub(crate) fn summaries_published(pair: &str) -> IntCounter {
+    SUMMARIES_PUBLISHED.with_label_values(&[pair])
+}
+
+/// Counts `event`s reported by `exchange`'s connectors.
+pub(crate) fn connector_events(exchange: &str, event: &str) -> IntCounter {
+    CONNECTOR_EVENTS.with_label_values(&[exchange, event])
+}
+
+/// Counts books for `pair` from `exchange` dropped for `violation`.
+pub(crate) fn invalid_books(pair: &str, exchange: &str, violation: &str) -> IntCounter {
+    INVALID_BOOKS.with_label_values(&[pair, exchange, violation])
+}
+
+/// The rolling `stat` of `exchange`'s message latency, either "mean" or "max".
+pub(crate) fn exchange_latency(exchange: &str, stat: &str) -> IntGauge {
+    EXCHANGE_LATENCY.with_label_values(&[exchange, stat])
+}
+
+/// The number of levels retained on `side` of `pair`'s merged book, either "bids" or "asks".
+pub(crate) fn book_levels(pair: &str, side: &str) -> IntGauge {
+    BOOK_LEVELS.with_label_values(&[pair, side])
+}
+
+/// Counts levels evicted from `side` of `pair`'s merged book.
+pub(crate) fn book_levels_evicted(pair: &str, side: &str) -> IntCounter {
+    BOOK_LEVELS_EVICTED.with_label_values(&[pair, side])
+}
+
+/// The number of subscriptions `tenant` has open.
+pub(crate) fn tenant_subscriptions(tenant: &str) -> IntGauge {
+    TENANT_SUBSCRIPTIONS.with_label_values(&[tenant])
+}
+
+/// The number of subscriptions open by `version` of the `client` application.
+pub(crate) fn client_subscriptions(client: &str, version: &str) -> IntGauge {
+    CLIENT_SUBSCRIPTIONS.with_label_values(&[client, version])
+}
+
+/// Counts subscriptions refused to `tenant` by its quota.
+pub(crate) fn tenant_rejected_subscriptions(tenant: &str) -> IntCounter {
+    TENANT_REJECTED_SUBSCRIPTIONS.with_label_values(&[tenant])
+}
+
+/// Whether `pair` is relayed from the standby's upstream.
+pub(crate) fn standby_relaying(pair: &str) -> IntGauge {
+    STANDBY_RELAYING.with_label_values(&[pair])
+}
+
+/// The number of `method` RPCs being served.
+pub(crate) fn rpc_active(method: &str) -> IntGauge {
+    RPC_ACTIVE.with_label_values(&[method])
+}
+
+/// Counts `method` RPCs which ended with the gRPC status `code`.
+pub(crate) fn rpc_requests(method: &str, code: &str) -> IntCounter {
+    RPC_REQUESTS.with_label_values(&[method, code])
+}
+
+/// Counts messages sent in response to `method` RPCs.
+pub(crate) fn rpc_messages_sent(method: &str) -> IntCounter {
+    RPC_MESSAGES_SENT.with_label_values(&[method])
+}
+
+/// How long `method` RPCs took, in seconds.
+pub(crate) fn rpc_duration(method: &str) -> Histogram {
+    RPC_DURATION.with_label_values(&[method])
+}
+
+static RUNTIME_THREADS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_runtime_threads",
+        "Runtime threads running with each configured thread setting, compare against the latency metrics",
+        &["setting"]
+    )
+    .expect("Metric should register")
+});
+
+#[cfg(feature = "runtime-metrics")]
+static RUNTIME_PROBE: Lazy<RuntimeProbeMetrics> = Lazy::new(|| {
+    RuntimeProbeMetrics::register(prometheus::default_registry()).expect("Metrics should register")
+});
+
+#[cfg(all(feature = "runtime-metrics", tokio_unstable))]
+static RUNTIME: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_runtime",
+        "Statistics of the async runtime as a whole",
+        &["stat"]
+    )
+    .expect("Metric should register")
+});
+
+#[cfg(all(feature = "runtime-metrics", tokio_unstable))]
+static RUNTIME_WORKER: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "orderbook_runtime_worker",
+        "Statistics of each worker thread of the async runtime, counts are since the runtime started",
+        &["worker", "stat"]
+    )
+    .expect("Metric should register")
+});
+
+/// What the runtime monitor records about each probe of the runtime.
+#[cfg(feature = "runtime-metrics")]
+#[derive(Clone)]
+pub(crate) struct RuntimeProbeMetrics {
+    /// How long each probe waited to be polled
+    pub(crate) schedule_delay: Histogram,
+    /// Probes still waiting after the stall threshold
+    pub(crate) stalls: IntCounter,
+}
+
+#[cfg(feature = "runtime-metrics")]
+impl RuntimeProbeMetrics {
+    /// Register the metrics on `registry`, the service uses the default registry, see [runtime_probe].
+    pub(crate) fn register(registry: &prometheus::Registry) -> Result<Self, prometheus::Error> {
+        let metrics = Self {
+            schedule_delay: Histogram::with_opts(
+                prometheus::HistogramOpts::new(
+                    "orderbook_runtime_schedule_delay_seconds",
+                    "How long a newly spawned task waited to be polled, long when the runtime's workers are busy or blocked",
+                )
+                .buckets(exponential_buckets(0.000_01, 4.0, 10)?),
+            )?,
+            stalls: IntCounter::new(
+                "orderbook_runtime_stalls_total",
+                "Samples where a task waited longer than the stall threshold to be polled",
+            )?,
+        };
+        registry.register(Box::new(metrics.schedule_delay.clone()))?;
+        registry.register(Box::new(metrics.stalls.clone()))?;
+        Ok(metrics)
+    }
+}
+
+/// The runtime monitor's metrics on the default registry.
+#[cfg(feature = "runtime-metrics")]
+pub(crate) fn runtime_probe() -> RuntimeProbeMetrics {
+    RUNTIME_PROBE.clone()
+}
+
+/// A statistic of the runtime, e.g. `workers`.
+#[cfg(all(feature = "runtime-metrics", tokio_unstable))]
+pub(crate) fn runtime(stat: &str) -> IntGauge {
+    RUNTIME.with_label_values(&[stat])
+}
+
+/// A statistic of one of the runtime's workers, e.g. `polls`.
+#[cfg(all(feature = "runtime-metrics", tokio_unstable))]
+pub(crate) fn runtime_worker(worker: usize, stat: &str) -> IntGauge {
+    RUNTIME_WORKER.with_label_values(&[&worker.to_string(), stat])
+}
+
+/// Runtime threads a thread `setting` was applied to, `pinned` or `prioritised`.
+pub(crate) fn runtime_threads(setting: &str) -> IntGauge {
+    RUNTIME_THREADS.with_label_values(&[setting])
+}
+
+/// The pairs given metric labels of their own, up to a maximum.
+#[derive(Debug)]
+struct PairLabels {
+    labelled: Mutex<HashSet<String>>,
+    max: usize,
+}
+
+impl PairLabels {
+    fn new(max: usize) -> Self {
+        Self {
+            labelled: Mutex::default(),
+            max,
+        }
+    }
+
+    fn label(&self, traded_pair: &TradedPair) -> String {
+        let pair = traded_pair.to_string();
+        let mut labelled = self.labelled.lock().expect("Should lock");
+        if labelled.contains(&pair) || labelled.len() < self.max {
+            labelled.insert(pair.clone());
+            pair
+        } else {
+            OTHER_PAIRS.to_string()
+        }
+    }
+}
+
+/// The `pair` label of `traded_pair`'s metrics, see [MAX_LABELLED_PAIRS].
+pub(crate) fn pair_label(traded_pair: &TradedPair) -> String {
+    PAIR_LABELS.label(traded_pair)
+}
+
+/// Records how full a channel gets and how many messages it loses, so that capacities can be sized.
+#[derive(Clone, Debug)]
+pub(crate) struct ChannelMeter {
+    high_watermark: IntGauge,
+    overflows: IntCounter,
+    dropped: IntCounter,
+}
+
+impl ChannelMeter {
+    /// `pair` should be empty for channels which aren't specific to a traded pair, or else from [pair_label].
+    pub(crate) fn new(channel: &str, pair: &str, capacity: usize) -> Self {
+        let labels = [channel, pair];
+        CHANNEL_CAPACITY
+            .with_label_values(&labels)
+            .set(capacity as i64);
+
+        Self {
+            high_watermark: CHANNEL_HIGH_WATERMARK.with_label_values(&labels),
+            overflows: CHANNEL_OVERFLOWS.with_label_values(&labels),
+            dropped: CHANNEL_DROPPED.with_label_values(&labels),
+        }
+    }
+
+    /// Record the number of messages currently queued.
+    pub(crate) fn record_len(&self, len: usize) {
+        let len = len as i64;
+        if len > self.high_watermark.get() {
+            self.high_watermark.set(len);
+        }
+    }
+
+    pub(crate) fn record_overflow(&self) {
+        self.overflows.inc();
+    }
+
+    pub(crate) fn record_dropped(&self, count: u64) {
+        self.dropped.inc_by(count);
+    }
+}
+
+/// An mpsc [Sender] which records its usage with a [ChannelMeter].
+#[derive(Debug)]
+pub(crate) struct MeteredSender<T> {
+    inner: Sender<T>,
+    meter: ChannelMeter,
+}
+
+impl<T> Clone for MeteredSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            meter: self.meter.clone(),
+        }
+    }
+}
+
+impl<T> MeteredSender<T> {
+    pub(crate) async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.inner.capacity() == 0 {
+            self.meter.record_overflow();
+        }
+
+        let result = self.inner.send(value).await;
+
+        match result {
+            Ok(_) => self
+                .meter
+                .record_len(self.inner.max_capacity() - self.inner.capacity()),
+            // The receiver has hung up
+            Err(_) => self.meter.record_dropped(1),
+        }
+
+        result
+    }
+}
+
+/// Create a bounded mpsc channel whose sending side records its usage with `meter`.
+pub(crate) fn metered_channel<T>(
+    capacity: usize,
+    meter: ChannelMeter,
+) -> (MeteredSender<T>, Receiver<T>) {
+    let (inner, receiver) = mpsc_channel(capacity);
+    (MeteredSender { inner, meter }, receiver)
+}
+
+/// Serve the metrics in the Prometheus text format on `/metrics` (or any other path).
+pub(crate) async fn serve(address: IpAddr, port: u16) -> Result<(), Error> {
+    let addr = SocketAddr::new(address, port);
+
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|_request| async {
+            Ok::<_, Infallible>(
+                Response::builder()
+                    .header(CONTENT_TYPE, TextEncoder::new().format_type())
+                    .body(Body::from(render()))
+                    .expect("Response should build"),
+            )
+        }))
+    });
+
+    Server::try_bind(&addr)
+        .context("Unable to bind metrics port")?
+        .serve(make_service)
+        .await
+        .context("Metrics server shutdown")
+}
+
+/// Render all registered metrics in the Prometheus text format.
+pub(crate) fn render() -> String {
+    let mut buffer = Vec::new();
+    // Encoding into a Vec can't fail
+    let _ = TextEncoder::new().encode(&prometheus::gather(), &mut buffer);
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::TradedPair;
+
+    use super::{metered_channel, render, ChannelMeter, PairLabels, OTHER_PAIRS};
+
+    #[test]
+    fn should_label_pairs_beyond_the_maximum_as_other() {
+        let labels = PairLabels::new(2);
+        assert_eq!(labels.label(&TradedPair::new("ETH", "BTC")), "ETH-BTC");
+        assert_eq!(labels.label(&TradedPair::new("LTC", "BTC")), "LTC-BTC");
+
+        assert_eq!(labels.label(&TradedPair::new("AAA", "ZZZ")), OTHER_PAIRS);
+        // Those already labelled keep their label
+        assert_eq!(labels.label(&TradedPair::new("ETH", "BTC")), "ETH-BTC");
+    }
+
+    #[tokio::test]
+    async fn should_record_high_watermark_and_overflows() {
+        let meter = ChannelMeter::new("test_channel", "ONE-TWO", 2);
+        let (sender, mut receiver) = metered_channel(2, meter.clone());
+
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+
+        // The channel is full so this send has to wait for a slot to be freed
+        let waiting_sender = sender.clone();
+        let waiting_send = tokio::spawn(async move { waiting_sender.send(3).await });
+        tokio::task::yield_now().await;
+        receiver.recv().await.unwrap();
+        waiting_send.await.unwrap().unwrap();
+
+        assert_eq!(meter.high_watermark.get(), 2);
+        assert_eq!(meter.overflows.get(), 1);
+
+        drop(receiver);
+        assert!(sender.send(4).await.is_err());
+        assert_eq!(meter.dropped.get(), 1);
+
+        let rendered = render();
+        assert!(rendered.contains(
+            r#"orderbook_channel_high_watermark{channel="test_channel",pair="ONE-TWO"} 2"#
+        ));
+    }
+}
@@ -0,0 +1,539 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    net::TcpStream,
+    select,
+    sync::mpsc::{
+        channel, error::TrySendError, unbounded_channel, Receiver, Sender, UnboundedReceiver,
+        UnboundedSender,
+    },
+    time::{interval_at, sleep_until, Instant},
+};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tracing::{debug, error, info, warn};
+use url::Url;
+
+use order_book_service_types::retry::Retry;
+
+use crate::{rate_limit::VenueRateLimit, tap::FrameTap};
+
+/// Attempts made to open a socket before its streams are disconnected
+const MAX_CONNECT_ATTEMPTS: usize = 3;
+/// Wait before retrying to open a socket, doubling for each retry up to the max
+const CONNECT_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_CONNECT_BACKOFF: Duration = Duration::from_secs(5);
+
+/// What a subscriber to one stream of a shared connection receives, `P` being its protocol's payload.
+#[derive(Debug, PartialEq)]
+pub(crate) enum MuxEvent<P> {
+    /// The stream has been subscribed to on a connected socket
+    Subscribed,
+    /// The payload of a frame belonging to the stream
+    Frame(P),
+    /// The shared socket has closed, nothing more will be received
+    Disconnected(String),
+}
+
+/// A message from the exchange about the connection rather than any one stream.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ControlFrame {
+    /// The exchange is about to close the socket, e.g. for maintenance, and asks to be reconnected to
+    Reconnect,
+    /// The exchange rejected a request or reported a problem, the socket stays open
+    Error(String),
+}
+
+/// What a frame from the exchange is, as told by [MuxProtocol::route].
+#[derive(Debug, PartialEq)]
+pub(crate) enum Routed<P> {
+    /// A frame belonging to a stream, with the payload to pass on to its subscribers
+    Stream(String, P),
+    /// A frame about the connection rather than any one stream
+    Control(ControlFrame),
+    /// A frame safe to ignore, such as an acknowledgement
+    Ignored,
+}
+
+/// How streams are subscribed to on an exchange's websocket, and which stream each frame belongs to.
+pub(crate) trait MuxProtocol: Send + Sync + 'static {
+    /// What subscribers are passed for each frame, e.g. the frame already parsed so it's only parsed once
+    type Payload: Clone + Send + 'static;
+
+    fn subscribe_message(&self, stream: &str) -> String;
+
+    fn unsubscribe_message(&self, stream: &str) -> String;
+
+    /// The stream a frame belongs to along with the payload to pass on, or what it asks of the connection.
+    fn route(&self, frame: &str) -> Routed<Self::Payload>;
+
+    /// The least time between messages sent to the exchange, for exchanges which limit incoming messages.
+    fn min_message_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// A message to send at an interval, for exchanges which close sockets they haven't heard from.
+    fn keepalive(&self) -> Option<(Duration, String)> {
+        None
+    }
+}
+
+/// Multiplexes the streams of many pairs over a few websockets to an exchange, rather than a socket per pair.
+///
+/// A socket is opened when a stream can't be added to an existing one, and frames are demultiplexed
+/// to each stream's subscribers. A stream is unsubscribed once its last subscriber is dropped.
+pub(crate) struct SharedConnections<P: MuxProtocol> {
+    settings: Arc<SocketSettings<P>>,
+    sockets: Arc<Mutex<Vec<SocketHandle<P>>>>,
+}
+
+impl<P: MuxProtocol> Clone for SharedConnections<P> {
+    fn clone(&self) -> Self {
+        Self {
+            settings: self.settings.clone(),
+            sockets: self.sockets.clone(),
+        }
+    }
+}
+
+struct SocketSettings<P> {
+    exchange: &'static str,
+    url: Url,
+    protocol: P,
+    max_streams_per_socket: usize,
+    channel_capacity: usize,
+    frame_tap: FrameTap,
+    rate_limit: VenueRateLimit,
+}
+
+struct SocketHandle<P: MuxProtocol> {
+    commands: UnboundedSender<Subscription<P>>,
+    streams: Arc<AtomicUsize>,
+}
+
+struct Subscription<P: MuxProtocol> {
+    stream: String,
+    subscriber: Sender<MuxEvent<P::Payload>>,
+}
+
+impl<P: MuxProtocol> SharedConnections<P> {
+    pub(crate) fn new(
+        exchange: &'static str,
+        url: Url,
+        protocol: P,
+        max_streams_per_socket: usize,
+        channel_capacity: usize,
+        frame_tap: FrameTap,
+        rate_limit: VenueRateLimit,
+    ) -> Self {
+        Self {
+            settings: Arc::new(SocketSettings {
+                exchange,
+                url,
+                protocol,
+                max_streams_per_socket,
+                channel_capacity,
+                frame_tap,
+                rate_limit,
+            }),
+            sockets: Arc::default(),
+        }
+    }
+
+    /// Subscribe to `stream`, e.g. `ethbtc@depth10@100ms`, on a socket with room for it.
+    pub(crate) fn subscribe(&self, stream: String) -> Receiver<MuxEvent<P::Payload>> {
+        let (subscriber, events) = channel(self.settings.channel_capacity);
+        let mut sockets = self.sockets.lock().expect("Should lock");
+
+        // Sockets which have closed are replaced
+        sockets.retain(|socket| !socket.commands.is_closed());
+        let socket = match sockets.iter().position(|socket| {
+            socket.streams.load(Ordering::SeqCst) < self.settings.max_streams_per_socket
+        }) {
+            Some(index) => &sockets[index],
+            None => {
+                let (commands, subscriptions) = unbounded_channel();
+                let streams = Arc::new(AtomicUsize::new(0));
+                tokio::spawn(run_socket(
+                    self.settings.clone(),
+                    subscriptions,
+                    streams.clone(),
+                ));
+                sockets.push(SocketHandle { commands, streams });
+                sockets.last().expect("A socket was just added")
+            }
+        };
+
+        socket.streams.fetch_add(1, Ordering::SeqCst);
+        if let Err(failed) = socket.commands.send(Subscription { stream, subscriber }) {
+            // The socket closed in the meantime
+            let _ = failed
+                .0
+                .subscriber
+                .try_send(MuxEvent::Disconnected("Socket closed".to_string()));
+        }
+
+        events
+    }
+}
+
+async fn run_socket<P: MuxProtocol>(
+    settings: Arc<SocketSettings<P>>,
+    mut subscriptions: UnboundedReceiver<Subscription<P>>,
+    streams: Arc<AtomicUsize>,
+) {
+    let exchange = settings.exchange;
+
+    let mut ws_stream = match connect(&settings).await {
+        Ok(ws_stream) => ws_stream,
+        Err(reason) => {
+            disconnect_pending(subscriptions, reason).await;
+            return;
+        }
+    };
+    debug!("{exchange} shared socket connected");
+
+    // Every subscriber to each stream, a stream subscribed to more than once is only sent by the exchange once
+    let mut routes: HashMap<String, Vec<Sender<MuxEvent<P::Payload>>>> = HashMap::new();
+    let mut next_send = Instant::now();
+    let keepalive = settings.protocol.keepalive();
+    // Never ticks without a keepalive, the branch is disabled
+    let keepalive_period = keepalive
+        .as_ref()
+        .map_or(Duration::from_secs(3600), |(period, _)| *period);
+    let mut keepalives = interval_at(Instant::now() + keepalive_period, keepalive_period);
+
+    let reason = loop {
+        select! {
+            subscription = subscriptions.recv() => {
+                // Every handle to the socket has been dropped
+                let Some(Subscription { stream, subscriber }) = subscription else {
+                    break "Connection closed".to_string();
+                };
+
+                if let Some(subscribers) = routes.get_mut(&stream) {
+                    // Already streamed, so it doesn't count towards the socket's streams again
+                    streams.fetch_sub(1, Ordering::SeqCst);
+                    let _ = subscriber.send(MuxEvent::Subscribed).await;
+                    subscribers.push(subscriber);
+                    continue;
+                }
+
+                sleep_until(next_send).await;
+                next_send = Instant::now() + settings.protocol.min_message_interval();
+                let request = settings.protocol.subscribe_message(&stream);
+                if let Err(ws_err) = ws_stream.send(Message::Text(request)).await {
+                    let _ = subscriber.send(MuxEvent::Disconnected(ws_err.to_string())).await;
+                    break ws_err.to_string();
+                }
+
+                debug!("{exchange} subscribed to {stream}");
+                let _ = subscriber.send(MuxEvent::Subscribed).await;
+                routes.insert(stream, vec![subscriber]);
+            }
+            _ = keepalives.tick(), if keepalive.is_some() => {
+                let (_, message) = keepalive.as_ref().expect("Only ticks with a keepalive");
+                if let Err(ws_err) = ws_stream.send(Message::Text(message.clone())).await {
+                    break ws_err.to_string();
+                }
+            }
+            msg = ws_stream.next() => {
+                let msg = match msg {
+                    Some(Ok(msg)) => msg,
+                    Some(Err(ws_err)) => break ws_err.to_string(),
+                    None => break "Stream ended".to_string(),
+                };
+
+                let frame = msg.to_string();
+                settings.frame_tap.record(exchange, &frame);
+                if let Message::Close(close_frame) = &msg {
+                    settings.rate_limit.check_close_frame(close_frame.as_ref());
+                    continue;
+                }
+
+                let (stream, payload) = match settings.protocol.route(&frame) {
+                    Routed::Stream(stream, payload) => (stream, payload),
+                    Routed::Control(ControlFrame::Reconnect) => {
+                        info!("{exchange} requested a reconnect, resubscribing {} streams", routes.len());
+                        match reconnect(&settings, &mut ws_stream, routes.keys(), &mut next_send).await {
+                            Ok(()) => continue,
+                            Err(reason) => break reason,
+                        }
+                    }
+                    Routed::Control(ControlFrame::Error(message)) => {
+                        warn!("{exchange} reported an error: {message}");
+                        continue;
+                    }
+                    Routed::Ignored => continue,
+                };
+                let Some(subscribers) = routes.get_mut(&stream) else {
+                    continue;
+                };
+                // A slow subscriber misses frames rather than holding up every stream on the socket
+                subscribers.retain(|subscriber| match subscriber.try_send(MuxEvent::Frame(payload.clone())) {
+                    Ok(()) => true,
+                    Err(TrySendError::Full(_)) => {
+                        debug!("{exchange} subscriber to {stream} is lagging, dropping a frame");
+                        true
+                    }
+                    Err(TrySendError::Closed(_)) => false,
+                });
+                if subscribers.is_empty() {
+                    // The subscribers have gone, the exchange no longer needs to send the stream
+                    routes.remove(&stream);
+                    streams.fetch_sub(1, Ordering::SeqCst);
+
+                    sleep_until(next_send).await;
+                    next_send = Instant::now() + settings.protocol.min_message_interval();
+                    let request = settings.protocol.unsubscribe_message(&stream);
+                    if let Err(ws_err) = ws_stream.send(Message::Text(request)).await {
+                        break ws_err.to_string();
+                    }
+                    debug!("{exchange} unsubscribed from {stream}");
+                }
+            }
+        }
+    };
+
+    for subscriber in routes.drain().flat_map(|(_, subscribers)| subscribers) {
+        let _ = subscriber
+            .send(MuxEvent::Disconnected(reason.clone()))
+            .await;
+    }
+    disconnect_pending(subscriptions, reason).await;
+}
+
+/// Open a socket, retrying failed attempts each paced by the exchange's rate limit.
+async fn connect<P>(
+    settings: &SocketSettings<P>,
+) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, String> {
+    let exchange = settings.exchange;
+    let mut retry = Retry::exponential(CONNECT_BACKOFF, MAX_CONNECT_BACKOFF)
+        .with_max_attempts(MAX_CONNECT_ATTEMPTS)
+        .with_jitter(CONNECT_BACKOFF / 2);
+    let mut connect_error = String::new();
+    while retry.next_attempt().await {
+        settings.rate_limit.acquire().await;
+        match connect_async(&settings.url).await {
+            Ok((ws_stream, _)) => return Ok(ws_stream),
+            Err(ws_err) => {
+                error!("\nWebsocket Error ({exchange}):\n{ws_err}");
+                settings.rate_limit.check_connect_error(&ws_err);
+                connect_error = ws_err.to_string();
+            }
+        }
+    }
+    Err(format!("Unable to connect: {connect_error}"))
+}
+
+/// Replace the socket with a new one subscribed to the same streams, so they carry on rather than ending when the
+/// exchange closes the old one.
+async fn reconnect<P: MuxProtocol>(
+    settings: &SocketSettings<P>,
+    ws_stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+    streams: impl Iterator<Item = &String>,
+    next_send: &mut Instant,
+) -> Result<(), String> {
+    let _ = ws_stream.close(None).await;
+    *ws_stream = connect(settings).await?;
+    for stream in streams {
+        sleep_until(*next_send).await;
+        *next_send = Instant::now() + settings.protocol.min_message_interval();
+        let request = settings.protocol.subscribe_message(stream);
+        ws_stream
+            .send(Message::Text(request))
+            .await
+            .map_err(|ws_err| ws_err.to_string())?;
+    }
+    Ok(())
+}
+
+/// Close the socket to new subscriptions and tell those already queued that it has gone.
+async fn disconnect_pending<P: MuxProtocol>(
+    mut subscriptions: UnboundedReceiver<Subscription<P>>,
+    reason: String,
+) {
+    subscriptions.close();
+    while let Some(Subscription { subscriber, .. }) = subscriptions.recv().await {
+        let _ = subscriber
+            .send(MuxEvent::Disconnected(reason.clone()))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{accept_async, tungstenite::Message};
+    use url::Url;
+
+    use crate::{
+        config::{RateLimitConfig, TapConfig},
+        exchange::ExchangeInfo,
+        rate_limit::RateLimits,
+        tap::FrameTap,
+    };
+
+    use super::{ControlFrame, MuxEvent, MuxProtocol, Routed, SharedConnections};
+
+    /// Frames are `<stream>:<payload>`, subscriptions are `+<stream>`.
+    struct TestProtocol;
+
+    impl MuxProtocol for TestProtocol {
+        type Payload = String;
+
+        fn subscribe_message(&self, stream: &str) -> String {
+            format!("+{stream}")
+        }
+
+        fn unsubscribe_message(&self, stream: &str) -> String {
+            format!("-{stream}")
+        }
+
+        fn route(&self, frame: &str) -> Routed<String> {
+            if frame == "!reconnect" {
+                return Routed::Control(ControlFrame::Reconnect);
+            }
+            match frame.split_once(':') {
+                Some((stream, payload)) => Routed::Stream(stream.to_string(), payload.to_string()),
+                None => Routed::Ignored,
+            }
+        }
+    }
+
+    fn test_connections(address: SocketAddr) -> SharedConnections<TestProtocol> {
+        SharedConnections::new(
+            "Test",
+            Url::parse(&format!("ws://{address}")).unwrap(),
+            TestProtocol,
+            10,
+            10,
+            FrameTap::new(TapConfig::default()),
+            RateLimits::new(RateLimitConfig::default()).venue(&ExchangeInfo::new("Test")),
+        )
+    }
+
+    #[tokio::test]
+    async fn should_demultiplex_streams_over_one_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // Echo each subscription back as a frame of its stream, only a single connection is accepted
+        tokio::spawn(async move {
+            let (connection, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(connection).await.unwrap();
+            while let Some(Ok(Message::Text(request))) = ws_stream.next().await {
+                if let Some(stream) = request.strip_prefix('+') {
+                    let frame = format!("{stream}:book for {stream}");
+                    ws_stream.send(Message::Text(frame)).await.unwrap();
+                }
+            }
+        });
+
+        let connections = test_connections(address);
+
+        let mut eth_btc = connections.subscribe("ethbtc".to_string());
+        let mut ltc_btc = connections.subscribe("ltcbtc".to_string());
+
+        assert_eq!(eth_btc.recv().await, Some(MuxEvent::Subscribed));
+        assert_eq!(
+            eth_btc.recv().await,
+            Some(MuxEvent::Frame("book for ethbtc".to_string()))
+        );
+        assert_eq!(ltc_btc.recv().await, Some(MuxEvent::Subscribed));
+        assert_eq!(
+            ltc_btc.recv().await,
+            Some(MuxEvent::Frame("book for ltcbtc".to_string()))
+        );
+        assert_eq!(connections.sockets.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_share_a_stream_between_its_subscribers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (requests_tx, mut requests) = tokio::sync::mpsc::unbounded_channel();
+
+        // Sends a frame of the stream on each subscription, reporting every request
+        tokio::spawn(async move {
+            let (connection, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(connection).await.unwrap();
+            let mut frames = 0;
+            while let Some(Ok(Message::Text(request))) = ws_stream.next().await {
+                let _ = requests_tx.send(request);
+                frames += 1;
+                let frame = format!("ethbtc:frame {frames}");
+                ws_stream.send(Message::Text(frame)).await.unwrap();
+            }
+        });
+
+        let connections = test_connections(address);
+        let mut first = connections.subscribe("ethbtc".to_string());
+        assert_eq!(first.recv().await, Some(MuxEvent::Subscribed));
+        assert_eq!(
+            first.recv().await,
+            Some(MuxEvent::Frame("frame 1".to_string()))
+        );
+
+        let second = connections.subscribe("ethbtc".to_string());
+        drop(second);
+        let mut third = connections.subscribe("ltcbtc".to_string());
+        assert_eq!(third.recv().await, Some(MuxEvent::Subscribed));
+        // Delivered to both the first subscriber and the dropped second, which is then removed
+        assert_eq!(
+            first.recv().await,
+            Some(MuxEvent::Frame("frame 2".to_string()))
+        );
+
+        let requests = std::iter::from_fn(|| requests.try_recv().ok()).collect::<Vec<_>>();
+        assert_eq!(requests, vec!["+ethbtc", "+ltcbtc"]);
+        // The stream is only counted once, the dropped subscriber didn't unsubscribe it
+        assert_eq!(
+            connections.sockets.lock().unwrap()[0]
+                .streams
+                .load(std::sync::atomic::Ordering::SeqCst),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn should_resubscribe_streams_when_asked_to_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // The first connection asks to be reconnected to, the second answers the resubscription
+        tokio::spawn(async move {
+            for reply in ["!reconnect", "ethbtc:book after reconnecting"] {
+                let (connection, _) = listener.accept().await.unwrap();
+                let mut ws_stream = accept_async(connection).await.unwrap();
+                let request = ws_stream.next().await.unwrap().unwrap();
+                assert_eq!(request, Message::Text("+ethbtc".to_string()));
+                ws_stream
+                    .send(Message::Text(reply.to_string()))
+                    .await
+                    .unwrap();
+                tokio::spawn(async move { while ws_stream.next().await.is_some() {} });
+            }
+        });
+
+        let connections = test_connections(address);
+        let mut eth_btc = connections.subscribe("ethbtc".to_string());
+
+        assert_eq!(eth_btc.recv().await, Some(MuxEvent::Subscribed));
+        // The stream carries on without being disconnected
+        assert_eq!(
+            eth_btc.recv().await,
+            Some(MuxEvent::Frame("book after reconnecting".to_string()))
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Display, Formatter},
+    sync::Mutex,
+    time::Duration,
+};
+
+use futures::future::{join_all, BoxFuture};
+use tokio::time::timeout;
+use tracing::warn;
+
+use order_book_service_types::proto::{ExchangeId, ExchangePairs, SupportedPairs, TradedPair};
+
+use crate::{
+    config::{Config, QuoteConversionConfig},
+    conversion::source_pair_for_exchange,
+    error::{error_chain, ExchangeError, ServerError},
+    exchange::BoxedExchange,
+};
+
+/// How long to wait for an exchange to list its pairs
+const LIST_PAIRS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A pending request for the pairs an exchange offers.
+pub(crate) type PairsRequest = BoxFuture<'static, Result<Vec<TradedPair>, ExchangeError>>;
+
+/// Queries each exchange for the pairs it offers, for the ListSupportedPairs RPC.
+pub(crate) struct PairDirectory {
+    // Exchanges aren't Sync, the lock allows the directory to be shared by the gRPC service
+    exchanges: Mutex<Vec<BoxedExchange>>,
+}
+
+impl Debug for PairDirectory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PairDirectory").finish_non_exhaustive()
+    }
+}
+
+impl PairDirectory {
+    pub(crate) fn new(exchanges: &[BoxedExchange]) -> Self {
+        Self {
+            exchanges: Mutex::new(exchanges.to_vec()),
+        }
+    }
+
+    /// The exchanges summaries are aggregated from.
+    pub(crate) fn exchanges(&self) -> Vec<ExchangeId> {
+        self.exchanges
+            .lock()
+            .expect("Should lock")
+            .iter()
+            .map(|exchange| exchange.id())
+            .collect()
+    }
+
+    /// Query every exchange concurrently, an exchange which can't be queried is reported with its error.
+    pub(crate) async fn list(&self) -> SupportedPairs {
+        let requests = self
+            .exchanges
+            .lock()
+            .expect("Should lock")
+            .iter()
+            .map(|exchange| (exchange.id(), exchange.supported_pairs()))
+            .collect::<Vec<_>>();
+
+        let results = join_all(requests.into_iter().map(|(exchange, request)| async move {
+            let pairs = timeout(LIST_PAIRS_TIMEOUT, request)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(ExchangeError::Timeout {
+                        exchange: exchange.clone(),
+                        resource: "pairs",
+                    })
+                });
+            (exchange, pairs)
+        }))
+        .await;
+
+        supported_pairs(results)
+    }
+}
+
+/// Whether the pairs from an exchange's `listing` include `traded_pair`, `None` when the exchange can't be asked.
+pub(crate) async fn still_listed(listing: PairsRequest, traded_pair: &TradedPair) -> Option<bool> {
+    match timeout(LIST_PAIRS_TIMEOUT, listing).await {
+        Ok(Ok(pairs)) => Some(lists(&pairs, traded_pair)),
+        Ok(Err(err)) => {
+            warn!(
+                "Unable to check whether {traded_pair} is still listed: {}",
+                error_chain(&err)
+            );
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Whether `pairs` include `traded_pair`, exchanges differ in the case of their symbols.
+fn lists(pairs: &[TradedPair], traded_pair: &TradedPair) -> bool {
+    pairs.iter().any(|pair| {
+        pair.first.eq_ignore_ascii_case(&traded_pair.first)
+            && pair.second.eq_ignore_ascii_case(&traded_pair.second)
+    })
+}
+
+/// A warm-up pair which some exchanges don't list.
+#[derive(Debug, PartialEq)]
+pub(crate) struct UnlistedWarmUpPair {
+    pub(crate) pair: TradedPair,
+    /// The exchanges not listing the pair, or the pair it's sourced from on that exchange
+    pub(crate) missing: Vec<ExchangeId>,
+    /// Whether enough exchanges list the pair, or couldn't be asked, for it to still be aggregated
+    pub(crate) aggregatable: bool,
+}
+
+impl Display for UnlistedWarmUpPair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let missing = self
+            .missing
+            .iter()
+            .map(ExchangeId::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{} isn't listed by {missing}", self.pair)?;
+        if !self.aggregatable {
+            write!(f, ", so can't be aggregated")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check each warm-up pair against the pairs each exchange lists, returning those which any exchange doesn't.
+///
+/// An exchange which couldn't be asked is assumed to list them.
+pub(crate) fn unlisted_warm_up_pairs(
+    listing: &SupportedPairs,
+    warm_up_pairs: &[TradedPair],
+    conversions: &[QuoteConversionConfig],
+    min_sources: usize,
+) -> Vec<UnlistedWarmUpPair> {
+    warm_up_pairs
+        .iter()
+        .filter_map(|pair| {
+            let missing = listing
+                .exchanges
+                .iter()
+                .filter(|exchange| exchange.error.is_empty())
+                .map(|exchange| {
+                    (
+                        ExchangeId::from(exchange.exchange.as_str()),
+                        &exchange.pairs,
+                    )
+                })
+                .filter(|(exchange, pairs)| {
+                    !lists(
+                        pairs,
+                        &source_pair_for_exchange(conversions, pair, exchange),
+                    )
+                })
+                .map(|(exchange, _)| exchange)
+                .collect::<Vec<_>>();
+            (!missing.is_empty()).then(|| UnlistedWarmUpPair {
+                pair: pair.clone(),
+                aggregatable: listing.exchanges.len() - missing.len() >= min_sources,
+                missing,
+            })
+        })
+        .collect()
+}
+
+/// Check the configured warm-up pairs can be aggregated before the server starts, failing when `strict_warm_up_pairs`
+/// is set rather than only warning, so a misconfigured pair isn't first noticed by its subscribers.
+pub(crate) async fn check_warm_up_pairs(
+    directory: &PairDirectory,
+    config: &Config,
+) -> Result<(), ServerError> {
+    let warm_up_pairs = config.warm_up_pairs();
+    if warm_up_pairs.is_empty() {
+        return Ok(());
+    }
+
+    let listing = directory.list().await;
+    for exchange in listing
+        .exchanges
+        .iter()
+        .filter(|exchange| !exchange.error.is_empty())
+    {
+        warn!(
+            "Unable to check the warm-up pairs are listed by {}: {}",
+            exchange.exchange, exchange.error
+        );
+    }
+    let unlisted = unlisted_warm_up_pairs(
+        &listing,
+        &warm_up_pairs,
+        &config.quote_conversions,
+        config.aggregator.min_sources(),
+    );
+    for pair in unlisted.iter() {
+        warn!("Warm-up pair {pair}");
+    }
+
+    let unaggregatable = unlisted
+        .iter()
+        .filter(|pair| !pair.aggregatable)
+        .map(UnlistedWarmUpPair::to_string)
+        .collect::<Vec<_>>();
+    if config.strict_warm_up_pairs && !unaggregatable.is_empty() {
+        return Err(ServerError::UnlistedWarmUpPairs(unaggregatable.join("; ")));
+    }
+    Ok(())
+}
+
+/// Combine each exchange's pairs, finding those offered by more than one exchange.
+fn supported_pairs(
+    results: Vec<(ExchangeId, Result<Vec<TradedPair>, ExchangeError>)>,
+) -> SupportedPairs {
+    // Exchanges differ in the case of their symbols
+    let mut offered_by = BTreeMap::<(String, String), usize>::new();
+
+    let exchanges = results
+        .into_iter()
+        .map(|(exchange, result)| match result {
+            Ok(pairs) => {
+                for pair in pairs.iter() {
+                    *offered_by
+                        .entry((pair.first.to_uppercase(), pair.second.to_uppercase()))
+                        .or_default() += 1;
+                }
+                ExchangePairs {
+                    exchange: exchange.to_string(),
+                    pairs,
+                    error: String::new(),
+                }
+            }
+            Err(err) => ExchangePairs {
+                exchange: exchange.to_string(),
+                pairs: Vec::new(),
+                error: error_chain(&err),
+            },
+        })
+        .collect();
+
+    let pairs = offered_by
+        .into_iter()
+        .filter(|(_, exchanges)| *exchanges > 1)
+        .map(|((first, second), _)| TradedPair { first, second })
+        .collect();
+
+    SupportedPairs { pairs, exchanges }
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::{ExchangeId, TradedPair};
+
+    use crate::{config::QuoteConversionConfig, error::ExchangeError};
+
+    use super::{
+        still_listed, supported_pairs, unlisted_warm_up_pairs, PairsRequest, UnlistedWarmUpPair,
+    };
+
+    #[test]
+    fn should_only_list_pairs_offered_by_multiple_exchanges() {
+        let supported = supported_pairs(vec![
+            (
+                ExchangeId::Binance,
+                Ok(vec![
+                    TradedPair::new("ETH", "BTC"),
+                    TradedPair::new("BNB", "BTC"),
+                ]),
+            ),
+            (
+                ExchangeId::Bitstamp,
+                Ok(vec![
+                    TradedPair::new("eth", "btc"),
+                    TradedPair::new("BTC", "GBP"),
+                ]),
+            ),
+            (
+                ExchangeId::Other("Kraken".to_string()),
+                Err(ExchangeError::Timeout {
+                    exchange: ExchangeId::Other("Kraken".to_string()),
+                    resource: "pairs",
+                }),
+            ),
+        ]);
+
+        assert_eq!(supported.pairs, vec![TradedPair::new("ETH", "BTC")]);
+        assert_eq!(supported.exchanges.len(), 3);
+        assert_eq!(
+            supported.exchanges[2].error,
+            "Timed out requesting Kraken pairs"
+        );
+    }
+
+    #[test]
+    fn should_report_warm_up_pairs_exchanges_do_not_list() {
+        let listing = supported_pairs(vec![
+            (
+                ExchangeId::Binance,
+                Ok(vec![
+                    TradedPair::new("ETH", "BTC"),
+                    TradedPair::new("BTC", "USDT"),
+                ]),
+            ),
+            (
+                ExchangeId::Bitstamp,
+                Ok(vec![
+                    TradedPair::new("eth", "btc"),
+                    TradedPair::new("btc", "usd"),
+                ]),
+            ),
+            (
+                ExchangeId::Other("Kraken".to_string()),
+                Err(ExchangeError::Timeout {
+                    exchange: ExchangeId::Other("Kraken".to_string()),
+                    resource: "pairs",
+                }),
+            ),
+        ]);
+        // Binance's BTC-USD book is sourced from BTC-USDT
+        let conversions = vec![QuoteConversionConfig {
+            exchange: "Binance".to_string(),
+            quote: "USD".to_string(),
+            source_quote: "USDT".to_string(),
+            rate: Some(1.0),
+            rate_exchange: None,
+        }];
+
+        let unlisted = unlisted_warm_up_pairs(
+            &listing,
+            &[
+                TradedPair::new("ETH", "BTC"),
+                TradedPair::new("BTC", "USD"),
+                TradedPair::new("LTC", "BTC"),
+            ],
+            &conversions,
+            2,
+        );
+
+        assert_eq!(
+            unlisted,
+            vec![UnlistedWarmUpPair {
+                pair: TradedPair::new("LTC", "BTC"),
+                missing: vec![ExchangeId::Binance, ExchangeId::Bitstamp],
+                // Kraken might list it, but one exchange isn't enough
+                aggregatable: false,
+            }]
+        );
+        assert_eq!(
+            unlisted[0].to_string(),
+            "LTC-BTC isn't listed by Binance, Bitstamp, so can't be aggregated"
+        );
+
+        // In degraded mode Kraken alone is enough
+        let unlisted =
+            unlisted_warm_up_pairs(&listing, &[TradedPair::new("LTC", "BTC")], &conversions, 1);
+        assert!(unlisted[0].aggregatable);
+    }
+
+    #[tokio::test]
+    async fn should_check_whether_a_pair_is_still_listed() {
+        let listing =
+            || -> PairsRequest { Box::pin(async { Ok(vec![TradedPair::new("eth", "btc")]) }) };
+        assert_eq!(
+            still_listed(listing(), &TradedPair::new("ETH", "BTC")).await,
+            Some(true)
+        );
+        assert_eq!(
+            still_listed(listing(), &TradedPair::new("LTC", "BTC")).await,
+            Some(false)
+        );
+
+        let failing = Box::pin(async {
+            Err(ExchangeError::Timeout {
+                exchange: ExchangeId::Binance,
+                resource: "pairs",
+            })
+        });
+        assert_eq!(
+            still_listed(failing, &TradedPair::new("ETH", "BTC")).await,
+            None
+        );
+    }
+}
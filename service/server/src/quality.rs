@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use order_book_service_types::proto::Quality;
+
+/// Books up to this old are fully fresh.
+const FRESH_AGE: Duration = Duration::from_secs(1);
+/// Books this old or older count for nothing towards freshness.
+const STALE_AGE: Duration = Duration::from_secs(10);
+/// Scores from which a summary is [Quality::Good], or else [Quality::Degraded].
+const GOOD_SCORE: f64 = 0.8;
+const DEGRADED_SCORE: f64 = 0.5;
+
+/// What a summary's quality is judged on, gathered as its books are merged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct QualityInputs {
+    /// Exchanges whose books were merged
+    pub(crate) contributing: usize,
+    /// Exchanges the pair is aggregated from
+    pub(crate) aggregated: usize,
+    /// Age of the oldest merged book, by its corrected timestamp
+    pub(crate) max_age: Duration,
+    /// Largest relative distance of an exchange's mid price from the median of all of them
+    pub(crate) mid_dispersion: f64,
+}
+
+impl QualityInputs {
+    /// A score from 0 to 1 and its coarse grade, the product of the share of exchanges contributing, the freshness of
+    /// the oldest book and how closely the exchanges' mid prices agree against `max_mid_deviation`.
+    pub(crate) fn assess(&self, max_mid_deviation: f64) -> (f64, Quality) {
+        let coverage = self.contributing as f64 / self.aggregated.max(1) as f64;
+        let freshness = 1.0
+            - (self.max_age.saturating_sub(FRESH_AGE).as_secs_f64()
+                / (STALE_AGE - FRESH_AGE).as_secs_f64());
+        let agreement = if max_mid_deviation > 0.0 {
+            1.0 - self.mid_dispersion / max_mid_deviation
+        } else {
+            1.0
+        };
+
+        let score = [coverage, freshness, agreement]
+            .iter()
+            .map(|factor| factor.clamp(0.0, 1.0))
+            .product::<f64>();
+        let quality = if score >= GOOD_SCORE {
+            Quality::Good
+        } else if score >= DEGRADED_SCORE {
+            Quality::Degraded
+        } else {
+            Quality::Poor
+        };
+        (score, quality)
+    }
+}
+
+/// The largest relative distance of any of `mids` from their median, 0 with fewer than two.
+pub(crate) fn mid_dispersion(mids: &mut [f64]) -> f64 {
+    if mids.len() < 2 {
+        return 0.0;
+    }
+    mids.sort_by(f64::total_cmp);
+    let middle = mids.len() / 2;
+    let median = if mids.len().is_multiple_of(2) {
+        (mids[middle - 1] + mids[middle]) / 2.0
+    } else {
+        mids[middle]
+    };
+    if median <= 0.0 {
+        return 0.0;
+    }
+    mids.iter()
+        .map(|mid| (mid - median).abs() / median)
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use order_book_service_types::proto::Quality;
+
+    use super::{mid_dispersion, QualityInputs};
+
+    #[test]
+    fn should_measure_mid_dispersion_from_the_median() {
+        assert_eq!(mid_dispersion(&mut [100.0]), 0.0);
+        assert!((mid_dispersion(&mut [99.0, 101.0]) - 0.01).abs() < 1e-9);
+        assert!((mid_dispersion(&mut [102.0, 100.0, 100.0]) - 0.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn should_grade_summaries_by_sources_age_and_agreement() {
+        let healthy = QualityInputs {
+            contributing: 2,
+            aggregated: 2,
+            max_age: Duration::from_millis(200),
+            mid_dispersion: 0.0,
+        };
+        assert_eq!(healthy.assess(0.05), (1.0, Quality::Good));
+
+        // Half the exchanges
+        let (score, quality) = QualityInputs {
+            contributing: 1,
+            ..healthy
+        }
+        .assess(0.05);
+        assert_eq!((score, quality), (0.5, Quality::Degraded));
+
+        // Halfway to stale and the mids half as far apart as is tolerated
+        let (score, quality) = QualityInputs {
+            max_age: Duration::from_millis(5500),
+            mid_dispersion: 0.025,
+            ..healthy
+        }
+        .assess(0.05);
+        assert!((score - 0.25).abs() < 1e-9);
+        assert_eq!(quality, Quality::Poor);
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::{sleep_until, Instant};
+use tokio_tungstenite::tungstenite::{
+    self,
+    http::{header::RETRY_AFTER, StatusCode},
+    protocol::{frame::coding::CloseCode, CloseFrame},
+};
+use tracing::warn;
+
+use order_book_service_types::proto::ExchangeId;
+
+use crate::{config::RateLimitConfig, exchange::ExchangeInfo};
+
+/// The window connection attempts are counted over.
+const WINDOW: Duration = Duration::from_secs(60);
+/// Binance responds with this status once an IP has been banned for ignoring 429s.
+const IP_BANNED: u16 = 418;
+
+/// Rate limit guards for each exchange, shared by every connector so that limits apply per venue
+/// rather than per aggregator.
+#[derive(Clone, Debug)]
+pub(crate) struct RateLimits {
+    config: RateLimitConfig,
+    venues: Arc<Mutex<HashMap<ExchangeId, VenueRateLimit>>>,
+}
+
+impl RateLimits {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            venues: Arc::default(),
+        }
+    }
+
+    /// The guard for the exchange, created on first use. Connections are limited to the lower of the configured and
+    /// the venue's published limits.
+    pub(crate) fn venue(&self, info: &ExchangeInfo) -> VenueRateLimit {
+        let configured = self.config.max_connections_per_minute;
+        let max_attempts = info
+            .rate_limit
+            .max_connections_per_minute
+            .map_or(configured, |published| published.min(configured));
+        self.venues
+            .lock()
+            .expect("Should lock")
+            .entry(info.id.clone())
+            .or_insert_with(|| VenueRateLimit {
+                exchange: info.id.clone(),
+                default_backoff: self.config.default_backoff(),
+                state: Arc::new(Mutex::new(VenueState::new(max_attempts))),
+            })
+            .clone()
+    }
+}
+
+/// Paces connection attempts to a single exchange and holds them off while it is signalling a rate limit.
+#[derive(Clone, Debug)]
+pub(crate) struct VenueRateLimit {
+    exchange: ExchangeId,
+    default_backoff: Duration,
+    state: Arc<Mutex<VenueState>>,
+}
+
+impl VenueRateLimit {
+    /// Wait until another connection attempt can be made to the exchange.
+    pub(crate) async fn acquire(&self) {
+        loop {
+            let wait = self
+                .state
+                .lock()
+                .expect("Should lock")
+                .attempt(Instant::now());
+            match wait {
+                Ok(()) => break,
+                Err(until) => sleep_until(until).await,
+            }
+        }
+    }
+
+    /// Stop every connector for the exchange from connecting for `retry_after`, or the default backoff when not given.
+    pub(crate) fn back_off(&self, retry_after: Option<Duration>) {
+        let retry_after = retry_after.unwrap_or(self.default_backoff);
+        warn!(
+            "{} is rate limiting connections, backing off for {}s",
+            self.exchange,
+            retry_after.as_secs()
+        );
+        self.state
+            .lock()
+            .expect("Should lock")
+            .block(Instant::now() + retry_after);
+    }
+
+    /// Back off if a failed connection attempt was refused due to a rate limit.
+    pub(crate) fn check_connect_error(&self, error: &tungstenite::Error) {
+        if let Some(retry_after) = connect_error_signal(error) {
+            self.back_off(retry_after);
+        }
+    }
+
+    /// Back off if the exchange closed the stream due to a rate limit.
+    pub(crate) fn check_close_frame(&self, frame: Option<&CloseFrame>) {
+        if frame.is_some_and(|frame| frame.code == CloseCode::Policy) {
+            self.back_off(None);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct VenueState {
+    max_attempts: usize,
+    attempts: VecDeque<Instant>,
+    blocked_until: Option<Instant>,
+}
+
+impl VenueState {
+    fn new(max_attempts: usize) -> Self {
+        Self {
+            max_attempts,
+            attempts: VecDeque::new(),
+            blocked_until: None,
+        }
+    }
+
+    /// Record an attempt, or return when one can next be made.
+    fn attempt(&mut self, now: Instant) -> Result<(), Instant> {
+        if let Some(blocked_until) = self.blocked_until.filter(|until| *until > now) {
+            return Err(blocked_until);
+        }
+
+        while self
+            .attempts
+            .front()
+            .is_some_and(|attempt| *attempt + WINDOW <= now)
+        {
+            self.attempts.pop_front();
+        }
+
+        match self.attempts.front() {
+            Some(oldest) if self.attempts.len() >= self.max_attempts => Err(*oldest + WINDOW),
+            _ => {
+                self.attempts.push_back(now);
+                Ok(())
+            }
+        }
+    }
+
+    fn block(&mut self, until: Instant) {
+        self.blocked_until = Some(
+            self.blocked_until
+                .map_or(until, |blocked| blocked.max(until)),
+        );
+    }
+}
+
+/// `Some` when the error is a rate limit response, holding its `Retry-After` if given.
+fn connect_error_signal(error: &tungstenite::Error) -> Option<Option<Duration>> {
+    let tungstenite::Error::Http(response) = error else {
+        return None;
+    };
+
+    let status = response.status();
+    if status != StatusCode::TOO_MANY_REQUESTS && status.as_u16() != IP_BANNED {
+        return None;
+    }
+
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs);
+    Some(retry_after)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+    use tokio_tungstenite::tungstenite::{self, http::Response};
+
+    use super::{connect_error_signal, VenueState, WINDOW};
+
+    #[test]
+    fn should_limit_attempts_per_window_and_respect_blocks() {
+        let mut state = VenueState::new(2);
+        let start = Instant::now();
+
+        assert!(state.attempt(start).is_ok());
+        assert!(state.attempt(start + Duration::from_secs(1)).is_ok());
+        assert_eq!(
+            state.attempt(start + Duration::from_secs(2)),
+            Err(start + WINDOW)
+        );
+        assert!(state.attempt(start + WINDOW).is_ok());
+
+        let blocked_until = start + Duration::from_secs(300);
+        state.block(blocked_until);
+        // A shorter block doesn't cut an existing one short
+        state.block(start + Duration::from_secs(100));
+        assert_eq!(state.attempt(start + WINDOW * 3), Err(blocked_until));
+        assert!(state.attempt(blocked_until).is_ok());
+    }
+
+    #[test]
+    fn should_recognise_rate_limit_responses() {
+        let too_many_requests = tungstenite::Error::Http(
+            Response::builder()
+                .status(429)
+                .header("Retry-After", "120")
+                .body(None)
+                .unwrap(),
+        );
+        assert_eq!(
+            connect_error_signal(&too_many_requests),
+            Some(Some(Duration::from_secs(120)))
+        );
+
+        let banned = tungstenite::Error::Http(Response::builder().status(418).body(None).unwrap());
+        assert_eq!(connect_error_signal(&banned), Some(None));
+
+        let not_found =
+            tungstenite::Error::Http(Response::builder().status(404).body(None).unwrap());
+        assert_eq!(connect_error_signal(&not_found), None);
+    }
+}
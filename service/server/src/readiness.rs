@@ -0,0 +1,168 @@
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::{env, io, time::Duration};
+
+use tokio::{
+    sync::{
+        broadcast::{error::RecvError, Receiver},
+        oneshot,
+    },
+    time::interval,
+};
+use tracing::{info, warn};
+
+use order_book_service_types::proto::{ConnectorEvent, ConnectorStatus};
+
+/// Notifies a service manager, e.g. systemd with `Type=notify`, of the service's readiness and that it's still alive.
+///
+/// Speaks the sd_notify protocol, a datagram to the Unix socket in `NOTIFY_SOCKET`, and does nothing when the
+/// service wasn't started by a manager which set it.
+#[derive(Clone, Debug)]
+pub(crate) struct ServiceNotifier {
+    socket: Option<String>,
+}
+
+impl ServiceNotifier {
+    pub(crate) fn from_env() -> Self {
+        Self {
+            socket: env::var("NOTIFY_SOCKET")
+                .ok()
+                .filter(|socket| !socket.is_empty()),
+        }
+    }
+
+    fn notify(&self, state: &str) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        if let Err(err) = send(socket, state) {
+            warn!("Unable to notify the service manager of {state}: {err}");
+        }
+    }
+
+    /// Report readiness once the gRPC server is listening and, when pairs are warmed up, an exchange has connected.
+    ///
+    /// Without warm-up pairs nothing connects to an exchange until the first subscription, so the listener is enough.
+    pub(crate) async fn notify_when_ready(
+        self,
+        listening: oneshot::Receiver<()>,
+        mut connector_status: Receiver<ConnectorStatus>,
+        expect_connection: bool,
+    ) {
+        if listening.await.is_err() {
+            // The server failed to start
+            return;
+        }
+
+        if expect_connection {
+            self.notify("STATUS=Waiting for an exchange to connect");
+            loop {
+                match connector_status.recv().await {
+                    Ok(status) if status.event() == ConnectorEvent::Connected => break,
+                    Ok(_) => {}
+                    // Missed statuses may have included a connection, the next will do
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        }
+
+        info!("Ready to serve");
+        self.notify("READY=1\nSTATUS=Serving");
+    }
+
+    /// Ping the manager's watchdog at half its timeout, for as long as the runtime is responsive.
+    ///
+    /// Does nothing unless the manager enabled the watchdog for this process with `WATCHDOG_USEC`.
+    pub(crate) async fn keep_watchdog_alive(self) {
+        let Some(timeout) = watchdog_timeout() else {
+            return;
+        };
+        let mut pings = interval(timeout / 2);
+        loop {
+            pings.tick().await;
+            self.notify("WATCHDOG=1");
+        }
+    }
+}
+
+/// The watchdog timeout, if it applies to this process rather than one it was started by.
+fn watchdog_timeout() -> Option<Duration> {
+    let micros = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    let for_this_process = env::var("WATCHDOG_PID")
+        .map(|pid| pid == std::process::id().to_string())
+        .unwrap_or(true);
+    for_this_process.then(|| Duration::from_micros(micros))
+}
+
+#[cfg(unix)]
+fn send(socket: &str, state: &str) -> io::Result<usize> {
+    let datagram = UnixDatagram::unbound()?;
+    // Sockets starting with @ are in the abstract namespace
+    match socket.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            datagram.send_to_addr(state.as_bytes(), &SocketAddr::from_abstract_name(name)?)
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Abstract sockets are only supported on Linux",
+        )),
+        None => datagram.send_to(state.as_bytes(), socket),
+    }
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &str, _state: &str) -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "The sd_notify protocol is only supported on Unix",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use tokio::{
+        net::UnixDatagram,
+        sync::{broadcast::channel, oneshot},
+    };
+
+    use order_book_service_types::proto::{ConnectorEvent, ConnectorStatus};
+
+    use super::ServiceNotifier;
+
+    #[tokio::test]
+    async fn should_notify_ready_once_listening_and_connected() {
+        let dir = std::env::temp_dir().join(format!("notify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.sock");
+        let _ = std::fs::remove_file(&path);
+        let manager = UnixDatagram::bind(&path).unwrap();
+
+        let notifier = ServiceNotifier {
+            socket: Some(path.to_string_lossy().to_string()),
+        };
+        let (listening_tx, listening_rx) = oneshot::channel();
+        let (status_tx, status_rx) = channel(10);
+        let ready = tokio::spawn(notifier.notify_when_ready(listening_rx, status_rx, true));
+
+        listening_tx.send(()).unwrap();
+        let mut buf = [0; 256];
+        let len = manager.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"STATUS=Waiting for an exchange to connect");
+
+        let status = |event: ConnectorEvent| ConnectorStatus {
+            event: event as i32,
+            ..Default::default()
+        };
+        status_tx.send(status(ConnectorEvent::Stale)).unwrap();
+        status_tx.send(status(ConnectorEvent::Connected)).unwrap();
+        ready.await.unwrap();
+
+        let len = manager.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"READY=1\nSTATUS=Serving");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
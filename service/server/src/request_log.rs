@@ -0,0 +1,347 @@
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use futures::future::BoxFuture;
+#[cfg(unix)]
+use tonic::transport::server::UdsConnectInfo;
+use tonic::{
+    codegen::{
+        http::{HeaderMap, HeaderValue, Request, Response},
+        Body, Bytes, Service,
+    },
+    transport::server::TcpConnectInfo,
+    Code,
+};
+use tower::Layer;
+use tracing::{info, warn};
+
+use crate::metrics::{rpc_active, rpc_duration, rpc_messages_sent, rpc_requests};
+
+/// Response metadata the summary RPCs name their traded pair in, for the request log.
+pub(crate) const TRADED_PAIR_METADATA: &str = "traded-pair";
+/// The services served, other paths are labelled "unknown" so clients can't grow the metrics
+const SERVICE_PREFIXES: [&str; 3] = [
+    "/orderbook.OrderbookAggregator/",
+    "/orderbook.OrderbookAdmin/",
+    "/grpc.health.v1.Health/",
+];
+
+/// Logs when each RPC starts and stops and records it in the RPC metrics, see [RequestLog].
+#[derive(Clone, Debug)]
+pub(crate) struct RequestLogLayer {
+    sampler: Arc<Sampler>,
+}
+
+impl RequestLogLayer {
+    /// Logs `sample_rate` of RPCs, as a fraction. Failed RPCs are always logged and every RPC is counted in the metrics.
+    pub(crate) fn new(sample_rate: f64) -> Self {
+        Self {
+            sampler: Arc::new(Sampler {
+                rate: sample_rate,
+                count: AtomicU64::new(0),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequestLogLayer {
+    type Service = RequestLog<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestLog {
+            inner,
+            sampler: self.sampler.clone(),
+        }
+    }
+}
+
+/// Picks an even spread of RPCs to log, e.g. every tenth at a rate of 0.1.
+#[derive(Debug)]
+struct Sampler {
+    rate: f64,
+    count: AtomicU64,
+}
+
+impl Sampler {
+    fn sample(&self) -> bool {
+        let count = self.count.fetch_add(1, Ordering::Relaxed) as f64;
+        ((count + 1.0) * self.rate).floor() > (count * self.rate).floor()
+    }
+}
+
+/// Wraps the server's routes, following each response body until the RPC ends.
+#[derive(Clone, Debug)]
+pub(crate) struct RequestLog<S> {
+    inner: S,
+    sampler: Arc<Sampler>,
+}
+
+impl<S, B, ReqBody> Service<Request<ReqBody>> for RequestLog<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<B>>,
+    S::Future: Send + 'static,
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Response = Response<LoggedBody<B>>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let mut rpc = Rpc::start(&request, self.sampler.sample());
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response
+                .await
+                .inspect_err(|_| rpc.code = Some(Code::Unknown))?;
+            let (parts, body) = response.into_parts();
+            rpc.pair = parts
+                .headers
+                .get(TRADED_PAIR_METADATA)
+                .and_then(|pair| pair.to_str().ok())
+                .map(ToString::to_string);
+            // Failures before any message is sent have their status in the headers
+            rpc.record_status(&parts.headers);
+
+            Ok(Response::from_parts(
+                parts,
+                LoggedBody {
+                    inner: body,
+                    rpc,
+                    frames: FrameCounter::default(),
+                },
+            ))
+        })
+    }
+}
+
+/// An RPC being served, logged and recorded when dropped along with its response body.
+struct Rpc {
+    method: String,
+    peer: String,
+    pair: Option<String>,
+    started: Instant,
+    messages: u64,
+    /// `None` until the status is sent, an RPC dropped before then was cancelled by the client
+    code: Option<Code>,
+    sampled: bool,
+}
+
+impl Rpc {
+    fn start<B>(request: &Request<B>, sampled: bool) -> Self {
+        let path = request.uri().path();
+        let method = if SERVICE_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+        {
+            path.trim_start_matches('/').to_string()
+        } else {
+            "unknown".to_string()
+        };
+        let peer = peer(request);
+
+        rpc_active(&method).inc();
+        if sampled {
+            info!(method, peer, "RPC started");
+        }
+
+        Self {
+            method,
+            peer,
+            pair: None,
+            started: Instant::now(),
+            messages: 0,
+            code: None,
+            sampled,
+        }
+    }
+
+    fn record_status(&mut self, headers: &HeaderMap<HeaderValue>) {
+        if let Some(status) = headers.get("grpc-status") {
+            self.code = Some(Code::from_bytes(status.as_bytes()));
+        }
+    }
+}
+
+impl Drop for Rpc {
+    fn drop(&mut self) {
+        let code = self.code.unwrap_or(Code::Cancelled);
+        let code_label = format!("{code:?}");
+        let duration = self.started.elapsed();
+
+        rpc_active(&self.method).dec();
+        rpc_requests(&self.method, &code_label).inc();
+        rpc_duration(&self.method).observe(duration.as_secs_f64());
+
+        let pair = self.pair.as_deref().unwrap_or_default();
+        let duration_ms = duration.as_millis() as u64;
+        if !matches!(code, Code::Ok | Code::Cancelled) {
+            warn!(
+                method = self.method,
+                peer = self.peer,
+                pair,
+                code = code_label,
+                duration_ms,
+                messages = self.messages,
+                "RPC failed"
+            );
+        } else if self.sampled {
+            info!(
+                method = self.method,
+                peer = self.peer,
+                pair,
+                code = code_label,
+                duration_ms,
+                messages = self.messages,
+                "RPC finished"
+            );
+        }
+    }
+}
+
+/// The client's address, as inserted into the request by the transport it connected over.
+fn peer<B>(request: &Request<B>) -> String {
+    if let Some(info) = request.extensions().get::<TcpConnectInfo>() {
+        return info
+            .remote_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+    }
+    #[cfg(unix)]
+    if let Some(info) = request.extensions().get::<UdsConnectInfo>() {
+        return info
+            .peer_cred
+            .and_then(|cred| cred.pid())
+            .map(|pid| format!("unix:pid={pid}"))
+            .unwrap_or_else(|| "unix".to_string());
+    }
+    "in-process".to_string()
+}
+
+/// Counts the length prefixed messages of a gRPC body, however its frames are split into chunks.
+#[derive(Debug, Default)]
+struct FrameCounter {
+    header: Vec<u8>,
+    /// Bytes of the current message still to come
+    remaining: usize,
+}
+
+impl FrameCounter {
+    /// The number of messages starting in `chunk`.
+    fn count(&mut self, mut chunk: &[u8]) -> u64 {
+        let mut messages = 0;
+        while !chunk.is_empty() {
+            if self.remaining > 0 {
+                let consumed = self.remaining.min(chunk.len());
+                self.remaining -= consumed;
+                chunk = &chunk[consumed..];
+                continue;
+            }
+            // A compressed flag then the message's length as a big endian u32
+            let consumed = (5 - self.header.len()).min(chunk.len());
+            self.header.extend_from_slice(&chunk[..consumed]);
+            chunk = &chunk[consumed..];
+            if self.header.len() == 5 {
+                let length = [
+                    self.header[1],
+                    self.header[2],
+                    self.header[3],
+                    self.header[4],
+                ];
+                self.remaining = u32::from_be_bytes(length) as usize;
+                self.header.clear();
+                messages += 1;
+            }
+        }
+        messages
+    }
+}
+
+/// A response body which counts the messages sent and takes the RPC's status from its trailers.
+pub(crate) struct LoggedBody<B> {
+    inner: B,
+    rpc: Rpc,
+    frames: FrameCounter,
+}
+
+impl<B> Body for LoggedBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = &mut *self;
+        let data = Pin::new(&mut this.inner).poll_data(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &data {
+            let messages = this.frames.count(chunk);
+            this.rpc.messages += messages;
+            rpc_messages_sent(&this.rpc.method).inc_by(messages);
+        }
+        data
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = &mut *self;
+        let trailers = Pin::new(&mut this.inner).poll_trailers(cx);
+        if let Poll::Ready(Ok(Some(trailers))) = &trailers {
+            this.rpc.record_status(trailers);
+        }
+        trailers
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+
+    use super::{FrameCounter, Sampler};
+
+    #[test]
+    fn should_count_messages_split_across_chunks() {
+        let mut frames = FrameCounter::default();
+        // Two messages of 3 and 2 bytes in one chunk
+        assert_eq!(frames.count(&[0, 0, 0, 0, 3, 1, 2, 3, 0, 0, 0, 0, 2, 1]), 2);
+        // The end of the second, then a third whose header is split
+        assert_eq!(frames.count(&[2, 0, 0]), 0);
+        assert_eq!(frames.count(&[0, 0, 1]), 1);
+        // The third's single byte, then an empty message
+        assert_eq!(frames.count(&[9, 0, 0, 0, 0, 0]), 1);
+    }
+
+    #[test]
+    fn should_sample_an_even_spread_of_rpcs() {
+        let sampled = |rate| {
+            let sampler = Sampler {
+                rate,
+                count: AtomicU64::new(0),
+            };
+            (0..20).filter(|_| sampler.sample()).count()
+        };
+        assert_eq!(sampled(1.0), 20);
+        assert_eq!(sampled(0.25), 5);
+        assert_eq!(sampled(0.0), 0);
+    }
+}
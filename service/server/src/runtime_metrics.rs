@@ -0,0 +1,159 @@
+//! Samples the async runtime into the metrics, for diagnosing stalls when many pairs are aggregated.
+//!
+//! Sampling runs on its own thread and runtime so that blocked workers are still reported when every one of them is
+//! blocked.
+
+use std::{thread, time::Duration};
+
+use anyhow::{Context, Error};
+use tokio::{
+    runtime::{Builder, Handle},
+    sync::oneshot,
+    time::{sleep, timeout},
+};
+use tracing::warn;
+
+use crate::{
+    config::RuntimeMetricsConfig,
+    metrics::{runtime_probe, RuntimeProbeMetrics},
+};
+
+/// Sample the runtime of `handle` every `interval_millis` until it shuts down.
+pub(crate) fn spawn_monitor(handle: Handle, config: RuntimeMetricsConfig) -> Result<(), Error> {
+    let interval = Duration::from_millis(config.interval_millis);
+    let stall_threshold = Duration::from_millis(config.stall_threshold_millis);
+    let monitor = Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .context("Unable to build the runtime monitor's runtime")?;
+    let metrics = runtime_probe();
+
+    thread::Builder::new()
+        .name("runtime-monitor".to_string())
+        .spawn(move || {
+            monitor.block_on(async move {
+                loop {
+                    sleep(interval).await;
+                    if probe(&handle, stall_threshold, &metrics).await.is_none() {
+                        return;
+                    }
+                    #[cfg(tokio_unstable)]
+                    export_tokio_metrics(&handle.metrics());
+                }
+            })
+        })
+        .context("Unable to start the runtime monitor")?;
+    Ok(())
+}
+
+/// How long a task spawned onto the runtime waited to be polled, `None` once the runtime has shut down.
+///
+/// Blocked workers are reported as soon as the wait passes `stall_threshold`, rather than when they're freed.
+async fn probe(
+    handle: &Handle,
+    stall_threshold: Duration,
+    metrics: &RuntimeProbeMetrics,
+) -> Option<Duration> {
+    let (delay_tx, mut delay_rx) = oneshot::channel();
+    let spawned = std::time::Instant::now();
+    // A runtime which has shut down drops the task without running it, closing the channel
+    handle.spawn(async move {
+        let _ = delay_tx.send(spawned.elapsed());
+    });
+
+    let delay = match timeout(stall_threshold, &mut delay_rx).await {
+        Ok(delay) => delay.ok()?,
+        Err(_) => {
+            metrics.stalls.inc();
+            warn!(
+                threshold_ms = stall_threshold.as_millis() as u64,
+                "Runtime workers are blocked, a task has been waiting longer than the threshold to be polled"
+            );
+            delay_rx.await.ok()?
+        }
+    };
+    metrics.schedule_delay.observe(delay.as_secs_f64());
+    Some(delay)
+}
+
+/// Export the statistics tokio keeps itself, mean poll time is `busy_micros` over `polls`.
+#[cfg(tokio_unstable)]
+fn export_tokio_metrics(metrics: &tokio::runtime::RuntimeMetrics) {
+    use crate::metrics::{runtime, runtime_worker};
+
+    runtime("workers").set(metrics.num_workers() as i64);
+    runtime("blocking_threads").set(metrics.num_blocking_threads() as i64);
+    runtime("idle_blocking_threads").set(metrics.num_idle_blocking_threads() as i64);
+    runtime("injection_queue_depth").set(metrics.injection_queue_depth() as i64);
+    runtime("blocking_queue_depth").set(metrics.blocking_queue_depth() as i64);
+    for worker in 0..metrics.num_workers() {
+        runtime_worker(worker, "polls").set(metrics.worker_poll_count(worker) as i64);
+        runtime_worker(worker, "busy_micros")
+            .set(metrics.worker_total_busy_duration(worker).as_micros() as i64);
+        runtime_worker(worker, "steals").set(metrics.worker_steal_count(worker) as i64);
+        runtime_worker(worker, "parks").set(metrics.worker_park_count(worker) as i64);
+        runtime_worker(worker, "local_queue_depth")
+            .set(metrics.worker_local_queue_depth(worker) as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use prometheus::Registry;
+    use tokio::{
+        join,
+        runtime::Builder,
+        sync::oneshot,
+        time::{self, sleep},
+    };
+
+    use crate::metrics::RuntimeProbeMetrics;
+
+    use super::probe;
+
+    #[tokio::test]
+    async fn should_report_blocked_workers() {
+        let metrics = RuntimeProbeMetrics::register(&Registry::new()).unwrap();
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .build()
+            .unwrap();
+        let handle = runtime.handle().clone();
+
+        assert!(probe(&handle, Duration::from_secs(5), &metrics)
+            .await
+            .is_some());
+        assert_eq!(metrics.stalls.get(), 0);
+
+        // Block the only worker until released
+        let (blocked_tx, blocked_rx) = oneshot::channel();
+        let (release, released) = mpsc::channel::<()>();
+        handle.spawn(async move {
+            let _ = blocked_tx.send(());
+            let _ = released.recv();
+        });
+        blocked_rx.await.unwrap();
+
+        // With time paused the threshold passes as soon as the probe is left waiting, and the worker is freed after
+        time::pause();
+        let (delay, _) = join!(
+            probe(&handle, Duration::from_millis(100), &metrics),
+            async {
+                sleep(Duration::from_millis(200)).await;
+                release.send(()).unwrap();
+            }
+        );
+        time::resume();
+
+        assert!(delay.is_some());
+        assert_eq!(metrics.stalls.get(), 1);
+        assert_eq!(metrics.schedule_delay.get_sample_count(), 2);
+
+        runtime.shutdown_background();
+        assert!(probe(&handle, Duration::from_secs(5), &metrics)
+            .await
+            .is_none());
+    }
+}
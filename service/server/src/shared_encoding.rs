@@ -0,0 +1,174 @@
+//! Each summary an aggregator broadcasts is encoded once per depth and the bytes shared by every BookSummary
+//! subscription at that depth, rather than tonic encoding a copy for each subscriber. See the `fanout_encoding` bench.
+//!
+//! Subscriptions whose summaries are tailored, e.g. with effective prices or a filter, are still encoded individually.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use prost::{
+    bytes::{BufMut, Bytes},
+    Message,
+};
+use tonic::{
+    codec::{Codec, EncodeBuf, Encoder, ProstCodec},
+    Status,
+};
+use tracing::Span;
+
+use order_book_service_types::proto::{OrderBookRequest, Summary};
+
+use crate::aggregator::MergedBook;
+
+/// A summary broadcast to each subscription of a pair, along with the full depth book it was built from.
+#[derive(Debug)]
+pub(crate) struct SummaryTick {
+    /// Built at the deepest depth requested from the aggregator
+    pub(crate) summary: Summary,
+    pub(crate) book: Arc<MergedBook>,
+    /// The merge which produced the summary, deliveries to clients are linked back to it
+    pub(crate) span: Span,
+    encodings: Mutex<HashMap<usize, Bytes>>,
+}
+
+impl SummaryTick {
+    pub(crate) fn new(summary: Summary, book: Arc<MergedBook>, span: Span) -> Self {
+        Self {
+            summary,
+            book,
+            span,
+            encodings: Mutex::default(),
+        }
+    }
+
+    /// The summary cut down to the best `depth` levels of each side.
+    pub(crate) fn summary_at(&self, depth: usize) -> Summary {
+        let mut summary = self.summary.clone();
+        summary.asks.truncate(depth);
+        summary.bids.truncate(depth);
+        summary.update_totals();
+        summary
+    }
+
+    /// The summary at `depth` encoded, only the first subscription at each depth encodes it.
+    pub(crate) fn encoded_at(&self, depth: usize) -> Bytes {
+        self.encodings
+            .lock()
+            .expect("Should lock")
+            .entry(depth)
+            .or_insert_with(|| Bytes::from(self.summary_at(depth).encode_to_vec()))
+            .clone()
+    }
+}
+
+/// An encoded [Summary], written to the client as is by [EncodedSummaryCodec].
+#[derive(Clone, Debug)]
+pub(crate) struct EncodedSummary(Bytes);
+
+/// How a subscription's summaries are passed to the client's stream.
+pub(crate) trait Outgoing: Send + 'static {
+    /// A summary tailored to the subscription, or a heartbeat.
+    fn tailored(summary: Summary) -> Self;
+    /// The tick's summary at `depth`, the same for every subscription which doesn't tailor its summaries.
+    fn shared(tick: &SummaryTick, depth: usize) -> Self;
+}
+
+/// Left for tonic to encode, for RPCs built on BookSummary's subscriptions.
+impl Outgoing for Summary {
+    fn tailored(summary: Summary) -> Self {
+        summary
+    }
+
+    fn shared(tick: &SummaryTick, depth: usize) -> Self {
+        tick.summary_at(depth)
+    }
+}
+
+impl Outgoing for EncodedSummary {
+    fn tailored(summary: Summary) -> Self {
+        Self(Bytes::from(summary.encode_to_vec()))
+    }
+
+    fn shared(tick: &SummaryTick, depth: usize) -> Self {
+        Self(tick.encoded_at(depth))
+    }
+}
+
+/// BookSummary's codec, decoding requests as tonic's prost codec does but sending [EncodedSummary]s as they are.
+#[derive(Debug, Default)]
+pub(crate) struct EncodedSummaryCodec;
+
+impl Codec for EncodedSummaryCodec {
+    type Encode = EncodedSummary;
+    type Decode = OrderBookRequest;
+    type Encoder = EncodedSummaryEncoder;
+    type Decoder = <ProstCodec<Summary, OrderBookRequest> as Codec>::Decoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        EncodedSummaryEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        ProstCodec::<Summary, OrderBookRequest>::default().decoder()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct EncodedSummaryEncoder;
+
+impl Encoder for EncodedSummaryEncoder {
+    type Item = EncodedSummary;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        dst.put_slice(&item.0);
+        Ok(())
+    }
+}
+
+/// Decodes a message as the client would, for tests.
+#[cfg(test)]
+pub(crate) fn decode(encoded: &EncodedSummary) -> Summary {
+    Summary::decode(encoded.0.clone()).expect("Should decode")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tracing::Span;
+
+    use order_book_service_types::proto::{Level, Summary};
+
+    use super::{decode, EncodedSummary, Outgoing, SummaryTick};
+
+    #[test]
+    fn should_encode_each_depth_once() {
+        let mut summary = Summary {
+            spread: 1.0,
+            asks: vec![
+                Level::new("Binance", 3.0, 1.0),
+                Level::new("Bitstamp", 4.0, 2.0),
+            ],
+            bids: vec![
+                Level::new("Binance", 2.0, 1.0),
+                Level::new("Bitstamp", 1.0, 2.0),
+            ],
+            ..Default::default()
+        };
+        summary.update_totals();
+        let tick = SummaryTick::new(summary, Arc::default(), Span::none());
+
+        let shallow = EncodedSummary::shared(&tick, 1);
+        // The same bytes are shared, not a copy
+        assert_eq!(
+            EncodedSummary::shared(&tick, 1).0.as_ptr(),
+            shallow.0.as_ptr()
+        );
+        assert_eq!(decode(&shallow), tick.summary_at(1));
+        assert_eq!(decode(&shallow).ask_notional, 3.0);
+        assert_eq!(decode(&EncodedSummary::shared(&tick, 10)), tick.summary);
+    }
+}
@@ -0,0 +1,107 @@
+use order_book_service_types::proto::{ExchangeFill, ExchangeId, Level, SlippageEstimate};
+
+/// Walk the `levels` from best to worst, filling `amount` and recording the price achieved on each exchange.
+/// If the levels don't hold enough liquidity the estimate covers as much as could be filled.
+pub(crate) fn estimate_slippage(levels: &[Level], amount: f64) -> SlippageEstimate {
+    let mut remaining = amount;
+    let mut notional = 0.0;
+    let mut worst_price = 0.0;
+    // Fills are kept in the order each exchange was first touched
+    let mut fills: Vec<(ExchangeId, f64, f64)> = Vec::new();
+
+    for level in levels {
+        if remaining <= 0.0 {
+            break;
+        }
+
+        let fill_amount = level.amount.min(remaining);
+        remaining -= fill_amount;
+        notional += fill_amount * level.price;
+        worst_price = level.price;
+
+        let level_exchange = level.exchange_id();
+        match fills
+            .iter_mut()
+            .find(|(exchange, _, _)| *exchange == level_exchange)
+        {
+            Some((_, exchange_amount, exchange_notional)) => {
+                *exchange_amount += fill_amount;
+                *exchange_notional += fill_amount * level.price;
+            }
+            None => fills.push((level_exchange, fill_amount, fill_amount * level.price)),
+        }
+    }
+
+    let filled_amount = amount - remaining.max(0.0);
+
+    SlippageEstimate {
+        average_price: average(notional, filled_amount),
+        worst_price,
+        filled_amount,
+        fills: fills
+            .into_iter()
+            .map(|(exchange, amount, notional)| ExchangeFill {
+                exchange: exchange.to_string(),
+                amount,
+                average_price: average(notional, amount),
+            })
+            .collect(),
+    }
+}
+
+fn average(notional: f64, amount: f64) -> f64 {
+    if amount > 0.0 {
+        notional / amount
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use order_book_service_types::proto::{ExchangeFill, Level};
+
+    use super::estimate_slippage;
+
+    #[test]
+    fn should_fill_across_exchanges() {
+        let asks = vec![
+            Level::new("ONE", 1.0, 1.0),
+            Level::new("TWO", 2.0, 1.0),
+            Level::new("ONE", 3.0, 2.0),
+        ];
+
+        let estimate = estimate_slippage(&asks, 3.0);
+
+        // 1x1.0 + 1x2.0 + 1x3.0
+        assert_eq!(estimate.average_price, 2.0);
+        assert_eq!(estimate.worst_price, 3.0);
+        assert_eq!(estimate.filled_amount, 3.0);
+        assert_eq!(
+            estimate.fills,
+            vec![
+                ExchangeFill {
+                    exchange: "ONE".to_string(),
+                    amount: 2.0,
+                    average_price: 2.0,
+                },
+                ExchangeFill {
+                    exchange: "TWO".to_string(),
+                    amount: 1.0,
+                    average_price: 2.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_partially_fill_when_book_is_too_thin() {
+        let bids = vec![Level::new("ONE", 2.0, 1.0), Level::new("TWO", 1.0, 1.0)];
+
+        let estimate = estimate_slippage(&bids, 5.0);
+
+        assert_eq!(estimate.filled_amount, 2.0);
+        assert_eq!(estimate.average_price, 1.5);
+        assert_eq!(estimate.worst_price, 1.0);
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Error};
+use prometheus::proto::{MetricFamily, MetricType};
+use serde::Serialize;
+use tokio::{
+    sync::Mutex as AsyncMutex,
+    task::spawn_blocking,
+    time::{interval, MissedTickBehavior},
+};
+use tracing::error;
+
+use order_book_service_types::proto::MetricsDump;
+
+use crate::config::MetricsSnapshotConfig;
+
+/// The metrics saved in each snapshot, those most useful for working out what led up to a crash.
+const SNAPSHOT_METRICS: [&str; 8] = [
+    "orderbook_summaries_total",
+    "orderbook_connector_events_total",
+    "orderbook_channel_dropped_total",
+    "orderbook_channel_overflows_total",
+    "orderbook_summaries_suppressed_total",
+    "orderbook_book_levels_evicted_total",
+    "orderbook_client_subscriptions",
+    "orderbook_rpc_active",
+];
+
+/// Saves the key metrics to a ring file on an interval, so the recent operational history can be attached to a bug
+/// report after a crash.
+///
+/// The file holds a JSON snapshot per line, oldest first. Snapshots already in the file at startup are kept, they're
+/// usually the history of the run which crashed.
+#[derive(Clone)]
+pub(crate) struct MetricsSnapshots {
+    /// `None` when snapshots aren't configured
+    ring: Option<Arc<Mutex<Ring>>>,
+    /// Held while the file is written, so writes can't overtake one another
+    writing: Arc<AsyncMutex<()>>,
+    interval: Duration,
+}
+
+impl MetricsSnapshots {
+    pub(crate) fn new(config: &MetricsSnapshotConfig) -> Self {
+        let ring = config.path.as_ref().map(|path| {
+            let mut lines = match fs::read_to_string(path) {
+                Ok(contents) => contents.lines().map(ToString::to_string).collect(),
+                Err(_) => VecDeque::new(),
+            };
+            while lines.len() > config.max_snapshots {
+                lines.pop_front();
+            }
+            Arc::new(Mutex::new(Ring {
+                path: path.clone(),
+                capacity: config.max_snapshots,
+                lines,
+                previous: None,
+            }))
+        });
+
+        Self {
+            ring,
+            writing: Arc::default(),
+            interval: config.interval(),
+        }
+    }
+
+    /// Save a snapshot now, `None` when snapshots aren't configured.
+    pub(crate) async fn dump(&self) -> Option<Result<MetricsDump, Error>> {
+        let ring = self.ring.as_ref()?;
+        let dump = ring
+            .lock()
+            .expect("Should lock")
+            .push(&prometheus::gather());
+        Some(match dump {
+            Ok(dump) => self.save(ring).await.map(|()| dump),
+            Err(err) => Err(err),
+        })
+    }
+
+    /// Write the ring's snapshots to its file on the blocking pool, the ring isn't locked meanwhile.
+    async fn save(&self, ring: &Mutex<Ring>) -> Result<(), Error> {
+        let _writing = self.writing.lock().await;
+        // Taken once the previous write is done, so the latest snapshots are always written last
+        let (path, contents) = {
+            let ring = ring.lock().expect("Should lock");
+            (ring.path.clone(), ring.contents())
+        };
+        spawn_blocking(move || write(&path, contents))
+            .await
+            .context("Metrics snapshot write panicked")?
+    }
+
+    /// Save a snapshot every interval, returning straight away when snapshots aren't configured.
+    pub(crate) async fn run(self) {
+        if self.ring.is_none() {
+            return;
+        }
+        let mut ticks = interval(self.interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            if let Some(Err(err)) = self.dump().await {
+                error!("Unable to save metrics snapshot: {err:#}");
+            }
+        }
+    }
+}
+
+struct Ring {
+    path: PathBuf,
+    capacity: usize,
+    lines: VecDeque<String>,
+    /// When the last snapshot was taken and its counters, to give each counter's rate since
+    previous: Option<(Instant, HashMap<String, f64>)>,
+}
+
+impl Ring {
+    fn push(&mut self, metrics: &[MetricFamily]) -> Result<MetricsDump, Error> {
+        let taken = Instant::now();
+        let mut snapshot = Snapshot {
+            timestamp_millis: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            metrics: snapshot_metrics(metrics),
+        };
+
+        let counters = snapshot
+            .metrics
+            .iter()
+            .filter(|metric| metric.counter)
+            .map(|metric| (metric.key(), metric.value))
+            .collect::<HashMap<_, _>>();
+        if let Some((previous_taken, previous)) = &self.previous {
+            let elapsed = taken.duration_since(*previous_taken).as_secs_f64();
+            for metric in snapshot.metrics.iter_mut().filter(|metric| metric.counter) {
+                // A counter first seen since the last snapshot started from zero
+                let before = previous.get(&metric.key()).copied().unwrap_or_default();
+                metric.per_sec = (elapsed > 0.0).then(|| (metric.value - before) / elapsed);
+            }
+        }
+        self.previous = Some((taken, counters));
+
+        self.lines.push_back(serde_json::to_string(&snapshot)?);
+        while self.lines.len() > self.capacity {
+            self.lines.pop_front();
+        }
+
+        Ok(MetricsDump {
+            path: self.path.display().to_string(),
+            snapshots: self.lines.len() as u32,
+        })
+    }
+
+    /// The snapshots as the file holds them, one per line.
+    fn contents(&self) -> String {
+        let mut contents = self
+            .lines
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+        contents.push('\n');
+        contents
+    }
+}
+
+/// Replace the file through a temporary one, so a crash part way through a write leaves the previous snapshots.
+fn write(path: &Path, contents: String) -> Result<(), Error> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).with_context(|| {
+            format!(
+                "Unable to create metrics snapshot directory {}",
+                directory.display()
+            )
+        })?;
+    }
+    let mut temporary = path.to_path_buf().into_os_string();
+    temporary.push(".tmp");
+
+    fs::write(&temporary, contents).context("Unable to write metrics snapshot")?;
+    fs::rename(&temporary, path)
+        .with_context(|| format!("Unable to replace metrics snapshot file {}", path.display()))
+}
+
+#[derive(Debug, Serialize)]
+struct Snapshot {
+    timestamp_millis: u64,
+    metrics: Vec<SnapshotMetric>,
+}
+
+#[derive(Debug, Serialize)]
+struct SnapshotMetric {
+    name: String,
+    labels: BTreeMap<String, String>,
+    value: f64,
+    /// Only given for counters, from the second snapshot of a run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    per_sec: Option<f64>,
+    #[serde(skip)]
+    counter: bool,
+}
+
+impl SnapshotMetric {
+    fn key(&self) -> String {
+        format!("{}{:?}", self.name, self.labels)
+    }
+}
+
+fn snapshot_metrics(metrics: &[MetricFamily]) -> Vec<SnapshotMetric> {
+    metrics
+        .iter()
+        .filter(|family| SNAPSHOT_METRICS.contains(&family.get_name()))
+        .flat_map(|family| {
+            // The snapshot metrics are all counters or gauges
+            let counter = family.get_field_type() == MetricType::COUNTER;
+            family
+                .get_metric()
+                .iter()
+                .map(move |metric| SnapshotMetric {
+                    name: family.get_name().to_string(),
+                    labels: metric
+                        .get_label()
+                        .iter()
+                        .map(|label| (label.get_name().to_string(), label.get_value().to_string()))
+                        .collect(),
+                    value: if counter {
+                        metric.get_counter().get_value()
+                    } else {
+                        metric.get_gauge().get_value()
+                    },
+                    per_sec: None,
+                    counter,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::Value;
+
+    use crate::{config::MetricsSnapshotConfig, metrics::summaries_published};
+
+    use super::MetricsSnapshots;
+
+    #[tokio::test]
+    async fn should_keep_the_latest_snapshots_across_restarts() {
+        let directory = tempfile::tempdir().unwrap();
+        let config = MetricsSnapshotConfig {
+            path: Some(directory.path().join("metrics.jsonl")),
+            interval_secs: 60,
+            max_snapshots: 2,
+        };
+        let snapshots = MetricsSnapshots::new(&config);
+
+        let mut held = Vec::new();
+        for _ in 0..3 {
+            summaries_published("SNAP-SHOT").inc_by(5);
+            let dump = snapshots
+                .dump()
+                .await
+                .expect("Should be configured")
+                .unwrap();
+            held.push(dump.snapshots);
+        }
+        // The oldest is dropped once the ring is full
+        assert_eq!(held, vec![1, 2, 2]);
+
+        let contents = fs::read_to_string(directory.path().join("metrics.jsonl")).unwrap();
+        let lines = contents
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let summaries = lines[1]["metrics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|metric| metric["labels"]["pair"] == "SNAP-SHOT")
+            .expect("Should snapshot the pair's summaries");
+        assert_eq!(summaries["value"], 15.0);
+        assert!(summaries["per_sec"].as_f64().unwrap() > 0.0);
+
+        // A restarted service keeps the snapshots from before
+        let restarted = MetricsSnapshots::new(&config);
+        let dump = restarted.dump().await.unwrap().unwrap();
+        assert_eq!(dump.snapshots, 2);
+        let after_restart = fs::read_to_string(directory.path().join("metrics.jsonl")).unwrap();
+        assert_eq!(after_restart.lines().next(), contents.lines().nth(1));
+
+        let disabled = MetricsSnapshots::new(&MetricsSnapshotConfig::default());
+        assert!(disabled.dump().await.is_none());
+    }
+}
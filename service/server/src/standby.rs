@@ -0,0 +1,199 @@
+//! A warm standby relays each pair's summaries from an active server, so the pair is already being served when the
+//! active server dies. Once the upstream stops sending, the standby aggregates the pair from the exchanges itself and
+//! carries on serving its subscribers without them reconnecting.
+
+use std::time::Duration;
+
+use futures_util::{Stream, StreamExt};
+use tokio::time::timeout;
+use tonic::{
+    metadata::AsciiMetadataValue,
+    transport::{Channel, Endpoint},
+    Request, Status,
+};
+use tracing::{info, warn};
+
+use order_book_service_types::proto::{
+    orderbook_aggregator_client::OrderbookAggregatorClient, OrderBookRequest, Summary, TradedPair,
+};
+
+use crate::{
+    aggregator::OrderbookAggregator,
+    config::Config,
+    error::{error_chain, UpstreamError},
+    metrics::{pair_label, standby_relaying},
+    tenancy::API_KEY_METADATA,
+};
+
+/// The active server a standby relays from.
+#[derive(Clone, Debug)]
+pub(crate) struct Upstream {
+    channel: Channel,
+    api_key: Option<AsciiMetadataValue>,
+    /// Depth requested from the upstream, the most any of the standby's subscriptions can request
+    depth: usize,
+    failover_after: Duration,
+}
+
+impl Upstream {
+    /// The configured upstream, `None` when the server is active itself.
+    ///
+    /// Connects lazily, so must be called from within the runtime.
+    pub(crate) fn from_config(config: &Config) -> Option<Self> {
+        let upstream = config.standby.upstream.clone()?;
+        let endpoint = Endpoint::from_shared(upstream).expect("Upstream should be validated");
+        Some(Self {
+            channel: endpoint.connect_lazy(),
+            api_key: config
+                .standby
+                .api_key
+                .as_ref()
+                .map(|api_key| api_key.parse().expect("API key should be validated")),
+            depth: config.depth.max,
+            failover_after: config.standby.failover_after(),
+        })
+    }
+
+    /// Relay the pair's summaries from the upstream to `aggregator`'s subscribers, then aggregate the pair from the
+    /// exchanges once the upstream can no longer be relied on. The switch is one way, a pair isn't handed back to a
+    /// recovered upstream.
+    pub(crate) async fn relay_then_aggregate(self, mut aggregator: OrderbookAggregator) {
+        let traded_pair = aggregator.traded_pair().clone();
+        let pair = traded_pair.to_string();
+        let relaying = standby_relaying(&pair_label(&traded_pair));
+        relaying.set(1);
+
+        let err = match self.subscribe(traded_pair).await {
+            Ok(summaries) => {
+                info!("Relaying {pair} from the upstream");
+                relay(&mut aggregator, summaries, self.failover_after).await
+            }
+            Err(err) => err,
+        };
+        warn!(
+            "Failing over to aggregating {pair} from the exchanges: {}",
+            error_chain(&err)
+        );
+        relaying.set(0);
+
+        aggregator.start().await
+    }
+
+    async fn subscribe(
+        &self,
+        traded_pair: TradedPair,
+    ) -> Result<impl Stream<Item = Result<Summary, Status>>, UpstreamError> {
+        let mut request = Request::new(OrderBookRequest {
+            traded_pair: Some(traded_pair),
+            depth: self.depth as u32,
+            ..Default::default()
+        });
+        if let Some(api_key) = &self.api_key {
+            request
+                .metadata_mut()
+                .insert(API_KEY_METADATA, api_key.clone());
+        }
+
+        let mut client = OrderbookAggregatorClient::new(self.channel.clone());
+        let connecting = timeout(self.failover_after, client.book_summary(request));
+        match connecting.await {
+            Ok(Ok(response)) => Ok(response.into_inner()),
+            Ok(Err(status)) => Err(UpstreamError::Subscribe(status)),
+            Err(_) => Err(UpstreamError::Silent(self.failover_after)),
+        }
+    }
+}
+
+/// Publish each summary from the upstream until it fails or goes quiet for `failover_after`, returning why.
+// The aggregator isn't Sync, so it's borrowed mutably to hold it across the waits
+async fn relay(
+    aggregator: &mut OrderbookAggregator,
+    summaries: impl Stream<Item = Result<Summary, Status>>,
+    failover_after: Duration,
+) -> UpstreamError {
+    tokio::pin!(summaries);
+    loop {
+        match timeout(failover_after, summaries.next()).await {
+            // Heartbeats show the upstream is alive, but the standby sends its own
+            Ok(Some(Ok(summary))) if summary.is_heartbeat() => continue,
+            Ok(Some(Ok(summary))) => aggregator.publish_relayed(summary),
+            Ok(Some(Err(status))) => return UpstreamError::Stream(status),
+            Ok(None) => return UpstreamError::Ended,
+            Err(_) => return UpstreamError::Silent(failover_after),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::Duration};
+
+    use futures_util::stream::{iter, pending};
+    use tokio::sync::watch::channel as watch_channel;
+    use tonic::Status;
+
+    use order_book_service_types::proto::{Level, Summary, TradedPair};
+
+    use crate::{
+        aggregator::OrderbookAggregator, clock::SystemClock, config::Config, error::UpstreamError,
+        events::EventBus, latency::ExchangeLatencies,
+    };
+
+    use super::relay;
+
+    fn aggregator() -> OrderbookAggregator {
+        OrderbookAggregator::new(
+            &[],
+            TradedPair::new("ETH", "BTC"),
+            watch_channel(HashSet::new()).1,
+            EventBus::new(10),
+            ExchangeLatencies::default(),
+            SystemClock::shared(),
+            &Config::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn should_relay_summaries_until_the_upstream_fails() {
+        let mut aggregator = aggregator();
+        let mut handle = aggregator.subscribe();
+
+        let upstream = Summary {
+            spread: 0.5,
+            asks: (0..20)
+                .map(|level| Level::new("Binance", 2.0 + level as f64, 1.0))
+                .collect(),
+            bids: vec![Level::new("Bitstamp", 1.5, 2.0)],
+            exchanges_in_maintenance: vec!["Bitstamp".to_string()],
+            ..Default::default()
+        };
+        let summaries = iter(vec![
+            Ok(upstream),
+            Ok(Summary::heartbeat(Duration::from_secs(5))),
+            Err(Status::unavailable("Upstream shutting down")),
+        ]);
+
+        let err = relay(&mut aggregator, summaries, Duration::from_secs(1)).await;
+        assert!(matches!(err, UpstreamError::Stream(_)));
+
+        // Trimmed to the depth subscribers want, with the full depth retained for snapshots
+        let tick = handle
+            .summary_receiver
+            .try_recv()
+            .expect("Should relay the summary")
+            .expect("Should not be an error");
+        let (summary, book) = (&tick.summary, &tick.book);
+        assert_eq!(summary.asks.len(), 10);
+        assert_eq!(summary.spread, 0.5);
+        assert_eq!(summary.exchanges_in_maintenance, vec!["Bitstamp"]);
+        assert_eq!(book.asks.len(), 20);
+        // The heartbeat isn't relayed
+        assert!(handle.summary_receiver.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_fail_over_when_the_upstream_goes_quiet() {
+        let err = relay(&mut aggregator(), pending(), Duration::from_secs(15)).await;
+        assert!(matches!(err, UpstreamError::Silent(after) if after == Duration::from_secs(15)));
+    }
+}
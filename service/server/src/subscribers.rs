@@ -0,0 +1,252 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use tonic::metadata::MetadataMap;
+
+use order_book_service_types::proto::{Subscriber, TradedPair};
+
+use crate::{metrics::client_subscriptions, tenancy::TenantId};
+
+/// Metadata keys clients identify themselves with.
+const CLIENT_NAME_METADATA: &str = "client-name";
+const CLIENT_VERSION_METADATA: &str = "client-version";
+/// Longer values are truncated, they're used as metric labels
+const MAX_IDENTITY_LEN: usize = 64;
+/// Distinct clients and versions given their own metric labels, those beyond are counted together as `other` so a
+/// client sending a new identity per connection can't grow the metrics without bound
+const MAX_LABELLED_CLIENTS: usize = 50;
+const OTHER_CLIENTS: &str = "other";
+
+/// The application which opened a subscription, as it identified itself in the request's metadata.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ClientIdentity {
+    pub(crate) name: String,
+    pub(crate) version: String,
+}
+
+impl ClientIdentity {
+    pub(crate) fn from_metadata(metadata: &MetadataMap) -> Self {
+        let value = |key| {
+            metadata
+                .get(key)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().chars().take(MAX_IDENTITY_LEN).collect())
+                .unwrap_or_default()
+        };
+
+        Self {
+            name: value(CLIENT_NAME_METADATA),
+            version: value(CLIENT_VERSION_METADATA),
+        }
+    }
+
+    /// Metric labels, clients which don't identify themselves are counted together.
+    fn labels(&self) -> (String, String) {
+        (
+            or_unknown(&self.name).to_string(),
+            or_unknown(&self.version).to_string(),
+        )
+    }
+}
+
+fn or_unknown(value: &str) -> &str {
+    if value.is_empty() {
+        "unknown"
+    } else {
+        value
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    traded_pair: TradedPair,
+    depth: usize,
+    client: ClientIdentity,
+    /// The client's metric labels, kept so the subscription is counted down under the labels it was counted up under
+    labels: (String, String),
+    tenant: Option<TenantId>,
+    opened: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    next_id: u64,
+    entries: BTreeMap<u64, Entry>,
+    /// Clients given labels of their own, see [MAX_LABELLED_CLIENTS]
+    labelled: HashSet<(String, String)>,
+}
+
+impl Registry {
+    fn labels(&mut self, client: &ClientIdentity) -> (String, String) {
+        let labels = client.labels();
+        if self.labelled.contains(&labels) || self.labelled.len() < MAX_LABELLED_CLIENTS {
+            self.labelled.insert(labels.clone());
+            labels
+        } else {
+            (OTHER_CLIENTS.to_string(), OTHER_CLIENTS.to_string())
+        }
+    }
+}
+
+/// The open summary subscriptions, for the ListSubscribers admin RPC.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Subscribers {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Subscribers {
+    /// Record a new subscription, which is listed for as long as the returned registration is held.
+    pub(crate) fn register(
+        &self,
+        traded_pair: TradedPair,
+        depth: usize,
+        client: ClientIdentity,
+        tenant: Option<TenantId>,
+    ) -> Registration {
+        let mut registry = self.registry.lock().expect("Should lock");
+        let labels = registry.labels(&client);
+        client_subscriptions(&labels.0, &labels.1).inc();
+        registry.next_id += 1;
+        let id = registry.next_id;
+        registry.entries.insert(
+            id,
+            Entry {
+                traded_pair,
+                depth,
+                client,
+                labels,
+                tenant,
+                opened: Instant::now(),
+            },
+        );
+
+        Registration {
+            id,
+            subscribers: self.clone(),
+        }
+    }
+
+    /// How many subscriptions are open.
+    pub(crate) fn count(&self) -> usize {
+        self.registry.lock().expect("Should lock").entries.len()
+    }
+
+    pub(crate) fn list(&self) -> Vec<Subscriber> {
+        self.registry
+            .lock()
+            .expect("Should lock")
+            .entries
+            .iter()
+            .map(|(id, entry)| Subscriber {
+                id: *id,
+                traded_pair: Some(entry.traded_pair.clone()),
+                depth: entry.depth as u32,
+                client_name: entry.client.name.clone(),
+                client_version: entry.client.version.clone(),
+                tenant: entry
+                    .tenant
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+                open_for_millis: entry.opened.elapsed().as_millis() as u64,
+            })
+            .collect()
+    }
+}
+
+/// A subscription's place in [Subscribers], removed when dropped.
+pub(crate) struct Registration {
+    id: u64,
+    subscribers: Subscribers,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let removed = self
+            .subscribers
+            .registry
+            .lock()
+            .expect("Should lock")
+            .entries
+            .remove(&self.id);
+        if let Some(entry) = removed {
+            let (name, version) = &entry.labels;
+            client_subscriptions(name, version).dec();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::metadata::MetadataMap;
+
+    use order_book_service_types::proto::TradedPair;
+
+    use crate::metrics::client_subscriptions;
+
+    use super::{ClientIdentity, Subscribers, MAX_LABELLED_CLIENTS};
+
+    #[test]
+    fn should_list_subscribers_until_their_registration_is_dropped() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("client-name", "risk-engine".parse().unwrap());
+        metadata.insert("client-version", "1.4.2".parse().unwrap());
+        let identity = ClientIdentity::from_metadata(&metadata);
+        assert_eq!(identity.name, "risk-engine");
+        assert_eq!(identity.version, "1.4.2");
+
+        let subscribers = Subscribers::default();
+        let first = subscribers.register(TradedPair::new("ETH", "BTC"), 10, identity, None);
+        let second = subscribers.register(
+            TradedPair::new("BTC", "USDT"),
+            5,
+            ClientIdentity::from_metadata(&MetadataMap::new()),
+            None,
+        );
+
+        let listed = subscribers.list();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].client_name, "risk-engine");
+        assert_eq!(listed[1].client_name, "");
+        assert_eq!(listed[1].depth, 5);
+
+        drop(first);
+        let listed = subscribers.list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].traded_pair, Some(TradedPair::new("BTC", "USDT")));
+        drop(second);
+        assert!(subscribers.list().is_empty());
+    }
+
+    #[test]
+    fn should_count_clients_beyond_the_labelled_limit_as_other() {
+        let subscribers = Subscribers::default();
+        let register = |version: usize| {
+            let mut metadata = MetadataMap::new();
+            metadata.insert("client-name", "per-build".parse().unwrap());
+            metadata.insert("client-version", version.to_string().parse().unwrap());
+            subscribers.register(
+                TradedPair::new("ETH", "BTC"),
+                10,
+                ClientIdentity::from_metadata(&metadata),
+                None,
+            )
+        };
+
+        let registrations = (0..MAX_LABELLED_CLIENTS + 2)
+            .map(register)
+            .collect::<Vec<_>>();
+        assert_eq!(client_subscriptions("per-build", "0").get(), 1);
+        assert_eq!(client_subscriptions("other", "other").get(), 2);
+        // A labelled client keeps its labels
+        let again = register(0);
+        assert_eq!(client_subscriptions("per-build", "0").get(), 2);
+
+        drop(again);
+        drop(registrations);
+        assert_eq!(client_subscriptions("other", "other").get(), 0);
+    }
+}
@@ -0,0 +1,417 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+        Arc, RwLock,
+    },
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Error};
+use serde_json::Value;
+use tracing::error;
+
+use crate::{config::TapConfig, metrics::ChannelMeter};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Frames queued for the writer, beyond which frames are dropped rather than holding up the websocket they came from.
+const TAP_QUEUE_FRAMES: usize = 4096;
+
+/// Tees raw websocket frames from exchanges to rotating files before they are parsed,
+/// so that real payloads can be captured when reporting parse failures.
+///
+/// Taps are enabled per exchange, either in the config or at runtime via the admin service. Exchange names are matched
+/// ignoring case. Frames are written by a dedicated thread, so a slow disk never holds up a websocket.
+#[derive(Clone)]
+pub(crate) struct FrameTap {
+    inner: Arc<TapInner>,
+}
+
+struct TapInner {
+    settings: TapConfig,
+    enabled: RwLock<HashSet<String>>,
+    writer: SyncSender<TapCommand>,
+    meter: ChannelMeter,
+}
+
+enum TapCommand {
+    Write {
+        exchange: String,
+        line: String,
+    },
+    /// Close the exchange's file so it can be collected
+    Close {
+        exchange: String,
+    },
+    #[cfg(test)]
+    Flush(SyncSender<()>),
+}
+
+impl FrameTap {
+    pub(crate) fn new(settings: TapConfig) -> Self {
+        let (writer, commands) = sync_channel(TAP_QUEUE_FRAMES);
+        let writer_settings = settings.clone();
+        thread::Builder::new()
+            .name("frame-tap".to_string())
+            .spawn(move || write_frames(writer_settings, commands))
+            .expect("Should spawn the frame tap writer");
+
+        let tap = Self {
+            inner: Arc::new(TapInner {
+                settings,
+                enabled: RwLock::default(),
+                writer,
+                meter: ChannelMeter::new("frame_tap", "", TAP_QUEUE_FRAMES),
+            }),
+        };
+        for exchange in tap.inner.settings.exchanges.iter() {
+            tap.set_enabled(exchange, true);
+        }
+        tap
+    }
+
+    pub(crate) fn set_enabled(&self, exchange: &str, enabled: bool) {
+        let mut enabled_exchanges = self.inner.enabled.write().expect("Should lock");
+        let was_enabled = enabled_exchanges.len();
+        enabled_exchanges.retain(|enabled| !enabled.eq_ignore_ascii_case(exchange));
+        if enabled {
+            enabled_exchanges.insert(exchange.to_string());
+        } else if enabled_exchanges.len() < was_enabled {
+            // Blocks for room rather than dropping, so the file is always closed
+            let _ = self.inner.writer.send(TapCommand::Close {
+                exchange: exchange.to_ascii_lowercase(),
+            });
+        }
+    }
+
+    /// The exchanges with the tap enabled, sorted by name.
+    pub(crate) fn enabled_exchanges(&self) -> Vec<String> {
+        let mut enabled_exchanges = self
+            .inner
+            .enabled
+            .read()
+            .expect("Should lock")
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        enabled_exchanges.sort_unstable();
+        enabled_exchanges
+    }
+
+    /// Queue a raw frame from `exchange` to be written if its tap is enabled, dropping it when the writer is behind.
+    pub(crate) fn record(&self, exchange: &str, frame: &str) {
+        if !self
+            .inner
+            .enabled
+            .read()
+            .expect("Should lock")
+            .iter()
+            .any(|enabled| enabled.eq_ignore_ascii_case(exchange))
+        {
+            return;
+        }
+
+        let settings = &self.inner.settings;
+        let mut frame = redact(frame, &settings.redact_keys);
+        truncate(&mut frame, settings.max_frame_bytes);
+
+        let received = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        let exchange = exchange.to_ascii_lowercase();
+        let line = format!("{received}\t{frame}");
+        match self
+            .inner
+            .writer
+            .try_send(TapCommand::Write { exchange, line })
+        {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.inner.meter.record_overflow();
+                self.inner.meter.record_dropped(1);
+            }
+            Err(TrySendError::Disconnected(_)) => error!("The frame tap writer has stopped"),
+        }
+    }
+
+    /// Wait for the frames queued so far to be written.
+    #[cfg(test)]
+    fn flush(&self) {
+        let (flushed, wait) = sync_channel(1);
+        self.inner
+            .writer
+            .send(TapCommand::Flush(flushed))
+            .expect("Writer should be running");
+        wait.recv().expect("Writer should flush");
+    }
+}
+
+/// Write queued frames to each exchange's files until every [FrameTap] is dropped, flushing whenever the queue empties.
+fn write_frames(settings: TapConfig, commands: Receiver<TapCommand>) {
+    let mut writers = HashMap::<String, RotatingWriter>::new();
+
+    let flush = |writers: &mut HashMap<String, RotatingWriter>| {
+        for (exchange, writer) in writers.iter_mut() {
+            if let Err(err) = writer.flush() {
+                error!("Unable to tap frames from {exchange}: {err:#}");
+            }
+        }
+    };
+
+    while let Ok(mut command) = commands.recv() {
+        loop {
+            match command {
+                TapCommand::Write { exchange, line } => {
+                    let result = match writers.get_mut(&exchange) {
+                        Some(writer) => writer.write_line(&line),
+                        None => {
+                            RotatingWriter::open(&settings, &exchange).and_then(|mut writer| {
+                                writer.write_line(&line)?;
+                                writers.insert(exchange.clone(), writer);
+                                Ok(())
+                            })
+                        }
+                    };
+                    if let Err(err) = result {
+                        error!("Unable to tap frame from {exchange}: {err:#}");
+                    }
+                }
+                TapCommand::Close { exchange } => {
+                    if let Some(mut writer) = writers.remove(&exchange) {
+                        let _ = writer.flush();
+                    }
+                }
+                #[cfg(test)]
+                TapCommand::Flush(flushed) => {
+                    flush(&mut writers);
+                    let _ = flushed.send(());
+                }
+            }
+
+            match commands.try_recv() {
+                Ok(next) => command = next,
+                Err(_) => break,
+            }
+        }
+        flush(&mut writers);
+    }
+    flush(&mut writers);
+}
+
+/// Replace the values of any `redact_keys` in a JSON frame, frames which aren't JSON are left as they are.
+fn redact(frame: &str, redact_keys: &[String]) -> String {
+    if redact_keys.is_empty() {
+        return frame.to_string();
+    }
+
+    match serde_json::from_str::<Value>(frame) {
+        Ok(mut value) => {
+            redact_value(&mut value, redact_keys);
+            value.to_string()
+        }
+        Err(_) => frame.to_string(),
+    }
+}
+
+fn redact_value(value: &mut Value, redact_keys: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if redact_keys.contains(key) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(value, redact_keys);
+                }
+            }
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| redact_value(value, redact_keys)),
+        _ => {}
+    }
+}
+
+/// Truncate to at most `max_bytes`, respecting char boundaries.
+fn truncate(frame: &mut String, max_bytes: usize) {
+    if frame.len() <= max_bytes {
+        return;
+    }
+    let mut boundary = max_bytes;
+    while !frame.is_char_boundary(boundary) {
+        boundary -= 1;
+    }
+    frame.truncate(boundary);
+}
+
+/// Appends lines to `<directory>/<exchange>.log`, rotating to `.log.1`, `.log.2`... once the file is full.
+struct RotatingWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    written: u64,
+    max_file_bytes: u64,
+    max_files: usize,
+}
+
+impl RotatingWriter {
+    fn open(settings: &TapConfig, exchange: &str) -> Result<Self, Error> {
+        fs::create_dir_all(&settings.directory).with_context(|| {
+            format!(
+                "Unable to create tap directory {}",
+                settings.directory.display()
+            )
+        })?;
+
+        let path = settings.directory.join(format!("{exchange}.log"));
+        let file = open_append(&path)?;
+        let written = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+            written,
+            max_file_bytes: settings.max_file_bytes,
+            max_files: settings.max_files,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), Error> {
+        if self.written > 0 && self.written + line.len() as u64 + 1 > self.max_file_bytes {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{line}").context("Unable to write to tap file")?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush().context("Unable to write to tap file")
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        // Shift each rotated file along by one, the oldest is overwritten
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1))
+                    .context("Unable to rotate tap file")?;
+            }
+        }
+
+        // Anything still buffered belongs to the file being rotated out
+        self.flush()?;
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated_path(1)).context("Unable to rotate tap file")?;
+        }
+
+        self.file = BufWriter::new(File::create(&self.path).context("Unable to create tap file")?);
+        self.written = 0;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+}
+
+fn open_append(path: &Path) -> Result<File, Error> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Unable to open tap file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::config::TapConfig;
+
+    use super::{redact, FrameTap};
+
+    fn settings(directory: &std::path::Path) -> TapConfig {
+        TapConfig {
+            directory: directory.to_path_buf(),
+            exchanges: vec!["Binance".to_string()],
+            max_frame_bytes: 16,
+            max_file_bytes: 64,
+            max_files: 2,
+            redact_keys: vec!["listenKey".to_string()],
+        }
+    }
+
+    #[test]
+    fn should_redact_nested_keys() {
+        let redacted = redact(
+            r#"{"data":{"listenKey":"secret","bids":[]}}"#,
+            &["listenKey".to_string()],
+        );
+
+        assert_eq!(redacted, r#"{"data":{"bids":[],"listenKey":"[REDACTED]"}}"#);
+    }
+
+    #[test]
+    fn should_only_record_enabled_exchanges() {
+        let directory = tempfile::tempdir().unwrap();
+        let tap = FrameTap::new(settings(directory.path()));
+
+        tap.record("Binance", "one");
+        tap.record("Bitstamp", "two");
+        tap.flush();
+
+        assert!(directory.path().join("binance.log").exists());
+        assert!(!directory.path().join("bitstamp.log").exists());
+
+        tap.set_enabled("Bitstamp", true);
+        tap.set_enabled("Binance", false);
+        assert_eq!(tap.enabled_exchanges(), vec!["Bitstamp"]);
+    }
+
+    #[test]
+    fn should_truncate_frames_and_rotate_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let tap = FrameTap::new(settings(directory.path()));
+
+        for _ in 0..10 {
+            tap.record("Binance", "a frame which is longer than the cap");
+        }
+        tap.flush();
+
+        let current = fs::read_to_string(directory.path().join("binance.log")).unwrap();
+        let line = current.lines().next().unwrap();
+        // Each line is the timestamp, a tab and then the truncated frame
+        assert!(line.ends_with("\ta frame which is"));
+
+        assert!(directory.path().join("binance.log.1").exists());
+        assert!(directory.path().join("binance.log.2").exists());
+        assert!(!directory.path().join("binance.log.3").exists());
+        assert!(current.len() <= 64);
+    }
+
+    #[test]
+    fn should_match_exchanges_ignoring_case() {
+        let directory = tempfile::tempdir().unwrap();
+        let tap = FrameTap::new(settings(directory.path()));
+
+        tap.set_enabled("BITSTAMP", true);
+        tap.record("Bitstamp", "one");
+        tap.record("BINANCE", "two");
+        tap.flush();
+        assert!(directory.path().join("bitstamp.log").exists());
+        assert!(directory.path().join("binance.log").exists());
+
+        tap.set_enabled("binance", false);
+        tap.set_enabled("bitstamp", true);
+        assert_eq!(tap.enabled_exchanges(), vec!["bitstamp"]);
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Mutex;
+
+use anyhow::{Context as _, Error};
+use once_cell::sync::OnceCell;
+use opentelemetry::{
+    global,
+    propagation::Extractor,
+    sdk::{propagation::TraceContextPropagator, trace, Resource},
+    Context, KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use tonic::metadata::{KeyRef, MetadataMap};
+use tracing_subscriber::{filter::Targets, fmt, prelude::*, reload, Registry};
+
+use crate::config::TracingConfig;
+
+/// The installed subscriber's filter, which the admin service can replace at runtime.
+static LOG_FILTER: OnceCell<LogFilter> = OnceCell::new();
+
+struct LogFilter {
+    handle: reload::Handle<Targets, Registry>,
+    configured: String,
+    current: Mutex<String>,
+}
+
+/// Install the global tracing subscriber, logging to stdout and, when an OTLP endpoint is configured,
+/// exporting spans so latency can be traced from exchange messages through to clients.
+///
+/// Must be called from within the tokio runtime.
+pub(crate) fn init(config: &TracingConfig) -> Result<(), Error> {
+    // Clients pass their trace context as W3C `traceparent` metadata
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let otel_layer = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(trace::config().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", config.service_name.clone()),
+                ])))
+                .install_batch(opentelemetry::runtime::Tokio)
+                .context("Unable to install OTLP exporter")?;
+
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+
+    let targets = config
+        .log_filter
+        .parse::<Targets>()
+        .context("Invalid log_filter")?;
+    let (filter_layer, handle) = reload::Layer::new(targets);
+    // The console filters tokio's own instrumentation itself, so the log filter only applies to the logs and spans
+    #[cfg(feature = "console")]
+    let console_layer = console_subscriber::spawn();
+    #[cfg(not(feature = "console"))]
+    let console_layer = tracing_subscriber::layer::Identity::new();
+
+    tracing_subscriber::registry()
+        .with(fmt::layer().and_then(otel_layer).with_filter(filter_layer))
+        .with(console_layer)
+        .try_init()
+        .context("Unable to install tracing subscriber")?;
+
+    let _ = LOG_FILTER.set(LogFilter {
+        handle,
+        configured: config.log_filter.clone(),
+        current: Mutex::new(config.log_filter.clone()),
+    });
+    Ok(())
+}
+
+/// The filter currently applied, if the service installed the subscriber.
+pub(crate) fn log_filter() -> Option<String> {
+    LOG_FILTER
+        .get()
+        .map(|log_filter| log_filter.current.lock().expect("Should lock").clone())
+}
+
+/// Replace the log filter, or restore the configured filter when `filter` is empty, returning the filter replaced.
+///
+/// `None` when the service didn't install the subscriber, e.g. when embedded with [start_in_process](crate::start_in_process).
+pub(crate) fn set_log_filter(filter: &str) -> Option<Result<String, Error>> {
+    let log_filter = LOG_FILTER.get()?;
+    let filter = if filter.trim().is_empty() {
+        log_filter.configured.as_str()
+    } else {
+        filter
+    };
+
+    Some(
+        filter
+            .parse::<Targets>()
+            .context("Invalid filter")
+            .and_then(|targets| {
+                log_filter
+                    .handle
+                    .reload(targets)
+                    .context("Unable to replace the filter")
+            })
+            .map(|_| {
+                let mut current = log_filter.current.lock().expect("Should lock");
+                std::mem::replace(&mut *current, filter.to_string())
+            }),
+    )
+}
+
+/// Flush any spans which haven't been exported yet.
+pub(crate) fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// The trace context a client propagated in the metadata of its request, if any.
+pub(crate) fn remote_context(metadata: &MetadataMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&MetadataExtractor(metadata)))
+}
+
+struct MetadataExtractor<'a>(&'a MetadataMap);
+
+impl Extractor for MetadataExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .map(|key| match key {
+                KeyRef::Ascii(key) => key.as_str(),
+                KeyRef::Binary(key) => key.as_str(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::{
+        propagation::TextMapPropagator, sdk::propagation::TraceContextPropagator,
+        trace::TraceContextExt,
+    };
+    use tonic::metadata::MetadataMap;
+
+    use super::MetadataExtractor;
+
+    #[test]
+    fn should_extract_trace_context_from_metadata() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+
+        let context = TraceContextPropagator::new().extract(&MetadataExtractor(&metadata));
+        let span_context = context.span().span_context().clone();
+
+        assert!(span_context.is_remote());
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+    }
+}
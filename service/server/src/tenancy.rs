@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    sync::{Arc, Mutex},
+};
+
+use tonic::{service::Interceptor, Code, Request, Status};
+
+use order_book_service_types::proto::CloseReason;
+
+use crate::{
+    config::TenantConfig,
+    metrics::{tenant_rejected_subscriptions, tenant_subscriptions},
+};
+
+/// Metadata key clients authenticate with.
+pub(crate) const API_KEY_METADATA: &str = "x-api-key";
+
+/// The tenant a request was authenticated as, added to the request's extensions by [TenantInterceptor].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct TenantId(String);
+
+impl Display for TenantId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The configured tenants, tenancy is disabled and every client allowed when there are none.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Tenants {
+    tenants: Arc<Vec<TenantConfig>>,
+}
+
+impl Tenants {
+    pub(crate) fn new(tenants: &[TenantConfig]) -> Self {
+        Self {
+            tenants: Arc::new(tenants.to_vec()),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.tenants.is_empty()
+    }
+
+    fn authenticate(&self, api_key: &str) -> Option<TenantId> {
+        self.tenants
+            .iter()
+            .find(|tenant| tenant.api_key == api_key)
+            .map(|tenant| TenantId(tenant.id.clone()))
+    }
+
+    fn get(&self, tenant: &TenantId) -> Option<&TenantConfig> {
+        self.tenants.iter().find(|config| config.id == tenant.0)
+    }
+}
+
+/// Authorizes admin operations by the `x-api-key` they send, which must be the admin `api_key` or an admin tenant's key.
+///
+/// Every admin operation is refused when neither is configured, whether or not tenancy is enabled.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AdminAuth {
+    tenants: Tenants,
+    api_key: Option<String>,
+}
+
+impl AdminAuth {
+    pub(crate) fn new(tenants: Tenants, api_key: Option<String>) -> Self {
+        Self { tenants, api_key }
+    }
+
+    // Status is large but is returned straight to the client
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn authorize<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        let has_admin_tenant = self.tenants.tenants.iter().any(|config| config.admin);
+        if self.api_key.is_none() && !has_admin_tenant {
+            return Err(Status::permission_denied(
+                "The admin service needs an admin api_key or admin tenant configured",
+            ));
+        }
+
+        let api_key = request
+            .metadata()
+            .get(API_KEY_METADATA)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated(format!("Missing {API_KEY_METADATA}")))?;
+        if self.api_key.as_deref() == Some(api_key) {
+            return Ok(());
+        }
+
+        match self.tenants.authenticate(api_key) {
+            Some(tenant) if self.tenants.get(&tenant).is_some_and(|config| config.admin) => Ok(()),
+            Some(tenant) => Err(Status::permission_denied(format!(
+                "Tenant {tenant} can't use the admin service"
+            ))),
+            None => Err(Status::unauthenticated(format!(
+                "Unknown {API_KEY_METADATA}"
+            ))),
+        }
+    }
+}
+
+/// Authenticates each request by its `x-api-key` metadata when tenancy is enabled, recording the [TenantId].
+#[derive(Clone, Debug)]
+pub(crate) struct TenantInterceptor {
+    tenants: Tenants,
+}
+
+impl TenantInterceptor {
+    pub(crate) fn new(tenants: Tenants) -> Self {
+        Self { tenants }
+    }
+}
+
+impl Interceptor for TenantInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if !self.tenants.is_enabled() {
+            return Ok(request);
+        }
+
+        let api_key = request
+            .metadata()
+            .get(API_KEY_METADATA)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated(format!("Missing {API_KEY_METADATA}")))?;
+        let tenant = self
+            .tenants
+            .authenticate(api_key)
+            .ok_or_else(|| Status::unauthenticated(format!("Unknown {API_KEY_METADATA}")))?;
+
+        request.extensions_mut().insert(tenant);
+        Ok(request)
+    }
+}
+
+/// Enforces each tenant's limit on open subscriptions.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SubscriptionGovernor {
+    tenants: Tenants,
+    open: Arc<Mutex<HashMap<TenantId, usize>>>,
+}
+
+impl SubscriptionGovernor {
+    pub(crate) fn new(tenants: Tenants) -> Self {
+        Self {
+            tenants,
+            open: Arc::default(),
+        }
+    }
+
+    /// A permit to hold for the life of a subscription, `None` when tenancy is disabled.
+    // Status is large but is returned straight to the client
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn admit(
+        &self,
+        tenant: Option<&TenantId>,
+    ) -> Result<Option<SubscriptionPermit>, Status> {
+        let Some(tenant) = tenant else {
+            return Ok(None);
+        };
+        let max_subscriptions = self
+            .tenants
+            .get(tenant)
+            .and_then(|config| config.max_subscriptions);
+
+        let mut open = self.open.lock().expect("Should lock");
+        let count = open.entry(tenant.clone()).or_default();
+        if max_subscriptions.is_some_and(|max| *count >= max) {
+            tenant_rejected_subscriptions(&tenant.0).inc();
+            return Err(CloseReason::QuotaExceeded.status(
+                Code::ResourceExhausted,
+                format!(
+                    "Tenant {tenant} is at its limit of {} subscriptions",
+                    *count
+                ),
+            ));
+        }
+        *count += 1;
+        tenant_subscriptions(&tenant.0).set(*count as i64);
+
+        Ok(Some(SubscriptionPermit {
+            tenant: tenant.clone(),
+            open: self.open.clone(),
+        }))
+    }
+}
+
+/// Counts towards its tenant's open subscriptions until dropped.
+#[derive(Debug)]
+pub(crate) struct SubscriptionPermit {
+    tenant: TenantId,
+    open: Arc<Mutex<HashMap<TenantId, usize>>>,
+}
+
+impl Drop for SubscriptionPermit {
+    fn drop(&mut self) {
+        let mut open = self.open.lock().expect("Should lock");
+        if let Some(count) = open.get_mut(&self.tenant) {
+            *count = count.saturating_sub(1);
+            tenant_subscriptions(&self.tenant.0).set(*count as i64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::{service::Interceptor, Code, Request};
+
+    use crate::config::TenantConfig;
+
+    use super::{AdminAuth, SubscriptionGovernor, TenantId, TenantInterceptor, Tenants};
+
+    fn tenants() -> Tenants {
+        Tenants::new(&[
+            TenantConfig {
+                id: "desk".to_string(),
+                api_key: "desk-key".to_string(),
+                max_subscriptions: Some(1),
+                admin: false,
+            },
+            TenantConfig {
+                id: "ops".to_string(),
+                api_key: "ops-key".to_string(),
+                max_subscriptions: None,
+                admin: true,
+            },
+        ])
+    }
+
+    fn request(api_key: &str) -> Request<()> {
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("x-api-key", api_key.parse().unwrap());
+        request
+    }
+
+    fn authenticate(tenants: &Tenants, api_key: &str) -> Result<Request<()>, Code> {
+        TenantInterceptor::new(tenants.clone())
+            .call(request(api_key))
+            .map_err(|status| status.code())
+    }
+
+    #[test]
+    fn should_authenticate_tenants_and_isolate_admin() {
+        let tenants = tenants();
+
+        let desk = authenticate(&tenants, "desk-key").unwrap();
+        assert_eq!(
+            desk.extensions().get::<TenantId>(),
+            Some(&TenantId("desk".to_string()))
+        );
+        let admin = AdminAuth::new(tenants.clone(), None);
+        assert_eq!(
+            admin.authorize(&desk).unwrap_err().code(),
+            Code::PermissionDenied
+        );
+
+        let ops = authenticate(&tenants, "ops-key").unwrap();
+        assert!(admin.authorize(&ops).is_ok());
+
+        assert_eq!(
+            authenticate(&tenants, "wrong-key").unwrap_err(),
+            Code::Unauthenticated
+        );
+        // Without tenants every request is allowed
+        assert!(authenticate(&Tenants::default(), "any-key").is_ok());
+    }
+
+    #[test]
+    fn should_require_an_admin_credential_without_tenancy() {
+        let unconfigured = AdminAuth::default();
+        assert_eq!(
+            unconfigured
+                .authorize(&request("any-key"))
+                .unwrap_err()
+                .code(),
+            Code::PermissionDenied
+        );
+
+        let admin = AdminAuth::new(Tenants::default(), Some("admin-key".to_string()));
+        assert!(admin.authorize(&request("admin-key")).is_ok());
+        assert_eq!(
+            admin.authorize(&request("wrong-key")).unwrap_err().code(),
+            Code::Unauthenticated
+        );
+        assert_eq!(
+            admin.authorize(&Request::new(())).unwrap_err().code(),
+            Code::Unauthenticated
+        );
+    }
+
+    #[test]
+    fn should_enforce_subscription_quota_until_permits_are_dropped() {
+        let governor = SubscriptionGovernor::new(tenants());
+        let desk = TenantId("desk".to_string());
+
+        let permit = governor.admit(Some(&desk)).unwrap();
+        assert!(permit.is_some());
+        assert_eq!(
+            governor.admit(Some(&desk)).unwrap_err().code(),
+            Code::ResourceExhausted
+        );
+
+        drop(permit);
+        assert!(governor.admit(Some(&desk)).is_ok());
+        assert!(governor.admit(None).unwrap().is_none());
+    }
+}
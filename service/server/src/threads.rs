@@ -0,0 +1,164 @@
+use std::{
+    cell::Cell,
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::{Context, Error};
+use tokio::runtime::{Builder, Runtime};
+use tracing::warn;
+
+use crate::{config::ThreadConfig, metrics::runtime_threads, Config};
+
+thread_local! {
+    /// The settings applied to this thread, so they're no longer counted once it stops
+    static APPLIED: Cell<(bool, bool)> = const { Cell::new((false, false)) };
+}
+
+/// Build the runtime for [serve](crate::serve), with its worker threads pinned to cores and its threads prioritised as
+/// configured.
+///
+/// Every task runs on the workers, the exchange connectors and each pair's aggregator alike, so pinning them keeps them
+/// all on the given cores. Blocking threads, e.g. writing metrics snapshots, are left free to run on any core.
+pub fn build_runtime(config: &Config) -> Result<Runtime, Error> {
+    let threads = config.threads.clone();
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    let workers = threads.cores.len();
+    if workers > 0 {
+        builder.worker_threads(workers);
+    }
+    // Tokio starts every worker as the runtime is built, before any blocking thread, so the first threads are workers
+    let started = Arc::new(AtomicUsize::new(0));
+    builder
+        .on_thread_start(move || {
+            let worker = started.fetch_add(1, Ordering::SeqCst) < workers;
+            configure_thread(&threads, worker)
+        })
+        .on_thread_stop(|| {
+            let (pinned, prioritised) = APPLIED.with(Cell::take);
+            if pinned {
+                runtime_threads("pinned").dec();
+            }
+            if prioritised {
+                runtime_threads("prioritised").dec();
+            }
+        })
+        .build()
+        .context("Unable to start the runtime")
+}
+
+/// Apply the settings to the current thread, only pinning it if it's a `worker`. A setting which can't be applied is
+/// logged rather than stopping the service.
+fn configure_thread(config: &ThreadConfig, worker: bool) {
+    let pinned = worker
+        && !config.cores.is_empty()
+        && pin_to_cores(&config.cores)
+            .map_err(|err| {
+                warn!(
+                    "Unable to pin a runtime thread to cores {:?}: {err}",
+                    config.cores
+                )
+            })
+            .is_ok();
+    let prioritised = config.nice.is_some_and(|nice| {
+        set_nice(nice)
+            .map_err(|err| warn!("Unable to set a runtime thread's nice value to {nice}: {err}"))
+            .is_ok()
+    });
+
+    if pinned {
+        runtime_threads("pinned").inc();
+    }
+    if prioritised {
+        runtime_threads("prioritised").inc();
+    }
+    APPLIED.with(|applied| applied.set((pinned, prioritised)));
+}
+
+#[cfg(target_os = "linux")]
+fn pin_to_cores(cores: &[usize]) -> io::Result<()> {
+    // SAFETY: the set is zeroed and cores are validated to be within its size
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for core in cores {
+            libc::CPU_SET(*core, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_nice(nice: i32) -> io::Result<()> {
+    // Each thread has a nice value of its own on Linux, set through its thread id
+    // SAFETY: gettid takes no arguments and always succeeds, and setpriority only reads its arguments
+    let result = unsafe {
+        let thread = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        libc::setpriority(libc::PRIO_PROCESS, thread, nice)
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_cores(_cores: &[usize]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Only supported on Linux",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_nice(_nice: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Only supported on Linux",
+    ))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use crate::{config::Config, metrics::runtime_threads};
+
+    use super::build_runtime;
+
+    /// The cores this thread may run on.
+    fn allowed_cores() -> Vec<usize> {
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set);
+            (0..libc::CPU_SETSIZE as usize)
+                .filter(|core| libc::CPU_ISSET(*core, &set))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn should_pin_runtime_threads_to_the_configured_cores() {
+        let core = allowed_cores()[0];
+        let config = Config::from_toml(&format!("[threads]\ncores = [{core}]")).unwrap();
+
+        let runtime = build_runtime(&config).unwrap();
+        // Run on a worker rather than the thread blocking on the runtime
+        let pinned = runtime
+            .block_on(runtime.spawn(async { allowed_cores() }))
+            .unwrap();
+        let blocking = runtime
+            .block_on(runtime.spawn_blocking(allowed_cores))
+            .unwrap();
+
+        assert_eq!(pinned, vec![core]);
+        assert!(runtime_threads("pinned").get() >= 1);
+        // Blocking threads aren't pinned
+        assert_eq!(blocking, allowed_cores());
+    }
+}
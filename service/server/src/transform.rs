@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+
+use order_book_service_types::proto::{
+    AskLevel, BidLevel, ExchangeId, KnownExchange, Level, Summary, TradedPair,
+};
+
+use crate::{
+    aggregator::MergedBook,
+    config::{Config, TransformKind},
+};
+
+/// A stage in an aggregator's pipeline, applied to each merged book before its summary is built and broadcast.
+pub(crate) trait SummaryTransform: Send + Sync {
+    /// Describes the transform for subscription descriptions.
+    fn name(&self) -> &'static str;
+
+    fn apply(&self, book: &mut MergedBook);
+}
+
+/// Build the chain of transforms configured for `traded_pair`, in the order they were configured.
+///
+/// The pair's `min_level_quantity` is applied first, so dust is gone before any configured transform sees the book.
+pub(crate) fn transforms_for_pair(
+    config: &Config,
+    traded_pair: &TradedPair,
+) -> Vec<Box<dyn SummaryTransform>> {
+    let pair = traded_pair.to_string();
+
+    let min_level_quantity = config
+        .aggregator
+        .min_level_quantity(traded_pair)
+        .map(|min_amount| -> Box<dyn SummaryTransform> { Box::new(DustFilter { min_amount }) });
+
+    let configured = config
+        .transforms
+        .iter()
+        .filter(|config| {
+            config
+                .pair
+                .as_ref()
+                .is_none_or(|config_pair| config_pair.eq_ignore_ascii_case(&pair))
+        })
+        .map(|config| -> Box<dyn SummaryTransform> {
+            match &config.kind {
+                TransformKind::FeeAdjustment { fees } => Box::new(FeeAdjustment::new(fees)),
+                TransformKind::DustFilter { min_amount } => Box::new(DustFilter {
+                    min_amount: *min_amount,
+                }),
+                TransformKind::Consolidation { tick_size } => Box::new(Consolidation {
+                    tick_size: *tick_size,
+                }),
+            }
+        });
+
+    min_level_quantity.into_iter().chain(configured).collect()
+}
+
+/// Adjusts prices by each exchange's taker fee so levels reflect the price actually paid or received,
+/// asks become more expensive and bids less valuable.
+#[derive(Debug)]
+pub(crate) struct FeeAdjustment {
+    fees: HashMap<ExchangeId, f64>,
+}
+
+impl FeeAdjustment {
+    pub(crate) fn new(fees: &HashMap<String, f64>) -> Self {
+        Self {
+            fees: fees
+                .iter()
+                .map(|(exchange, fee)| (ExchangeId::from(exchange.as_str()), *fee))
+                .collect(),
+        }
+    }
+
+    /// Adjust the price of each level by its exchange's fee, re-sorting as the order may change.
+    pub(crate) fn adjust(&self, asks: &mut [Level], bids: &mut [Level]) {
+        for ask in asks.iter_mut() {
+            ask.price *= 1.0 + self.fee(ask);
+        }
+        for bid in bids.iter_mut() {
+            bid.price *= 1.0 - self.fee(bid);
+        }
+
+        AskLevel::sort(asks);
+        BidLevel::sort(bids);
+    }
+
+    /// Adjust a summary to effective prices, recomputing its spread from the adjusted levels.
+    pub(crate) fn adjust_summary(&self, summary: &mut Summary) {
+        self.adjust(&mut summary.asks, &mut summary.bids);
+
+        if let (Some(ask), Some(bid)) = (summary.asks.first(), summary.bids.first()) {
+            summary.spread = ask.price - bid.price;
+        }
+        summary.update_totals();
+        summary
+            .metadata
+            .get_or_insert_with(Default::default)
+            .effective_prices = true;
+    }
+
+    fn fee(&self, level: &Level) -> f64 {
+        self.fees
+            .get(&level.exchange_id())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl SummaryTransform for FeeAdjustment {
+    fn name(&self) -> &'static str {
+        "fee_adjustment"
+    }
+
+    fn apply(&self, book: &mut MergedBook) {
+        self.adjust(&mut book.asks, &mut book.bids);
+    }
+}
+
+/// Drops levels too small to be worth trading against.
+pub(crate) struct DustFilter {
+    min_amount: f64,
+}
+
+impl SummaryTransform for DustFilter {
+    fn name(&self) -> &'static str {
+        "dust_filter"
+    }
+
+    fn apply(&self, book: &mut MergedBook) {
+        book.asks.retain(|level| level.amount >= self.min_amount);
+        book.bids.retain(|level| level.amount >= self.min_amount);
+    }
+}
+
+/// Merges the levels of every exchange into one level per `tick_size` of price, recording which
+/// exchanges contributed to each.
+///
+/// Ask prices are rounded up and bid prices down, so a consolidated level is never better than the levels within it.
+/// Levels with a single contributor keep their exchange, otherwise the exchange is the contributors joined by `+`.
+pub(crate) struct Consolidation {
+    tick_size: f64,
+}
+
+impl Consolidation {
+    fn consolidate(&self, levels: &[Level], bucket: impl Fn(f64) -> f64) -> Vec<Level> {
+        let mut consolidated: Vec<Level> = Vec::new();
+
+        // Levels are sorted so each bucket's levels are adjacent
+        for level in levels {
+            // Division leaves prices already on a tick slightly off it, which shouldn't round to the next tick
+            let ticks = (level.price / self.tick_size * 1e9).round() / 1e9;
+            let bucket = bucket(ticks) as i64;
+            let price = bucket as f64 * self.tick_size;
+
+            match consolidated.last_mut() {
+                Some(last) if last.price == price => {
+                    last.amount += level.amount;
+                    if !last.contributing_exchanges.contains(&level.exchange) {
+                        last.contributing_exchanges.push(level.exchange.clone());
+                        last.exchange = last.contributing_exchanges.join("+");
+                        last.known_exchange = KnownExchange::Other as i32;
+                    }
+                }
+                _ => consolidated.push(Level {
+                    price,
+                    contributing_exchanges: vec![level.exchange.clone()],
+                    ..level.clone()
+                }),
+            }
+        }
+
+        consolidated
+    }
+}
+
+impl SummaryTransform for Consolidation {
+    fn name(&self) -> &'static str {
+        "consolidation"
+    }
+
+    fn apply(&self, book: &mut MergedBook) {
+        book.asks = self.consolidate(&book.asks, f64::ceil);
+        book.bids = self.consolidate(&book.bids, f64::floor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use order_book_service_types::proto::{Level, Summary, TradedPair};
+
+    use crate::{
+        aggregator::MergedBook,
+        config::{AggregatorConfig, Config, TransformConfig, TransformKind},
+    };
+
+    use super::{transforms_for_pair, Consolidation, DustFilter, FeeAdjustment, SummaryTransform};
+
+    #[test]
+    fn should_adjust_prices_by_fee_and_reorder() {
+        let mut book = MergedBook {
+            asks: vec![
+                Level::new("Binance", 100.0, 1.0),
+                Level::new("Bitstamp", 100.5, 1.0),
+            ],
+            bids: vec![Level::new("Binance", 99.0, 1.0)],
+        };
+
+        FeeAdjustment::new(&HashMap::from([("binance".to_string(), 0.01)])).apply(&mut book);
+
+        // Binance's ask is now worse than Bitstamp's, which has no fee configured
+        assert_eq!(
+            book.asks,
+            vec![
+                Level::new("Bitstamp", 100.5, 1.0),
+                Level::new("Binance", 101.0, 1.0)
+            ]
+        );
+        assert_eq!(book.bids, vec![Level::new("Binance", 98.01, 1.0)]);
+    }
+
+    #[test]
+    fn should_recompute_spread_for_effective_prices() {
+        let mut summary = Summary {
+            spread: 1.0,
+            asks: vec![Level::new("Binance", 101.0, 1.0)],
+            bids: vec![Level::new("Bitstamp", 100.0, 1.0)],
+            ..Default::default()
+        };
+
+        FeeAdjustment::new(&HashMap::from([
+            ("Binance".to_string(), 0.01),
+            ("Bitstamp".to_string(), 0.01),
+        ]))
+        .adjust_summary(&mut summary);
+
+        assert!((summary.spread - (102.01 - 99.0)).abs() < 1e-9);
+        assert!(summary.metadata.unwrap().effective_prices);
+    }
+
+    #[test]
+    fn should_filter_dust_levels() {
+        let mut book = MergedBook {
+            asks: vec![
+                Level::new("Binance", 1.0, 0.001),
+                Level::new("Binance", 2.0, 1.0),
+            ],
+            bids: vec![Level::new("Binance", 0.5, 0.5)],
+        };
+
+        DustFilter { min_amount: 0.5 }.apply(&mut book);
+
+        assert_eq!(book.asks, vec![Level::new("Binance", 2.0, 1.0)]);
+        assert_eq!(book.bids.len(), 1);
+    }
+
+    #[test]
+    fn should_consolidate_levels_and_record_contributors() {
+        let mut book = MergedBook {
+            asks: vec![
+                Level::new("Binance", 0.0700, 1.0),
+                Level::new("Bitstamp", 0.07004, 2.0),
+                Level::new("Binance", 0.07008, 3.0),
+                Level::new("Binance", 0.0702, 4.0),
+            ],
+            bids: vec![
+                Level::new("Bitstamp", 0.06999, 1.0),
+                Level::new("Binance", 0.06991, 1.0),
+            ],
+        };
+
+        Consolidation { tick_size: 0.0001 }.apply(&mut book);
+
+        let asks: Vec<_> = book
+            .asks
+            .iter()
+            .map(|level| (level.exchange.as_str(), level.amount))
+            .collect();
+        // Asks round up, 0.0700 is already on a tick
+        assert_eq!(
+            asks,
+            vec![
+                ("Binance", 1.0),
+                ("Bitstamp+Binance", 5.0),
+                ("Binance", 4.0)
+            ]
+        );
+        assert_eq!(
+            book.asks[1].contributing_exchanges,
+            vec!["Bitstamp".to_string(), "Binance".to_string()]
+        );
+
+        // Bids round down into the same level
+        assert_eq!(book.bids.len(), 1);
+        assert_eq!(book.bids[0].amount, 2.0);
+        assert_eq!(book.bids[0].contributing_exchanges.len(), 2);
+    }
+
+    #[test]
+    fn should_only_build_transforms_for_matching_pairs() {
+        let transforms = vec![
+            TransformConfig {
+                pair: Some("eth-btc".to_string()),
+                kind: TransformKind::DustFilter { min_amount: 1.0 },
+            },
+            TransformConfig {
+                pair: None,
+                kind: TransformKind::FeeAdjustment {
+                    fees: HashMap::new(),
+                },
+            },
+        ];
+        let config = Config {
+            transforms,
+            ..Config::default()
+        };
+
+        let eth_btc = transforms_for_pair(&config, &TradedPair::new("ETH", "BTC"));
+        let ltc_btc = transforms_for_pair(&config, &TradedPair::new("LTC", "BTC"));
+
+        assert_eq!(
+            eth_btc.iter().map(|t| t.name()).collect::<Vec<_>>(),
+            vec!["dust_filter", "fee_adjustment"]
+        );
+        assert_eq!(ltc_btc.len(), 1);
+    }
+
+    #[test]
+    fn should_filter_below_min_level_quantity_before_configured_transforms() {
+        let config = Config {
+            aggregator: AggregatorConfig {
+                min_level_quantity: HashMap::from([("ETH-BTC".to_string(), 0.5)]),
+                ..AggregatorConfig::default()
+            },
+            transforms: vec![TransformConfig {
+                pair: None,
+                kind: TransformKind::Consolidation { tick_size: 1.0 },
+            }],
+            ..Config::default()
+        };
+
+        let eth_btc = transforms_for_pair(&config, &TradedPair::new("ETH", "BTC"));
+        assert_eq!(
+            eth_btc.iter().map(|t| t.name()).collect::<Vec<_>>(),
+            vec!["dust_filter", "consolidation"]
+        );
+
+        // The dust would otherwise have been consolidated into the level
+        let mut book = MergedBook {
+            asks: vec![
+                Level::new("Binance", 100.2, 1.0),
+                Level::new("Bitstamp", 100.4, 0.001),
+            ],
+            bids: vec![Level::new("Binance", 99.0, 1.0)],
+        };
+        for transform in eth_btc.iter() {
+            transform.apply(&mut book);
+        }
+        assert_eq!(book.asks.len(), 1);
+        assert_eq!(book.asks[0].amount, 1.0);
+
+        assert_eq!(
+            transforms_for_pair(&config, &TradedPair::new("LTC", "BTC")).len(),
+            1
+        );
+    }
+}
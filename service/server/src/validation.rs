@@ -0,0 +1,296 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{Display, Formatter},
+};
+
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use order_book_service_types::proto::{
+    service_event::Event, ExchangeId, FeedQuarantine, Level, TradedPair,
+};
+
+use crate::{
+    clock::SharedClock,
+    config::ValidationConfig,
+    events::EventBus,
+    exchange::OrderBook,
+    metrics::{invalid_books, pair_label},
+};
+
+/// Why a book is invalid.
+///
+/// Non-finite prices and amounts are refused as books are parsed and each book sorts its own levels, so its top is what's
+/// left to check.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Violation {
+    /// The best bid is above the best ask
+    Crossed,
+}
+
+impl Violation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Violation::Crossed => "crossed",
+        }
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The invariant the top of a book breaks, if any.
+fn violation(best_ask: Option<&Level>, best_bid: Option<&Level>) -> Option<Violation> {
+    match (best_ask, best_bid) {
+        (Some(ask), Some(bid)) if bid.price > ask.price => Some(Violation::Crossed),
+        _ => None,
+    }
+}
+
+/// An exchange's recent invalid books.
+#[derive(Debug, Default)]
+struct Strikes {
+    received: VecDeque<Instant>,
+    quarantined_until: Option<Instant>,
+}
+
+/// Checks the top of each book before it's merged, counting those which are invalid.
+///
+/// In strict mode invalid books are dropped so a single bad message can't corrupt a summary, and an exchange sending
+/// `max_invalid_books` within the window is quarantined, its books are left out of the pair's merges until the
+/// quarantine ends, rather than dropping its bad books one at a time indefinitely.
+pub(crate) struct FeedValidator {
+    traded_pair: TradedPair,
+    config: ValidationConfig,
+    event_bus: EventBus,
+    clock: SharedClock,
+    strikes: HashMap<ExchangeId, Strikes>,
+}
+
+impl FeedValidator {
+    pub(crate) fn new(
+        traded_pair: TradedPair,
+        config: ValidationConfig,
+        event_bus: EventBus,
+        clock: SharedClock,
+    ) -> Self {
+        Self {
+            traded_pair,
+            config,
+            event_bus,
+            clock,
+            strikes: HashMap::new(),
+        }
+    }
+
+    /// Whether the book can be merged, `false` in strict mode when it's invalid or its exchange is quarantined.
+    pub(crate) fn admit(&mut self, orderbook: &dyn OrderBook) -> bool {
+        let exchange = orderbook.source();
+        let now = self.clock.now();
+
+        if let Some(strikes) = self.strikes.get_mut(&exchange) {
+            match strikes.quarantined_until {
+                Some(until) if now < until => return false,
+                Some(_) => {
+                    info!(
+                        "{exchange} is no longer quarantined for {}, merging its books again",
+                        self.traded_pair
+                    );
+                    *strikes = Strikes::default();
+                    self.publish(&exchange, 0, "", true);
+                }
+                None => {}
+            }
+        }
+
+        let Some(violation) =
+            violation(orderbook.best_ask().as_ref(), orderbook.best_bid().as_ref())
+        else {
+            return true;
+        };
+
+        invalid_books(
+            &pair_label(&self.traded_pair),
+            exchange.as_str(),
+            violation.as_str(),
+        )
+        .inc();
+        if !self.config.strict {
+            debug!(
+                "Merging {violation} book from {exchange} for {}",
+                self.traded_pair
+            );
+            return true;
+        }
+        debug!(
+            "Dropping {violation} book from {exchange} for {}",
+            self.traded_pair
+        );
+
+        let strikes = self.strikes.entry(exchange.clone()).or_default();
+        let window = self.config.window();
+        while strikes
+            .received
+            .front()
+            .is_some_and(|received| *received + window <= now)
+        {
+            strikes.received.pop_front();
+        }
+        strikes.received.push_back(now);
+
+        let count = strikes.received.len();
+        if count >= self.config.max_invalid_books {
+            strikes.quarantined_until = Some(now + self.config.quarantine());
+            warn!(
+                "{exchange} sent {count} invalid books for {} in {window:?}, the last {violation}, quarantining it for {:?}",
+                self.traded_pair,
+                self.config.quarantine()
+            );
+            self.publish(&exchange, count, violation.as_str(), false);
+        }
+        false
+    }
+
+    fn publish(
+        &self,
+        exchange: &ExchangeId,
+        invalid_books: usize,
+        violation: &str,
+        resolved: bool,
+    ) {
+        self.event_bus
+            .publish(Event::FeedQuarantine(FeedQuarantine {
+                traded_pair: Some(self.traded_pair.clone()),
+                exchange: exchange.to_string(),
+                invalid_books: invalid_books as u32,
+                violation: violation.to_string(),
+                quarantine_millis: self.config.quarantine().as_millis() as u64,
+                resolved,
+            }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use tokio::time::advance;
+
+    use order_book_service_types::proto::{service_event::Event, ExchangeId, Level, TradedPair};
+
+    use crate::{
+        clock::ManualClock, config::ValidationConfig, events::EventBus, exchange::OrderBook,
+    };
+
+    use super::{violation, FeedValidator, Violation};
+
+    struct LevelsBook {
+        asks: Vec<Level>,
+        bids: Vec<Level>,
+    }
+
+    impl OrderBook for LevelsBook {
+        fn source(&self) -> ExchangeId {
+            ExchangeId::from("Binance")
+        }
+
+        fn best_asks(&self, depth: usize, levels: &mut Vec<Level>) {
+            levels.extend(self.asks.iter().take(depth).cloned());
+        }
+
+        fn best_bids(&self, depth: usize, levels: &mut Vec<Level>) {
+            levels.extend(self.bids.iter().take(depth).cloned());
+        }
+    }
+
+    fn levels(prices: &[f64]) -> Vec<Level> {
+        prices
+            .iter()
+            .map(|price| Level::new("Binance", *price, 1.0))
+            .collect()
+    }
+
+    fn book(asks: &[f64], bids: &[f64]) -> LevelsBook {
+        LevelsBook {
+            asks: levels(asks),
+            bids: levels(bids),
+        }
+    }
+
+    #[test]
+    fn should_find_violations() {
+        let level = |price| Level::new("Binance", price, 1.0);
+        assert_eq!(violation(Some(&level(11.0)), Some(&level(10.0))), None);
+        // Locked books are left to the exchange
+        assert_eq!(violation(Some(&level(10.0)), Some(&level(10.0))), None);
+        assert_eq!(violation(None, Some(&level(10.0))), None);
+        assert_eq!(
+            violation(Some(&level(11.0)), Some(&level(11.5))),
+            Some(Violation::Crossed)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_quarantine_an_exchange_repeatedly_sending_invalid_books() {
+        let event_bus = EventBus::new(10);
+        let mut events = event_bus.subscribe();
+        let mut validator = FeedValidator::new(
+            TradedPair::new("ETH", "BTC"),
+            ValidationConfig {
+                strict: true,
+                max_invalid_books: 3,
+                window_secs: 10,
+                quarantine_secs: 60,
+            },
+            event_bus,
+            ManualClock::starting_at(UNIX_EPOCH),
+        );
+        let valid = book(&[11.0], &[10.0]);
+        let crossed = book(&[11.0], &[12.0]);
+
+        // Strikes outside the window are forgotten
+        assert!(!validator.admit(&crossed));
+        advance(Duration::from_secs(11)).await;
+        assert!(!validator.admit(&crossed));
+        assert!(validator.admit(&valid));
+        assert!(!validator.admit(&crossed));
+        assert!(events.try_recv().is_err());
+
+        assert!(!validator.admit(&crossed));
+        assert!(!validator.admit(&valid));
+        advance(Duration::from_secs(60)).await;
+        assert!(validator.admit(&valid));
+
+        let alerts = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| match event.event {
+                Some(Event::FeedQuarantine(alert)) => {
+                    (alert.invalid_books, alert.violation, alert.resolved)
+                }
+                other => panic!("Unexpected event {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            alerts,
+            vec![(3, "crossed".to_string(), false), (0, String::new(), true)]
+        );
+    }
+
+    #[test]
+    fn should_merge_invalid_books_when_not_strict() {
+        let mut validator = FeedValidator::new(
+            TradedPair::new("ETH", "BTC"),
+            ValidationConfig {
+                max_invalid_books: 1,
+                ..Default::default()
+            },
+            EventBus::new(10),
+            ManualClock::starting_at(UNIX_EPOCH),
+        );
+
+        assert!(validator.admit(&book(&[11.0], &[12.0])));
+        assert!(validator.admit(&book(&[11.0], &[12.0])));
+    }
+}
@@ -0,0 +1,119 @@
+use std::{ffi::OsString, sync::Mutex, time::Duration};
+
+use anyhow::{Context, Error};
+use once_cell::sync::Lazy;
+use tokio::sync::oneshot;
+use tracing::error;
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+};
+
+use crate::{build_runtime, Aggregator, Config};
+
+/// The name the service is installed under, e.g. with `sc.exe create order-book-service binPath= ...`.
+const SERVICE_NAME: &str = "order-book-service";
+
+/// Time allowed to start, as reported to the service control manager.
+const START_WAIT_HINT: Duration = Duration::from_secs(30);
+/// Time allowed to stop beyond the drain deadline, for in-flight requests to finish.
+const STOP_GRACE: Duration = Duration::from_secs(10);
+
+/// The config for the service, handed from [run_windows_service] to [service_main] which the dispatcher calls without it.
+static SERVICE_CONFIG: Lazy<Mutex<Option<Config>>> = Lazy::new(Mutex::default);
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Run as a Windows service with `config`, returning once the service control manager has stopped it.
+///
+/// Stopping the service drains the server, as the admin service's `Drain` RPC would, before it shuts down. Fails when the
+/// process wasn't started by the service control manager.
+pub fn run_windows_service(config: Config) -> Result<(), Error> {
+    *SERVICE_CONFIG.lock().expect("Should lock") = Some(config);
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main).context(
+        "Unable to run as a Windows service, it should be started by the service control manager",
+    )
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(err) = run_service() {
+        error!("{err:#}");
+    }
+}
+
+fn run_service() -> Result<(), Error> {
+    let config = SERVICE_CONFIG
+        .lock()
+        .expect("Should lock")
+        .take()
+        .context("The service was started without a config")?;
+    let stop_wait_hint = config.drain.deadline() + STOP_GRACE;
+
+    let (stop_sender, stop_receiver) = oneshot::channel();
+    let mut stop_sender = Some(stop_sender);
+    let status_handle =
+        service_control_handler::register(SERVICE_NAME, move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                if let Some(stop_sender) = stop_sender.take() {
+                    let _ = stop_sender.send(());
+                }
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        })
+        .context("Unable to register the service control handler")?;
+
+    set_status(
+        status_handle,
+        ServiceState::StartPending,
+        START_WAIT_HINT,
+        0,
+    );
+    let result = build_runtime(&config).and_then(|runtime| {
+        set_status(status_handle, ServiceState::Running, Duration::ZERO, 0);
+        runtime.block_on(Aggregator::new(config).serve_until(async move {
+            let _ = stop_receiver.await;
+            // Subscriptions are given until the drain deadline to move to another server
+            set_status(status_handle, ServiceState::StopPending, stop_wait_hint, 0);
+        }))
+    });
+
+    let exit_code = if result.is_ok() { 0 } else { 1 };
+    set_status(
+        status_handle,
+        ServiceState::Stopped,
+        Duration::ZERO,
+        exit_code,
+    );
+    result
+}
+
+fn set_status(
+    status_handle: ServiceStatusHandle,
+    state: ServiceState,
+    wait_hint: Duration,
+    exit_code: u32,
+) {
+    let controls_accepted = match state {
+        ServiceState::Running => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        _ => ServiceControlAccept::empty(),
+    };
+    let status = ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted,
+        exit_code: ServiceExitCode::Win32(exit_code),
+        checkpoint: 0,
+        wait_hint,
+        process_id: None,
+    };
+    if let Err(err) = status_handle.set_service_status(status) {
+        error!("Unable to report the service as {state:?}: {err}");
+    }
+}